@@ -13,6 +13,13 @@ pub fn compile(prql_query: &str, options: Option<CompileOptions>) -> Option<Stri
     )
 }
 
+/// Compiles a query and never throws: returns a JSON object with either the
+/// SQL or the errors. See [prqlc::compile_json] for its shape.
+#[wasm_bindgen]
+pub fn compile_json(prql_query: &str, options_json: &str) -> String {
+    prqlc::compile_json(prql_query, options_json)
+}
+
 #[wasm_bindgen]
 pub fn prql_to_pl(prql_query: &str) -> Option<String> {
     return_or_throw(
@@ -72,6 +72,28 @@ describe("prqlc-js", () => {
     });
   });
 
+  describe("compile_json", () => {
+    it("should return sql", () => {
+      const res = JSON.parse(
+        prqlc.compile_json(
+          "from a | take 10",
+          '{"target": "sql.mssql", "signature_comment": false}',
+        ),
+      );
+      assert(res.sql.includes("FETCH FIRST 10 ROWS ONLY"));
+      assert.equal(res.errors, undefined);
+    });
+
+    it("should return errors instead of throwing", () => {
+      const res = JSON.parse(
+        prqlc.compile_json("from x | select a | select b", "{}"),
+      );
+      assert.equal(res.sql, undefined);
+      assert.deepEqual(Object.keys(res.errors[0]), ["message", "span", "hint"]);
+      assert.deepEqual(res.errors[0].span, [27, 28]);
+    });
+  });
+
   describe("prql_to_pl", () => {
     it("should return valid json from valid prql", () => {
       JSON.parse(prqlc.prql_to_pl(employee_prql));
@@ -204,6 +204,54 @@ impl WithErrorInfo for Error {
     }
 }
 
+impl WithErrorInfo for Errors {
+    fn push_hint<S: Into<String>>(self, hint: S) -> Self {
+        let hint = hint.into();
+        Errors(
+            self.0
+                .into_iter()
+                .map(|e| e.push_hint(hint.clone()))
+                .collect(),
+        )
+    }
+
+    fn with_hints<S: Into<String>, I: IntoIterator<Item = S>>(self, hints: I) -> Self {
+        let hints: Vec<String> = hints.into_iter().map(|x| x.into()).collect();
+        Errors(
+            self.0
+                .into_iter()
+                .map(|e| e.with_hints(hints.clone()))
+                .collect(),
+        )
+    }
+
+    fn with_span(self, span: Option<Span>) -> Self {
+        Errors(self.0.into_iter().map(|e| e.with_span(span)).collect())
+    }
+
+    fn with_span_fallback(self, span: Option<Span>) -> Self {
+        Errors(
+            self.0
+                .into_iter()
+                .map(|e| e.with_span_fallback(span))
+                .collect(),
+        )
+    }
+
+    fn with_code(self, code: &'static str) -> Self {
+        Errors(self.0.into_iter().map(|e| e.with_code(code)).collect())
+    }
+
+    fn with_source(self, source: ErrorSource) -> Self {
+        Errors(
+            self.0
+                .into_iter()
+                .map(|e| e.with_source(source.clone()))
+                .collect(),
+        )
+    }
+}
+
 impl<T, E: WithErrorInfo> WithErrorInfo for Result<T, E> {
     fn push_hint<S: Into<String>>(self, hint: S) -> Self {
         self.map_err(|e| e.push_hint(hint))
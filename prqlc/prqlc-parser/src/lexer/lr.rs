@@ -38,19 +38,21 @@ pub enum TokenKind {
     /// single-char control tokens
     Control(char),
 
-    ArrowThin,   // ->
-    ArrowFat,    // =>
-    Eq,          // ==
-    Ne,          // !=
-    Gte,         // >=
-    Lte,         // <=
-    RegexSearch, // ~=
-    And,         // &&
-    Or,          // ||
-    Coalesce,    // ??
-    DivInt,      // //
-    Pow,         // **
-    Annotate,    // @
+    ArrowThin,      // ->
+    ArrowFat,       // =>
+    Eq,             // ==
+    Ne,             // !=
+    Gte,            // >=
+    Lte,            // <=
+    RegexSearch,    // ~=
+    RegexNotSearch, // !~=
+    And,            // &&
+    Or,             // ||
+    Coalesce,       // ??
+    DivInt,         // //
+    Pow,            // **
+    Annotate,       // @
+    Spread,         // ...
 
     // Aesthetics only
     Comment(String),
@@ -226,12 +228,14 @@ impl std::fmt::Display for TokenKind {
             TokenKind::Gte => f.write_str(">="),
             TokenKind::Lte => f.write_str("<="),
             TokenKind::RegexSearch => f.write_str("~="),
+            TokenKind::RegexNotSearch => f.write_str("!~="),
             TokenKind::And => f.write_str("&&"),
             TokenKind::Or => f.write_str("||"),
             TokenKind::Coalesce => f.write_str("??"),
             TokenKind::DivInt => f.write_str("//"),
             TokenKind::Pow => f.write_str("**"),
             TokenKind::Annotate => f.write_str("@{"),
+            TokenKind::Spread => f.write_str("..."),
 
             TokenKind::Param(id) => write!(f, "${id}"),
 
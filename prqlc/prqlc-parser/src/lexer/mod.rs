@@ -38,6 +38,103 @@ pub fn lex_source(source: &str) -> Result<lr::Tokens, Vec<Error>> {
         })
 }
 
+/// Lex PRQL into LR lazily, a window of the source at a time, so the tokens of
+/// a large source don't need to be held in memory at once. Stops after the
+/// first error.
+pub(crate) fn lex_source_lazily(
+    source: &str,
+    source_id: u16,
+) -> impl Iterator<Item = Result<Token, Error>> + '_ {
+    // chars of the window, and chars at its end which are not lexed, since
+    // lexing the tokens that precede them could look ahead into them
+    const WINDOW: usize = 4096;
+    const LOOKAHEAD: usize = 16;
+
+    // without recovery, so lexing stops before a token that is cut off by
+    // the end of the window
+    let tokens = lex_token_of(token_kind()).repeated();
+    let mut lexed = std::collections::VecDeque::from([Token {
+        kind: TokenKind::Start,
+        span: 0..0,
+    }]);
+
+    // spans of tokens are in chars, but the rest of the source is sliced in bytes
+    let mut byte_pos = 0;
+    let mut char_pos = 0;
+    let mut done = false;
+
+    let shift = move |span: std::ops::Range<usize>, char_pos: usize| {
+        (span.start + char_pos)..(span.end + char_pos)
+    };
+
+    std::iter::from_fn(move || loop {
+        if let Some(token) = lexed.pop_front() {
+            return Some(Ok(token));
+        }
+        if done {
+            return None;
+        }
+
+        let rest = &source[byte_pos..];
+        let mut window = WINDOW;
+        loop {
+            let Some((window_end, _)) = rest.char_indices().nth(window) else {
+                // the window contains the rest of the source
+                done = true;
+                match lexer().parse(rest) {
+                    Ok(tokens) => lexed.extend(tokens.into_iter().map(|t| Token {
+                        span: shift(t.span, char_pos),
+                        ..t
+                    })),
+                    Err(errors) => {
+                        let mut error =
+                            convert_lexer_error(rest, errors.into_iter().next()?, source_id);
+                        if let Some(span) = &mut error.span {
+                            span.start += char_pos;
+                            span.end += char_pos;
+                        }
+                        return Some(Err(error));
+                    }
+                }
+                break;
+            };
+
+            let chars = rest[..window_end].chars().enumerate();
+            let stream =
+                chumsky::Stream::from_iter(window..window, chars.map(|(i, c)| (c, i..i + 1)));
+            let (tokens_in_window, errors) = tokens.parse_recovery(stream);
+
+            // Tokens close to the end of the window, or to an error, could be
+            // lexed differently from the whole source. So could new lines and
+            // comments that precede a line wrap past the end of the window.
+            let limit = errors.iter().map(|e| e.span().start);
+            let limit = limit.chain([window - LOOKAHEAD]).min().unwrap();
+            let mut kept = tokens_in_window.unwrap_or_default();
+            kept.retain(|t| t.span.end <= limit);
+            while let Some(Token {
+                kind: TokenKind::NewLine | TokenKind::Comment(_),
+                ..
+            }) = kept.last()
+            {
+                kept.pop();
+            }
+
+            let Some(last) = kept.last() else {
+                window *= 2;
+                continue;
+            };
+            let len = last.span.end;
+            byte_pos += rest.chars().take(len).map(char::len_utf8).sum::<usize>();
+            lexed.extend(kept.into_iter().map(|t| Token {
+                span: shift(t.span, char_pos),
+                ..t
+            }));
+            char_pos += len;
+            break;
+        }
+    })
+}
+
 /// Insert a start token so later stages can treat the start of a file like a newline
 fn insert_start(tokens: Vec<Token>) -> Vec<Token> {
     std::iter::once(Token {
@@ -77,6 +174,41 @@ pub(crate) fn lexer() -> impl Parser<char, Vec<Token>, Error = Cheap<char>> {
 
 /// Lex chars to a single token
 fn lex_token() -> impl Parser<char, Token, Error = Cheap<char>> {
+    lex_token_of(token_kind().recover_with(skip_then_retry_until([]).skip_start()))
+}
+
+/// Lex chars to a single token, which is either a range, a spread, or lexed
+/// by `token`
+fn lex_token_of(
+    token: impl Parser<char, TokenKind, Error = Cheap<char>>,
+) -> impl Parser<char, Token, Error = Cheap<char>> {
+    let range = (whitespace().or_not())
+        .then_ignore(just(".."))
+        .then(whitespace().or_not())
+        .map(|(left, right)| TokenKind::Range {
+            // If there was no whitespace before (after), then we mark the range
+            // as bound on the left (right).
+            bind_left: left.is_none(),
+            bind_right: right.is_none(),
+        })
+        .map_with_span(|kind, span| Token { kind, span });
+
+    // must be tried before the range, which would otherwise consume the `..`
+    let spread = ignored().ignore_then(
+        just("...")
+            .to(TokenKind::Spread)
+            .map_with_span(|kind, span| Token { kind, span }),
+    );
+
+    choice((
+        spread,
+        range,
+        ignored().ignore_then(token.map_with_span(|kind, span| Token { kind, span })),
+    ))
+}
+
+/// Lex chars to the kind of a single token, other than a range or a spread
+fn token_kind() -> impl Parser<char, TokenKind, Error = Cheap<char>> {
     let control_multi = choice((
         just("->").to(TokenKind::ArrowThin),
         just("=>").to(TokenKind::ArrowFat),
@@ -85,6 +217,7 @@ fn lex_token() -> impl Parser<char, Token, Error = Cheap<char>> {
         just(">=").to(TokenKind::Gte),
         just("<=").to(TokenKind::Lte),
         just("~=").to(TokenKind::RegexSearch),
+        just("!~=").to(TokenKind::RegexNotSearch),
         just("&&").then_ignore(end_expr()).to(TokenKind::And),
         just("||").then_ignore(end_expr()).to(TokenKind::Or),
         just("??").to(TokenKind::Coalesce),
@@ -126,7 +259,7 @@ fn lex_token() -> impl Parser<char, Token, Error = Cheap<char>> {
         .then(quoted_string(true))
         .map(|(c, s)| TokenKind::Interpolation(c, s));
 
-    let token = choice((
+    choice((
         line_wrap(),
         newline().to(TokenKind::NewLine),
         control_multi,
@@ -138,23 +271,6 @@ fn lex_token() -> impl Parser<char, Token, Error = Cheap<char>> {
         ident,
         comment(),
     ))
-    .recover_with(skip_then_retry_until([]).skip_start());
-
-    let range = (whitespace().or_not())
-        .then_ignore(just(".."))
-        .then(whitespace().or_not())
-        .map(|(left, right)| TokenKind::Range {
-            // If there was no whitespace before (after), then we mark the range
-            // as bound on the left (right).
-            bind_left: left.is_none(),
-            bind_right: right.is_none(),
-        })
-        .map_with_span(|kind, span| Token { kind, span });
-
-    choice((
-        range,
-        ignored().ignore_then(token.map_with_span(|kind, span| Token { kind, span })),
-    ))
 }
 
 fn ignored() -> impl Parser<char, (), Error = Cheap<char>> {
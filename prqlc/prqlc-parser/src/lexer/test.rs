@@ -187,6 +187,19 @@ fn range() {
     "#);
 }
 
+#[test]
+fn spread() {
+    assert_debug_snapshot!(Tokens(lexer().parse("f ...x").unwrap()), @r#"
+    Tokens(
+        [
+            0..1: Ident("f"),
+            2..5: Spread,
+            5..6: Ident("x"),
+        ],
+    )
+    "#);
+}
+
 #[test]
 fn test_lex_source() {
     use insta::assert_debug_snapshot;
@@ -223,3 +236,38 @@ fn test_lex_source() {
     )
     "#);
 }
+
+#[test]
+fn test_lex_source_lazily() {
+    use itertools::Itertools;
+
+    use crate::lexer::lex_source_lazily;
+
+    // Lexed a window at a time, tokens that cross the end of a window, such as
+    // line wraps that follow comments, must be the same as when lexed at once.
+    let snippet = r#"
+from tracks # ünïcödé
+derive {r = 1..5, t = """
+  multi-line
+  """}
+# comment
+  # another comment
+  \ select {a, b}
+"#;
+    let long_string = format!("let s = \"{}\"\n", "ö".repeat(4500));
+    let comments = format!("# {}\n", "comment ".repeat(20)).repeat(50);
+    let source = [
+        snippet.repeat(40),
+        long_string,
+        comments,
+        snippet.repeat(40),
+    ]
+    .concat();
+
+    let lazily: Vec<_> = lex_source_lazily(&source, 0).try_collect().unwrap();
+    assert_eq!(lazily, lex_source(&source).unwrap().0);
+
+    let source = format!("{source}5 + ^");
+    let error = lex_source_lazily(&source, 0).last().unwrap().unwrap_err();
+    assert_eq!(error.span, lex_source(&source).unwrap_err()[0].span);
+}
@@ -25,7 +25,10 @@ pub(crate) fn expr_call() -> impl Parser<TokenKind, Expr, Error = PError> + Clon
 
 pub(crate) fn expr() -> impl Parser<TokenKind, Expr, Error = PError> + Clone {
     recursive(|expr| {
-        let literal = select! { TokenKind::Literal(lit) => ExprKind::Literal(lit) };
+        let literal = select! { TokenKind::Literal(lit) => lit }.try_map(|lit, span| {
+            validate_datetime(&lit).map_err(|msg| PError::custom(span, msg))?;
+            Ok(ExprKind::Literal(lit))
+        });
 
         let ident_kind = ident_part().map(ExprKind::Ident);
 
@@ -71,6 +74,7 @@ pub(crate) fn expr() -> impl Parser<TokenKind, Expr, Error = PError> + Clone {
 
         let term = field_lookup(term);
         let term = unary(term);
+        let term = cast(term);
         let term = range(term);
 
         // Binary operators
@@ -78,7 +82,7 @@ pub(crate) fn expr() -> impl Parser<TokenKind, Expr, Error = PError> + Clone {
         let expr = binary_op_parser_right(expr, operator_pow());
         let expr = binary_op_parser(expr, operator_mul());
         let expr = binary_op_parser(expr, operator_add());
-        let expr = binary_op_parser(expr, operator_compare());
+        let expr = comparison_parser(expr);
         let expr = binary_op_parser(expr, operator_coalesce());
         let expr = binary_op_parser(expr, operator_and());
 
@@ -150,12 +154,25 @@ fn case<'a>(
     let mapping = func_call(expr.clone())
         .map(Box::new)
         .then_ignore(just(TokenKind::ArrowFat))
-        .then(func_call(expr).map(Box::new))
+        .then(func_call(expr.clone()).map(Box::new))
         .map(|(condition, value)| SwitchCase { condition, value });
 
+    // The `else "unknown"` part, which is the same as `true => "unknown"`
+    let otherwise = select! { TokenKind::Ident(name) if name == "else" => () }
+        .map_with_span(|_, span| ExprKind::Literal(Literal::Boolean(true)).into_expr(span))
+        .then(func_call(expr.clone()))
+        .map(|(condition, value)| SwitchCase {
+            condition: Box::new(condition),
+            value: Box::new(value),
+        });
+
     keyword("case")
         .ignore_then(sequence(mapping).delimited_by(ctrl('['), ctrl(']')))
-        .map(ExprKind::Case)
+        .then(otherwise.or_not())
+        .map(|(mut cases, otherwise)| {
+            cases.extend(otherwise);
+            ExprKind::Case(cases)
+        })
 }
 
 fn unary<'a, E>(expr: E) -> impl Parser<TokenKind, Expr, Error = PError> + Clone + 'a
@@ -170,6 +187,24 @@ where
         .boxed()
 }
 
+/// A term, converted to a type with `as`, as in `price as float`.
+fn cast<'a, E>(expr: E) -> impl Parser<TokenKind, Expr, Error = PError> + Clone + 'a
+where
+    E: Parser<TokenKind, Expr, Error = PError> + Clone + 'a,
+{
+    let as_ = select! { TokenKind::Ident(name) if name == "as" => () };
+
+    expr.then(as_.ignore_then(type_expr()).or_not())
+        .map_with_span(|(expr, ty), span| match ty {
+            Some(ty) => {
+                let expr = Box::new(expr);
+                ExprKind::Cast { expr, ty }.into_expr(span)
+            }
+            None => expr,
+        })
+        .boxed()
+}
+
 fn field_lookup<'a, E>(expr: E) -> impl Parser<TokenKind, Expr, Error = PError> + Clone + 'a
 where
     E: Parser<TokenKind, Expr, Error = PError> + Clone + 'a,
@@ -257,9 +292,29 @@ where
     // TODO: do we need the `maybe_aliased` here rather than in `expr`? We had
     // tried `with_doc_comment(expr)` in #4775 (and push an aliased expr into
     // `expr`) but couldn't get it work.
-    with_doc_comment(maybe_aliased(expr))
-        .separated_by(pipe())
-        .at_least(1)
+    let step = with_doc_comment(maybe_aliased(expr.clone()));
+
+    // a binding of a name within the rest of the pipeline, which must be
+    // followed by another step, so a `let` statement after the main pipeline
+    // is not taken as one
+    let binding = keyword("let")
+        .ignore_then(ident_part())
+        .then_ignore(ctrl('='))
+        .then(expr)
+        .map_with_span(|(name, value), span| {
+            let value = Box::new(value);
+            ExprKind::Let { name, value }.into_expr(span)
+        })
+        .then_ignore(pipe());
+
+    step.clone()
+        .then(
+            pipe()
+                .ignore_then(binding.repeated().chain(step))
+                .repeated()
+                .flatten(),
+        )
+        .map(|(first, rest)| [vec![first], rest].concat())
         .map_with_span(|exprs, span| {
             // If there's only one expr, then we don't need to wrap it
             // in a pipeline — just return the lone expr. Otherwise,
@@ -283,35 +338,114 @@ where
         .labelled("pipeline")
 }
 
-fn binary_op_parser<'a, Term, Op>(
+/// Operator of a binary expression, whose result may be negated.
+/// This is how `!~=` is parsed: as `~=`, wrapped into a `!`.
+#[derive(Clone)]
+struct BinaryOperator {
+    op: BinOp,
+    negated: bool,
+}
+
+impl From<BinOp> for BinaryOperator {
+    fn from(op: BinOp) -> Self {
+        BinaryOperator { op, negated: false }
+    }
+}
+
+fn binary_op_parser<'a, Term, Op, O>(
     term: Term,
     op: Op,
 ) -> impl Parser<TokenKind, Expr, Error = PError> + 'a + Clone
 where
     Term: Parser<TokenKind, Expr, Error = PError> + 'a + Clone,
-    Op: Parser<TokenKind, BinOp, Error = PError> + 'a + Clone,
+    Op: Parser<TokenKind, O, Error = PError> + 'a + Clone,
+    O: Into<BinaryOperator> + 'a,
 {
     let term = term.map_with_span(|e, s| (e, s)).boxed();
 
     term.clone()
         .then(op.then(term).repeated())
-        .foldl(|left, (op, right)| {
-            let span = Span {
-                start: left.1.start,
-                end: right.1.end,
-                source_id: left.1.source_id,
-            };
-            let kind = ExprKind::Binary(BinaryExpr {
-                left: Box::new(left.0),
-                op,
-                right: Box::new(right.0),
-            });
-            (ExprKind::into_expr(kind, span), span)
-        })
+        .foldl(|left, (op, right)| binary_expr(left, op.into(), right))
         .map(|(e, _)| e)
         .boxed()
 }
 
+/// Comparisons, which may be chained, as in `0 < score < 100`. A chain of
+/// comparisons is their conjunction, `0 < score && score < 100`, so the
+/// operands in the middle are repeated.
+///
+/// Chains of `in` and `~=` are not desugared, and are compared in turn as
+/// other binary operators.
+fn comparison_parser<'a, Term>(
+    term: Term,
+) -> impl Parser<TokenKind, Expr, Error = PError> + 'a + Clone
+where
+    Term: Parser<TokenKind, Expr, Error = PError> + 'a + Clone,
+{
+    let term = term.map_with_span(|e, s| (e, s)).boxed();
+
+    term.clone()
+        .then(operator_compare().then(term).repeated())
+        .try_map(|(first, rest), _| {
+            let is_chain = rest.len() > 1
+                && (rest.iter()).all(|(op, _)| {
+                    use BinOp::*;
+                    !op.negated && matches!(op.op, Eq | Ne | Lt | Gt | Lte | Gte)
+                });
+            if !is_chain {
+                let expr = (rest.into_iter())
+                    .fold(first, |left, (op, right)| binary_expr(left, op, right));
+                return Ok(expr.0);
+            }
+
+            let last = rest.len() - 1;
+            let mut left = first;
+            let mut comparisons = Vec::with_capacity(rest.len());
+            for (i, (op, right)) in rest.into_iter().enumerate() {
+                // an s-string may evaluate to another value each time
+                if i < last && matches!(right.0.kind, ExprKind::SString(_)) {
+                    return Err(PError::custom(
+                        right.1,
+                        "an s-string can't be in the middle of a chain of comparisons, \
+                         since it would be evaluated twice; `derive` it as a column first",
+                    ));
+                }
+                comparisons.push(binary_expr(left, op, right.clone()));
+                left = right;
+            }
+            let chain = (comparisons.into_iter())
+                .reduce(|left, right| binary_expr(left, BinOp::And.into(), right));
+            Ok(chain.unwrap().0)
+        })
+        .boxed()
+}
+
+/// A binary expression of two operands and their spans, which spans both.
+fn binary_expr(
+    (left, left_span): (Expr, Span),
+    op: BinaryOperator,
+    (right, right_span): (Expr, Span),
+) -> (Expr, Span) {
+    let span = Span {
+        start: left_span.start,
+        end: right_span.end,
+        source_id: left_span.source_id,
+    };
+    let BinaryOperator { op, negated } = op;
+    let mut kind = ExprKind::Binary(BinaryExpr {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    });
+    if negated {
+        kind = ExprKind::Unary(UnaryExpr {
+            op: UnOp::Not,
+            expr: Box::new(ExprKind::into_expr(kind, span)),
+        });
+    }
+    (ExprKind::into_expr(kind, span), span)
+}
+
 pub(crate) fn binary_op_parser_right<'a, Term, Op>(
     term: Term,
     op: Op,
@@ -396,15 +530,27 @@ where
         // This is added for `maybe_aliased`; possibly we should integrate
         // the funcs
         .or_not()
-        .then(expr)
+        .then(expr.clone())
         .map(|(alias, mut expr)| {
             expr.alias = alias.or(expr.alias);
             expr
         });
+    // `{a, b} = $expr` binds the columns of a relation to names
+    let destructure = ident_part()
+        .separated_by(ctrl(','))
+        .allow_trailing()
+        .at_least(1)
+        .delimited_by(ctrl('{'), ctrl('}'))
+        .then_ignore(ctrl('='))
+        .then(expr)
+        .map_with_span(|(names, expr), span| {
+            let expr = Box::new(expr);
+            ExprKind::Destructure { names, expr }.into_expr(span)
+        });
     // Because `expr` accounts for parentheses, and aliased is `x=$expr`, we
     // need to allow another layer of parentheses here.
-    aliased
-        .clone()
+    destructure
+        .or(aliased.clone())
         .or(aliased.delimited_by(ctrl('('), ctrl(')')))
 }
 
@@ -436,8 +582,19 @@ where
     // (id==album_id)`.
     let positional_arg = maybe_aliased(expr.clone()).map(|e| (None, e));
 
+    let spread_arg = just(TokenKind::Spread)
+        .ignore_then(expr.clone().map(Box::new))
+        .map(|expr| {
+            ExprKind::Unary(UnaryExpr {
+                op: UnOp::Spread,
+                expr,
+            })
+        })
+        .map_with_span(ExprKind::into_expr)
+        .map(|e| (None, e));
+
     func_name
-        .then(named_arg.or(positional_arg).repeated())
+        .then(choice((named_arg, spread_arg, positional_arg)).repeated())
         .validate(|(name, args), span, emit| {
             if args.is_empty() {
                 return name.kind;
@@ -556,7 +713,12 @@ fn operator_mul() -> impl Parser<TokenKind, BinOp, Error = PError> + Clone {
 fn operator_add() -> impl Parser<TokenKind, BinOp, Error = PError> + Clone {
     (ctrl('+').to(BinOp::Add)).or(ctrl('-').to(BinOp::Sub))
 }
-fn operator_compare() -> impl Parser<TokenKind, BinOp, Error = PError> + Clone {
+fn operator_compare() -> impl Parser<TokenKind, BinaryOperator, Error = PError> + Clone {
+    let regex_not_search = BinaryOperator {
+        op: BinOp::RegexSearch,
+        negated: true,
+    };
+
     choice((
         just(TokenKind::Eq).to(BinOp::Eq),
         just(TokenKind::Ne).to(BinOp::Ne),
@@ -565,7 +727,10 @@ fn operator_compare() -> impl Parser<TokenKind, BinOp, Error = PError> + Clone {
         just(TokenKind::RegexSearch).to(BinOp::RegexSearch),
         ctrl('<').to(BinOp::Lt),
         ctrl('>').to(BinOp::Gt),
+        select! { TokenKind::Ident(name) if name == "in" => BinOp::In },
     ))
+    .map(BinaryOperator::from)
+    .or(just(TokenKind::RegexNotSearch).to(regex_not_search))
 }
 fn operator_and() -> impl Parser<TokenKind, BinOp, Error = PError> + Clone {
     just(TokenKind::And).to(BinOp::And)
@@ -577,6 +742,59 @@ fn operator_coalesce() -> impl Parser<TokenKind, BinOp, Error = PError> + Clone
     just(TokenKind::Coalesce).to(BinOp::Coalesce)
 }
 
+/// Checks that the components of date and time literals are in range, so
+/// `@2024-13-01` is rejected, rather than being passed to the database.
+fn validate_datetime(literal: &Literal) -> Result<(), String> {
+    let (kind, date, time) = match literal {
+        Literal::Date(date) => ("date", Some(date.as_str()), None),
+        Literal::Time(time) => ("time", None, Some(time.as_str())),
+        Literal::Timestamp(timestamp) => match timestamp.split_once('T') {
+            Some((date, time)) => ("timestamp", Some(date), Some(time)),
+            None => ("timestamp", Some(timestamp.as_str()), None),
+        },
+        _ => return Ok(()),
+    };
+    let invalid = |reason: &str| Err(format!("invalid {kind} `{literal}`: {reason}"));
+
+    // the lexer only produces literals of digits in the expected positions,
+    // so the components parse
+    let number = |s: &str| s.parse::<u32>().unwrap_or_default();
+
+    if let Some(date) = date {
+        let parts: Vec<_> = date.split('-').collect();
+        let [year, month, day] = [number(parts[0]), number(parts[1]), number(parts[2])];
+
+        if !(1..=12).contains(&month) {
+            return invalid("month must be between 1 and 12");
+        }
+        let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            2 if is_leap_year => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        if !(1..=days).contains(&day) {
+            return invalid(&format!("day must be between 1 and {days}"));
+        }
+    }
+
+    if let Some(time) = time {
+        // the offset of the timezone, if any, is not validated
+        let time = time.split(['Z', '+', '-']).next().unwrap_or(time);
+        let time = time.split('.').next().unwrap_or(time);
+
+        let parts: Vec<_> = time.split(':').map(number).collect();
+        let limits = [(23, "hour"), (59, "minute"), (59, "second")];
+        for (value, (max, name)) in parts.into_iter().zip(limits) {
+            if value > max {
+                return invalid(&format!("{name} must be between 0 and {max}"));
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
 
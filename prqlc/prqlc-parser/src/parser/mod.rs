@@ -12,10 +12,13 @@ mod interpolation;
 pub(crate) mod perror;
 pub mod pr;
 pub(crate) mod stmt;
+mod streaming;
 #[cfg(test)]
 mod test;
 mod types;
 
+pub use self::streaming::parse_stmts_streaming;
+
 // Note that `parse_source` is in `prqlc` crate, not in `prqlc-parser` crate,
 // because it logs using the logging framework in `prqlc`.
 
@@ -132,8 +132,11 @@ impl<T: Hash + Eq + Display + Debug> chumsky::Error<T> for ChumError<T> {
         // };
 
         self.reason = self.reason.zip(other.reason).map(|(mut r1, r2)| {
-            r1.push_str(" | ");
-            r1.push_str(r2.as_str());
+            // the same parser may fail more than once at the same position
+            if r1 != r2 {
+                r1.push_str(" | ");
+                r1.push_str(r2.as_str());
+            }
             r1
         });
 
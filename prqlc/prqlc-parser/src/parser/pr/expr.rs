@@ -80,6 +80,27 @@ pub enum ExprKind {
     FString(Vec<InterpolateItem>),
     Case(Vec<SwitchCase>),
 
+    /// Binds each column of a relation to a name, as in
+    /// `{min_p, max_p} = (from orders | aggregate {min price, max price})`.
+    Destructure {
+        names: Vec<String>,
+        expr: Box<Expr>,
+    },
+
+    /// Binding of a name to an expression within a pipeline, as in
+    /// `let discounted = price * 0.9`. The transforms that follow it can
+    /// reference the name, which stands for the expression.
+    Let {
+        name: String,
+        value: Box<Expr>,
+    },
+
+    /// Conversion of a value to a type, as in `price as float`.
+    Cast {
+        expr: Box<Expr>,
+        ty: Ty,
+    },
+
     /// placeholder for values provided after query is compiled
     Param(String),
 
@@ -23,6 +23,9 @@ pub enum UnOp {
     Not,
     #[strum(to_string = "==")]
     EqSelf,
+    /// Expands a tuple or an array into positional args of a function call.
+    #[strum(to_string = "...")]
+    Spread,
 }
 
 #[derive(
@@ -67,6 +70,8 @@ pub enum BinOp {
     Lte,
     #[strum(to_string = "~=")]
     RegexSearch,
+    #[strum(to_string = "in")]
+    In,
     #[strum(to_string = "&&")]
     And,
     #[strum(to_string = "||")]
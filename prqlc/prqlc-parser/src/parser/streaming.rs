@@ -0,0 +1,284 @@
+use chumsky::prelude::*;
+
+use super::perror::PError;
+use super::pr::Stmt;
+use super::{prepare_stream, stmt};
+use crate::error::Error;
+use crate::lexer::lex_source_lazily;
+use crate::lexer::lr::{Token, TokenKind};
+
+/// Parse PRQL into statements, one top-level statement at a time.
+///
+/// Unlike [super::parse_lr_to_pr], neither the tokens nor the statements of
+/// the whole source are held in memory at once, so a consumer can process
+/// each statement before the next one is parsed. The source is split into
+/// statements at new lines that start a `let`, `module`, `type` or `import`,
+/// or an expression following one of them, without parsing the statements
+/// that follow. So a `let` within a pipeline must follow its `|` on the same
+/// line, rather than start a line of its own.
+///
+/// After the first error, the iterator yields the errors and stops.
+pub fn parse_stmts_streaming(source: &str) -> impl Iterator<Item = Result<Stmt, Vec<Error>>> + '_ {
+    StmtStream::new(source)
+}
+
+struct StmtStream<'a> {
+    tokens: Box<dyn Iterator<Item = Result<Token, Error>> + 'a>,
+    parser: BoxedParser<'a, TokenKind, Vec<Stmt>, PError>,
+
+    /// Tokens of the statement that is being split off.
+    buffer: Vec<Token>,
+    /// Tokens that were read past the end of the statement in the buffer,
+    /// which start the next statement.
+    carry: Vec<Token>,
+    /// Statements parsed from the last split, which can contain more than one
+    /// (i.e. the query header and the statement after it).
+    parsed: std::vec::IntoIter<Stmt>,
+    done: bool,
+}
+
+/// State of the statement that is being split off.
+#[derive(Default)]
+struct Split {
+    depth: usize,
+    line_start: bool,
+    /// Whether a token of the statement itself has been read, rather than
+    /// only its doc comments and annotations.
+    has_body: bool,
+    /// Whether the statement ends with its line, rather than continuing on the
+    /// next line like a pipeline.
+    ends_with_line: bool,
+    is_query_def: bool,
+}
+
+impl<'a> StmtStream<'a> {
+    fn new(source: &'a str) -> Self {
+        StmtStream {
+            tokens: Box::new(lex_source_lazily(source, 0)),
+            parser: stmt::source().boxed(),
+            buffer: Vec::new(),
+            carry: Vec::new(),
+            parsed: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+
+    /// Reads tokens into the buffer, up to the start of the next statement.
+    fn split_next(&mut self) -> Result<(), Vec<Error>> {
+        let mut split = Split {
+            line_start: true,
+            ..Default::default()
+        };
+
+        let carried = std::mem::take(&mut self.carry).into_iter().map(Ok);
+        for token in carried.chain(&mut self.tokens) {
+            let token = token.map_err(|e| vec![e])?;
+
+            match &token.kind {
+                TokenKind::Start | TokenKind::NewLine if split.depth == 0 => {
+                    split.line_start = true;
+                }
+                TokenKind::Comment(_) | TokenKind::LineWrap(_) => {}
+                kind => {
+                    if split.line_start && split.depth == 0 && split.starts_stmt(kind) {
+                        self.carry_new_lines(split.is_query_def);
+                        self.carry.push(token);
+                        return Ok(());
+                    }
+                    split.line_start = false;
+
+                    match kind {
+                        TokenKind::Control('(' | '[' | '{') => split.depth += 1,
+                        TokenKind::Control(')' | ']' | '}') => {
+                            split.depth = split.depth.saturating_sub(1)
+                        }
+                        TokenKind::Keyword(kw) if kw == "into" && split.depth == 0 => {
+                            split.ends_with_line = true;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            self.buffer.push(token);
+        }
+        Ok(())
+    }
+
+    /// Moves the new lines at the end of the buffer to the next statement,
+    /// which includes them in its span. A query header keeps the new line
+    /// that ends it.
+    fn carry_new_lines(&mut self, is_query_def: bool) {
+        let mut end = self
+            .buffer
+            .iter()
+            .rposition(|t| !matches!(t.kind, TokenKind::NewLine | TokenKind::Comment(_)))
+            .map_or(0, |i| i + 1);
+        if is_query_def {
+            end += self.buffer[end..]
+                .iter()
+                .position(|t| t.kind == TokenKind::NewLine)
+                .map_or(0, |i| i + 1);
+        }
+
+        self.carry = self.buffer.split_off(end);
+    }
+}
+
+impl Split {
+    /// Whether a token at the start of a line starts the next statement, and
+    /// if not, updates the state of the current one.
+    fn starts_stmt(&mut self, kind: &TokenKind) -> bool {
+        let keyword = match kind {
+            TokenKind::Keyword(kw) => kw.as_str(),
+            _ => "",
+        };
+        let is_stmt_keyword = ["let", "module", "type", "import"].contains(&keyword);
+        let is_prefix = matches!(kind, TokenKind::DocComment(_) | TokenKind::Annotate);
+
+        // a query header after another statement is left to fail parsing
+        let is_query_def = keyword == "prql";
+
+        if self.has_body && !is_query_def && (is_stmt_keyword || is_prefix || self.ends_with_line) {
+            return true;
+        }
+
+        if is_stmt_keyword || is_query_def {
+            self.has_body = true;
+            self.ends_with_line = true;
+            self.is_query_def = is_query_def;
+        } else if !is_prefix {
+            // main pipeline
+            self.has_body = true;
+        }
+        false
+    }
+}
+
+impl Iterator for StmtStream<'_> {
+    type Item = Result<Stmt, Vec<Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(stmt) = self.parsed.next() {
+                return Some(Ok(stmt));
+            }
+            if self.done {
+                return None;
+            }
+
+            if let Err(errors) = self.split_next() {
+                self.done = true;
+                return Some(Err(errors));
+            }
+            // the buffer holds the last statement
+            self.done = self.carry.is_empty();
+
+            // drain rather than take, so the buffer keeps its capacity
+            let tokens = self.buffer.drain(..).collect();
+            let (stmts, errors) = self.parser.parse_recovery(prepare_stream(tokens, 0));
+            if !errors.is_empty() {
+                self.done = true;
+                return Some(Err(errors.into_iter().map(|e| e.into()).collect()));
+            }
+            self.parsed = stmts.unwrap_or_default().into_iter();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_debug_snapshot;
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::test::parse_source;
+
+    #[test]
+    fn test_same_as_batch() {
+        let source = r#"
+        #! A query header with a doc comment
+        prql target:sql.postgres # a comment
+
+        let a = 5
+        let b <int> = (
+            a + 1
+        )
+        let d = a
+          \ + 2
+
+        #! doc comment
+        @{binding_strength=1}
+        let add = x y -> x + y
+
+        module m {
+          let c = 3
+        }
+        type t = int
+
+        from artists
+        into x
+
+        # a comment before the main pipeline
+        from x
+        derive {c = case [
+          a > 1 => "big",
+          true => "small",
+        ]}
+        select {a, c}
+        take 10
+        "#;
+
+        let streamed: Vec<_> = parse_stmts_streaming(source).try_collect().unwrap();
+        assert_eq!(streamed, parse_source(source).unwrap());
+        assert_eq!(streamed.len(), 9);
+    }
+
+    #[test]
+    fn test_error_stops() {
+        let mut stmts = parse_stmts_streaming(
+            r#"
+        let a = 1
+        let b = )
+        let c = 3
+        "#,
+        );
+
+        assert!(stmts.next().unwrap().is_ok());
+        assert_debug_snapshot!(stmts.next().unwrap().unwrap_err(), @r#"
+        [
+            Error {
+                kind: Error,
+                span: Some(
+                    0:35-36,
+                ),
+                reason: Simple(
+                    "unexpected ) while parsing variable definition",
+                ),
+                hints: [],
+                code: None,
+            },
+        ]
+        "#);
+        assert!(stmts.next().is_none());
+
+        // the query header must be the first statement
+        let stmts = parse_stmts_streaming("let a = 1\nprql target:sql.generic\n");
+        assert!(stmts.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_bounded_memory() {
+        let source = (0..10_000).map(|i| format!("let x_{i} = {i}\n")).join("");
+
+        let mut stmts = StmtStream::new(&source);
+        let mut count = 0;
+        for stmt in &mut stmts {
+            stmt.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, 10_000);
+
+        // only the tokens of a single statement are ever held at once
+        assert!(stmts.buffer.capacity() < 64);
+    }
+}
@@ -1,5 +1,5 @@
 use chumsky::Parser;
-use insta::assert_yaml_snapshot;
+use insta::{assert_debug_snapshot, assert_yaml_snapshot};
 
 use super::{new_line, pr::Expr};
 use super::{perror::PError, prepare_stream};
@@ -680,6 +680,32 @@ fn test_derive() {
     "#);
 }
 
+#[test]
+fn test_spread() {
+    assert_yaml_snapshot!(
+            parse_expr(r#"coalesce x ...{y, z}"#).unwrap()
+        , @r#"
+    FuncCall:
+      name:
+        Ident: coalesce
+        span: "0:0-8"
+      args:
+        - Ident: x
+          span: "0:9-10"
+        - Unary:
+            op: Spread
+            expr:
+              Tuple:
+                - Ident: y
+                  span: "0:15-16"
+                - Ident: z
+                  span: "0:18-19"
+              span: "0:14-20"
+          span: "0:11-20"
+    span: "0:0-20"
+    "#);
+}
+
 #[test]
 fn test_select() {
     assert_yaml_snapshot!(
@@ -829,6 +855,164 @@ fn test_regex() {
     "#);
 }
 
+#[test]
+fn test_regex_not_search() {
+    assert_yaml_snapshot!(
+            parse_expr(
+                "'oba' !~= 'foobar'"
+            ).unwrap(),
+            @r#"
+    Unary:
+      op: Not
+      expr:
+        Binary:
+          left:
+            Literal:
+              String: oba
+            span: "0:0-5"
+          op: RegexSearch
+          right:
+            Literal:
+              String: foobar
+            span: "0:10-18"
+        span: "0:0-18"
+    span: "0:0-18"
+    "#);
+}
+
+#[test]
+fn test_in() {
+    assert_yaml_snapshot!(
+            parse_expr(
+                "country in ['US', 'CA']"
+            ).unwrap(),
+            @r#"
+    Binary:
+      left:
+        Ident: country
+        span: "0:0-7"
+      op: In
+      right:
+        Array:
+          - Literal:
+              String: US
+            span: "0:12-16"
+          - Literal:
+              String: CA
+            span: "0:18-22"
+        span: "0:11-23"
+    span: "0:0-23"
+    "#);
+}
+
+#[test]
+fn test_destructure() {
+    assert_yaml_snapshot!(
+            parse_expr(
+                "{{lo, hi} = (from orders | aggregate {min price, max price})}"
+            ).unwrap(),
+            @r#"
+    Tuple:
+      - Destructure:
+          names:
+            - lo
+            - hi
+          expr:
+            Pipeline:
+              exprs:
+                - FuncCall:
+                    name:
+                      Ident: from
+                      span: "0:13-17"
+                    args:
+                      - Ident: orders
+                        span: "0:18-24"
+                  span: "0:13-24"
+                - FuncCall:
+                    name:
+                      Ident: aggregate
+                      span: "0:27-36"
+                    args:
+                      - Tuple:
+                          - FuncCall:
+                              name:
+                                Ident: min
+                                span: "0:38-41"
+                              args:
+                                - Ident: price
+                                  span: "0:42-47"
+                            span: "0:38-47"
+                          - FuncCall:
+                              name:
+                                Ident: max
+                                span: "0:49-52"
+                              args:
+                                - Ident: price
+                                  span: "0:53-58"
+                            span: "0:49-58"
+                        span: "0:37-59"
+                  span: "0:27-59"
+            span: "0:12-60"
+        span: "0:1-60"
+    span: "0:0-61"
+    "#);
+}
+
+#[test]
+fn test_cast() {
+    assert_yaml_snapshot!(
+            parse_expr(
+                "-price as float + 1"
+            ).unwrap(),
+            @r#"
+    Binary:
+      left:
+        Cast:
+          expr:
+            Unary:
+              op: Neg
+              expr:
+                Ident: price
+                span: "0:1-6"
+            span: "0:0-6"
+          ty:
+            kind:
+              Primitive: Float
+            span: "0:10-15"
+            name: ~
+        span: "0:0-15"
+      op: Add
+      right:
+        Literal:
+          Integer: 1
+        span: "0:18-19"
+    span: "0:0-19"
+    "#
+    );
+
+    // `as` as a function is still a function call
+    assert_yaml_snapshot!(
+            parse_expr(
+                "(price | as REAL)"
+            ).unwrap(),
+            @r#"
+    Pipeline:
+      exprs:
+        - Ident: price
+          span: "0:1-6"
+        - FuncCall:
+            name:
+              Ident: as
+              span: "0:9-11"
+            args:
+              - Ident: REAL
+                span: "0:12-16"
+          span: "0:9-16"
+    span: "0:0-17"
+    "#
+    );
+}
+
 #[test]
 fn test_func_call() {
     // Function without argument
@@ -1153,6 +1337,39 @@ fn test_dates() {
     parse_expr("@2020-01-011").unwrap_err();
 
     parse_expr("@2020-01-01T111").unwrap_err();
+
+    // components out of range
+    assert_debug_snapshot!(parse_expr("@2024-13-01").unwrap_err(), @r#"
+    [
+        Error {
+            kind: Error,
+            span: Some(
+                0:0-11,
+            ),
+            reason: Simple(
+                "invalid date `@2024-13-01`: month must be between 1 and 12",
+            ),
+            hints: [],
+            code: None,
+        },
+    ]
+    "#);
+
+    let reason = |source| parse_expr(source).unwrap_err()[0].reason.to_string();
+    assert_eq!(
+        reason("@2023-02-29"),
+        "invalid date `@2023-02-29`: day must be between 1 and 28"
+    );
+    assert_eq!(
+        reason("@2024-01-15T24:00"),
+        "invalid timestamp `@2024-01-15T24:00`: hour must be between 0 and 23"
+    );
+    assert_eq!(
+        reason("@10:60"),
+        "invalid time `@10:60`: minute must be between 0 and 59"
+    );
+    parse_expr("@2024-02-29").unwrap();
+    parse_expr("@2000-02-29T23:59:59.999-05:00").unwrap();
 }
 
 #[test]
@@ -1220,6 +1437,37 @@ fn test_case() {
     "#);
 }
 
+#[test]
+fn test_case_else() {
+    assert_yaml_snapshot!(parse_expr(r#"case [x > 0 => "pos"] else "neg""#).unwrap(), @r#"
+    Case:
+      - condition:
+          Binary:
+            left:
+              Ident: x
+              span: "0:6-7"
+            op: Gt
+            right:
+              Literal:
+                Integer: 0
+              span: "0:10-11"
+          span: "0:6-11"
+        value:
+          Literal:
+            String: pos
+          span: "0:15-20"
+      - condition:
+          Literal:
+            Boolean: true
+          span: "0:22-26"
+        value:
+          Literal:
+            String: neg
+          span: "0:27-32"
+    span: "0:0-32"
+    "#);
+}
+
 #[test]
 fn test_params() {
     assert_yaml_snapshot!(parse_expr(r#"$2"#).unwrap(), @r#"
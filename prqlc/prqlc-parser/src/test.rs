@@ -848,6 +848,73 @@ fn test_inline_pipeline() {
     "#);
 }
 
+#[test]
+fn test_pipeline_let() {
+    assert_yaml_snapshot!(parse_source(r#"
+from sales
+let discounted = price * 0.9
+filter discounted > 10
+"#).unwrap(), @r#"
+    - VarDef:
+        kind: Main
+        name: main
+        value:
+          Pipeline:
+            exprs:
+              - FuncCall:
+                  name:
+                    Ident: from
+                    span: "0:1-5"
+                  args:
+                    - Ident: sales
+                      span: "0:6-11"
+                span: "0:1-11"
+              - Let:
+                  name: discounted
+                  value:
+                    Binary:
+                      left:
+                        Ident: price
+                        span: "0:29-34"
+                      op: Mul
+                      right:
+                        Literal:
+                          Float: 0.9
+                        span: "0:37-40"
+                    span: "0:29-40"
+                span: "0:12-40"
+              - FuncCall:
+                  name:
+                    Ident: filter
+                    span: "0:41-47"
+                  args:
+                    - Binary:
+                        left:
+                          Ident: discounted
+                          span: "0:48-58"
+                        op: Gt
+                        right:
+                          Literal:
+                            Integer: 10
+                          span: "0:61-63"
+                      span: "0:48-63"
+                span: "0:41-63"
+          span: "0:1-63"
+      span: "0:0-63"
+    "#);
+
+    // without a transform after it, a `let` is a statement of its own
+    let stmts = parse_source(
+        r#"
+from sales
+let discounted = price * 0.9
+"#,
+    )
+    .unwrap();
+    assert_eq!(stmts.len(), 2);
+    assert_eq!(stmts[1].kind.as_var_def().unwrap().name, "discounted");
+}
+
 #[test]
 fn test_sql_parameters() {
     assert_yaml_snapshot!(parse_source(r#"
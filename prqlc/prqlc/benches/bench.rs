@@ -1,6 +1,6 @@
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use glob::glob;
-use prqlc::{compile, pl_to_prql, pl_to_rq, prql_to_pl, Options};
+use prqlc::{compile, pl_to_prql, pl_to_rq, prql_to_pl, sql::Dialect, Compiler, Options};
 use std::collections::BTreeMap;
 use std::fs;
 
@@ -69,11 +69,46 @@ fn bench_pl_to_prql(c: &mut Criterion) {
     group.finish();
 }
 
+/// A query of 50 declarations, followed by a main pipeline that takes `n`
+/// rows.
+fn declarations_query(n: usize) -> String {
+    let mut query = String::new();
+    for i in 0..50 {
+        query += &format!("let f_{i} = x -> x * {i} + 1\n");
+    }
+    query += &format!("from t | derive {{a = f_0 b, c = f_49 d}} | take {n}\n");
+    query
+}
+
+fn bench_incremental(c: &mut Criterion) {
+    let mut group = c.benchmark_group("incremental");
+    let options = Options::default();
+
+    group.bench_function("compile", |b| {
+        let mut n = 0;
+        b.iter(|| {
+            n += 1;
+            compile(&declarations_query(n), &options)
+        });
+    });
+    group.bench_function("update", |b| {
+        let mut compiler = Compiler::new();
+        let mut n = 0;
+        b.iter(|| {
+            n += 1;
+            compiler.update(&declarations_query(n)).unwrap();
+            compiler.current_sql(Dialect::Generic)
+        });
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_compile,
     bench_prql_to_pl,
     bench_pl_to_rq,
-    bench_pl_to_prql
+    bench_pl_to_prql,
+    bench_incremental
 );
 criterion_main!(benches);
@@ -54,13 +54,16 @@ fn highlight_token_kind(token: &TokenKind) -> String {
         | TokenKind::Ne
         | TokenKind::Gte
         | TokenKind::Lte
-        | TokenKind::RegexSearch => output.push_str(&format!("{}", token)),
+        | TokenKind::RegexSearch
+        | TokenKind::RegexNotSearch => output.push_str(&format!("{}", token)),
         TokenKind::And | TokenKind::Or => {
             output.push_str(&format!("{}", token).purple().to_string())
         }
-        TokenKind::Coalesce | TokenKind::DivInt | TokenKind::Pow | TokenKind::Annotate => {
-            output.push_str(&format!("{}", token))
-        }
+        TokenKind::Coalesce
+        | TokenKind::DivInt
+        | TokenKind::Pow
+        | TokenKind::Annotate
+        | TokenKind::Spread => output.push_str(&format!("{}", token)),
         TokenKind::Comment(comment) => output.push_str(
             &format!("#{comment}")
                 .truecolor(95, 135, 135)
@@ -28,6 +28,7 @@ use prqlc::ir::{pl, rq};
 use prqlc::pr;
 use prqlc::semantic;
 use prqlc::semantic::reporting::FrameCollector;
+use prqlc::sql::{Dialect, DialectVersion};
 use prqlc::utils::maybe_strip_colors;
 use prqlc::{pl_to_prql, pl_to_rq_tree, prql_to_pl, prql_to_pl_tree, prql_to_tokens, rq_to_sql};
 use prqlc::{Options, SourceTree, Target};
@@ -141,14 +142,28 @@ enum Command {
         #[arg(long = "hide-signature-comment", action = clap::ArgAction::SetFalse)]
         signature_comment: bool,
 
+        /// Embed the PRQL source as a comment at the top of the SQL
+        #[arg(long)]
+        include_source: bool,
+
         /// Emit unformatted, dense SQL
         #[arg(long = "no-format", action = clap::ArgAction::SetFalse)]
         format: bool,
 
         /// Target to compile to
+        ///
+        /// Also accepts a database connection URL, such as
+        /// `postgres://localhost/db`, whose scheme selects the dialect.
         #[arg(short, long, default_value = "sql.any", env = "PRQLC_TARGET")]
         target: String,
 
+        /// Version of the database to compile to, such as `8.0`
+        ///
+        /// Features that older versions lack are emulated or reported as
+        /// errors. Defaults to the newest version.
+        #[arg(long, env = "PRQLC_TARGET_VERSION")]
+        target_version: Option<String>,
+
         /// File path into which to write the debug log to.
         #[arg(long, env = "PRQLC_DEBUG_LOG")]
         debug_log: Option<PathBuf>,
@@ -289,13 +304,13 @@ impl Command {
                 let root = sources.root;
 
                 for (path, source) in sources.sources {
-                    let ast = prql_to_pl(&source)?;
+                    let formatted = prqlc::format(&source)?;
 
                     // If we're writing to stdout (though could this be nicer?
                     // We're discarding many of the benefits of Clio here...)
                     if path.as_os_str() == "" {
                         let mut output: Output = Output::new(input.path())?;
-                        output.write_all(&pl_to_prql(&ast)?.into_bytes())?;
+                        output.write_all(formatted.as_bytes())?;
                         break;
                     }
 
@@ -307,7 +322,7 @@ impl Command {
                     })?;
                     let mut output: Output = Output::new(path_str)?;
 
-                    output.write_all(&pl_to_prql(&ast)?.into_bytes())?;
+                    output.write_all(formatted.as_bytes())?;
                 }
                 Ok(())
             }
@@ -448,8 +463,10 @@ impl Command {
             }
             Command::Compile {
                 signature_comment,
+                include_source,
                 format,
                 target,
+                target_version,
                 debug_log,
                 ..
             } => {
@@ -457,8 +474,13 @@ impl Command {
                     debug::log_start();
                 }
 
+                let target_version = (target_version.as_deref())
+                    .map(DialectVersion::from_str)
+                    .transpose()
+                    .map_err(prqlc::ErrorMessages::from)?;
                 let opts = Options::default()
-                    .with_target(Target::from_str(target).map_err(prqlc::ErrorMessages::from)?)
+                    .with_target(parse_target(target)?)
+                    .with_target_version(target_version)
                     .with_signature_comment(*signature_comment)
                     .with_format(*format);
 
@@ -473,7 +495,12 @@ impl Command {
                     write_log(path)?;
                 }
 
-                res?.as_bytes().to_vec()
+                let sql = res?;
+                if *include_source {
+                    (source_comment(sources) + &sql).into_bytes()
+                } else {
+                    sql.into_bytes()
+                }
             }
             _ => unreachable!("Other commands shouldn't reach `execute`"),
         })
@@ -593,6 +620,61 @@ fn read_files(input: &mut clio::ClioPath) -> Result<SourceTree> {
     Ok(SourceTree::new(sources, Some(root.to_path_buf())))
 }
 
+/// Database URL schemes and the dialect each of them selects. SQLAlchemy style
+/// driver suffixes, as in `postgresql+psycopg2`, are ignored.
+const URL_SCHEME_DIALECTS: &[(&str, Dialect)] = &[
+    ("bigquery", Dialect::BigQuery),
+    ("clickhouse", Dialect::ClickHouse),
+    ("duckdb", Dialect::DuckDb),
+    ("glaredb", Dialect::GlareDb),
+    ("mariadb", Dialect::MySql),
+    ("mssql", Dialect::MsSql),
+    ("mysql", Dialect::MySql),
+    ("postgres", Dialect::Postgres),
+    ("postgresql", Dialect::Postgres),
+    ("snowflake", Dialect::Snowflake),
+    ("sqlite", Dialect::SQLite),
+    ("sqlite3", Dialect::SQLite),
+    ("sqlserver", Dialect::MsSql),
+];
+
+/// Parses a `--target`, which is either a target name or a database
+/// connection URL whose scheme selects the dialect.
+fn parse_target(target: &str) -> Result<Target> {
+    let Some((scheme, _)) = target.split_once("://") else {
+        return Ok(Target::from_str(target).map_err(prqlc::ErrorMessages::from)?);
+    };
+
+    let scheme = scheme.split('+').next().unwrap_or_default();
+    let dialect = URL_SCHEME_DIALECTS
+        .iter()
+        .find(|(s, _)| s.eq_ignore_ascii_case(scheme))
+        .map(|(_, dialect)| *dialect);
+
+    match dialect {
+        Some(dialect) => Ok(Target::Sql(Some(dialect))),
+        None => bail!(
+            "Unknown database URL scheme `{scheme}`; supported schemes are: {}",
+            URL_SCHEME_DIALECTS.iter().map(|(s, _)| s).join(", ")
+        ),
+    }
+}
+
+/// Renders the PRQL sources as a SQL block comment, so that generated SQL
+/// records what it was compiled from.
+fn source_comment(sources: &SourceTree) -> String {
+    let prql = (sources.sources.iter())
+        .sorted_by_key(|(path, _)| *path)
+        .map(|(_, source)| source.trim())
+        .join("\n\n");
+
+    // `*/` would close the comment early and some databases nest `/*`, so
+    // we break both up.
+    let prql = prql.replace("*/", "* /").replace("/*", "/ *");
+
+    format!("/*\n{prql}\n*/\n")
+}
+
 fn combine_prql_and_frames(source: &str, frames: Vec<(Option<pr::Span>, pl::Lineage)>) -> String {
     let source = Source::from(source);
     let lines = source.lines().collect_vec();
@@ -687,8 +769,10 @@ sort full
             &Command::Compile {
                 io_args: IoArgs::default(),
                 signature_comment: false,
+                include_source: false,
                 format: true,
                 target: "sql.any".to_string(),
+                target_version: None,
                 debug_log: None,
             },
             &mut "asdf".into(),
@@ -712,8 +796,10 @@ sort full
             &Command::Compile {
                 io_args: IoArgs::default(),
                 signature_comment: false,
+                include_source: false,
                 format: true,
                 target: "sql.any".to_string(),
+                target_version: None,
                 debug_log: None,
             },
             &mut SourceTree::new(
@@ -744,6 +830,68 @@ sort full
         ");
     }
 
+    #[test]
+    fn compile_include_source() {
+        let result = Command::execute(
+            &Command::Compile {
+                io_args: IoArgs::default(),
+                signature_comment: false,
+                include_source: true,
+                format: true,
+                target: "sql.any".to_string(),
+                target_version: None,
+                debug_log: None,
+            },
+            &mut "from x # trailing */ breaks comments\nselect y".into(),
+            "",
+        )
+        .unwrap();
+        assert_snapshot!(String::from_utf8(result).unwrap().trim(), @r"
+        /*
+        from x # trailing * / breaks comments
+        select y
+        */
+        SELECT
+          y
+        FROM
+          x
+        ");
+    }
+
+    #[test]
+    fn compile_connection_url_target() {
+        let result = Command::execute(
+            &Command::Compile {
+                io_args: IoArgs::default(),
+                signature_comment: false,
+                include_source: false,
+                format: true,
+                target: "mssql://localhost/db".to_string(),
+                target_version: None,
+                debug_log: None,
+            },
+            &mut "from x | take 3".into(),
+            "",
+        )
+        .unwrap();
+        assert_snapshot!(String::from_utf8(result).unwrap().trim(), @r"
+        SELECT
+          TOP 3 *
+        FROM
+          x
+        ");
+
+        assert!(matches!(
+            parse_target("postgresql+psycopg2://user@localhost/db").unwrap(),
+            Target::Sql(Some(Dialect::Postgres))
+        ));
+        assert!(matches!(
+            parse_target("sql.duckdb").unwrap(),
+            Target::Sql(Some(Dialect::DuckDb))
+        ));
+        assert_snapshot!(parse_target("oracle://localhost/db").unwrap_err(), @"Unknown database URL scheme `oracle`; supported schemes are: bigquery, clickhouse, duckdb, glaredb, mariadb, mssql, mysql, postgres, postgresql, snowflake, sqlite, sqlite3, sqlserver");
+    }
+
     #[test]
     fn parse() {
         let output = Command::execute(
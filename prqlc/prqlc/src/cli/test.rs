@@ -109,15 +109,29 @@ fn compile_help() {
           --hide-signature-comment
               Exclude the signature comment containing the PRQL version
 
+          --include-source
+              Embed the PRQL source as a comment at the top of the SQL
+
           --no-format
               Emit unformatted, dense SQL
 
       -t, --target <TARGET>
               Target to compile to
               
+              Also accepts a database connection URL, such as `postgres://localhost/db`, whose scheme
+              selects the dialect.
+              
               [env: PRQLC_TARGET=]
               [default: sql.any]
 
+          --target-version <TARGET_VERSION>
+              Version of the database to compile to, such as `8.0`
+              
+              Features that older versions lack are emulated or reported as errors. Defaults to the
+              newest version.
+              
+              [env: PRQLC_TARGET_VERSION=]
+
           --debug-log <DEBUG_LOG>
               File path into which to write the debug log to
               
@@ -394,7 +408,7 @@ fn compare_directories(dir1: &Path, dir2: &Path) {
 fn debug() {
     assert_cmd_snapshot!(prqlc_command()
         .args(["debug", "lineage"])
-        .pass_stdin("from tracks | select {artist, album}"), @r###"
+        .pass_stdin("from tracks | select {artist, album}"), @r"
     success: true
     exit_code: 0
     ----- stdout -----
@@ -405,29 +419,29 @@ fn debug() {
           name:
           - tracks
           - artist
-          target_id: 120
+          target_id: 143
           target_name: null
         - !Single
           name:
           - tracks
           - album
-          target_id: 121
+          target_id: 144
           target_name: null
         inputs:
-        - id: 118
+        - id: 141
           name: tracks
           table:
           - default_db
           - tracks
     nodes:
-    - id: 118
+    - id: 141
       kind: Ident
       span: 1:0-11
       ident: !Ident
       - default_db
       - tracks
-      parent: 123
-    - id: 120
+      parent: 146
+    - id: 143
       kind: Ident
       span: 1:22-28
       ident: !Ident
@@ -435,9 +449,9 @@ fn debug() {
       - tracks
       - artist
       targets:
-      - 118
-      parent: 122
-    - id: 121
+      - 141
+      parent: 145
+    - id: 144
       kind: Ident
       span: 1:30-35
       ident: !Ident
@@ -445,21 +459,21 @@ fn debug() {
       - tracks
       - album
       targets:
-      - 118
-      parent: 122
-    - id: 122
+      - 141
+      parent: 145
+    - id: 145
       kind: Tuple
       span: 1:21-36
       children:
-      - 120
-      - 121
-      parent: 123
-    - id: 123
+      - 143
+      - 144
+      parent: 146
+    - id: 146
       kind: 'TransformCall: Select'
       span: 1:14-36
       children:
-      - 118
-      - 122
+      - 141
+      - 145
     ast:
       name: Project
       stmts:
@@ -493,7 +507,7 @@ fn debug() {
         span: 1:0-36
 
     ----- stderr -----
-    "###);
+    ");
 
     // Don't test the output of this, since on one min-versions check it had
     // different results, and didn't repro on Mac. It having different results
@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::collections::HashSet;
 use std::sync::OnceLock;
 
+use itertools::Itertools;
 use regex::Regex;
 
 use super::{WriteOpt, WriteSource};
@@ -175,7 +176,9 @@ impl WriteSource for pr::ExprKind {
                 r += opt.consume(&name)?;
                 opt.unbound_expr = true;
 
-                for (name, arg) in &func_call.named_args {
+                // named args are held in a map, so sort them for a stable output
+                let named_args = func_call.named_args.iter().sorted_by_key(|(n, _)| *n);
+                for (name, arg) in named_args {
                     r += opt.consume(" ")?;
 
                     r += opt.consume(name)?;
@@ -185,9 +188,17 @@ impl WriteSource for pr::ExprKind {
                     let arg = write_within(arg, self, opt.clone())?;
                     r += opt.consume(&arg)?;
                 }
-                for arg in &func_call.args {
+                for (index, arg) in func_call.args.iter().enumerate() {
                     r += opt.consume(" ")?;
 
+                    let is_last = index + 1 == func_call.args.len();
+                    if opt.expand_pipelines && is_last && takes_sub_pipeline(func_call) {
+                        if let Some(exprs) = as_sub_pipeline(arg) {
+                            r += &write_pipeline_block(exprs, opt.clone())?;
+                            continue;
+                        }
+                    }
+
                     let arg = write_within(arg, self, opt.clone())?;
                     r += opt.consume(&arg)?;
                 }
@@ -258,6 +269,32 @@ impl WriteSource for pr::ExprKind {
                 .write_between("[", "]", opt)?;
                 Some(r)
             }
+            Destructure { names, expr } => {
+                let mut r = String::new();
+                r += opt.consume("{")?;
+                r += opt.consume(&names.iter().map(|n| write_ident_part(n)).join(", "))?;
+                r += opt.consume("} = ")?;
+                opt.unbound_expr = false;
+                r += &expr.write(opt)?;
+                Some(r)
+            }
+            Let { name, value } => {
+                let mut r = String::new();
+                r += opt.consume("let ")?;
+                r += opt.consume(&write_ident_part(name))?;
+                r += opt.consume(" = ")?;
+                opt.unbound_expr = false;
+                r += &value.write(opt)?;
+                Some(r)
+            }
+            Cast { expr, ty } => {
+                let mut r = write_within(expr.as_ref(), self, opt.clone())?;
+                opt.consume_width(r.len() as u16)?;
+
+                r += opt.consume(" as ")?;
+                r += &ty.write(opt)?;
+                Some(r)
+            }
             Param(id) => Some(format!("${id}")),
             Internal(operator_name) => Some(format!("internal {operator_name}")),
         }
@@ -277,6 +314,48 @@ fn break_line_within_parenthesis<T: WriteSource>(expr: &T, mut opt: WriteOpt) ->
     Some(r)
 }
 
+/// Writes a pipeline within parentheses, with one transform per line.
+fn write_pipeline_block(exprs: &[pr::Expr], mut opt: WriteOpt) -> Option<String> {
+    opt.context_strength = 0;
+    opt.binary_position = super::Position::Unspecified;
+    opt.unbound_expr = false;
+
+    let mut r = "(\n".to_string();
+    opt.indent += 1;
+    for expr in exprs {
+        r += &opt.write_indent();
+        opt.reset_line()?;
+        r += &expr.write(opt.clone())?;
+        r += "\n";
+    }
+    opt.indent -= 1;
+    r += &opt.write_indent();
+    r += ")";
+    Some(r)
+}
+
+/// True for transforms whose last argument is a pipeline applied to a subset
+/// of rows, such as `group {a} (take 1)`.
+fn takes_sub_pipeline(func_call: &pr::FuncCall) -> bool {
+    matches!(
+        &func_call.name.kind,
+        pr::ExprKind::Ident(name) if ["group", "window", "loop"].contains(&name.as_str())
+    )
+}
+
+/// Returns the transforms of an argument that can be written as a pipeline:
+/// either a pipeline or a single transform.
+fn as_sub_pipeline(arg: &pr::Expr) -> Option<&[pr::Expr]> {
+    if arg.alias.is_some() {
+        return None;
+    }
+    match &arg.kind {
+        pr::ExprKind::Pipeline(pipeline) => Some(&pipeline.exprs),
+        pr::ExprKind::FuncCall(_) => Some(std::slice::from_ref(arg)),
+        _ => None,
+    }
+}
+
 fn binding_strength(expr: &pr::ExprKind) -> u8 {
     match expr {
         // For example, if it's an Ident, it's basically infinite — a simple
@@ -288,6 +367,9 @@ fn binding_strength(expr: &pr::ExprKind) -> u8 {
         // Stronger than a func call, since `exists !y` is `exists (!y)`
         pr::ExprKind::Unary(..) => 20,
 
+        // Weaker than unary, since `-x as int` is `(-x) as int`
+        pr::ExprKind::Cast { .. } => 19,
+
         pr::ExprKind::Range(_) => 19,
 
         pr::ExprKind::Binary(pr::BinaryExpr { op, .. }) => match op {
@@ -300,7 +382,8 @@ fn binding_strength(expr: &pr::ExprKind) -> u8 {
             | pr::BinOp::Lt
             | pr::BinOp::Gte
             | pr::BinOp::Lte
-            | pr::BinOp::RegexSearch => 16,
+            | pr::BinOp::RegexSearch
+            | pr::BinOp::In => 16,
             pr::BinOp::Coalesce => 15,
             pr::BinOp::And => 14,
             pr::BinOp::Or => 13,
@@ -327,7 +410,8 @@ fn associativity(expr: &pr::ExprKind) -> super::Position {
             | pr::BinOp::Lt
             | pr::BinOp::Gte
             | pr::BinOp::Lte
-            | pr::BinOp::RegexSearch => super::Position::Unspecified,
+            | pr::BinOp::RegexSearch
+            | pr::BinOp::In => super::Position::Unspecified,
             _ => super::Position::Left,
         },
 
@@ -447,7 +531,15 @@ impl WriteSource for pr::Stmt {
                 pr::VarDefKind::Let => {
                     r += opt.consume(&format!("let {} = ", var_def.name))?;
 
-                    r += &var_def.value.as_ref().unwrap().write(opt)?;
+                    let value = var_def.value.as_ref().unwrap();
+                    match &value.kind {
+                        pr::ExprKind::Pipeline(pipeline)
+                            if opt.expand_pipelines && value.alias.is_none() =>
+                        {
+                            r += &write_pipeline_block(&pipeline.exprs, opt)?;
+                        }
+                        _ => r += &value.write(opt)?,
+                    }
                     r += "\n";
                 }
                 pr::VarDefKind::Into | pr::VarDefKind::Main => {
@@ -630,6 +722,25 @@ mod test {
         assert_is_formatted(r#"let a = 5 ** 2 ** 2"#);
     }
 
+    #[test]
+    fn test_cast() {
+        assert_is_formatted(r#"derive {a = -price as float, b = (x + y) as int}"#);
+
+        assert_is_formatted(r#"let a = (x as int) ** 2"#);
+        assert_is_formatted(r#"let a = -(x as int)"#);
+    }
+
+    #[test]
+    fn test_pipeline_let() {
+        assert_is_formatted(
+            r#"
+from sales
+let discounted = price * 0.9
+filter discounted > 10
+"#,
+        );
+    }
+
     #[test]
     fn test_func() {
         assert_is_formatted(r#"let a = func x y:false -> x and y"#);
@@ -669,6 +780,24 @@ group {title, country} (aggregate {
 })"#,
         );
     }
+    #[test]
+    fn test_case() {
+        assert_is_formatted(
+            r#"
+let sign = case [
+  x > 0 => "pos",
+  x < 0 => "neg",
+  true => "zero",
+]"#,
+        );
+
+        // `else` is a branch with a `true` condition
+        assert_snapshot!(
+            format_single_stmt(r#"let sign = case [x > 0 => "pos"] else "neg""#),
+            @r#"let sign = case [x > 0 => "pos", true => "neg"]"#
+        );
+    }
+
     #[test]
     fn test_range() {
         assert_is_formatted(
@@ -735,6 +864,33 @@ into a
         );
     }
 
+    #[test]
+    fn test_format_sub_pipelines() {
+        let formatted = crate::format(
+            r#"
+from e = employees
+window rows:-2..0 expanding:true (derive {s = s"SUM({salary})", l = f"{e.first} {{x}}"})
+group {dept} (group {title} (take 1) | sort salary)
+"#,
+        )
+        .unwrap();
+        assert_snapshot!(formatted, @r#"
+        from e = employees
+        window expanding:true rows:(-2)..0 (
+          derive {
+            s = s"SUM({salary})",
+            l = f"{e.first} {{x}}",
+          }
+        )
+        group {dept} (
+          group {title} (
+            take 1
+          )
+          sort salary
+        )
+        "#);
+    }
+
     #[test]
     fn test_query_def() {
         assert_is_formatted(
@@ -76,6 +76,11 @@ pub struct WriteOpt {
     /// For example:
     /// `join foo` has an unbound expr, since `join foo ==bar` produced a binary op.
     pub unbound_expr: bool,
+
+    /// When true, pipelines of `let` relations and sub-pipelines of `group`,
+    /// `window` & `loop` are written with one transform per line, even when
+    /// they would fit on a single line.
+    pub expand_pipelines: bool,
 }
 
 #[derive(Clone, PartialEq)]
@@ -96,6 +101,7 @@ impl Default for WriteOpt {
             context_strength: 0,
             binary_position: Position::Unspecified,
             unbound_expr: false,
+            expand_pipelines: false,
         }
     }
 }
@@ -0,0 +1,346 @@
+//! Completion of names at a position of a query, for editor integrations.
+
+use serde::Serialize;
+
+use crate::ir::decl::{DeclKind, Module, RootModule, TableExpr};
+use crate::ir::pl::{Expr, ExprKind, Lineage, LineageColumn, TransformKind};
+use crate::pr::{Span, TyTupleField};
+use crate::semantic::NS_STD;
+use crate::{lr, parser, semantic, SourceTree};
+
+/// A name that can be written at a position of a query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Completion {
+    pub name: String,
+    pub kind: CompletionKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CompletionKind {
+    /// A column of the relation that a transform is applied to.
+    Column,
+
+    /// A function of the standard library. Functions of nested modules are
+    /// named by their path within `std`, as in `math.abs`.
+    Function,
+}
+
+/// Lists the names that can be written at a byte offset of a query: the
+/// columns in scope at that position of a pipeline, followed by the functions
+/// of the standard library.
+///
+/// Columns in scope are the columns of the relation that the transform at the
+/// offset is applied to, or of the relation produced by the transform before
+/// the offset, when the offset is between transforms. Columns of tables are
+/// known when they are declared or referenced elsewhere in the query.
+///
+/// Columns can only be listed when the query compiles, so a query that cannot
+/// be parsed or resolved completes to functions only. SQL within an s-string
+/// does not reference PRQL names, so an offset inside an s-string completes to
+/// nothing.
+///
+/// ```
+/// use prqlc::{completions_at, CompletionKind};
+///
+/// let prql = "from employees | derive {gross = salary + bonus} | select {gross}";
+/// let completions = completions_at(prql, prql.find("{gross}").unwrap());
+///
+/// let columns: Vec<_> = completions
+///     .iter()
+///     .filter(|c| c.kind == CompletionKind::Column)
+///     .map(|c| c.name.as_str())
+///     .collect();
+/// assert_eq!(columns, ["salary", "bonus", "gross"]);
+/// ```
+pub fn completions_at(prql: &str, offset: usize) -> Vec<Completion> {
+    // spans count chars, rather than bytes
+    let offset = prql.get(..offset).map_or(offset, |p| p.chars().count());
+
+    if is_in_s_string(prql, offset) {
+        return Vec::new();
+    }
+
+    let Some(root_mod) = resolve(prql) else {
+        // the std module alone
+        return resolve("").map_or_else(Vec::new, |root_mod| std_functions(&root_mod));
+    };
+
+    let mut completions = Vec::new();
+    if let Some(lineage) = scope_at(&root_mod, offset) {
+        for name in lineage_columns(&root_mod, lineage) {
+            let completion = Completion {
+                name,
+                kind: CompletionKind::Column,
+            };
+            if !completions.contains(&completion) {
+                completions.push(completion);
+            }
+        }
+    }
+    completions.extend(std_functions(&root_mod));
+    completions
+}
+
+fn resolve(prql: &str) -> Option<RootModule> {
+    let module_tree = parser::parse(&SourceTree::from(prql)).ok()?;
+    semantic::resolve(module_tree).ok()
+}
+
+fn is_in_s_string(prql: &str, offset: usize) -> bool {
+    let (tokens, _) = prqlc_parser::lexer::lex_source_recovery(prql, 0);
+    tokens.unwrap_or_default().iter().any(|token| {
+        matches!(token.kind, lr::TokenKind::Interpolation('s', _))
+            && token.span.start < offset
+            && offset < token.span.end
+    })
+}
+
+/// Lineage of the relation in scope at an offset, within any of the relation
+/// variables of the query, including the main pipeline.
+fn scope_at(root_mod: &RootModule, offset: usize) -> Option<&Lineage> {
+    let relations = root_mod.module.names.values().filter_map(|decl| {
+        let table_decl = decl.kind.as_table_decl()?;
+        table_decl.expr.as_relation_var()
+    });
+    for relation in relations {
+        if relation_span(relation).is_some_and(|span| contains(span, offset)) {
+            return scope_in_relation(relation, offset);
+        }
+    }
+    None
+}
+
+fn scope_in_relation(expr: &Expr, offset: usize) -> Option<&Lineage> {
+    let span = expr.span?;
+    let ExprKind::TransformCall(call) = &expr.kind else {
+        // a relation reference, as in `from employees`
+        return expr.lineage.as_ref().filter(|_| offset > span.end);
+    };
+
+    if offset < span.start {
+        return scope_in_relation(&call.input, offset);
+    }
+    if offset > span.end {
+        return expr.lineage.as_ref();
+    }
+
+    let nested = match call.kind.as_ref() {
+        TransformKind::Join { with, .. } => Some(with.as_ref()),
+        TransformKind::Append(bottom) => Some(bottom.as_ref()),
+        TransformKind::Group { pipeline, .. }
+        | TransformKind::Window { pipeline, .. }
+        | TransformKind::Loop(pipeline) => match &pipeline.kind {
+            ExprKind::Func(func) => Some(func.body.as_ref()),
+            _ => Some(pipeline.as_ref()),
+        },
+        _ => None,
+    };
+    if let Some(nested) = nested {
+        if relation_span(nested).is_some_and(|span| contains(span, offset)) {
+            if let Some(lineage) = scope_in_relation(nested, offset) {
+                return Some(lineage);
+            }
+        }
+    }
+    call.input.lineage.as_ref()
+}
+
+/// Span of a whole pipeline, from its first to its last transform.
+fn relation_span(expr: &Expr) -> Option<Span> {
+    let span = expr.span?;
+    let ExprKind::TransformCall(call) = &expr.kind else {
+        return Some(span);
+    };
+    Some(match relation_span(&call.input) {
+        Some(input) if input.source_id == span.source_id => Span {
+            start: input.start.min(span.start),
+            ..span
+        },
+        _ => span,
+    })
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+/// Names of the columns of a relation. Columns of inputs whose columns are
+/// not all known are listed from the declaration of their table, or from the
+/// lineage of their relation variable.
+fn lineage_columns(root_mod: &RootModule, lineage: &Lineage) -> Vec<String> {
+    let mut names = Vec::new();
+    for col in &lineage.columns {
+        match col {
+            LineageColumn::Single { name, .. } => {
+                names.extend(name.as_ref().map(|n| n.name.clone()));
+            }
+            LineageColumn::All { input_id, except } => {
+                let Some(input) = lineage.inputs.iter().find(|i| i.id == *input_id) else {
+                    continue;
+                };
+                let decl = root_mod.module.get(&input.table);
+                let Some(decl) = decl.and_then(|d| d.kind.as_table_decl()) else {
+                    continue;
+                };
+                let columns = match &decl.expr {
+                    TableExpr::RelationVar(rel) => (rel.lineage.as_ref())
+                        .map(|lineage| lineage_columns(root_mod, lineage))
+                        .unwrap_or_default(),
+                    _ => (decl.ty.as_ref().and_then(|ty| ty.as_relation()))
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|field| match field {
+                            TyTupleField::Single(Some(name), _) => Some(name.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                };
+                names.extend(columns.into_iter().filter(|name| !except.contains(name)));
+            }
+        }
+    }
+    names
+}
+
+/// Functions of the std module, sorted by name.
+fn std_functions(root_mod: &RootModule) -> Vec<Completion> {
+    let mut names = Vec::new();
+    if let Some(DeclKind::Module(std)) = root_mod.module.names.get(NS_STD).map(|d| &d.kind) {
+        collect_functions(std, "", &mut names);
+    }
+    names.sort();
+
+    (names.into_iter())
+        .map(|name| Completion {
+            name,
+            kind: CompletionKind::Function,
+        })
+        .collect()
+}
+
+fn collect_functions(module: &Module, prefix: &str, names: &mut Vec<String>) {
+    for (name, decl) in &module.names {
+        if name.starts_with('_') {
+            continue;
+        }
+        match &decl.kind {
+            DeclKind::Expr(expr) if matches!(expr.kind, ExprKind::Func(_)) => {
+                names.push(format!("{prefix}{name}"));
+            }
+            DeclKind::Module(module) => {
+                collect_functions(module, &format!("{prefix}{name}."), names);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::assert_snapshot;
+    use itertools::Itertools;
+
+    use super::{completions_at, CompletionKind};
+
+    /// Columns completed at the position of `^` in the query.
+    fn columns_at(query: &str) -> String {
+        let offset = query.find('^').unwrap();
+        let query = query.replacen('^', "", 1);
+
+        (completions_at(&query, offset).into_iter())
+            .filter(|c| c.kind == CompletionKind::Column)
+            .map(|c| c.name)
+            .join(", ")
+    }
+
+    #[test]
+    fn test_derive() {
+        assert_snapshot!(columns_at(r#"
+        from employees
+        derive {gross = salary + bonus}
+        select {id, ^gross}
+        "#), @"salary, bonus, id, gross");
+
+        assert_snapshot!(columns_at(r#"
+        from employees
+        derive {gross = ^salary + bonus}
+        select {id, gross}
+        "#), @"salary, bonus, id");
+    }
+
+    #[test]
+    fn test_between_transforms() {
+        assert_snapshot!(columns_at(r#"
+        from employees
+        select {id, name}
+        ^
+        take 10
+        "#), @"id, name");
+
+        assert_snapshot!(columns_at(r#"
+        from ^employees
+        select {id, name}
+        "#), @"");
+    }
+
+    #[test]
+    fn test_join() {
+        assert_snapshot!(columns_at(r#"
+        from e = employees
+        join d = departments (e.dept_id == d.id)
+        group {d.title} (
+          aggregate {total = sum ^e.salary}
+        )
+        "#), @"dept_id, salary, id, title");
+    }
+
+    #[test]
+    fn test_relation_var() {
+        assert_snapshot!(columns_at(r#"
+        let paid = (
+          from employees
+          derive {pay = ^salary + bonus}
+        )
+
+        from paid
+        select {id, pay}
+        "#), @"salary, bonus, id");
+
+        assert_snapshot!(columns_at(r#"
+        let paid = (
+          from employees
+          derive {pay = salary + bonus}
+        )
+
+        from paid
+        select {id, ^pay}
+        "#), @"salary, bonus, id, pay");
+    }
+
+    #[test]
+    fn test_s_string() {
+        let query = r#"from employees | select {id, s"UPPER(name)"}"#;
+        let offset = query.find("UPPER").unwrap();
+        assert!(completions_at(query, offset).is_empty());
+    }
+
+    #[test]
+    fn test_functions() {
+        let completions = completions_at("from employees | select {id}", 26);
+        let functions = (completions.iter())
+            .filter(|c| c.kind == CompletionKind::Function)
+            .map(|c| c.name.as_str())
+            .collect_vec();
+        assert!(functions.contains(&"derive"));
+        assert!(functions.contains(&"math.abs"));
+        assert!(functions.contains(&"text.lower"));
+        assert!(!functions.iter().any(|f| f.starts_with('_')));
+
+        // a query that does not parse still completes to functions
+        let completions = completions_at("from employees | select {", 25);
+        assert!(!completions.is_empty());
+        assert!(completions
+            .iter()
+            .all(|c| c.kind == CompletionKind::Function));
+    }
+}
@@ -12,7 +12,8 @@ use crate::{Error, Errors, MessageKind, SourceTree};
 
 #[derive(Clone, Serialize)]
 pub struct ErrorMessage {
-    /// Message kind. Currently only Error is implemented.
+    /// Message kind, which is a warning for the warnings of
+    /// [compile_with_warnings](crate::compile_with_warnings).
     pub kind: MessageKind,
     /// Machine-readable identifier of the error
     pub code: Option<String>,
@@ -169,7 +170,11 @@ impl ErrorMessage {
 
         let span = Range::from(self.span?);
 
-        let mut report = Report::build(ReportKind::Error, source_path.clone(), span.start)
+        let kind = match self.kind {
+            MessageKind::Error => ReportKind::Error,
+            MessageKind::Warning | MessageKind::Lint => ReportKind::Warning,
+        };
+        let mut report = Report::build(kind, source_path.clone(), span.start)
             .with_config(config)
             .with_label(Label::new((source_path, span)).with_message(&self.reason));
 
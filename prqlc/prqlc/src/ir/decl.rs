@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 
 use enum_as_inner::EnumAsInner;
@@ -17,6 +17,12 @@ pub struct RootModule {
     pub module: Module,
 
     pub span_map: HashMap<usize, Span>,
+
+    /// Comments that precede transforms in the source, by source id and the
+    /// position of the first token of the transform. Collected only when
+    /// [crate::Options::transform_comments] is set.
+    #[serde(skip)]
+    pub transform_comments: BTreeMap<(u16, usize), String>,
 }
 
 #[derive(Default, PartialEq, Serialize, Deserialize, Clone)]
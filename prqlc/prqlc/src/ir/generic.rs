@@ -7,6 +7,28 @@ use prqlc_parser::generic;
 pub struct ColumnSort<T> {
     pub direction: SortDirection,
     pub column: T,
+
+    /// Placement of nulls. When `None`, nulls are placed where the database
+    /// places them by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nulls: Option<NullsOrder>,
+}
+
+impl<T> ColumnSort<T> {
+    /// The sort of the same column in reverse, with nulls placed at the other end.
+    pub fn reversed(self) -> Self {
+        ColumnSort {
+            direction: match self.direction {
+                SortDirection::Asc => SortDirection::Desc,
+                SortDirection::Desc => SortDirection::Asc,
+            },
+            nulls: self.nulls.map(|nulls| match nulls {
+                NullsOrder::First => NullsOrder::Last,
+                NullsOrder::Last => NullsOrder::First,
+            }),
+            ..self
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Default, Deserialize, PartialEq, Eq, JsonSchema)]
@@ -16,6 +38,12 @@ pub enum SortDirection {
     Desc,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WindowFrame<T> {
     pub kind: WindowKind,
@@ -28,6 +56,28 @@ pub enum WindowKind {
     Range,
 }
 
+/// A boundary of a window frame.
+///
+/// Ends of [WindowFrame::range] are integer offsets relative to the current
+/// row: `None` is unbounded and `0` is the current row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFrameBound {
+    Unbounded,
+    CurrentRow,
+    /// Negative offsets precede the current row, positive ones follow it.
+    Offset(i64),
+}
+
+impl WindowFrameBound {
+    pub fn from_offset(offset: Option<i64>) -> Self {
+        match offset {
+            None => WindowFrameBound::Unbounded,
+            Some(0) => WindowFrameBound::CurrentRow,
+            Some(n) => WindowFrameBound::Offset(n),
+        }
+    }
+}
+
 impl<T> WindowFrame<T> {
     pub(crate) fn is_default(&self) -> bool {
         matches!(
@@ -42,7 +42,7 @@ pub struct Expr {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lineage: Option<Lineage>,
 
-    #[serde(skip)]
+    #[serde(skip_serializing_if = "is_false", default)]
     pub needs_window: bool,
 
     /// When true on [ExprKind::Tuple], this list will be flattened when placed
@@ -71,6 +71,20 @@ pub enum ExprKind {
     SString(Vec<InterpolateItem>),
     FString(Vec<InterpolateItem>),
     Case(Vec<SwitchCase>),
+
+    /// Binds each column of a relation to a name, as in
+    /// `{min_p, max_p} = (from orders | aggregate {min price, max price})`.
+    Destructure {
+        names: Vec<String>,
+        expr: Box<Expr>,
+    },
+
+    /// Conversion of a value to a type, as in `price as float`.
+    Cast {
+        expr: Box<Expr>,
+        ty: Ty,
+    },
+
     RqOperator {
         name: String,
         args: Vec<Expr>,
@@ -79,6 +93,11 @@ pub enum ExprKind {
     /// placeholder for values provided after query is compiled
     Param(String),
 
+    /// A tuple or an array, whose fields are passed to a function as
+    /// positional arguments. Only valid within [FuncCall::args] and expanded
+    /// during resolution.
+    Spread(Box<Expr>),
+
     /// When used instead of function body, the function will be translated to a RQ operator.
     /// Contains ident of the RQ operator.
     Internal(String),
@@ -195,3 +214,7 @@ impl std::fmt::Debug for Expr {
         ds.finish()
     }
 }
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
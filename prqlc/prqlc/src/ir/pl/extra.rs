@@ -92,6 +92,22 @@ pub enum TransformKind {
     },
     Append(Box<Expr>),
     Loop(Box<Expr>),
+    Pivot {
+        /// Test of the pivoted column against a literal list of values
+        /// (`std.array_in`), one output column is produced for each value.
+        on: Box<Expr>,
+        /// Aggregations computed for each of the values.
+        using: Vec<Expr>,
+        by: Vec<Expr>,
+    },
+    Fill {
+        /// Columns whose nulls are replaced, each with a new id and named
+        /// after the column.
+        columns: Vec<Expr>,
+        by: Vec<Expr>,
+        sort: Vec<ColumnSort>,
+        direction: FillDir,
+    },
 }
 
 /// A reference to a table that is not in scope of this query.
@@ -107,6 +123,15 @@ pub enum TableExternRef {
     // TODO: add other sources such as files, URLs
 }
 
+/// Which non-null value `fill` carries into the nulls of a column.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub enum FillDir {
+    /// The last one before the null.
+    Forward,
+    /// The first one after the null.
+    Backward,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum JoinSide {
     Inner,
@@ -4,8 +4,10 @@
 use itertools::Itertools;
 
 use super::*;
+use crate::lr::ValueAndUnit;
 use crate::pr::{Ty, TyFunc, TyKind, TyTupleField};
 use crate::Result;
+use crate::Span;
 
 // Fold pattern:
 // - https://rust-unofficial.github.io/patterns/patterns/creational/fold.html
@@ -21,6 +23,7 @@ use crate::Result;
 // some cases. Ref https://stackoverflow.com/a/66077767/3064736
 pub trait PlFold {
     fn fold_stmt(&mut self, mut stmt: Stmt) -> Result<Stmt> {
+        stmt.span = self.fold_span(stmt.span)?;
         stmt.kind = fold_stmt_kind(self, stmt.kind)?;
         Ok(stmt)
     }
@@ -28,6 +31,7 @@ pub trait PlFold {
         stmts.into_iter().map(|stmt| self.fold_stmt(stmt)).collect()
     }
     fn fold_expr(&mut self, mut expr: Expr) -> Result<Expr> {
+        expr.span = self.fold_span(expr.span)?;
         expr.kind = self.fold_expr_kind(expr.kind)?;
         Ok(expr)
     }
@@ -52,9 +56,35 @@ pub trait PlFold {
     fn fold_func_call(&mut self, func_call: FuncCall) -> Result<FuncCall> {
         fold_func_call(self, func_call)
     }
+    /// Named argument of a function call, such as `side:left` in
+    /// `join side:left`. Returning `None` drops the argument.
+    fn fold_named_arg(&mut self, name: String, expr: Expr) -> Result<Option<(String, Expr)>> {
+        Ok(Some((name, self.fold_expr(expr)?)))
+    }
+    /// Name of a called function, when the function is referenced by an
+    /// identifier, such as `average` in `average salary`.
+    fn fold_func_name(&mut self, name: Ident) -> Result<Ident> {
+        Ok(name)
+    }
+    /// Branches of a `case`, including its `else`, which is a branch with a
+    /// `true` condition.
+    fn fold_case(&mut self, cases: Vec<SwitchCase>) -> Result<Vec<SwitchCase>> {
+        fold_cases(self, cases)
+    }
+    /// A value converted to a type, such as `price as float`.
+    fn fold_cast(&mut self, expr: Expr, ty: Ty) -> Result<(Expr, Ty)> {
+        Ok((self.fold_expr(expr)?, self.fold_type(ty)?))
+    }
     fn fold_transform_call(&mut self, transform_call: TransformCall) -> Result<TransformCall> {
         fold_transform_call(self, transform_call)
     }
+    fn fold_transform_kind(&mut self, transform_kind: TransformKind) -> Result<TransformKind> {
+        fold_transform_kind(self, transform_kind)
+    }
+    /// Name of an operator, such as `std.mod` for `%`.
+    fn fold_operator(&mut self, name: String) -> Result<String> {
+        Ok(name)
+    }
     fn fold_func(&mut self, func: Func) -> Result<Func> {
         fold_func(self, func)
     }
@@ -67,6 +97,25 @@ pub trait PlFold {
     fn fold_window(&mut self, window: WindowFrame) -> Result<WindowFrame> {
         fold_window(self, window)
     }
+    fn fold_literal(&mut self, literal: Literal) -> Result<Literal> {
+        fold_literal(self, literal)
+    }
+    /// Intervals, such as `6months`, which are literals with a unit.
+    fn fold_interval(&mut self, interval: ValueAndUnit) -> Result<ValueAndUnit> {
+        Ok(interval)
+    }
+    /// Spans of statements, expressions and types are routed through this, so
+    /// passes that synthesize nodes can remap them. Defaults to identity.
+    fn fold_span(&mut self, span: Option<Span>) -> Result<Option<Span>> {
+        Ok(span)
+    }
+}
+
+pub fn fold_literal<T: ?Sized + PlFold>(fold: &mut T, literal: Literal) -> Result<Literal> {
+    Ok(match literal {
+        Literal::ValueAndUnit(interval) => Literal::ValueAndUnit(fold.fold_interval(interval)?),
+        literal => literal,
+    })
 }
 
 pub fn fold_expr_kind<T: ?Sized + PlFold>(fold: &mut T, expr_kind: ExprKind) -> Result<ExprKind> {
@@ -91,19 +140,31 @@ pub fn fold_expr_kind<T: ?Sized + PlFold>(fold: &mut T, expr_kind: ExprKind) ->
                 .map(|x| fold.fold_interpolate_item(x))
                 .try_collect()?,
         ),
-        Case(cases) => Case(fold_cases(fold, cases)?),
+        Case(cases) => Case(fold.fold_case(cases)?),
+        Destructure { names, expr } => Destructure {
+            names,
+            expr: Box::new(fold.fold_expr(*expr)?),
+        },
+        Cast { expr, ty } => {
+            let (expr, ty) = fold.fold_cast(*expr, ty)?;
+            let expr = Box::new(expr);
+            Cast { expr, ty }
+        }
 
         FuncCall(func_call) => FuncCall(fold.fold_func_call(func_call)?),
         Func(closure) => Func(Box::new(fold.fold_func(*closure)?)),
 
         TransformCall(transform) => TransformCall(fold.fold_transform_call(transform)?),
         RqOperator { name, args } => RqOperator {
-            name,
+            name: fold.fold_operator(name)?,
             args: fold.fold_exprs(args)?,
         },
+        Spread(expr) => Spread(Box::new(fold.fold_expr(*expr)?)),
+
+        Literal(literal) => Literal(fold.fold_literal(literal)?),
 
         // None of these capture variables, so we don't need to fold them.
-        Param(_) | Internal(_) | Literal(_) => expr_kind,
+        Param(_) | Internal(_) => expr_kind,
     })
 }
 
@@ -169,7 +230,10 @@ pub fn fold_interpolate_item<F: ?Sized + PlFold>(
     })
 }
 
-fn fold_cases<F: ?Sized + PlFold>(fold: &mut F, cases: Vec<SwitchCase>) -> Result<Vec<SwitchCase>> {
+pub fn fold_cases<F: ?Sized + PlFold>(
+    fold: &mut F,
+    cases: Vec<SwitchCase>,
+) -> Result<Vec<SwitchCase>> {
     cases
         .into_iter()
         .map(|c| fold_switch_case(fold, c))
@@ -199,17 +263,26 @@ pub fn fold_column_sort<T: ?Sized + PlFold>(
     Ok(ColumnSort {
         direction: sort_column.direction,
         column: Box::new(fold.fold_expr(*sort_column.column)?),
+        nulls: sort_column.nulls,
     })
 }
 
 pub fn fold_func_call<T: ?Sized + PlFold>(fold: &mut T, func_call: FuncCall) -> Result<FuncCall> {
+    let mut name = *func_call.name;
+    if let ExprKind::Ident(ident) = name.kind {
+        name.kind = ExprKind::Ident(fold.fold_func_name(ident)?);
+    }
+
     Ok(FuncCall {
-        name: Box::new(fold.fold_expr(*func_call.name)?),
+        name: Box::new(fold.fold_expr(name)?),
         args: fold.fold_exprs(func_call.args)?,
+        // sorted, so folds that generate ids or report errors are not
+        // affected by the iteration order of the map
         named_args: func_call
             .named_args
             .into_iter()
-            .map(|(name, expr)| fold.fold_expr(expr).map(|e| (name, e)))
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .filter_map(|(name, expr)| fold.fold_named_arg(name, expr).transpose())
             .try_collect()?,
     })
 }
@@ -219,7 +292,7 @@ pub fn fold_transform_call<T: ?Sized + PlFold>(
     t: TransformCall,
 ) -> Result<TransformCall> {
     Ok(TransformCall {
-        kind: Box::new(fold_transform_kind(fold, *t.kind)?),
+        kind: Box::new(fold.fold_transform_kind(*t.kind)?),
         input: Box::new(fold.fold_expr(*t.input)?),
         partition: fold_optional_box(fold, t.partition)?,
         frame: fold.fold_window(t.frame)?,
@@ -271,6 +344,22 @@ pub fn fold_transform_kind<T: ?Sized + PlFold>(
             pipeline: Box::new(fold.fold_expr(*pipeline)?),
         },
         Loop(pipeline) => Loop(Box::new(fold.fold_expr(*pipeline)?)),
+        Pivot { on, using, by } => Pivot {
+            on: Box::new(fold.fold_expr(*on)?),
+            using: fold.fold_exprs(using)?,
+            by: fold.fold_exprs(by)?,
+        },
+        Fill {
+            columns,
+            by,
+            sort,
+            direction,
+        } => Fill {
+            columns: fold.fold_exprs(columns)?,
+            by: fold.fold_exprs(by)?,
+            sort: fold_column_sorts(fold, sort)?,
+            direction,
+        },
     })
 }
 
@@ -355,7 +444,326 @@ pub fn fold_type<T: ?Sized + PlFold>(fold: &mut T, ty: Ty) -> Result<Ty> {
             | TyKind::Singleton(_)
             | TyKind::GenericArg(_) => ty.kind,
         },
-        span: ty.span,
+        span: fold.fold_span(ty.span)?,
         name: ty.name,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::pr::PrimitiveSet;
+    use crate::semantic::ast_expand::expand_module_def;
+
+    /// Rewrites all spans to `None`.
+    struct SpanEraser;
+
+    impl PlFold for SpanEraser {
+        fn fold_span(&mut self, _: Option<Span>) -> Result<Option<Span>> {
+            Ok(None)
+        }
+    }
+
+    /// Counts the spans it is given, and how many of them are set.
+    #[derive(Default)]
+    struct SpanCounter {
+        total: usize,
+        set: usize,
+    }
+
+    impl PlFold for SpanCounter {
+        fn fold_span(&mut self, span: Option<Span>) -> Result<Option<Span>> {
+            self.total += 1;
+            self.set += span.is_some() as usize;
+            Ok(span)
+        }
+    }
+
+    /// Doubles all integer literals.
+    struct IntDoubler;
+
+    impl PlFold for IntDoubler {
+        fn fold_literal(&mut self, literal: Literal) -> Result<Literal> {
+            Ok(match literal {
+                Literal::Integer(i) => Literal::Integer(i * 2),
+                literal => literal,
+            })
+        }
+    }
+
+    /// Expresses weeks as days.
+    struct WeeksToDays;
+
+    impl PlFold for WeeksToDays {
+        fn fold_interval(&mut self, interval: ValueAndUnit) -> Result<ValueAndUnit> {
+            Ok(match interval.unit.as_str() {
+                "weeks" => ValueAndUnit {
+                    n: interval.n * 7,
+                    unit: "days".to_string(),
+                },
+                _ => interval,
+            })
+        }
+    }
+
+    /// Replaces `%` with a call to `math.mod`.
+    struct ModRewriter;
+
+    impl PlFold for ModRewriter {
+        fn fold_operator(&mut self, name: String) -> Result<String> {
+            Ok(match name.as_str() {
+                "std.mod" => "std.math.mod".to_string(),
+                _ => name,
+            })
+        }
+    }
+
+    /// Removes the default branch of each `case`, so it results in null.
+    struct DefaultRemover;
+
+    impl PlFold for DefaultRemover {
+        fn fold_case(&mut self, cases: Vec<SwitchCase>) -> Result<Vec<SwitchCase>> {
+            let mut cases = fold_cases(self, cases)?;
+            cases
+                .retain(|c| !matches!(c.condition.kind, ExprKind::Literal(Literal::Boolean(true))));
+            Ok(cases)
+        }
+    }
+
+    /// Casts to float instead of int, including the casts within a cast.
+    struct IntToFloat;
+
+    impl PlFold for IntToFloat {
+        fn fold_type(&mut self, ty: Ty) -> Result<Ty> {
+            Ok(match ty.kind {
+                TyKind::Primitive(PrimitiveSet::Int) => Ty {
+                    kind: TyKind::Primitive(PrimitiveSet::Float),
+                    ..ty
+                },
+                _ => fold_type(self, ty)?,
+            })
+        }
+    }
+
+    /// Turns all joins into left joins.
+    struct LeftJoiner;
+
+    impl PlFold for LeftJoiner {
+        fn fold_transform_kind(&mut self, kind: TransformKind) -> Result<TransformKind> {
+            Ok(match fold_transform_kind(self, kind)? {
+                TransformKind::Join { with, filter, .. } => TransformKind::Join {
+                    side: JoinSide::Left,
+                    with,
+                    filter,
+                },
+                kind => kind,
+            })
+        }
+    }
+
+    /// Renames `by` to `partition`, drops `direction` and records the names
+    /// in the order it is given them.
+    #[derive(Default)]
+    struct NamedArgRewriter {
+        seen: Vec<String>,
+    }
+
+    impl PlFold for NamedArgRewriter {
+        fn fold_named_arg(&mut self, name: String, expr: Expr) -> Result<Option<(String, Expr)>> {
+            self.seen.push(name.clone());
+            Ok(match name.as_str() {
+                "by" => Some(("partition".to_string(), expr)),
+                "direction" => None,
+                _ => Some((name, expr)),
+            })
+        }
+    }
+
+    #[test]
+    fn test_fold_transform_kind() {
+        let span = Some(Span {
+            start: 1,
+            end: 2,
+            source_id: 0,
+        });
+        let join = TransformCall {
+            input: Box::new(Expr::new(Ident::from_name("x"))),
+            kind: Box::new(TransformKind::Join {
+                side: JoinSide::Inner,
+                with: Box::new(Expr::new(Ident::from_name("y"))),
+                filter: Box::new(Expr::new(Literal::Boolean(true))),
+            }),
+            partition: None,
+            frame: WindowFrame::default(),
+            sort: vec![],
+        };
+        let expr = Expr {
+            span,
+            ..Expr::new(ExprKind::TransformCall(join))
+        };
+
+        let expr = LeftJoiner.fold_expr(expr).unwrap();
+        assert_eq!(expr.span, span);
+
+        let join = expr.kind.into_transform_call().unwrap();
+        assert!(matches!(
+            *join.kind,
+            TransformKind::Join {
+                side: JoinSide::Left,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_fold_named_arg() {
+        let named_args: HashMap<_, _> = ["sort", "direction", "by", "a"]
+            .into_iter()
+            .map(|name| (name.to_string(), Expr::new(Ident::from_name(name))))
+            .collect();
+        let call = FuncCall {
+            name: Box::new(Expr::new(Ident::from_name("fill"))),
+            args: vec![],
+            named_args,
+        };
+
+        let mut rewriter = NamedArgRewriter::default();
+        let call = rewriter.fold_func_call(call).unwrap();
+        assert_eq!(rewriter.seen, vec!["a", "by", "direction", "sort"]);
+
+        let names: Vec<_> = call.named_args.keys().sorted().collect();
+        assert_eq!(names, vec!["a", "partition", "sort"]);
+        assert_eq!(
+            call.named_args["partition"],
+            Expr::new(Ident::from_name("by"))
+        );
+    }
+
+    #[test]
+    fn test_fold_case() {
+        let case = |cases: Vec<(Expr, i64)>| {
+            let cases = (cases.into_iter())
+                .map(|(condition, value)| SwitchCase {
+                    condition: Box::new(condition),
+                    value: Box::new(Expr::new(Literal::Integer(value))),
+                })
+                .collect();
+            Expr::new(ExprKind::Case(cases))
+        };
+        let x = Expr::new(Ident::from_name("x"));
+        let otherwise = Expr::new(Literal::Boolean(true));
+
+        let inner = case(vec![(x.clone(), 1), (otherwise.clone(), 2)]);
+        let expr = Expr::new(ExprKind::Tuple(vec![inner, case(vec![(otherwise, 3)])]));
+
+        let expr = DefaultRemover.fold_expr(expr).unwrap();
+        let items = expr.kind.into_tuple().unwrap();
+        assert_eq!(items[0], case(vec![(x, 1)]));
+        assert_eq!(items[1], case(vec![]));
+    }
+
+    #[test]
+    fn test_fold_operator() {
+        let modulo = Expr::new(ExprKind::RqOperator {
+            name: "std.mod".to_string(),
+            args: vec![
+                Expr::new(Ident::from_name("x")),
+                Expr::new(Literal::Integer(2)),
+            ],
+        });
+        let expr = Expr::new(ExprKind::RqOperator {
+            name: "std.neg".to_string(),
+            args: vec![modulo],
+        });
+
+        let expr = ModRewriter.fold_expr(expr).unwrap();
+        let ExprKind::RqOperator { name, args } = expr.kind else {
+            panic!()
+        };
+        assert_eq!(name, "std.neg");
+        assert!(
+            matches!(&args[0].kind, ExprKind::RqOperator { name, .. } if name == "std.math.mod")
+        );
+    }
+
+    #[test]
+    fn test_fold_cast() {
+        let cast = |expr: Expr, ty: PrimitiveSet| {
+            let expr = Box::new(expr);
+            let ty = Ty::new(TyKind::Primitive(ty));
+            Expr::new(ExprKind::Cast { expr, ty })
+        };
+        let x = Expr::new(Ident::from_name("x"));
+
+        let expr = cast(cast(x.clone(), PrimitiveSet::Int), PrimitiveSet::Int);
+        let expr = IntToFloat.fold_expr(expr).unwrap();
+        assert_eq!(
+            expr,
+            cast(cast(x, PrimitiveSet::Float), PrimitiveSet::Float)
+        );
+    }
+
+    #[test]
+    fn test_fold_literal() {
+        let expr = Expr::new(ExprKind::Tuple(vec![
+            Expr::new(Literal::Integer(3)),
+            Expr::new(Literal::String("3".to_string())),
+        ]));
+
+        let expr = IntDoubler.fold_expr(expr).unwrap();
+        let fields = expr.kind.into_tuple().unwrap();
+        assert_eq!(fields[0].kind, ExprKind::Literal(Literal::Integer(6)));
+        assert_eq!(
+            fields[1].kind,
+            ExprKind::Literal(Literal::String("3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_fold_interval() {
+        let interval = |n, unit: &str| {
+            Expr::new(Literal::ValueAndUnit(ValueAndUnit {
+                n,
+                unit: unit.to_string(),
+            }))
+        };
+        let expr = Expr::new(ExprKind::Array(vec![
+            interval(2, "weeks"),
+            interval(3, "months"),
+        ]));
+
+        let expr = WeeksToDays.fold_expr(expr).unwrap();
+        let items = expr.kind.into_array().unwrap();
+        assert_eq!(items[0], interval(14, "days"));
+        assert_eq!(items[1], interval(3, "months"));
+    }
+
+    #[test]
+    fn test_fold_span() {
+        let source = r#"
+        let add_one = x -> x + 1
+        let employees <[{name = text, salary = int}]>
+
+        from employees
+        derive {raise = add_one salary, label = f"{name}!"}
+        filter (case [raise > 100 => true, true => false])
+        sort {-raise}
+        take 10
+        "#;
+        let module_def = crate::parser::parse(&source.into()).unwrap();
+        let stmts = expand_module_def(module_def).unwrap().stmts;
+
+        let mut counter = SpanCounter::default();
+        let stmts = counter.fold_stmts(stmts).unwrap();
+        assert!(counter.set > 0);
+
+        let stmts = SpanEraser.fold_stmts(stmts).unwrap();
+
+        let mut counter = SpanCounter::default();
+        counter.fold_stmts(stmts).unwrap();
+        assert!(counter.total > 0);
+        assert_eq!(counter.set, 0);
+    }
+}
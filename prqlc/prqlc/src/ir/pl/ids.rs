@@ -0,0 +1,127 @@
+use itertools::Itertools;
+
+use super::*;
+use crate::utils::IdGenerator;
+use crate::Result;
+
+/// Assigns ids to all statements and expressions, in the order they are
+/// visited: each node before its children, and the children in the order of
+/// their fields.
+///
+/// Existing ids are replaced, so the ids depend only on the statements, and
+/// are the same each time the same source is compiled. Because references to
+/// the replaced ids are not updated, this is meant for PL that has not been
+/// resolved yet.
+pub fn assign_ids(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    let mut assigner = IdAssigner::default();
+    assigner.fold_stmts(stmts).unwrap()
+}
+
+#[derive(Default)]
+struct IdAssigner {
+    id: IdGenerator<usize>,
+}
+
+impl PlFold for IdAssigner {
+    fn fold_stmt(&mut self, mut stmt: Stmt) -> Result<Stmt> {
+        stmt.id = Some(self.id.gen());
+        stmt.annotations = (stmt.annotations.into_iter())
+            .map(|a| -> Result<_> {
+                Ok(Annotation {
+                    expr: Box::new(self.fold_expr(*a.expr)?),
+                })
+            })
+            .try_collect()?;
+        stmt.kind = fold_stmt_kind(self, stmt.kind)?;
+        Ok(stmt)
+    }
+
+    fn fold_expr(&mut self, mut expr: Expr) -> Result<Expr> {
+        expr.id = Some(self.id.gen());
+        expr.kind = self.fold_expr_kind(expr.kind)?;
+        Ok(expr)
+    }
+
+    fn fold_func(&mut self, func: Func) -> Result<Func> {
+        // unlike the default fold, this includes the defaults of params and
+        // the environment, which is visited in the order of names
+        let params = fold_func_param(self, func.params)?;
+        let named_params = fold_func_param(self, func.named_params)?;
+        let body = Box::new(self.fold_expr(*func.body)?);
+        let args = self.fold_exprs(func.args)?;
+        let env = (func.env.into_iter())
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(name, expr)| -> Result<_> { Ok((name, self.fold_expr(expr)?)) })
+            .try_collect()?;
+
+        Ok(Func {
+            params,
+            named_params,
+            body,
+            args,
+            env,
+            ..func
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::ast_expand::expand_module_def;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let module_def = crate::parser::parse(&source.into()).unwrap();
+        expand_module_def(module_def).unwrap().stmts
+    }
+
+    /// Ids of all statements and expressions, in no particular order.
+    fn collect_ids(stmts: &[Stmt]) -> Vec<usize> {
+        fn collect(value: &serde_json::Value, ids: &mut Vec<usize>) {
+            match value {
+                serde_json::Value::Object(fields) => {
+                    for (name, value) in fields {
+                        match (name.as_str(), value.as_u64()) {
+                            ("id", Some(id)) => ids.push(id as usize),
+                            _ => collect(value, ids),
+                        }
+                    }
+                }
+                serde_json::Value::Array(items) => {
+                    items.iter().for_each(|item| collect(item, ids));
+                }
+                _ => {}
+            }
+        }
+
+        let mut ids = Vec::new();
+        collect(&serde_json::to_value(stmts).unwrap(), &mut ids);
+        ids
+    }
+
+    #[test]
+    fn test_assign_ids() {
+        let source = r#"
+        @{binding_strength=1}
+        let add = a b:1 -> a + b
+
+        from employees
+        derive {gross = add salary b:tax, rank = case [salary > 10 => "high"]}
+        join side:left managers (==manager_id)
+        sort {-gross}
+        "#;
+
+        let stmts = assign_ids(parse(source));
+        assert_eq!(stmts[0].id, Some(0));
+        assert_eq!(stmts[0].annotations[0].expr.id, Some(1));
+
+        // each id is assigned once, without gaps
+        let ids = collect_ids(&stmts).into_iter().sorted().collect_vec();
+        assert!(ids.len() > 20);
+        assert_eq!(ids, (0..ids.len()).collect_vec());
+
+        // the same source gets the same ids, even when its ids were set before
+        assert_eq!(assign_ids(parse(source)), stmts);
+        assert_eq!(assign_ids(stmts.clone()), stmts);
+    }
+}
@@ -15,16 +15,20 @@ pub use crate::pr::{BinOp, BinaryExpr, Ident, UnOp, UnaryExpr};
 pub use self::expr::*;
 pub use self::extra::*;
 pub use self::fold::*;
+pub use self::ids::*;
 pub use self::lineage::*;
 pub use self::stmt::*;
 pub use self::utils::*;
+pub use self::visit::*;
 
 mod expr;
 mod extra;
 mod fold;
+mod ids;
 mod lineage;
 mod stmt;
 mod utils;
+mod visit;
 
 pub fn print_mem_sizes() {
     use std::mem::size_of;
@@ -0,0 +1,290 @@
+use std::ops::ControlFlow;
+
+use itertools::Itertools;
+
+use super::*;
+
+/// A trait to "visit" a PRQL AST, so we can inspect a whole tree without
+/// consuming and rebuilding it, as [PlFold](super::PlFold) does.
+///
+/// The default methods descend into the same children as the free functions
+/// of the fold, in the same order. Returning [ControlFlow::Break] stops the
+/// visit.
+pub trait PlVisitor {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> ControlFlow<()> {
+        visit_stmt_kind(self, &stmt.kind)
+    }
+    fn visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        visit_expr_kind(self, &expr.kind)
+    }
+    /// Identifier of an [ExprKind::Ident].
+    fn visit_ident(&mut self, _ident: &Ident) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+    fn visit_func_call(&mut self, func_call: &FuncCall) -> ControlFlow<()> {
+        visit_func_call(self, func_call)
+    }
+    fn visit_func(&mut self, func: &Func) -> ControlFlow<()> {
+        visit_func(self, func)
+    }
+    fn visit_transform_call(&mut self, transform_call: &TransformCall) -> ControlFlow<()> {
+        visit_transform_call(self, transform_call)
+    }
+    fn visit_transform_kind(&mut self, transform_kind: &TransformKind) -> ControlFlow<()> {
+        visit_transform_kind(self, transform_kind)
+    }
+    fn visit_interpolate_item(&mut self, item: &InterpolateItem) -> ControlFlow<()> {
+        visit_interpolate_item(self, item)
+    }
+    /// Branches of a `case`, including its `else`.
+    fn visit_case(&mut self, cases: &[SwitchCase]) -> ControlFlow<()> {
+        visit_cases(self, cases)
+    }
+}
+
+pub fn visit_exprs<'a, V: ?Sized + PlVisitor>(
+    visitor: &mut V,
+    exprs: impl IntoIterator<Item = &'a Expr>,
+) -> ControlFlow<()> {
+    for expr in exprs {
+        visitor.visit_expr(expr)?;
+    }
+    ControlFlow::Continue(())
+}
+
+pub fn visit_expr_kind<V: ?Sized + PlVisitor>(visitor: &mut V, kind: &ExprKind) -> ControlFlow<()> {
+    use ExprKind::*;
+    match kind {
+        Ident(ident) => visitor.visit_ident(ident),
+        All { within, except } => {
+            visitor.visit_expr(within)?;
+            visitor.visit_expr(except)
+        }
+        Tuple(items) | Array(items) => visit_exprs(visitor, items),
+        SString(items) | FString(items) => {
+            for item in items {
+                visitor.visit_interpolate_item(item)?;
+            }
+            ControlFlow::Continue(())
+        }
+        Case(cases) => visitor.visit_case(cases),
+        Destructure { expr, .. } | Cast { expr, .. } | Spread(expr) => visitor.visit_expr(expr),
+
+        FuncCall(func_call) => visitor.visit_func_call(func_call),
+        Func(func) => visitor.visit_func(func),
+
+        TransformCall(transform) => visitor.visit_transform_call(transform),
+        RqOperator { args, .. } => visit_exprs(visitor, args),
+
+        Literal(_) | Param(_) | Internal(_) => ControlFlow::Continue(()),
+    }
+}
+
+pub fn visit_stmt_kind<V: ?Sized + PlVisitor>(visitor: &mut V, kind: &StmtKind) -> ControlFlow<()> {
+    match kind {
+        StmtKind::VarDef(var_def) => visit_exprs(visitor, var_def.value.as_deref()),
+        StmtKind::ModuleDef(module_def) => {
+            for stmt in &module_def.stmts {
+                visitor.visit_stmt(stmt)?;
+            }
+            ControlFlow::Continue(())
+        }
+        StmtKind::QueryDef(_) | StmtKind::TypeDef(_) | StmtKind::ImportDef(_) => {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+pub fn visit_range<V: ?Sized + PlVisitor>(visitor: &mut V, range: &Range) -> ControlFlow<()> {
+    visit_exprs(visitor, range.start.as_deref())?;
+    visit_exprs(visitor, range.end.as_deref())
+}
+
+pub fn visit_interpolate_item<V: ?Sized + PlVisitor>(
+    visitor: &mut V,
+    item: &InterpolateItem,
+) -> ControlFlow<()> {
+    match item {
+        InterpolateItem::String(_) => ControlFlow::Continue(()),
+        InterpolateItem::Expr { expr, .. } => visitor.visit_expr(expr),
+    }
+}
+
+pub fn visit_cases<V: ?Sized + PlVisitor>(
+    visitor: &mut V,
+    cases: &[SwitchCase],
+) -> ControlFlow<()> {
+    for case in cases {
+        visitor.visit_expr(&case.condition)?;
+        visitor.visit_expr(&case.value)?;
+    }
+    ControlFlow::Continue(())
+}
+
+pub fn visit_column_sorts<V: ?Sized + PlVisitor>(
+    visitor: &mut V,
+    sort: &[ColumnSort],
+) -> ControlFlow<()> {
+    visit_exprs(visitor, sort.iter().map(|s| s.column.as_ref()))
+}
+
+pub fn visit_func_call<V: ?Sized + PlVisitor>(
+    visitor: &mut V,
+    func_call: &FuncCall,
+) -> ControlFlow<()> {
+    visitor.visit_expr(&func_call.name)?;
+    visit_exprs(visitor, &func_call.args)?;
+    // in the order of names, as the fold does
+    let named_args = (func_call.named_args.iter())
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, expr)| expr);
+    visit_exprs(visitor, named_args)
+}
+
+pub fn visit_transform_call<V: ?Sized + PlVisitor>(
+    visitor: &mut V,
+    t: &TransformCall,
+) -> ControlFlow<()> {
+    visitor.visit_transform_kind(&t.kind)?;
+    visitor.visit_expr(&t.input)?;
+    visit_exprs(visitor, t.partition.as_deref())?;
+    visit_range(visitor, &t.frame.range)?;
+    visit_column_sorts(visitor, &t.sort)
+}
+
+pub fn visit_transform_kind<V: ?Sized + PlVisitor>(
+    visitor: &mut V,
+    t: &TransformKind,
+) -> ControlFlow<()> {
+    use TransformKind::*;
+    match t {
+        Derive { assigns } | Select { assigns } | Aggregate { assigns } => {
+            visitor.visit_expr(assigns)
+        }
+        Filter { filter } => visitor.visit_expr(filter),
+        Sort { by } => visit_column_sorts(visitor, by),
+        Take { range } => visit_range(visitor, range),
+        Join { with, filter, .. } => {
+            visitor.visit_expr(with)?;
+            visitor.visit_expr(filter)
+        }
+        Append(bottom) => visitor.visit_expr(bottom),
+        Group { by, pipeline } => {
+            visitor.visit_expr(by)?;
+            visitor.visit_expr(pipeline)
+        }
+        Window {
+            range, pipeline, ..
+        } => {
+            visit_range(visitor, range)?;
+            visitor.visit_expr(pipeline)
+        }
+        Loop(pipeline) => visitor.visit_expr(pipeline),
+        Pivot { on, using, by } => {
+            visitor.visit_expr(on)?;
+            visit_exprs(visitor, using)?;
+            visit_exprs(visitor, by)
+        }
+        Fill {
+            columns, by, sort, ..
+        } => {
+            visit_exprs(visitor, columns)?;
+            visit_exprs(visitor, by)?;
+            visit_column_sorts(visitor, sort)
+        }
+    }
+}
+
+pub fn visit_func<V: ?Sized + PlVisitor>(visitor: &mut V, func: &Func) -> ControlFlow<()> {
+    visitor.visit_expr(&func.body)?;
+    visit_exprs(visitor, &func.args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::ast_expand::expand_module_def;
+    use crate::Result;
+
+    /// Counts identifiers, by visiting.
+    #[derive(Default)]
+    struct IdentVisitor {
+        count: usize,
+    }
+
+    impl PlVisitor for IdentVisitor {
+        fn visit_ident(&mut self, _: &Ident) -> ControlFlow<()> {
+            self.count += 1;
+            ControlFlow::Continue(())
+        }
+    }
+
+    /// Counts identifiers, by folding.
+    #[derive(Default)]
+    struct IdentFolder {
+        count: usize,
+    }
+
+    impl PlFold for IdentFolder {
+        fn fold_expr_kind(&mut self, kind: ExprKind) -> Result<ExprKind> {
+            self.count += kind.is_ident() as usize;
+            fold_expr_kind(self, kind)
+        }
+    }
+
+    /// Finds the first identifier, and stops.
+    #[derive(Default)]
+    struct FirstIdent {
+        found: Option<String>,
+        visited: usize,
+    }
+
+    impl PlVisitor for FirstIdent {
+        fn visit_ident(&mut self, ident: &Ident) -> ControlFlow<()> {
+            self.visited += 1;
+            self.found = Some(ident.to_string());
+            ControlFlow::Break(())
+        }
+    }
+
+    #[test]
+    fn test_visit_idents() {
+        let source = r#"
+        let add = a b:1 -> a + b
+
+        module m {
+          let c = f"{a}-{b}"
+        }
+
+        from employees
+        derive {gross = add salary b:tax, rank = case [salary > 10 => "high"]}
+        join side:left managers (==manager_id)
+        group {department} (sort {-gross} | take 1)
+        select {e = s"UPPER({name})", department}
+        "#;
+        let module_def = crate::parser::parse(&source.into()).unwrap();
+        let stmts = expand_module_def(module_def).unwrap().stmts;
+
+        let mut visitor = IdentVisitor::default();
+        for stmt in &stmts {
+            let _ = visitor.visit_stmt(stmt);
+        }
+
+        let mut folder = IdentFolder::default();
+        folder.fold_stmts(stmts.clone()).unwrap();
+
+        assert!(visitor.count > 15);
+        assert_eq!(visitor.count, folder.count);
+
+        let mut first = FirstIdent::default();
+        let flow = visit_exprs(
+            &mut first,
+            stmts
+                .iter()
+                .filter_map(|s| s.kind.as_var_def()?.value.as_deref()),
+        );
+        assert!(flow.is_break());
+        assert_eq!(first.visited, 1);
+        assert_eq!(first.found.as_deref(), Some("std.add"));
+    }
+}
@@ -0,0 +1,127 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::ir::pl::Literal;
+use crate::Result;
+
+/// Heuristic measures of how expensive a query is to run.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Complexity {
+    pub joins: usize,
+
+    pub window_functions: usize,
+
+    /// Number of tables defined by the query, which are compiled into CTEs
+    /// or sub-queries. Extern tables are not counted.
+    pub subqueries: usize,
+
+    /// Whether any of the joins has no predicate (a condition of `true`),
+    /// which produces a cartesian product of the two relations.
+    pub has_cross_join: bool,
+}
+
+/// Tallies joins, window functions and subqueries of a query.
+pub fn estimate_complexity(query: &RelationalQuery) -> Complexity {
+    let mut counter = ComplexityCounter::default();
+
+    // the counter never returns an error
+    counter.fold_query(query.clone()).unwrap();
+    counter.complexity
+}
+
+#[derive(Default)]
+struct ComplexityCounter {
+    complexity: Complexity,
+}
+
+impl RqFold for ComplexityCounter {
+    fn fold_table(&mut self, table: TableDecl) -> Result<TableDecl> {
+        if !matches!(table.relation.kind, RelationKind::ExternRef(_)) {
+            self.complexity.subqueries += 1;
+        }
+        fold_table(self, table)
+    }
+
+    fn fold_transform(&mut self, transform: Transform) -> Result<Transform> {
+        if let Transform::Join { filter, .. } = &transform {
+            self.complexity.joins += 1;
+
+            if let ExprKind::Literal(Literal::Boolean(true)) = filter.kind {
+                self.complexity.has_cross_join = true;
+            }
+        }
+        fold_transform(self, transform)
+    }
+
+    fn fold_compute(&mut self, compute: Compute) -> Result<Compute> {
+        if compute.window.is_some() {
+            self.complexity.window_functions += 1;
+        }
+        fold_compute(self, compute)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::assert_snapshot;
+
+    use super::*;
+    use crate::semantic::test::parse_resolve_and_lower;
+
+    fn estimate(prql: &str) -> Complexity {
+        estimate_complexity(&parse_resolve_and_lower(prql).unwrap())
+    }
+
+    #[test]
+    fn test_simple_query() {
+        let complexity = estimate(
+            r#"
+            from employees
+            filter salary > 1000
+            select {first_name, salary}
+            "#,
+        );
+
+        assert_eq!(complexity, Complexity::default());
+    }
+
+    #[test]
+    fn test_joins_and_windows() {
+        let complexity = estimate(
+            r#"
+            let managers = (from employees | filter is_manager)
+
+            from employees
+            join managers (==manager_id)
+            join side:left departments (employees.dept_id == departments.id)
+            group departments.name (
+                sort employees.salary
+                derive {rn = row_number, total = sum employees.salary}
+            )
+            "#,
+        );
+
+        assert_eq!(
+            complexity,
+            Complexity {
+                joins: 2,
+                window_functions: 2,
+                subqueries: 1,
+                has_cross_join: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cross_join() {
+        let complexity = estimate(
+            r#"
+            from employees
+            join departments (true)
+            "#,
+        );
+
+        assert_snapshot!(serde_json::to_string(&complexity).unwrap(), @r#"{"joins":1,"window_functions":0,"subqueries":0,"has_cross_join":true}"#);
+    }
+}
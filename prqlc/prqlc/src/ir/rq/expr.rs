@@ -4,7 +4,7 @@ use prqlc_parser::lexer::lr::Literal;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::CId;
+use super::{CId, TId};
 use crate::Span;
 
 /// Analogous to [crate::ir::pl::Expr], but with fewer kinds.
@@ -43,6 +43,9 @@ pub enum ExprKind {
     Param(String),
 
     Array(Vec<Expr>),
+
+    /// Values of the only column of a table of the query, as in `x IN (SELECT ...)`.
+    SubQuery(TId),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, JsonSchema)]
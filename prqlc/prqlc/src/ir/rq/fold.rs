@@ -62,7 +62,7 @@ pub trait RqFold {
     }
 }
 
-fn fold_compute<F: ?Sized + RqFold>(fold: &mut F, compute: Compute) -> Result<Compute> {
+pub fn fold_compute<F: ?Sized + RqFold>(fold: &mut F, compute: Compute) -> Result<Compute> {
     Ok(Compute {
         id: fold.fold_cid(compute.id)?,
         expr: fold.fold_expr(compute.expr)?,
@@ -186,6 +186,7 @@ pub fn fold_transform<T: ?Sized + RqFold>(
         },
         Append(bottom) => Append(fold.fold_table_ref(bottom)?),
         Loop(transforms) => Loop(fold_transforms(fold, transforms)?),
+        Comment(comment) => Comment(comment),
     };
     Ok(transform)
 }
@@ -200,6 +201,7 @@ pub fn fold_column_sorts<T: ?Sized + RqFold>(
             Ok(ColumnSort {
                 column: fold.fold_cid(s.column)?,
                 direction: s.direction,
+                nulls: s.nulls,
             })
         })
         .try_collect()
@@ -222,7 +224,7 @@ pub fn fold_expr_kind<F: ?Sized + RqFold>(fold: &mut F, kind: ExprKind) -> Resul
         },
         ExprKind::Param(id) => ExprKind::Param(id),
 
-        ExprKind::Literal(_) => kind,
+        ExprKind::Literal(_) | ExprKind::SubQuery(_) => kind,
         ExprKind::Array(exprs) => {
             ExprKind::Array(exprs.into_iter().map(|e| fold.fold_expr(e)).try_collect()?)
         }
@@ -6,6 +6,7 @@ use enum_as_inner::EnumAsInner;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+pub use complexity::{estimate_complexity, Complexity};
 pub use expr::{Expr, ExprKind, UnOp};
 use expr::{InterpolateItem, Range, SwitchCase};
 pub use fold::*;
@@ -17,6 +18,7 @@ pub use utils::*;
 use super::pl::QueryDef;
 use super::pl::TableExternRef;
 
+mod complexity;
 mod expr;
 mod fold;
 mod ids;
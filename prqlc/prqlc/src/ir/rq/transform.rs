@@ -29,6 +29,10 @@ pub enum Transform {
     },
     Append(TableRef),
     Loop(Vec<Transform>),
+
+    /// A comment of the source, which annotates the transform after it. It
+    /// does not affect the relation.
+    Comment(String),
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema)]
@@ -103,6 +103,8 @@ use semver::Version;
 use serde::{Deserialize, Serialize};
 use strum::VariantNames;
 
+pub use compiler::Compiler;
+pub use completion::{completions_at, Completion, CompletionKind};
 pub use error_message::{ErrorMessage, ErrorMessages, SourceLocation};
 pub use prqlc_parser::error::{Error, ErrorSource, Errors, MessageKind, Reason, WithErrorInfo};
 pub use prqlc_parser::lexer::lr;
@@ -110,10 +112,13 @@ pub use prqlc_parser::parser::pr;
 pub use prqlc_parser::span::Span;
 
 mod codegen;
+mod compiler;
+mod completion;
 pub mod debug;
 mod error_message;
 pub mod ir;
 pub mod parser;
+mod polars;
 pub mod semantic;
 pub mod sql;
 #[cfg(feature = "cli")]
@@ -196,33 +201,479 @@ pub fn compiler_version() -> Version {
 /// [`sql::Dialect`](sql/enum.Dialect.html) for options and supported SQL
 /// dialects.
 pub fn compile(prql: &str, options: &Options) -> Result<String, ErrorMessages> {
-    let sources = SourceTree::from(prql);
+    compile_with(prql.into(), options, sql::compile).map(log_warnings)
+}
+
+/// Compile a PRQL string into a SQL string, along with the warnings of the
+/// compilation, which [compile] only logs.
+///
+/// Warnings are reported by the checks of [Options] that are set to
+/// [LintLevel::Warn], such as [Options::cartesian_join].
+///
+/// ```
+/// use prqlc::{compile_with_warnings, LintLevel, Options};
+///
+/// let opts = Options::default().with_cartesian_join(LintLevel::Warn);
+/// let result = compile_with_warnings("from shirts | join hats true", &opts).unwrap();
+/// assert!(result.sql.contains("CROSS JOIN"));
+/// assert_eq!(result.warnings.len(), 1);
+/// ```
+pub fn compile_with_warnings(
+    prql: &str,
+    options: &Options,
+) -> Result<CompileResult, ErrorMessages> {
+    let (sql, warnings) = compile_with(prql.into(), options, sql::compile)?;
+    Ok(CompileResult { sql, warnings })
+}
 
+/// SQL of a query, and the warnings of its compilation.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompileResult {
+    pub sql: String,
+
+    /// Messages of kind [MessageKind::Warning], with their spans in the
+    /// source of the query.
+    pub warnings: Vec<ErrorMessage>,
+}
+
+/// Compile a PRQL project of multiple source files into a SQL string.
+///
+/// `entry` is the source of the root module, which contains the main
+/// pipeline. `sources` maps paths of the other files, relative to the project
+/// root, to their contents. Each of them is a module named after its path, so
+/// a function declared in `utils/text.prql` is referenced as
+/// `utils.text.my_function`, or imported with `import utils.text.my_function`.
+///
+/// Declaring the same name in a module from more than one file, and circular
+/// imports, are errors.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use prqlc::{compile_project, Options};
+///
+/// let entry = "from employees | derive {bonus = salaries.bonus salary}";
+/// let sources = HashMap::from([(
+///     "salaries.prql".to_string(),
+///     "let bonus = s -> s * 0.1".to_string(),
+/// )]);
+/// let opts = Options::default().no_signature().no_format();
+/// let sql = compile_project(entry, sources, &opts).unwrap();
+/// assert_eq!("SELECT *, salary * 0.1 AS bonus FROM employees", sql);
+/// ```
+pub fn compile_project(
+    entry: &str,
+    sources: HashMap<String, String>,
+    options: &Options,
+) -> Result<String, ErrorMessages> {
+    // sort, so source ids don't depend on the order of the map
+    let mut sources: Vec<_> = (sources.into_iter())
+        .map(|(path, content)| (PathBuf::from(path), content))
+        .collect();
+    sources.sort();
+
+    let root = (PathBuf::new(), entry.to_string());
+    let tree = SourceTree::new(std::iter::once(root).chain(sources), None);
+
+    compile_with(tree, options, sql::compile).map(log_warnings)
+}
+
+/// Compile each query of a PRQL script into a SQL string of its own.
+///
+/// The queries of a script are its relation variables that no other relation
+/// references, including the main pipeline, in the order of their
+/// declaration. The other relations and all functions are shared by the
+/// queries, so a relation that several queries reference is compiled into a
+/// CTE of each of them.
+///
+/// ```
+/// use prqlc::{compile_all, Options};
+///
+/// let prql = r#"
+/// let adults = (from people | filter age >= 18)
+/// let names = (from adults | select {name})
+/// from adults | aggregate {count this}
+/// "#;
+/// let opts = Options::default().no_signature().no_format();
+/// let sqls = compile_all(prql, &opts).unwrap();
+/// assert_eq!(sqls.len(), 2);
+/// assert!(sqls[0].ends_with("SELECT name FROM adults"));
+/// ```
+pub fn compile_all(prql: &str, options: &Options) -> Result<Vec<String>, ErrorMessages> {
+    compile_ast_with(prql.into(), options, |ast| {
+        let root_mod = semantic::resolve_with_table_columns(ast, &options.table_columns)
+            .map_err(|e| e.with_source(ErrorSource::NameResolver))?;
+
+        (semantic::find_query_roots(&root_mod).into_iter())
+            .map(|ident| {
+                let main_path: Vec<_> = ident.into_iter().collect();
+                let rq = semantic::lower(root_mod.clone(), &main_path, None)
+                    .map_err(|e| e.with_source(ErrorSource::NameResolver))?;
+                sql::compile(rq, options).map_err(|e| Errors::from(e.with_source(ErrorSource::SQL)))
+            })
+            .collect()
+    })
+}
+
+/// Compile a PRQL string into a SQL string with placeholders in place of
+/// literals, and the values bound to the placeholders, in order.
+///
+/// Placeholders follow the style of the dialect, such as `$1` for PostgreSQL
+/// and `?` for MySQL or SQLite. The bounds of `take` are inlined, since some
+/// drivers reject bound values in `LIMIT` and `OFFSET`, unless
+/// [`Options::bind_take`] is set.
+///
+/// ```
+/// use prqlc::{compile_parameterized, ir::pl::Literal, Options, Target, sql::Dialect};
+///
+/// let prql = "from employees | filter age > 30 | take 10";
+/// let opts = Options::default().with_target(Target::Sql(Some(Dialect::Postgres))).no_signature().no_format();
+/// let (sql, params) = compile_parameterized(prql, &opts).unwrap();
+/// assert_eq!("SELECT * FROM employees WHERE age > $1 LIMIT 10", sql);
+/// assert_eq!(vec![Literal::Integer(30)], params);
+/// ```
+pub fn compile_parameterized(
+    prql: &str,
+    options: &Options,
+) -> Result<(String, Vec<ir::pl::Literal>), ErrorMessages> {
+    compile_with(prql.into(), options, sql::compile_parameterized).map(log_warnings)
+}
+
+/// Compile a PRQL string into a SQL string, along with the inferred name and
+/// type of each column of the result.
+///
+/// Types of columns that may contain nulls are unions with `null`, such as
+/// `int || null`. Columns whose type can't be inferred, such as those computed
+/// by s-strings or undeclared columns of source tables, have type `anytype`.
+/// When the columns of a source table are unknown, they are reported as a
+/// single `table.*` column.
+///
+/// ```
+/// use prqlc::{compile_with_schema, pr::PrimitiveSet, pr::TyKind, Options};
+///
+/// let prql = r#"
+/// module default_db {
+///   let employees <[{name = text, salary = float}]>
+/// }
+/// from employees
+/// select {name, n = 1}
+/// "#;
+/// let (_sql, schema) = compile_with_schema(prql, &Options::default()).unwrap();
+/// let (name, ty) = &schema[0];
+/// assert_eq!(name.to_string(), "name");
+/// assert_eq!(ty.kind, TyKind::Primitive(PrimitiveSet::Text));
+/// ```
+pub fn compile_with_schema(
+    prql: &str,
+    options: &Options,
+) -> Result<(String, Vec<(pr::Ident, pr::Ty)>), ErrorMessages> {
+    compile_ast_with(prql.into(), options, |ast| {
+        let root_mod = semantic::resolve_with_table_columns(ast, &options.table_columns)
+            .map_err(|e| e.with_source(ErrorSource::NameResolver))?;
+
+        let (main, _) = root_mod.find_main_rel(&[]).map_err(|(hint, span)| {
+            Error::new_simple("Missing main pipeline")
+                .with_code("E0001")
+                .with_hints(hint)
+                .with_span(span)
+        })?;
+        let main = main.clone().into_relation_var().unwrap();
+        let schema = semantic::schema::infer_columns(&root_mod, &main)
+            .map_err(|e| e.with_source(ErrorSource::NameResolver))?;
+        let schema = (schema.into_iter())
+            .map(|(name, ty, nullable)| {
+                let ty = if nullable {
+                    semantic::schema::union_null(ty)
+                } else {
+                    ty
+                };
+                (name, ty)
+            })
+            .collect();
+
+        let rq = semantic::lower(root_mod, &[], None)
+            .map_err(|e| e.with_source(ErrorSource::NameResolver))?;
+        let sql = sql::compile(rq, options).map_err(|e| e.with_source(ErrorSource::SQL))?;
+        Ok((sql, schema))
+    })
+}
+
+/// Resolve a PRQL string into RQ, the relational query that backends compile,
+/// without compiling it into SQL.
+///
+/// The query has the main pipeline and a declaration of each table that it
+/// references, including the relation variables, which are pipelines of their
+/// own. Errors have their displays composed with the source, as with [compile].
+///
+/// ```
+/// use prqlc::ir::rq::{RelationKind, Transform};
+///
+/// let rq = prqlc::resolve("from employees | filter age > 30 | take 10").unwrap();
+/// let RelationKind::Pipeline(transforms) = &rq.relation.kind else {
+///     panic!("the main relation is a pipeline");
+/// };
+/// assert!(matches!(transforms[0], Transform::From(_)));
+/// assert!(matches!(transforms[1], Transform::Filter(_)));
+/// ```
+pub fn resolve(prql: &str) -> Result<ir::rq::RelationalQuery, ErrorMessages> {
+    compile_ast_with(prql.into(), &Options::default(), |ast| {
+        semantic::resolve_and_lower(ast, &[], None)
+            .map_err(|e| e.with_source(ErrorSource::NameResolver))
+    })
+}
+
+/// Compile a PRQL string into Python code of a Polars lazy frame.
+///
+/// This is an experimental target. Each transform is a method call on the
+/// frame, such as `.group_by().agg()` for an `aggregate` within a `group`, and
+/// tables are referenced as variables of lazy frames of the same name.
+/// Transforms that can't be compiled to Polars yet, such as `loop` and
+/// `append`, are errors.
+///
+/// ```
+/// let prql = "from employees | filter age > 30 | sort {-age} | take 10";
+/// assert_eq!(
+///     prqlc::compile_to_polars(prql).unwrap(),
+///     r#"(
+///   employees
+///   .filter((pl.col("age") > pl.lit(30)))
+///   .sort([pl.col("age")], descending=[True])
+///   .limit(10)
+/// )
+/// "#
+/// );
+/// ```
+pub fn compile_to_polars(prql: &str) -> Result<String, ErrorMessages> {
+    compile_ast_with(prql.into(), &Options::default(), |ast| {
+        let rq = semantic::resolve_and_lower(ast, &[], None)
+            .map_err(|e| e.with_source(ErrorSource::NameResolver))?;
+        polars::compile(rq)
+    })
+}
+
+/// Lists the tables of a database that a query reads, sorted by name, such as
+/// for scheduling the query after the jobs that write those tables.
+///
+/// Relation variables of the query, which are compiled into CTEs, are not
+/// tables of the database, so they are listed by the tables that they read
+/// instead. Names are paths within the database, so a table of the default
+/// database is a name without a path.
+///
+/// ```
+/// use prqlc::referenced_tables;
+///
+/// let prql = r#"
+/// let managers = (from employees | filter is_manager)
+/// from managers
+/// join departments (==dept_id)
+/// "#;
+/// let tables = referenced_tables(prql).unwrap();
+/// let names: Vec<_> = tables.iter().map(|t| t.to_string()).collect();
+/// assert_eq!(names, ["departments", "employees"]);
+/// ```
+pub fn referenced_tables(prql: &str) -> Result<Vec<pr::Ident>, ErrorMessages> {
+    let mut tables = Vec::new();
+    for table in resolve(prql)?.tables {
+        if let ir::rq::RelationKind::ExternRef(ir::pl::TableExternRef::LocalTable(ident)) =
+            table.relation.kind
+        {
+            tables.push(ident);
+        }
+    }
+    tables.sort_by_key(|t| t.to_string());
+    tables.dedup();
+    Ok(tables)
+}
+
+/// Compile a PRQL string into a JSON object, for bindings that must not fail,
+/// such as the playground.
+///
+/// `options_json` is an object with the optional fields `target` (e.g.
+/// `"sql.postgres"`) and `signature_comment`. The result is either
+/// `{"sql": "..."}` or `{"errors": [{"message": "...", "span": [start, end],
+/// "hint": "..."}]}`, where `span` and `hint` may be null. Invalid options and
+/// panics of the compiler are reported as errors too.
+///
+/// ```
+/// let json = prqlc::compile_json("from employees", r#"{"signature_comment": false}"#);
+/// assert_eq!(json, r#"{"sql":"SELECT\n  *\nFROM\n  employees\n"}"#);
+/// ```
+pub fn compile_json(prql: &str, options_json: &str) -> String {
+    let result = std::panic::catch_unwind(|| {
+        let options: JsonOptions = serde_json::from_str(options_json)
+            .map_err(|e| Error::new_simple(format!("invalid options: {e}")))?;
+        compile(prql, &Options::try_from(options)?)
+    })
+    .unwrap_or_else(|panic| {
+        let details = (panic.downcast_ref::<&str>().map(|s| s.to_string()))
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(Error::new_assert(details).into())
+    });
+
+    let output = match result {
+        Ok(sql) => JsonOutput::Sql { sql },
+        Err(errors) => JsonOutput::Errors {
+            errors: errors.inner.into_iter().map(JsonError::from).collect(),
+        },
+    };
+    serde_json::to_string(&output).unwrap()
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct JsonOptions {
+    target: Option<String>,
+    signature_comment: bool,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions {
+            target: None,
+            signature_comment: true,
+        }
+    }
+}
+
+impl TryFrom<JsonOptions> for Options {
+    type Error = Error;
+
+    fn try_from(options: JsonOptions) -> Result<Self> {
+        let target = options
+            .target
+            .as_deref()
+            .map(Target::from_str)
+            .transpose()?;
+
+        Ok(Options::default()
+            .with_target(target.unwrap_or_default())
+            .with_signature_comment(options.signature_comment)
+            .with_display(DisplayOptions::Plain))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum JsonOutput {
+    Sql { sql: String },
+    Errors { errors: Vec<JsonError> },
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    message: String,
+    span: Option<[usize; 2]>,
+    hint: Option<String>,
+}
+
+impl From<ErrorMessage> for JsonError {
+    fn from(e: ErrorMessage) -> Self {
+        JsonError {
+            message: e.reason,
+            span: e.span.map(|s| [s.start, s.end]),
+            hint: (!e.hints.is_empty()).then(|| e.hints.join("\n")),
+        }
+    }
+}
+
+/// Compiles sources, and returns the result along with the warnings of the
+/// compilation.
+fn compile_with<T>(
+    sources: SourceTree,
+    options: &Options,
+    to_sql: impl FnOnce(ir::rq::RelationalQuery, &Options) -> Result<T>,
+) -> Result<(T, Vec<ErrorMessage>), ErrorMessages> {
+    let comments = if options.transform_comments {
+        parser::collect_comments(&sources)
+    } else {
+        Default::default()
+    };
+    let lints: [(LintLevel, semantic::lint::Lint); 3] = [
+        (options.unsorted_take, semantic::lint::find_unsorted_takes),
+        (
+            options.unsafe_interpolations,
+            semantic::lint::find_unsafe_interpolations,
+        ),
+        (options.cartesian_join, semantic::lint::find_cartesian_joins),
+    ];
+    let lint_sources = (lints.iter())
+        .any(|(level, _)| *level == LintLevel::Warn)
+        .then(|| sources.clone());
+    let mut warnings = Vec::new();
+    let compiled = compile_ast_with(sources, options, |ast| {
+        let rq = semantic::resolve_with_table_columns(ast, &options.table_columns)
+            .and_then(|mut root_mod| {
+                for (level, find) in lints {
+                    if level != LintLevel::Allow {
+                        report_lints(find(&root_mod), level, &mut warnings)?;
+                    }
+                }
+                root_mod.transform_comments = comments;
+                Ok(semantic::lower(root_mod, &[], None)?)
+            })
+            .map_err(|e| e.with_source(ErrorSource::NameResolver))?;
+        Ok(to_sql(rq, options).map_err(|e| e.with_source(ErrorSource::SQL))?)
+    })?;
+
+    let mut warnings = ErrorMessages::from(Errors(warnings));
+    if let Some(sources) = &lint_sources {
+        warnings = warnings.composed(sources);
+    }
+    Ok((compiled, displayed(warnings, options).inner))
+}
+
+/// Fails with the lints when denied, or adds them to the warnings when warned
+/// about.
+fn report_lints(
+    lints: Vec<Error>,
+    level: LintLevel,
+    warnings: &mut Vec<Error>,
+) -> Result<(), Errors> {
+    match level {
+        LintLevel::Deny if !lints.is_empty() => Err(Errors(lints)),
+        LintLevel::Warn => {
+            warnings.extend(lints.into_iter().map(|e| Error {
+                kind: MessageKind::Warning,
+                ..e
+            }));
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn log_warnings<T>((compiled, warnings): (T, Vec<ErrorMessage>)) -> T {
+    for warning in warnings {
+        log::warn!("{}", warning.display.unwrap_or(warning.reason));
+    }
+    compiled
+}
+
+fn compile_ast_with<T>(
+    sources: SourceTree,
+    options: &Options,
+    compile: impl FnOnce(pr::ModuleDef) -> Result<T, Errors>,
+) -> Result<T, ErrorMessages> {
     Ok(&sources)
         .and_then(parser::parse)
-        .and_then(|ast| {
-            semantic::resolve_and_lower(ast, &[], None)
-                .map_err(|e| e.with_source(ErrorSource::NameResolver).into())
-        })
-        .and_then(|rq| {
-            sql::compile(rq, options).map_err(|e| e.with_source(ErrorSource::SQL).into())
-        })
-        .map_err(|e| {
-            let error_messages = ErrorMessages::from(e).composed(&sources);
-            match options.display {
-                DisplayOptions::AnsiColor => error_messages,
-                DisplayOptions::Plain => ErrorMessages {
-                    inner: error_messages
-                        .inner
-                        .into_iter()
-                        .map(|e| ErrorMessage {
-                            display: e.display.map(|s| strip_str(&s).to_string()),
-                            ..e
-                        })
-                        .collect(),
-                },
-            }
-        })
+        .and_then(compile)
+        .map_err(|e| displayed(ErrorMessages::from(e).composed(&sources), options))
+}
+
+/// Strips colors from the displays of messages, unless they are to be shown
+/// in color.
+fn displayed(messages: ErrorMessages, options: &Options) -> ErrorMessages {
+    match options.display {
+        DisplayOptions::AnsiColor => messages,
+        DisplayOptions::Plain => ErrorMessages {
+            inner: (messages.inner.into_iter())
+                .map(|e| ErrorMessage {
+                    display: e.display.map(|s| strip_str(&s).to_string()),
+                    ..e
+                })
+                .collect(),
+        },
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -281,6 +732,20 @@ pub struct Options {
     /// Target and dialect to compile to.
     pub target: Target,
 
+    /// Version of the database to compile to, such as `8.0` for MySQL 8.0.
+    /// Features that older versions lack are emulated, such as CTEs with
+    /// sub-queries, or are reported as errors.
+    ///
+    /// Defaults to `None`, which assumes the newest version.
+    pub target_version: Option<sql::DialectVersion>,
+
+    /// Columns of tables of the database, by the name of the table. Queries
+    /// can then reference all columns of these tables, such as with column
+    /// patterns of `select`, as in `select (column_names.starts_with "amt_")`.
+    ///
+    /// Defaults to no tables, whose columns are inferred from their references.
+    pub table_columns: HashMap<String, Vec<String>>,
+
     /// Emits the compiler signature as a comment after generated SQL
     ///
     /// Defaults to true.
@@ -301,6 +766,110 @@ pub struct Options {
     /// - Strip colors from the output (possibly also with a library such as
     ///   `anstream`).
     pub display: DisplayOptions,
+
+    /// Number of spaces used for each level of indentation when `format` is
+    /// enabled.
+    ///
+    /// Defaults to 2.
+    pub indent: u8,
+
+    /// Rejects queries whose `prql target` conflicts with `target`, instead of
+    /// compiling them to `target`.
+    ///
+    /// Defaults to false.
+    pub strict: bool,
+
+    /// Simplifies boolean predicates by pushing `not` inwards, following de
+    /// Morgan's laws, and removing double negations.
+    ///
+    /// Defaults to false.
+    pub simplify_predicates: bool,
+
+    /// Pushes conditions of filters that follow a join, and reference columns
+    /// of only one side of it, into that side of the join, for databases
+    /// that don't do so themselves.
+    ///
+    /// Defaults to false.
+    pub push_down_predicates: bool,
+
+    /// Emits the columns that both `group` groups by and its `aggregate` lists
+    /// where `aggregate` lists them, rather than first, along with the other
+    /// grouped columns. Either way, each of them is emitted once.
+    ///
+    /// Defaults to false.
+    pub interleave_group_columns: bool,
+
+    /// When compiling with [compile_parameterized], binds the `LIMIT` and
+    /// `OFFSET` of `take` to placeholders too, rather than inlining them.
+    ///
+    /// Defaults to false.
+    pub bind_take: bool,
+
+    /// Inlines tables that are referenced only once as sub-queries, instead
+    /// of defining them as CTEs. Some query planners optimize sub-queries
+    /// better, but CTEs are often easier to read.
+    ///
+    /// Defaults to false.
+    pub inline_ctes: bool,
+
+    /// Inlines all tables as sub-queries, and does not emit `WITH`, for
+    /// databases that do not support CTEs, such as MySQL before 8.0. Tables
+    /// that are referenced more than once are computed once for each
+    /// reference, which is logged as a warning. Recursive CTEs of `loop`
+    /// are still emitted.
+    ///
+    /// Defaults to false.
+    pub prefer_subqueries: bool,
+
+    /// Maximum number of queries that are nested within each other, for
+    /// databases whose parsers reject deeply nested SQL. A query that reads
+    /// from another query, be it a sub-query or a CTE, is nested one level
+    /// deeper. Queries that exceed it are reported as errors.
+    ///
+    /// Defaults to `None`, which does not limit the depth.
+    pub max_subquery_depth: Option<usize>,
+
+    /// Emits comments that precede transforms in the PRQL source as `--`
+    /// comments above the SQL clauses that the transforms compile to. Comments
+    /// of transforms that are merged into another clause are emitted above
+    /// that clause.
+    ///
+    /// Defaults to false.
+    pub transform_comments: bool,
+
+    /// Names each column of the final `SELECT` that is computed by an
+    /// expression, so the columns of the result have predictable names rather
+    /// than ones chosen by the database. Columns named in PRQL are aliased
+    /// with their names, and the others with a generated name, such as
+    /// `_expr_0`. Columns that are passed through keep their names.
+    ///
+    /// Defaults to false.
+    pub alias_output_columns: bool,
+
+    /// When to quote identifiers, using the quote character of the target
+    /// dialect.
+    ///
+    /// Defaults to [QuotingPolicy::WhenNeeded].
+    pub quoting: QuotingPolicy,
+
+    /// How to report `take` transforms that are not preceded by a `sort`, and
+    /// so return rows in an order that is up to the database.
+    ///
+    /// Defaults to [LintLevel::Allow].
+    pub unsorted_take: LintLevel,
+
+    /// How to report interpolations of s-strings that are neither a column
+    /// nor a number, such as `s"{a + b}"`, for reviewing queries written by
+    /// untrusted users for SQL injection.
+    ///
+    /// Defaults to [LintLevel::Allow].
+    pub unsafe_interpolations: LintLevel,
+
+    /// How to report joins whose condition is `true`, which return every
+    /// combination of the rows of both relations.
+    ///
+    /// Defaults to [LintLevel::Allow].
+    pub cartesian_join: LintLevel,
 }
 
 impl Default for Options {
@@ -308,9 +877,26 @@ impl Default for Options {
         Self {
             format: true,
             target: Target::Sql(None),
+            target_version: None,
+            table_columns: HashMap::new(),
             signature_comment: true,
             color: true,
             display: DisplayOptions::AnsiColor,
+            indent: 2,
+            strict: false,
+            simplify_predicates: false,
+            push_down_predicates: false,
+            interleave_group_columns: false,
+            bind_take: false,
+            inline_ctes: false,
+            prefer_subqueries: false,
+            max_subquery_depth: None,
+            transform_comments: false,
+            alias_output_columns: false,
+            quoting: QuotingPolicy::WhenNeeded,
+            unsorted_take: LintLevel::Allow,
+            unsafe_interpolations: LintLevel::Allow,
+            cartesian_join: LintLevel::Allow,
         }
     }
 }
@@ -339,6 +925,16 @@ impl Options {
         self
     }
 
+    pub fn with_target_version(mut self, target_version: Option<sql::DialectVersion>) -> Self {
+        self.target_version = target_version;
+        self
+    }
+
+    pub fn with_table_columns(mut self, table_columns: HashMap<String, Vec<String>>) -> Self {
+        self.table_columns = table_columns;
+        self
+    }
+
     #[deprecated(note = "`color` is replaced by `display`; see `Options` docs for more details")]
     pub fn with_color(mut self, color: bool) -> Self {
         self.color = color;
@@ -349,6 +945,81 @@ impl Options {
         self.display = display;
         self
     }
+
+    pub fn with_indent(mut self, indent: u8) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn with_simplify_predicates(mut self, simplify_predicates: bool) -> Self {
+        self.simplify_predicates = simplify_predicates;
+        self
+    }
+
+    pub fn with_push_down_predicates(mut self, push_down_predicates: bool) -> Self {
+        self.push_down_predicates = push_down_predicates;
+        self
+    }
+
+    pub fn with_interleave_group_columns(mut self, interleave_group_columns: bool) -> Self {
+        self.interleave_group_columns = interleave_group_columns;
+        self
+    }
+
+    pub fn with_bind_take(mut self, bind_take: bool) -> Self {
+        self.bind_take = bind_take;
+        self
+    }
+
+    pub fn with_inline_ctes(mut self, inline_ctes: bool) -> Self {
+        self.inline_ctes = inline_ctes;
+        self
+    }
+
+    pub fn with_prefer_subqueries(mut self, prefer_subqueries: bool) -> Self {
+        self.prefer_subqueries = prefer_subqueries;
+        self
+    }
+
+    pub fn with_max_subquery_depth(mut self, max_subquery_depth: Option<usize>) -> Self {
+        self.max_subquery_depth = max_subquery_depth;
+        self
+    }
+
+    pub fn with_transform_comments(mut self, transform_comments: bool) -> Self {
+        self.transform_comments = transform_comments;
+        self
+    }
+
+    pub fn with_alias_output_columns(mut self, alias_output_columns: bool) -> Self {
+        self.alias_output_columns = alias_output_columns;
+        self
+    }
+
+    pub fn with_quoting(mut self, quoting: QuotingPolicy) -> Self {
+        self.quoting = quoting;
+        self
+    }
+
+    pub fn with_unsorted_take(mut self, unsorted_take: LintLevel) -> Self {
+        self.unsorted_take = unsorted_take;
+        self
+    }
+
+    pub fn with_unsafe_interpolations(mut self, unsafe_interpolations: LintLevel) -> Self {
+        self.unsafe_interpolations = unsafe_interpolations;
+        self
+    }
+
+    pub fn with_cartesian_join(mut self, cartesian_join: LintLevel) -> Self {
+        self.cartesian_join = cartesian_join;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, strum::EnumString)]
@@ -361,6 +1032,39 @@ pub enum DisplayOptions {
     AnsiColor,
 }
 
+/// When identifiers in generated SQL are quoted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, strum::EnumString)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum QuotingPolicy {
+    /// Quote all identifiers.
+    Always,
+    /// Never quote identifiers, even if they are keywords or contain
+    /// characters that require quoting.
+    Never,
+    /// Quote identifiers that are keywords of SQL or contain characters
+    /// other than lowercase letters, digits and underscores.
+    #[default]
+    WhenNeeded,
+}
+
+/// How a check of a query that compiles, but is likely to be a mistake, is
+/// reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, strum::EnumString)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum LintLevel {
+    /// Do not run the check.
+    #[default]
+    Allow,
+    /// Report a warning, and compile the query anyway.
+    Warn,
+    /// Fail to compile the query.
+    Deny,
+}
+
 #[doc = include_str!("../README.md")]
 #[cfg(doctest)]
 pub struct ReadmeDoctests;
@@ -409,11 +1113,54 @@ pub fn rq_to_sql(rq: ir::rq::RelationalQuery, options: &Options) -> Result<Strin
     sql::compile(rq, options).map_err(|e| e.with_source(ErrorSource::SQL).into())
 }
 
+/// Translate a SQL query into PRQL, for migrating existing queries.
+///
+/// Only a subset of SQL is translated: a single `SELECT` with inner and outer
+/// joins, `WHERE`, `GROUP BY` with aggregate functions, `ORDER BY` and
+/// `LIMIT`. Each construct that can't be translated is reported as an error.
+///
+/// ```
+/// use prqlc::{sql::Dialect, sql_to_prql};
+///
+/// let sql = "SELECT name, age FROM employees WHERE age > 30 ORDER BY name LIMIT 10";
+/// let prql = sql_to_prql(sql, Dialect::Generic).unwrap();
+/// assert_eq!(
+///     prql,
+///     "from employees\nfilter age > 30\nselect {name, age}\nsort {name}\ntake 10\n"
+/// );
+/// ```
+pub fn sql_to_prql(sql: &str, dialect: sql::Dialect) -> Result<String, ErrorMessages> {
+    let pl = sql::sql_to_pl(sql, dialect)?;
+    pl_to_prql(&pl)
+}
+
 /// Generate PRQL code from PL AST
 pub fn pl_to_prql(pl: &pr::ModuleDef) -> Result<String, ErrorMessages> {
     Ok(codegen::WriteSource::write(&pl.stmts, codegen::WriteOpt::default()).unwrap())
 }
 
+/// Format PRQL code into a canonical layout
+///
+/// Each transform of a pipeline is written on its own line, including those of
+/// `let` relations and of the sub-pipelines of `group`, `window` and `loop`,
+/// which are indented by two spaces. Comments are not preserved.
+///
+/// ```
+/// let prql = "from employees | group {dept} (sort salary | take 1)";
+/// assert_eq!(
+///     prqlc::format(prql).unwrap(),
+///     "from employees\ngroup {dept} (\n  sort salary\n  take 1\n)\n"
+/// );
+/// ```
+pub fn format(prql: &str) -> Result<String, ErrorMessages> {
+    let pl = prql_to_pl(prql)?;
+    let opt = codegen::WriteOpt {
+        expand_pipelines: true,
+        ..codegen::WriteOpt::default()
+    };
+    Ok(codegen::WriteSource::write(&pl.stmts, opt).unwrap())
+}
+
 /// JSON serialization and deserialization functions
 pub mod json {
     use super::*;
@@ -525,6 +1272,52 @@ pub mod internal {
         Ok(fc)
     }
 
+    /// Resolve PL and return the main relation, with its transforms, types and
+    /// lineage
+    pub fn pl_to_resolved(pl: pr::ModuleDef) -> Result<ir::pl::Expr, ErrorMessages> {
+        let root_module = semantic::resolve(pl).map_err(ErrorMessages::from)?;
+
+        let (main, _) = root_module.find_main_rel(&[]).map_err(|(hint, span)| {
+            Error::new_simple("Missing main pipeline")
+                .with_code("E0001")
+                .with_hints(hint)
+                .with_span(span)
+        })?;
+        Ok(*main.clone().into_relation_var().unwrap())
+    }
+
+    /// Infer the name, type and nullability of each column of the main relation
+    pub fn pl_to_schema(pl: pr::ModuleDef) -> Result<Vec<(String, pr::Ty, bool)>, ErrorMessages> {
+        let root_module = semantic::resolve(pl).map_err(ErrorMessages::from)?;
+
+        let (main, _) = root_module.find_main_rel(&[]).map_err(|(hint, span)| {
+            Error::new_simple("Missing main pipeline")
+                .with_code("E0001")
+                .with_hints(hint)
+                .with_span(span)
+        })?;
+        let main = main.clone().into_relation_var().unwrap();
+
+        semantic::schema::infer_schema(&root_module, &main).map_err(ErrorMessages::from)
+    }
+
+    /// Infer the source columns of each column of the main relation
+    pub fn pl_to_column_lineage(
+        pl: pr::ModuleDef,
+    ) -> Result<semantic::column_lineage::ColumnLineage, ErrorMessages> {
+        let root_module = semantic::resolve(pl).map_err(ErrorMessages::from)?;
+
+        let (main, _) = root_module.find_main_rel(&[]).map_err(|(hint, span)| {
+            Error::new_simple("Missing main pipeline")
+                .with_code("E0001")
+                .with_hints(hint)
+                .with_span(span)
+        })?;
+        let main = main.clone().into_relation_var().unwrap();
+
+        semantic::column_lineage::column_lineage(&root_module, &main).map_err(ErrorMessages::from)
+    }
+
     pub mod json {
         use super::*;
 
@@ -535,6 +1328,16 @@ pub mod internal {
             serde_json::to_string(fc).map_err(convert_json_err)
         }
 
+        /// JSON serialization of the resolved main relation
+        pub fn from_resolved(expr: &ir::pl::Expr) -> Result<String, ErrorMessages> {
+            serde_json::to_string(expr).map_err(convert_json_err)
+        }
+
+        /// JSON deserialization of the resolved main relation
+        pub fn to_resolved(json: &str) -> Result<ir::pl::Expr, ErrorMessages> {
+            serde_json::from_str(json).map_err(convert_json_err)
+        }
+
         fn convert_json_err(err: serde_json::Error) -> ErrorMessages {
             ErrorMessages::from(Error::new_simple(err.to_string()))
         }
@@ -1,5 +1,6 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::path::PathBuf;
-use std::{collections::HashMap, path::Path};
 
 use itertools::Itertools;
 
@@ -39,7 +40,11 @@ pub fn parse(file_tree: &SourceTree) -> Result<pr::ModuleDef, Errors> {
 
         match parse_source(source_file.content, id) {
             Ok(stmts) => {
-                insert_stmts_at_path(&mut root, source_file.module_path, stmts);
+                let path = source_file.module_path;
+                let res = insert_stmts_at_path(&mut root, path, stmts, file_tree);
+                if let Err(err) = res {
+                    errors.push(err);
+                }
             }
             Err(errs) => errors.extend(errs),
         }
@@ -77,6 +82,49 @@ pub(crate) fn parse_source(source: &str, source_id: u16) -> Result<Vec<pr::Stmt>
     }
 }
 
+/// Collects the comments that precede transforms, by source id and the
+/// position of the token after them. Only comments on lines of their own are
+/// collected. Consecutive comment lines are joined with new lines, and a blank
+/// line discards the comments above it.
+pub(crate) fn collect_comments(file_tree: &SourceTree) -> BTreeMap<(u16, usize), String> {
+    let mut comments = BTreeMap::new();
+    for (source_id, path) in &file_tree.source_ids {
+        let Some(source) = file_tree.sources.get(path) else {
+            continue;
+        };
+        let (tokens, _) = prqlc_parser::lexer::lex_source_recovery(source, *source_id);
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut line_start = true;
+        for token in tokens.unwrap_or_default() {
+            match token.kind {
+                lr::TokenKind::Start => continue,
+                lr::TokenKind::NewLine => {
+                    if line_start {
+                        lines.clear();
+                    }
+                    line_start = true;
+                    continue;
+                }
+                lr::TokenKind::Comment(comment) if line_start => {
+                    lines.push(comment.trim().to_string());
+                }
+                lr::TokenKind::Comment(_)
+                | lr::TokenKind::LineWrap(_)
+                | lr::TokenKind::Control('|') => {}
+                _ => {
+                    if !lines.is_empty() {
+                        comments.insert((*source_id, token.span.start), lines.join("\n"));
+                        lines.clear();
+                    }
+                }
+            }
+            line_start = false;
+        }
+    }
+    comments
+}
+
 struct SourceFile<'a> {
     file_path: &'a Path,
     module_path: Vec<String>,
@@ -150,10 +198,14 @@ fn linearize_tree(tree: &SourceTree) -> Result<Vec<SourceFile>> {
     Ok(sources)
 }
 
-fn insert_stmts_at_path(module: &mut pr::ModuleDef, mut path: Vec<String>, stmts: Vec<pr::Stmt>) {
+fn insert_stmts_at_path(
+    module: &mut pr::ModuleDef,
+    mut path: Vec<String>,
+    stmts: Vec<pr::Stmt>,
+    tree: &SourceTree,
+) -> Result<()> {
     if path.is_empty() {
-        module.stmts.extend(stmts);
-        return;
+        return merge_stmts(module, stmts, tree);
     }
 
     let step = path.remove(0);
@@ -173,7 +225,61 @@ fn insert_stmts_at_path(module: &mut pr::ModuleDef, mut path: Vec<String>, stmts
     };
     let submodule = submodule.kind.as_module_def_mut().unwrap();
 
-    insert_stmts_at_path(submodule, path, stmts);
+    insert_stmts_at_path(submodule, path, stmts, tree)
+}
+
+/// Appends statements to a module. Definitions of a module that is already
+/// defined are merged into the existing one, so a module can be declared both
+/// by a file and within another file.
+///
+/// Declaring the same name in more than one file is an error; within a single
+/// file, the later declaration is used.
+fn merge_stmts(module: &mut pr::ModuleDef, stmts: Vec<pr::Stmt>, tree: &SourceTree) -> Result<()> {
+    for stmt in stmts {
+        let existing = declared_name(&stmt)
+            .and_then(|name| (module.stmts.iter_mut()).find(|s| declared_name(s) == Some(name)));
+
+        let Some(existing) = existing else {
+            module.stmts.push(stmt);
+            continue;
+        };
+
+        if existing.kind.is_module_def() && stmt.kind.is_module_def() {
+            let existing = existing.kind.as_module_def_mut().unwrap();
+            let new = stmt.kind.into_module_def().unwrap();
+            merge_stmts(existing, new.stmts, tree)?;
+            continue;
+        }
+
+        let existing_source = existing.span.map(|s| s.source_id);
+        if existing_source != stmt.span.map(|s| s.source_id) {
+            let name = declared_name(&stmt).unwrap();
+            let file = (existing_source.and_then(|id| tree.get_path(id)))
+                .filter(|p| !p.as_os_str().is_empty())
+                .map_or("the root file".to_string(), |p| {
+                    format!("`{}`", p.display())
+                });
+
+            return Err(Error::new_simple(format!(
+                "`{name}` is declared in more than one source file"
+            ))
+            .push_hint(format!("`{name}` is also declared in {file}"))
+            .with_span(stmt.span));
+        }
+
+        module.stmts.push(stmt);
+    }
+    Ok(())
+}
+
+/// Name of the declaration, or `None` for statements that don't declare a name.
+fn declared_name(stmt: &pr::Stmt) -> Option<&str> {
+    match &stmt.kind {
+        pr::StmtKind::VarDef(def) if def.kind == pr::VarDefKind::Let => Some(&def.name),
+        pr::StmtKind::TypeDef(def) => Some(&def.name),
+        pr::StmtKind::ModuleDef(def) => Some(&def.name),
+        _ => None,
+    }
 }
 
 pub(crate) fn is_mod_def_for(stmt: &pr::Stmt, name: &str) -> bool {
@@ -0,0 +1,552 @@
+//! Translation of RQ into Python code of a Polars lazy frame, an experimental
+//! target besides SQL.
+//!
+//! Each transform of a pipeline is a method call on the frame, such as
+//! `.filter` for `filter` and `.group_by().agg()` for `aggregate` within
+//! `group`. Tables are referenced as variables of lazy frames of the same
+//! name. Transforms and functions without a translation, such as `loop` and
+//! `append`, are reported as errors, all at once.
+
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+
+use crate::ir::generic::{SortDirection, WindowFrame};
+use crate::ir::pl::{JoinSide, Literal, TableExternRef};
+use crate::ir::rq::{self, CId, RelationColumn, RelationKind, RelationalQuery, TId, Transform};
+use crate::{Error, Errors, WithErrorInfo};
+
+/// Translates a query into a Python expression of a Polars lazy frame.
+pub(crate) fn compile(rq: RelationalQuery) -> Result<String, Errors> {
+    let mut translator = Translator {
+        tables: rq.tables.into_iter().map(|t| (t.id, t.relation)).collect(),
+        ..Translator::default()
+    };
+    let frame = translator.translate_relation(rq.relation);
+    if !translator.errors.is_empty() {
+        return Err(Errors(translator.errors));
+    }
+
+    let calls = frame.calls.iter().map(|call| format!("  {call}\n"));
+    Ok(format!(
+        "(\n  {}\n{})\n",
+        frame.source,
+        calls.collect::<String>()
+    ))
+}
+
+/// A lazy frame, as its source and the method calls applied to it.
+struct Frame {
+    source: String,
+    calls: Vec<String>,
+
+    /// Names of the columns of the frame that are known.
+    names: HashSet<String>,
+}
+
+impl Frame {
+    fn new(source: String) -> Self {
+        Frame {
+            source,
+            calls: Vec::new(),
+            names: HashSet::new(),
+        }
+    }
+
+    fn inline(&self) -> String {
+        format!("{}{}", self.source, self.calls.concat())
+    }
+}
+
+/// Translates RQ into Polars, collecting an error for each construct that
+/// can't be translated, so they can all be reported at once.
+#[derive(Default)]
+struct Translator {
+    tables: HashMap<TId, rq::Relation>,
+
+    /// Names of the columns in the frames.
+    names: HashMap<CId, String>,
+
+    /// Expressions of the computes of the next `aggregate`.
+    aggregations: HashMap<CId, String>,
+
+    errors: Vec<Error>,
+}
+
+fn unsupported(construct: impl std::fmt::Display) -> Error {
+    Error::new_simple(format!("cannot compile {construct} to Polars"))
+}
+
+impl Translator {
+    fn unsupported(&mut self, construct: impl std::fmt::Display) {
+        self.errors.push(unsupported(construct));
+    }
+
+    fn translate_relation(&mut self, relation: rq::Relation) -> Frame {
+        match relation.kind {
+            RelationKind::ExternRef(TableExternRef::LocalTable(ident)) => {
+                Frame::new(ident.to_string())
+            }
+            RelationKind::Pipeline(transforms) => {
+                self.translate_pipeline(transforms, &relation.columns)
+            }
+            RelationKind::Literal(literal) => {
+                let columns = (literal.columns.iter().enumerate())
+                    .map(|(i, name)| {
+                        let values = (literal.rows.iter())
+                            .map(|row| self.translate_literal(&row[i], None))
+                            .join(", ");
+                        format!("{}: [{values}]", quote(name))
+                    })
+                    .join(", ");
+                Frame::new(format!("pl.LazyFrame({{{columns}}})"))
+            }
+            RelationKind::BuiltInFunction { name, args } => {
+                let scan = match name.as_str() {
+                    "std.read_csv" => "pl.scan_csv",
+                    "std.read_parquet" => "pl.scan_parquet",
+                    _ => {
+                        self.unsupported(format!("`{name}`"));
+                        return Frame::new(String::new());
+                    }
+                };
+                let args = (args.into_iter())
+                    .map(|arg| match arg.kind {
+                        rq::ExprKind::Literal(literal) => {
+                            self.translate_literal(&literal, arg.span)
+                        }
+                        _ => self.unsupported_expr("arguments that are not literals", arg.span),
+                    })
+                    .join(", ");
+                Frame::new(format!("{scan}({args})"))
+            }
+            RelationKind::ExternRef(_) => {
+                self.unsupported("references to parameters");
+                Frame::new(String::new())
+            }
+            RelationKind::SString(_) => {
+                self.unsupported("s-strings");
+                Frame::new(String::new())
+            }
+        }
+    }
+
+    fn translate_pipeline(
+        &mut self,
+        transforms: Vec<Transform>,
+        columns: &[RelationColumn],
+    ) -> Frame {
+        // computes that are selected at the end are named after their columns
+        if let Some(Transform::Select(selected)) = transforms.last() {
+            for (cid, column) in selected.iter().zip(columns) {
+                if let RelationColumn::Single(Some(name)) = column {
+                    self.names.entry(*cid).or_insert_with(|| name.clone());
+                }
+            }
+        }
+
+        let mut frame = Frame::new(String::new());
+        let last = transforms.len().saturating_sub(1);
+        for (index, transform) in transforms.into_iter().enumerate() {
+            match transform {
+                Transform::From(table) => frame = self.translate_table_ref(table),
+                Transform::Compute(compute) => self.translate_compute(compute, &mut frame),
+                // the frame has all columns of a wildcard already
+                Transform::Select(cids) if cids.iter().any(|cid| self.is_wildcard(cid)) => {}
+                Transform::Select(cids) => {
+                    let exprs = (cids.iter().enumerate())
+                        .map(|(i, cid)| {
+                            let col = self.col(cid);
+                            match columns.get(i) {
+                                Some(RelationColumn::Single(Some(name)))
+                                    if index == last && name != &self.names[cid] =>
+                                {
+                                    format!("{col}.alias({})", quote(name))
+                                }
+                                _ => col,
+                            }
+                        })
+                        .join(", ");
+                    let select = format!(".select([{exprs}])");
+                    if frame.calls.last() != Some(&select) {
+                        frame.calls.push(select);
+                    }
+                }
+                Transform::Filter(expr) => {
+                    let expr = self.translate_expr(expr);
+                    frame.calls.push(format!(".filter({expr})"));
+                }
+                Transform::Aggregate { partition, compute } => {
+                    let aggregations = (compute.iter())
+                        .map(|cid| {
+                            let expr = self.aggregations.remove(cid).unwrap_or_default();
+                            format!("{expr}.alias({})", quote(&self.names[cid]))
+                        })
+                        .join(", ");
+                    if partition.is_empty() {
+                        frame.calls.push(format!(".select([{aggregations}])"));
+                    } else {
+                        let by = partition.iter().map(|cid| self.col(cid)).join(", ");
+                        frame
+                            .calls
+                            .push(format!(".group_by([{by}], maintain_order=True)"));
+                        frame.calls.push(format!(".agg([{aggregations}])"));
+                    }
+                    frame.names = (partition.iter().chain(&compute))
+                        .map(|cid| self.names[cid].clone())
+                        .collect();
+                }
+                Transform::Sort(sort) => {
+                    if !sort.is_empty() {
+                        frame.calls.push(self.translate_sort(&sort));
+                    }
+                }
+                Transform::Take(take) => self.translate_take(take, &mut frame),
+                Transform::Join { side, with, filter } => {
+                    self.translate_join(side, with, filter, &mut frame)
+                }
+                Transform::Append(_) => self.unsupported("`append`"),
+                Transform::Loop(_) => self.unsupported("`loop`"),
+                Transform::Comment(_) => {}
+            }
+        }
+        frame
+    }
+
+    fn translate_table_ref(&mut self, table: rq::TableRef) -> Frame {
+        let relation = self.tables[&table.source].clone();
+        let mut frame = self.translate_relation(relation);
+        for (column, cid) in table.columns {
+            let name = match column {
+                RelationColumn::Single(Some(name)) => name,
+                RelationColumn::Single(None) => continue,
+                RelationColumn::Wildcard => "*".to_string(),
+            };
+            frame.names.insert(name.clone());
+            self.names.insert(cid, name);
+        }
+        frame
+    }
+
+    fn translate_compute(&mut self, compute: rq::Compute, frame: &mut Frame) {
+        let name = (self.names.entry(compute.id))
+            .or_insert_with(|| format!("_expr_{}", compute.id.get()))
+            .clone();
+        let mut expr = self.translate_expr(compute.expr);
+
+        if compute.is_aggregation {
+            self.aggregations.insert(compute.id, expr);
+            return;
+        }
+        if let Some(window) = compute.window {
+            if !window.sort.is_empty() || window.frame != WindowFrame::default() {
+                self.unsupported("window functions with `sort` or a frame");
+            }
+            if !window.partition.is_empty() {
+                let by = window.partition.iter().map(|cid| self.col(cid)).join(", ");
+                expr = format!("{expr}.over([{by}])");
+            }
+        }
+        frame
+            .calls
+            .push(format!(".with_columns([{expr}.alias({})])", quote(&name)));
+        frame.names.insert(name);
+    }
+
+    fn translate_sort(&mut self, sort: &[crate::ir::generic::ColumnSort<CId>]) -> String {
+        let by = sort.iter().map(|s| self.col(&s.column)).join(", ");
+        if sort.iter().all(|s| s.direction == SortDirection::Asc) {
+            format!(".sort([{by}])")
+        } else {
+            let descending = (sort.iter())
+                .map(|s| match s.direction {
+                    SortDirection::Asc => "False",
+                    SortDirection::Desc => "True",
+                })
+                .join(", ");
+            format!(".sort([{by}], descending=[{descending}])")
+        }
+    }
+
+    fn translate_take(&mut self, take: rq::Take, frame: &mut Frame) {
+        if !take.partition.is_empty() {
+            self.unsupported("`take` within `group`");
+            return;
+        }
+        let mut bound = |expr: Option<rq::Expr>| match expr.map(|e| e.kind) {
+            None => Some(None),
+            Some(rq::ExprKind::Literal(Literal::Integer(i))) => Some(Some(i)),
+            Some(_) => {
+                self.unsupported("`take` of a range that is not of integer literals");
+                None
+            }
+        };
+        let (Some(start), Some(end)) = (bound(take.range.start), bound(take.range.end)) else {
+            return;
+        };
+
+        let offset = start.unwrap_or(1).max(1) - 1;
+        frame.calls.push(match end {
+            Some(end) if offset == 0 => format!(".limit({})", end.max(0)),
+            Some(end) => format!(".slice({offset}, {})", (end - offset).max(0)),
+            None => format!(".slice({offset})"),
+        });
+    }
+
+    fn translate_join(
+        &mut self,
+        side: JoinSide,
+        with: rq::TableRef,
+        filter: rq::Expr,
+        frame: &mut Frame,
+    ) {
+        let right_cids: HashSet<_> = with.columns.iter().map(|(_, cid)| *cid).collect();
+        let left_names = std::mem::take(&mut frame.names);
+        let mut right = self.translate_table_ref(with);
+
+        let mut left_on = Vec::new();
+        let mut right_on = Vec::new();
+        for condition in conjunctions(filter) {
+            let span = condition.span;
+            let key = match condition.kind {
+                rq::ExprKind::Operator { name, args } if name == "std.eq" => match &args[..] {
+                    [rq::Expr {
+                        kind: rq::ExprKind::ColumnRef(a),
+                        ..
+                    }, rq::Expr {
+                        kind: rq::ExprKind::ColumnRef(b),
+                        ..
+                    }] => match (right_cids.contains(a), right_cids.contains(b)) {
+                        (false, true) => Some((*a, *b)),
+                        (true, false) => Some((*b, *a)),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                _ => None,
+            };
+            let Some((left, right)) = key else {
+                self.errors.push(
+                    unsupported("join conditions other than equality of columns")
+                        .push_hint("join on columns of both sides, as in `join b (==id)`")
+                        .with_span(span),
+                );
+                continue;
+            };
+            left_on.push(quote(&self.names[&left]));
+            right_on.push(quote(&self.names[&right]));
+        }
+
+        // columns of both sides that share a name are suffixed on the right
+        for cid in &right_cids {
+            let name = &self.names[cid];
+            if name != "*" && left_names.contains(name) {
+                let suffixed = format!("{name}_right");
+                right.names.insert(suffixed.clone());
+                self.names.insert(*cid, suffixed);
+            }
+        }
+
+        let how = match side {
+            JoinSide::Inner => "inner",
+            JoinSide::Left => "left",
+            JoinSide::Right => "right",
+            JoinSide::Full => "full",
+        };
+        frame.calls.push(format!(
+            ".join({}, left_on=[{}], right_on=[{}], how=\"{how}\", coalesce=False)",
+            right.inline(),
+            left_on.join(", "),
+            right_on.join(", "),
+        ));
+        frame.names = left_names.union(&right.names).cloned().collect();
+    }
+
+    fn is_wildcard(&self, cid: &CId) -> bool {
+        self.names.get(cid).is_some_and(|name| name == "*")
+    }
+
+    fn col(&mut self, cid: &CId) -> String {
+        match self.names.get(cid).map(|n| n.as_str()) {
+            Some("*") => "pl.all()".to_string(),
+            Some(name) => format!("pl.col({})", quote(name)),
+            None => {
+                self.unsupported(format!("a reference to an unnamed column {cid:?}"));
+                String::new()
+            }
+        }
+    }
+
+    fn translate_expr(&mut self, expr: rq::Expr) -> String {
+        let span = expr.span;
+        match expr.kind {
+            rq::ExprKind::ColumnRef(cid) => self.col(&cid),
+            rq::ExprKind::Literal(literal) => {
+                format!("pl.lit({})", self.translate_literal(&literal, span))
+            }
+            rq::ExprKind::Operator { name, args } => self.translate_operator(name, args, span),
+            rq::ExprKind::Case(cases) => {
+                let mut when = "pl".to_string();
+                for case in cases {
+                    let value = self.translate_expr(case.value);
+                    if matches!(
+                        case.condition.kind,
+                        rq::ExprKind::Literal(Literal::Boolean(true))
+                    ) {
+                        return format!("{when}.otherwise({value})");
+                    }
+                    let condition = self.translate_expr(case.condition);
+                    when = format!("{when}.when({condition}).then({value})");
+                }
+                when
+            }
+            rq::ExprKind::SString(_) => self.unsupported_expr("s-strings", span),
+            rq::ExprKind::Param(_) => self.unsupported_expr("parameters", span),
+            rq::ExprKind::Array(_) => self.unsupported_expr("arrays", span),
+            rq::ExprKind::SubQuery(_) => self.unsupported_expr("sub-queries", span),
+        }
+    }
+
+    fn unsupported_expr(&mut self, construct: &str, span: Option<crate::Span>) -> String {
+        self.errors.push(unsupported(construct).with_span(span));
+        String::new()
+    }
+
+    fn translate_operator(
+        &mut self,
+        name: String,
+        args: Vec<rq::Expr>,
+        span: Option<crate::Span>,
+    ) -> String {
+        // comparisons with null are checks of nulls, as in SQL
+        if let (Some(check), [a, b]) = (null_check(&name), &args[..]) {
+            let operand = match (&a.kind, &b.kind) {
+                (_, rq::ExprKind::Literal(Literal::Null)) => Some(a),
+                (rq::ExprKind::Literal(Literal::Null), _) => Some(b),
+                _ => None,
+            };
+            if let Some(operand) = operand {
+                return format!("{}.{check}()", self.translate_expr(operand.clone()));
+            }
+        }
+
+        if let ("std.math.round", [digits, column]) = (name.as_str(), &args[..]) {
+            let digits = match &digits.kind {
+                rq::ExprKind::Literal(Literal::Integer(digits)) => digits.to_string(),
+                _ => self.unsupported_expr("`math.round` to digits that are not a literal", span),
+            };
+            return format!("{}.round({digits})", self.translate_expr(column.clone()));
+        }
+
+        let mut args = args.into_iter().map(|a| self.translate_expr(a));
+        let mut arg = || args.next().unwrap_or_default();
+
+        if let Some(op) = binary_operator(&name) {
+            let (a, b) = (arg(), arg());
+            return format!("({a} {op} {b})");
+        }
+        match name.as_str() {
+            "std.neg" => format!("(-{})", arg()),
+            "std.not" => format!("(~{})", arg()),
+            "std.coalesce" => {
+                let (a, b) = (arg(), arg());
+                format!("pl.coalesce([{a}, {b}])")
+            }
+            "std.count" => "pl.len()".to_string(),
+            "std.text.contains" => {
+                let (substr, column) = (arg(), arg());
+                format!("{column}.str.contains({substr}, literal=True)")
+            }
+            "std.text.starts_with" | "std.text.ends_with" => {
+                let (affix, column) = (arg(), arg());
+                format!("{column}.str.{}({affix})", &name["std.text.".len()..])
+            }
+            _ => match method(&name) {
+                Some(method) => format!("{}.{method}()", arg()),
+                None => self.unsupported_expr(&format!("function `{name}`"), span),
+            },
+        }
+    }
+
+    fn translate_literal(&mut self, literal: &Literal, span: Option<crate::Span>) -> String {
+        match literal {
+            Literal::Null => "None".to_string(),
+            Literal::Integer(i) => i.to_string(),
+            Literal::Float(f) => format!("{f:?}"),
+            Literal::Boolean(true) => "True".to_string(),
+            Literal::Boolean(false) => "False".to_string(),
+            Literal::String(s) | Literal::RawString(s) => quote(s),
+            _ => self.unsupported_expr(&format!("the literal `{literal}`"), span),
+        }
+    }
+}
+
+/// Splits a condition into the conditions that it is a conjunction of.
+fn conjunctions(expr: rq::Expr) -> Vec<rq::Expr> {
+    match expr.kind {
+        rq::ExprKind::Operator { name, args } if name == "std.and" => {
+            args.into_iter().flat_map(conjunctions).collect()
+        }
+        kind => vec![rq::Expr { kind, ..expr }],
+    }
+}
+
+fn null_check(name: &str) -> Option<&'static str> {
+    match name {
+        "std.eq" => Some("is_null"),
+        "std.ne" => Some("is_not_null"),
+        _ => None,
+    }
+}
+
+fn binary_operator(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "std.mul" => "*",
+        "std.div_f" => "/",
+        "std.div_i" => "//",
+        "std.mod" => "%",
+        "std.add" => "+",
+        "std.sub" => "-",
+        "std.eq" => "==",
+        "std.ne" => "!=",
+        "std.gt" => ">",
+        "std.lt" => "<",
+        "std.gte" => ">=",
+        "std.lte" => "<=",
+        "std.and" => "&",
+        "std.or" => "|",
+        _ => return None,
+    })
+}
+
+/// Functions that are a method of the expression of their only argument.
+fn method(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "std.sum" => "sum",
+        "std.average" => "mean",
+        "std.min" => "min",
+        "std.max" => "max",
+        "std.stddev" => "std",
+        "std.any" => "any",
+        "std.all" => "all",
+        "std.count_values" => "count",
+        "std.count_distinct" => "n_unique",
+        "std.math.abs" => "abs",
+        "std.math.floor" => "floor",
+        "std.math.ceil" => "ceil",
+        "std.math.sqrt" => "sqrt",
+        "std.math.exp" => "exp",
+        "std.text.lower" => "str.to_lowercase",
+        "std.text.upper" => "str.to_uppercase",
+        "std.text.trim" => "str.strip_chars",
+        "std.text.ltrim" => "str.strip_chars_start",
+        "std.text.rtrim" => "str.strip_chars_end",
+        "std.text.length" => "str.len_chars",
+        _ => return None,
+    })
+}
+
+/// Quotes a string as a Python string literal.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
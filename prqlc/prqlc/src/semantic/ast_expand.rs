@@ -1,11 +1,12 @@
 use std::collections::HashMap;
+use std::ops::ControlFlow;
 
 use itertools::Itertools;
 use prqlc_parser::error::WithErrorInfo;
 use prqlc_parser::generic;
 
 use crate::ir::decl;
-use crate::ir::pl::{self, new_binop};
+use crate::ir::pl::{self, new_binop, PlFold, PlVisitor};
 use crate::pr;
 use crate::semantic::{NS_THAT, NS_THIS};
 use crate::{Error, Result};
@@ -81,6 +82,11 @@ pub fn expand_expr(expr: pr::Expr) -> Result<pl::Expr> {
                 .map(|v| v.try_map(expand_expr))
                 .try_collect()?,
         ),
+        pr::ExprKind::Case(v) if v.is_empty() => {
+            return Err(Error::new_simple("`case` requires at least one branch")
+                .push_hint("add a branch, as in `case [x > 0 => \"positive\"]`")
+                .with_span(expr.span))
+        }
         pr::ExprKind::Case(v) => pl::ExprKind::Case(
             v.into_iter()
                 .map(|case| -> Result<_> {
@@ -91,6 +97,20 @@ pub fn expand_expr(expr: pr::Expr) -> Result<pl::Expr> {
                 })
                 .try_collect()?,
         ),
+        pr::ExprKind::Destructure { names, expr } => pl::ExprKind::Destructure {
+            names,
+            expr: expand_expr_box(expr)?,
+        },
+        pr::ExprKind::Cast { expr, ty } => pl::ExprKind::Cast {
+            expr: expand_expr_box(expr)?,
+            ty,
+        },
+        pr::ExprKind::Let { name, .. } => {
+            return Err(Error::new_simple(format!(
+                "`let {name}` can only be used within a pipeline, before the transforms that reference it"
+            ))
+            .with_span(expr.span))
+        }
         pr::ExprKind::Param(v) => pl::ExprKind::Param(v),
         pr::ExprKind::Internal(v) => pl::ExprKind::Internal(v),
     };
@@ -138,8 +158,21 @@ fn desugar_pipeline(mut pipeline: pr::Pipeline) -> Result<pl::Expr> {
     let value = pipeline.exprs.remove(0);
     let mut value = expand_expr(value)?;
 
+    let mut bindings = LetExpander::default();
     for expr in pipeline.exprs {
-        let expr = expand_expr(expr)?;
+        if let pr::ExprKind::Let { name, value } = expr.kind {
+            let value = expand_expr(*value)?;
+            if references_name(&value, &name) {
+                return Err(Error::new_simple(format!("`let {name}` references itself"))
+                    .push_hint("use another name for the binding, or `derive` a column instead")
+                    .with_span(expr.span));
+            }
+            let value = bindings.fold_expr(value)?;
+            bindings.bindings.insert(name, value);
+            continue;
+        }
+
+        let expr = bindings.fold_expr(expand_expr(expr)?)?;
         let span = expr.span;
 
         value = pl::Expr::new(pl::ExprKind::FuncCall(pl::FuncCall::new_simple(
@@ -152,6 +185,87 @@ fn desugar_pipeline(mut pipeline: pr::Pipeline) -> Result<pl::Expr> {
     Ok(value)
 }
 
+/// Replaces the names bound with `let` in a pipeline with the expressions that
+/// they are bound to, in the transforms that follow the binding.
+#[derive(Default)]
+struct LetExpander {
+    bindings: HashMap<String, pl::Expr>,
+}
+
+impl LetExpander {
+    fn bound(&self, ident: &pr::Ident) -> Option<&pl::Expr> {
+        if ident.path.is_empty() {
+            self.bindings.get(&ident.name)
+        } else {
+            None
+        }
+    }
+}
+
+impl PlFold for LetExpander {
+    fn fold_expr(&mut self, expr: pl::Expr) -> Result<pl::Expr> {
+        if let pl::ExprKind::Ident(ident) = &expr.kind {
+            if let Some(value) = self.bound(ident) {
+                return Ok(pl::Expr {
+                    span: expr.span,
+                    alias: expr.alias,
+                    ..value.clone()
+                });
+            }
+        }
+        Ok(pl::Expr {
+            kind: self.fold_expr_kind(expr.kind)?,
+            ..expr
+        })
+    }
+
+    fn fold_expr_kind(&mut self, kind: pl::ExprKind) -> Result<pl::ExprKind> {
+        Ok(match kind {
+            // a column of a tuple keeps the name it is referenced with
+            pl::ExprKind::Tuple(fields) => pl::ExprKind::Tuple(
+                (fields.into_iter())
+                    .map(|mut field| {
+                        if field.alias.is_none() {
+                            field.alias = (field.kind.as_ident())
+                                .filter(|ident| self.bound(ident).is_some())
+                                .map(|ident| ident.name.clone());
+                        }
+                        self.fold_expr(field)
+                    })
+                    .try_collect()?,
+            ),
+            // params of a function shadow the bindings
+            pl::ExprKind::Func(func) => {
+                let mut inner = LetExpander {
+                    bindings: self.bindings.clone(),
+                };
+                for param in func.params.iter().chain(&func.named_params) {
+                    inner.bindings.remove(&param.name);
+                }
+                pl::ExprKind::Func(Box::new(inner.fold_func(*func)?))
+            }
+            kind => pl::fold_expr_kind(self, kind)?,
+        })
+    }
+}
+
+/// Whether an expression references a name, not within a namespace.
+fn references_name(expr: &pl::Expr, name: &str) -> bool {
+    struct NameFinder<'a>(&'a str);
+
+    impl PlVisitor for NameFinder<'_> {
+        fn visit_ident(&mut self, ident: &pr::Ident) -> ControlFlow<()> {
+            if ident.path.is_empty() && ident.name == self.0 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    NameFinder(name).visit_expr(expr).is_break()
+}
+
 /// Desugar unary operators into function calls.
 fn expand_unary(pr::UnaryExpr { op, expr }: pr::UnaryExpr) -> Result<pl::ExprKind> {
     use pr::UnOp::*;
@@ -162,16 +276,23 @@ fn expand_unary(pr::UnaryExpr { op, expr }: pr::UnaryExpr) -> Result<pl::ExprKin
         Neg => ["std", "neg"],
         Not => ["std", "not"],
         Add => return Ok(expr.kind),
+        Spread => return Ok(pl::ExprKind::Spread(Box::new(expr))),
         EqSelf => {
+            // `==` compares columns of the same name, so it is followed by a
+            // name, rather than an expression that could differ between sides
             let pl::ExprKind::Ident(ident) = expr.kind else {
                 return Err(Error::new_simple(
                     "you can only use column names with self-equality operator",
-                ));
+                )
+                .push_hint("compare the columns explicitly, as in `(this.a + 1 == that.b)`")
+                .with_span(expr.span));
             };
             if !ident.path.is_empty() {
                 return Err(Error::new_simple(
                     "you cannot use namespace prefix with self-equality operator",
-                ));
+                )
+                .push_hint(format!("use `=={}`", ident.name))
+                .with_span(expr.span));
             }
 
             let left = pl::Expr {
@@ -217,6 +338,7 @@ fn expand_binary(pr::BinaryExpr { op, left, right }: pr::BinaryExpr) -> Result<p
         pr::BinOp::Gte => vec!["std", "gte"],
         pr::BinOp::Lte => vec!["std", "lte"],
         pr::BinOp::RegexSearch => vec!["std", "regex_search"],
+        pr::BinOp::In => vec!["std", "in"],
         pr::BinOp::And => vec!["std", "and"],
         pr::BinOp::Or => vec!["std", "or"],
         pr::BinOp::Coalesce => vec!["std", "coalesce"],
@@ -224,13 +346,14 @@ fn expand_binary(pr::BinaryExpr { op, left, right }: pr::BinaryExpr) -> Result<p
 
     // For the power operator, we need to reverse the order, since `math.pow a
     // b` is equivalent to `b ** a`. (but for example `sub a b` is equivalent to
-    // `a - b`).
+    // `a - b`). The same goes for `in`, since `in pattern value` is equivalent
+    // to `value in pattern`.
     //
     // (I think this is the most globally consistent approach, since final
     // arguments should be the "data", which in the case of `pow` would be the
     // base; but it's not perfect, we could change it...)
     let (left, right) = match op {
-        pr::BinOp::Pow => (right, left),
+        pr::BinOp::Pow | pr::BinOp::In => (right, left),
         _ => (left, right),
     };
     Ok(new_binop(left, &func_name, right).kind)
@@ -375,8 +498,20 @@ fn restrict_expr_kind(value: pl::ExprKind) -> pr::ExprKind {
                 })
                 .collect(),
         ),
+        pl::ExprKind::Destructure { names, expr } => pr::ExprKind::Destructure {
+            names,
+            expr: restrict_expr_box(expr),
+        },
+        pl::ExprKind::Cast { expr, ty } => pr::ExprKind::Cast {
+            expr: restrict_expr_box(expr),
+            ty,
+        },
         pl::ExprKind::Param(v) => pr::ExprKind::Param(v),
         pl::ExprKind::Internal(v) => pr::ExprKind::Internal(v),
+        pl::ExprKind::Spread(v) => pr::ExprKind::Unary(pr::UnaryExpr {
+            op: pr::UnOp::Spread,
+            expr: restrict_expr_box(v),
+        }),
 
         // TODO: these are not correct, they are producing invalid PRQL
         pl::ExprKind::All { within, .. } => restrict_expr(*within).kind,
@@ -0,0 +1,421 @@
+//! Column-level lineage: the columns of source tables that each output column
+//! of a relation is computed from.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+
+use serde::Serialize;
+
+use crate::ir::decl::{RootModule, TableExpr};
+use crate::ir::pl::{
+    visit_expr_kind, Expr, ExprKind, Lineage, LineageColumn, PlVisitor, TransformKind,
+};
+use crate::pr::Ident;
+use crate::semantic::NS_DEFAULT_DB;
+use crate::{Error, Result, WithErrorInfo};
+
+/// Source columns of each output column of a relation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ColumnLineage {
+    /// Source columns of each output column, by name of the output column.
+    /// Source columns are identified by their table and name, as in
+    /// `employees.salary`. Columns that select all columns of an input are
+    /// named `input.*`.
+    pub columns: HashMap<Ident, HashSet<Ident>>,
+
+    /// Output columns that are computed by an s-string. Their sources might be
+    /// incomplete, since s-strings are not parsed.
+    pub opaque: HashSet<Ident>,
+}
+
+/// Infers the source columns of each output column of a resolved relation,
+/// following columns through joins and relation variables to the tables they
+/// are read from.
+pub fn column_lineage(root_mod: &RootModule, relation: &Expr) -> Result<ColumnLineage> {
+    let mut collector = LineageCollector::new(root_mod);
+    collector.infer_relation(relation)?;
+
+    let lineage = relation_lineage(relation)?;
+    let mut res = ColumnLineage::default();
+    for col in &lineage.columns {
+        let (name, sources) = match col {
+            LineageColumn::Single { name, .. } => {
+                let name = name.as_ref().map(|n| n.name.clone()).unwrap_or_default();
+                let key = column_key(col).unwrap();
+                (Ident::from_name(name), collector.column(&key))
+            }
+            LineageColumn::All { input_id, .. } => {
+                let input = lineage.inputs.iter().find(|i| i.id == *input_id);
+                let name = input.map_or("?", |i| i.name.as_str());
+                let key = ColumnKey::Input(*input_id, "*".to_string());
+                (Ident::from_path(vec![name, "*"]), collector.column(&key))
+            }
+        };
+        if sources.opaque {
+            res.opaque.insert(name.clone());
+        }
+        res.columns.insert(name, sources.columns);
+    }
+    Ok(res)
+}
+
+/// A column, identified either by the expression that computes it or by its
+/// name within a relation input.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ColumnKey {
+    Expr(usize),
+    Input(usize, String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Sources {
+    columns: HashSet<Ident>,
+    opaque: bool,
+}
+
+impl Sources {
+    fn extend(&mut self, other: Sources) {
+        self.columns.extend(other.columns);
+        self.opaque |= other.opaque;
+    }
+}
+
+/// Table that a relation input reads from, along with its column lineage
+/// when the table is a relation variable.
+type InputSource = (Ident, Option<ColumnLineage>);
+
+struct LineageCollector<'a> {
+    root_mod: &'a RootModule,
+
+    /// Source of each relation input.
+    inputs: HashMap<usize, InputSource>,
+
+    /// Sources of columns computed so far.
+    columns: HashMap<ColumnKey, Sources>,
+}
+
+impl<'a> LineageCollector<'a> {
+    fn new(root_mod: &'a RootModule) -> Self {
+        LineageCollector {
+            root_mod,
+            inputs: HashMap::new(),
+            columns: HashMap::new(),
+        }
+    }
+
+    fn infer_relation(&mut self, expr: &Expr) -> Result<()> {
+        match &expr.kind {
+            ExprKind::TransformCall(call) => {
+                self.infer_relation(&call.input)?;
+                if let Some(partition) = &call.partition {
+                    self.infer_assigns(partition)?;
+                }
+                self.infer_transform(&call.input, &call.kind)
+            }
+            ExprKind::Ident(_) => {
+                self.declare_inputs(expr);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn infer_transform(&mut self, input: &Expr, kind: &TransformKind) -> Result<()> {
+        match kind {
+            TransformKind::Derive { assigns }
+            | TransformKind::Select { assigns }
+            | TransformKind::Aggregate { assigns } => self.infer_assigns(assigns),
+
+            TransformKind::Join { with, .. } => self.infer_relation(with),
+
+            TransformKind::Group { by, pipeline } => {
+                self.infer_assigns(by)?;
+                self.infer_pipeline(pipeline)
+            }
+            TransformKind::Window { pipeline, .. } => self.infer_pipeline(pipeline),
+
+            TransformKind::Append(bottom) => {
+                self.infer_relation(bottom)?;
+
+                // columns of a union are matched by position
+                let top = relation_lineage(input)?.columns.clone();
+                let bottom = relation_lineage(bottom)?.columns.clone();
+                for (top, bottom) in top.iter().zip(bottom.iter()) {
+                    let (Some(top), Some(bottom)) = (column_key(top), column_key(bottom)) else {
+                        continue;
+                    };
+                    let mut sources = self.column(&top);
+                    sources.extend(self.column(&bottom));
+                    self.columns.insert(top, sources);
+                }
+                Ok(())
+            }
+
+            TransformKind::Pivot { on, using, by } => {
+                for field in by {
+                    self.infer_assigns(field)?;
+                }
+                // each cell is also computed from the column that is pivoted on
+                let on = self.expr_sources(on)?;
+                for field in using {
+                    let mut sources = self.expr_sources(field)?;
+                    sources.extend(on.clone());
+                    self.columns
+                        .insert(ColumnKey::Expr(field.id.unwrap()), sources);
+                }
+                Ok(())
+            }
+
+            TransformKind::Fill { columns, .. } => {
+                for field in columns {
+                    self.infer_assigns(field)?;
+                }
+                Ok(())
+            }
+
+            TransformKind::Filter { .. }
+            | TransformKind::Sort { .. }
+            | TransformKind::Take { .. }
+            | TransformKind::Loop(_) => Ok(()),
+        }
+    }
+
+    fn infer_pipeline(&mut self, pipeline: &Expr) -> Result<()> {
+        match &pipeline.kind {
+            ExprKind::Func(func) => self.infer_relation(&func.body),
+            _ => self.infer_relation(pipeline),
+        }
+    }
+
+    fn infer_assigns(&mut self, assigns: &Expr) -> Result<()> {
+        let fields = match &assigns.kind {
+            ExprKind::Tuple(fields) => fields.as_slice(),
+            _ => std::slice::from_ref(assigns),
+        };
+        for field in fields {
+            let sources = self.expr_sources(field)?;
+            self.columns
+                .insert(ColumnKey::Expr(field.id.unwrap()), sources);
+        }
+        Ok(())
+    }
+
+    /// Registers the tables of the inputs introduced by a relation reference,
+    /// such as `from employees`.
+    fn declare_inputs(&mut self, expr: &Expr) {
+        let Some(lineage) = &expr.lineage else {
+            return;
+        };
+        for input in &lineage.inputs {
+            if Some(input.id) != expr.id || self.inputs.contains_key(&input.id) {
+                continue;
+            }
+
+            let decl = self.root_mod.module.get(&input.table);
+            let relation_var = match decl.and_then(|d| d.kind.as_table_decl()) {
+                Some(decl) => match &decl.expr {
+                    TableExpr::RelationVar(rel) => column_lineage(self.root_mod, rel).ok(),
+                    _ => None,
+                },
+                None => None,
+            };
+
+            let table = match input.table.clone().pop_front() {
+                (first, Some(table)) if first == NS_DEFAULT_DB => table,
+                _ => input.table.clone(),
+            };
+            self.inputs.insert(input.id, (table, relation_var));
+        }
+    }
+
+    /// Sources of a column. Columns of inputs that are not known otherwise
+    /// are read directly from their table.
+    fn column(&self, key: &ColumnKey) -> Sources {
+        if let Some(sources) = self.columns.get(key) {
+            return sources.clone();
+        }
+
+        let ColumnKey::Input(input_id, name) = key else {
+            return Sources::default();
+        };
+        let Some((table, relation_var)) = self.inputs.get(input_id) else {
+            return Sources::default();
+        };
+
+        if let Some(lineage) = relation_var {
+            let name = Ident::from_name(name);
+            if let Some(columns) = lineage.columns.get(&name) {
+                return Sources {
+                    columns: columns.clone(),
+                    opaque: lineage.opaque.contains(&name),
+                };
+            }
+
+            // the column must come from one of the `input.*` columns
+            let wildcards = (lineage.columns.iter())
+                .filter(|(col, _)| col.name == "*")
+                .flat_map(|(_, sources)| sources)
+                .map(|source| source.clone().with_name(&name.name))
+                .collect::<HashSet<_>>();
+            if !wildcards.is_empty() {
+                return Sources {
+                    columns: wildcards,
+                    opaque: false,
+                };
+            }
+        }
+
+        let mut column = table.clone();
+        column.push(name.clone());
+        Sources {
+            columns: HashSet::from([column]),
+            opaque: false,
+        }
+    }
+
+    fn expr_sources(&self, expr: &Expr) -> Result<Sources> {
+        let mut collector = SourceCollector {
+            lineage: self,
+            sources: Sources::default(),
+        };
+        let _ = collector.visit_expr(expr);
+        Ok(collector.sources)
+    }
+}
+
+/// Collects the sources of all columns referenced by an expression.
+struct SourceCollector<'a, 'b> {
+    lineage: &'b LineageCollector<'a>,
+    sources: Sources,
+}
+
+impl PlVisitor for SourceCollector<'_, '_> {
+    fn visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        match &expr.kind {
+            ExprKind::Ident(ident) => {
+                if let Some(target_id) = expr.target_id {
+                    let key = if self.lineage.inputs.contains_key(&target_id) {
+                        ColumnKey::Input(target_id, ident.name.clone())
+                    } else {
+                        ColumnKey::Expr(target_id)
+                    };
+                    self.sources.extend(self.lineage.column(&key));
+                }
+            }
+            ExprKind::SString(_) => {
+                self.sources.opaque = true;
+            }
+            _ => {}
+        }
+        visit_expr_kind(self, &expr.kind)
+    }
+}
+
+fn relation_lineage(expr: &Expr) -> Result<&Lineage> {
+    expr.lineage.as_ref().ok_or_else(|| {
+        Error::new_simple("expected a relation, but its columns are unknown").with_span(expr.span)
+    })
+}
+
+fn column_key(col: &LineageColumn) -> Option<ColumnKey> {
+    match col {
+        LineageColumn::Single {
+            target_id,
+            target_name: Some(name),
+            ..
+        } => Some(ColumnKey::Input(*target_id, name.clone())),
+        LineageColumn::Single { target_id, .. } => Some(ColumnKey::Expr(*target_id)),
+        LineageColumn::All { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::assert_snapshot;
+    use itertools::Itertools;
+
+    use super::column_lineage;
+    use crate::parser::parse;
+    use crate::semantic::resolve;
+
+    fn lineage_of(query: &str) -> String {
+        let root_mod = resolve(parse(&query.into()).unwrap()).unwrap();
+        let (main, _) = root_mod.find_main_rel(&[]).unwrap();
+        let main = main.clone().into_relation_var().unwrap();
+
+        let lineage = column_lineage(&root_mod, &main).unwrap();
+        (lineage.columns.iter())
+            .map(|(name, sources)| {
+                let sources = sources.iter().map(|s| s.to_string()).sorted().join(", ");
+                let opaque = if lineage.opaque.contains(name) {
+                    " (opaque)"
+                } else {
+                    ""
+                };
+                format!("{name}: {sources}{opaque}")
+            })
+            .sorted()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_derive() {
+        assert_snapshot!(lineage_of(r#"
+        from employees
+        derive {gross = salary + bonus}
+        select {id, gross, net = gross - tax}
+        "#), @r"
+        gross: employees.bonus, employees.salary
+        id: employees.id
+        net: employees.bonus, employees.salary, employees.tax
+        ");
+    }
+
+    #[test]
+    fn test_join() {
+        assert_snapshot!(lineage_of(r#"
+        from e = employees
+        join d = departments (e.dept_id == d.id)
+        group {d.title} (
+          aggregate {total = sum e.salary}
+        )
+        "#), @r"
+        title: departments.title
+        total: employees.salary
+        ");
+    }
+
+    #[test]
+    fn test_relation_var() {
+        assert_snapshot!(lineage_of(r#"
+        let paid = (
+          from employees
+          derive {pay = salary + bonus}
+        )
+
+        from paid
+        select {id, pay = pay * 12}
+        "#), @r"
+        id: employees.id
+        pay: employees.bonus, employees.salary
+        ");
+    }
+
+    #[test]
+    fn test_s_string() {
+        assert_snapshot!(lineage_of(r#"
+        from employees
+        select {id, name = s"UPPER({first_name}) || last_name"}
+        "#), @r"
+        id: employees.id
+        name: employees.first_name (opaque)
+        ");
+    }
+
+    #[test]
+    fn test_all_columns() {
+        assert_snapshot!(lineage_of(r#"
+        from employees
+        "#), @"employees.*: employees.*");
+    }
+}
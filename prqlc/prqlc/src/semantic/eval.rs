@@ -80,8 +80,11 @@ impl PlFold for Evaluator {
             | ExprKind::SString(_)
             | ExprKind::FString(_)
             | ExprKind::Case(_)
+            | ExprKind::Destructure { .. }
+            | ExprKind::Cast { .. }
             | ExprKind::RqOperator { .. }
             | ExprKind::Param(_)
+            | ExprKind::Spread(_)
             | ExprKind::Internal(_) => {
                 return Err(Error::new_simple("not a value").with_span(expr.span))
             }
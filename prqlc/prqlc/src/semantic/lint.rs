@@ -0,0 +1,155 @@
+//! Checks of resolved queries that are valid, but likely to be mistakes.
+
+use std::ops::ControlFlow;
+
+use crate::ir::decl::{DeclKind, RootModule};
+use crate::ir::pl::{
+    visit_interpolate_item, Expr, ExprKind, InterpolateItem, Literal, PlVisitor, TransformCall,
+    TransformKind,
+};
+use crate::semantic::NS_STD;
+use crate::{Error, WithErrorInfo};
+
+/// A check of a resolved query, which returns the places where it fails.
+pub type Lint = fn(&RootModule) -> Vec<Error>;
+
+/// Finds `take` transforms that are not preceded by a `sort`. The rows that
+/// such a `take` returns are up to the database, and may differ between runs.
+///
+/// A `take` within `group` is not reported, as `group {..} (take 1)` is a
+/// common way of picking any one row of each group.
+pub fn find_unsorted_takes(root_mod: &RootModule) -> Vec<Error> {
+    let mut errors = Vec::new();
+    for (expr, call) in transform_calls(root_mod) {
+        if let TransformKind::Take { .. } = call.kind.as_ref() {
+            if call.sort.is_empty() && call.partition.is_none() {
+                errors.push(
+                    Error::new_simple("`take` without a preceding `sort` returns arbitrary rows")
+                        .push_hint("add a `sort` before `take` to choose which rows are returned")
+                        .with_span(expr.span),
+                );
+            }
+        }
+    }
+    sort_by_span(errors)
+}
+
+/// Finds joins whose condition is `true`, which return every combination of
+/// the rows of the two relations. Such a join is intended as a cross join at
+/// times, but it is more often a condition that was left out.
+pub fn find_cartesian_joins(root_mod: &RootModule) -> Vec<Error> {
+    let mut errors = Vec::new();
+    for (expr, call) in transform_calls(root_mod) {
+        if let TransformKind::Join { filter, .. } = call.kind.as_ref() {
+            if let ExprKind::Literal(Literal::Boolean(true)) = filter.kind {
+                errors.push(
+                    Error::new_simple("`join` on `true` returns every combination of rows")
+                        .push_hint("join on a condition that relates the rows of both relations")
+                        .with_span(expr.span),
+                );
+            }
+        }
+    }
+    sort_by_span(errors)
+}
+
+/// Finds interpolations of s-strings that are neither a column nor a number,
+/// such as `s"{a + b}"`. Their SQL is harder to review than a column, so they
+/// are where SQL of untrusted input would most likely be injected.
+pub fn find_unsafe_interpolations(root_mod: &RootModule) -> Vec<Error> {
+    let mut collector = InterpolationCollector::default();
+    for relation in relation_vars(root_mod) {
+        let _ = collector.visit_expr(relation);
+    }
+    sort_by_span(collector.errors)
+}
+
+/// Relation variables of all modules, except the std module.
+fn relation_vars(root_mod: &RootModule) -> Vec<&Expr> {
+    let mut relations = Vec::new();
+    for (name, decl) in &root_mod.module.names {
+        if name != NS_STD {
+            relation_vars_of_decl(&decl.kind, &mut relations);
+        }
+    }
+    relations
+}
+
+fn relation_vars_of_decl<'a>(decl: &'a DeclKind, relations: &mut Vec<&'a Expr>) {
+    match decl {
+        DeclKind::Module(module) => {
+            for decl in module.names.values() {
+                relation_vars_of_decl(&decl.kind, relations);
+            }
+        }
+        DeclKind::TableDecl(table_decl) => {
+            relations.extend(table_decl.expr.as_relation_var().map(AsRef::as_ref))
+        }
+        _ => {}
+    }
+}
+
+fn sort_by_span(mut errors: Vec<Error>) -> Vec<Error> {
+    errors.sort_by_key(|e| e.span.map(|s| (s.source_id, s.start)));
+    errors
+}
+
+/// Transforms of all relation variables, including the transforms of nested
+/// relations, such as joined relations and pipelines of `group`.
+fn transform_calls(root_mod: &RootModule) -> Vec<(&Expr, &TransformCall)> {
+    let mut calls = Vec::new();
+    for relation in relation_vars(root_mod) {
+        transform_calls_in(relation, &mut calls);
+    }
+    calls
+}
+
+fn transform_calls_in<'a>(expr: &'a Expr, calls: &mut Vec<(&'a Expr, &'a TransformCall)>) {
+    let ExprKind::TransformCall(call) = &expr.kind else {
+        return;
+    };
+    calls.push((expr, call));
+
+    let nested = match call.kind.as_ref() {
+        TransformKind::Join { with, .. } => Some(with.as_ref()),
+        TransformKind::Append(bottom) => Some(bottom.as_ref()),
+        TransformKind::Group { pipeline, .. }
+        | TransformKind::Window { pipeline, .. }
+        | TransformKind::Loop(pipeline) => match &pipeline.kind {
+            ExprKind::Func(func) => Some(func.body.as_ref()),
+            _ => Some(pipeline.as_ref()),
+        },
+        _ => None,
+    };
+    if let Some(nested) = nested {
+        transform_calls_in(nested, calls);
+    }
+    transform_calls_in(&call.input, calls);
+}
+
+#[derive(Default)]
+struct InterpolationCollector {
+    errors: Vec<Error>,
+}
+
+impl PlVisitor for InterpolationCollector {
+    fn visit_interpolate_item(&mut self, item: &InterpolateItem) -> ControlFlow<()> {
+        if let InterpolateItem::Expr { expr, .. } = item {
+            let is_safe = match &expr.kind {
+                ExprKind::Ident(_) => true,
+                ExprKind::Literal(literal) => {
+                    matches!(literal, Literal::Integer(_) | Literal::Float(_))
+                }
+                _ => false,
+            };
+            if !is_safe {
+                self.errors.push(
+                    Error::new_simple("s-string interpolates an expression other than a column")
+                        .push_hint("interpolate only columns and numbers, and compute the expression with PRQL")
+                        .with_span(expr.span),
+                );
+            }
+        }
+        visit_interpolate_item(self, item)
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::hash_map::RandomState;
 use std::collections::{HashMap, HashSet};
 use std::iter::zip;
+use std::ops::ControlFlow;
 
 use enum_as_inner::EnumAsInner;
 use itertools::Itertools;
@@ -10,13 +11,13 @@ use semver::{Prerelease, Version};
 
 use crate::compiler_version;
 use crate::ir::decl::{self, DeclKind, Module, RootModule, TableExpr};
-use crate::ir::generic::{ColumnSort, WindowFrame};
+use crate::ir::generic::{ColumnSort, SortDirection, WindowFrame, WindowKind};
 use crate::ir::pl::TableExternRef::LocalTable;
-use crate::ir::pl::{self, Ident, Lineage, LineageColumn, PlFold, QueryDef};
+use crate::ir::pl::{self, FillDir, Ident, Lineage, LineageColumn, PlVisitor, QueryDef};
 use crate::ir::rq::{
     self, CId, RelationColumn, RelationLiteral, RelationalQuery, TId, TableDecl, Transform,
 };
-use crate::pr::TyTupleField;
+use crate::pr::{TyKind, TyTupleField};
 use crate::semantic::write_pl;
 use crate::utils::{toposort, IdGenerator};
 use crate::{Error, Reason, Result, Span, WithErrorInfo};
@@ -81,6 +82,30 @@ pub fn lower_to_ir(
     Ok((query, l.root_mod))
 }
 
+/// Finds relation variables at the top level of the root module that no other
+/// relation references, in the order of their declaration. Each of them is a
+/// query of its own, such as the main pipeline.
+pub fn find_query_roots(root_mod: &RootModule) -> Vec<Ident> {
+    let tables = TableExtractor::extract(&root_mod.module);
+
+    let referenced: HashSet<Ident> = (tables.iter())
+        .filter_map(|(_, (table, _))| table.expr.as_relation_var())
+        .flat_map(|expr| TableDepsCollector::collect(expr))
+        .collect();
+
+    (tables.into_iter())
+        .filter(|(ident, (table, _))| {
+            ident.path.is_empty()
+                && table.expr.as_relation_var().is_some()
+                && !referenced.contains(ident)
+        })
+        .sorted_by_key(|(_, (_, declared_at))| {
+            declared_at.and_then(|id| root_mod.span_map.get(&id).map(|s| s.start))
+        })
+        .map(|(ident, _)| ident)
+        .collect()
+}
+
 fn extern_ref_to_relation(
     mut columns: Vec<TyTupleField>,
     fq_ident: &Ident,
@@ -367,28 +392,23 @@ impl Lowerer {
                     })
                     .collect_vec();
 
+                // columns of the literal are those of its first row; the frame
+                // also contains any other columns that later rows have introduced
+                let first_row = elements.first().and_then(|r| r.kind.as_tuple());
+                let first_row_names: Option<HashSet<_>> =
+                    first_row.and_then(|fields| fields.iter().map(|f| f.alias.as_ref()).collect());
+                let column_names = columns
+                    .iter()
+                    .map(|c| c.as_single().unwrap().clone().unwrap())
+                    .filter(|name| first_row_names.as_ref().map_or(true, |n| n.contains(name)))
+                    .collect_vec();
+                let rows = elements
+                    .into_iter()
+                    .map(|row| lower_relation_literal_row(row, &column_names))
+                    .try_collect()?;
                 let lit = RelationLiteral {
-                    columns: columns
-                        .iter()
-                        .map(|c| c.as_single().unwrap().clone().unwrap())
-                        .collect_vec(),
-                    rows: elements
-                        .into_iter()
-                        .map(|row| {
-                            row.kind
-                                .into_tuple()
-                                .unwrap()
-                                .into_iter()
-                                .map(|element| {
-                                    element.try_cast(
-                                        |x| x.into_literal(),
-                                        Some("relation literal"),
-                                        "literals",
-                                    )
-                                })
-                                .try_collect()
-                        })
-                        .try_collect()?,
+                    columns: column_names,
+                    rows,
                 };
 
                 log::debug!("lowering literal relation table, columns = {columns:?}");
@@ -419,6 +439,43 @@ impl Lowerer {
         })
     }
 
+    /// Lower a relation whose values are used within an expression into a
+    /// new table of the query.
+    fn lower_sub_query(&mut self, expr: pl::Expr) -> Result<TId> {
+        let span = expr.span;
+
+        let relation = if expr.kind.is_transform_call() {
+            self.lower_relation(expr)?
+        } else {
+            // select all columns of an instance of the table
+            let table_ref = self.lower_table_ref(expr)?;
+            let (columns, cids) = table_ref.columns.iter().cloned().unzip();
+            rq::Relation {
+                kind: rq::RelationKind::Pipeline(vec![
+                    Transform::From(table_ref),
+                    Transform::Select(cids),
+                ]),
+                columns,
+            }
+        };
+
+        if !matches!(relation.columns.as_slice(), [RelationColumn::Single(_)]) {
+            return Err(Error::new_simple(
+                "the relation on the right side of `in` must have exactly one column",
+            )
+            .push_hint("select the column, as in `(from t | select {id})`")
+            .with_span(span));
+        }
+
+        let tid = self.tid.gen();
+        self.table_buffer.push(TableDecl {
+            id: tid,
+            name: None,
+            relation,
+        });
+        Ok(tid)
+    }
+
     fn redirect_mappings(&mut self, redirects: HashMap<CId, CId>) {
         for target in self.node_mapping.values_mut() {
             match target {
@@ -491,6 +548,23 @@ impl Lowerer {
 
     // Result is stored in self.pipeline
     fn lower_pipeline(&mut self, ast: pl::Expr, closure_param: Option<usize>) -> Result<()> {
+        let comment = ast.span.and_then(|span| {
+            let end = (span.source_id, span.start);
+
+            // transforms of `group` and `window` take the place of the call,
+            // so they take its comment too, which is between them and their input
+            let input = ast
+                .kind
+                .as_transform_call()
+                .and_then(|call| call.input.span);
+            let start = input
+                .filter(|input| input.source_id == span.source_id && input.end <= span.start)
+                .map_or(end, |input| (input.source_id, input.end));
+
+            let mut comments = self.root_mod.transform_comments.range(start..=end);
+            comments.next_back().map(|(_, comment)| comment.clone())
+        });
+
         let transform_call = match ast.kind {
             pl::ExprKind::TransformCall(transform) => transform,
             pl::ExprKind::Func(closure) => {
@@ -507,6 +581,7 @@ impl Lowerer {
                 }
 
                 let table_ref = self.lower_table_ref(ast)?;
+                self.pipeline.extend(comment.map(Transform::Comment));
                 self.pipeline.push(Transform::From(table_ref));
                 return Ok(());
             }
@@ -515,6 +590,12 @@ impl Lowerer {
         // lower input table
         self.lower_pipeline(*transform_call.input, closure_param)?;
 
+        // columns of an aggregation are computed before it, but are part of it
+        let mut comment = comment.map(Transform::Comment);
+        if !matches!(*transform_call.kind, pl::TransformKind::Aggregate { .. }) {
+            self.pipeline.extend(comment.take());
+        }
+
         // ... and continues with transforms created in this function
 
         let window = rq::Window {
@@ -542,14 +623,33 @@ impl Lowerer {
             pl::TransformKind::Filter { filter, .. } => {
                 let filter = self.lower_expr(*filter)?;
 
+                if let Some(func) = self.find_unordered_rank(&filter) {
+                    return Err(Error::new_simple(format!(
+                        "filter depends on `{func}` without an ordering, so it keeps either all rows or none"
+                    ))
+                    .push_hint(format!("add a `sort` before the transform that computes `{func}`"))
+                    .with_span(filter.span.or(ast.span)));
+                }
+
                 self.pipeline.push(Transform::Filter(filter));
             }
             pl::TransformKind::Aggregate { assigns, .. } => {
-                let window = self.window.take();
+                let partition = self.window.take().unwrap().partition;
+
+                if let Some(column) = self.find_unaggregated(&assigns, &partition)? {
+                    let name = column.kind.as_ident().unwrap().name.clone();
+                    return Err(Error::new_simple(format!(
+                        "column `{name}` is used in `aggregate` without an aggregate function"
+                    ))
+                    .push_hint(format!(
+                        "wrap it in an aggregate function, such as `sum {name}`, or group by it"
+                    ))
+                    .with_span(column.span));
+                }
 
                 let compute = self.declare_as_columns(*assigns, true)?;
 
-                let partition = window.unwrap().partition;
+                self.pipeline.extend(comment);
                 self.pipeline
                     .push(Transform::Aggregate { partition, compute });
             }
@@ -561,13 +661,41 @@ impl Lowerer {
                 let window = self.window.take().unwrap_or_default();
                 let range = self.lower_range(range)?;
 
-                validate_take_range(&range, ast.span)?;
+                if let Some(range) = reverse_take_range(&range, ast.span)? {
+                    // the last rows are the first rows of the reversed sort
+                    if window.sort.is_empty() {
+                        return Err(Error::new_simple(
+                            "`take` of the last rows requires the rows to be sorted",
+                        )
+                        .push_hint("add a `sort` before the `take`, as in `sort id | take (-5)..`")
+                        .with_span(ast.span));
+                    }
+                    validate_take_range(&range, ast.span)?;
+
+                    let reversed = (window.sort.iter().cloned())
+                        .map(ColumnSort::reversed)
+                        .collect_vec();
+                    let ungrouped = window.partition.is_empty();
+                    if ungrouped {
+                        self.pipeline.push(Transform::Sort(reversed.clone()));
+                    }
+                    self.pipeline.push(Transform::Take(rq::Take {
+                        range,
+                        partition: window.partition,
+                        sort: reversed,
+                    }));
+                    if ungrouped {
+                        self.pipeline.push(Transform::Sort(window.sort));
+                    }
+                } else {
+                    validate_take_range(&range, ast.span)?;
 
-                self.pipeline.push(Transform::Take(rq::Take {
-                    range,
-                    partition: window.partition,
-                    sort: window.sort,
-                }));
+                    self.pipeline.push(Transform::Take(rq::Take {
+                        range,
+                        partition: window.partition,
+                        sort: window.sort,
+                    }));
+                }
             }
             pl::TransformKind::Join {
                 side, with, filter, ..
@@ -595,6 +723,110 @@ impl Lowerer {
 
                 self.pipeline.push(Transform::Loop(pipeline));
             }
+            pl::TransformKind::Pivot { on, using, by } => {
+                self.window = None;
+
+                let partition = (by.into_iter())
+                    .map(|e| self.declare_as_column(e, false))
+                    .try_collect()?;
+
+                let on = self.lower_expr(*on)?;
+                let [column, values] = on.kind.into_operator().unwrap().1.try_into().unwrap();
+                let values = values.kind.into_array().unwrap();
+
+                // each of the values gets an aggregation for each of `using`
+                let per_value = using.len() / values.len().max(1);
+                let mut compute = Vec::with_capacity(using.len());
+                for (index, mut cell) in using.into_iter().enumerate() {
+                    let id = cell.id.unwrap();
+                    cell.needs_window = false;
+                    let aggregation = self.lower_expr(cell)?;
+
+                    let value = values[index / per_value].clone();
+                    let expr = rq::Expr {
+                        span: aggregation.span,
+                        kind: rq::ExprKind::Operator {
+                            name: "std.pivot".to_string(),
+                            args: vec![aggregation, column.clone(), value],
+                        },
+                    };
+
+                    let cid = self.cid.gen();
+                    self.pipeline.push(Transform::Compute(rq::Compute {
+                        id: cid,
+                        expr,
+                        window: None,
+                        is_aggregation: true,
+                    }));
+                    self.node_mapping.insert(id, LoweredTarget::Compute(cid));
+                    compute.push(cid);
+                }
+
+                self.pipeline
+                    .push(Transform::Aggregate { partition, compute });
+            }
+            pl::TransformKind::Fill {
+                columns,
+                by,
+                sort,
+                direction,
+            } => {
+                let window = self.window.take().unwrap_or_default();
+
+                let mut partition = window.partition;
+                for e in by {
+                    partition.push(self.declare_as_column(e, false)?);
+                }
+                // the frame spans up to the current row, or from it
+                let current_row = Some(rq::Expr {
+                    kind: rq::ExprKind::Literal(Literal::Integer(0)),
+                    span: None,
+                });
+                let (operator, range) = match direction {
+                    FillDir::Forward => (
+                        "std.fill_forward",
+                        Range {
+                            start: None,
+                            end: current_row,
+                        },
+                    ),
+                    FillDir::Backward => (
+                        "std.fill_backward",
+                        Range {
+                            start: current_row,
+                            end: None,
+                        },
+                    ),
+                };
+                let window = rq::Window {
+                    frame: WindowFrame {
+                        kind: WindowKind::Rows,
+                        range,
+                    },
+                    partition,
+                    sort: self.lower_sorts(sort)?,
+                };
+
+                for column in columns {
+                    let id = column.id.unwrap();
+                    let column = self.lower_expr(column)?;
+
+                    let cid = self.cid.gen();
+                    self.pipeline.push(Transform::Compute(rq::Compute {
+                        id: cid,
+                        expr: rq::Expr {
+                            span: column.span,
+                            kind: rq::ExprKind::Operator {
+                                name: operator.to_string(),
+                                args: vec![column],
+                            },
+                        },
+                        window: Some(window.clone()),
+                        is_aggregation: false,
+                    }));
+                    self.node_mapping.insert(id, LoweredTarget::Compute(cid));
+                }
+            }
             pl::TransformKind::Group { .. } | pl::TransformKind::Window { .. } => unreachable!(
                 "transform `{}` cannot be lowered.",
                 (*transform_call.kind).as_ref()
@@ -606,6 +838,96 @@ impl Lowerer {
         Ok(())
     }
 
+    /// Finds a ranking window function without ordering that the expression
+    /// depends on, following column references through the current pipeline.
+    fn find_unordered_rank(&self, expr: &rq::Expr) -> Option<&'static str> {
+        match &expr.kind {
+            rq::ExprKind::ColumnRef(cid) => {
+                let compute = self.pipeline.iter().find_map(|t| match t {
+                    Transform::Compute(compute) if compute.id == *cid => Some(compute),
+                    _ => None,
+                })?;
+
+                if let Some(window) = &compute.window {
+                    if window.sort.is_empty() {
+                        if let rq::ExprKind::Operator { name, .. } = &compute.expr.kind {
+                            match name.as_str() {
+                                "std.rank" => return Some("rank"),
+                                "std.rank_dense" => return Some("rank_dense"),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                self.find_unordered_rank(&compute.expr)
+            }
+            rq::ExprKind::Operator { args, .. } => {
+                args.iter().find_map(|a| self.find_unordered_rank(a))
+            }
+            rq::ExprKind::Case(cases) => cases.iter().find_map(|c| {
+                self.find_unordered_rank(&c.condition)
+                    .or_else(|| self.find_unordered_rank(&c.value))
+            }),
+            rq::ExprKind::Array(items) => items.iter().find_map(|a| self.find_unordered_rank(a)),
+            rq::ExprKind::SString(items) => items.iter().find_map(|i| match i {
+                InterpolateItem::Expr { expr, .. } => self.find_unordered_rank(expr),
+                InterpolateItem::String(_) => None,
+            }),
+            rq::ExprKind::Literal(_) | rq::ExprKind::Param(_) | rq::ExprKind::SubQuery(_) => None,
+        }
+    }
+
+    /// Finds a column that is referenced by an expression of `aggregate`
+    /// outside of any aggregate function, and is not one of the columns
+    /// the relation is grouped by.
+    fn find_unaggregated<'a>(
+        &self,
+        expr: &'a pl::Expr,
+        partition: &[CId],
+    ) -> Result<Option<&'a pl::Expr>> {
+        let find_in = |exprs: &mut dyn Iterator<Item = &'a pl::Expr>| -> Result<_> {
+            for expr in exprs {
+                if let Some(column) = self.find_unaggregated(expr, partition)? {
+                    return Ok(Some(column));
+                }
+            }
+            Ok(None)
+        };
+
+        match &expr.kind {
+            pl::ExprKind::Ident(ident) => {
+                let Some(id) = expr.target_id else {
+                    return Ok(None);
+                };
+                if expr.ty.as_ref().map_or(false, |x| x.kind.is_tuple()) {
+                    return Ok(None);
+                }
+                let cid = self.lookup_cid(id, Some(&ident.name))?;
+                Ok((!partition.contains(&cid)).then_some(expr))
+            }
+            pl::ExprKind::RqOperator { name, .. }
+                if AGGREGATE_FUNCTIONS.contains(&name.as_str()) =>
+            {
+                Ok(None)
+            }
+            pl::ExprKind::RqOperator { args: items, .. }
+            | pl::ExprKind::Tuple(items)
+            | pl::ExprKind::Array(items) => find_in(&mut items.iter()),
+            pl::ExprKind::Case(cases) => find_in(
+                &mut cases
+                    .iter()
+                    .flat_map(|c| [c.condition.as_ref(), c.value.as_ref()]),
+            ),
+            pl::ExprKind::Cast { expr, .. } => self.find_unaggregated(expr, partition),
+            pl::ExprKind::FString(items) => find_in(&mut items.iter().filter_map(|i| match i {
+                InterpolateItem::Expr { expr, .. } => Some(expr.as_ref()),
+                InterpolateItem::String(_) => None,
+            })),
+            // s-strings may contain aggregate functions of their own
+            _ => Ok(None),
+        }
+    }
+
     fn lower_range(&mut self, range: Range<Box<pl::Expr>>) -> Result<Range<rq::Expr>> {
         Ok(Range {
             start: range.start.map(|x| self.lower_expr(*x)).transpose()?,
@@ -615,9 +937,13 @@ impl Lowerer {
 
     fn lower_sorts(&mut self, by: Vec<ColumnSort<Box<pl::Expr>>>) -> Result<Vec<ColumnSort<CId>>> {
         by.into_iter()
-            .map(|ColumnSort { column, direction }| {
-                let column = self.declare_as_column(*column, false)?;
-                Ok(ColumnSort { direction, column })
+            .map(|sort| {
+                let column = self.declare_as_column(*sort.column, false)?;
+                Ok(ColumnSort {
+                    direction: sort.direction,
+                    column,
+                    nulls: sort.nulls,
+                })
             })
             .try_collect()
     }
@@ -786,6 +1112,11 @@ impl Lowerer {
             None
         };
         let id = expr_ast.id.unwrap();
+        let (explicit_partition, explicit_sort) = if needs_window {
+            self.lower_window_args(&mut expr_ast)?
+        } else {
+            (None, None)
+        };
 
         // lower
         let expr = self.lower_expr(expr_ast)?;
@@ -799,11 +1130,26 @@ impl Lowerer {
         }
 
         // determine window
-        let window = if needs_window {
+        let mut window = if needs_window {
             self.window.clone()
         } else {
             None
         };
+        if let Some(partition) = explicit_partition {
+            window.get_or_insert_with(Default::default).partition = partition;
+        }
+        if let Some(sort) = explicit_sort {
+            window.get_or_insert_with(Default::default).sort = sort;
+        }
+
+        // bare `row_number` takes its ordering from the pipeline
+        if is_ordinal(&expr) && window.as_ref().map_or(true, |w| w.sort.is_empty()) {
+            return Err(Error::new_simple(
+                "`row_number` without an argument requires an ordering",
+            )
+            .push_hint("add a `sort` before this transform, or use `row_number this` for an arbitrary order")
+            .with_span(expr.span));
+        }
 
         // construct ColumnDef
         let cid = self.cid.gen();
@@ -819,6 +1165,46 @@ impl Lowerer {
         Ok(cid)
     }
 
+    /// Takes `by` and `sort` out of a call to a window function, and lowers
+    /// them into the partition and the ordering of its window, when given.
+    /// They take precedence over those of the enclosing transforms.
+    #[allow(clippy::type_complexity)]
+    fn lower_window_args(
+        &mut self,
+        expr: &mut pl::Expr,
+    ) -> Result<(Option<Vec<CId>>, Option<Vec<ColumnSort<CId>>>)> {
+        let pl::ExprKind::RqOperator { name, args } = &mut expr.kind else {
+            return Ok((None, None));
+        };
+        if !WINDOW_FUNCTIONS.contains(&name.as_str()) {
+            return Ok((None, None));
+        }
+
+        // named params precede the positional ones, in the order of declaration
+        let by = args.remove(0);
+        let sort = args.remove(0);
+        if name == "std.lag" || name == "std.lead" {
+            let default = args.remove(0);
+            if !matches!(default.kind, pl::ExprKind::Literal(Literal::Null)) {
+                *name = format!("{name}_default");
+                args.insert(0, default);
+            }
+        }
+
+        let is_given = |e: &pl::Expr| !matches!(&e.kind, pl::ExprKind::Tuple(f) if f.is_empty());
+        let partition = if is_given(&by) {
+            Some(self.declare_as_columns(by, false)?)
+        } else {
+            None
+        };
+        let sort = if is_given(&sort) {
+            Some(self.lower_sorts(column_sorts(sort))?)
+        } else {
+            None
+        };
+        Ok((partition, sort))
+    }
+
     fn lower_expr(&mut self, expr: pl::Expr) -> Result<rq::Expr> {
         let span = expr.span;
 
@@ -902,11 +1288,41 @@ impl Lowerer {
                     })
                     .try_collect()?,
             ),
+            pl::ExprKind::RqOperator { name, args } if name == "std.relation_in" => {
+                let [value, relation] = <[_; 2]>::try_from(args).unwrap();
+                let relation_span = relation.span;
+
+                let value = self.lower_expr(value)?;
+                let relation = rq::Expr {
+                    kind: rq::ExprKind::SubQuery(self.lower_sub_query(relation)?),
+                    span: relation_span,
+                };
+
+                rq::ExprKind::Operator {
+                    name,
+                    args: vec![value, relation],
+                }
+            }
             pl::ExprKind::RqOperator { name, args } => {
                 let args = args.into_iter().map(|x| self.lower_expr(x)).try_collect()?;
 
                 rq::ExprKind::Operator { name, args }
             }
+            pl::ExprKind::Cast { expr, ty } => {
+                // the resolver only allows casts to primitive types
+                let TyKind::Primitive(primitive) = ty.kind else {
+                    return Err(Error::new_assert("cast to a non-primitive type").with_span(span));
+                };
+                let ty = rq::Expr {
+                    kind: rq::ExprKind::Literal(Literal::String(primitive.to_string())),
+                    span: ty.span,
+                };
+
+                rq::ExprKind::Operator {
+                    name: "std.cast".to_string(),
+                    args: vec![self.lower_expr(*expr)?, ty],
+                }
+            }
             pl::ExprKind::Param(id) => rq::ExprKind::Param(id),
 
             pl::ExprKind::Tuple(_) => {
@@ -924,7 +1340,25 @@ impl Lowerer {
                     .try_collect()?,
             ),
 
-            pl::ExprKind::FuncCall(_) | pl::ExprKind::Func(_) | pl::ExprKind::TransformCall(_) => {
+            pl::ExprKind::Func(func) if func.body.kind.is_s_string() => {
+                // a template that was not given all of its arguments
+                let params = (func.params.iter())
+                    .map(|p| format!("`{}`", p.name))
+                    .join(", ");
+                return Err(Error::new_simple(format!(
+                    "s-string template `{}` expects {} arguments, but was called with {}",
+                    func.as_debug_name(),
+                    func.params.len(),
+                    func.args.len()
+                ))
+                .push_hint(format!("its parameters are {params}"))
+                .with_span(span));
+            }
+
+            pl::ExprKind::FuncCall(_)
+            | pl::ExprKind::Func(_)
+            | pl::ExprKind::TransformCall(_)
+            | pl::ExprKind::Spread(_) => {
                 log::debug!("cannot lower {expr:?}");
                 return Err(Error::new(Reason::Unexpected {
                     found: format!("`{}`", write_pl(expr.clone())),
@@ -933,6 +1367,13 @@ impl Lowerer {
                 .with_span(expr.span));
             }
 
+            pl::ExprKind::Destructure { .. } => {
+                return Err(
+                    Error::new_simple("columns can only be destructured within `derive`")
+                        .with_span(expr.span),
+                );
+            }
+
             pl::ExprKind::Internal(_) => {
                 return Err(Error::new_assert(format!(
                     "Unresolved lowering: {}",
@@ -962,7 +1403,7 @@ impl Lowerer {
             .try_collect()
     }
 
-    fn lookup_cid(&mut self, id: usize, name: Option<&String>) -> Result<CId> {
+    fn lookup_cid(&self, id: usize, name: Option<&String>) -> Result<CId> {
         let cid = match self.node_mapping.get(&id) {
             Some(LoweredTarget::Compute(cid)) => *cid,
             Some(LoweredTarget::Input(input_columns)) => {
@@ -998,6 +1439,106 @@ fn str_lit(string: String) -> rq::Expr {
     }
 }
 
+/// Aggregate functions, which can reference any column of the relation that
+/// is being aggregated.
+const AGGREGATE_FUNCTIONS: &[&str] = &[
+    "std.min",
+    "std.max",
+    "std.sum",
+    "std.average",
+    "std.stddev",
+    "std.all",
+    "std.any",
+    "std.concat_array",
+    "std.arg_max",
+    "std.arg_min",
+    "std.count",
+    "std.count_values",
+    "std.count_distinct",
+    "std.filtered",
+];
+
+/// Window functions that can be given a partition and an ordering of their own,
+/// with `by` and `sort`.
+const WINDOW_FUNCTIONS: &[&str] = &[
+    "std.lag",
+    "std.lead",
+    "std.first",
+    "std.last",
+    "std.rank",
+    "std.rank_dense",
+];
+
+/// Converts `sort` arg of a window function, which is a column or a tuple of
+/// columns, optionally negated for descending order.
+fn column_sorts(sort: pl::Expr) -> Vec<ColumnSort<Box<pl::Expr>>> {
+    let columns = match sort.kind {
+        pl::ExprKind::Tuple(fields) => fields,
+        _ => vec![sort],
+    };
+    (columns.into_iter())
+        .map(|column| {
+            let (column, direction) = match column.kind {
+                pl::ExprKind::RqOperator { name, mut args } if name == "std.neg" => {
+                    (args.remove(0), SortDirection::Desc)
+                }
+                _ => (column, SortDirection::default()),
+            };
+            ColumnSort {
+                direction,
+                column: Box::new(column),
+                nulls: None,
+            }
+        })
+        .collect()
+}
+
+/// Is this a bare `row_number`, which has no args since it is ordered by the
+/// sort of the pipeline?
+fn is_ordinal(expr: &rq::Expr) -> bool {
+    matches!(&expr.kind, rq::ExprKind::Operator { name, args } if name == "std.row_number" && args.is_empty())
+}
+
+/// Converts a range of positions counted from the last row, such as `-5..`
+/// or `-3..-2`, into the range of the same rows when the sort is reversed.
+/// Returns `None` for ranges that are counted from the first row, which
+/// includes ranges that only end with a negative position, such as the `..-1`
+/// of `take -1`.
+fn reverse_take_range(
+    range: &Range<rq::Expr>,
+    span: Option<Span>,
+) -> Result<Option<Range<rq::Expr>>> {
+    // a bound that is not an int literal is counted from the first row
+    let as_negative_int = |bound: &rq::Expr| {
+        let int = bound.kind.as_literal().and_then(|l| l.as_integer());
+        int.filter(|i| **i < 0).copied()
+    };
+    let start = range.start.as_ref().map(as_negative_int);
+    let end = range.end.as_ref().map(as_negative_int);
+
+    // the start of a range counted from the last row is negative
+    if !matches!(start, Some(Some(_))) {
+        return Ok(None);
+    }
+    if matches!(end, Some(None)) {
+        return Err(Error::new_simple(
+            "`take` cannot count one bound of a range from the first row, and the other from the last",
+        )
+        .with_span(span));
+    }
+
+    let reversed_bound = |bound: Option<Option<i64>>| {
+        bound.flatten().map(|b| rq::Expr {
+            kind: rq::ExprKind::Literal(Literal::Integer(-b)),
+            span: None,
+        })
+    };
+    Ok(Some(Range {
+        start: reversed_bound(end),
+        end: reversed_bound(start),
+    }))
+}
+
 fn validate_take_range(range: &Range<rq::Expr>, span: Option<Span>) -> Result<()> {
     fn bound_as_int(bound: &Option<rq::Expr>) -> Option<Option<&i64>> {
         bound
@@ -1039,6 +1580,50 @@ fn validate_take_range(range: &Range<rq::Expr>, span: Option<Span>) -> Result<()
     }
 }
 
+/// Converts a row of a relation literal into literals, ordered as `columns`.
+///
+/// Fields are matched to columns by name, so rows may list them in any order,
+/// but must all contain the same columns.
+fn lower_relation_literal_row(row: pl::Expr, columns: &[String]) -> Result<Vec<Literal>> {
+    let span = row.span;
+    let fields = row.kind.into_tuple().map_err(|kind| {
+        Error::new(Reason::Expected {
+            who: Some("relation literal".to_string()),
+            expected: "a tuple of columns".to_string(),
+            found: format!("`{}`", write_pl(pl::Expr::new(kind))),
+        })
+        .with_span(span)
+    })?;
+
+    let mut values: Vec<Option<Literal>> = vec![None; columns.len()];
+    for (position, field) in fields.into_iter().enumerate() {
+        let index = match &field.alias {
+            Some(name) => columns.iter().position(|c| c == name),
+            None => (position < columns.len()).then_some(position),
+        };
+        let Some(index) = index.filter(|i| values[*i].is_none()) else {
+            let name = field.alias.as_deref().unwrap_or("<unnamed>");
+            return Err(Error::new_simple(format!(
+                "relation literal row contains an unexpected column `{name}`"
+            ))
+            .push_hint(format!("expected columns {}", columns.join(", ")))
+            .with_span(field.span.or(span)));
+        };
+
+        let value = field.try_cast(|x| x.into_literal(), Some("relation literal"), "literals")?;
+        values[index] = Some(value);
+    }
+
+    zip(columns, values)
+        .map(|(name, value)| {
+            value.ok_or_else(|| {
+                Error::new_simple(format!("relation literal row is missing column `{name}`"))
+                    .with_span(span)
+            })
+        })
+        .try_collect()
+}
+
 #[derive(Default)]
 struct TableExtractor {
     path: Vec<String>,
@@ -1087,7 +1672,7 @@ fn toposort_tables(
     let mut dependencies: Vec<(Ident, Vec<Ident>)> = Vec::new();
     for (ident, table) in &tables {
         let deps = if let TableExpr::RelationVar(e) = &table.0.expr {
-            TableDepsCollector::collect(*e.clone())
+            TableDepsCollector::collect(e)
         } else {
             vec![]
         };
@@ -1112,33 +1697,37 @@ struct TableDepsCollector {
 }
 
 impl TableDepsCollector {
-    fn collect(expr: pl::Expr) -> Vec<Ident> {
+    fn collect(expr: &pl::Expr) -> Vec<Ident> {
         let mut c = TableDepsCollector::default();
-        c.fold_expr(expr).unwrap();
+        let _ = c.visit_expr(expr);
         c.deps
     }
 }
 
-impl PlFold for TableDepsCollector {
-    fn fold_expr(&mut self, mut expr: pl::Expr) -> Result<pl::Expr> {
-        expr.kind = match expr.kind {
-            pl::ExprKind::Ident(ref ident) => {
+impl PlVisitor for TableDepsCollector {
+    fn visit_expr(&mut self, expr: &pl::Expr) -> ControlFlow<()> {
+        match &expr.kind {
+            pl::ExprKind::Ident(ident) => {
                 if let Some(ty) = &expr.ty {
                     if ty.is_relation() {
                         self.deps.push(ident.clone());
                     }
                 }
-                expr.kind
+                ControlFlow::Continue(())
             }
-            pl::ExprKind::TransformCall(tc) => {
-                pl::ExprKind::TransformCall(self.fold_transform_call(tc)?)
+
+            pl::ExprKind::TransformCall(_) | pl::ExprKind::Func(_) => {
+                pl::visit_expr_kind(self, &expr.kind)
+            }
+
+            // expressions that may contain a relation used by `in`
+            pl::ExprKind::RqOperator { .. } | pl::ExprKind::Tuple(_) | pl::ExprKind::Case(_) => {
+                pl::visit_expr_kind(self, &expr.kind)
             }
-            pl::ExprKind::Func(func) => pl::ExprKind::Func(Box::new(self.fold_func(*func)?)),
 
             // optimization: don't recurse into anything else than TransformCalls and Func
-            _ => expr.kind,
-        };
-        Ok(expr)
+            _ => ControlFlow::Continue(()),
+        }
     }
 }
 
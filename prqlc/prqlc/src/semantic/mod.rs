@@ -1,35 +1,51 @@
 //! Semantic resolver (name resolution, type checking and lowering to RQ)
 
 pub mod ast_expand;
+pub mod column_lineage;
 mod eval;
+pub mod lint;
 mod lowering;
 mod module;
 pub mod reporting;
 mod resolver;
+pub mod schema;
+mod stdlib_alias;
 
 pub use eval::eval;
-pub use lowering::lower_to_ir;
+pub use lowering::{find_query_roots, lower_to_ir};
+
+use std::collections::HashMap;
+use std::iter::zip;
 
 use self::resolver::Resolver;
 pub use self::resolver::ResolverOptions;
 use crate::ir::constant::ConstExpr;
-use crate::ir::decl::{Module, RootModule};
+use crate::ir::decl::{DeclKind, Module, RootModule, TableDecl, TableExpr};
 use crate::ir::pl::{self, Expr, ImportDef, ModuleDef, Stmt, StmtKind, TypeDef, VarDef};
 use crate::ir::rq::RelationalQuery;
 use crate::parser::is_mod_def_for;
 use crate::pr;
 use crate::WithErrorInfo;
 use crate::{debug, parser};
-use crate::{Error, Reason, Result};
+use crate::{Error, Errors, Reason, Result};
 
 /// Runs semantic analysis on the query and lowers PL to RQ.
 pub fn resolve_and_lower(
     file_tree: pr::ModuleDef,
     main_path: &[String],
     database_module_path: Option<&[String]>,
-) -> Result<RelationalQuery> {
+) -> Result<RelationalQuery, Errors> {
     let root_mod = resolve(file_tree)?;
 
+    Ok(lower(root_mod, main_path, database_module_path)?)
+}
+
+/// Lowers the resolved PL to RQ.
+pub fn lower(
+    root_mod: RootModule,
+    main_path: &[String],
+    database_module_path: Option<&[String]>,
+) -> Result<RelationalQuery> {
     debug::log_stage(debug::Stage::Semantic(debug::StageSemantic::Lowering));
     let default_db = [NS_DEFAULT_DB.to_string()];
     let database_module_path = database_module_path.unwrap_or(&default_db);
@@ -40,29 +56,133 @@ pub fn resolve_and_lower(
 }
 
 /// Runs semantic analysis on the query.
-pub fn resolve(mut module_tree: pr::ModuleDef) -> Result<RootModule> {
-    load_std_lib(&mut module_tree);
+pub fn resolve(module_tree: pr::ModuleDef) -> Result<RootModule, Errors> {
+    resolve_with_table_columns(module_tree, &HashMap::new())
+}
 
-    // expand AST into PL
-    debug::log_stage(debug::Stage::Semantic(debug::StageSemantic::AstExpand));
-    let root_module_def = ast_expand::expand_module_def(module_tree)?;
-    debug::log_entry(|| debug::DebugEntryKind::ReprPl(root_module_def.clone()));
+/// Runs semantic analysis on the query, with the columns of some tables of the
+/// database known in advance, rather than inferred from their references.
+pub fn resolve_with_table_columns(
+    module_tree: pr::ModuleDef,
+    table_columns: &HashMap<String, Vec<String>>,
+) -> Result<RootModule, Errors> {
+    let root_module_def = expand(module_tree)?;
 
     // init new root module
     let mut root_module = RootModule {
         module: Module::new_root(),
         ..Default::default()
     };
+    for (table, columns) in table_columns {
+        let columns = (columns.iter())
+            .map(|name| pr::TyTupleField::Single(Some(name.clone()), None))
+            .collect();
+        let decl = DeclKind::TableDecl(TableDecl {
+            ty: Some(pr::Ty::relation(columns)),
+            expr: TableExpr::LocalTable,
+        });
+        let fq_table =
+            pr::Ident::from_name(NS_DEFAULT_DB) + pr::Ident::from_path(table.split('.').collect());
+        root_module.module.insert(fq_table, decl.into())?;
+    }
     let mut resolver = Resolver::new(&mut root_module);
 
     // resolve the module def into the root module
     debug::log_stage(debug::Stage::Semantic(debug::StageSemantic::Resolver));
     resolver.fold_statements(root_module_def.stmts)?;
+    if !resolver.errors.is_empty() {
+        return Err(Errors(resolver.errors));
+    }
     debug::log_entry(|| debug::DebugEntryKind::ReprDecl(root_module.clone()));
 
     Ok(root_module)
 }
 
+/// Loads the std module and expands the AST into PL.
+fn expand(mut module_tree: pr::ModuleDef) -> Result<ModuleDef> {
+    load_std_lib(&mut module_tree);
+
+    debug::log_stage(debug::Stage::Semantic(debug::StageSemantic::AstExpand));
+    let mut root_module_def = ast_expand::expand_module_def(module_tree)?;
+    root_module_def.stmts = stdlib_alias::canonicalize_aliases(root_module_def.stmts)?;
+    debug::log_entry(|| debug::DebugEntryKind::ReprPl(root_module_def.clone()));
+
+    Ok(root_module_def)
+}
+
+/// Runs semantic analysis on a query that changes over time, resolving again
+/// only the top-level statements from the first one that changed since the
+/// previous query.
+///
+/// A statement may reference any statement before it, so all statements after
+/// a changed one are resolved again, even if they haven't changed themselves.
+/// Statements are compared with their spans, so a statement that moved within
+/// the source is resolved again too. The std module is the first statement,
+/// so it is parsed and resolved only once.
+#[derive(Default)]
+pub struct IncrementalResolver {
+    /// The std module, as parsed by the first query.
+    std: Option<pr::Stmt>,
+
+    /// Top-level statements of the previous query, expanded into PL.
+    stmts: Vec<Stmt>,
+
+    /// Resolution state before each statement of `stmts`, and after the last
+    /// one.
+    checkpoints: Vec<(RootModule, resolver::ResolverState)>,
+}
+
+impl IncrementalResolver {
+    /// Runs semantic analysis on the query. Returns the resolved root module,
+    /// and the number of top-level statements that had to be resolved.
+    pub fn resolve(
+        &mut self,
+        mut module_tree: pr::ModuleDef,
+    ) -> Result<(RootModule, usize), Errors> {
+        if !module_tree.stmts.iter().any(|s| is_mod_def_for(s, NS_STD)) {
+            let std = self.std.get_or_insert_with(|| {
+                let mut module_tree = pr::ModuleDef {
+                    name: String::new(),
+                    stmts: Vec::new(),
+                };
+                load_std_lib(&mut module_tree);
+                module_tree.stmts.remove(0)
+            });
+            module_tree.stmts.insert(0, std.clone());
+        }
+        let stmts = expand(module_tree)?.stmts;
+
+        let unchanged = zip(&self.stmts, &stmts).take_while(|(a, b)| a == b).count();
+        self.checkpoints.truncate(unchanged + 1);
+        if self.checkpoints.is_empty() {
+            let root_module = RootModule {
+                module: Module::new_root(),
+                ..Default::default()
+            };
+            self.checkpoints.push((root_module, Default::default()));
+        }
+        let (mut root_module, mut state) = self.checkpoints.last().unwrap().clone();
+
+        debug::log_stage(debug::Stage::Semantic(debug::StageSemantic::Resolver));
+        for stmt in &stmts[unchanged..] {
+            let mut resolver = Resolver::resume(&mut root_module, state);
+            resolver.fold_statements(vec![stmt.clone()])?;
+            state = resolver.suspend();
+
+            self.checkpoints.push((root_module.clone(), state.clone()));
+        }
+        let resolved = stmts.len() - unchanged;
+        self.stmts = stmts;
+
+        if !state.errors.is_empty() {
+            return Err(Errors(state.errors));
+        }
+        debug::log_entry(|| debug::DebugEntryKind::ReprDecl(root_module.clone()));
+
+        Ok((root_module, resolved))
+    }
+}
+
 /// Preferred way of injecting std module.
 pub fn load_std_lib(module_tree: &mut pr::ModuleDef) {
     if !module_tree.stmts.iter().any(|s| is_mod_def_for(s, NS_STD)) {
@@ -178,12 +298,12 @@ pub mod test {
 
     pub fn parse_resolve_and_lower(query: &str) -> Result<RelationalQuery, Errors> {
         let source_tree = query.into();
-        Ok(resolve_and_lower(parse(&source_tree)?, &[], None)?)
+        resolve_and_lower(parse(&source_tree)?, &[], None)
     }
 
     pub fn parse_and_resolve(query: &str) -> Result<RootModule, Errors> {
         let source_tree = query.into();
-        Ok(resolve(parse(&source_tree)?)?)
+        resolve(parse(&source_tree)?)
     }
 
     #[test]
@@ -229,7 +349,6 @@ pub mod test {
         select {a, a, a = a + 1}
         "###).unwrap().relation.columns, @r"
         - Single: ~
-        - Single: ~
         - Single: a
         ")
     }
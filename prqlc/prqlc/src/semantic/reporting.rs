@@ -290,6 +290,24 @@ impl PlFold for FrameCollector {
                                 tcc.push(c.column.id.unwrap());
                             }
                         }
+                        pl::TransformKind::Pivot {
+                            ref on,
+                            ref using,
+                            ref by,
+                        } => {
+                            let exprs = std::iter::once(on.as_ref()).chain(using).chain(by);
+                            tcc.extend(exprs.filter_map(|e| e.id));
+                        }
+                        pl::TransformKind::Fill {
+                            ref columns,
+                            ref by,
+                            ref sort,
+                            ..
+                        } => {
+                            let sort = sort.iter().map(|c| c.column.as_ref());
+                            let exprs = columns.iter().chain(by).chain(sort);
+                            tcc.extend(exprs.filter_map(|e| e.id));
+                        }
                     };
 
                     tcc
@@ -1,11 +1,12 @@
 use itertools::Itertools;
 
+use crate::codegen::write_ty;
 use crate::ir::decl::{DeclKind, Module};
 use crate::ir::pl;
 use crate::ir::pl::PlFold;
 use crate::pr::{Ty, TyKind, TyTupleField};
-use crate::semantic::resolver::{flatten, types, Resolver};
-use crate::semantic::{NS_INFER, NS_SELF, NS_THAT, NS_THIS};
+use crate::semantic::resolver::{flatten, names, types, Resolver};
+use crate::semantic::{write_pl, NS_INFER, NS_SELF, NS_THAT, NS_THIS};
 use crate::utils::IdGenerator;
 use crate::Result;
 use crate::{Error, Reason, Span, WithErrorInfo};
@@ -75,7 +76,15 @@ impl pl::PlFold for Resolver<'_> {
         let r = match node.kind {
             pl::ExprKind::Ident(ident) => {
                 log::debug!("resolving ident {ident}...");
-                let fq_ident = self.resolve_ident(&ident).with_span(node.span)?;
+                let fq_ident = match self.resolve_ident(&ident) {
+                    Ok(fq_ident) => fq_ident,
+                    Err(err) => {
+                        let Some(field) = self.resolve_struct_field(&ident, node.span)? else {
+                            return Err(err.with_span(node.span));
+                        };
+                        return self.finish_expr_resolve(field, id, *alias, *span);
+                    }
+                };
                 log::debug!("... resolved to {fq_ident}");
                 let entry = self.root_mod.module.get(&fq_ident).unwrap();
                 log::debug!("... which is {entry}");
@@ -114,6 +123,17 @@ impl pl::PlFold for Resolver<'_> {
 
                             if self.in_func_call_name {
                                 expr
+                            } else if fq_ident.to_string() == "std.row_number" {
+                                // sugar: bare `row_number` numbers rows in the
+                                // order of the pipeline. The operator gets no
+                                // args, which tells lowering to require a sort.
+                                pl::Expr {
+                                    needs_window: true,
+                                    ..pl::Expr::new(pl::ExprKind::RqOperator {
+                                        name: "std.row_number".to_string(),
+                                        args: vec![],
+                                    })
+                                }
                             } else {
                                 self.fold_expr(expr)?
                             }
@@ -172,6 +192,7 @@ impl pl::PlFold for Resolver<'_> {
                 let func = name.try_cast(|n| n.into_func(), None, "a function")?;
 
                 // fold function
+                let args = self.expand_spread_args(args)?;
                 let func = self.apply_args_to_closure(func, args, named_args)?;
                 self.fold_function(func, id, *span)?
             }
@@ -196,6 +217,25 @@ impl pl::PlFold for Resolver<'_> {
                 }
             }
 
+            pl::ExprKind::Cast { expr, ty } => {
+                let (expr, ty) = self.fold_cast(*expr, ty)?;
+
+                if !ty.kind.is_primitive() {
+                    return Err(Error::new_simple(format!(
+                        "cannot cast to `{}`, which is not a scalar type",
+                        write_ty(&ty)
+                    ))
+                    .push_hint("cast to one of int, float, bool, text, date, time or timestamp")
+                    .with_span(ty.span.or(*span)));
+                }
+
+                let expr = Box::new(expr);
+                pl::Expr {
+                    kind: pl::ExprKind::Cast { expr, ty },
+                    ..node
+                }
+            }
+
             item => pl::Expr {
                 kind: pl::fold_expr_kind(self, item)?,
                 ..node
@@ -247,6 +287,52 @@ impl Resolver<'_> {
         Ok(*r)
     }
 
+    /// Resolves an ident that does not name a declaration as an access of
+    /// fields of a struct column, as in `address.city`.
+    ///
+    /// The longest prefix of the ident that names a column is the struct
+    /// column, and the remaining parts are its nested fields. So a column of
+    /// a relation in scope always takes precedence over a field of a struct
+    /// column of the same name. When the struct column could be a column of
+    /// more than one relation, the access is ambiguous.
+    fn resolve_struct_field(
+        &mut self,
+        ident: &pl::Ident,
+        span: Option<Span>,
+    ) -> Result<Option<pl::Expr>> {
+        let parts = ident.iter().cloned().collect_vec();
+
+        for len in (1..parts.len()).rev() {
+            let column = pl::Ident::from_path(parts[..len].to_vec());
+            let fq_column = match self.resolve_ident(&column) {
+                Ok(fq_column) => fq_column,
+                Err(err) if names::is_ambiguous(&err) => return Err(err.with_span(span)),
+                Err(_) => continue,
+            };
+            let decl = self.root_mod.module.get(&fq_column).unwrap();
+            if !matches!(decl.kind, DeclKind::Column(_)) {
+                return Ok(None);
+            }
+
+            let column = self.fold_expr(pl::Expr {
+                span,
+                ..pl::Expr::new(column)
+            })?;
+            let field = (parts[len..].iter()).fold(column, |expr, field| {
+                let field = pl::Literal::String(field.clone());
+                pl::Expr {
+                    span,
+                    ..pl::Expr::new(pl::ExprKind::RqOperator {
+                        name: "std.struct_field".to_string(),
+                        args: vec![expr, pl::Expr::new(field)],
+                    })
+                }
+            });
+            return Ok(Some(field));
+        }
+        Ok(None)
+    }
+
     pub fn resolve_column_exclusion(&mut self, expr: pl::Expr) -> Result<pl::Expr> {
         let expr = self.fold_expr(expr)?;
         let except = self.coerce_into_tuple(expr)?;
@@ -257,6 +343,31 @@ impl Resolver<'_> {
         }))
     }
 
+    /// Replaces each `...x` in args of a function call with the fields of `x`.
+    fn expand_spread_args(&mut self, args: Vec<pl::Expr>) -> Result<Vec<pl::Expr>> {
+        let mut res = Vec::with_capacity(args.len());
+        for arg in args {
+            let pl::ExprKind::Spread(spread) = arg.kind else {
+                res.push(arg);
+                continue;
+            };
+
+            let spread = self.fold_expr(*spread)?;
+            match spread.kind {
+                pl::ExprKind::Tuple(fields) | pl::ExprKind::Array(fields) => res.extend(fields),
+                _ => {
+                    return Err(Error::new_simple(format!(
+                        "cannot spread `{}` into function arguments",
+                        write_pl(spread)
+                    ))
+                    .push_hint("only tuples and arrays can be spread")
+                    .with_span(arg.span))
+                }
+            }
+        }
+        Ok(res)
+    }
+
     pub fn construct_wildcard_include(&mut self, module_fq_self: &pl::Ident) -> Vec<pl::Expr> {
         let module_fq = module_fq_self.clone().pop().unwrap();
 
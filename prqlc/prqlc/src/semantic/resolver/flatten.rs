@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
 use crate::ir::pl::{
-    fold_column_sorts, fold_transform_kind, ColumnSort, Expr, ExprKind, PlFold, TransformCall,
-    TransformKind, WindowFrame,
+    fold_column_sorts, ColumnSort, Expr, ExprKind, PlFold, TransformCall, TransformKind,
+    WindowFrame,
 };
 use crate::Result;
 
@@ -128,7 +128,7 @@ impl PlFold for Flattener {
                             ..pipeline
                         });
                     }
-                    kind => (self.fold_expr(*t.input)?, fold_transform_kind(self, kind)?),
+                    kind => (self.fold_expr(*t.input)?, self.fold_transform_kind(kind)?),
                 };
 
                 ExprKind::TransformCall(TransformCall {
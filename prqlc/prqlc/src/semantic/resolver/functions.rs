@@ -84,12 +84,22 @@ impl Resolver<'_> {
             // special case: functions that have internal body
 
             if operator_name.starts_with("std.") {
+                let mut args = closure.args;
+                let mut needs_window = needs_window;
+                if operator_name == "std.filtered" {
+                    // the aggregation is computed together with its condition,
+                    // so the window (if any) applies to `filtered` as a whole
+                    if let Some(aggregation) = args.last_mut() {
+                        needs_window |= std::mem::take(&mut aggregation.needs_window);
+                    }
+                }
+
                 Expr {
                     ty: closure.return_ty,
                     needs_window,
                     ..Expr::new(ExprKind::RqOperator {
                         name: operator_name.clone(),
-                        args: closure.args,
+                        args,
                     })
                 }
             } else {
@@ -98,7 +108,24 @@ impl Resolver<'_> {
             }
         } else {
             // base case: materialize
-            self.materialize_function(closure)?
+            if let Some(name) = &closure.name_hint {
+                if self.materializing.contains(name) {
+                    let cycle = super::format_cycle(&self.materializing, name);
+                    return Err(Error::new_simple(format!(
+                        "Recursive functions are not supported: {cycle}"
+                    ))
+                    .push_hint("functions are inlined into the query, so recursion over rows has to be written with `loop`")
+                    .with_span(span));
+                }
+            }
+
+            self.materializing.extend(closure.name_hint.clone());
+            let has_name = closure.name_hint.is_some();
+            let res = self.materialize_function(closure);
+            if has_name {
+                self.materializing.pop();
+            }
+            res?
         };
 
         // pop the env
@@ -212,7 +239,7 @@ impl Resolver<'_> {
             closure.args.push(arg);
             closure.params.insert(closure.args.len() - 1, param);
         }
-        if let Some((name, _)) = named_args.into_iter().next() {
+        if let Some(name) = named_args.into_keys().min() {
             // TODO: report all remaining named_args as separate errors
             return Err(Error::new_simple(format!(
                 "unknown named argument `{name}` to closure {:?}",
@@ -273,6 +300,15 @@ impl Resolver<'_> {
 
                 // add relation frame into scope
                 if partial_application_position.is_none() {
+                    let is_empty_literal =
+                        matches!(&arg.kind, ExprKind::Array(rows) if rows.is_empty());
+                    if arg.lineage.is_none() && is_empty_literal {
+                        return Err(Error::new_simple(
+                            "relation literal must contain at least one row",
+                        )
+                        .push_hint("columns of a relation literal are inferred from its rows")
+                        .with_span(arg.span));
+                    }
                     let frame = arg
                         .lineage
                         .as_ref()
@@ -291,13 +327,22 @@ impl Resolver<'_> {
         // resolve other positional
         for (index, (param, mut arg)) in other {
             if partial_application_position.is_none() {
+                let sorts = is_sort_param(func_name, &param.name);
+                if sorts && !matches!(arg.kind, ExprKind::Tuple(_)) {
+                    arg = self.fold_sort_key(arg, &param.name)?;
+                }
+
                 if let ExprKind::Tuple(fields) = arg.kind {
                     // if this is a tuple, resolve elements separately,
                     // so they can be added to scope, before resolving subsequent elements.
 
                     let mut fields_new = Vec::with_capacity(fields.len());
                     for field in fields {
-                        let field = self.fold_within_namespace(field, &param.name)?;
+                        let field = if sorts {
+                            self.fold_sort_key(field, &param.name)?
+                        } else {
+                            self.fold_within_namespace(field, &param.name)?
+                        };
 
                         // add aliased columns into scope
                         if let Some(alias) = field.alias.clone() {
@@ -373,6 +418,33 @@ impl Resolver<'_> {
         Ok(Ok(arg))
     }
 
+    /// Resolves a column of a sort. A negated column is sorted in descending
+    /// order, rather than negated, so its type need not be a number.
+    fn fold_sort_key(&mut self, key: Expr, param_name: &str) -> Result<Expr> {
+        let ExprKind::FuncCall(call) = &key.kind else {
+            return self.fold_within_namespace(key, param_name);
+        };
+        let is_neg =
+            matches!(&call.name.kind, ExprKind::Ident(name) if name.to_string() == "std.neg");
+        if !is_neg || call.args.len() != 1 {
+            return self.fold_within_namespace(key, param_name);
+        }
+
+        let ExprKind::FuncCall(call) = key.kind else {
+            unreachable!()
+        };
+        let column = call.args.into_iter().next().unwrap();
+        let column = self.fold_within_namespace(column, param_name)?;
+        self.fold_expr(Expr {
+            span: key.span,
+            alias: key.alias,
+            ..Expr::new(ExprKind::RqOperator {
+                name: "std.neg".to_string(),
+                args: vec![column],
+            })
+        })
+    }
+
     fn fold_within_namespace(&mut self, expr: Expr, param_name: &str) -> Result<Expr> {
         let prev_namespace = self.default_namespace.take();
 
@@ -390,6 +462,18 @@ impl Resolver<'_> {
     }
 }
 
+/// Whether a param of a function is a list of columns to sort by, where a
+/// negated column is sorted in descending order.
+fn is_sort_param(func_name: &Option<Ident>, param_name: &str) -> bool {
+    let Some(func_name) = func_name else {
+        return false;
+    };
+    matches!(
+        (func_name.to_string().as_str(), param_name),
+        ("std.sort", "by") | ("std.fill", "sort")
+    )
+}
+
 fn extract_partial_application(mut func: Box<Func>, position: usize) -> Box<Func> {
     // Input:
     // Func {
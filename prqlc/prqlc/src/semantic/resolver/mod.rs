@@ -1,7 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::ir::decl::RootModule;
+use crate::pr::Ident;
 use crate::utils::IdGenerator;
+use crate::Error;
 
 mod expr;
 mod flatten;
@@ -27,11 +29,33 @@ pub struct Resolver<'a> {
     pub id: IdGenerator<usize>,
 
     pub generics: HashMap<(usize, String), Vec<crate::pr::Ty>>,
+
+    /// Imports that are being followed, used to detect circular imports.
+    imports: Vec<Ident>,
+
+    /// Functions whose bodies are being materialized, used to detect recursion.
+    materializing: Vec<Ident>,
+
+    /// Errors of statements that failed to resolve.
+    pub errors: Vec<Error>,
+
+    /// Names of statements that failed to resolve.
+    failed: HashSet<String>,
 }
 
 #[derive(Default, Clone)]
 pub struct ResolverOptions {}
 
+/// State that the resolver carries from one top-level statement to the next,
+/// from which resolution of the following statements can be resumed.
+#[derive(Clone, Default)]
+pub(super) struct ResolverState {
+    id: IdGenerator<usize>,
+    generics: HashMap<(usize, String), Vec<crate::pr::Ty>>,
+    pub errors: Vec<Error>,
+    failed: HashSet<String>,
+}
+
 impl Resolver<'_> {
     pub fn new(root_mod: &mut RootModule) -> Resolver {
         Resolver {
@@ -41,8 +65,42 @@ impl Resolver<'_> {
             in_func_call_name: false,
             id: IdGenerator::new(),
             generics: Default::default(),
+            imports: Vec::new(),
+            materializing: Vec::new(),
+            errors: Vec::new(),
+            failed: HashSet::new(),
         }
     }
+
+    /// Creates a resolver that continues from the state of a resolver that was
+    /// suspended between two statements.
+    pub(super) fn resume(root_mod: &mut RootModule, state: ResolverState) -> Resolver {
+        Resolver {
+            id: state.id,
+            generics: state.generics,
+            errors: state.errors,
+            failed: state.failed,
+            ..Resolver::new(root_mod)
+        }
+    }
+
+    pub(super) fn suspend(&self) -> ResolverState {
+        ResolverState {
+            id: self.id.clone(),
+            generics: self.generics.clone(),
+            errors: self.errors.clone(),
+            failed: self.failed.clone(),
+        }
+    }
+}
+
+/// Formats a cycle of names, such as `a.f -> b.g -> a.f`.
+fn format_cycle(chain: &[Ident], repeated: &Ident) -> String {
+    let start = chain.iter().position(|i| i == repeated).unwrap_or_default();
+    (chain[start..].iter().chain([repeated]))
+        .map(|i| format!("`{i}`"))
+        .collect::<Vec<_>>()
+        .join(" -> ")
 }
 
 #[cfg(test)]
@@ -8,6 +8,7 @@ use crate::ir::pl::{Expr, ExprKind};
 use crate::pr::Ident;
 use crate::semantic::{NS_INFER, NS_INFER_MODULE, NS_SELF, NS_THAT, NS_THIS};
 use crate::Error;
+use crate::Reason;
 use crate::Result;
 use crate::WithErrorInfo;
 
@@ -34,7 +35,16 @@ impl Resolver<'_> {
                 let decl = self.root_mod.module.get(fq_ident).unwrap();
                 if let DeclKind::Import(target) = &decl.kind {
                     let target = target.clone();
-                    return self.resolve_ident(&target);
+
+                    if self.imports.contains(fq_ident) {
+                        let cycle = super::format_cycle(&self.imports, fq_ident);
+                        return Err(Error::new_simple(format!("Circular import: {cycle}")));
+                    }
+
+                    self.imports.push(fq_ident.clone());
+                    let res = self.resolve_ident(&target);
+                    self.imports.pop();
+                    return res;
                 }
             }
             Err(e) => {
@@ -239,6 +249,13 @@ impl Resolver<'_> {
     }
 }
 
+/// Whether resolving a name failed because it names more than one declaration.
+pub(super) fn is_ambiguous(err: &Error) -> bool {
+    matches!(&err.reason, Reason::Simple(reason) if reason == AMBIGUOUS_NAME)
+}
+
+const AMBIGUOUS_NAME: &str = "Ambiguous name";
+
 fn ambiguous_error(idents: HashSet<Ident>, replace_name: Option<&String>) -> Error {
     let all_this = idents.iter().all(|d| d.starts_with_part(NS_THIS));
 
@@ -260,5 +277,5 @@ fn ambiguous_error(idents: HashSet<Ident>, replace_name: Option<&String>) -> Err
     }
     chunks.sort();
     let hint = format!("could be any of: {}", chunks.join(", "));
-    Error::new_simple("Ambiguous name").push_hint(hint)
+    Error::new_simple(AMBIGUOUS_NAME).push_hint(hint)
 }
@@ -4,6 +4,7 @@ use itertools::Itertools;
 
 use crate::ir::constant::{ConstExpr, ConstExprKind};
 use crate::ir::pl::{Expr, ExprKind, Literal, PlFold};
+use crate::lr::ValueAndUnit;
 use crate::{Error, Result, WithErrorInfo};
 
 impl super::Resolver<'_> {
@@ -86,6 +87,30 @@ fn static_eval_rq_operator(mut expr: Expr) -> Expr {
                 return args.remove(1);
             }
         }
+        "std.add" | "std.sub" | "std.mul" | "std.div_i" | "std.div_f" | "std.mod" => {
+            if let (ExprKind::Literal(left), ExprKind::Literal(right)) =
+                (&args[0].kind, &args[1].kind)
+            {
+                if let Some(res) = static_eval_arithmetic(&name, left, right) {
+                    return Expr::new(res);
+                }
+            }
+        }
+        "std.gt" | "std.gte" | "std.lt" | "std.lte" => {
+            if let (ExprKind::Literal(left), ExprKind::Literal(right)) =
+                (&args[0].kind, &args[1].kind)
+            {
+                if let Some(ordering) = compare_numbers(left, right) {
+                    let res = match name.as_str() {
+                        "std.gt" => ordering.is_gt(),
+                        "std.gte" => ordering.is_ge(),
+                        "std.lt" => ordering.is_lt(),
+                        _ => ordering.is_le(),
+                    };
+                    return Expr::new(Literal::Boolean(res));
+                }
+            }
+        }
 
         _ => {}
     };
@@ -93,6 +118,106 @@ fn static_eval_rq_operator(mut expr: Expr) -> Expr {
     expr
 }
 
+/// Evaluates arithmetic over numeric literals. Returns `None` when the result
+/// would be an error in the database, such as division by zero or an overflow,
+/// so that the expression is left for the database to evaluate.
+fn static_eval_arithmetic(name: &str, left: &Literal, right: &Literal) -> Option<Literal> {
+    Some(match (left, right) {
+        (Literal::Integer(l), Literal::Integer(r)) => Literal::Integer(match name {
+            "std.add" => l.checked_add(*r)?,
+            "std.sub" => l.checked_sub(*r)?,
+            "std.mul" => l.checked_mul(*r)?,
+            // truncates towards zero, like `div_i` does in SQL
+            "std.div_i" => l.checked_div(*r)?,
+            "std.mod" => l.checked_rem(*r)?,
+            // integer division of `/` differs between databases
+            _ => return None,
+        }),
+        (Literal::Integer(_) | Literal::Float(_), Literal::Integer(_) | Literal::Float(_)) => {
+            let (l, r) = (as_float(left)?, as_float(right)?);
+            let res = match name {
+                "std.add" => l + r,
+                "std.sub" => l - r,
+                "std.mul" => l * r,
+                "std.div_f" if r != 0.0 => l / r,
+                _ => return None,
+            };
+            if !res.is_finite() {
+                return None;
+            }
+            Literal::Float(res)
+        }
+        (Literal::ValueAndUnit(l), Literal::ValueAndUnit(r)) => {
+            Literal::ValueAndUnit(static_eval_interval_arithmetic(name, l, r)?)
+        }
+        _ => return None,
+    })
+}
+
+/// Combines intervals whose units convert exactly into one another, such as
+/// `1years + 6months` into `18months`, expressed in the finer of the two
+/// units. Months don't convert to days, nor days to hours (because of daylight
+/// saving time), so such intervals are left for the database to add.
+fn static_eval_interval_arithmetic(
+    name: &str,
+    left: &ValueAndUnit,
+    right: &ValueAndUnit,
+) -> Option<ValueAndUnit> {
+    let (left_base, left_size) = interval_unit_size(&left.unit)?;
+    let (right_base, right_size) = interval_unit_size(&right.unit)?;
+    if left_base != right_base {
+        return None;
+    }
+
+    let (unit, size) = if left_size <= right_size {
+        (&left.unit, left_size)
+    } else {
+        (&right.unit, right_size)
+    };
+    let l = left.n.checked_mul(left_size / size)?;
+    let r = right.n.checked_mul(right_size / size)?;
+    let n = match name {
+        "std.add" => l.checked_add(r)?,
+        "std.sub" => l.checked_sub(r)?,
+        _ => return None,
+    };
+    Some(ValueAndUnit {
+        n,
+        unit: unit.clone(),
+    })
+}
+
+/// The finest unit an interval unit converts to exactly, and its size in it.
+fn interval_unit_size(unit: &str) -> Option<(&'static str, i64)> {
+    Some(match unit {
+        "years" => ("months", 12),
+        "months" => ("months", 1),
+        "weeks" => ("days", 7),
+        "days" => ("days", 1),
+        "hours" => ("microseconds", 3_600_000_000),
+        "minutes" => ("microseconds", 60_000_000),
+        "seconds" => ("microseconds", 1_000_000),
+        "milliseconds" => ("microseconds", 1_000),
+        "microseconds" => ("microseconds", 1),
+        _ => return None,
+    })
+}
+
+fn compare_numbers(left: &Literal, right: &Literal) -> Option<std::cmp::Ordering> {
+    match (left, right) {
+        (Literal::Integer(l), Literal::Integer(r)) => Some(l.cmp(r)),
+        _ => as_float(left)?.partial_cmp(&as_float(right)?),
+    }
+}
+
+fn as_float(lit: &Literal) -> Option<f64> {
+    match lit {
+        Literal::Integer(i) => Some(*i as f64),
+        Literal::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
 fn static_eval_case(mut expr: Expr) -> Expr {
     let items = expr.kind.into_case().unwrap();
     let mut res = Vec::with_capacity(items.len());
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
 
 use crate::ir::decl::{Decl, DeclKind, Module, TableDecl, TableExpr};
 use crate::ir::pl::*;
@@ -7,137 +8,183 @@ use crate::Result;
 use crate::WithErrorInfo;
 
 impl super::Resolver<'_> {
-    // entry point to the resolver
+    /// Entry point to the resolver. Resolves statements, collecting errors into `self.errors` instead of
+    /// bailing on the first one, so independent statements are all checked.
+    ///
+    /// Errors of statements that reference a statement that has already
+    /// failed are omitted, since they are most likely caused by that failure.
     pub fn fold_statements(&mut self, stmts: Vec<Stmt>) -> Result<()> {
-        for mut stmt in stmts {
-            stmt.id = Some(self.id.gen());
-            if let Some(span) = stmt.span {
-                self.root_mod.span_map.insert(stmt.id.unwrap(), span);
+        for stmt in stmts {
+            let name = stmt.name().to_string();
+            let references = referenced_names(&stmt);
+
+            let module_path = self.current_module_path.clone();
+            if let Err(err) = self.fold_statement(stmt) {
+                self.current_module_path = module_path;
+                self.default_namespace = None;
+                self.in_func_call_name = false;
+                self.imports.clear();
+                self.materializing.clear();
+
+                if references.is_disjoint(&self.failed) {
+                    self.errors.push(err);
+                }
+                self.failed.insert(name);
             }
+        }
+        Ok(())
+    }
 
-            let ident = Ident {
-                path: self.current_module_path.clone(),
-                name: stmt.name().to_string(),
-            };
-
-            let mut def = match stmt.kind {
-                StmtKind::QueryDef(d) => {
-                    let decl = DeclKind::QueryDef(*d);
-                    self.root_mod
-                        .declare(ident, decl, stmt.id, Vec::new())
-                        .with_span(stmt.span)?;
-                    continue;
-                }
-                StmtKind::VarDef(var_def) => self.fold_var_def(var_def)?,
-                StmtKind::TypeDef(ty_def) => {
-                    let value = if let Some(value) = ty_def.value {
-                        value
-                    } else {
-                        Ty::new(Literal::Null)
-                    };
-
-                    let ty = fold_type_opt(self, Some(value))?.unwrap();
-                    let mut ty = super::types::normalize_type(ty);
-                    ty.name = Some(ident.name.clone());
-
-                    let decl = DeclKind::Ty(ty);
-
-                    self.root_mod
-                        .declare(ident, decl, stmt.id, stmt.annotations)
-                        .with_span(stmt.span)?;
-                    continue;
-                }
-                StmtKind::ModuleDef(module_def) => {
-                    self.current_module_path.push(ident.name);
-
-                    let decl = Decl {
-                        declared_at: stmt.id,
-                        kind: DeclKind::Module(Module {
-                            names: HashMap::new(),
-                            redirects: Vec::new(),
-                            shadowed: None,
-                        }),
-                        annotations: stmt.annotations,
-                        ..Default::default()
-                    };
-                    let ident = Ident::from_path(self.current_module_path.clone());
-                    self.root_mod
-                        .module
-                        .insert(ident, decl)
-                        .with_span(stmt.span)?;
-
-                    self.fold_statements(module_def.stmts)?;
-                    self.current_module_path.pop();
-                    continue;
-                }
-                StmtKind::ImportDef(target) => {
-                    let decl = Decl {
-                        declared_at: stmt.id,
-                        kind: DeclKind::Import(target.name),
-                        annotations: stmt.annotations,
-                        ..Default::default()
-                    };
-
-                    self.root_mod
-                        .module
-                        .insert(ident, decl)
-                        .with_span(stmt.span)?;
-                    continue;
-                }
-            };
+    fn fold_statement(&mut self, mut stmt: Stmt) -> Result<()> {
+        stmt.id = Some(self.id.gen());
+        if let Some(span) = stmt.span {
+            self.root_mod.span_map.insert(stmt.id.unwrap(), span);
+        }
 
-            if def.name == "main" {
-                def.ty = Some(Ty::new(TyKind::Ident(Ident::from_path(vec![
-                    "std", "relation",
-                ]))));
+        let ident = Ident {
+            path: self.current_module_path.clone(),
+            name: stmt.name().to_string(),
+        };
+
+        let mut def = match stmt.kind {
+            StmtKind::QueryDef(d) => {
+                let decl = DeclKind::QueryDef(*d);
+                self.root_mod
+                    .declare(ident, decl, stmt.id, Vec::new())
+                    .with_span(stmt.span)?;
+                return Ok(());
             }
-
-            if let Some(ExprKind::Func(closure)) = def.value.as_mut().map(|x| &mut x.kind) {
-                if closure.name_hint.is_none() {
-                    closure.name_hint = Some(ident.clone());
-                }
+            StmtKind::VarDef(var_def) => self.fold_var_def(var_def)?,
+            StmtKind::TypeDef(ty_def) => {
+                let value = if let Some(value) = ty_def.value {
+                    value
+                } else {
+                    Ty::new(Literal::Null)
+                };
+
+                let ty = fold_type_opt(self, Some(value))?.unwrap();
+                let mut ty = super::types::normalize_type(ty);
+                ty.name = Some(ident.name.clone());
+
+                let decl = DeclKind::Ty(ty);
+
+                self.root_mod
+                    .declare(ident, decl, stmt.id, stmt.annotations)
+                    .with_span(stmt.span)?;
+                return Ok(());
+            }
+            StmtKind::ModuleDef(module_def) => {
+                self.current_module_path.push(ident.name);
+
+                let decl = Decl {
+                    declared_at: stmt.id,
+                    kind: DeclKind::Module(Module {
+                        names: HashMap::new(),
+                        redirects: Vec::new(),
+                        shadowed: None,
+                    }),
+                    annotations: stmt.annotations,
+                    ..Default::default()
+                };
+                let ident = Ident::from_path(self.current_module_path.clone());
+                self.root_mod
+                    .module
+                    .insert(ident, decl)
+                    .with_span(stmt.span)?;
+
+                let res = self.fold_statements(module_def.stmts);
+                self.current_module_path.pop();
+                res?;
+                return Ok(());
             }
+            StmtKind::ImportDef(target) => {
+                let decl = Decl {
+                    declared_at: stmt.id,
+                    kind: DeclKind::Import(target.name),
+                    annotations: stmt.annotations,
+                    ..Default::default()
+                };
+
+                self.root_mod
+                    .module
+                    .insert(ident, decl)
+                    .with_span(stmt.span)?;
+                return Ok(());
+            }
+        };
 
-            let expected_ty = fold_type_opt(self, def.ty)?;
+        if def.name == "main" {
+            def.ty = Some(Ty::new(TyKind::Ident(Ident::from_path(vec![
+                "std", "relation",
+            ]))));
+        }
 
-            let decl = match def.value {
-                Some(mut def_value) => {
-                    // var value is provided
+        if let Some(ExprKind::Func(closure)) = def.value.as_mut().map(|x| &mut x.kind) {
+            if closure.name_hint.is_none() {
+                closure.name_hint = Some(ident.clone());
+            }
+        }
 
-                    // validate type
-                    if expected_ty.is_some() {
-                        let who = || Some(def.name.clone());
-                        self.validate_expr_type(&mut def_value, expected_ty.as_ref(), &who)?;
-                    }
+        let expected_ty = fold_type_opt(self, def.ty)?;
+
+        let decl = match def.value {
+            Some(mut def_value) => {
+                // var value is provided
 
-                    prepare_expr_decl(def_value)
+                // validate type
+                if expected_ty.is_some() {
+                    let who = || Some(def.name.clone());
+                    self.validate_expr_type(&mut def_value, expected_ty.as_ref(), &who)?;
                 }
-                None => {
-                    // var value is not provided
-
-                    // is this a relation?
-                    if expected_ty.as_ref().map_or(false, |t| t.is_relation()) {
-                        // treat this var as a TableDecl
-                        DeclKind::TableDecl(TableDecl {
-                            ty: expected_ty,
-                            expr: TableExpr::LocalTable,
-                        })
-                    } else {
-                        // treat this var as a param
-                        let mut expr = Box::new(Expr::new(ExprKind::Param(def.name)));
-                        expr.ty = expected_ty;
-                        DeclKind::Expr(expr)
-                    }
+
+                prepare_expr_decl(def_value)
+            }
+            None => {
+                // var value is not provided
+
+                // is this a relation?
+                if expected_ty.as_ref().map_or(false, |t| t.is_relation()) {
+                    // treat this var as a TableDecl
+                    DeclKind::TableDecl(TableDecl {
+                        ty: expected_ty,
+                        expr: TableExpr::LocalTable,
+                    })
+                } else {
+                    // treat this var as a param
+                    let mut expr = Box::new(Expr::new(ExprKind::Param(def.name)));
+                    expr.ty = expected_ty;
+                    DeclKind::Expr(expr)
                 }
-            };
-            self.root_mod
-                .declare(ident, decl, stmt.id, stmt.annotations)
-                .with_span(stmt.span)?;
-        }
+            }
+        };
+        self.root_mod
+            .declare(ident, decl, stmt.id, stmt.annotations)
+            .with_span(stmt.span)?;
         Ok(())
     }
 }
 
+/// Names of all identifiers that a var def statement references.
+fn referenced_names(stmt: &Stmt) -> HashSet<String> {
+    let mut collector = NameCollector::default();
+    if let StmtKind::VarDef(_) = &stmt.kind {
+        let _ = collector.visit_stmt(stmt);
+    }
+    collector.names
+}
+
+#[derive(Default)]
+struct NameCollector {
+    names: HashSet<String>,
+}
+
+impl PlVisitor for NameCollector {
+    fn visit_ident(&mut self, ident: &Ident) -> ControlFlow<()> {
+        self.names.extend(ident.iter().cloned());
+        ControlFlow::Continue(())
+    }
+}
+
 fn prepare_expr_decl(value: Box<Expr>) -> DeclKind {
     match &value.lineage {
         Some(frame) => {
@@ -7,7 +7,7 @@ use serde::Deserialize;
 use super::types::{ty_tuple_kind, type_intersection};
 use super::Resolver;
 use crate::ir::decl::{Decl, DeclKind, Module};
-use crate::ir::generic::{SortDirection, WindowKind};
+use crate::ir::generic::{NullsOrder, SortDirection, WindowKind};
 use crate::ir::pl::*;
 use crate::pr::{Ty, TyKind, TyTupleField};
 use crate::semantic::ast_expand::{restrict_null_literal, try_restrict_range};
@@ -25,7 +25,8 @@ impl Resolver<'_> {
             "select" => {
                 let [assigns, tbl] = unpack::<2>(func.args);
 
-                let assigns = Box::new(self.coerce_into_tuple(assigns)?);
+                let assigns = self.coerce_into_tuple(assigns)?;
+                let assigns = Box::new(self.expand_column_patterns(assigns, &tbl)?);
                 (TransformKind::Select { assigns }, tbl)
             }
             "filter" => {
@@ -37,9 +38,42 @@ impl Resolver<'_> {
             "derive" => {
                 let [assigns, tbl] = unpack::<2>(func.args);
 
-                let assigns = Box::new(self.coerce_into_tuple(assigns)?);
+                let assigns = self.coerce_into_tuple(assigns)?;
+                let (assigns, tbl) = self.join_destructured(assigns, tbl)?;
+                let assigns = Box::new(assigns);
                 (TransformKind::Derive { assigns }, tbl)
             }
+            "unnest" => {
+                let [columns, tbl] = unpack::<2>(func.args);
+
+                // sugar for `derive {column = array.unnest column}`
+                let mut assigns = self.coerce_into_tuple(columns)?;
+                for field in assigns.kind.as_tuple_mut().unwrap() {
+                    let column = std::mem::replace(field, Expr::new(Literal::Null));
+                    let alias = (column.alias.clone())
+                        .or_else(|| column.kind.as_ident().map(|i| i.name.clone()));
+
+                    *field = Expr {
+                        id: Some(self.id.gen()),
+                        alias,
+                        span: column.span,
+                        ty: column.ty.clone(),
+                        ..Expr::new(ExprKind::RqOperator {
+                            name: "std.array.unnest".to_string(),
+                            args: vec![Expr {
+                                alias: None,
+                                ..column
+                            }],
+                        })
+                    };
+                }
+                (
+                    TransformKind::Derive {
+                        assigns: Box::new(assigns),
+                    },
+                    tbl,
+                )
+            }
             "aggregate" => {
                 let [assigns, tbl] = unpack::<2>(func.args);
 
@@ -47,24 +81,28 @@ impl Resolver<'_> {
                 (TransformKind::Aggregate { assigns }, tbl)
             }
             "sort" => {
-                let [by, tbl] = unpack::<2>(func.args);
+                let [nulls, by, tbl] = unpack::<3>(func.args);
 
-                let by = self
-                    .coerce_into_tuple(by)?
-                    .try_cast(|x| x.into_tuple(), Some("sort"), "tuple")?
-                    .into_iter()
-                    .map(|expr| {
-                        let (column, direction) = match expr.kind {
-                            ExprKind::RqOperator { name, mut args } if name == "std.neg" => {
-                                (args.remove(0), SortDirection::Desc)
-                            }
-                            _ => (expr, SortDirection::default()),
-                        };
-                        let column = Box::new(column);
+                let nulls = {
+                    let span = nulls.span;
+                    let ident = nulls.try_cast(ExprKind::into_ident, Some("nulls"), "ident")?;
 
-                        ColumnSort { direction, column }
-                    })
-                    .collect();
+                    match ident.to_string().as_str() {
+                        "default" => None,
+                        "first" => Some(NullsOrder::First),
+                        "last" => Some(NullsOrder::Last),
+                        found => {
+                            return Err(Error::new(Reason::Expected {
+                                who: Some("`nulls`".to_string()),
+                                expected: "first, last or default".to_string(),
+                                found: found.to_string(),
+                            })
+                            .with_span(span))
+                        }
+                    }
+                };
+
+                let by = self.column_sorts(by, "sort", nulls)?;
 
                 (TransformKind::Sort { by }, tbl)
             }
@@ -217,12 +255,7 @@ impl Resolver<'_> {
                 } else {
                     (WindowKind::Rows, None, None)
                 };
-                // let start = Expr::new(start.map_or(Literal::Null, Literal::Integer));
-                // let end = Expr::new(end.map_or(Literal::Null, Literal::Integer));
-                let range = Range {
-                    start: start.map(Literal::Integer).map(Expr::new).map(Box::new),
-                    end: end.map(Literal::Integer).map(Expr::new).map(Box::new),
-                };
+                let range = range_from_ints(start, end);
 
                 let pipeline = self.fold_by_simulating_eval(pipeline, &tbl)?;
 
@@ -245,12 +278,119 @@ impl Resolver<'_> {
 
                 (TransformKind::Loop(Box::new(pipeline)), tbl)
             }
+            "pivot" => {
+                let [by, on, using, tbl] = unpack::<4>(func.args);
+
+                let values = pivot_values(&on)?;
+                let using = self.coerce_into_tuple(using)?.kind.into_tuple().unwrap();
+                let by = self.coerce_into_tuple(by)?.kind.into_tuple().unwrap();
+
+                if using.len() > 1 {
+                    if let Some(unnamed) = using.iter().find(|e| e.alias.is_none()) {
+                        return Err(Error::new_simple(
+                            "multiple aggregations in `pivot` require names",
+                        )
+                        .push_hint("name them, as in `{total = sum amount, n = count this}`")
+                        .with_span(unnamed.span));
+                    }
+                }
+
+                // one column for each of the values and aggregations
+                let mut cells = Vec::with_capacity(values.len() * using.len());
+                for value in &values {
+                    for aggregation in &using {
+                        let name = match &aggregation.alias {
+                            Some(alias) => format!("{alias}_{value}"),
+                            None => value.clone(),
+                        };
+                        cells.push(Expr {
+                            id: Some(self.id.gen()),
+                            alias: Some(name),
+                            ..aggregation.clone()
+                        });
+                    }
+                }
+
+                let kind = TransformKind::Pivot {
+                    on: Box::new(on),
+                    using: cells,
+                    by,
+                };
+                (kind, tbl)
+            }
+            "fill" => {
+                let [by, sort, direction, columns, tbl] = unpack::<5>(func.args);
+
+                let direction = {
+                    let span = direction.span;
+                    let ident =
+                        direction.try_cast(ExprKind::into_ident, Some("direction"), "ident")?;
+
+                    match ident.to_string().as_str() {
+                        "forward" => FillDir::Forward,
+                        "backward" => FillDir::Backward,
+                        found => {
+                            return Err(Error::new(Reason::Expected {
+                                who: Some("`direction`".to_string()),
+                                expected: "forward or backward".to_string(),
+                                found: found.to_string(),
+                            })
+                            .with_span(span))
+                        }
+                    }
+                };
+
+                let sort = self.column_sorts(sort, "fill", None)?;
+                if sort.is_empty() {
+                    return Err(Error::new_simple(
+                        "`fill` requires an ordering, since it depends on the order of rows",
+                    )
+                    .push_hint("add a `sort` argument, as in `fill {price} sort:{timestamp}`")
+                    .with_span(columns.span));
+                }
+
+                let by = self.coerce_into_tuple(by)?.kind.into_tuple().unwrap();
+
+                // each column is replaced by a column of the same name
+                let columns = self.coerce_into_tuple(columns)?.kind.into_tuple().unwrap();
+                let columns = (columns.into_iter())
+                    .map(|column| {
+                        let name = (column.alias.clone())
+                            .or_else(|| column.kind.as_ident().map(|i| i.name.clone()));
+                        let Some(name) = name else {
+                            return Err(Error::new_simple("`fill` expects columns")
+                                .push_hint("name the expression, as in `fill {price = x + y}`")
+                                .with_span(column.span));
+                        };
+                        Ok(Expr {
+                            id: Some(self.id.gen()),
+                            alias: Some(name),
+                            ..column
+                        })
+                    })
+                    .try_collect()?;
+
+                let kind = TransformKind::Fill {
+                    columns,
+                    by,
+                    sort,
+                    direction,
+                };
+                (kind, tbl)
+            }
 
             "in" => {
                 // yes, this is not a transform, but this is the most appropriate place for it
 
                 let [pattern, value] = unpack::<2>(func.args);
 
+                if pattern.ty.as_ref().map_or(false, |x| x.is_relation()) {
+                    return Ok(Expr::new(ExprKind::RqOperator {
+                        name: "std.relation_in".to_string(),
+                        args: vec![value, pattern],
+                    }));
+                }
+
                 if pattern.ty.as_ref().map_or(false, |x| x.kind.is_array()) {
                     return Ok(Expr::new(ExprKind::RqOperator {
                         name: "std.array_in".to_string(),
@@ -327,6 +467,17 @@ impl Resolver<'_> {
                 let [a, b] = unpack::<2>(func.args);
                 let a = a.kind.into_tuple().unwrap();
                 let b = b.kind.into_tuple().unwrap();
+                // std.intersect and std.remove zip the columns of two relations
+                if a.len() != b.len() {
+                    return Err(Error::new_simple(
+                        "cannot compare two relations with non-matching number of columns.",
+                    )
+                    .push_hint(format!(
+                        "top has {} columns, but bottom has {}",
+                        a.len(),
+                        b.len()
+                    )));
+                }
 
                 let mut res = Vec::new();
                 for (a, b) in std::iter::zip(a, b) {
@@ -425,10 +576,68 @@ impl Resolver<'_> {
                 return Ok(Expr::new(ExprKind::Literal(Literal::String(ver))));
             }
 
-            "count" | "row_number" => {
-                // HACK: these functions get `this`, resolved to `{x = {_self}}`, which
+            "date_trunc" | "date_part" => {
+                // yes, this is not a transform, but this is the most appropriate place for it
+
+                let return_ty = func.return_ty;
+                let [unit, column] = unpack::<2>(func.args);
+                let name = internal_name.replace('_', ".");
+
+                let unit = match &unit.kind {
+                    ExprKind::Literal(Literal::String(unit))
+                        if DATE_UNITS.contains(&unit.as_str()) =>
+                    {
+                        unit.clone()
+                    }
+                    _ => {
+                        return Err(Error::new(Reason::Expected {
+                            who: Some(format!("`{name}`")),
+                            expected: "year, quarter, month, week, day or hour".to_string(),
+                            found: format!("`{}`", write_pl(unit.clone())),
+                        })
+                        .with_span(unit.span));
+                    }
+                };
+
+                // the unit is passed as a keyword, which is quoted where needed
+                let unit = Expr::new(ExprKind::SString(vec![InterpolateItem::String(unit)]));
+                return Ok(Expr {
+                    ty: return_ty.or_else(|| column.ty.clone()),
+                    needs_window,
+                    ..Expr::new(ExprKind::RqOperator {
+                        name: format!("std.{name}"),
+                        args: vec![unit, column],
+                    })
+                });
+            }
+
+            "count" => {
+                let return_ty = func.return_ty;
+                let [column] = unpack::<1>(func.args);
+
+                // `count this` counts the rows, and `this` is resolved to a
+                // tuple, which can't be lowered, so it's replaced by a null.
+                // `count column` counts the values of the column that are not
+                // null.
+                let (name, column) = if column.kind.is_tuple() {
+                    ("std.count", Expr::new(Literal::Null))
+                } else {
+                    ("std.count_values", column)
+                };
+                return Ok(Expr {
+                    ty: return_ty,
+                    needs_window,
+                    ..Expr::new(ExprKind::RqOperator {
+                        name: name.to_string(),
+                        args: vec![column],
+                    })
+                });
+            }
+
+            "row_number" => {
+                // HACK: this function gets `this`, resolved to `{x = {_self}}`, which
                 // throws an error during lowering.
-                // But because these functions don't *really* need an arg, we can just pass
+                // But because this function doesn't *really* need an arg, we can just pass
                 // a null instead.
                 return Ok(Expr {
                     needs_window,
@@ -485,6 +694,142 @@ impl Resolver<'_> {
         })
     }
 
+    /// Pulls each `{a, b} = relation` out of the assigns of a `derive` and
+    /// cross joins the relation to `tbl`, with its columns named `a` and `b`.
+    fn join_destructured(&mut self, mut assigns: Expr, mut tbl: Expr) -> Result<(Expr, Expr)> {
+        let fields = assigns.kind.as_tuple_mut().unwrap();
+        if !fields.iter().any(|f| f.kind.is_destructure()) {
+            return Ok((assigns, tbl));
+        }
+
+        let (destructures, rest) = std::mem::take(fields)
+            .into_iter()
+            .partition::<Vec<_>, _>(|f| f.kind.is_destructure());
+        *fields = rest;
+        assigns.ty = None;
+        assigns.ty = Resolver::infer_type(&assigns)?;
+
+        for destructure in destructures {
+            let span = destructure.span;
+            let (names, relation) = destructure.kind.into_destructure().unwrap();
+            let relation = rename_columns(*relation, names).with_span(span)?;
+
+            let join = FuncCall {
+                name: Box::new(Expr::new(Ident::from_path(vec!["std", "join"]))),
+                args: vec![relation, Expr::new(Literal::Boolean(true)), tbl],
+                named_args: Default::default(),
+            };
+            tbl = self.fold_expr(Expr::new(ExprKind::FuncCall(join)))?;
+        }
+        Ok((assigns, tbl))
+    }
+
+    /// Replaces each column pattern of the assigns of a `select`, such as
+    /// `column_names.starts_with "amt_"`, with the columns of `tbl` whose names
+    /// match it. All columns of `tbl` must be known, so the columns of its
+    /// tables have to be declared.
+    fn expand_column_patterns(&mut self, mut assigns: Expr, tbl: &Expr) -> Result<Expr> {
+        let fields = assigns.kind.as_tuple_mut().unwrap();
+        if !fields.iter().any(|f| ColumnPattern::of(f).is_some()) {
+            return Ok(assigns);
+        }
+        let lineage = tbl.lineage.as_ref().unwrap();
+
+        let mut expanded = Vec::new();
+        for field in std::mem::take(fields) {
+            let Some(pattern) = ColumnPattern::of(&field) else {
+                expanded.push(field);
+                continue;
+            };
+            let pattern = pattern.with_span(field.span)?;
+
+            let mut names = Vec::new();
+            for column in &lineage.columns {
+                match column {
+                    LineageColumn::Single {
+                        name: Some(name), ..
+                    } => {
+                        if pattern.matches(&name.name) {
+                            names.push(name.clone());
+                        }
+                    }
+                    LineageColumn::Single { name: None, .. } => {}
+                    LineageColumn::All { input_id, .. } => {
+                        let input = lineage.find_input(*input_id).unwrap();
+                        let table = input
+                            .table
+                            .clone()
+                            .pop_front()
+                            .1
+                            .unwrap_or(input.table.clone());
+                        return Err(Error::new_simple(format!(
+                            "cannot match column names, because the columns of `{table}` are unknown"
+                        ))
+                        .push_hint("declare the columns of the table with `Options::table_columns`")
+                        .with_span(field.span));
+                    }
+                }
+            }
+            if names.is_empty() {
+                return Err(
+                    Error::new_simple(format!("no columns match {}", pattern.description))
+                        .with_span(field.span),
+                );
+            }
+
+            // resolve the names within the frame of `tbl`
+            self.root_mod.module.shadow(NS_THIS);
+            self.root_mod.module.insert_frame(lineage, NS_THIS);
+            let columns = (names.into_iter())
+                .map(|name| {
+                    let ident = Ident::from_name(NS_THIS) + name;
+                    self.fold_expr(Expr {
+                        span: field.span,
+                        ..Expr::new(ident)
+                    })
+                })
+                .collect::<Result<Vec<_>>>();
+            self.root_mod.module.unshadow(NS_THIS);
+            expanded.extend(columns?);
+        }
+
+        let fields = assigns.kind.as_tuple_mut().unwrap();
+        *fields = expanded;
+        assigns.ty = None;
+        assigns.ty = Resolver::infer_type(&assigns)?;
+        Ok(assigns)
+    }
+
+    /// Converts the columns of `sort` or a similar argument into column sorts,
+    /// where a negated column is sorted in descending order.
+    fn column_sorts(
+        &mut self,
+        by: Expr,
+        who: &str,
+        nulls: Option<NullsOrder>,
+    ) -> Result<Vec<ColumnSort>> {
+        Ok(self
+            .coerce_into_tuple(by)?
+            .try_cast(|x| x.into_tuple(), Some(who), "tuple")?
+            .into_iter()
+            .map(|expr| {
+                let (column, direction) = match expr.kind {
+                    ExprKind::RqOperator { name, mut args } if name == "std.neg" => {
+                        (args.remove(0), SortDirection::Desc)
+                    }
+                    _ => (expr, SortDirection::default()),
+                };
+                let column = Box::new(column);
+
+                ColumnSort {
+                    direction,
+                    column,
+                    nulls,
+                }
+            })
+            .collect())
+    }
+
     /// Figure out the type of a function call, if this function is a *special function*.
     /// (declared in std module & requires special handling).
     pub fn infer_type_of_special_func(
@@ -516,6 +861,17 @@ impl Resolver<'_> {
 
                 Some(Ty::new(TyKind::Array(Box::new(tuple))))
             }
+            TransformKind::Fill { columns, .. } => {
+                let input = transform_call.input.ty.clone().unwrap();
+                let input = input.into_relation().unwrap();
+
+                let filled =
+                    (columns.iter()).map(|e| TyTupleField::Single(e.alias.clone(), e.ty.clone()));
+
+                Some(Ty::new(TyKind::Array(Box::new(Ty::new(ty_tuple_kind(
+                    input.into_iter().chain(filled).collect(),
+                ))))))
+            }
             TransformKind::Filter { .. }
             | TransformKind::Sort { .. }
             | TransformKind::Take { .. } => transform_call.input.ty.clone(),
@@ -555,10 +911,93 @@ impl Resolver<'_> {
 
                 Some(type_intersection(top, bottom))
             }
+            TransformKind::Pivot { using, by, .. } => {
+                let fields = (by.iter().chain(using))
+                    .map(|e| {
+                        let name =
+                            (e.alias.clone()).or_else(|| e.kind.as_ident().map(|i| i.name.clone()));
+                        TyTupleField::Single(name, e.ty.clone())
+                    })
+                    .collect();
+
+                Some(Ty::new(TyKind::Array(Box::new(Ty::new(ty_tuple_kind(
+                    fields,
+                ))))))
+            }
         })
     }
 }
 
+/// Names the columns of a relation, in order. The relation must have exactly
+/// as many columns as there are names.
+fn rename_columns(mut relation: Expr, names: Vec<String>) -> Result<Expr> {
+    let Some(lineage) = relation.lineage.as_mut() else {
+        return Err(Error::new(Reason::Expected {
+            who: None,
+            expected: "a relation".to_string(),
+            found: write_pl(relation),
+        })
+        .push_hint("only the columns of a relation can be destructured"));
+    };
+
+    if lineage.columns.iter().any(|c| c.is_all()) {
+        return Err(
+            Error::new_simple("cannot destructure a relation with unknown columns")
+                .push_hint("select the columns, as in `(from t | select {a, b})`"),
+        );
+    }
+    if lineage.columns.len() != names.len() {
+        return Err(Error::new_simple(format!(
+            "expected a relation with {} columns, but found one with {}",
+            names.len(),
+            lineage.columns.len()
+        )));
+    }
+
+    let input_name = lineage.inputs.first().map(|i| i.name.clone());
+    for (col, name) in zip(&mut lineage.columns, &names) {
+        if let LineageColumn::Single { name: col_name, .. } = col {
+            let path = input_name.iter().cloned().collect();
+            *col_name = Some(Ident::from_path([path, vec![name.clone()]].concat()));
+        }
+    }
+
+    let fields = (relation.ty.as_mut())
+        .and_then(|ty| ty.kind.as_array_mut())
+        .and_then(|item| item.kind.as_tuple_mut());
+    if let Some(fields) = fields {
+        for (field, name) in zip(fields, names) {
+            if let TyTupleField::Single(field_name, _) = field {
+                *field_name = Some(name);
+            }
+        }
+    }
+    Ok(relation)
+}
+
+/// Extracts the names of the columns that `pivot` produces from its `on`
+/// argument, which has to test a column against a literal list of values.
+fn pivot_values(on: &Expr) -> Result<Vec<String>> {
+    let values = match &on.kind {
+        ExprKind::RqOperator { name, args } if name == "std.array_in" => args[1].kind.as_array(),
+        _ => None,
+    };
+    let values = values.and_then(|values| {
+        (values.iter())
+            .map(|v| match v.kind.as_literal()? {
+                Literal::String(s) => Some(s.clone()),
+                literal => Some(literal.to_string()),
+            })
+            .collect::<Option<Vec<_>>>()
+    });
+
+    values.ok_or_else(|| {
+        Error::new_simple("`pivot` requires `on` to test a column against a literal list")
+            .push_hint("the values must be known statically, as in `quarter | in [\"Q1\", \"Q2\"]`")
+            .with_span(on.span)
+    })
+}
+
 fn range_is_empty(range: &(Option<i64>, Option<i64>)) -> bool {
     match (&range.0, &range.1) {
         (Some(s), Some(e)) => s > e,
@@ -651,6 +1090,57 @@ impl Resolver<'_> {
     }
 }
 
+/// A pattern of the `std.column_names` module.
+struct ColumnPattern {
+    regex: regex::Regex,
+    description: String,
+}
+
+impl ColumnPattern {
+    /// Pattern of an expression, if it is a call to a function of
+    /// `std.column_names`, or an error when its argument is not a text literal.
+    fn of(expr: &Expr) -> Option<Result<Self, Error>> {
+        let ExprKind::RqOperator { name, args } = &expr.kind else {
+            return None;
+        };
+        let kind = name.strip_prefix("std.column_names.")?;
+
+        let text = match args.as_slice() {
+            [Expr {
+                kind: ExprKind::Literal(Literal::String(text)),
+                ..
+            }] => text,
+            _ => {
+                return Some(Err(Error::new_simple(format!(
+                    "`column_names.{kind}` expects a text literal"
+                ))))
+            }
+        };
+        let (pattern, description) = match kind {
+            "starts_with" => (
+                format!("^{}", regex::escape(text)),
+                format!("prefix `{text}`"),
+            ),
+            "ends_with" => (
+                format!("{}$", regex::escape(text)),
+                format!("suffix `{text}`"),
+            ),
+            _ => (text.clone(), format!("pattern `{text}`")),
+        };
+        Some(
+            regex::Regex::new(&pattern)
+                .map(|regex| ColumnPattern { regex, description })
+                .map_err(|_| {
+                    Error::new_simple(format!("`{text}` is not a valid regular expression"))
+                }),
+        )
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
 impl TransformCall {
     pub fn infer_lineage(&self) -> Result<Lineage> {
         use TransformKind::*;
@@ -712,6 +1202,23 @@ impl TransformCall {
                 let bottom = lineage_or_default(bottom)?;
                 append(top, bottom)?
             }
+            Pivot { using, by, .. } => {
+                let mut lineage = lineage_or_default(&self.input)?;
+                lineage.clear();
+
+                for expr in by.iter().chain(using) {
+                    lineage.apply_assign(expr, false);
+                }
+                lineage
+            }
+            Fill { columns, .. } => {
+                let mut lineage = lineage_or_default(&self.input)?;
+
+                for expr in columns {
+                    lineage.apply_assign(expr, false);
+                }
+                lineage
+            }
             Loop(_) => lineage_or_default(&self.input)?,
             Sort { .. } | Filter { .. } | Take { .. } => lineage_or_default(&self.input)?,
         })
@@ -930,14 +1437,25 @@ impl Lineage {
         let (target_id, target_name) = (expr.id.unwrap(), None);
 
         let alias = expr.alias.as_ref().map(Ident::from_name);
+        let is_binding = alias.is_some();
         let name = alias.or_else(|| expr.kind.as_ident()?.clone().pop_front().1);
 
-        // remove names from columns with the same name
-        if name.is_some() {
-            for c in &mut self.columns {
-                if let LineageColumn::Single { name: n, .. } = c {
-                    if n.as_ref().map(|i| &i.name) == name.as_ref().map(|i| &i.name) {
-                        *n = None;
+        if let Some(name) = &name {
+            let is_shadowed = |c: &LineageColumn| matches!(c, LineageColumn::Single { name: Some(n), .. } if n.name == name.name);
+
+            if is_binding {
+                // an assignment shadows the columns of the same name, so they
+                // are dropped from the relation. Expressions that referenced
+                // them have been resolved already.
+                self.columns.retain(|c| !is_shadowed(c));
+            } else {
+                // a reference keeps the columns of the same name, but they
+                // can no longer be referenced by name
+                for c in &mut self.columns {
+                    if is_shadowed(c) {
+                        if let LineageColumn::Single { name: n, .. } = c {
+                            *n = None;
+                        }
                     }
                 }
             }
@@ -978,6 +1496,9 @@ impl Lineage {
 
 /// Expects closure's args to be resolved.
 /// Note that named args are before positional args, in order of declaration.
+/// Units accepted by `date.trunc` and `date.part`.
+const DATE_UNITS: [&str; 6] = ["year", "quarter", "month", "week", "day", "hour"];
+
 fn unpack<const P: usize>(func_args: Vec<Expr>) -> [Expr; P] {
     func_args.try_into().expect("bad special function cast")
 }
@@ -78,6 +78,28 @@ impl Resolver<'_> {
                 TyKind::Array(Box::new(items_ty))
             }
 
+            ExprKind::Case(cases) => {
+                // one of the values, or null when none of the conditions hold
+                let mut variants = Vec::with_capacity(cases.len() + 1);
+                for case in cases {
+                    let Some(value_ty) = Resolver::infer_type(&case.value)? else {
+                        return Ok(None);
+                    };
+                    variants.push((None, value_ty));
+                }
+
+                let has_default = cases.last().map_or(false, |c| {
+                    matches!(c.condition.kind, ExprKind::Literal(Literal::Boolean(true)))
+                });
+                if !has_default {
+                    variants.push((None, Ty::new(Literal::Null)));
+                }
+
+                normalize_type(Ty::new(TyKind::Union(variants))).kind
+            }
+
+            ExprKind::Cast { ty, .. } => return Ok(Some(ty.clone())),
+
             ExprKind::All { within, except } => {
                 let base = Box::new(Resolver::infer_type(within)?.unwrap());
                 let exclude = Box::new(Resolver::infer_type(except)?.unwrap());
@@ -0,0 +1,658 @@
+//! Inference of the output schema of a relation: the name, type and
+//! nullability of each of its columns.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::decl::{RootModule, TableExpr};
+use crate::ir::pl::{Expr, ExprKind, JoinSide, Lineage, LineageColumn, Literal, TransformKind};
+use crate::pr::{Ident, PrimitiveSet, Ty, TyKind, TyTupleField};
+use crate::{Error, Result, WithErrorInfo};
+
+/// Infers the name, type and nullability of each column of a resolved relation.
+///
+/// A column is nullable when it may contain nulls: when it comes from a
+/// nullable or undeclared column of a source table, from the optional side of
+/// an outer join, or from an expression that may evaluate to null. Columns
+/// whose type cannot be inferred have type `anytype`. Unnamed columns have an
+/// empty name.
+pub fn infer_schema(root_mod: &RootModule, relation: &Expr) -> Result<Vec<(String, Ty, bool)>> {
+    let mut schema = Vec::new();
+    for (name, ty, nullable) in infer_columns(root_mod, relation)? {
+        if name.name == "*" {
+            let name = name.path.join(".");
+            return Err(Error::new_simple(format!(
+                "cannot infer the schema of `{name}.*`, since its columns are unknown"
+            ))
+            .push_hint(format!(
+                "declare the columns of `{name}` or select the columns explicitly"
+            ))
+            .with_span(relation.span));
+        }
+        schema.push((name.name, ty, nullable));
+    }
+    Ok(schema)
+}
+
+/// Like [infer_schema], but the columns of an input whose columns are not
+/// known are reported as a single `input.*` column of type `anytype`.
+pub fn infer_columns(root_mod: &RootModule, relation: &Expr) -> Result<Vec<(Ident, Ty, bool)>> {
+    let mut inference = SchemaInference::new(root_mod);
+    inference.infer_relation(relation)?;
+
+    let lineage = relation_lineage(relation)?;
+    let mut columns = Vec::with_capacity(lineage.columns.len());
+    for col in &lineage.columns {
+        match col {
+            LineageColumn::Single { name, .. } => {
+                let key = column_key(col).unwrap();
+                let (ty, nullable) = inference.column(&key);
+                let name = name.as_ref().map(|n| n.name.clone()).unwrap_or_default();
+                columns.push((Ident::from_name(name), ty, nullable));
+            }
+            LineageColumn::All { input_id, .. } => {
+                let input = lineage.inputs.iter().find(|i| i.id == *input_id);
+                let name = input.map_or("?", |i| i.name.as_str());
+                let name = Ident::from_path(vec![name.to_string(), "*".to_string()]);
+                columns.push((name, Ty::new(TyKind::Any), true));
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// A column, identified either by the expression that computes it or by its
+/// name within a relation input.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ColumnKey {
+    Expr(usize),
+    Input(usize, String),
+}
+
+/// Type and nullability of columns, by their name.
+type DeclaredColumns = HashMap<String, (Ty, bool)>;
+
+struct SchemaInference<'a> {
+    root_mod: &'a RootModule,
+
+    /// Declared columns of each relation input. `None` when the columns of
+    /// the input are not known.
+    inputs: HashMap<usize, Option<DeclaredColumns>>,
+
+    /// Inputs all columns of which have been made nullable by an outer join.
+    nullable_inputs: HashSet<usize>,
+
+    /// Columns inferred so far. Entries are updated as the pipeline
+    /// progresses, since joins and filters change nullability of columns.
+    columns: HashMap<ColumnKey, (Ty, bool)>,
+}
+
+impl<'a> SchemaInference<'a> {
+    fn new(root_mod: &'a RootModule) -> Self {
+        SchemaInference {
+            root_mod,
+            inputs: HashMap::new(),
+            nullable_inputs: HashSet::new(),
+            columns: HashMap::new(),
+        }
+    }
+
+    fn infer_relation(&mut self, expr: &Expr) -> Result<()> {
+        match &expr.kind {
+            ExprKind::TransformCall(call) => {
+                self.infer_relation(&call.input)?;
+                if let Some(partition) = &call.partition {
+                    self.infer_assigns(partition)?;
+                }
+                self.infer_transform(&call.input, &call.kind)
+            }
+            ExprKind::Ident(_) => self.declare_inputs(expr),
+            _ => Ok(()),
+        }
+    }
+
+    fn infer_transform(&mut self, input: &Expr, kind: &TransformKind) -> Result<()> {
+        match kind {
+            TransformKind::Derive { assigns }
+            | TransformKind::Select { assigns }
+            | TransformKind::Aggregate { assigns } => self.infer_assigns(assigns),
+
+            TransformKind::Filter { filter } => {
+                self.infer_filter(filter);
+                Ok(())
+            }
+
+            TransformKind::Join { side, with, .. } => {
+                self.infer_relation(with)?;
+
+                if matches!(side, JoinSide::Right | JoinSide::Full) {
+                    self.make_nullable(relation_lineage(input)?);
+                }
+                if matches!(side, JoinSide::Left | JoinSide::Full) {
+                    self.make_nullable(relation_lineage(with)?);
+                }
+                Ok(())
+            }
+
+            TransformKind::Group { by, pipeline } => {
+                self.infer_assigns(by)?;
+                self.infer_pipeline(pipeline)
+            }
+            TransformKind::Window { pipeline, .. } => self.infer_pipeline(pipeline),
+
+            TransformKind::Append(bottom) => {
+                self.infer_relation(bottom)?;
+
+                // columns of a union are matched by position
+                let top = relation_lineage(input)?.columns.clone();
+                let bottom = relation_lineage(bottom)?.columns.clone();
+                for (top, bottom) in top.iter().zip(bottom.iter()) {
+                    let (Some(top), Some(bottom)) = (column_key(top), column_key(bottom)) else {
+                        continue;
+                    };
+                    if self.column(&bottom).1 {
+                        let ty = self.column(&top).0;
+                        self.columns.insert(top, (ty, true));
+                    }
+                }
+                Ok(())
+            }
+
+            TransformKind::Pivot { using, by, .. } => {
+                for field in by {
+                    self.infer_assigns(field)?;
+                }
+                // a group might not contain a value, which leaves its column null
+                for field in using {
+                    let column = (self.infer_ty(field), true);
+                    self.columns
+                        .insert(ColumnKey::Expr(field.id.unwrap()), column);
+                }
+                Ok(())
+            }
+
+            // columns stay null until their first value
+            TransformKind::Fill { columns, .. } => {
+                for field in columns {
+                    self.infer_assigns(field)?;
+                }
+                Ok(())
+            }
+
+            TransformKind::Sort { .. } | TransformKind::Take { .. } | TransformKind::Loop(_) => {
+                Ok(())
+            }
+        }
+    }
+
+    fn infer_pipeline(&mut self, pipeline: &Expr) -> Result<()> {
+        match &pipeline.kind {
+            ExprKind::Func(func) => self.infer_relation(&func.body),
+            _ => self.infer_relation(pipeline),
+        }
+    }
+
+    fn infer_assigns(&mut self, assigns: &Expr) -> Result<()> {
+        let fields = match &assigns.kind {
+            ExprKind::Tuple(fields) => fields.as_slice(),
+            _ => std::slice::from_ref(assigns),
+        };
+        for field in fields {
+            let column = (self.infer_ty(field), self.is_nullable(field));
+            self.columns
+                .insert(ColumnKey::Expr(field.id.unwrap()), column);
+        }
+        Ok(())
+    }
+
+    /// Columns that are compared to null with `!=` in a conjunction of the
+    /// filter cannot be null after the filter.
+    fn infer_filter(&mut self, filter: &Expr) {
+        let ExprKind::RqOperator { name, args } = &filter.kind else {
+            return;
+        };
+        match (name.as_str(), args.as_slice()) {
+            ("std.and", [left, right]) => {
+                self.infer_filter(left);
+                self.infer_filter(right);
+            }
+            ("std.ne", [left, right]) => {
+                let operand = match (&left.kind, &right.kind) {
+                    (_, ExprKind::Literal(Literal::Null)) => left,
+                    (ExprKind::Literal(Literal::Null), _) => right,
+                    _ => return,
+                };
+                if let Some(key) = self.ident_key(operand) {
+                    let ty = self.column(&key).0;
+                    self.columns.insert(key, (ty, false));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Registers the declared columns of the inputs introduced by a relation
+    /// reference, such as `from employees`.
+    fn declare_inputs(&mut self, expr: &Expr) -> Result<()> {
+        let Some(lineage) = &expr.lineage else {
+            return Ok(());
+        };
+        for input in &lineage.inputs {
+            if Some(input.id) != expr.id || self.inputs.contains_key(&input.id) {
+                continue;
+            }
+
+            let decl = self.root_mod.module.get(&input.table);
+            let columns = match decl.and_then(|d| d.kind.as_table_decl()) {
+                Some(decl) => match &decl.expr {
+                    TableExpr::RelationVar(rel) => {
+                        let schema = infer_schema(self.root_mod, rel).ok();
+                        schema.map(|s| s.into_iter().map(|(name, ty, n)| (name, (ty, n))).collect())
+                    }
+                    _ => decl.ty.as_ref().and_then(declared_columns),
+                },
+                None => None,
+            };
+            self.inputs.insert(input.id, columns);
+        }
+        Ok(())
+    }
+
+    fn make_nullable(&mut self, lineage: &Lineage) {
+        for col in &lineage.columns {
+            match column_key(col) {
+                Some(key) => {
+                    let ty = self.column(&key).0;
+                    self.columns.insert(key, (ty, true));
+                }
+                None => {
+                    let LineageColumn::All { input_id, .. } = col else {
+                        unreachable!()
+                    };
+                    self.nullable_inputs.insert(*input_id);
+                    for (key, (_, nullable)) in self.columns.iter_mut() {
+                        if matches!(key, ColumnKey::Input(id, _) if id == input_id) {
+                            *nullable = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Type and nullability of a column. Unknown columns are assumed to be
+    /// nullable.
+    fn column(&self, key: &ColumnKey) -> (Ty, bool) {
+        if let Some(column) = self.columns.get(key) {
+            return column.clone();
+        }
+
+        let ColumnKey::Input(input_id, name) = key else {
+            return (Ty::new(TyKind::Any), true);
+        };
+        let declared = (self.inputs.get(input_id))
+            .and_then(|columns| columns.as_ref())
+            .and_then(|columns| columns.get(name));
+        match declared {
+            Some((ty, nullable)) => (
+                ty.clone(),
+                *nullable || self.nullable_inputs.contains(input_id),
+            ),
+            None => (Ty::new(TyKind::Any), true),
+        }
+    }
+
+    fn ident_key(&self, expr: &Expr) -> Option<ColumnKey> {
+        let ExprKind::Ident(ident) = &expr.kind else {
+            return None;
+        };
+        let target_id = expr.target_id?;
+
+        Some(if self.inputs.contains_key(&target_id) {
+            ColumnKey::Input(target_id, ident.name.clone())
+        } else {
+            ColumnKey::Expr(target_id)
+        })
+    }
+
+    fn infer_ty(&self, expr: &Expr) -> Ty {
+        if let Some(key) = self.ident_key(expr) {
+            return self.column(&key).0;
+        }
+        if let Some(ty) = &expr.ty {
+            return split_null(ty.clone()).0;
+        }
+        match &expr.kind {
+            ExprKind::RqOperator { name, args } => match name.as_str() {
+                "std.coalesce" => self.infer_ty(&args[0]),
+                "std.count" | "std.count_values" | "std.count_distinct" | "std.row_number"
+                | "std.rank" | "std.rank_dense" => Ty::new(PrimitiveSet::Int),
+                _ => Ty::new(TyKind::Any),
+            },
+            _ => Ty::new(TyKind::Any),
+        }
+    }
+
+    fn is_nullable(&self, expr: &Expr) -> bool {
+        if let Some(key) = self.ident_key(expr) {
+            return self.column(&key).1;
+        }
+
+        match &expr.kind {
+            ExprKind::Literal(literal) => matches!(literal, Literal::Null),
+
+            // nulls in f-strings are treated as empty strings
+            ExprKind::FString(_) | ExprKind::Tuple(_) | ExprKind::Array(_) => false,
+
+            ExprKind::Case(cases) => {
+                let has_default = cases.last().map_or(false, |c| {
+                    matches!(c.condition.kind, ExprKind::Literal(Literal::Boolean(true)))
+                });
+                !has_default || cases.iter().any(|c| self.is_nullable(&c.value))
+            }
+
+            ExprKind::RqOperator { name, args } => match name.as_str() {
+                "std.coalesce" => args.iter().all(|a| self.is_nullable(a)),
+
+                "std.eq" | "std.ne"
+                    if args
+                        .iter()
+                        .any(|a| matches!(a.kind, ExprKind::Literal(Literal::Null))) =>
+                {
+                    false
+                }
+
+                "std.count" | "std.count_values" | "std.count_distinct" | "std.sum" | "std.any"
+                | "std.every" | "std.concat_array" | "std.row_number" | "std.rank"
+                | "std.rank_dense" | "std.zero_if_null" | "std.blank_if_null" => false,
+
+                "std.nullif" | "std.lag" | "std.lead" | "std.first" | "std.last" => true,
+
+                _ => {
+                    let returns_null = expr.ty.clone().map_or(false, |ty| split_null(ty).1);
+                    returns_null || args.iter().any(|a| self.is_nullable(a))
+                }
+            },
+
+            _ => true,
+        }
+    }
+}
+
+fn relation_lineage(expr: &Expr) -> Result<&Lineage> {
+    expr.lineage.as_ref().ok_or_else(|| {
+        Error::new_simple("expected a relation, but its columns are unknown").with_span(expr.span)
+    })
+}
+
+fn column_key(col: &LineageColumn) -> Option<ColumnKey> {
+    match col {
+        LineageColumn::Single {
+            target_id,
+            target_name: Some(name),
+            ..
+        } => Some(ColumnKey::Input(*target_id, name.clone())),
+        LineageColumn::Single { target_id, .. } => Some(ColumnKey::Expr(*target_id)),
+        LineageColumn::All { .. } => None,
+    }
+}
+
+/// Columns of a table declaration, such as `let employees <[{id = int}]>`.
+fn declared_columns(ty: &Ty) -> Option<DeclaredColumns> {
+    let fields = ty.kind.as_array()?.kind.as_tuple()?;
+
+    let columns = fields.iter().filter_map(|field| match field {
+        TyTupleField::Single(Some(name), ty) => {
+            let column = ty.clone().map_or((Ty::new(TyKind::Any), true), split_null);
+            Some((name.clone(), column))
+        }
+        _ => None,
+    });
+    Some(columns.collect())
+}
+
+/// Splits `null` from a union type, returning the remaining type and whether
+/// it contained `null`.
+fn split_null(ty: Ty) -> (Ty, bool) {
+    fn is_null(ty: &Ty) -> bool {
+        matches!(ty.kind, TyKind::Singleton(Literal::Null))
+    }
+
+    match ty.kind {
+        TyKind::Union(variants) if variants.iter().any(|(_, v)| is_null(v)) => {
+            let mut variants = variants
+                .into_iter()
+                .filter(|(_, v)| !is_null(v))
+                .collect::<Vec<_>>();
+            let ty = if variants.len() == 1 {
+                variants.remove(0).1
+            } else {
+                Ty {
+                    kind: TyKind::Union(variants),
+                    ..ty
+                }
+            };
+            (ty, true)
+        }
+        TyKind::Singleton(Literal::Null) => (Ty::new(TyKind::Any), true),
+        kind => (Ty { kind, ..ty }, false),
+    }
+}
+
+/// Adds `null` to a type, as the inverse of [split_null].
+pub fn union_null(ty: Ty) -> Ty {
+    let null = Ty::new(TyKind::Singleton(Literal::Null));
+    match ty.kind {
+        // any type contains null already
+        TyKind::Any => ty,
+        TyKind::Union(mut variants) => {
+            variants.push((None, null));
+            Ty {
+                kind: TyKind::Union(variants),
+                ..ty
+            }
+        }
+        _ => Ty::new(TyKind::Union(vec![(None, ty), (None, null)])),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::assert_snapshot;
+    use itertools::Itertools;
+
+    use super::infer_schema;
+    use crate::codegen::write_ty;
+    use crate::parser::parse;
+    use crate::semantic::resolve;
+    use crate::Result;
+
+    fn schema_of(query: &str) -> Result<String> {
+        let root_mod = resolve(parse(&query.into()).unwrap()).unwrap();
+        let (main, _) = root_mod.find_main_rel(&[]).unwrap();
+        let main = main.clone().into_relation_var().unwrap();
+
+        let schema = infer_schema(&root_mod, &main)?;
+        Ok((schema.into_iter())
+            .map(|(name, ty, nullable)| {
+                let null = if nullable { " || null" } else { "" };
+                format!("{name} <{}{null}>", write_ty(&ty))
+            })
+            .join("\n"))
+    }
+
+    #[test]
+    fn test_declared_columns() {
+        assert_snapshot!(schema_of(r#"
+        let employees <[{id = int, name = text, manager_id = int || null}]>
+
+        from employees
+        derive {is_top = manager_id == null}
+        "#).unwrap(), @r"
+        id <int>
+        name <text>
+        manager_id <int || null>
+        is_top <bool>
+        ");
+    }
+
+    #[test]
+    fn test_joins() {
+        assert_snapshot!(schema_of(r#"
+        let employees <[{id = int, dept_id = int}]>
+        let departments <[{id = int, title = text}]>
+
+        from e = employees
+        join side:left d = departments (==id)
+        select {e.id, d.title, title_or_blank = coalesce d.title ""}
+        "#).unwrap(), @r"
+        id <int>
+        title <text || null>
+        title_or_blank <text>
+        ");
+
+        assert_snapshot!(schema_of(r#"
+        let employees <[{id = int, dept_id = int}]>
+        let departments <[{id = int, title = text}]>
+
+        from e = employees
+        join side:full d = departments (e.dept_id == d.id)
+        select {e.id, d.title}
+        "#).unwrap(), @r"
+        id <int || null>
+        title <text || null>
+        ");
+    }
+
+    #[test]
+    fn test_filter_not_null() {
+        assert_snapshot!(schema_of(r#"
+        from employees
+        filter manager_id != null && age > 30
+        select {manager_id, age, label = f"{first_name} {last_name}"}
+        "#).unwrap(), @r"
+        manager_id <anytype>
+        age <anytype || null>
+        label <text>
+        ");
+    }
+
+    #[test]
+    fn test_relation_var() {
+        assert_snapshot!(schema_of(r#"
+        let employees <[{id = int, manager_id = int || null}]>
+
+        let managed = (
+          from employees
+          filter manager_id != null
+        )
+
+        from managed
+        select {id, manager_id}
+        "#).unwrap(), @r"
+        id <int>
+        manager_id <int>
+        ");
+    }
+
+    #[test]
+    fn test_unknown_columns() {
+        let err = schema_of("from employees").unwrap_err();
+        assert_snapshot!(err.reason, @"cannot infer the schema of `employees.*`, since its columns are unknown");
+    }
+
+    #[test]
+    fn test_aggregate() {
+        assert_snapshot!(schema_of(r#"
+        let employees <[{id = int, dept_id = int, salary = float}]>
+
+        from employees
+        group {dept_id} (
+          aggregate {n = count this, total = sum salary, top = max salary}
+        )
+        "#).unwrap(), @r"
+        dept_id <int>
+        n <int>
+        total <int || float>
+        top <int || float || null>
+        ");
+    }
+
+    #[test]
+    fn test_case() {
+        assert_snapshot!(schema_of(r#"
+        let employees <[{id = int, city = text}]>
+
+        from employees
+        select {
+          distance = case [city == "Calgary" => 0, city == "Edmonton" => 300.5],
+          label = case [city == "Calgary" => "home"] else "away",
+        }
+        "#).unwrap(), @r"
+        distance <int || float || null>
+        label <text>
+        ");
+    }
+
+    #[test]
+    fn test_compile_with_schema() {
+        let compiled_schema = |query: &str| {
+            let (_, schema) =
+                crate::compile_with_schema(query, &crate::Options::default()).unwrap();
+            (schema.into_iter())
+                .map(|(name, ty)| format!("{name} <{}>", write_ty(&ty)))
+                .join("\n")
+        };
+
+        assert_snapshot!(compiled_schema(r#"
+        module default_db {
+          let employees <[{id = int, name = text, dept_id = int, salary = float}]>
+          let departments <[{id = int, title = text}]>
+        }
+
+        from e = employees
+        derive {greeting = f"{name}!", raised = salary * 1.1, code = s"UPPER({name})"}
+        join side:left d = departments (e.dept_id == d.id)
+        select {e.name, d.title, greeting, raised, code}
+        "#), @r"
+        name <text>
+        title <text || null>
+        greeting <text>
+        raised <int || float>
+        code <anytype>
+        ");
+
+        assert_snapshot!(compiled_schema(r#"
+        module default_db {
+          let employees <[{id = int, dept_id = int, salary = float}]>
+        }
+
+        from employees
+        group {dept_id} (aggregate {n = count this, total = sum salary})
+        "#), @r"
+        dept_id <int>
+        n <int>
+        total <int || float>
+        ");
+
+        assert_snapshot!(compiled_schema(r#"
+        from employees
+        aggregate {
+          n_salaries = count salary,
+          n_depts = count_distinct dept_id,
+          n_pairs = count_distinct [dept_id, title],
+        }
+        "#), @r"
+        n_salaries <int>
+        n_depts <int>
+        n_pairs <int>
+        ");
+
+        assert_snapshot!(compiled_schema(r#"
+        from employees
+        derive {age_next_year = age + 1}
+        "#), @r"
+        employees.* <anytype>
+        age_next_year <int || float || timestamp || date || null>
+        ");
+    }
+}
@@ -0,0 +1,55 @@
+//! Canonicalization of alternative names of std functions.
+
+use std::collections::HashSet;
+
+use crate::ir::pl::{PlFold, Stmt, StmtKind};
+use crate::pr::Ident;
+use crate::semantic::NS_STD;
+use crate::Result;
+
+/// Alternative names of std functions, along with their canonical names.
+const ALIASES: &[(&str, &str)] = &[("avg", "average"), ("mean", "average")];
+
+/// Renames calls to aliases of std functions (i.e. `avg x`) to their canonical
+/// names (i.e. `average x`), so later stages only deal with one name for each
+/// operation.
+///
+/// Aliases that the query declares itself are left alone.
+pub fn canonicalize_aliases(stmts: Vec<Stmt>) -> Result<Vec<Stmt>> {
+    let mut declared = HashSet::new();
+    collect_declared_names(&stmts, &mut declared);
+
+    StdlibAlias { declared }.fold_stmts(stmts)
+}
+
+fn collect_declared_names(stmts: &[Stmt], declared: &mut HashSet<String>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::VarDef(var_def) => {
+                declared.insert(var_def.name.clone());
+            }
+            StmtKind::ModuleDef(module_def) if module_def.name != NS_STD => {
+                collect_declared_names(&module_def.stmts, declared);
+            }
+            _ => {}
+        }
+    }
+}
+
+struct StdlibAlias {
+    declared: HashSet<String>,
+}
+
+impl PlFold for StdlibAlias {
+    fn fold_func_name(&mut self, mut name: Ident) -> Result<Ident> {
+        let in_std = name.path.is_empty() || name.path == [NS_STD];
+        if !in_std || self.declared.contains(&name.name) {
+            return Ok(name);
+        }
+
+        if let Some((_, canonical)) = ALIASES.iter().find(|(alias, _)| *alias == name.name) {
+            name.name = canonical.to_string();
+        }
+        Ok(name)
+    }
+}
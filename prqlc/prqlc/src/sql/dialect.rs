@@ -18,6 +18,7 @@ use chrono::format::{Fixed, Item, Numeric, Pad, StrftimeItems};
 use serde::{Deserialize, Serialize};
 use strum::VariantNames;
 
+use crate::pr::PrimitiveSet;
 use crate::{Error, Result};
 
 /// SQL dialect.
@@ -74,7 +75,8 @@ impl Dialect {
             Dialect::DuckDb => Box::new(DuckDbDialect),
             Dialect::Postgres => Box::new(PostgresDialect),
             Dialect::GlareDb => Box::new(GlareDbDialect),
-            Dialect::Ansi | Dialect::Generic => Box::new(GenericDialect),
+            Dialect::Ansi => Box::new(AnsiDialect),
+            Dialect::Generic => Box::new(GenericDialect),
         }
     }
 
@@ -105,9 +107,71 @@ pub enum SupportLevel {
     Nascent,
 }
 
+/// Version of the database that a dialect is compiled for, as in `8.0` or
+/// `3.25.1`, for features that only newer versions support.
+///
+/// Queries compiled without a version may use the features of the newest
+/// version.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+pub struct DialectVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl DialectVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        DialectVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Whether a version, when known, is older than the first version with
+    /// a feature, when that is known.
+    pub(super) fn predates(version: Option<Self>, since: Option<Self>) -> bool {
+        matches!((version, since), (Some(version), Some(since)) if version < since)
+    }
+}
+
+impl std::str::FromStr for DialectVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::new_simple(format!(
+                "invalid version `{s}`, expected a version such as `8.0` or `3.25.1`"
+            ))
+        };
+
+        let parts: Vec<u32> = (s.split('.'))
+            .map(|part| part.parse().map_err(|_| invalid()))
+            .collect::<Result<_>>()?;
+        match parts[..] {
+            [major] => Ok(DialectVersion::new(major, 0, 0)),
+            [major, minor] => Ok(DialectVersion::new(major, minor, 0)),
+            [major, minor, patch] => Ok(DialectVersion::new(major, minor, patch)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl std::fmt::Display for DialectVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.patch != 0 {
+            write!(f, ".{}", self.patch)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct GenericDialect;
 #[derive(Debug)]
+pub struct AnsiDialect;
+#[derive(Debug)]
 pub struct SQLiteDialect;
 #[derive(Debug)]
 pub struct MySqlDialect;
@@ -136,6 +200,19 @@ pub(super) trait DialectHandler: Any + Debug {
         false
     }
 
+    /// Whether a `take` without an offset compiles to `SELECT TOP n`, which
+    /// does not require an ORDER BY, unlike FETCH.
+    fn use_top(&self) -> bool {
+        false
+    }
+
+    /// Whether FETCH requires an OFFSET and an ORDER BY, which are padded
+    /// with `OFFSET 0 ROWS` and `ORDER BY (SELECT NULL)` when missing. An
+    /// OFFSET alone requires an ORDER BY too.
+    fn fetch_requires_order(&self) -> bool {
+        false
+    }
+
     fn ident_quote(&self) -> char {
         '"'
     }
@@ -166,6 +243,25 @@ pub(super) trait DialectHandler: Any + Debug {
         true
     }
 
+    /// Whether string concatenation treats NULLs as empty strings, as
+    /// `CONCAT` does in PostgreSQL.
+    /// When it doesn't, we wrap the args in `COALESCE(arg, '')`.
+    fn concat_ignores_nulls(&self) -> bool {
+        true
+    }
+
+    /// Whether unnesting an array has to be done with a lateral join in FROM,
+    /// because set-returning functions are not allowed in the projection.
+    fn unnest_as_lateral_join(&self) -> bool {
+        false
+    }
+
+    /// Whether a struct column must be parenthesized to access its fields, as
+    /// in `(address).city`.
+    fn struct_field_requires_parentheses(&self) -> bool {
+        false
+    }
+
     /// Whether or not intervals such as `INTERVAL 1 HOUR` require quotes like
     /// `INTERVAL '1 HOUR'`
     fn requires_quotes_intervals(&self) -> bool {
@@ -181,6 +277,136 @@ pub(super) trait DialectHandler: Any + Debug {
         false
     }
 
+    /// Support for `COUNT(DISTINCT a, b)` of several columns. When not
+    /// supported, `count_distinct [a, b]` counts concatenations of the values.
+    fn supports_count_distinct_columns(&self) -> bool {
+        false
+    }
+
+    /// Support for `LIMIT n BY`, which takes the first rows of each group.
+    /// When not supported, `take` within `group` filters by `ROW_NUMBER()`.
+    fn supports_limit_by(&self) -> bool {
+        false
+    }
+
+    /// Support for the PIVOT operator.
+    /// When not supported, pivoted columns are aggregations over CASE.
+    fn supports_pivot(&self) -> bool {
+        false
+    }
+
+    /// Whether PIVOT accepts more than one aggregation.
+    fn pivot_multiple_aggregations(&self) -> bool {
+        true
+    }
+
+    /// Whether OFFSET is only allowed together with LIMIT.
+    /// When so, an open-ended range gets `LIMIT -1`, which means "no limit".
+    fn requires_limit_for_offset(&self) -> bool {
+        false
+    }
+
+    /// Support for QUALIFY, which filters on window functions within the
+    /// SELECT that computes them. When not supported, such filters require a
+    /// subquery.
+    fn supports_qualify(&self) -> bool {
+        false
+    }
+
+    /// Support for NULLS FIRST and NULLS LAST in ORDER BY.
+    /// When not supported, the placement of nulls is emulated with a CASE.
+    fn supports_nulls_order(&self) -> bool {
+        true
+    }
+
+    /// Support for a FILTER clause of aggregate functions, as in
+    /// `COUNT(*) FILTER (WHERE paid)`. When not supported, the filter is
+    /// emulated with a CASE.
+    fn supports_aggregate_filter(&self) -> bool {
+        false
+    }
+
+    /// Support for a boolean type, whose values can be selected, and used as
+    /// conditions. Without it, booleans are represented by 1 and 0, which
+    /// conditions compare with 1.
+    fn supports_booleans(&self) -> bool {
+        true
+    }
+
+    /// Support for VALUES lists in FROM, named with column aliases, as in
+    /// `SELECT * FROM (VALUES (1, 'a')) AS t (id, name)`, which relation
+    /// literals compile to. When not supported, a SELECT is emitted for each
+    /// row and the SELECTs are combined with UNION ALL.
+    fn supports_values_in_from(&self) -> bool {
+        false
+    }
+
+    /// Support for WITH RECURSIVE, which `loop` compiles to.
+    fn supports_recursive_cte(&self) -> bool {
+        true
+    }
+
+    /// First version of the database that supports CTEs (`WITH`). Older
+    /// versions get all tables inlined as sub-queries, and cannot compile
+    /// `loop`.
+    fn ctes_since(&self) -> Option<DialectVersion> {
+        None
+    }
+
+    /// First version of the database that supports window functions.
+    fn window_functions_since(&self) -> Option<DialectVersion> {
+        None
+    }
+
+    /// Support for FULL JOIN. When not supported, full joins are emulated
+    /// with a LEFT JOIN from each side, combined with UNION ALL.
+    fn supports_full_join(&self) -> bool {
+        true
+    }
+
+    /// First version of the database that supports FULL JOIN.
+    fn full_join_since(&self) -> Option<DialectVersion> {
+        None
+    }
+
+    /// Support for the WINDOW clause, which defines windows that are shared
+    /// by more than one window function. When not supported, the OVER clause
+    /// is repeated.
+    fn supports_named_windows(&self) -> bool {
+        true
+    }
+
+    /// Support for array literals, written as `[1, 2, 3]`.
+    fn supports_array_literals(&self) -> bool {
+        false
+    }
+
+    /// Support for IGNORE NULLS in FIRST_VALUE and LAST_VALUE, which `fill`
+    /// compiles to. When not supported, it is emulated by grouping each value
+    /// with the nulls that follow it.
+    fn supports_ignore_nulls(&self) -> bool {
+        true
+    }
+
+    /// Support for DATE_TRUNC, or a function in `std.sql.prql` that does the
+    /// same, which `date.trunc` compiles to. When not supported, the truncated
+    /// date is built from its parts with MySQL's date functions.
+    fn has_date_trunc(&self) -> bool {
+        true
+    }
+
+    /// Name of a type in `CAST(x AS type)`, which a cast such as `x as float`
+    /// compiles to.
+    fn cast_type(&self, ty: &PrimitiveSet) -> &'static str {
+        standard_cast_type(ty)
+    }
+
+    /// Placeholder of the value at `position`, starting at 1, in a
+    /// parameterized query.
+    fn placeholder(&self, _position: usize) -> String {
+        "?".to_string()
+    }
+
     /// Get the date format for the given dialect
     /// PRQL uses the same format as `chrono` crate
     /// (see https://docs.rs/chrono/latest/chrono/format/strftime/index.html)
@@ -202,6 +428,18 @@ pub(super) trait DialectHandler: Any + Debug {
     }
 }
 
+fn standard_cast_type(ty: &PrimitiveSet) -> &'static str {
+    match ty {
+        PrimitiveSet::Int => "INTEGER",
+        PrimitiveSet::Float => "DOUBLE",
+        PrimitiveSet::Bool => "BOOLEAN",
+        PrimitiveSet::Text => "TEXT",
+        PrimitiveSet::Date => "DATE",
+        PrimitiveSet::Time => "TIME",
+        PrimitiveSet::Timestamp => "TIMESTAMP",
+    }
+}
+
 impl dyn DialectHandler {
     #[inline]
     pub fn is<T: DialectHandler + 'static>(&self) -> bool {
@@ -215,11 +453,65 @@ impl DialectHandler for GenericDialect {
     }
 }
 
+/// Standard SQL, for databases that are not known in advance. Constructs
+/// without a standard equivalent are rejected rather than emitted.
+impl DialectHandler for AnsiDialect {
+    fn use_fetch(&self) -> bool {
+        true
+    }
+
+    fn has_concat_function(&self) -> bool {
+        false
+    }
+
+    fn concat_ignores_nulls(&self) -> bool {
+        false
+    }
+
+    fn stars_in_group(&self) -> bool {
+        false
+    }
+
+    fn translate_chrono_item(&self, _item: Item) -> Result<String> {
+        Err(Error::new_simple(
+            "Date formatting is not supported by dialect ansi, since there is no standard format",
+        ))
+    }
+
+    fn cast_type(&self, ty: &PrimitiveSet) -> &'static str {
+        match ty {
+            PrimitiveSet::Float => "DOUBLE PRECISION",
+            ty => standard_cast_type(ty),
+        }
+    }
+}
+
 impl DialectHandler for PostgresDialect {
+    fn supports_ignore_nulls(&self) -> bool {
+        false
+    }
+
+    // https://www.postgresql.org/docs/current/queries-values.html
+    fn supports_values_in_from(&self) -> bool {
+        true
+    }
+
+    fn supports_aggregate_filter(&self) -> bool {
+        true
+    }
+
+    fn placeholder(&self, position: usize) -> String {
+        format!("${position}")
+    }
+
     fn requires_quotes_intervals(&self) -> bool {
         true
     }
 
+    fn struct_field_requires_parentheses(&self) -> bool {
+        true
+    }
+
     fn supports_distinct_on(&self) -> bool {
         true
     }
@@ -269,15 +561,62 @@ impl DialectHandler for PostgresDialect {
     fn supports_zero_columns(&self) -> bool {
         true
     }
+
+    fn cast_type(&self, ty: &PrimitiveSet) -> &'static str {
+        match ty {
+            PrimitiveSet::Float => "DOUBLE PRECISION",
+            ty => standard_cast_type(ty),
+        }
+    }
 }
 
 impl DialectHandler for GlareDbDialect {
+    fn supports_named_windows(&self) -> bool {
+        false
+    }
+
+    fn supports_ignore_nulls(&self) -> bool {
+        false
+    }
+
+    fn placeholder(&self, position: usize) -> String {
+        format!("${position}")
+    }
+
     fn requires_quotes_intervals(&self) -> bool {
         true
     }
+
+    fn supports_recursive_cte(&self) -> bool {
+        false
+    }
 }
 
 impl DialectHandler for SQLiteDialect {
+    fn supports_named_windows(&self) -> bool {
+        false
+    }
+
+    fn supports_aggregate_filter(&self) -> bool {
+        true
+    }
+
+    fn ctes_since(&self) -> Option<DialectVersion> {
+        Some(DialectVersion::new(3, 8, 3))
+    }
+
+    fn window_functions_since(&self) -> Option<DialectVersion> {
+        Some(DialectVersion::new(3, 25, 0))
+    }
+
+    fn full_join_since(&self) -> Option<DialectVersion> {
+        Some(DialectVersion::new(3, 39, 0))
+    }
+
+    fn supports_ignore_nulls(&self) -> bool {
+        false
+    }
+
     fn set_ops_distinct(&self) -> bool {
         false
     }
@@ -290,16 +629,62 @@ impl DialectHandler for SQLiteDialect {
         false
     }
 
+    fn concat_ignores_nulls(&self) -> bool {
+        false
+    }
+
     fn stars_in_group(&self) -> bool {
         false
     }
+
+    fn requires_limit_for_offset(&self) -> bool {
+        true
+    }
+
+    fn supports_nulls_order(&self) -> bool {
+        false
+    }
+
+    // https://www.sqlite.org/datatype3.html
+    // There are no booleans, and dates are stored as text.
+    fn cast_type(&self, ty: &PrimitiveSet) -> &'static str {
+        match ty {
+            PrimitiveSet::Float => "REAL",
+            PrimitiveSet::Bool => "INTEGER",
+            PrimitiveSet::Date | PrimitiveSet::Time | PrimitiveSet::Timestamp => "TEXT",
+            ty => standard_cast_type(ty),
+        }
+    }
 }
 
 impl DialectHandler for MsSqlDialect {
+    fn supports_named_windows(&self) -> bool {
+        false
+    }
+
     fn use_fetch(&self) -> bool {
         true
     }
 
+    // https://learn.microsoft.com/en-us/sql/t-sql/queries/top-transact-sql
+    fn use_top(&self) -> bool {
+        true
+    }
+
+    // https://learn.microsoft.com/en-us/sql/t-sql/queries/table-value-constructor-transact-sql
+    fn supports_values_in_from(&self) -> bool {
+        true
+    }
+
+    // https://learn.microsoft.com/en-us/sql/t-sql/queries/select-order-by-clause-transact-sql#using-offset-and-fetch-to-limit-the-rows-returned
+    fn fetch_requires_order(&self) -> bool {
+        true
+    }
+
+    fn ident_quote(&self) -> char {
+        '['
+    }
+
     // https://learn.microsoft.com/en-us/sql/t-sql/language-elements/set-operators-except-and-intersect-transact-sql?view=sql-server-ver16
     fn except_all(&self) -> bool {
         false
@@ -353,13 +738,62 @@ impl DialectHandler for MsSqlDialect {
             }
         })
     }
+
+    fn supports_nulls_order(&self) -> bool {
+        false
+    }
+
+    // https://learn.microsoft.com/en-us/sql/t-sql/data-types/bit-transact-sql
+    fn supports_booleans(&self) -> bool {
+        false
+    }
+
+    fn cast_type(&self, ty: &PrimitiveSet) -> &'static str {
+        match ty {
+            PrimitiveSet::Float => "FLOAT",
+            PrimitiveSet::Bool => "BIT",
+            PrimitiveSet::Text => "NVARCHAR(MAX)",
+            PrimitiveSet::Timestamp => "DATETIME2",
+            ty => standard_cast_type(ty),
+        }
+    }
 }
 
 impl DialectHandler for MySqlDialect {
+    fn ctes_since(&self) -> Option<DialectVersion> {
+        Some(DialectVersion::new(8, 0, 0))
+    }
+
+    // https://dev.mysql.com/doc/refman/8.0/en/aggregate-functions.html#function_count-distinct
+    fn supports_count_distinct_columns(&self) -> bool {
+        true
+    }
+
+    fn window_functions_since(&self) -> Option<DialectVersion> {
+        Some(DialectVersion::new(8, 0, 0))
+    }
+
+    fn supports_full_join(&self) -> bool {
+        false
+    }
+
+    fn supports_ignore_nulls(&self) -> bool {
+        false
+    }
+
+    fn has_date_trunc(&self) -> bool {
+        false
+    }
+
     fn ident_quote(&self) -> char {
         '`'
     }
 
+    fn concat_ignores_nulls(&self) -> bool {
+        // https://dev.mysql.com/doc/refman/8.4/en/string-functions.html#function_concat
+        false
+    }
+
     fn set_ops_distinct(&self) -> bool {
         // https://dev.mysql.com/doc/refman/8.0/en/set-operations.html
         true
@@ -395,6 +829,21 @@ impl DialectHandler for MySqlDialect {
             }
         })
     }
+
+    fn supports_nulls_order(&self) -> bool {
+        false
+    }
+
+    // https://dev.mysql.com/doc/refman/8.0/en/cast-functions.html#function_cast
+    // Booleans are integers.
+    fn cast_type(&self, ty: &PrimitiveSet) -> &'static str {
+        match ty {
+            PrimitiveSet::Int | PrimitiveSet::Bool => "SIGNED",
+            PrimitiveSet::Text => "CHAR",
+            PrimitiveSet::Timestamp => "DATETIME",
+            ty => standard_cast_type(ty),
+        }
+    }
 }
 
 impl DialectHandler for ClickHouseDialect {
@@ -406,6 +855,24 @@ impl DialectHandler for ClickHouseDialect {
         true
     }
 
+    // https://clickhouse.com/docs/en/sql-reference/statements/select/limit-by
+    fn supports_limit_by(&self) -> bool {
+        true
+    }
+
+    fn supports_count_distinct_columns(&self) -> bool {
+        true
+    }
+
+    // https://clickhouse.com/docs/en/sql-reference/data-types/array
+    fn supports_array_literals(&self) -> bool {
+        true
+    }
+
+    fn concat_ignores_nulls(&self) -> bool {
+        false
+    }
+
     // https://clickhouse.com/docs/en/sql-reference/functions/date-time-functions#formatDateTimeInJodaSyntax
     fn translate_chrono_item<'a>(&self, item: Item) -> Result<String> {
         Ok(match item {
@@ -446,6 +913,18 @@ impl DialectHandler for ClickHouseDialect {
             }
         })
     }
+
+    fn cast_type(&self, ty: &PrimitiveSet) -> &'static str {
+        match ty {
+            PrimitiveSet::Int => "Int64",
+            PrimitiveSet::Float => "Float64",
+            PrimitiveSet::Bool => "Bool",
+            PrimitiveSet::Text => "String",
+            PrimitiveSet::Date => "Date",
+            PrimitiveSet::Timestamp => "DateTime",
+            ty => standard_cast_type(ty),
+        }
+    }
 }
 
 impl DialectHandler for BigQueryDialect {
@@ -461,9 +940,37 @@ impl DialectHandler for BigQueryDialect {
         // https://cloud.google.com/bigquery/docs/reference/standard-sql/query-syntax#set_operators
         true
     }
+
+    fn concat_ignores_nulls(&self) -> bool {
+        // https://cloud.google.com/bigquery/docs/reference/standard-sql/string_functions#concat
+        false
+    }
+
+    fn unnest_as_lateral_join(&self) -> bool {
+        true
+    }
+
+    // https://cloud.google.com/bigquery/docs/reference/standard-sql/query-syntax#pivot_operator
+    fn supports_pivot(&self) -> bool {
+        true
+    }
+
+    fn cast_type(&self, ty: &PrimitiveSet) -> &'static str {
+        match ty {
+            PrimitiveSet::Int => "INT64",
+            PrimitiveSet::Float => "FLOAT64",
+            PrimitiveSet::Bool => "BOOL",
+            PrimitiveSet::Text => "STRING",
+            ty => standard_cast_type(ty),
+        }
+    }
 }
 
 impl DialectHandler for SnowflakeDialect {
+    fn supports_named_windows(&self) -> bool {
+        false
+    }
+
     fn column_exclude(&self) -> Option<ColumnExclude> {
         // https://docs.snowflake.com/en/sql-reference/sql/select.html
         Some(ColumnExclude::Exclude)
@@ -473,9 +980,50 @@ impl DialectHandler for SnowflakeDialect {
         // https://docs.snowflake.com/en/sql-reference/operators-query.html
         false
     }
+
+    fn concat_ignores_nulls(&self) -> bool {
+        // https://docs.snowflake.com/en/sql-reference/functions/concat
+        false
+    }
+
+    fn unnest_as_lateral_join(&self) -> bool {
+        true
+    }
+
+    // https://docs.snowflake.com/en/sql-reference/constructs/pivot
+    fn supports_pivot(&self) -> bool {
+        true
+    }
+
+    fn pivot_multiple_aggregations(&self) -> bool {
+        false
+    }
+
+    // https://docs.snowflake.com/en/sql-reference/constructs/qualify
+    fn supports_qualify(&self) -> bool {
+        true
+    }
+
+    // https://docs.snowflake.com/en/sql-reference/constructs/values
+    fn supports_values_in_from(&self) -> bool {
+        true
+    }
 }
 
 impl DialectHandler for DuckDbDialect {
+    fn placeholder(&self, position: usize) -> String {
+        format!("${position}")
+    }
+
+    // https://duckdb.org/docs/sql/query_syntax/values
+    fn supports_values_in_from(&self) -> bool {
+        true
+    }
+
+    fn supports_aggregate_filter(&self) -> bool {
+        true
+    }
+
     fn column_exclude(&self) -> Option<ColumnExclude> {
         // https://duckdb.org/2022/05/04/friendlier-sql.html#select--exclude
         Some(ColumnExclude::Exclude)
@@ -490,6 +1038,11 @@ impl DialectHandler for DuckDbDialect {
         true
     }
 
+    // https://duckdb.org/docs/sql/data_types/list
+    fn supports_array_literals(&self) -> bool {
+        true
+    }
+
     // https://duckdb.org/docs/sql/functions/dateformat
     fn translate_chrono_item<'a>(&self, item: Item) -> Result<String> {
         Ok(match item {
@@ -528,7 +1081,7 @@ mod tests {
 
     use insta::assert_debug_snapshot;
 
-    use super::Dialect;
+    use super::{Dialect, DialectVersion};
 
     #[test]
     fn test_dialect_from_str() {
@@ -544,6 +1097,20 @@ mod tests {
         )
         ");
     }
+
+    #[test]
+    fn test_dialect_version_from_str() {
+        let version = DialectVersion::from_str("3.25.1").unwrap();
+        assert_eq!(version, DialectVersion::new(3, 25, 1));
+        assert_eq!(version.to_string(), "3.25.1");
+
+        let version = DialectVersion::from_str("8").unwrap();
+        assert_eq!(version.to_string(), "8.0");
+        assert!(version > DialectVersion::from_str("5.7").unwrap());
+
+        assert!(DialectVersion::from_str("8.x").is_err());
+        assert!(DialectVersion::from_str("1.2.3.4").is_err());
+    }
 }
 
 /*
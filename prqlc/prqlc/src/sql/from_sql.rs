@@ -0,0 +1,666 @@
+//! Translation of SQL queries into PRQL, for migrating existing queries.
+//!
+//! Only a subset of SQL is translated: a single `SELECT` with joins, `WHERE`,
+//! `GROUP BY`, `ORDER BY` and `LIMIT`. Anything else is reported as an error,
+//! rather than silently dropped.
+
+use sqlparser::ast::{
+    self as sql_ast, BinaryOperator, DuplicateTreatment, FunctionArg, FunctionArgExpr,
+    FunctionArguments, GroupByExpr, JoinConstraint, JoinOperator, ObjectName, SelectItem, SetExpr,
+    Statement, TableFactor, UnaryOperator, Value,
+};
+use sqlparser::dialect as sql_dialect;
+use sqlparser::parser::Parser;
+
+use super::Dialect;
+use crate::ir::pl::Literal;
+use crate::pr::{self, BinOp, ExprKind, UnOp};
+use crate::{Error, Errors, WithErrorInfo};
+
+/// Translates a SQL query into a PRQL module with its main pipeline.
+pub(crate) fn sql_to_pl(sql: &str, dialect: Dialect) -> Result<pr::ModuleDef, Errors> {
+    let statements = Parser::parse_sql(parser_dialect(dialect).as_ref(), sql)
+        .map_err(|e| Errors(vec![Error::new_simple(e.to_string())]))?;
+
+    let [Statement::Query(query)] = statements.as_slice() else {
+        return Err(Errors(vec![Error::new_simple(
+            "expected a single SELECT query",
+        )]));
+    };
+
+    let mut translator = Translator::default();
+    let pipeline = translator.translate_query(query);
+    if !translator.errors.is_empty() {
+        return Err(Errors(translator.errors));
+    }
+
+    let main = pr::VarDef {
+        kind: pr::VarDefKind::Main,
+        name: "main".to_string(),
+        value: Some(Box::new(pr::Expr::new(ExprKind::Pipeline(pr::Pipeline {
+            exprs: pipeline,
+        })))),
+        ty: None,
+    };
+    Ok(pr::ModuleDef {
+        name: "".to_string(),
+        stmts: vec![pr::Stmt::new(pr::StmtKind::VarDef(main))],
+    })
+}
+
+fn parser_dialect(dialect: Dialect) -> Box<dyn sql_dialect::Dialect> {
+    match dialect {
+        Dialect::Ansi => Box::new(sql_dialect::AnsiDialect {}),
+        Dialect::BigQuery => Box::new(sql_dialect::BigQueryDialect {}),
+        Dialect::ClickHouse => Box::new(sql_dialect::ClickHouseDialect {}),
+        Dialect::DuckDb => Box::new(sql_dialect::DuckDbDialect {}),
+        Dialect::Generic => Box::new(sql_dialect::GenericDialect {}),
+        Dialect::GlareDb | Dialect::Postgres => Box::new(sql_dialect::PostgreSqlDialect {}),
+        Dialect::MsSql => Box::new(sql_dialect::MsSqlDialect {}),
+        Dialect::MySql => Box::new(sql_dialect::MySqlDialect {}),
+        Dialect::SQLite => Box::new(sql_dialect::SQLiteDialect {}),
+        Dialect::Snowflake => Box::new(sql_dialect::SnowflakeDialect {}),
+    }
+}
+
+/// Translates SQL into PRQL, collecting an error for each construct that
+/// can't be translated, so they can all be reported at once.
+#[derive(Default)]
+struct Translator {
+    errors: Vec<Error>,
+}
+
+fn unsupported(construct: impl std::fmt::Display) -> Error {
+    Error::new_simple(format!("cannot translate {construct} to PRQL"))
+}
+
+impl Translator {
+    fn unsupported(&mut self, construct: impl std::fmt::Display) {
+        self.errors.push(unsupported(construct));
+    }
+
+    fn unsupported_with_hint(&mut self, construct: impl std::fmt::Display, hint: &str) {
+        self.errors.push(unsupported(construct).push_hint(hint));
+    }
+
+    fn translate_query(&mut self, query: &sql_ast::Query) -> Vec<pr::Expr> {
+        if query.with.is_some() {
+            self.unsupported("`WITH`");
+        }
+        if query.fetch.is_some() {
+            self.unsupported("`FETCH`");
+        }
+        if !query.limit_by.is_empty() {
+            self.unsupported("`LIMIT BY`");
+        }
+        if !query.locks.is_empty() {
+            self.unsupported("locking clauses");
+        }
+
+        let SetExpr::Select(select) = query.body.as_ref() else {
+            self.unsupported(format!("`{}`", query.body));
+            return Vec::new();
+        };
+        let projection = self.translate_select(select);
+
+        let mut pipeline = projection.source;
+        let order_by = query
+            .order_by
+            .iter()
+            .flat_map(|o| &o.exprs)
+            .collect::<Vec<_>>();
+        let sort = if order_by.is_empty() {
+            None
+        } else {
+            let sort_after = (order_by.iter())
+                .all(|o| matches!(&o.expr, sql_ast::Expr::Identifier(i) if projection.names.contains(&i.value)));
+            Some((self.translate_order_by(&order_by), sort_after))
+        };
+
+        if let Some((sort, false)) = &sort {
+            pipeline.push(sort.clone());
+        }
+        pipeline.extend(projection.transforms);
+        if let Some((sort, true)) = sort {
+            pipeline.push(sort);
+        }
+
+        let offset = query
+            .offset
+            .as_ref()
+            .and_then(|o| self.translate_count(&o.value));
+        let limit = query.limit.as_ref().and_then(|l| self.translate_count(l));
+        if let Some(take) = translate_take(offset, limit) {
+            pipeline.push(func_call("take", vec![take]));
+        }
+        pipeline
+    }
+
+    fn translate_select(&mut self, select: &sql_ast::Select) -> Projection {
+        if select.distinct.is_some() {
+            self.unsupported("`DISTINCT`");
+        }
+        if select.top.is_some() {
+            self.unsupported("`TOP`");
+        }
+        if select.into.is_some() {
+            self.unsupported("`INTO`");
+        }
+        if select.having.is_some() {
+            self.unsupported("`HAVING`");
+        }
+        if select.qualify.is_some() {
+            self.unsupported("`QUALIFY`");
+        }
+        if !select.named_window.is_empty() {
+            self.unsupported("`WINDOW`");
+        }
+
+        let mut source = self.translate_from(&select.from);
+        if let Some(selection) = &select.selection {
+            source.push(func_call("filter", vec![self.translate_expr(selection)]));
+        }
+
+        let group_by = match &select.group_by {
+            GroupByExpr::Expressions(exprs, modifiers) => {
+                if !modifiers.is_empty() {
+                    self.unsupported("`GROUP BY` modifiers");
+                }
+                exprs.as_slice()
+            }
+            GroupByExpr::All(_) => {
+                self.unsupported("`GROUP BY ALL`");
+                &[]
+            }
+        };
+
+        let items = select.projection.iter().collect::<Vec<_>>();
+        let aggregated = !group_by.is_empty() || items.iter().any(|item| is_aggregation_item(item));
+        let (transforms, names) = if aggregated {
+            self.translate_aggregation(&items, group_by)
+        } else {
+            self.translate_projection(&items)
+        };
+        Projection {
+            source,
+            transforms,
+            names,
+        }
+    }
+
+    fn translate_from(&mut self, from: &[sql_ast::TableWithJoins]) -> Vec<pr::Expr> {
+        let [from] = from else {
+            self.unsupported("a `FROM` which isn't of a single table");
+            return Vec::new();
+        };
+
+        let mut pipeline = vec![func_call(
+            "from",
+            vec![self.translate_table(&from.relation)],
+        )];
+        for join in &from.joins {
+            let (side, constraint) = match &join.join_operator {
+                JoinOperator::Inner(c) => (None, c),
+                JoinOperator::LeftOuter(c) => (Some("left"), c),
+                JoinOperator::RightOuter(c) => (Some("right"), c),
+                JoinOperator::FullOuter(c) => (Some("full"), c),
+                _ => {
+                    self.unsupported(format!("`{}`", join.to_string().trim()));
+                    continue;
+                }
+            };
+            let condition = match constraint {
+                JoinConstraint::On(expr) => self.translate_expr(expr),
+                JoinConstraint::Using(columns) => (columns.iter())
+                    .map(|c| unary(UnOp::EqSelf, ident(&c.value)))
+                    .reduce(|l, r| binary(l, BinOp::And, r))
+                    .unwrap(),
+                JoinConstraint::Natural | JoinConstraint::None => {
+                    self.unsupported(format!("`{}`", join.to_string().trim()));
+                    continue;
+                }
+            };
+
+            let mut call = func_call(
+                "join",
+                vec![self.translate_table(&join.relation), condition],
+            );
+            if let (Some(side), ExprKind::FuncCall(call)) = (side, &mut call.kind) {
+                call.named_args.insert("side".to_string(), ident(side));
+            }
+            pipeline.push(call);
+        }
+        pipeline
+    }
+
+    fn translate_table(&mut self, table: &TableFactor) -> pr::Expr {
+        match table {
+            TableFactor::Table {
+                name,
+                alias,
+                args: None,
+                ..
+            } => {
+                let mut expr = object_name(name);
+                if let Some(alias) = alias {
+                    if !alias.columns.is_empty() {
+                        self.unsupported(format!("aliases of columns in `{table}`"));
+                    }
+                    expr.alias = Some(alias.name.value.clone());
+                }
+                expr
+            }
+            _ => {
+                self.unsupported(format!("`{table}`"));
+                ident("_")
+            }
+        }
+    }
+
+    /// Translates a projection without aggregations into `select`, or into
+    /// `derive` when it starts with `*`.
+    fn translate_projection(&mut self, items: &[&SelectItem]) -> (Vec<pr::Expr>, Vec<String>) {
+        let (derive, items) = match items {
+            [SelectItem::Wildcard(_), rest @ ..] => (true, rest),
+            _ => (false, items),
+        };
+        if derive && items.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+
+        let mut names = Vec::new();
+        let mut fields = Vec::new();
+        for item in items {
+            let field = match item {
+                SelectItem::UnnamedExpr(expr) => {
+                    names.extend(output_name(expr));
+                    self.translate_expr(expr)
+                }
+                SelectItem::ExprWithAlias { expr, alias } => {
+                    names.push(alias.value.clone());
+                    aliased(self.translate_expr(expr), &alias.value)
+                }
+                SelectItem::QualifiedWildcard(name, _) if !derive => {
+                    let base = Box::new(object_name(name));
+                    let field = pr::IndirectionKind::Star;
+                    pr::Expr::new(ExprKind::Indirection { base, field })
+                }
+                _ => {
+                    self.unsupported(format!("`{item}` in this position of the projection"));
+                    continue;
+                }
+            };
+            fields.push(field);
+        }
+
+        let name = if derive { "derive" } else { "select" };
+        let tuple = pr::Expr::new(ExprKind::Tuple(fields));
+        (vec![func_call(name, vec![tuple])], names)
+    }
+
+    /// Translates a projection with aggregations into `group` and `aggregate`.
+    /// The projection must list the grouping keys first, so the shape of the
+    /// result is the same.
+    fn translate_aggregation(
+        &mut self,
+        items: &[&SelectItem],
+        group_by: &[sql_ast::Expr],
+    ) -> (Vec<pr::Expr>, Vec<String>) {
+        let mut names = Vec::new();
+        let mut aggregations = Vec::new();
+        for (position, item) in items.iter().enumerate() {
+            let (expr, alias) = match item {
+                SelectItem::UnnamedExpr(expr) => (expr, None),
+                SelectItem::ExprWithAlias { expr, alias } => (expr, Some(&alias.value)),
+                _ => {
+                    self.unsupported(format!("`{item}` in an aggregation"));
+                    continue;
+                }
+            };
+
+            if position < group_by.len() {
+                if *expr != group_by[position] || alias.is_some() {
+                    self.unsupported_with_hint(
+                        format!("`{item}`"),
+                        "the projection of an aggregation must start with the `GROUP BY` keys",
+                    );
+                }
+                names.extend(output_name(expr));
+                continue;
+            }
+
+            let aggregation = self.translate_expr(expr);
+            let aggregation = match alias {
+                Some(alias) => {
+                    names.push(alias.clone());
+                    aliased(aggregation, alias)
+                }
+                None => aggregation,
+            };
+            aggregations.push(aggregation);
+        }
+        if items.len() < group_by.len() {
+            self.unsupported("a `GROUP BY` of columns that aren't projected");
+        }
+
+        let aggregate = func_call(
+            "aggregate",
+            vec![pr::Expr::new(ExprKind::Tuple(aggregations))],
+        );
+        if group_by.is_empty() {
+            return (vec![aggregate], names);
+        }
+
+        let keys = group_by.iter().map(|e| self.translate_expr(e)).collect();
+        let keys = pr::Expr::new(ExprKind::Tuple(keys));
+        (vec![func_call("group", vec![keys, aggregate])], names)
+    }
+
+    fn translate_order_by(&mut self, order_by: &[&sql_ast::OrderByExpr]) -> pr::Expr {
+        let mut sorts = Vec::new();
+        for o in order_by {
+            if o.nulls_first.is_some() || o.with_fill.is_some() {
+                self.unsupported(format!("`{o}`"));
+            }
+            let expr = self.translate_expr(&o.expr);
+            sorts.push(match o.asc {
+                Some(false) => unary(UnOp::Neg, expr),
+                _ => expr,
+            });
+        }
+        func_call("sort", vec![pr::Expr::new(ExprKind::Tuple(sorts))])
+    }
+
+    /// Translates the count of rows of `LIMIT` or `OFFSET`.
+    fn translate_count(&mut self, expr: &sql_ast::Expr) -> Option<i64> {
+        if let sql_ast::Expr::Value(Value::Number(n, _)) = expr {
+            if let Ok(n) = n.parse() {
+                return Some(n);
+            }
+        }
+        self.unsupported(format!("`{expr}` as a number of rows"));
+        None
+    }
+
+    fn translate_expr(&mut self, expr: &sql_ast::Expr) -> pr::Expr {
+        match expr {
+            sql_ast::Expr::Identifier(i) => ident(&i.value),
+            sql_ast::Expr::CompoundIdentifier(parts) => object_name(&ObjectName(parts.clone())),
+            sql_ast::Expr::Value(value) => match translate_value(value) {
+                Some(literal) => pr::Expr::new(literal),
+                None => self.unsupported_expr(expr),
+            },
+            sql_ast::Expr::Nested(expr) => self.translate_expr(expr),
+            sql_ast::Expr::BinaryOp { left, op, right } => {
+                let Some(op) = translate_binary_operator(op) else {
+                    return self.unsupported_expr(expr);
+                };
+                let left = self.translate_expr(left);
+                let right = self.translate_expr(right);
+                binary(left, op, right)
+            }
+            sql_ast::Expr::UnaryOp { op, expr: operand } => {
+                let op = match op {
+                    UnaryOperator::Not => UnOp::Not,
+                    UnaryOperator::Minus => UnOp::Neg,
+                    UnaryOperator::Plus => UnOp::Add,
+                    _ => return self.unsupported_expr(expr),
+                };
+                unary(op, self.translate_expr(operand))
+            }
+            sql_ast::Expr::IsNull(operand) => {
+                binary(self.translate_expr(operand), BinOp::Eq, null())
+            }
+            sql_ast::Expr::IsNotNull(operand) => {
+                binary(self.translate_expr(operand), BinOp::Ne, null())
+            }
+            sql_ast::Expr::Between {
+                expr: operand,
+                negated,
+                low,
+                high,
+            } => {
+                let range = pr::Range {
+                    start: Some(Box::new(self.translate_expr(low))),
+                    end: Some(Box::new(self.translate_expr(high))),
+                };
+                let operand = self.translate_expr(operand);
+                negate_if(
+                    *negated,
+                    is_in(operand, pr::Expr::new(ExprKind::Range(range))),
+                )
+            }
+            sql_ast::Expr::InList {
+                expr: operand,
+                list,
+                negated,
+            } => {
+                let list = list.iter().map(|e| self.translate_expr(e)).collect();
+                let operand = self.translate_expr(operand);
+                negate_if(
+                    *negated,
+                    is_in(operand, pr::Expr::new(ExprKind::Array(list))),
+                )
+            }
+            sql_ast::Expr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                let operand = operand.as_ref().map(|o| self.translate_expr(o));
+                let mut cases = Vec::new();
+                for (condition, result) in conditions.iter().zip(results) {
+                    let condition = self.translate_expr(condition);
+                    let condition = match &operand {
+                        Some(operand) => binary(operand.clone(), BinOp::Eq, condition),
+                        None => condition,
+                    };
+                    cases.push(pr::SwitchCase {
+                        condition: Box::new(condition),
+                        value: Box::new(self.translate_expr(result)),
+                    });
+                }
+                if let Some(else_result) = else_result {
+                    cases.push(pr::SwitchCase {
+                        condition: Box::new(pr::Expr::new(Literal::Boolean(true))),
+                        value: Box::new(self.translate_expr(else_result)),
+                    });
+                }
+                pr::Expr::new(ExprKind::Case(cases))
+            }
+            sql_ast::Expr::Function(function) => self.translate_function(function, expr),
+            _ => self.unsupported_expr(expr),
+        }
+    }
+
+    /// Translates aggregate functions, which are the only functions whose
+    /// PRQL counterparts are known.
+    fn translate_function(
+        &mut self,
+        function: &sql_ast::Function,
+        expr: &sql_ast::Expr,
+    ) -> pr::Expr {
+        let FunctionArguments::List(list) = &function.args else {
+            return self.unsupported_expr(expr);
+        };
+        let supported = function.filter.is_none()
+            && function.over.is_none()
+            && function.within_group.is_empty()
+            && list.clauses.is_empty();
+        let [FunctionArg::Unnamed(arg)] = list.args.as_slice() else {
+            return self.unsupported_expr(expr);
+        };
+        if !supported {
+            return self.unsupported_expr(expr);
+        }
+
+        let distinct = matches!(list.duplicate_treatment, Some(DuplicateTreatment::Distinct));
+        let name = function.name.to_string().to_lowercase();
+        let name = match (name.as_str(), distinct) {
+            ("count", false) => "count",
+            ("count", true) => "count_distinct",
+            ("sum", false) => "sum",
+            ("avg", false) => "average",
+            ("min", false) => "min",
+            ("max", false) => "max",
+            _ => return self.unsupported_expr(expr),
+        };
+        let arg = match arg {
+            FunctionArgExpr::Expr(arg) => self.translate_expr(arg),
+            FunctionArgExpr::Wildcard if name == "count" => ident("this"),
+            _ => return self.unsupported_expr(expr),
+        };
+        func_call(name, vec![arg])
+    }
+
+    fn unsupported_expr(&mut self, expr: &sql_ast::Expr) -> pr::Expr {
+        self.unsupported(format!("`{expr}`"));
+        null()
+    }
+}
+
+/// Translation of a `SELECT`: the transforms of its source, of its projection,
+/// and the names of the columns that it outputs.
+struct Projection {
+    source: Vec<pr::Expr>,
+    transforms: Vec<pr::Expr>,
+    names: Vec<String>,
+}
+
+fn translate_take(offset: Option<i64>, limit: Option<i64>) -> Option<pr::Expr> {
+    let int = |n: i64| Box::new(pr::Expr::new(Literal::Integer(n)));
+    Some(match (offset.filter(|o| *o > 0), limit) {
+        (None, Some(limit)) => *int(limit),
+        (None, None) => return None,
+        (Some(offset), limit) => pr::Expr::new(ExprKind::Range(pr::Range {
+            start: Some(int(offset + 1)),
+            end: limit.map(|limit| int(offset + limit)),
+        })),
+    })
+}
+
+fn translate_value(value: &Value) -> Option<Literal> {
+    Some(match value {
+        Value::Number(n, _) => match n.parse() {
+            Ok(i) => Literal::Integer(i),
+            Err(_) => Literal::Float(n.parse().ok()?),
+        },
+        Value::SingleQuotedString(s) => Literal::String(s.clone()),
+        Value::Boolean(b) => Literal::Boolean(*b),
+        Value::Null => Literal::Null,
+        _ => return None,
+    })
+}
+
+fn translate_binary_operator(op: &BinaryOperator) -> Option<BinOp> {
+    Some(match op {
+        BinaryOperator::Plus => BinOp::Add,
+        BinaryOperator::Minus => BinOp::Sub,
+        BinaryOperator::Multiply => BinOp::Mul,
+        BinaryOperator::Divide => BinOp::DivFloat,
+        BinaryOperator::Modulo => BinOp::Mod,
+        BinaryOperator::Eq => BinOp::Eq,
+        BinaryOperator::NotEq => BinOp::Ne,
+        BinaryOperator::Gt => BinOp::Gt,
+        BinaryOperator::Lt => BinOp::Lt,
+        BinaryOperator::GtEq => BinOp::Gte,
+        BinaryOperator::LtEq => BinOp::Lte,
+        BinaryOperator::And => BinOp::And,
+        BinaryOperator::Or => BinOp::Or,
+        _ => return None,
+    })
+}
+
+fn is_aggregation_item(item: &SelectItem) -> bool {
+    match item {
+        SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+            is_aggregation(expr)
+        }
+        _ => false,
+    }
+}
+
+fn is_aggregation(expr: &sql_ast::Expr) -> bool {
+    match expr {
+        sql_ast::Expr::Function(f) => {
+            let name = f.name.to_string().to_lowercase();
+            matches!(name.as_str(), "count" | "sum" | "avg" | "min" | "max")
+        }
+        sql_ast::Expr::Nested(expr) | sql_ast::Expr::UnaryOp { expr, .. } => is_aggregation(expr),
+        sql_ast::Expr::BinaryOp { left, right, .. } => {
+            is_aggregation(left) || is_aggregation(right)
+        }
+        _ => false,
+    }
+}
+
+/// Name of the column that an unaliased expression of a projection outputs.
+fn output_name(expr: &sql_ast::Expr) -> Option<String> {
+    match expr {
+        sql_ast::Expr::Identifier(i) => Some(i.value.clone()),
+        sql_ast::Expr::CompoundIdentifier(parts) => parts.last().map(|i| i.value.clone()),
+        _ => None,
+    }
+}
+
+fn object_name(name: &ObjectName) -> pr::Expr {
+    let mut parts = name.0.iter();
+    let first = parts.next().map_or("", |i| i.value.as_str());
+    parts.fold(ident(first), |base, part| {
+        let base = Box::new(base);
+        let field = pr::IndirectionKind::Name(part.value.clone());
+        pr::Expr::new(ExprKind::Indirection { base, field })
+    })
+}
+
+fn ident(name: &str) -> pr::Expr {
+    pr::Expr::new(ExprKind::Ident(name.to_string()))
+}
+
+fn null() -> pr::Expr {
+    pr::Expr::new(Literal::Null)
+}
+
+fn aliased(expr: pr::Expr, alias: &str) -> pr::Expr {
+    pr::Expr {
+        alias: Some(alias.to_string()),
+        ..expr
+    }
+}
+
+fn func_call(name: &str, args: Vec<pr::Expr>) -> pr::Expr {
+    pr::Expr::new(ExprKind::FuncCall(pr::FuncCall {
+        name: Box::new(ident(name)),
+        args,
+        named_args: Default::default(),
+    }))
+}
+
+fn binary(left: pr::Expr, op: BinOp, right: pr::Expr) -> pr::Expr {
+    pr::Expr::new(ExprKind::Binary(pr::BinaryExpr {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }))
+}
+
+fn unary(op: UnOp, expr: pr::Expr) -> pr::Expr {
+    pr::Expr::new(ExprKind::Unary(pr::UnaryExpr {
+        op,
+        expr: Box::new(expr),
+    }))
+}
+
+fn is_in(expr: pr::Expr, values: pr::Expr) -> pr::Expr {
+    let exprs = vec![expr, func_call("in", vec![values])];
+    pr::Expr::new(ExprKind::Pipeline(pr::Pipeline { exprs }))
+}
+
+fn negate_if(negated: bool, expr: pr::Expr) -> pr::Expr {
+    if negated {
+        unary(UnOp::Not, expr)
+    } else {
+        expr
+    }
+}
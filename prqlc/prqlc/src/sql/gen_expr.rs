@@ -3,22 +3,25 @@
 use std::cmp::Ordering;
 
 use itertools::Itertools;
-use prqlc_parser::generic::{InterpolateItem, Range};
+use prqlc_parser::generic::{InterpolateItem, Range, SwitchCase};
 use regex::Regex;
 use sqlparser::ast::{
     self as sql_ast, BinaryOperator, DateTimeField, Fetch, Function, FunctionArg, FunctionArgExpr,
-    FunctionArgumentList, ObjectName, OrderByExpr, SelectItem, UnaryOperator, Value,
-    WindowFrameBound, WindowSpec,
+    FunctionArgumentList, NamedWindowDefinition, NamedWindowExpr, ObjectName, OrderByExpr,
+    SelectItem, Top, TopQuantity, UnaryOperator, Value, WindowSpec,
 };
 
 use super::gen_projection::try_into_exprs;
-use super::{keywords, Context};
-use crate::ir::generic::{ColumnSort, SortDirection, WindowFrame, WindowKind};
+use super::{keywords, Context, Dialect};
+use crate::ir::generic::{
+    ColumnSort, NullsOrder, SortDirection, WindowFrame, WindowFrameBound, WindowKind,
+};
 use crate::ir::pl::{self, Ident, Literal};
 use crate::ir::rq;
+use crate::pr::PrimitiveSet;
 use crate::sql::pq::context::ColumnDecl;
 use crate::utils::{valid_ident, OrMap};
-use crate::{Error, Reason, Result, Span, WithErrorInfo};
+use crate::{Error, QuotingPolicy, Reason, Result, Span, WithErrorInfo};
 
 pub(super) fn translate_expr(expr: rq::Expr, ctx: &mut Context) -> Result<ExprOrSource> {
     Ok(match expr.kind {
@@ -41,7 +44,10 @@ pub(super) fn translate_expr(expr: rq::Expr, ctx: &mut Context) -> Result<ExprOr
             binding_strength: 100,
             window_frame: false,
         }),
-        rq::ExprKind::Literal(l) => translate_literal(l, ctx)?.into(),
+        rq::ExprKind::Literal(l) => match &mut ctx.params {
+            Some(params) => params.bind(l).into(),
+            None => translate_literal(l, ctx)?.into(),
+        },
         rq::ExprKind::Case(mut cases) => {
             let default = cases
                 .last()
@@ -59,9 +65,8 @@ pub(super) fn translate_expr(expr: rq::Expr, ctx: &mut Context) -> Result<ExprOr
                 cases.pop();
             }
 
-            let else_result = default
-                .or(Some(sql_ast::Expr::Value(Value::Null)))
-                .map(Box::new);
+            // without a default, SQL results in NULL
+            let else_result = default.map(Box::new);
 
             let cases: Vec<_> = cases
                 .into_iter()
@@ -102,11 +107,60 @@ pub(super) fn translate_expr(expr: rq::Expr, ctx: &mut Context) -> Result<ExprOr
                         }
                     }
                 }
-                "std.concat" => return Ok(process_concat(&expr, ctx)?.into()),
-                "std.array_in" => return Ok(process_array_in(&expr, args, ctx)?.into()),
+                "std.concat" => return Ok(process_concat(collect_concat_args(&expr), ctx)?.into()),
+                "std.coalesce" => {
+                    return Ok(process_coalesce(collect_coalesce_args(&expr), ctx)?.into())
+                }
+                "std.text.concat" => return Ok(process_text_concat(&expr, args, ctx)?.into()),
+                "std.text.concat_ws" => return Ok(process_concat_ws(&expr, args, ctx)?.into()),
+                "std.count_distinct" => {
+                    if let [rq::Expr {
+                        kind: rq::ExprKind::Array(columns),
+                        ..
+                    }] = args.as_slice()
+                    {
+                        return Ok(process_count_distinct(&expr, columns, ctx)?.into());
+                    }
+                }
+                "std.json.get" | "std.json.get_text" => {
+                    return Ok(process_json_get(&expr, name, args, ctx)?.into())
+                }
+                "std.array_in" => return Ok(process_array_in(&expr, args, false, ctx)?.into()),
+                "std.relation_in" => {
+                    return Ok(process_relation_in(&expr, args, false, ctx)?.into())
+                }
+                "std.not" => match args.as_slice() {
+                    [arg @ rq::Expr {
+                        kind: rq::ExprKind::Operator { name, args },
+                        ..
+                    }] if name == "std.array_in" => {
+                        return Ok(process_array_in(arg, args, true, ctx)?.into())
+                    }
+                    [arg @ rq::Expr {
+                        kind: rq::ExprKind::Operator { name, args },
+                        ..
+                    }] if name == "std.relation_in" => {
+                        return Ok(process_relation_in(arg, args, true, ctx)?.into())
+                    }
+                    _ => {}
+                },
+                "std.filtered" => {
+                    return Ok(ExprOrSource::Source(
+                        super::operators::translate_filtered_aggregation(expr, ctx)?,
+                    ))
+                }
+                "std.pivot" => return Ok(process_pivot(&expr, args, ctx)?.into()),
+                "std.struct_field" => return Ok(process_struct_field(&expr, args, ctx)?.into()),
+                "std.cast" => return Ok(process_cast(&expr, args, ctx)?.into()),
+                "std.array.unnest" if ctx.dialect.unnest_as_lateral_join() => {
+                    return Ok(process_lateral_unnest(&expr, args, ctx)?.into())
+                }
                 "std.date.to_text" => {
                     return Ok(process_date_to_text(&expr, name, args, ctx)?.into())
                 }
+                "std.date.trunc" if !ctx.dialect.has_date_trunc() => {
+                    return Ok(process_date_trunc(&expr, args, ctx)?.into())
+                }
                 _ => match try_into_between(expr.clone(), ctx)? {
                     Some(between_expr) => return Ok(between_expr.into()),
                     None => {
@@ -120,15 +174,32 @@ pub(super) fn translate_expr(expr: rq::Expr, ctx: &mut Context) -> Result<ExprOr
             }
             super::operators::translate_operator_expr(expr, ctx)?
         }
+        rq::ExprKind::Array(items) if ctx.dialect.supports_array_literals() => {
+            let elem = items
+                .into_iter()
+                .map(|item| translate_expr(item, ctx).map(|e| e.into_ast()))
+                .try_collect()?;
+            sql_ast::Expr::Array(sql_ast::Array { elem, named: false }).into()
+        }
         rq::ExprKind::Array(_) => {
             return Err(Error::new(Reason::Unexpected {
                 found: "array of values (not supported here)".to_string(),
             })
             .with_span(expr.span));
         }
+        rq::ExprKind::SubQuery(tid) => {
+            sql_ast::Expr::Subquery(Box::new(translate_sub_query(tid, ctx)?)).into()
+        }
     })
 }
 
+fn translate_sub_query(tid: rq::TId, ctx: &Context) -> Result<sql_ast::Query> {
+    ctx.sub_queries
+        .get(&tid)
+        .cloned()
+        .ok_or_else(|| Error::new_assert(format!("sub-query of table {tid:?} is not translated")))
+}
+
 /// Translates into IS NULL if possible
 fn process_null(name: &str, args: &[rq::Expr], ctx: &mut Context) -> Result<sql_ast::Expr> {
     let (a, b) = (&args[0], &args[1]);
@@ -156,10 +227,11 @@ fn process_null(name: &str, args: &[rq::Expr], ctx: &mut Context) -> Result<sql_
     }
 }
 
-/// Translates into IN (v1, v2, ...) if possible
+/// Translates into IN (v1, v2, ...) if possible, or NOT IN when negated
 fn process_array_in(
     expr: &rq::Expr,
     args: &[rq::Expr],
+    negated: bool,
     ctx: &mut Context,
 ) -> Result<sql_ast::Expr> {
     match args {
@@ -179,7 +251,7 @@ fn process_array_in(
                 // We avoid producing `in ()` expressions since they are not syntactically valid
                 // in some engines like PostgreSQL or MySQL.
                 // We can instead optimize this to a condition that is always false
-                Ok(sql_ast::Expr::Value(Value::Boolean(false)))
+                Ok(sql_ast::Expr::Value(Value::Boolean(negated)))
             } else {
                 Ok(sql_ast::Expr::InList {
                     expr: Box::new(translate_expr(col_expr.clone(), ctx)?.into_ast()),
@@ -187,7 +259,7 @@ fn process_array_in(
                         .iter()
                         .map(|a| Ok(translate_expr(a.clone(), ctx)?.into_ast()))
                         .collect::<Result<Vec<sql_ast::Expr>>>()?,
-                    negated: false,
+                    negated,
                 })
             }
         }
@@ -198,6 +270,102 @@ fn process_array_in(
     }
 }
 
+/// Translates into IN (SELECT ...), or NOT IN when negated
+fn process_relation_in(
+    expr: &rq::Expr,
+    args: &[rq::Expr],
+    negated: bool,
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let [value, rq::Expr {
+        kind: rq::ExprKind::SubQuery(tid),
+        ..
+    }] = args
+    else {
+        return Err(Error::new_simple(
+            "args to `std.relation_in` must be an expression and a relation",
+        )
+        .with_span(expr.span));
+    };
+
+    Ok(sql_ast::Expr::InSubquery {
+        expr: Box::new(translate_expr(value.clone(), ctx)?.into_ast()),
+        subquery: Box::new(translate_sub_query(*tid, ctx)?),
+        negated,
+    })
+}
+
+/// Translates a column of `pivot` into an aggregation over the rows that
+/// contain its value, i.e. `SUM(CASE WHEN quarter = 'Q1' THEN amount END)`.
+fn process_pivot(expr: &rq::Expr, args: &[rq::Expr], ctx: &mut Context) -> Result<sql_ast::Expr> {
+    let [aggregation, column, value] = args else {
+        return Err(
+            Error::new_simple("`std.pivot` requires an aggregation, a column and a value")
+                .with_span(expr.span),
+        );
+    };
+    let rq::ExprKind::Operator { name, args } = &aggregation.kind else {
+        return Err(
+            Error::new_simple("`pivot` requires calls of aggregation functions")
+                .with_span(aggregation.span),
+        );
+    };
+
+    let condition = rq::Expr {
+        kind: rq::ExprKind::Operator {
+            name: "std.eq".to_string(),
+            args: vec![column.clone(), value.clone()],
+        },
+        span: None,
+    };
+    let when_matches = |value: rq::Expr| SwitchCase {
+        condition: condition.clone(),
+        value,
+    };
+    let int = |i: i64| rq::Expr {
+        kind: rq::ExprKind::Literal(Literal::Integer(i)),
+        span: None,
+    };
+
+    let kind = if name == "std.count" {
+        // COUNT(*) has no arg to filter, so the matching rows are summed up instead
+        let cases = vec![
+            when_matches(int(1)),
+            SwitchCase {
+                condition: rq::Expr {
+                    kind: rq::ExprKind::Literal(Literal::Boolean(true)),
+                    span: None,
+                },
+                value: int(0),
+            },
+        ];
+        rq::ExprKind::Operator {
+            name: "std.sum".to_string(),
+            args: vec![rq::Expr {
+                kind: rq::ExprKind::Case(cases),
+                span: None,
+            }],
+        }
+    } else {
+        let args = (args.iter().cloned())
+            .map(|arg| rq::Expr {
+                kind: rq::ExprKind::Case(vec![when_matches(arg)]),
+                span: None,
+            })
+            .collect();
+        rq::ExprKind::Operator {
+            name: name.clone(),
+            args,
+        }
+    };
+
+    let aggregation = rq::Expr {
+        kind,
+        span: aggregation.span,
+    };
+    Ok(translate_expr(aggregation, ctx)?.into_ast())
+}
+
 /// Translates PRQL date format (based on `chrono` crate) to dialect specific date format
 /// For now only date format as string literal is supported
 fn process_date_to_text(
@@ -237,42 +405,72 @@ fn process_date_to_text(
     }
 }
 
-fn process_concat(expr: &rq::Expr, ctx: &mut Context) -> Result<sql_ast::Expr> {
-    if ctx.dialect.has_concat_function() {
-        let concat_args = collect_concat_args(expr);
+/// Emulates `DATE_TRUNC` by building the truncated date from its parts, with
+/// MySQL's date functions. Weeks start on Monday, as in other dialects.
+fn process_date_trunc(
+    expr: &rq::Expr,
+    args: &[rq::Expr],
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let [unit, column] = args else { unreachable!() };
+    // the unit was validated during resolution
+    let rq::ExprKind::SString(unit) = &unit.kind else {
+        unreachable!()
+    };
+    let [InterpolateItem::String(unit)] = unit.as_slice() else {
+        unreachable!()
+    };
 
-        let args_list = concat_args
-            .iter()
-            .map(|a| {
-                translate_expr((*a).clone(), ctx)
-                    .map(|x| FunctionArg::Unnamed(FunctionArgExpr::Expr(x.into_ast())))
-            })
-            .try_collect()?;
+    let template = match unit.as_str() {
+        "year" => "MAKEDATE(YEAR({}), 1)",
+        "quarter" => "(MAKEDATE(YEAR({}), 1) + INTERVAL (QUARTER({}) - 1) QUARTER)",
+        "month" => "(MAKEDATE(YEAR({}), 1) + INTERVAL (MONTH({}) - 1) MONTH)",
+        "week" => "(DATE({}) - INTERVAL WEEKDAY({}) DAY)",
+        "day" => "DATE({})",
+        "hour" => "TIMESTAMP(DATE({}), MAKETIME(HOUR({}), 0, 0))",
+        _ => unreachable!(),
+    };
 
-        let args = sql_ast::FunctionArguments::List(FunctionArgumentList {
-            args: args_list,
-            clauses: vec![],
-            duplicate_treatment: None,
+    let parts = template
+        .split("{}")
+        .map(|s| InterpolateItem::String(s.to_string()));
+    let column = InterpolateItem::Expr {
+        expr: Box::new(column.clone()),
+        format: None,
+    };
+    let items = Itertools::intersperse(parts, column).collect();
+
+    let expr = rq::Expr {
+        kind: rq::ExprKind::SString(items),
+        span: expr.span,
+    };
+    Ok(translate_expr(expr, ctx)?.into_ast())
+}
+
+/// Concatenates strings, treating NULLs as empty strings in all dialects.
+fn process_concat(concat_args: Vec<&rq::Expr>, ctx: &mut Context) -> Result<sql_ast::Expr> {
+    let ignores_nulls = ctx.dialect.concat_ignores_nulls();
+
+    let mut args = Vec::with_capacity(concat_args.len());
+    for arg in concat_args {
+        let translated = translate_expr(arg.clone(), ctx)?.into_ast();
+
+        let is_non_null_literal = matches!(&arg.kind, rq::ExprKind::Literal(l) if !l.is_null());
+        args.push(if ignores_nulls || is_non_null_literal {
+            translated
+        } else {
+            let empty = sql_ast::Expr::Value(Value::SingleQuotedString(String::new()));
+            function_call("COALESCE", vec![translated, empty])
         });
+    }
 
-        Ok(sql_ast::Expr::Function(Function {
-            name: ObjectName(vec![sql_ast::Ident::new("CONCAT")]),
-            args,
-            over: None,
-            filter: None,
-            null_treatment: None,
-            within_group: vec![],
-            parameters: sql_ast::FunctionArguments::None,
-        }))
+    if ctx.dialect.has_concat_function() {
+        Ok(function_call("CONCAT", args))
     } else {
-        let concat_args = collect_concat_args(expr);
-
-        let mut iter = concat_args.into_iter();
-        let first_expr = iter.next().unwrap();
-        let mut current_expr = translate_expr(first_expr.clone(), ctx)?.into_ast();
+        let mut iter = args.into_iter();
+        let mut current_expr = iter.next().unwrap();
 
-        for arg in iter {
-            let translated_arg = translate_expr(arg.clone(), ctx)?.into_ast();
+        for translated_arg in iter {
             current_expr = sql_ast::Expr::BinaryOp {
                 left: Box::new(current_expr),
                 op: BinaryOperator::StringConcat,
@@ -284,6 +482,434 @@ fn process_concat(expr: &rq::Expr, ctx: &mut Context) -> Result<sql_ast::Expr> {
     }
 }
 
+/// Translates a chain of coalesce operators, such as `a ?? b ?? c`, into a
+/// single `COALESCE(a, b, c)`.
+fn process_coalesce(args: Vec<&rq::Expr>, ctx: &mut Context) -> Result<sql_ast::Expr> {
+    let args = args
+        .into_iter()
+        .map(|arg| Ok(translate_expr(arg.clone(), ctx)?.into_ast()))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(function_call("COALESCE", args))
+}
+
+/// Translates `text.concat [a, b, ...]`
+fn process_text_concat(
+    expr: &rq::Expr,
+    args: &[rq::Expr],
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    match args {
+        [rq::Expr {
+            kind: rq::ExprKind::Array(parts),
+            ..
+        }] if !parts.is_empty() => {
+            process_concat(parts.iter().flat_map(collect_concat_args).collect(), ctx)
+        }
+        _ => Err(
+            Error::new_simple("arg to `std.text.concat` must be a non-empty array")
+                .with_span(expr.span),
+        ),
+    }
+}
+
+/// Translates `text.concat_ws sep [a, b, ...]`, which skips NULLs in all
+/// dialects.
+fn process_concat_ws(
+    expr: &rq::Expr,
+    args: &[rq::Expr],
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let [separator, rq::Expr {
+        kind: rq::ExprKind::Array(parts),
+        ..
+    }] = args
+    else {
+        return Err(Error::new_simple(
+            "args to `std.text.concat_ws` must be a separator and an array",
+        )
+        .with_span(expr.span));
+    };
+
+    let separator = translate_expr(separator.clone(), ctx)?.into_ast();
+    let parts: Vec<_> = parts
+        .iter()
+        .map(|a| Ok(translate_expr(a.clone(), ctx)?.into_ast()))
+        .collect::<Result<_>>()?;
+    let array = |elem| sql_ast::Expr::Array(sql_ast::Array { elem, named: false });
+
+    Ok(match ctx.dialect_enum {
+        // https://cloud.google.com/bigquery/docs/reference/standard-sql/array_functions#array_to_string
+        Dialect::BigQuery => function_call("ARRAY_TO_STRING", vec![array(parts), separator]),
+
+        // Snowflake's CONCAT_WS returns NULL when any of the args are NULL
+        Dialect::Snowflake => function_call(
+            "ARRAY_TO_STRING",
+            vec![function_call("ARRAY_CONSTRUCT_COMPACT", parts), separator],
+        ),
+
+        Dialect::ClickHouse => {
+            let x = sql_ast::Ident::new("x");
+            let not_null = sql_ast::Expr::Lambda(sql_ast::LambdaFunction {
+                params: sql_ast::OneOrManyWithParens::One(x.clone()),
+                body: Box::new(function_call(
+                    "isNotNull",
+                    vec![sql_ast::Expr::Identifier(x)],
+                )),
+            });
+            let non_null_parts = function_call("arrayFilter", vec![not_null, array(parts)]);
+
+            function_call("arrayStringConcat", vec![non_null_parts, separator])
+        }
+
+        _ => function_call("CONCAT_WS", [separator].into_iter().chain(parts).collect()),
+    })
+}
+
+/// Translates `count_distinct [a, b, ...]`, which counts the distinct
+/// combinations of the values of the columns. Where `COUNT` takes a single
+/// column, the values are concatenated, so a row with a null is not counted,
+/// as with `COUNT(DISTINCT a, b)`.
+fn process_count_distinct(
+    expr: &rq::Expr,
+    columns: &[rq::Expr],
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    if columns.is_empty() {
+        return Err(
+            Error::new_simple("`count_distinct` requires at least one column").with_span(expr.span),
+        );
+    }
+    let mut columns: Vec<_> = (columns.iter())
+        .map(|c| Ok(translate_expr(c.clone(), ctx)?.into_ast()))
+        .collect::<Result<_>>()?;
+
+    if !ctx.dialect.supports_count_distinct_columns() {
+        let separator = sql_ast::Expr::Value(Value::SingleQuotedString("|".to_string()));
+        let concat = |left, right| sql_ast::Expr::BinaryOp {
+            left: Box::new(left),
+            op: BinaryOperator::StringConcat,
+            right: Box::new(right),
+        };
+        let concatenated = (columns.into_iter())
+            .map(|column| match column {
+                sql_ast::Expr::Identifier(_) | sql_ast::Expr::CompoundIdentifier(_) => column,
+                column => sql_ast::Expr::Nested(Box::new(column)),
+            })
+            .reduce(|left, right| concat(concat(left, separator.clone()), right));
+        columns = concatenated.into_iter().collect();
+    }
+
+    let mut count = function_call("COUNT", columns);
+    if let sql_ast::Expr::Function(Function {
+        args: sql_ast::FunctionArguments::List(list),
+        ..
+    }) = &mut count
+    {
+        list.duplicate_treatment = Some(sql_ast::DuplicateTreatment::Distinct);
+    }
+    Ok(count)
+}
+
+/// A step of a JSON path, which is either a key of an object or an index of
+/// an array.
+enum JsonPathStep {
+    Key(String),
+    Index(u64),
+}
+
+/// Parses a JSON path such as `$.address.city` or `$.tags[0]`. The leading `$`
+/// may be omitted.
+fn parse_json_path(path: &str) -> Option<Vec<JsonPathStep>> {
+    let path = match path.strip_prefix('$') {
+        Some(steps) => steps.to_string(),
+        None => format!(".{path}"),
+    };
+
+    let mut steps = Vec::new();
+    let mut rest = path.as_str();
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            let key = &after_dot[..end];
+            if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return None;
+            }
+            steps.push(JsonPathStep::Key(key.to_string()));
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let (index, after_index) = after_bracket.split_once(']')?;
+            steps.push(JsonPathStep::Index(index.parse().ok()?));
+            rest = after_index;
+        } else {
+            return None;
+        }
+    }
+    (!steps.is_empty()).then_some(steps)
+}
+
+/// Translates `json.get path column` and `json.get_text path column`, where
+/// the latter extracts the value as text rather than as JSON.
+fn process_json_get(
+    expr: &rq::Expr,
+    op_name: &str,
+    args: &[rq::Expr],
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let [rq::Expr {
+        kind: rq::ExprKind::Literal(Literal::String(path)),
+        ..
+    }, column] = args
+    else {
+        return Err(Error::new_simple(format!(
+            "`{op_name}` requires a path, as a string literal, and a column"
+        ))
+        .with_span(expr.span));
+    };
+    let steps = parse_json_path(path).ok_or_else(|| {
+        Error::new_simple(format!("invalid JSON path `{path}`"))
+            .push_hint(
+                "a path is a sequence of keys and indexes, as in `$.address.city` or `$.tags[0]`",
+            )
+            .with_span(expr.span)
+    })?;
+    let as_text = op_name == "std.json.get_text";
+
+    let column = translate_expr(column.clone(), ctx)?.into_ast();
+    let json_path = steps.iter().fold("$".to_string(), |path, step| match step {
+        JsonPathStep::Key(key) => format!("{path}.{key}"),
+        JsonPathStep::Index(index) => format!("{path}[{index}]"),
+    });
+    let json_path = sql_ast::Expr::Value(Value::SingleQuotedString(json_path));
+    // the JSON operators of Postgres and Snowflake bind tighter than most
+    // expressions, so the column is parenthesized unless it is a name
+    let nested = |column| match column {
+        sql_ast::Expr::Identifier(_) | sql_ast::Expr::CompoundIdentifier(_) => column,
+        column => sql_ast::Expr::Nested(Box::new(column)),
+    };
+
+    Ok(match ctx.dialect_enum {
+        // https://www.postgresql.org/docs/current/functions-json.html
+        Dialect::Postgres => {
+            let last = steps.len() - 1;
+            steps
+                .into_iter()
+                .enumerate()
+                .fold(nested(column), |left, (i, step)| sql_ast::Expr::BinaryOp {
+                    left: Box::new(left),
+                    op: if as_text && i == last {
+                        BinaryOperator::LongArrow
+                    } else {
+                        BinaryOperator::Arrow
+                    },
+                    right: Box::new(sql_ast::Expr::Value(match step {
+                        JsonPathStep::Key(key) => Value::SingleQuotedString(key),
+                        JsonPathStep::Index(index) => Value::Number(index.to_string(), false),
+                    })),
+                })
+        }
+
+        Dialect::MySql => {
+            let json = function_call("JSON_EXTRACT", vec![column, json_path]);
+            if as_text {
+                function_call("JSON_UNQUOTE", vec![json])
+            } else {
+                json
+            }
+        }
+
+        // SQLite's JSON_EXTRACT already returns scalars as SQL values
+        Dialect::SQLite => function_call("JSON_EXTRACT", vec![column, json_path]),
+
+        // https://docs.snowflake.com/en/user-guide/querying-semistructured
+        Dialect::Snowflake => {
+            let access = sql_ast::Expr::JsonAccess {
+                value: Box::new(nested(column)),
+                path: sql_ast::JsonPath {
+                    path: steps
+                        .into_iter()
+                        .map(|step| match step {
+                            JsonPathStep::Key(key) => {
+                                sql_ast::JsonPathElem::Dot { key, quoted: false }
+                            }
+                            JsonPathStep::Index(index) => sql_ast::JsonPathElem::Bracket {
+                                key: sql_ast::Expr::Value(Value::Number(index.to_string(), false)),
+                            },
+                        })
+                        .collect(),
+                },
+            };
+            if as_text {
+                sql_ast::Expr::Cast {
+                    kind: sql_ast::CastKind::DoubleColon,
+                    expr: Box::new(access),
+                    data_type: sql_ast::DataType::Custom(
+                        ObjectName(vec![sql_ast::Ident::new("STRING")]),
+                        vec![],
+                    ),
+                    format: None,
+                }
+            } else {
+                access
+            }
+        }
+
+        Dialect::DuckDb => {
+            let name = if as_text {
+                "json_extract_string"
+            } else {
+                "json_extract"
+            };
+            function_call(name, vec![column, json_path])
+        }
+
+        _ => {
+            let name = if as_text { "JSON_VALUE" } else { "JSON_QUERY" };
+            function_call(name, vec![column, json_path])
+        }
+    })
+}
+
+fn process_cast(expr: &rq::Expr, args: &[rq::Expr], ctx: &mut Context) -> Result<sql_ast::Expr> {
+    let [value, rq::Expr {
+        kind: rq::ExprKind::Literal(Literal::String(ty)),
+        ..
+    }] = args
+    else {
+        return Err(
+            Error::new_simple("`std.cast` requires a value and a type name").with_span(expr.span),
+        );
+    };
+    let ty: PrimitiveSet = ty.parse().map_err(|_| {
+        Error::new_simple(format!("cannot cast to unknown type `{ty}`")).with_span(expr.span)
+    })?;
+
+    let type_name = ctx.dialect.cast_type(&ty);
+    Ok(sql_ast::Expr::Cast {
+        kind: sql_ast::CastKind::Cast,
+        expr: Box::new(translate_expr(value.clone(), ctx)?.into_ast()),
+        data_type: sql_ast::DataType::Custom(
+            ObjectName(vec![sql_ast::Ident::new(type_name)]),
+            vec![],
+        ),
+        format: None,
+    })
+}
+
+/// Translates an access of a field of a struct column, as in `address.city`.
+fn process_struct_field(
+    expr: &rq::Expr,
+    args: &[rq::Expr],
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let [column, rq::Expr {
+        kind: rq::ExprKind::Literal(Literal::String(field)),
+        ..
+    }] = args
+    else {
+        return Err(
+            Error::new_simple("`std.struct_field` requires a column and a field name")
+                .with_span(expr.span),
+        );
+    };
+    let column = translate_expr(column.clone(), ctx)?.into_ast();
+
+    let column = match column {
+        sql_ast::Expr::CompositeAccess { .. } => column,
+        sql_ast::Expr::Identifier(_) | sql_ast::Expr::CompoundIdentifier(_)
+            if !ctx.dialect.struct_field_requires_parentheses() =>
+        {
+            column
+        }
+        column => sql_ast::Expr::Nested(Box::new(column)),
+    };
+    Ok(sql_ast::Expr::CompositeAccess {
+        expr: Box::new(column),
+        key: translate_ident_part(field.clone(), ctx),
+    })
+}
+
+/// Translates `array.unnest` into a lateral join, which is added to FROM of
+/// the current query, and returns a reference to the element it produces.
+fn process_lateral_unnest(
+    expr: &rq::Expr,
+    args: &[rq::Expr],
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let [array] = args else {
+        return Err(
+            Error::new_simple("`std.array.unnest` requires a single arg").with_span(expr.span),
+        );
+    };
+    let array = translate_expr(array.clone(), ctx)?.into_ast();
+
+    let name = ctx.anchor.table_name.gen();
+    let alias = Some(sql_ast::TableAlias {
+        name: translate_ident_part(name.clone(), ctx),
+        columns: vec![],
+    });
+
+    let (relation, element) = match ctx.dialect_enum {
+        // https://cloud.google.com/bigquery/docs/reference/standard-sql/query-syntax#unnest_operator
+        Dialect::BigQuery => (
+            sql_ast::TableFactor::UNNEST {
+                alias,
+                array_exprs: vec![array],
+                with_offset: false,
+                with_offset_alias: None,
+                with_ordinality: false,
+            },
+            vec![translate_ident_part(name, ctx)],
+        ),
+
+        // https://docs.snowflake.com/en/sql-reference/functions/flatten
+        Dialect::Snowflake => (
+            sql_ast::TableFactor::Function {
+                lateral: true,
+                name: ObjectName(vec![sql_ast::Ident::new("FLATTEN")]),
+                // passed positionally, since our formatter breaks up `input => ...`
+                args: vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(array))],
+                alias,
+            },
+            vec![
+                translate_ident_part(name, ctx),
+                sql_ast::Ident::new("value"),
+            ],
+        ),
+
+        dialect => {
+            return Err(Error::new_simple(format!(
+                "lateral unnest is not implemented for dialect {dialect}"
+            ))
+            .with_span(expr.span))
+        }
+    };
+    ctx.query.lateral_relations.push(relation);
+
+    Ok(sql_ast::Expr::CompoundIdentifier(element))
+}
+
+pub(super) fn function_call(name: &str, args: Vec<sql_ast::Expr>) -> sql_ast::Expr {
+    let args = args
+        .into_iter()
+        .map(|a| FunctionArg::Unnamed(FunctionArgExpr::Expr(a)))
+        .collect();
+
+    sql_ast::Expr::Function(Function {
+        name: ObjectName(vec![sql_ast::Ident::new(name)]),
+        args: sql_ast::FunctionArguments::List(FunctionArgumentList {
+            args,
+            clauses: vec![],
+            duplicate_treatment: None,
+        }),
+        over: None,
+        filter: None,
+        null_treatment: None,
+        within_group: vec![],
+        parameters: sql_ast::FunctionArguments::None,
+    })
+}
+
 fn translate_binary_operator(
     left: &rq::Expr,
     right: &rq::Expr,
@@ -310,6 +936,15 @@ fn collect_concat_args(expr: &rq::Expr) -> Vec<&rq::Expr> {
     }
 }
 
+fn collect_coalesce_args(expr: &rq::Expr) -> Vec<&rq::Expr> {
+    match &expr.kind {
+        rq::ExprKind::Operator { name, args } if name == "std.coalesce" => {
+            args.iter().flat_map(collect_coalesce_args).collect()
+        }
+        _ => vec![expr],
+    }
+}
+
 /// Translate expr into a BETWEEN statement if possible, otherwise returns the expr unchanged.
 fn try_into_between(expr: rq::Expr, ctx: &mut Context) -> Result<Option<sql_ast::Expr>> {
     match expr.kind {
@@ -383,6 +1018,9 @@ pub(super) fn translate_literal(l: Literal, ctx: &Context) -> Result<sql_ast::Ex
         Literal::String(s) | Literal::RawString(s) => {
             sql_ast::Expr::Value(Value::SingleQuotedString(s))
         }
+        Literal::Boolean(b) if !ctx.dialect.supports_booleans() => {
+            sql_ast::Expr::Value(Value::Number(format!("{}", b as u8), false))
+        }
         Literal::Boolean(b) => sql_ast::Expr::Value(Value::Boolean(b)),
         Literal::Float(f) => sql_ast::Expr::Value(Value::Number(format!("{f:?}"), false)),
         Literal::Integer(i) => sql_ast::Expr::Value(Value::Number(format!("{i}"), false)),
@@ -415,7 +1053,27 @@ pub(super) fn translate_literal(l: Literal, ctx: &Context) -> Result<sql_ast::Ex
                     )))
                 }
             };
-            if ctx.dialect.requires_quotes_intervals() {
+            if ctx.dialect.is::<crate::sql::dialect::AnsiDialect>() {
+                // standard SQL quotes the value, and has no weeks nor units
+                // below seconds, which can be fractional instead
+                let (value, field) = match sql_parser_datetime {
+                    DateTimeField::Week(_) => ((vau.n * 7).to_string(), DateTimeField::Day),
+                    DateTimeField::Millisecond => {
+                        (seconds_fraction(vau.n, 3), DateTimeField::Second)
+                    }
+                    DateTimeField::Microsecond => {
+                        (seconds_fraction(vau.n, 6), DateTimeField::Second)
+                    }
+                    field => (vau.n.to_string(), field),
+                };
+                sql_ast::Expr::Interval(sqlparser::ast::Interval {
+                    value: Box::new(sql_ast::Expr::Value(Value::SingleQuotedString(value))),
+                    leading_field: Some(field),
+                    leading_precision: None,
+                    last_field: None,
+                    fractional_seconds_precision: None,
+                })
+            } else if ctx.dialect.requires_quotes_intervals() {
                 //postgres requires quotes around number and unit together eg '3 WEEK'
                 let value = Box::new(sql_ast::Expr::Value(Value::SingleQuotedString(format!(
                     "{} {}",
@@ -442,6 +1100,19 @@ pub(super) fn translate_literal(l: Literal, ctx: &Context) -> Result<sql_ast::Ex
     })
 }
 
+/// Formats `n` units of `10^-digits` seconds as a decimal number of seconds.
+fn seconds_fraction(n: i64, digits: u32) -> String {
+    let unit = 10_u64.pow(digits);
+    let sign = if n < 0 { "-" } else { "" };
+    let n = n.unsigned_abs();
+    format!(
+        "{sign}{}.{:0width$}",
+        n / unit,
+        n % unit,
+        width = digits as usize
+    )
+}
+
 fn translate_datetime_literal(
     data_type: sql_ast::DataType,
     value: String,
@@ -543,7 +1214,11 @@ pub(super) fn translate_cid(cid: rq::CId, ctx: &mut Context) -> Result<ExprOrSou
         // translate into ident
         let column_decl = &&ctx.anchor.column_decls[&cid];
 
-        let table_name = if let ColumnDecl::RelationColumn(riid, _, _) = column_decl {
+        // a renamed column of a relation is referred to by its alias, which
+        // does not belong to the relation
+        let table_name = if ctx.anchor.is_renamed(cid) {
+            None
+        } else if let ColumnDecl::RelationColumn(riid, _, _) = column_decl {
             let t = &ctx.anchor.relation_instances[riid];
             Some(t.table_ref.name.clone().unwrap())
         } else {
@@ -647,15 +1322,40 @@ pub(super) fn expr_of_i64(number: i64) -> sql_ast::Expr {
 }
 
 pub(super) fn fetch_of_i64(take: i64, ctx: &mut Context) -> Fetch {
-    let kind = rq::ExprKind::Literal(Literal::Integer(take));
-    let expr = rq::Expr { kind, span: None };
     Fetch {
-        quantity: Some(translate_expr(expr, ctx).unwrap().into_ast()),
+        quantity: Some(translate_take_bound(take, ctx).unwrap()),
         with_ties: false,
         percent: false,
     }
 }
 
+pub(super) fn top_of_i64(take: i64, ctx: &mut Context) -> Top {
+    let quantity = match bind_take_bound(take, ctx) {
+        Some(placeholder) => TopQuantity::Expr(placeholder),
+        None => TopQuantity::Constant(take.max(0) as u64),
+    };
+    Top {
+        quantity: Some(quantity),
+        with_ties: false,
+        percent: false,
+    }
+}
+
+/// Translates a bound of `take`, which is inlined into the SQL unless the
+/// query binds them to placeholders.
+pub(super) fn translate_take_bound(bound: i64, ctx: &mut Context) -> Result<sql_ast::Expr> {
+    match bind_take_bound(bound, ctx) {
+        Some(placeholder) => Ok(placeholder),
+        None => translate_literal(Literal::Integer(bound), ctx),
+    }
+}
+
+/// Binds a bound of `take` to a placeholder, if the query binds them.
+pub(super) fn bind_take_bound(bound: i64, ctx: &mut Context) -> Option<sql_ast::Expr> {
+    let params = ctx.params.as_mut().filter(|params| params.bind_take)?;
+    Some(params.bind(Literal::Integer(bound)))
+}
+
 pub(super) fn translate_select_item(cid: rq::CId, ctx: &mut Context) -> Result<SelectItem> {
     let expr = translate_cid(cid, ctx)?.into_ast();
 
@@ -691,6 +1391,17 @@ fn translate_windowed(
     ctx: &mut Context,
     span: Option<Span>,
 ) -> Result<ExprOrSource> {
+    if let (Some(version), Some(since)) = (ctx.version, ctx.dialect.window_functions_since()) {
+        if version < since {
+            return Err(Error::new_simple(format!(
+                "{} {version} does not support window functions",
+                ctx.dialect_enum
+            ))
+            .push_hint(format!("window functions were added in {since}"))
+            .with_span(span));
+        }
+    }
+
     let default_frame = {
         let (kind, range) = if window.sort.is_empty() {
             (WindowKind::Rows, Range::unbounded())
@@ -709,6 +1420,7 @@ fn translate_windowed(
         WindowFrame { kind, range }
     };
 
+    let shared_window = window.clone();
     let supports_frame = matches!(
         expr,
         ExprOrSource::Source(SourceExpr {
@@ -723,6 +1435,7 @@ fn translate_windowed(
         order_by: (window.sort)
             .into_iter()
             .map(|sort| translate_column_sort(&sort, ctx))
+            .flatten_ok()
             .try_collect()?,
         window_frame: if supports_frame && window.frame != default_frame {
             Some(try_into_window_frame(window.frame)?)
@@ -732,25 +1445,73 @@ fn translate_windowed(
     };
 
     let expr = expr.into_source();
+    let is_empty = window.partition_by.is_empty()
+        && window.order_by.is_empty()
+        && window.window_frame.is_none();
+    let name = if is_empty {
+        None
+    } else {
+        named_window(&window, &shared_window, ctx)
+    };
+    let text = match name {
+        Some(name) => format!("{expr} OVER {name}"),
+        None => format!("{expr} OVER ({window})"),
+    };
     Ok(ExprOrSource::Source(SourceExpr {
-        text: format!("{expr} OVER ({window})"),
+        text,
         binding_strength: 100,
         window_frame: false,
     }))
 }
 
+/// Name of a window in the WINDOW clause of the current query. Windows that
+/// are shared by the projection are defined when they are first referenced,
+/// and any later window with the same spec references them too.
+fn named_window(
+    window: &WindowSpec,
+    shared_window: &rq::Window,
+    ctx: &mut Context,
+) -> Option<sql_ast::Ident> {
+    let named_windows = &mut ctx.query.named_windows;
+    let existing = named_windows.iter().find(|w| match &w.1 {
+        NamedWindowExpr::WindowSpec(spec) => spec == window,
+        NamedWindowExpr::NamedWindow(_) => false,
+    });
+    if let Some(NamedWindowDefinition(name, _)) = existing {
+        return Some(name.clone());
+    }
+    if !ctx.query.shared_windows.contains(shared_window) {
+        return None;
+    }
+
+    let name = format!("window_{}", named_windows.len());
+    let name = translate_ident_part(name, ctx);
+    let definition = NamedWindowExpr::WindowSpec(window.clone());
+    (ctx.query.named_windows).push(NamedWindowDefinition(name.clone(), definition));
+    Some(name)
+}
+
 fn try_into_window_frame(frame: WindowFrame<rq::Expr>) -> Result<sql_ast::WindowFrame> {
-    fn parse_bound(bound: rq::Expr) -> Result<WindowFrameBound> {
-        let as_int = unpack_as_int_literal(bound)?;
-        Ok(match as_int {
-            0 => WindowFrameBound::CurrentRow,
-            1.. => WindowFrameBound::Following(Some(Box::new(sql_ast::Expr::Value(
-                sql_ast::Value::Number(as_int.to_string(), false),
-            )))),
-            _ => WindowFrameBound::Preceding(Some(Box::new(sql_ast::Expr::Value(
-                sql_ast::Value::Number((-as_int).to_string(), false),
-            )))),
-        })
+    fn parse_bound(bound: Option<rq::Expr>) -> Result<WindowFrameBound> {
+        let offset = bound.map(unpack_as_int_literal).transpose()?;
+        Ok(WindowFrameBound::from_offset(offset))
+    }
+
+    fn offset(n: i64) -> Option<Box<sql_ast::Expr>> {
+        Some(Box::new(sql_ast::Expr::Value(sql_ast::Value::Number(
+            n.abs().to_string(),
+            false,
+        ))))
+    }
+
+    fn translate_bound(bound: WindowFrameBound, is_start: bool) -> sql_ast::WindowFrameBound {
+        match bound {
+            WindowFrameBound::Unbounded if is_start => sql_ast::WindowFrameBound::Preceding(None),
+            WindowFrameBound::Unbounded => sql_ast::WindowFrameBound::Following(None),
+            WindowFrameBound::CurrentRow => sql_ast::WindowFrameBound::CurrentRow,
+            WindowFrameBound::Offset(n @ 1..) => sql_ast::WindowFrameBound::Following(offset(n)),
+            WindowFrameBound::Offset(n) => sql_ast::WindowFrameBound::Preceding(offset(n)),
+        }
     }
 
     Ok(sql_ast::WindowFrame {
@@ -758,33 +1519,66 @@ fn try_into_window_frame(frame: WindowFrame<rq::Expr>) -> Result<sql_ast::Window
             WindowKind::Rows => sql_ast::WindowFrameUnits::Rows,
             WindowKind::Range => sql_ast::WindowFrameUnits::Range,
         },
-        start_bound: if let Some(start) = frame.range.start {
-            parse_bound(start)?
-        } else {
-            WindowFrameBound::Preceding(None)
-        },
-        end_bound: Some(if let Some(end) = frame.range.end {
-            parse_bound(end)?
-        } else {
-            WindowFrameBound::Following(None)
-        }),
+        start_bound: translate_bound(parse_bound(frame.range.start)?, true),
+        end_bound: Some(translate_bound(parse_bound(frame.range.end)?, false)),
     })
 }
 
+/// Translates a column sort into items of ORDER BY. When the dialect doesn't
+/// support `NULLS FIRST` or `NULLS LAST`, the placement of nulls is emulated by
+/// sorting on whether the column is null first.
 pub(super) fn translate_column_sort(
     sort: &ColumnSort<rq::CId>,
     ctx: &mut Context,
-) -> Result<OrderByExpr> {
-    Ok(OrderByExpr {
-        expr: translate_cid(sort.column, ctx)?.into_ast(),
-        asc: if matches!(sort.direction, SortDirection::Asc) {
-            None // default order is ASC, so there is no need to emit it
-        } else {
-            Some(false)
+) -> Result<Vec<OrderByExpr>> {
+    let expr = translate_cid(sort.column, ctx)?.into_ast();
+    let asc = if matches!(sort.direction, SortDirection::Asc) {
+        None // default order is ASC, so there is no need to emit it
+    } else {
+        Some(false)
+    };
+
+    let Some(nulls) = sort.nulls else {
+        return Ok(vec![OrderByExpr {
+            expr,
+            asc,
+            nulls_first: None,
+            with_fill: None,
+        }]);
+    };
+    let nulls_first = matches!(nulls, NullsOrder::First);
+
+    if ctx.dialect.supports_nulls_order() {
+        return Ok(vec![OrderByExpr {
+            expr,
+            asc,
+            nulls_first: Some(nulls_first),
+            with_fill: None,
+        }]);
+    }
+
+    let number = |n: &str| sql_ast::Expr::Value(Value::Number(n.to_string(), false));
+    let (null_rank, other_rank) = if nulls_first { ("0", "1") } else { ("1", "0") };
+    let is_null = sql_ast::Expr::Case {
+        operand: None,
+        conditions: vec![sql_ast::Expr::IsNull(Box::new(expr.clone()))],
+        results: vec![number(null_rank)],
+        else_result: Some(Box::new(number(other_rank))),
+    };
+    Ok(vec![
+        OrderByExpr {
+            expr: is_null,
+            asc: None,
+            nulls_first: None,
+            with_fill: None,
         },
-        nulls_first: None,
-        with_fill: None,
-    })
+        OrderByExpr {
+            expr,
+            asc,
+            nulls_first: None,
+            with_fill: None,
+        },
+    ])
 }
 
 /// Translate a PRQL Ident to a Vec of SQL Idents.
@@ -813,12 +1607,18 @@ pub(super) fn translate_ident(
 }
 
 pub(super) fn translate_ident_part(ident: String, ctx: &Context) -> sql_ast::Ident {
-    let is_bare = valid_ident().is_match(&ident);
+    let quote = match ctx.quoting {
+        QuotingPolicy::Always => ident != "*",
+        QuotingPolicy::Never => false,
+        QuotingPolicy::WhenNeeded => {
+            !valid_ident().is_match(&ident) || keywords::is_keyword(&ident)
+        }
+    };
 
-    if is_bare && !keywords::is_keyword(&ident) {
-        sql_ast::Ident::new(ident)
-    } else {
+    if quote {
         sql_ast::Ident::with_quote(ctx.dialect.ident_quote(), ident)
+    } else {
+        sql_ast::Ident::new(ident)
     }
 }
 
@@ -894,6 +1694,8 @@ pub enum Associativity {
     /// `Both` means mathematically associative, like `+` or `*`
     Both,
     Right,
+    /// `Neither` means that the operator can't be chained, like `<` or `=`
+    Neither,
 }
 
 impl Associativity {
@@ -965,6 +1767,7 @@ impl SQLExpression for BinaryOperator {
         use BinaryOperator::*;
         match self {
             Minus | Divide | Modulo => Associativity::Left,
+            Gt | Lt | GtEq | LtEq | Eq | NotEq => Associativity::Neither,
             _ => Associativity::Both,
         }
     }
@@ -82,11 +82,13 @@ pub(super) fn translate_wildcards(ctx: &AnchorContext, cols: Vec<CId>) -> (Vec<C
 
     let mut output = Vec::new();
     for cid in cols {
-        // don't use cols that have been included by preceding star
-        let in_star = star
-            .as_mut()
-            .map(|s: &mut (CId, HashSet<CId>)| s.1.remove(&cid))
-            .unwrap_or_default();
+        // don't use cols that have been included by preceding star,
+        // unless they are projected under another name
+        let in_star = !ctx.is_renamed(cid)
+            && star
+                .as_mut()
+                .map(|s: &mut (CId, HashSet<CId>)| s.1.remove(&cid))
+                .unwrap_or_default();
         if in_star {
             continue;
         }
@@ -103,12 +105,19 @@ pub(super) fn translate_wildcards(ctx: &AnchorContext, cols: Vec<CId>) -> (Vec<C
 
                 // remove preceding cols that will be included with this star
                 if let Some((_, in_star)) = &mut star {
+                    let mut renamed = Vec::new();
                     while let Some(prev) = output.pop() {
-                        if !in_star.remove(&prev) {
+                        if !in_star.contains(&prev) {
                             output.push(prev);
                             break;
                         }
+                        if ctx.is_renamed(prev) {
+                            renamed.push(prev);
+                        } else {
+                            in_star.remove(&prev);
+                        }
                     }
+                    output.extend(renamed.into_iter().rev());
                 }
             }
         }
@@ -138,13 +147,17 @@ pub(super) fn translate_select_items(
             // wildcard case
             let t = &ctx.anchor.relation_instances[riid];
             let table_name = t.table_ref.name.clone().map(Ident::from_name);
+            let source = &ctx.anchor.table_decls[&t.table_ref.source];
+            let source = source.is_extern.then(|| source.name.clone()).flatten();
 
             let ident = translate_ident(table_name, Some("*".to_string()), ctx);
 
             // excluded columns
-            let opts = (excluded.remove(&cid))
-                .and_then(|excluded| translate_exclude(ctx, excluded))
-                .unwrap_or_default();
+            let opts = match excluded.remove(&cid) {
+                Some(excluded) => translate_exclude(ctx, excluded, source)?,
+                None => None,
+            }
+            .unwrap_or_default();
 
             Ok(if ident.len() > 1 {
                 let mut object_name = ident;
@@ -171,30 +184,49 @@ pub(super) fn translate_select_items(
     Ok(res)
 }
 
+/// Translates columns excluded from a star.
+///
+/// `source` is the name of the database table the star is reading from, if it does read from
+/// one. Because the columns of such table are not known, the exclusion cannot be emulated by
+/// listing the remaining columns.
 fn translate_exclude(
     ctx: &mut Context,
-    excluded: HashSet<CId>,
-) -> Option<WildcardAdditionalOptions> {
-    let excluded = as_col_names(&excluded, &ctx.anchor);
-
+    mut excluded: HashSet<CId>,
+    source: Option<Ident>,
+) -> Result<Option<WildcardAdditionalOptions>> {
     let Some(supported) = ctx.dialect.column_exclude() else {
-        // TODO: eventually this should throw an error
-        //   I don't want to do this now, because we have no way around it.
-        //   We could also ask the user to add table definitions.
-        if log::log_enabled!(log::Level::Warn) {
-            let excluded = excluded.join(", ");
+        // Columns that are also projected under another name can stay in the
+        // star, where they have their own name.
+        excluded.retain(|cid| !ctx.anchor.is_renamed(*cid));
+        if excluded.is_empty() {
+            return Ok(None);
+        }
+        let excluded = as_col_names(&excluded, &ctx.anchor);
+        let excluded = excluded.join(", ");
 
-            log::warn!("Columns {excluded} will be included with *, but were not requested.")
+        if let Some(source) = source {
+            return Err(Error::new_simple(format!(
+                "Excluding columns `{excluded}` from `{source}` is not supported by dialect {:?}, because the columns of `{source}` are not known",
+                ctx.dialect
+            ))
+            .push_hint(format!(
+                "declare the columns of `{source}` with `let {source} <[{{...}}]>` within `module default_db`"
+            ))
+            .push_hint("or select the columns explicitly"));
         }
-        return None;
+
+        // Internal columns (i.e. row numbers of a CTE) can end up in a star too.
+        // There is no way around this, so we include them in the result.
+        log::warn!("Columns {excluded} will be included with *, but were not requested.");
+        return Ok(None);
     };
 
-    let mut excluded = excluded
+    let mut excluded = as_col_names(&excluded, &ctx.anchor)
         .into_iter()
         .map(|name| translate_ident_part(name.to_string(), ctx))
         .collect_vec();
 
-    Some(match supported {
+    Ok(Some(match supported {
         ColumnExclude::Exclude => WildcardAdditionalOptions {
             opt_exclude: Some(ExcludeSelectItem::Multiple(excluded)),
             ..Default::default()
@@ -206,7 +238,7 @@ fn translate_exclude(
             }),
             ..Default::default()
         },
-    })
+    }))
 }
 
 fn as_col_names<'a>(cids: &'a HashSet<CId>, ctx: &'a AnchorContext) -> Vec<&'a str> {
@@ -2,6 +2,8 @@
 //! then to a String. We use sqlparser because it's trivial to create the string
 //! once it's in their AST (it's just `.to_string()`). It also lets us support a
 //! few dialects of SQL immediately.
+use std::collections::{HashMap, HashSet};
+
 use itertools::Itertools;
 use regex::Regex;
 use sqlparser::ast::{
@@ -11,30 +13,66 @@ use sqlparser::ast::{
 
 use super::gen_expr::*;
 use super::gen_projection::*;
-use super::operators::translate_operator;
-use super::pq::ast::{Cte, CteKind, RelationExpr, RelationExprKind, SqlRelation, SqlTransform};
-use super::{Context, Dialect};
+use super::operators::{coalesce_default, translate_operator};
+use super::pq::ast::{
+    CommentClause, Cte, CteKind, RelationExpr, RelationExprKind, SqlRelation, SqlTransform,
+};
+use super::pq::context::ColumnDecl;
+use super::{Context, Dialect, DialectVersion, QueryParams};
 use crate::debug;
 use crate::ir::pl::{JoinSide, Literal};
-use crate::ir::rq::{CId, Expr, ExprKind, RelationLiteral, RelationalQuery};
+use crate::ir::rq::{CId, Expr, ExprKind, RelationLiteral, RelationalQuery, TId, Window};
 use crate::utils::{BreakUp, Pluck};
-use crate::{Error, Result, WithErrorInfo};
+use crate::{Error, QuotingPolicy, Result, WithErrorInfo};
 use prqlc_parser::generic::InterpolateItem;
 
 type Transform = SqlTransform<RelationExpr, ()>;
 
-pub fn translate_query(query: RelationalQuery, dialect: Option<Dialect>) -> Result<sql_ast::Query> {
+#[allow(clippy::too_many_arguments)]
+pub fn translate_query(
+    query: RelationalQuery,
+    dialect: Option<Dialect>,
+    version: Option<DialectVersion>,
+    params: Option<QueryParams>,
+    inline_tables: HashSet<TId>,
+    prefer_subqueries: bool,
+    max_subquery_depth: Option<usize>,
+    quoting: QuotingPolicy,
+    alias_output_columns: bool,
+) -> Result<(sql_ast::Query, Option<QueryParams>)> {
     // compile from RQ to PQ
-    let (pq_query, mut ctx) = super::pq::compile_query(query, dialect)?;
+    let (pq_query, mut ctx) = super::pq::compile_query(
+        query,
+        dialect,
+        version,
+        inline_tables,
+        prefer_subqueries,
+        max_subquery_depth,
+    )?;
+    ctx.params = params;
+    ctx.quoting = quoting;
 
     debug::log_stage(debug::Stage::Sql(debug::StageSql::Main));
+
+    // translate sub-queries first, so expressions that use them can inline them
+    let (sub_queries, ctes): (Vec<_>, Vec<_>) =
+        (pq_query.ctes.into_iter()).partition(|cte| matches!(cte.kind, CteKind::SubQuery(_)));
+    for cte in sub_queries {
+        let CteKind::SubQuery(rel) = cte.kind else {
+            unreachable!()
+        };
+        let sub_query = translate_relation(rel, &mut ctx)?;
+        ctx.sub_queries.insert(cte.tid, sub_query);
+    }
+
+    ctx.query.name_columns = alias_output_columns;
     let mut query = translate_relation(pq_query.main_relation, &mut ctx)?;
 
-    if !pq_query.ctes.is_empty() {
+    if !ctes.is_empty() {
         // attach CTEs
         let mut cte_tables = Vec::new();
         let mut recursive = false;
-        for cte in pq_query.ctes {
+        for cte in ctes {
             let (cte, rec) = translate_cte(cte, &mut ctx)?;
             cte_tables.push(cte);
             recursive = recursive || rec;
@@ -46,7 +84,7 @@ pub fn translate_query(query: RelationalQuery, dialect: Option<Dialect>) -> Resu
     }
 
     debug::log_entry(|| debug::DebugEntryKind::ReprSqlParser(query.clone()));
-    Ok(query)
+    Ok((query, ctx.params))
 }
 
 fn translate_relation(relation: SqlRelation, ctx: &mut Context) -> Result<sql_ast::Query> {
@@ -58,13 +96,16 @@ fn translate_relation(relation: SqlRelation, ctx: &mut Context) -> Result<sql_as
     }
 }
 
-fn translate_pipeline(pipeline: Vec<Transform>, ctx: &mut Context) -> Result<sql_ast::Query> {
+fn translate_pipeline(mut pipeline: Vec<Transform>, ctx: &mut Context) -> Result<sql_ast::Query> {
     use SqlTransform::*;
 
+    let comments = pipeline.pluck(|t| t.into_comment());
+
     let (select, set_ops) =
         pipeline.break_up(|t| matches!(t, Union { .. } | Except { .. } | Intersect { .. }));
 
-    let select = translate_select_pipeline(select, ctx)?;
+    let mut select = translate_select_pipeline(select, ctx)?;
+    add_comments(&mut select, comments);
 
     translate_set_ops_pipeline(select, set_ops, ctx)
 }
@@ -73,10 +114,16 @@ fn translate_select_pipeline(
     mut pipeline: Vec<Transform>,
     ctx: &mut Context,
 ) -> Result<sql_ast::Query> {
+    if let Some(pivot) = find_native_pivot(&pipeline, ctx) {
+        return translate_native_pivot(pipeline, pivot, ctx);
+    }
+
     let table_count = count_tables(&pipeline);
     log::debug!("atomic query contains {table_count} tables");
+    let name_columns = std::mem::take(&mut ctx.query.name_columns);
     ctx.push_query();
-    ctx.query.omit_ident_prefix = table_count == 1;
+    // lateral joins add tables to FROM, so the columns must be prefixed
+    ctx.query.omit_ident_prefix = table_count == 1 && !has_lateral_unnest(&pipeline, ctx);
     ctx.query.pre_projection = true;
 
     let mut from: Vec<_> = pipeline
@@ -108,11 +155,33 @@ fn translate_select_pipeline(
         .into_iter()
         .exactly_one()
         .unwrap();
+    let selected = projection.clone();
+    if name_columns {
+        name_computed_columns(&projection, ctx);
+    }
     let projection = translate_wildcards(&ctx.anchor, projection);
+    ctx.query.shared_windows = if ctx.dialect.supports_named_windows() {
+        find_shared_windows(&projection.0, ctx)
+    } else {
+        Vec::new()
+    };
+    ctx.query.named_windows = Vec::new();
     let projection = translate_select_items(projection.0, projection.1, ctx)?;
 
+    // relations that the projection references
+    let lateral_relations = std::mem::take(&mut ctx.query.lateral_relations);
+    from.extend(
+        lateral_relations
+            .into_iter()
+            .map(|relation| TableWithJoins {
+                relation,
+                joins: vec![],
+            }),
+    );
+
     let order_by = pipeline.pluck(|t| t.into_sort());
     let takes = pipeline.pluck(|t| t.into_take());
+    let limit_bys = pipeline.pluck(|t| t.into_limit_by());
     let is_distinct = pipeline.iter().any(|t| matches!(t, SqlTransform::Distinct));
     let distinct_ons = pipeline.pluck(|t| t.into_distinct_on());
     let distinct = if is_distinct {
@@ -131,6 +200,8 @@ fn translate_select_pipeline(
         None
     };
 
+    let qualify = filter_of_conditions(pipeline.pluck(|t| t.into_qualify()), ctx)?;
+
     // Split the pipeline into before & after the aggregate
     let (mut before_agg, mut after_agg) =
         pipeline.break_up(|t| matches!(t, Transform::Aggregate { .. } | Transform::Union { .. }));
@@ -148,18 +219,29 @@ fn translate_select_pipeline(
 
     ctx.query.pre_projection = false;
 
-    let ranges = takes.into_iter().map(|x| x.range).collect();
+    // LIMIT n BY
+    let limit_by = (limit_bys.iter().flat_map(|(_, partition)| partition))
+        .map(|cid| {
+            // columns that are not selected are computed within LIMIT BY
+            ctx.query.pre_projection = !selected.contains(cid);
+            let expr = translate_cid(*cid, ctx).map(|x| x.into_ast());
+            ctx.query.pre_projection = false;
+            expr
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let ranges = (takes.into_iter().map(|x| x.range))
+        .chain(limit_bys.into_iter().map(|(range, _)| range))
+        .collect();
     let take = range_of_ranges(ranges)?;
     let offset = take.start.map(|s| s - 1).unwrap_or(0);
-    let limit = take.end.map(|e| e - offset);
+    let mut limit = take.end.map(|e| e - offset);
 
     let mut offset = if offset == 0 {
         None
     } else {
-        let kind = ExprKind::Literal(Literal::Integer(offset));
-        let expr = Expr { kind, span: None };
         Some(sqlparser::ast::Offset {
-            value: translate_expr(expr, ctx)?.into_ast(),
+            value: translate_take_bound(offset, ctx)?,
             rows: if ctx.dialect.use_fetch() {
                 sqlparser::ast::OffsetRows::Rows
             } else {
@@ -174,27 +256,50 @@ fn translate_select_pipeline(
         .map(|sorts| {
             sorts
                 .iter()
-                .map(|s| translate_column_sort(s, ctx))
+                .map(|s| {
+                    // columns that are not selected are computed within ORDER BY
+                    ctx.query.pre_projection = !selected.contains(&s.column);
+                    let sort = translate_column_sort(s, ctx);
+                    ctx.query.pre_projection = false;
+                    sort
+                })
+                .flatten_ok()
                 .try_collect()
         })
         .transpose()?
         .unwrap_or_default();
 
-    let (fetch, limit) = if ctx.dialect.use_fetch() {
+    // TOP has no offset
+    let top = match limit {
+        Some(l) if offset.is_none() && ctx.dialect.use_top() => {
+            limit = None;
+            Some(top_of_i64(l, ctx))
+        }
+        _ => None,
+    };
+
+    let (fetch, mut limit) = if ctx.dialect.use_fetch() {
         (limit.map(|l| fetch_of_i64(l, ctx)), None)
     } else {
-        (None, limit.map(expr_of_i64))
+        let limit = limit.map(|l| bind_take_bound(l, ctx).unwrap_or_else(|| expr_of_i64(l)));
+        (None, limit)
     };
 
-    // If we have a FETCH we need to make sure that:
+    if limit.is_none() && offset.is_some() && ctx.dialect.requires_limit_for_offset() {
+        limit = Some(sql_ast::Expr::Value(sql_ast::Value::Number(
+            "-1".to_string(),
+            false,
+        )));
+    }
+
+    // If we have a FETCH, some dialects need to make sure that:
     // - we have an OFFSET (set to 0)
-    // - we have an ORDER BY (see https://stackoverflow.com/a/44919325)
-    if fetch.is_some() {
+    // - we have an ORDER BY (see https://stackoverflow.com/a/44919325),
+    //   which an OFFSET alone requires too
+    if (fetch.is_some() || offset.is_some()) && ctx.dialect.fetch_requires_order() {
         if offset.is_none() {
-            let kind = ExprKind::Literal(Literal::Integer(0));
-            let expr = Expr { kind, span: None };
             offset = Some(sqlparser::ast::Offset {
-                value: translate_expr(expr, ctx)?.into_ast(),
+                value: translate_literal(Literal::Integer(0), ctx)?,
                 rows: sqlparser::ast::OffsetRows::Rows,
             })
         }
@@ -210,6 +315,7 @@ fn translate_select_pipeline(
         }
     }
 
+    let named_window = std::mem::take(&mut ctx.query.named_windows);
     ctx.pop_query();
 
     Ok(sql_ast::Query {
@@ -222,20 +328,338 @@ fn translate_select_pipeline(
             })
         },
         limit,
+        limit_by,
         offset,
         fetch,
         ..default_query(SetExpr::Select(Box::new(Select {
             distinct,
+            top,
             projection,
             from,
             selection: where_,
             group_by,
             having,
+            qualify,
+            named_window,
             ..default_select()
         })))
     })
 }
 
+/// Gives a name to the computed columns of a projection that do not have one,
+/// so they are emitted with an alias.
+fn name_computed_columns(projection: &[CId], ctx: &mut Context) {
+    for cid in projection {
+        let is_computed = matches!(
+            ctx.anchor.column_decls.get(cid),
+            Some(ColumnDecl::Compute(_))
+        );
+        if is_computed && !ctx.anchor.column_names.contains_key(cid) {
+            let name = ctx.anchor.col_name.gen();
+            ctx.anchor.column_names.insert(*cid, name);
+        }
+    }
+}
+
+/// Windows of more than one column of a projection, which are defined once in
+/// the WINDOW clause and referenced by name.
+fn find_shared_windows(projection: &[CId], ctx: &Context) -> Vec<Window> {
+    let windows = (projection.iter()).filter_map(|cid| match ctx.anchor.column_decls.get(cid) {
+        Some(ColumnDecl::Compute(compute)) => compute.window.as_ref(),
+        _ => None,
+    });
+
+    let mut counts: Vec<(&Window, usize)> = Vec::new();
+    for window in windows {
+        match counts.iter_mut().find(|(w, _)| *w == window) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((window, 1)),
+        }
+    }
+    (counts.into_iter())
+        .filter(|(_, count)| *count > 1)
+        .map(|(window, _)| window.clone())
+        .collect()
+}
+
+/// Columns of `pivot` that can be translated into the PIVOT of the dialect.
+struct NativePivot {
+    column: Expr,
+    values: Vec<Expr>,
+    aggregations: Vec<Expr>,
+}
+
+/// Finds an aggregation of `pivot` columns, which can be translated into the
+/// PIVOT of the dialect. It takes the place of GROUP BY, so the query must not
+/// contain anything that would have to be applied after it.
+fn find_native_pivot(pipeline: &[Transform], ctx: &Context) -> Option<NativePivot> {
+    if !ctx.dialect.supports_pivot() || count_tables(pipeline) != 1 {
+        return None;
+    }
+
+    let mut aggregate = None;
+    for transform in pipeline {
+        match transform {
+            Transform::From(_) | Transform::Select(_) => {}
+            Transform::Sort(sorts) if sorts.is_empty() => {}
+            Transform::Filter(_) if aggregate.is_none() => {}
+            Transform::Aggregate { partition, compute } if aggregate.is_none() => {
+                aggregate = Some((partition, compute));
+            }
+            _ => return None,
+        }
+    }
+    let (partition, compute) = aggregate?;
+
+    let mut cells = Vec::with_capacity(compute.len());
+    for cid in compute {
+        let Some(ColumnDecl::Compute(compute)) = ctx.anchor.column_decls.get(cid) else {
+            return None;
+        };
+        match &compute.expr.kind {
+            ExprKind::Operator { name, args } if name == "std.pivot" => {
+                let [aggregation, column, value] = args.as_slice() else {
+                    return None;
+                };
+                aggregation.kind.as_operator()?;
+                cells.push((aggregation, column, value));
+            }
+            _ => return None,
+        }
+    }
+
+    let (_, column, _) = *cells.first()?;
+    let values = cells.iter().map(|(_, _, v)| *v).dedup().collect_vec();
+    let per_value = cells.len() / values.len();
+    if per_value > 1 && !ctx.dialect.pivot_multiple_aggregations() {
+        return None;
+    }
+
+    // each of the values must have the same aggregations
+    let aggregations = cells[..per_value].iter().map(|(a, _, _)| *a).collect_vec();
+    let is_grid = cells.chunks(per_value).all(|chunk| {
+        chunk
+            .iter()
+            .map(|(a, _, _)| *a)
+            .eq(aggregations.iter().copied())
+            && chunk
+                .iter()
+                .all(|(_, c, v)| *c == column && *v == chunk[0].2)
+    });
+    let selected = pipeline.iter().filter_map(|t| t.as_select()).flatten();
+    if !is_grid
+        || !selected
+            .into_iter()
+            .all(|c| partition.contains(c) || compute.contains(c))
+    {
+        return None;
+    }
+
+    Some(NativePivot {
+        column: column.clone(),
+        values: values.into_iter().cloned().collect(),
+        aggregations: aggregations.into_iter().cloned().collect(),
+    })
+}
+
+/// Translates into `SELECT ... FROM (SELECT ...) PIVOT(... FOR column IN (...))`.
+fn translate_native_pivot(
+    mut pipeline: Vec<Transform>,
+    pivot: NativePivot,
+    ctx: &mut Context,
+) -> Result<sql_ast::Query> {
+    ctx.push_query();
+    ctx.query.omit_ident_prefix = true;
+    ctx.query.pre_projection = true;
+
+    let source = pipeline
+        .pluck(|t| t.into_from())
+        .into_iter()
+        .exactly_one()
+        .unwrap();
+    let from = vec![TableWithJoins {
+        relation: translate_relation_expr(source, ctx)?,
+        joins: vec![],
+    }];
+    let where_ = filter_of_conditions(pipeline.pluck(|t| t.into_filter()), ctx)?;
+    let (partition, compute) = pipeline
+        .pluck(|t| t.into_aggregate())
+        .into_iter()
+        .next()
+        .unwrap();
+    let projection = pipeline
+        .pluck(|t| t.into_select())
+        .into_iter()
+        .exactly_one()
+        .unwrap();
+
+    // PIVOT groups by all of the other columns of its input, so the input
+    // contains only the partition, the pivoted column and the aggregated args
+    let mut input = Vec::new();
+    let mut input_names = Vec::new();
+    for cid in &partition {
+        input.push(translate_select_item(*cid, ctx)?);
+        input_names.push(ctx.anchor.column_names[cid].clone());
+    }
+    let mut reference = |expr: Expr, ctx: &mut Context| -> Result<String> {
+        let expr = translate_expr(expr, ctx)?.into_ast();
+        let name = match &expr {
+            sql_ast::Expr::CompoundIdentifier(parts) => parts.last().unwrap().value.clone(),
+            _ => ctx.anchor.col_name.gen(),
+        };
+        if !input_names.contains(&name) {
+            input.push(if matches!(expr, sql_ast::Expr::CompoundIdentifier(_)) {
+                SelectItem::UnnamedExpr(expr)
+            } else {
+                SelectItem::ExprWithAlias {
+                    expr,
+                    alias: translate_ident_part(name.clone(), ctx),
+                }
+            });
+            input_names.push(name.clone());
+        }
+        Ok(name)
+    };
+
+    let column = reference(pivot.column, ctx)?;
+    let mut aggregations = Vec::with_capacity(pivot.aggregations.len());
+    for aggregation in pivot.aggregations {
+        let (name, args) = aggregation.kind.into_operator().unwrap();
+        let args = (args.into_iter())
+            .map(|arg| -> Result<Expr> {
+                if matches!(arg.kind, ExprKind::Literal(_)) {
+                    return Ok(arg);
+                }
+                let name = reference(arg, ctx)?;
+                let ident = translate_ident_part(name, ctx).to_string();
+                Ok(Expr {
+                    kind: ExprKind::SString(vec![InterpolateItem::String(ident)]),
+                    span: None,
+                })
+            })
+            .try_collect()?;
+        let kind = ExprKind::Operator { name, args };
+        aggregations.push(Expr {
+            kind,
+            ..aggregation
+        });
+    }
+
+    let input = default_query(SetExpr::Select(Box::new(Select {
+        projection: input,
+        from,
+        selection: where_,
+        ..default_select()
+    })));
+
+    // With a single aggregation, columns are named after the values. With more,
+    // they are named `{aggregation}_{value}`.
+    let single = aggregations.len() == 1;
+    let value_names = (pivot.values.iter())
+        .map(|v| match &v.kind {
+            ExprKind::Literal(Literal::String(s)) => s.clone(),
+            ExprKind::Literal(literal) => literal.to_string(),
+            _ => ctx.anchor.col_name.gen(),
+        })
+        .collect_vec();
+    // names of the aggregations are recovered from the columns of the first value
+    let aggregation_names = (compute.iter().take(aggregations.len()))
+        .map(|cid| {
+            (ctx.anchor.column_names.get(cid))
+                .and_then(|n| n.strip_suffix(&format!("_{}", value_names[0])))
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| ctx.anchor.col_name.gen())
+        })
+        .collect_vec();
+
+    let mut output_names = HashMap::new();
+    for (index, cid) in compute.iter().enumerate() {
+        let value = &value_names[index / aggregations.len()];
+        let name = if single {
+            (ctx.anchor.column_names.get(cid).cloned()).unwrap_or_else(|| value.clone())
+        } else {
+            format!("{}_{value}", aggregation_names[index % aggregations.len()])
+        };
+        output_names.insert(*cid, (name, index % aggregations.len()));
+    }
+
+    let defaults = (aggregations.iter())
+        .map(|a| coalesce_default(a.kind.as_operator().unwrap().0, ctx.dialect_enum))
+        .collect_vec();
+    ctx.query.pivot_aggregation = true;
+    let aggregate_functions = (aggregations.into_iter().zip(aggregation_names))
+        .map(|(aggregation, name)| {
+            Ok(sql_ast::ExprWithAlias {
+                expr: translate_expr(aggregation, ctx)?.into_ast(),
+                alias: (!single).then(|| translate_ident_part(name, ctx)),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ctx.query.pivot_aggregation = false;
+    let values = (pivot.values.into_iter().enumerate())
+        .map(|(index, value)| {
+            let alias = if single {
+                output_names[&compute[index]].0.clone()
+            } else {
+                value_names[index].clone()
+            };
+            Ok(sql_ast::ExprWithAlias {
+                expr: translate_expr(value, ctx)?.into_ast(),
+                alias: Some(translate_ident_part(alias, ctx)),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let relation = TableFactor::Pivot {
+        table: Box::new(TableFactor::Derived {
+            lateral: false,
+            subquery: Box::new(input),
+            alias: None,
+        }),
+        aggregate_functions,
+        value_column: vec![translate_ident_part(column, ctx)],
+        value_source: sql_ast::PivotValueSource::List(values),
+        default_on_null: None,
+        alias: None,
+    };
+
+    // refer to the output of PIVOT by name
+    let projection = (projection.into_iter())
+        .map(|cid| {
+            let (name, default) = match output_names.get(&cid) {
+                Some((name, aggregation)) => (name.clone(), defaults[*aggregation].clone()),
+                None => (ctx.anchor.column_names[&cid].clone(), None),
+            };
+            let mut expr = sql_ast::Expr::Identifier(translate_ident_part(name.clone(), ctx));
+            if let Some(default) = default {
+                let default = sql_ast::Expr::Value(sql_ast::Value::Placeholder(default));
+                expr = function_call("COALESCE", vec![expr, default]);
+            }
+            let expected = ctx.anchor.column_names.entry(cid).or_insert(name.clone());
+            if *expected == name && matches!(expr, sql_ast::Expr::Identifier(_)) {
+                SelectItem::UnnamedExpr(expr)
+            } else {
+                let alias = expected.clone();
+                SelectItem::ExprWithAlias {
+                    expr,
+                    alias: translate_ident_part(alias, ctx),
+                }
+            }
+        })
+        .collect();
+
+    ctx.pop_query();
+
+    Ok(default_query(SetExpr::Select(Box::new(Select {
+        projection,
+        from: vec![TableWithJoins {
+            relation,
+            joins: vec![],
+        }],
+        ..default_select()
+    }))))
+}
+
 fn translate_set_ops_pipeline(
     mut top: sql_ast::Query,
     mut pipeline: Vec<Transform>,
@@ -293,6 +717,123 @@ fn translate_set_ops_pipeline(
     Ok(top)
 }
 
+/// Marks the clauses of a query with the comments of the transforms they were
+/// compiled from. Markers are block comments in front of the content of the
+/// clause, which [super::place_comments] later turns into `--` comments above
+/// the clause.
+///
+/// Comments of clauses that the query does not contain are placed at SELECT.
+fn add_comments(query: &mut sql_ast::Query, comments: Vec<(CommentClause, String)>) {
+    let SetExpr::Select(select) = query.body.as_mut() else {
+        return;
+    };
+    let joins = select.from.iter().map(|f| f.joins.len()).sum::<usize>();
+
+    let mut by_clause: Vec<(CommentClause, Vec<String>)> = Vec::new();
+    for (clause, comment) in comments {
+        let exists = match clause {
+            CommentClause::Select => true,
+            CommentClause::From => !select.from.is_empty(),
+            CommentClause::Join(index) => index < joins,
+            CommentClause::Where => select.selection.is_some(),
+            CommentClause::GroupBy => {
+                matches!(&select.group_by, sql_ast::GroupByExpr::Expressions(e, _) if !e.is_empty())
+            }
+            CommentClause::Having => select.having.is_some(),
+            CommentClause::Qualify => select.qualify.is_some(),
+            CommentClause::OrderBy => query.order_by.is_some(),
+            CommentClause::Limit => {
+                query.limit.is_some() || query.fetch.is_some() || query.offset.is_some()
+            }
+        };
+        let clause = if exists {
+            clause
+        } else {
+            CommentClause::Select
+        };
+
+        match by_clause.iter_mut().find(|(c, _)| *c == clause) {
+            Some((_, group)) => group.push(comment),
+            None => by_clause.push((clause, vec![comment])),
+        }
+    }
+
+    for (clause, comments) in by_clause {
+        let markers = |keyword: &str| {
+            (comments.iter())
+                .flat_map(|c| c.lines())
+                .map(|line| {
+                    format!(
+                        "/* prql-comment {keyword}: {} */",
+                        line.replace("*/", "* /")
+                    )
+                })
+                .join(" ")
+        };
+        let prefix_expr = |expr: &mut sql_ast::Expr, keyword: &str| {
+            let ident = sql_ast::Ident::new(format!("{} {expr}", markers(keyword)));
+            *expr = sql_ast::Expr::Identifier(ident);
+        };
+        let prefix_relation = |relation: &mut TableFactor, keyword: &str| {
+            let ident = sql_ast::Ident::new(format!("{} {relation}", markers(keyword)));
+            *relation = TableFactor::Table {
+                name: sql_ast::ObjectName(vec![ident]),
+                alias: None,
+                args: None,
+                with_hints: vec![],
+                with_ordinality: false,
+                version: None,
+                partitions: vec![],
+            };
+        };
+
+        match clause {
+            CommentClause::Select => {
+                if let Some(item) = select.projection.first_mut() {
+                    let ident = sql_ast::Ident::new(format!("{} {item}", markers("SELECT")));
+                    *item = SelectItem::UnnamedExpr(sql_ast::Expr::Identifier(ident));
+                }
+            }
+            CommentClause::From => prefix_relation(&mut select.from[0].relation, "FROM"),
+            CommentClause::Join(index) => {
+                let join = select.from.iter_mut().flat_map(|f| &mut f.joins).nth(index);
+                let join = join.unwrap();
+                let keyword = match join.join_operator {
+                    JoinOperator::LeftOuter(_) => "LEFT JOIN",
+                    JoinOperator::RightOuter(_) => "RIGHT JOIN",
+                    JoinOperator::FullOuter(_) => "FULL JOIN",
+                    JoinOperator::CrossJoin => "CROSS JOIN",
+                    _ => "JOIN",
+                };
+                prefix_relation(&mut join.relation, keyword);
+            }
+            CommentClause::Where => prefix_expr(select.selection.as_mut().unwrap(), "WHERE"),
+            CommentClause::GroupBy => {
+                if let sql_ast::GroupByExpr::Expressions(exprs, _) = &mut select.group_by {
+                    prefix_expr(&mut exprs[0], "GROUP BY");
+                }
+            }
+            CommentClause::Having => prefix_expr(select.having.as_mut().unwrap(), "HAVING"),
+            CommentClause::Qualify => prefix_expr(select.qualify.as_mut().unwrap(), "QUALIFY"),
+            CommentClause::OrderBy => {
+                let order_by = query.order_by.as_mut().unwrap();
+                prefix_expr(&mut order_by.exprs[0].expr, "ORDER BY");
+            }
+            CommentClause::Limit => {
+                if let Some(limit) = &mut query.limit {
+                    prefix_expr(limit, "LIMIT");
+                } else if let Some(quantity) =
+                    query.fetch.as_mut().and_then(|f| f.quantity.as_mut())
+                {
+                    prefix_expr(quantity, "FETCH");
+                } else if let Some(offset) = &mut query.offset {
+                    prefix_expr(&mut offset.value, "OFFSET");
+                }
+            }
+        }
+    }
+}
+
 fn translate_relation_expr(relation_expr: RelationExpr, ctx: &mut Context) -> Result<TableFactor> {
     let alias = Some(&relation_expr.riid)
         .and_then(|riid| ctx.anchor.relation_instances.get(riid))
@@ -347,6 +888,14 @@ fn translate_join(
 ) -> Result<Join> {
     let relation = translate_relation_expr(with, ctx)?;
 
+    if side == JoinSide::Inner && matches!(filter.kind, ExprKind::Literal(Literal::Boolean(true))) {
+        return Ok(Join {
+            relation,
+            join_operator: JoinOperator::CrossJoin,
+            global: false,
+        });
+    }
+
     let constraint = JoinConstraint::On(translate_expr(filter, ctx)?.into_ast());
 
     Ok(Join {
@@ -371,6 +920,9 @@ fn translate_cte(cte: Cte, ctx: &mut Context) -> Result<(sql_ast::Cte, bool)> {
         // base case
         CteKind::Normal(rel) => (translate_relation(rel, ctx)?, false),
 
+        // sub-queries are inlined into expressions
+        CteKind::SubQuery(_) => unreachable!(),
+
         // special: WITH RECURSIVE
         CteKind::Loop { initial, step } => {
             // compile initial
@@ -432,13 +984,13 @@ fn translate_cte(cte: Cte, ctx: &mut Context) -> Result<(sql_ast::Cte, bool)> {
     Ok((cte, recursive))
 }
 
-fn translate_relation_literal(data: RelationLiteral, ctx: &Context) -> Result<sql_ast::Query> {
-    // TODO: this could be made to use VALUES instead of SELECT UNION ALL SELECT
-    //       I'm not sure about compatibility though.
-    // edit: probably not, because VALUES has no way of setting names of the columns
-    //       Postgres will just name them column1, column2 as so on.
-    //       Which means we can use VALUES, but only if this is not the top-level statement,
-    //       where they really matter.
+fn translate_relation_literal(data: RelationLiteral, ctx: &mut Context) -> Result<sql_ast::Query> {
+    // VALUES has no way of naming its columns (Postgres names them column1,
+    // column2 and so on), so it is wrapped into a SELECT, which names them
+    // with an alias of the derived table.
+    if !data.rows.is_empty() && ctx.dialect.supports_values_in_from() {
+        return translate_relation_literal_values(data, ctx);
+    }
 
     if data.rows.is_empty() {
         let mut nulls: Vec<_> = (data.columns.iter())
@@ -493,6 +1045,47 @@ fn translate_relation_literal(data: RelationLiteral, ctx: &Context) -> Result<sq
     Ok(default_query(body))
 }
 
+/// Translates a relation literal to
+/// `SELECT * FROM (VALUES (...), (...)) AS table_0 (col_a, col_b)`.
+fn translate_relation_literal_values(
+    data: RelationLiteral,
+    ctx: &mut Context,
+) -> Result<sql_ast::Query> {
+    let rows = (data.rows.into_iter())
+        .map(|row| {
+            (row.into_iter())
+                .map(|value| translate_literal(value, ctx))
+                .try_collect()
+        })
+        .try_collect()?;
+    let values = default_query(SetExpr::Values(sql_ast::Values {
+        explicit_row: false,
+        rows,
+    }));
+
+    let alias = TableAlias {
+        name: sql_ast::Ident::new(ctx.anchor.table_name.gen()),
+        columns: (data.columns.into_iter())
+            .map(|col| translate_ident_part(col, ctx))
+            .collect(),
+    };
+
+    Ok(default_query(SetExpr::Select(Box::new(Select {
+        projection: vec![SelectItem::Wildcard(
+            sql_ast::WildcardAdditionalOptions::default(),
+        )],
+        from: vec![TableWithJoins {
+            relation: TableFactor::Derived {
+                lateral: false,
+                subquery: Box::new(values),
+                alias: Some(alias),
+            },
+            joins: vec![],
+        }],
+        ..default_select()
+    }))))
+}
+
 pub(super) fn translate_query_sstring(
     items: Vec<InterpolateItem<Expr>>,
     ctx: &mut Context,
@@ -651,6 +1244,29 @@ fn count_tables(transforms: &[Transform]) -> usize {
 
     count
 }
+
+/// Whether the projection contains an unnest that will be translated into a
+/// lateral join.
+fn has_lateral_unnest(transforms: &[Transform], ctx: &Context) -> bool {
+    fn contains_unnest(expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Operator { name, args } => {
+                name == "std.array.unnest" || args.iter().any(contains_unnest)
+            }
+            _ => false,
+        }
+    }
+
+    ctx.dialect.unnest_as_lateral_join()
+        && (transforms.iter())
+            .filter_map(|t| t.as_select())
+            .flatten()
+            .any(|cid| match ctx.anchor.column_decls.get(cid) {
+                Some(ColumnDecl::Compute(compute)) => contains_unnest(&compute.expr),
+                _ => false,
+            })
+}
+
 #[cfg(test)]
 mod test {
     use insta::assert_snapshot;
@@ -1,6 +1,7 @@
 //! Backend for translating RQ into SQL
 
 mod dialect;
+mod from_sql;
 mod gen_expr;
 mod gen_projection;
 mod gen_query;
@@ -8,36 +9,126 @@ mod keywords;
 mod operators;
 mod pq;
 
-pub use dialect::{Dialect, SupportLevel};
+pub use dialect::{Dialect, DialectVersion, SupportLevel};
 pub use pq::ast as pq_ast;
 
+pub(crate) use from_sql::sql_to_pl;
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use regex::Regex;
+use sqlparser::ast as sql_ast;
+
 use self::dialect::DialectHandler;
 use self::pq::ast::Cte;
 use self::pq::context::AnchorContext;
 use crate::debug;
-use crate::ir::rq;
-use crate::Result;
-use crate::{compiler_version, Options};
+use crate::ir::pl::Literal;
+use crate::ir::rq::{self, TId};
+use crate::{compiler_version, Error, Options, QuotingPolicy, Result, WithErrorInfo};
 
 /// Translate a PRQL AST into a SQL string.
 pub fn compile(query: rq::RelationalQuery, options: &Options) -> Result<String> {
+    Ok(compile_with_params(query, options, None)?.0)
+}
+
+/// Translate a PRQL AST into a SQL string with placeholders in place of
+/// literals, and the values bound to the placeholders, in order.
+pub fn compile_parameterized(
+    query: rq::RelationalQuery,
+    options: &Options,
+) -> Result<(String, Vec<Literal>)> {
+    let params = QueryParams {
+        bind_take: options.bind_take,
+        values: Vec::new(),
+    };
+    compile_with_params(query, options, Some(params))
+}
+
+fn compile_with_params(
+    query: rq::RelationalQuery,
+    options: &Options,
+    params: Option<QueryParams>,
+) -> Result<(String, Vec<Literal>)> {
     let crate::Target::Sql(dialect) = options.target;
-    let sql_ast = gen_query::translate_query(query, dialect)?;
+    if options.strict {
+        ensure_declared_target(&query, dialect)?;
+    }
+    let resolved_dialect = resolve_dialect(&query, dialect)?;
+    let query = pq::preprocess::prune_tables(query)?;
+    let query = pq::preprocess::merge_filters(query)?;
+    let query = if options.simplify_predicates {
+        pq::preprocess::simplify_predicates(query)?
+    } else {
+        query
+    };
+    let query = pq::preprocess::eliminate_constant_filters(query)?;
+    let query = if options.push_down_predicates {
+        pq::preprocess::push_down_predicates(query)?
+    } else {
+        query
+    };
+    let full_join_since = resolved_dialect.handler().full_join_since();
+    let query = if resolved_dialect.handler().supports_full_join()
+        && !DialectVersion::predates(options.target_version, full_join_since)
+    {
+        query
+    } else {
+        pq::preprocess::emulate_full_joins(query)?
+    };
+    let query = pq::preprocess::order_group_columns(query, options.interleave_group_columns)?;
+    let query = pq::preprocess::merge_duplicate_tables(query)?;
+    let query = if resolved_dialect.handler().supports_booleans() {
+        query
+    } else {
+        pq::preprocess::emulate_booleans(query)?
+    };
+    let inline_tables = if options.inline_ctes {
+        pq::preprocess::single_use_tables(&query)?
+    } else {
+        HashSet::new()
+    };
+    let ctes_since = resolved_dialect.handler().ctes_since();
+    let prefer_subqueries =
+        options.prefer_subqueries || DialectVersion::predates(options.target_version, ctes_since);
+    if prefer_subqueries {
+        warn_repeated_tables(&query)?;
+    }
+    let (sql_ast, params) = gen_query::translate_query(
+        query,
+        Some(resolved_dialect),
+        options.target_version,
+        params,
+        inline_tables,
+        prefer_subqueries,
+        options.max_subquery_depth,
+        options.quoting,
+        options.alias_output_columns,
+    )?;
 
     let sql = sql_ast.to_string();
+    let (sql, values) = match params {
+        Some(params) => params.into_placeholders(&sql, resolved_dialect.handler().as_ref()),
+        None => (sql, Vec::new()),
+    };
 
     // formatting
     let sql = if options.format {
         let formatted = sqlformat::format(
             &sql,
             &sqlformat::QueryParams::default(),
-            sqlformat::FormatOptions::default(),
+            sqlformat::FormatOptions {
+                indent: sqlformat::Indent::Spaces(options.indent),
+                ..Default::default()
+            },
         );
 
-        formatted + "\n"
+        keep_full_join_together(formatted) + "\n"
     } else {
         sql
     };
+    let sql = place_comments(sql);
 
     debug::log_entry(|| debug::DebugEntryKind::ReprSql(sql.clone()));
 
@@ -58,7 +149,152 @@ pub fn compile(query: rq::RelationalQuery, options: &Options) -> Result<String>
         sql
     };
 
-    Ok(sql)
+    Ok((sql, values))
+}
+
+/// The formatter starts a new line at `JOIN`, but not at `FULL JOIN`, so this
+/// moves `FULL` onto the line of its `JOIN`.
+fn keep_full_join_together(sql: String) -> String {
+    let re = regex::Regex::new(r" FULL\n( *)JOIN ").unwrap();
+    re.replace_all(&sql, "\n${1}FULL JOIN ").into_owned()
+}
+
+/// Warns about tables that are referenced more than once, which are computed
+/// once for each reference when they are inlined as sub-queries.
+fn warn_repeated_tables(query: &rq::RelationalQuery) -> Result<()> {
+    let references = pq::preprocess::table_references(query)?;
+    for table in &query.tables {
+        let count = references.get(&table.id).copied().unwrap_or_default();
+        if count > 1 {
+            let name = table.name.as_ref().map_or("a relation", |n| n.as_str());
+            log::warn!(
+                "`{name}` is referenced {count} times, so its sub-query is computed {count} times"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Marks the comments of transforms in generated SQL, followed by the keyword
+/// of the clause they annotate and a line of the comment. The marker directly
+/// follows the keyword, so the comment can be moved above it.
+const COMMENT_MARKER: &str = "/* prql-comment ";
+
+/// Replaces the markers of comments with `--` comments on their own lines,
+/// above the keywords of their clauses.
+fn place_comments(mut sql: String) -> String {
+    if !sql.contains(COMMENT_MARKER) {
+        return sql;
+    }
+    let re = Regex::new(&format!(
+        r"{}([A-Z ]+?): (.*?) \*/\s*",
+        regex::escape(COMMENT_MARKER)
+    ))
+    .unwrap();
+
+    while let Some(caps) = re.captures(&sql) {
+        let marker = caps.get(0).unwrap().range();
+        let keyword = caps[1].to_string();
+        let comment = format!("-- {}", &caps[2]).trim_end().to_string();
+
+        // keywords of joins are followed by their relation on the same line
+        let marker = if keyword.ends_with("JOIN") {
+            let start = sql[..marker.start].trim_end().len();
+            sql.replace_range(start..marker.end, " ");
+            start..start + 1
+        } else {
+            sql.replace_range(marker.clone(), "");
+            marker
+        };
+
+        let Some(keyword_start) = sql[..marker.start].rfind(&keyword) else {
+            continue;
+        };
+        let line_start = sql[..keyword_start].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &sql[line_start..keyword_start];
+        if indent.trim().is_empty() {
+            let line = format!("{indent}{comment}\n");
+            sql.insert_str(line_start, &line);
+        } else {
+            // the keyword is within a line, so it is moved onto a new one
+            let content_end = sql[..keyword_start].trim_end().len();
+            sql.replace_range(content_end..keyword_start, &format!("\n{comment}\n"));
+        }
+    }
+    sql
+}
+
+/// Picks the dialect to compile to: the one of `options`, or else the one
+/// declared by `prql target`, or else the generic dialect.
+pub(super) fn resolve_dialect(
+    query: &rq::RelationalQuery,
+    dialect: Option<Dialect>,
+) -> Result<Dialect> {
+    if let Some(dialect) = dialect {
+        return Ok(dialect);
+    }
+    let target = query.def.other.get("target");
+    let crate::Target::Sql(maybe_dialect) = target
+        .map(|s| crate::Target::from_str(s))
+        .transpose()?
+        .unwrap_or_default();
+    Ok(maybe_dialect.unwrap_or_default())
+}
+
+/// Checks that the `prql target` of the query, if any, names the same dialect
+/// that the query is being compiled to.
+fn ensure_declared_target(query: &rq::RelationalQuery, dialect: Option<Dialect>) -> Result<()> {
+    let (Some(dialect), Some(declared)) = (dialect, query.def.other.get("target")) else {
+        return Ok(());
+    };
+    let crate::Target::Sql(declared) = crate::Target::from_str(declared)?;
+
+    match declared {
+        Some(declared) if declared != dialect => Err(Error::new_simple(format!(
+            "query declares `target:sql.{declared}`, but is being compiled to `sql.{dialect}`"
+        ))
+        .push_hint("remove the `prql target` from the query or compile to the declared target")),
+        _ => Ok(()),
+    }
+}
+
+/// The literals of a parameterized query, which are bound to placeholders
+/// rather than inlined into the SQL.
+#[derive(Debug)]
+struct QueryParams {
+    /// Whether the bounds of `take` are bound too, rather than inlined into
+    /// `LIMIT` and `OFFSET`.
+    bind_take: bool,
+
+    values: Vec<Literal>,
+}
+
+/// Marks the placeholders in generated SQL, followed by the index of the
+/// value. Translation doesn't follow the order of the SQL text, so the
+/// placeholders are numbered once the text is generated.
+const PARAM_MARKER: &str = "$__prql_param_";
+
+impl QueryParams {
+    /// Records a value and returns the marker of its placeholder.
+    fn bind(&mut self, value: Literal) -> sql_ast::Expr {
+        let marker = format!("{PARAM_MARKER}{}", self.values.len());
+        self.values.push(value);
+        sql_ast::Expr::Value(sql_ast::Value::Placeholder(marker))
+    }
+
+    /// Replaces the markers with the placeholders of the dialect and returns
+    /// the values bound to them, in the order they appear in the SQL.
+    fn into_placeholders(self, sql: &str, dialect: &dyn DialectHandler) -> (String, Vec<Literal>) {
+        let re = Regex::new(&format!(r"{}(\d+)", regex::escape(PARAM_MARKER))).unwrap();
+
+        let mut values = Vec::with_capacity(self.values.len());
+        let sql = re.replace_all(sql, |caps: &regex::Captures| {
+            let index: usize = caps[1].parse().unwrap();
+            values.push(self.values[index].clone());
+            dialect.placeholder(values.len())
+        });
+        (sql.into_owned(), values)
+    }
 }
 
 #[derive(Debug)]
@@ -75,6 +311,25 @@ struct Context {
     query_stack: Vec<QueryOpts>,
 
     pub ctes: Vec<Cte>,
+
+    /// Literals bound to placeholders, when compiling a parameterized query.
+    params: Option<QueryParams>,
+
+    /// When identifiers are quoted.
+    quoting: QuotingPolicy,
+
+    /// Tables that are inlined as sub-queries instead of being defined as CTEs.
+    pub inline_tables: HashSet<TId>,
+
+    /// When true, all tables are inlined as sub-queries, once for each of
+    /// their references.
+    pub prefer_subqueries: bool,
+
+    /// Version of the database, when compiling for a specific one.
+    pub version: Option<DialectVersion>,
+
+    /// Translated tables that expressions use as sub-queries.
+    pub sub_queries: HashMap<TId, sql_ast::Query>,
 }
 
 #[derive(Clone, Debug)]
@@ -96,6 +351,30 @@ struct QueryOpts {
 
     /// True when translating function that will have an OVER clause.
     pub window_function: bool,
+
+    /// True when translating an aggregation of PIVOT, which must be a bare
+    /// function call, so COALESCE is applied to the output columns instead.
+    pub pivot_aggregation: bool,
+
+    /// Condition of a FILTER clause, to be appended to the aggregation that is
+    /// translated next.
+    pub aggregate_filter: Option<String>,
+
+    /// Relations that have to be added to FROM of the current query, because
+    /// the projection references them (i.e. `UNNEST` in BigQuery).
+    pub lateral_relations: Vec<sqlparser::ast::TableFactor>,
+
+    /// Windows of more than one column of the projection, which are defined
+    /// once in the WINDOW clause of the current query.
+    pub shared_windows: Vec<rq::Window>,
+
+    /// Definitions of the WINDOW clause of the current query.
+    pub named_windows: Vec<sqlparser::ast::NamedWindowDefinition>,
+
+    /// When true, the computed columns of the projection of the next SELECT
+    /// are given names, if they don't have one. Set only for the outermost
+    /// SELECT, for [crate::Options::alias_output_columns].
+    pub name_columns: bool,
 }
 
 impl Default for QueryOpts {
@@ -106,6 +385,12 @@ impl Default for QueryOpts {
             allow_ctes: true,
             allow_stars: true,
             window_function: false,
+            pivot_aggregation: false,
+            aggregate_filter: None,
+            lateral_relations: Vec::new(),
+            shared_windows: Vec::new(),
+            named_windows: Vec::new(),
+            name_columns: false,
         }
     }
 }
@@ -119,6 +404,12 @@ impl Context {
             query: QueryOpts::default(),
             query_stack: Vec::new(),
             ctes: Vec::new(),
+            params: None,
+            quoting: QuotingPolicy::default(),
+            inline_tables: HashSet::new(),
+            prefer_subqueries: false,
+            version: None,
+            sub_queries: HashMap::new(),
         }
     }
 
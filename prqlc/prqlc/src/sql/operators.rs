@@ -4,8 +4,9 @@ use std::path::PathBuf;
 use std::sync::OnceLock;
 
 use itertools::Itertools;
+use prqlc_parser::generic::SwitchCase;
 
-use super::gen_expr::{translate_operand, ExprOrSource, SourceExpr};
+use super::gen_expr::{translate_expr, translate_operand, ExprOrSource, SourceExpr};
 use super::{Context, Dialect};
 use crate::ir::{decl, pl, rq};
 use crate::utils::Pluck;
@@ -48,6 +49,7 @@ pub(super) fn translate_operator(
     let (func_def, binding_strength, window_frame, coalesce) =
         find_operator_impl(&name, ctx.dialect_enum).unwrap();
     let parent_binding_strength = binding_strength.unwrap_or(100);
+    let filter = ctx.query.aggregate_filter.take();
 
     let params = func_def
         .named_params
@@ -106,7 +108,11 @@ pub(super) fn translate_operator(
 
     let mut binding_strength = parent_binding_strength;
 
-    if !ctx.query.window_function {
+    if let Some(filter) = filter {
+        text = format!("{text} FILTER (WHERE {filter})");
+    }
+
+    if !ctx.query.window_function && !ctx.query.pivot_aggregation {
         if let Some(default) = coalesce {
             text = format!("COALESCE({text}, {default})");
             binding_strength = 100;
@@ -120,6 +126,107 @@ pub(super) fn translate_operator(
     })
 }
 
+/// Translates an aggregation of the rows for which a condition holds. Dialects
+/// that support it use a FILTER clause. Others aggregate the values of a CASE,
+/// which are null for the other rows, and so are ignored by the aggregation.
+pub(super) fn translate_filtered_aggregation(
+    expr: rq::Expr,
+    ctx: &mut Context,
+) -> Result<SourceExpr> {
+    let span = expr.span;
+    let (_, args) = expr.kind.into_operator().unwrap();
+    let [condition, aggregation]: [rq::Expr; 2] = args.try_into().unwrap();
+
+    let aggregation_span = aggregation.span.or(span);
+    let aggregation = match aggregation.kind {
+        rq::ExprKind::Operator { name, args } => find_operator_impl(&name, ctx.dialect_enum)
+            .filter(|(_, _, window_frame, _)| *window_frame)
+            .map(|(func_def, ..)| is_single_call(func_def))
+            .map(|is_single_call| (name, args, is_single_call)),
+        _ => None,
+    };
+    let Some((name, args, is_single_call)) = aggregation else {
+        return Err(Error::new_simple(
+            "`filtered` applies only to aggregate functions, such as `sum` or `count`",
+        )
+        .with_span(aggregation_span));
+    };
+
+    if ctx.dialect.supports_aggregate_filter() && is_single_call {
+        ctx.query.aggregate_filter = Some(translate_expr(condition, ctx)?.into_source());
+        return translate_operator(name, args, ctx).with_span(span);
+    }
+
+    let when = |value: rq::Expr| rq::Expr {
+        kind: rq::ExprKind::Case(vec![SwitchCase {
+            condition: condition.clone(),
+            value,
+        }]),
+        span: None,
+    };
+    if name == "std.count" {
+        // `COUNT(*)` has no values to replace, so it counts the values of the
+        // CASE instead
+        let one = rq::Expr {
+            kind: rq::ExprKind::Literal(pl::Literal::Integer(1)),
+            span: None,
+        };
+        let case = translate_expr(when(one), ctx)?.into_source();
+        return Ok(SourceExpr {
+            text: format!("COUNT({case})"),
+            binding_strength: 100,
+            window_frame: true,
+        });
+    }
+    let args = args.into_iter().map(when).collect();
+    translate_operator(name, args, ctx).with_span(span)
+}
+
+/// Whether the implementation of an operator is a single call of a function,
+/// such as `SUM({column})`, so it can be followed by a FILTER clause.
+fn is_single_call(func_def: &pl::Func) -> bool {
+    let pl::ExprKind::SString(items) = &func_def.body.kind else {
+        return false;
+    };
+    let text: String = (items.iter())
+        .map(|item| match item {
+            pl::InterpolateItem::String(s) => s.as_str(),
+            pl::InterpolateItem::Expr { .. } => "x",
+        })
+        .collect();
+
+    let Some(open) = text.find('(') else {
+        return false;
+    };
+    if !text[..open]
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return false;
+    }
+
+    // the parenthesis that follows the name of the function closes at the end
+    let mut depth = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return index == text.len() - 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The default that the result of an operator is coalesced with, if any.
+pub(super) fn coalesce_default(operator_name: &str, dialect: Dialect) -> Option<String> {
+    find_operator_impl(operator_name, dialect).and_then(|(_, _, _, coalesce)| coalesce)
+}
+
 fn find_operator_impl(
     operator_name: &str,
     dialect: Dialect,
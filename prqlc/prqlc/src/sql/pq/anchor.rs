@@ -35,6 +35,7 @@ pub(super) fn extract_atomic(
     // sometimes, additional columns will be added into select, because they are needed for
     // other clauses. To filter them out, we use an additional limiting SELECT.
     let output: Vec<_> = CidRedirector::redirect_cids(output, &atomic, ctx);
+    let atomic = inline_sort_columns(atomic, &output, ctx);
     let select_cols = atomic
         .iter()
         .find_map(|x| x.as_super().and_then(|y| y.as_select()))
@@ -58,6 +59,46 @@ pub(super) fn extract_atomic(
     atomic
 }
 
+/// Removes columns from SELECT that are computed only to be sorted by, so they
+/// are computed within ORDER BY instead. This avoids a limiting SELECT that
+/// would remove them from the output.
+///
+/// ORDER BY of a SELECT with DISTINCT, GROUP BY or a set operation can only
+/// reference the selected columns, so these pipelines are left as they are.
+fn inline_sort_columns(
+    mut atomic: Vec<SqlTransform>,
+    output: &[CId],
+    ctx: &AnchorContext,
+) -> Vec<SqlTransform> {
+    let can_inline = atomic.iter().all(|t| {
+        !matches!(
+            t,
+            SqlTransform::Super(Transform::Aggregate { .. })
+                | SqlTransform::Distinct
+                | SqlTransform::DistinctOn(_)
+                | SqlTransform::Except { .. }
+                | SqlTransform::Intersect { .. }
+                | SqlTransform::Union { .. }
+        )
+    });
+    if !can_inline {
+        return atomic;
+    }
+
+    let select = (atomic.iter_mut())
+        .find_map(|x| x.as_super_mut().and_then(|y| y.as_select_mut()))
+        .unwrap();
+    select.retain(|cid| {
+        let ColumnDecl::Compute(compute) = &ctx.column_decls[cid] else {
+            return true;
+        };
+        let is_plain = infer_complexity(compute) == Complexity::Plain
+            && !references_window(&compute.expr, ctx, &|_| true);
+        output.contains(cid) || !is_plain
+    });
+    atomic
+}
+
 /// Splits pipeline into two parts, such that the second part contains
 /// maximum number of transforms while "fitting" into a SELECT query.
 ///
@@ -139,6 +180,16 @@ pub(super) fn split_off_back(
         }
     }
 
+    // QUALIFY requires a window function, so filters whose window functions were
+    // computed by a preceding pipeline fall back to WHERE or HAVING
+    for transform in &mut curr_pipeline_rev {
+        if let SqlTransform::Qualify(filter) = transform {
+            if !references_window(filter, ctx, &|cid| inputs_avail.contains(cid)) {
+                *transform = SqlTransform::Super(Transform::Filter(filter.clone()));
+            }
+        }
+    }
+
     let selected = inputs_required
         .iter()
         .filter(|r| r.selected)
@@ -270,6 +321,7 @@ pub(super) fn anchor_split(
                 preceding, columns,
             )),
             redirect_to: None,
+            is_extern: false,
         },
     );
 
@@ -320,6 +372,11 @@ fn is_split_required(transform: &SqlTransform, following: &mut HashSet<String>)
         }
     }
 
+    // comments go along with the transform after them
+    if let Super(Comment(_)) = transform {
+        return false;
+    }
+
     fn contains_any<const C: usize>(set: &HashSet<String>, elements: [&'static str; C]) -> bool {
         for t in elements {
             if set.contains(t) {
@@ -336,13 +393,26 @@ fn is_split_required(transform: &SqlTransform, following: &mut HashSet<String>)
             contains_any(following, ["From", "Join", "Aggregate", "Compute"])
         }
         Super(Filter(_)) => contains_any(following, ["From", "Join"]),
+        SqlTransform::Qualify(_) => contains_any(
+            following,
+            ["From", "Join", "Compute", "Filter", "Aggregate"],
+        ),
         Super(Compute(_)) => contains_any(following, ["From", "Join", /* "Aggregate" */ "Filter"]),
 
         // Sort will be pushed down the CTEs, so there is no point in splitting for it.
         // Super(Sort(_)) => contains_any(following, ["From", "Join", "Compute", "Aggregate"]),
         Super(Take(_)) => contains_any(
             following,
-            ["From", "Join", "Compute", "Filter", "Aggregate", "Sort"],
+            [
+                "From",
+                "Join",
+                "Compute",
+                "Filter",
+                "Qualify",
+                "Aggregate",
+                "Sort",
+                "LimitBy",
+            ],
         ),
         SqlTransform::DistinctOn(_) => contains_any(
             following,
@@ -351,10 +421,27 @@ fn is_split_required(transform: &SqlTransform, following: &mut HashSet<String>)
                 "Join",
                 "Compute",
                 "Filter",
+                "Qualify",
+                "Aggregate",
+                "Sort",
+                "Take",
+                "DistinctOn",
+                "LimitBy",
+            ],
+        ),
+        SqlTransform::LimitBy { .. } => contains_any(
+            following,
+            [
+                "From",
+                "Join",
+                "Compute",
+                "Filter",
+                "Qualify",
                 "Aggregate",
                 "Sort",
                 "Take",
                 "DistinctOn",
+                "LimitBy",
             ],
         ),
         SqlTransform::Distinct => contains_any(
@@ -364,9 +451,11 @@ fn is_split_required(transform: &SqlTransform, following: &mut HashSet<String>)
                 "Join",
                 "Compute",
                 "Filter",
+                "Qualify",
                 "Aggregate",
                 "Sort",
                 "Take",
+                "LimitBy",
             ],
         ),
         SqlTransform::Union { .. }
@@ -378,10 +467,12 @@ fn is_split_required(transform: &SqlTransform, following: &mut HashSet<String>)
                 "Join",
                 "Compute",
                 "Filter",
+                "Qualify",
                 "Aggregate",
                 "Sort",
                 "Take",
                 "Distinct",
+                "LimitBy",
             ],
         ),
         Super(Loop(_)) => !following.is_empty(),
@@ -484,9 +575,9 @@ pub(super) fn get_requirements(
     // general case: extract cids
     let cids = match transform {
         Super(Compute(compute)) => CidCollector::collect(compute.expr.clone()),
-        Super(Filter(expr)) | SqlTransform::Join { filter: expr, .. } => {
-            CidCollector::collect(expr.clone())
-        }
+        Super(Filter(expr))
+        | SqlTransform::Qualify(expr)
+        | SqlTransform::Join { filter: expr, .. } => CidCollector::collect(expr.clone()),
         Super(Sort(sorts)) => sorts.iter().map(|s| s.column).collect(),
         Super(Take(rq::Take { range, .. })) => {
             let mut cids = Vec::new();
@@ -498,6 +589,7 @@ pub(super) fn get_requirements(
             }
             cids
         }
+        SqlTransform::LimitBy { partition, .. } => partition.clone(),
 
         _ => return Vec::new(),
     };
@@ -512,11 +604,14 @@ pub(super) fn get_requirements(
             },
             false,
         ),
+        // QUALIFY is applied after window functions are computed
+        SqlTransform::Qualify(_) => (Complexity::Windowed, false),
         // we only use SELECTed columns in ORDER BY, so the columns can have high complexity
         Super(Sort(_)) => (Complexity::Aggregation, true),
 
         // LIMIT and OFFSET can use constant expressions which don't need to be SELECTed
         Super(Take(_)) => (Complexity::Plain, false),
+        SqlTransform::LimitBy { .. } => (Complexity::Plain, false),
         SqlTransform::Join { .. } => (Complexity::Plain, false),
         _ => unreachable!(),
     };
@@ -558,6 +653,8 @@ pub fn infer_complexity(compute: &Compute) -> Complexity {
 pub fn infer_complexity_expr(expr: &Expr) -> Complexity {
     match &expr.kind {
         rq::ExprKind::Case(_) => Complexity::NonGroup,
+        // set-returning, so it must be computed before filters and aggregations
+        rq::ExprKind::Operator { name, .. } if name == "std.array.unnest" => Complexity::Windowed,
         rq::ExprKind::Operator { args, .. } => args
             .iter()
             .map(infer_complexity_expr)
@@ -566,11 +663,32 @@ pub fn infer_complexity_expr(expr: &Expr) -> Complexity {
         rq::ExprKind::ColumnRef(_)
         | rq::ExprKind::Literal(_)
         | rq::ExprKind::SString(_)
-        | rq::ExprKind::Param(_) => Complexity::Plain,
+        | rq::ExprKind::Param(_)
+        | rq::ExprKind::SubQuery(_) => Complexity::Plain,
         rq::ExprKind::Array(_) => Complexity::highest(),
     }
 }
 
+/// Whether an expression references a window function, directly or through
+/// other computed columns. Only columns accepted by `within` are considered.
+pub(super) fn references_window(
+    expr: &Expr,
+    ctx: &AnchorContext,
+    within: &dyn Fn(&CId) -> bool,
+) -> bool {
+    CidCollector::collect(expr.clone()).iter().any(|cid| {
+        if !within(cid) {
+            return false;
+        }
+        match ctx.column_decls.get(cid) {
+            Some(ColumnDecl::Compute(compute)) => {
+                compute.window.is_some() || references_window(&compute.expr, ctx, within)
+            }
+            _ => false,
+        }
+    })
+}
+
 #[derive(Default)]
 pub struct CidCollector {
     // we could use HashSet instead of Vec, but this caused nondeterministic
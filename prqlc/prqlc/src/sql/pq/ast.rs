@@ -5,7 +5,7 @@
 
 use enum_as_inner::EnumAsInner;
 use itertools::Itertools;
-use prqlc_parser::generic::InterpolateItem;
+use prqlc_parser::generic::{InterpolateItem, Range};
 use serde::Serialize;
 
 use super::context::RIId;
@@ -57,6 +57,9 @@ pub enum CteKind {
         initial: SqlRelation,
         step: SqlRelation,
     },
+    /// A relation that is used within an expression, as in `x IN (SELECT ...)`.
+    /// It is inlined at the place of use, instead of being defined in WITH.
+    SubQuery(SqlRelation),
 }
 
 /// Similar to [rq::Transform], but closer to a SQL clause.
@@ -86,6 +89,8 @@ pub enum SqlTransform<Rel = RIId, Super = rq::Transform> {
     From(Rel),
     Select(Vec<rq::CId>),
     Filter(rq::Expr),
+    /// A filter on window functions, applied after they are computed.
+    Qualify(rq::Expr),
     Aggregate {
         partition: Vec<rq::CId>,
         compute: Vec<rq::CId>,
@@ -100,6 +105,11 @@ pub enum SqlTransform<Rel = RIId, Super = rq::Transform> {
 
     Distinct,
     DistinctOn(Vec<rq::CId>),
+    /// Takes a range of rows of each partition, as `LIMIT n BY` of ClickHouse.
+    LimitBy {
+        range: Range<rq::Expr>,
+        partition: Vec<rq::CId>,
+    },
     Except {
         bottom: Rel,
         distinct: bool,
@@ -112,6 +122,24 @@ pub enum SqlTransform<Rel = RIId, Super = rq::Transform> {
         bottom: Rel,
         distinct: bool,
     },
+
+    /// A comment of the source, to be emitted above a clause of the SELECT.
+    Comment(CommentClause, String),
+}
+
+/// Clause of a SELECT that a comment is emitted above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CommentClause {
+    Select,
+    From,
+    /// A join, by its position among the joins of the SELECT.
+    Join(usize),
+    Where,
+    GroupBy,
+    Having,
+    Qualify,
+    OrderBy,
+    Limit,
 }
 
 impl<Rel> SqlTransform<Rel> {
@@ -176,6 +204,10 @@ pub fn fold_sql_transform<
 
         SqlTransform::Distinct => SqlTransform::Distinct,
         SqlTransform::DistinctOn(ids) => SqlTransform::DistinctOn(fold.fold_cids(ids)?),
+        SqlTransform::LimitBy { range, partition } => SqlTransform::LimitBy {
+            range,
+            partition: fold.fold_cids(partition)?,
+        },
         SqlTransform::Union { bottom, distinct } => SqlTransform::Union {
             bottom: fold.fold_rel(bottom)?,
             distinct,
@@ -190,6 +222,7 @@ pub fn fold_sql_transform<
         },
         SqlTransform::Select(v) => SqlTransform::Select(fold.fold_cids(v)?),
         SqlTransform::Filter(v) => SqlTransform::Filter(fold.fold_expr(v)?),
+        SqlTransform::Qualify(v) => SqlTransform::Qualify(fold.fold_expr(v)?),
         SqlTransform::Aggregate { partition, compute } => SqlTransform::Aggregate {
             partition: fold.fold_cids(partition)?,
             compute: fold.fold_cids(compute)?,
@@ -200,6 +233,7 @@ pub fn fold_sql_transform<
             sort: fold_column_sorts(fold, take.sort)?,
             range: take.range,
         }),
+        SqlTransform::Comment(clause, comment) => SqlTransform::Comment(clause, comment),
     })
 }
 
@@ -233,6 +267,7 @@ pub trait PqFold: PqMapper<RelationExpr, RelationExpr, (), ()> {
                     initial: self.fold_sql_relation(initial)?,
                     step: self.fold_sql_relation(step)?,
                 },
+                CteKind::SubQuery(rel) => CteKind::SubQuery(self.fold_sql_relation(rel)?),
             },
         })
     }
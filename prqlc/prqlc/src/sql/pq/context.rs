@@ -51,6 +51,10 @@ pub struct SqlTableDecl {
     /// None means that it has already been defined, or was not needed to be defined in the
     /// first place.
     pub relation: RelationStatus,
+
+    /// Whether this decl refers to a table in the database (and not to a relation defined
+    /// within the query). Columns of such tables are not known unless declared.
+    pub is_extern: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -196,6 +200,18 @@ impl AnchorContext {
         Some(entry.or_insert_with(|| self.col_name.gen()))
     }
 
+    /// Whether a column of a relation is projected under a name other than
+    /// its own, because its name clashes with another column of the same
+    /// projection (i.e. `id` of both sides of a join).
+    pub(crate) fn is_renamed(&self, cid: CId) -> bool {
+        let ColumnDecl::RelationColumn(_, _, RelationColumn::Single(Some(name))) =
+            &self.column_decls[&cid]
+        else {
+            return false;
+        };
+        self.column_names.get(&cid).map_or(false, |n| n != name)
+    }
+
     pub(super) fn load_names(
         &mut self,
         pipeline: &[SqlTransform],
@@ -305,10 +321,11 @@ impl QueryLoader {
             name = Some(table.clone());
         }
 
+        let is_extern = matches!(decl.relation.kind, RelationKind::ExternRef(_));
         let sql_decl = SqlTableDecl {
             id: decl.id,
             name,
-            relation: if matches!(decl.relation.kind, RelationKind::ExternRef(_)) {
+            relation: if is_extern {
                 // this relation can be materialized by just using table name as a reference
                 // ... i.e. it's already defined.
                 RelationStatus::Defined
@@ -317,6 +334,7 @@ impl QueryLoader {
                 RelationStatus::NotYetDefined(decl.relation.into())
             },
             redirect_to: None,
+            is_extern,
         };
 
         self.context.table_decls.insert(decl.id, sql_decl);
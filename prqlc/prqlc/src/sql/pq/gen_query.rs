@@ -1,43 +1,48 @@
 //! This module is responsible for translating RQ to PQ.
 
-use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
 
-use super::super::{Context, Dialect};
+use super::super::{resolve_dialect, Context, Dialect, DialectVersion};
 use super::anchor::{self, anchor_split};
 use super::ast::{self as pq, fold_sql_transform, PqMapper};
-use super::context::{AnchorContext, RIId, RelationAdapter, RelationStatus};
+use super::context::{AnchorContext, ColumnDecl, RIId, RelationAdapter, RelationStatus};
 use super::{postprocess, preprocess};
 use crate::debug;
-use crate::ir::rq::{self, RqFold};
+use crate::ir::rq::{self, RqFold, TId};
 use crate::utils::BreakUp;
-use crate::{Result, Target};
+use crate::{Error, Result, Span, WithErrorInfo};
 
 pub(in super::super) fn compile_query(
     query: rq::RelationalQuery,
     dialect: Option<Dialect>,
+    version: Option<DialectVersion>,
+    inline_tables: HashSet<TId>,
+    prefer_subqueries: bool,
+    max_subquery_depth: Option<usize>,
 ) -> Result<(pq::SqlQuery, Context)> {
     debug::log_stage(debug::Stage::Sql(debug::StageSql::Anchor));
 
-    let dialect = if let Some(dialect) = dialect {
-        dialect
-    } else {
-        let target = query.def.other.get("target");
-        let Target::Sql(maybe_dialect) = target
-            .map(|s| Target::from_str(s))
-            .transpose()?
-            .unwrap_or_default();
-        maybe_dialect.unwrap_or_default()
-    };
+    let dialect = resolve_dialect(&query, dialect)?;
+
+    let sub_queries = SubQueryCollector::collect(&query)?;
 
     let (anchor, main_relation) = AnchorContext::of(query);
 
     let mut ctx = Context::new(dialect, anchor);
+    ctx.inline_tables = inline_tables;
+    ctx.prefer_subqueries = prefer_subqueries;
+    ctx.version = version;
 
     // compile main relation that will recursively compile CTEs
     let main_relation = compile_relation(main_relation.into(), &mut ctx)?;
 
+    // compile tables that expressions use as sub-queries
+    for tid in sub_queries {
+        compile_sub_query(tid, &mut ctx)?;
+    }
+
     // attach CTEs
     let ctes = ctx.ctes.drain(..).collect_vec();
 
@@ -47,6 +52,10 @@ pub(in super::super) fn compile_query(
     };
     debug::log_entry(|| debug::DebugEntryKind::ReprPq(query.clone()));
 
+    if let Some(max_depth) = max_subquery_depth {
+        validate_depth(&query, &ctx, max_depth)?;
+    }
+
     debug::log_stage(debug::Stage::Sql(debug::StageSql::Postprocess));
     let query = postprocess::postprocess(query, &mut ctx);
     debug::log_entry(|| debug::DebugEntryKind::ReprPq(query.clone()));
@@ -140,6 +149,8 @@ impl<'a> PqMapper<RIId, pq::RelationExpr, rq::Transform, ()> for TransformCompil
         &mut self,
         transforms: Vec<pq::SqlTransform<RIId, rq::Transform>>,
     ) -> Result<Vec<pq::SqlTransform<pq::RelationExpr, ()>>> {
+        let mut comment_clauses = comment_clauses(&transforms).into_iter();
+
         transforms
             .into_iter()
             .map(|transform| {
@@ -160,6 +171,9 @@ impl<'a> PqMapper<RIId, pq::RelationExpr, rq::Transform, ()> for TransformCompil
                             }
                             rq::Transform::Sort(v) => pq::SqlTransform::Sort(v),
                             rq::Transform::Take(v) => pq::SqlTransform::Take(v),
+                            rq::Transform::Comment(comment) => {
+                                pq::SqlTransform::Comment(comment_clauses.next().unwrap(), comment)
+                            }
                             rq::Transform::Compute(_)
                             | rq::Transform::Append(_)
                             | rq::Transform::Loop(_) => {
@@ -177,6 +191,55 @@ impl<'a> PqMapper<RIId, pq::RelationExpr, rq::Transform, ()> for TransformCompil
     }
 }
 
+/// Clauses that the comments of a pipeline are emitted above, in order of the
+/// comments. A comment is emitted above the clause of the transform after it,
+/// or above SELECT when the transform does not have a clause of its own.
+fn comment_clauses(pipeline: &[pq::SqlTransform]) -> Vec<pq::CommentClause> {
+    use pq::{CommentClause, SqlTransform::*};
+
+    let mut aggregated = false;
+    let mut joins = 0;
+    let clauses = (pipeline.iter())
+        .map(|transform| match transform {
+            From(_) => CommentClause::From,
+            Join { .. } => {
+                joins += 1;
+                CommentClause::Join(joins - 1)
+            }
+            Super(rq::Transform::Filter(_)) if aggregated => CommentClause::Having,
+            Super(rq::Transform::Filter(_)) => CommentClause::Where,
+            Qualify(_) => CommentClause::Qualify,
+            Super(rq::Transform::Aggregate { partition, .. }) => {
+                aggregated = true;
+                if partition.is_empty() {
+                    CommentClause::Select
+                } else {
+                    CommentClause::GroupBy
+                }
+            }
+            Union { .. } => {
+                aggregated = true;
+                CommentClause::Select
+            }
+            Super(rq::Transform::Sort(_)) => CommentClause::OrderBy,
+            Super(rq::Transform::Take(_)) => CommentClause::Limit,
+            _ => CommentClause::Select,
+        })
+        .collect_vec();
+
+    let mut res = Vec::new();
+    let mut next = CommentClause::Select;
+    for (transform, clause) in pipeline.iter().zip(clauses).rev() {
+        if matches!(transform, Super(rq::Transform::Comment(_))) {
+            res.push(next);
+        } else {
+            next = clause;
+        }
+    }
+    res.reverse();
+    res
+}
+
 pub(super) fn compile_relation_instance(riid: RIId, ctx: &mut Context) -> Result<pq::RelationExpr> {
     let table_ref = &ctx.anchor.relation_instances.get(&riid).unwrap().table_ref;
     let source = table_ref.source;
@@ -184,8 +247,9 @@ pub(super) fn compile_relation_instance(riid: RIId, ctx: &mut Context) -> Result
 
     // ensure that the table is declared
     if let RelationStatus::NotYetDefined(sql_relation) = decl.relation.take_to_define() {
-        // if we cannot use CTEs (probably because we are within RECURSIVE)
-        if !ctx.query.allow_ctes {
+        // if we cannot use CTEs (probably because we are within RECURSIVE),
+        // or should not use them
+        if !ctx.query.allow_ctes || ctx.prefer_subqueries {
             // restore relation for other references
             decl.relation = RelationStatus::NotYetDefined(sql_relation.clone());
 
@@ -197,6 +261,15 @@ pub(super) fn compile_relation_instance(riid: RIId, ctx: &mut Context) -> Result
             });
         }
 
+        // tables that are referenced only here can be inlined as a sub-query
+        if ctx.inline_tables.contains(&source) {
+            let relation = compile_relation(sql_relation, ctx)?;
+            return Ok(pq::RelationExpr {
+                kind: pq::RelationExprKind::SubQuery(relation),
+                riid,
+            });
+        }
+
         let relation = compile_relation(sql_relation, ctx)?;
         ctx.ctes.push(pq::Cte {
             tid: source,
@@ -210,10 +283,157 @@ pub(super) fn compile_relation_instance(riid: RIId, ctx: &mut Context) -> Result
     })
 }
 
+fn compile_sub_query(tid: TId, ctx: &mut Context) -> Result<()> {
+    let decl = ctx.anchor.table_decls.get_mut(&tid).unwrap();
+
+    if let RelationStatus::NotYetDefined(sql_relation) = decl.relation.take_to_define() {
+        let relation = compile_relation(sql_relation, ctx)?;
+        ctx.ctes.push(pq::Cte {
+            tid,
+            kind: pq::CteKind::SubQuery(relation),
+        });
+    }
+    Ok(())
+}
+
+/// Ensures that no query is nested deeper than `max_depth` levels.
+///
+/// Each relation of PQ is a SELECT, which reads from relations that were split
+/// off its pipeline, either as CTEs or as sub-queries. A relation is nested one
+/// level deeper than the deepest relation it reads from.
+fn validate_depth(query: &pq::SqlQuery, ctx: &Context, max_depth: usize) -> Result<()> {
+    // CTEs are defined after the CTEs they read from
+    let mut cte_depths = HashMap::new();
+    for cte in &query.ctes {
+        let depth = match &cte.kind {
+            pq::CteKind::Normal(rel) | pq::CteKind::SubQuery(rel) => {
+                relation_depth(rel, &cte_depths, ctx, max_depth)?
+            }
+            pq::CteKind::Loop { initial, step } => {
+                let initial = relation_depth(initial, &cte_depths, ctx, max_depth)?;
+                let step = relation_depth(step, &cte_depths, ctx, max_depth)?;
+                initial.max(step)
+            }
+        };
+        cte_depths.insert(cte.tid, depth);
+    }
+
+    relation_depth(&query.main_relation, &cte_depths, ctx, max_depth)?;
+    Ok(())
+}
+
+fn relation_depth(
+    relation: &pq::SqlRelation,
+    cte_depths: &HashMap<TId, usize>,
+    ctx: &Context,
+    max_depth: usize,
+) -> Result<usize> {
+    let pq::SqlRelation::AtomicPipeline(pipeline) = relation else {
+        return Ok(1);
+    };
+
+    let mut inner_depth = 0;
+    for transform in pipeline {
+        let rel = match transform {
+            pq::SqlTransform::From(rel)
+            | pq::SqlTransform::Join { with: rel, .. }
+            | pq::SqlTransform::Union { bottom: rel, .. }
+            | pq::SqlTransform::Except { bottom: rel, .. }
+            | pq::SqlTransform::Intersect { bottom: rel, .. } => rel,
+            _ => continue,
+        };
+        let depth = match &rel.kind {
+            // tables of the database are not nested
+            pq::RelationExprKind::Ref(tid) => cte_depths.get(tid).cloned().unwrap_or_default(),
+            pq::RelationExprKind::SubQuery(rel) => relation_depth(rel, cte_depths, ctx, max_depth)?,
+        };
+        inner_depth = inner_depth.max(depth);
+    }
+
+    let depth = inner_depth + 1;
+    if depth > max_depth {
+        return Err(Error::new_simple(format!(
+            "query is nested {depth} levels deep, which exceeds `max_subquery_depth` of {max_depth}"
+        ))
+        .push_hint(
+            "each transform that cannot be applied within the preceding SELECT starts a new level",
+        )
+        .with_span(pipeline_span(pipeline, ctx)));
+    }
+    Ok(depth)
+}
+
+/// Span of the first expression of a pipeline that has one.
+fn pipeline_span(
+    pipeline: &[pq::SqlTransform<pq::RelationExpr, ()>],
+    ctx: &Context,
+) -> Option<Span> {
+    let span_of_cid = |cid: &rq::CId| match ctx.anchor.column_decls.get(cid) {
+        Some(ColumnDecl::Compute(compute)) => compute.expr.span,
+        _ => None,
+    };
+
+    pipeline.iter().find_map(|transform| match transform {
+        pq::SqlTransform::Filter(expr)
+        | pq::SqlTransform::Qualify(expr)
+        | pq::SqlTransform::Join { filter: expr, .. } => expr.span,
+        pq::SqlTransform::Select(cids) | pq::SqlTransform::DistinctOn(cids) => {
+            cids.iter().find_map(span_of_cid)
+        }
+        pq::SqlTransform::Aggregate { partition, compute } => {
+            partition.iter().chain(compute).find_map(span_of_cid)
+        }
+        pq::SqlTransform::Sort(sorts) => sorts.iter().find_map(|s| span_of_cid(&s.column)),
+        _ => None,
+    })
+}
+
+/// Collects tables that are used as sub-queries, in the order of their ids.
+/// A sub-query is always declared after the sub-queries it contains.
+#[derive(Default)]
+struct SubQueryCollector {
+    tables: Vec<TId>,
+}
+
+impl SubQueryCollector {
+    fn collect(query: &rq::RelationalQuery) -> Result<Vec<TId>> {
+        let mut collector = SubQueryCollector::default();
+        collector.fold_query(query.clone())?;
+
+        collector.tables.sort_by_key(|tid| tid.get());
+        Ok(collector.tables)
+    }
+}
+
+impl RqFold for SubQueryCollector {
+    fn fold_expr_kind(&mut self, kind: rq::ExprKind) -> Result<rq::ExprKind> {
+        if let rq::ExprKind::SubQuery(tid) = kind {
+            self.tables.push(tid);
+        }
+        rq::fold_expr_kind(self, kind)
+    }
+}
+
 fn compile_loop(
     pipeline: Vec<pq::SqlTransform>,
     ctx: &mut Context,
 ) -> Result<Vec<pq::SqlTransform>> {
+    if !ctx.dialect.supports_recursive_cte() {
+        return Err(Error::new_simple(format!(
+            "The dialect {:?} does not support recursive CTEs, which are required by `loop`",
+            ctx.dialect
+        )));
+    }
+    if let (Some(version), Some(since)) = (ctx.version, ctx.dialect.ctes_since()) {
+        if version < since {
+            return Err(Error::new_simple(format!(
+                "{} {version} does not support CTEs, which are required by `loop`",
+                ctx.dialect_enum
+            ))
+            .push_hint(format!("CTEs were added in {since}")));
+        }
+    }
+
     // split the pipeline
     let (mut initial, mut following) =
         pipeline.break_up(|t| matches!(t, pq::SqlTransform::Super(rq::Transform::Loop(_))));
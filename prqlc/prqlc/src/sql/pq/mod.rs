@@ -27,7 +27,14 @@ mod test {
     fn parse_and_resolve(source: &str) -> Result<SqlQuery, Errors> {
         let query = crate::semantic::test::parse_resolve_and_lower(source)?;
 
-        let (sql, _) = compile_query(query, Some(Dialect::Generic))?;
+        let (sql, _) = compile_query(
+            query,
+            Some(Dialect::Generic),
+            None,
+            Default::default(),
+            false,
+            None,
+        )?;
         Ok(sql)
     }
 
@@ -82,4 +89,40 @@ mod test {
 
         assert!(count_atomics(prql).unwrap() == 1);
     }
+
+    #[test]
+    fn test_prune_tables() {
+        use crate::ir::rq::{RelationKind, TableRef, Transform};
+
+        let query = crate::semantic::test::parse_resolve_and_lower(
+            r#"
+        let a = (from employees | take 10)
+        let b = (from a | select {id})
+        from b
+        "#,
+        )
+        .unwrap();
+        let ids = |query: &crate::ir::rq::RelationalQuery| {
+            query.tables.iter().map(|t| t.id).collect::<Vec<_>>()
+        };
+
+        // all tables are used
+        let pruned = preprocess::prune_tables(query.clone()).unwrap();
+        assert_eq!(ids(&pruned), ids(&query));
+
+        // a main relation that reads only from `employees` leaves `b` unused,
+        // which in turn leaves `a` unused
+        let employees = (query.tables.iter())
+            .find(|t| t.relation.kind.is_extern_ref())
+            .unwrap()
+            .id;
+        let mut query = query.clone();
+        query.relation.kind = RelationKind::Pipeline(vec![Transform::From(TableRef {
+            source: employees,
+            columns: vec![],
+            name: None,
+        })]);
+        let pruned = preprocess::prune_tables(query).unwrap();
+        assert_eq!(ids(&pruned), vec![employees]);
+    }
 }
@@ -158,7 +158,9 @@ impl<'a> PqMapper<RelationExpr, RelationExpr, (), ()> for SortingInference<'a> {
                 }
 
                 // emit Sort before Take
-                SqlTransform::Take(_) | SqlTransform::DistinctOn(_) => {
+                SqlTransform::Take(_)
+                | SqlTransform::DistinctOn(_)
+                | SqlTransform::LimitBy { .. } => {
                     result.push(SqlTransform::Sort(sorting.clone()));
                 }
                 _ => {}
@@ -177,8 +179,24 @@ impl<'a> PqMapper<RelationExpr, RelationExpr, (), ()> for SortingInference<'a> {
 fn assign_names(query: SqlQuery, ctx: &mut Context) -> SqlQuery {
     // generate CTE names, make sure they don't clash
     let decls = ctx.anchor.table_decls.values_mut();
-    let mut names = HashSet::new();
-    for decl in decls.sorted_by_key(|d| d.id.get()) {
+    let (externs, mut decls): (Vec<_>, Vec<_>) =
+        (decls.sorted_by_key(|d| d.id.get())).partition(|d| d.is_extern);
+
+    // tables of the database cannot be renamed
+    let mut names: HashSet<_> = externs.into_iter().flat_map(|d| d.name.clone()).collect();
+
+    // names from the query are kept, but suffixed when already taken
+    for name in decls.iter_mut().flat_map(|d| d.name.as_mut()) {
+        let base = name.name.clone();
+        let mut suffix = 1..;
+        while names.contains(name) {
+            name.name = format!("{base}_{}", suffix.next().unwrap());
+        }
+        names.insert(name.clone());
+    }
+
+    // other relations are named by the order of their ids
+    for decl in decls.into_iter().filter(|d| d.name.is_none()) {
         while decl.name.is_none() || names.contains(decl.name.as_ref().unwrap()) {
             decl.name = Some(Ident::from_name(ctx.anchor.table_name.gen()));
         }
@@ -1,20 +1,24 @@
 use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
 use std::collections::hash_map::RandomState;
 use std::collections::{HashMap, HashSet};
+use std::iter::zip;
 
 use itertools::Itertools;
 
-use super::anchor::{infer_complexity, CidCollector, Complexity};
+use super::anchor::{infer_complexity, references_window, CidCollector, Complexity};
 use super::ast::*;
 
 use crate::ir::generic::{ColumnSort, SortDirection, WindowFrame, WindowKind};
 use crate::ir::pl::{JoinSide, Literal};
 use crate::ir::rq::{
-    self, maybe_binop, new_binop, CId, Compute, Expr, ExprKind, RqFold, Transform, Window,
+    self, fold_expr_kind, maybe_binop, new_binop, CId, Compute, Expr, ExprKind, RelationColumn,
+    RqFold, TId, TableRef, Transform, Window,
 };
 use crate::sql::Context;
-use crate::{debug, Error, Result, WithErrorInfo};
-use prqlc_parser::generic::{InterpolateItem, Range};
+use crate::utils::IdGenerator;
+use crate::{debug, Error, Result, Span, WithErrorInfo};
+use prqlc_parser::generic::{InterpolateItem, Range, SwitchCase};
 
 /// Converts RQ AST into SqlRQ AST and applies a few preprocessing operations.
 ///
@@ -26,9 +30,11 @@ pub(in crate::sql) fn preprocess(
 ) -> Result<Vec<SqlTransform>> {
     Ok(pipeline)
         .and_then(normalize)
+        .and_then(|p| fill_nulls(p, ctx))
         .and_then(|p| wrap(p, ctx))
         .and_then(|p| prune_inputs(p, ctx))
         .and_then(|p| distinct(p, ctx))
+        .and_then(|p| qualify(p, ctx))
         .and_then(|p| union(p, ctx))
         .and_then(|p| except(p, ctx))
         .and_then(|p| intersect(p, ctx))
@@ -57,6 +63,103 @@ pub(in crate::sql) fn preprocess(
 //     res
 // }
 
+/// Emulates the windows that `fill` computes, for dialects that don't support
+/// IGNORE NULLS. A running count of the non-null values of the column groups
+/// each value with the nulls that follow it, and the value is then spread over
+/// its group with MAX.
+///
+/// The count runs in the opposite order when filling backward, so the default
+/// frame of the window can be used.
+pub(in crate::sql) fn fill_nulls(
+    pipeline: Vec<Transform>,
+    ctx: &mut Context,
+) -> Result<Vec<Transform>> {
+    if ctx.dialect.supports_ignore_nulls() {
+        return Ok(pipeline);
+    }
+
+    let mut res = Vec::with_capacity(pipeline.len());
+    // fills follow all the counts of adjacent fills, so they share a query
+    let mut fills = Vec::new();
+    for transform in pipeline {
+        let compute = match transform {
+            Transform::Compute(compute) if is_fill(&compute.expr) => compute,
+            transform => {
+                res.append(&mut fills);
+                res.push(transform);
+                continue;
+            }
+        };
+        let (name, mut args) = compute.expr.kind.into_operator().unwrap();
+        let column = args.remove(0);
+        let window = compute.window.unwrap();
+
+        let mut sort = window.sort;
+        if name == "std.fill_backward" {
+            sort = sort.into_iter().map(ColumnSort::reversed).collect();
+        }
+
+        let group = Compute {
+            id: ctx.anchor.cid.gen(),
+            expr: Expr {
+                kind: ExprKind::SString(vec![
+                    InterpolateItem::String("COUNT(".to_string()),
+                    InterpolateItem::Expr {
+                        expr: Box::new(column.clone()),
+                        format: None,
+                    },
+                    InterpolateItem::String(")".to_string()),
+                ]),
+                span: None,
+            },
+            window: Some(Window {
+                frame: WindowFrame {
+                    kind: WindowKind::Range,
+                    range: Range {
+                        start: None,
+                        end: Some(int_expr(0)),
+                    },
+                },
+                partition: window.partition.clone(),
+                sort,
+            }),
+            is_aggregation: false,
+        };
+        ctx.anchor.register_compute(group.clone());
+
+        let mut partition = window.partition;
+        partition.push(group.id);
+        let fill = Compute {
+            expr: Expr {
+                kind: ExprKind::Operator {
+                    name: "std.max".to_string(),
+                    args: vec![column],
+                },
+                span: compute.expr.span,
+            },
+            window: Some(Window {
+                frame: WindowFrame {
+                    kind: WindowKind::Rows,
+                    range: Range::unbounded(),
+                },
+                partition,
+                sort: Vec::new(),
+            }),
+            ..compute
+        };
+        ctx.anchor.register_compute(fill.clone());
+
+        res.push(Transform::Compute(group));
+        fills.push(Transform::Compute(fill));
+    }
+    res.append(&mut fills);
+    Ok(res)
+}
+
+fn is_fill(expr: &Expr) -> bool {
+    matches!(&expr.kind, ExprKind::Operator { name, .. } if name == "std.fill_forward" || name == "std.fill_backward")
+}
+
 /// Removes unused relation inputs
 pub(in crate::sql) fn prune_inputs(
     mut pipeline: Vec<SqlTransform>,
@@ -172,6 +275,11 @@ pub(in crate::sql) fn distinct(
 
                     res.push(SqlTransform::Sort(sort));
                     res.push(SqlTransform::DistinctOn(partition));
+                } else if ctx.dialect.supports_limit_by() && range_int.end.is_some() {
+                    // LIMIT n BY (only if the range is bounded)
+
+                    res.push(SqlTransform::Sort(sort));
+                    res.push(SqlTransform::LimitBy { range, partition });
                 } else {
                     // convert `take range` into:
                     //   derive _rn = s"ROW NUMBER"
@@ -187,12 +295,34 @@ pub(in crate::sql) fn distinct(
     Ok(res)
 }
 
+/// Creates [SqlTransform::Qualify] from filters on window functions, when
+/// supported by the dialect.
+pub(in crate::sql) fn qualify(
+    pipeline: Vec<SqlTransform>,
+    ctx: &mut Context,
+) -> Result<Vec<SqlTransform>> {
+    if !ctx.dialect.supports_qualify() {
+        return Ok(pipeline);
+    }
+
+    let res = pipeline.into_iter().map(|transform| match transform {
+        SqlTransform::Super(Transform::Filter(filter))
+            if references_window(&filter, &ctx.anchor, &|_| true) =>
+        {
+            SqlTransform::Qualify(filter)
+        }
+        transform => transform,
+    });
+    Ok(res.collect())
+}
+
 fn into_column_sort(partition: &[CId]) -> Vec<ColumnSort<CId>> {
     partition
         .iter()
         .map(|cid| ColumnSort {
             direction: SortDirection::Asc,
             column: *cid,
+            nulls: None,
         })
         .collect_vec()
 }
@@ -248,10 +378,11 @@ fn create_filter_by_row_number(
 
     let compute = SqlTransform::Super(Transform::Compute(compute));
     let filter = SqlTransform::Super(Transform::Filter(match (range_int.start, range_int.end) {
-        (Some(s), Some(e)) if s == e => new_binop(col_ref, "std.eq", int_expr(s)),
+        (Some(s), Some(e)) if s == e => new_binop(col_ref, "std.eq", take_bound_expr(s)),
         (start, end) => {
-            let start = start.map(|start| new_binop(col_ref.clone(), "std.gte", int_expr(start)));
-            let end = end.map(|end| new_binop(col_ref, "std.lte", int_expr(end)));
+            let start =
+                start.map(|start| new_binop(col_ref.clone(), "std.gte", take_bound_expr(start)));
+            let end = end.map(|end| new_binop(col_ref, "std.lte", take_bound_expr(end)));
 
             maybe_binop(start, "std.and", end).unwrap_or(Expr {
                 kind: ExprKind::Literal(Literal::Boolean(true)),
@@ -275,6 +406,15 @@ fn int_expr(i: i64) -> Expr {
     }
 }
 
+/// Bounds of `take` are s-strings rather than literals, so they are inlined
+/// into SQL even when a parameterized query binds its literals.
+fn take_bound_expr(i: i64) -> Expr {
+    Expr {
+        span: None,
+        kind: ExprKind::SString(vec![InterpolateItem::String(i.to_string())]),
+    }
+}
+
 /// Creates [SqlTransform::Union] from [Transform::Append]
 pub(in crate::sql) fn union(
     pipeline: Vec<SqlTransform>,
@@ -546,12 +686,24 @@ fn col_refs(exprs: Vec<&Expr>) -> Vec<CId> {
 /// - the transform order in SQL requires Computes to be before Filter. This
 ///   can be circumvented by materializing the column earlier in the pipeline,
 ///   which is done in this function.
-pub(in crate::sql) fn reorder(mut pipeline: Vec<SqlTransform>) -> Vec<SqlTransform> {
+///
+/// Comments are moved along with the transforms they annotate.
+pub(in crate::sql) fn reorder(pipeline: Vec<SqlTransform>) -> Vec<SqlTransform> {
     use SqlTransform::Super;
     use Transform::*;
 
+    let mut annotated = Vec::with_capacity(pipeline.len());
+    let mut comments = Vec::new();
+    for transform in pipeline {
+        if let Super(Comment(_)) = transform {
+            comments.push(transform);
+        } else {
+            annotated.push((std::mem::take(&mut comments), transform));
+        }
+    }
+
     // reorder Compose
-    pipeline.sort_by(|a, b| match (a, b) {
+    annotated.sort_by(|(_, a), (_, b)| match (a, b) {
         // don't reorder with From or Join or itself
         (
             SqlTransform::From(_) | SqlTransform::Join { .. } | Super(Compute(_)),
@@ -574,12 +726,18 @@ pub(in crate::sql) fn reorder(mut pipeline: Vec<SqlTransform>) -> Vec<SqlTransfo
         _ => Ordering::Equal,
     });
 
-    pipeline
+    (annotated.into_iter())
+        .flat_map(|(comments, transform)| comments.into_iter().chain([transform]))
+        .chain(comments)
+        .collect()
 }
 
 /// Normalize query:
 /// - Swap null checks such that null is always on the right side.
 ///   This is needed to simplify code for Except and for compiling to IS NULL.
+/// - Remove sorts that are followed by an aggregation. The aggregation does
+///   not preserve row order, but the sorted columns would still be SELECTed
+///   next to the aggregates, which is not valid without a GROUP BY.
 pub(in crate::sql) fn normalize(pipeline: Vec<Transform>) -> Result<Vec<Transform>> {
     Normalizer {}.fold_transforms(pipeline)
 }
@@ -587,6 +745,27 @@ pub(in crate::sql) fn normalize(pipeline: Vec<Transform>) -> Result<Vec<Transfor
 struct Normalizer {}
 
 impl RqFold for Normalizer {
+    fn fold_transforms(&mut self, transforms: Vec<Transform>) -> Result<Vec<Transform>> {
+        let transforms = rq::fold_transforms(self, transforms)?;
+
+        let mut is_aggregated = false;
+        let mut res = Vec::with_capacity(transforms.len());
+        for transform in transforms.into_iter().rev() {
+            match &transform {
+                Transform::Aggregate { .. } => is_aggregated = true,
+
+                // take and loop depend on the order of rows
+                Transform::Take(_) | Transform::Loop(_) => is_aggregated = false,
+
+                Transform::Sort(_) if is_aggregated => continue,
+                _ => {}
+            }
+            res.push(transform);
+        }
+        res.reverse();
+        Ok(res)
+    }
+
     fn fold_expr(&mut self, expr: Expr) -> Result<Expr> {
         let expr = Expr {
             kind: rq::fold_expr_kind(self, expr.kind)?,
@@ -616,3 +795,1111 @@ impl RqFold for Normalizer {
         Ok(expr)
     }
 }
+
+/// Removes declarations of tables that are not referenced by the main
+/// relation, neither directly nor through other tables.
+///
+/// Removing a table can leave the tables that it references unused, so this
+/// repeats until no more tables are removed.
+pub(in crate::sql) fn prune_tables(mut query: rq::RelationalQuery) -> Result<rq::RelationalQuery> {
+    loop {
+        let mut collector = TableRefCollector::default();
+        collector.fold_relation(query.relation.clone())?;
+        for table in &query.tables {
+            collector.current = Some(table.id);
+            collector.fold_table(table.clone())?;
+        }
+
+        let len = query.tables.len();
+        query
+            .tables
+            .retain(|t| collector.referenced.contains(&t.id));
+        if query.tables.len() == len {
+            return Ok(query);
+        }
+    }
+}
+
+/// Merges tables whose relations are the same, such as a filtered table that
+/// is joined twice, into a single table that all of their references share.
+///
+/// Relations are compared after numbering their columns in the order of
+/// their appearance, and without spans, so the same pipeline written at two
+/// places of a query is a match. Tables that only pick columns of another
+/// table are not merged, since they are not computed on their own anyway.
+///
+/// Merging tables can make the tables that reference them the same, so this
+/// repeats until no more tables are merged.
+pub(in crate::sql) fn merge_duplicate_tables(
+    mut query: rq::RelationalQuery,
+) -> Result<rq::RelationalQuery> {
+    loop {
+        let mut first_of_relation: HashMap<String, TId> = HashMap::new();
+        let mut redirects = HashMap::new();
+        for table in &query.tables {
+            if is_table_alias(&table.relation) {
+                continue;
+            }
+
+            let relation = CidNumbering::default().fold_relation(table.relation.clone())?;
+            let key =
+                serde_json::to_string(&relation).map_err(|e| Error::new_simple(e.to_string()))?;
+            match first_of_relation.entry(key) {
+                Entry::Occupied(first) => {
+                    redirects.insert(table.id, *first.get());
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(table.id);
+                }
+            }
+        }
+        if redirects.is_empty() {
+            return Ok(query);
+        }
+
+        log::debug!("merging duplicate tables {redirects:?}");
+        query.tables.retain(|t| !redirects.contains_key(&t.id));
+        query = TidRenamer { redirects }.fold_query(query)?;
+    }
+}
+
+/// Whether a relation only picks columns of another table.
+fn is_table_alias(relation: &rq::Relation) -> bool {
+    match &relation.kind {
+        rq::RelationKind::Pipeline(transforms) => {
+            (transforms.iter()).all(|t| matches!(t, Transform::From(_) | Transform::Select(_)))
+        }
+        _ => false,
+    }
+}
+
+/// Numbers columns in the order of their appearance, and removes spans, so
+/// relations that differ only in the ids of their columns become equal.
+#[derive(Default)]
+struct CidNumbering {
+    numbers: HashMap<CId, CId>,
+}
+
+impl RqFold for CidNumbering {
+    fn fold_cid(&mut self, cid: CId) -> Result<CId> {
+        let next = CId::from(self.numbers.len());
+        Ok(*self.numbers.entry(cid).or_insert(next))
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Result<Expr> {
+        Ok(Expr {
+            kind: self.fold_expr_kind(expr.kind)?,
+            span: None,
+        })
+    }
+}
+
+struct TidRenamer {
+    redirects: HashMap<TId, TId>,
+}
+
+impl RqFold for TidRenamer {
+    fn fold_table_ref(&mut self, table_ref: TableRef) -> Result<TableRef> {
+        let source = table_ref.source;
+        Ok(TableRef {
+            source: self.redirects.get(&source).cloned().unwrap_or(source),
+            ..table_ref
+        })
+    }
+
+    fn fold_expr_kind(&mut self, kind: ExprKind) -> Result<ExprKind> {
+        Ok(match kind {
+            ExprKind::SubQuery(tid) => {
+                ExprKind::SubQuery(self.redirects.get(&tid).cloned().unwrap_or(tid))
+            }
+            kind => fold_expr_kind(self, kind)?,
+        })
+    }
+}
+
+#[derive(Default)]
+struct TableRefCollector {
+    /// Table whose relation is being folded, if any.
+    current: Option<TId>,
+
+    referenced: HashSet<TId>,
+}
+
+impl RqFold for TableRefCollector {
+    fn fold_table_ref(&mut self, table_ref: TableRef) -> Result<TableRef> {
+        // a table that references itself is still unused
+        if self.current != Some(table_ref.source) {
+            self.referenced.insert(table_ref.source);
+        }
+        Ok(table_ref)
+    }
+
+    fn fold_expr_kind(&mut self, kind: ExprKind) -> Result<ExprKind> {
+        if let ExprKind::SubQuery(tid) = kind {
+            self.referenced.insert(tid);
+        }
+        fold_expr_kind(self, kind)
+    }
+}
+
+/// Finds tables that are referenced exactly once, by the main relation or by
+/// other tables. Such tables can be inlined as sub-queries at the place of the
+/// reference, instead of being defined as CTEs.
+///
+/// Tables that are referenced more than once (i.e. in a self-join) are
+/// excluded, since inlining them would duplicate their relation.
+pub(in crate::sql) fn single_use_tables(query: &rq::RelationalQuery) -> Result<HashSet<TId>> {
+    Ok((table_references(query)?.into_iter())
+        .filter(|(_, count)| *count == 1)
+        .map(|(tid, _)| tid)
+        .collect())
+}
+
+/// Counts the references to each of the tables of a query.
+pub(in crate::sql) fn table_references(query: &rq::RelationalQuery) -> Result<HashMap<TId, usize>> {
+    let mut counter = TableRefCounter::default();
+    counter.fold_relation(query.relation.clone())?;
+    for table in &query.tables {
+        counter.fold_table(table.clone())?;
+    }
+    Ok(counter.references)
+}
+
+#[derive(Default)]
+struct TableRefCounter {
+    references: HashMap<TId, usize>,
+}
+
+impl RqFold for TableRefCounter {
+    fn fold_table_ref(&mut self, table_ref: TableRef) -> Result<TableRef> {
+        *self.references.entry(table_ref.source).or_default() += 1;
+        Ok(table_ref)
+    }
+}
+
+/// Pushes conditions of filters that directly follow a join, and reference
+/// columns of only one side of it, into that side:
+/// - conditions on the left side are moved before the join,
+/// - conditions on the right side are moved into a new table, which filters
+///   the joined table.
+///
+/// Filters are split on `and`, so each condition moves on its own. Conditions
+/// that reference both sides, no columns at all, or contain s-strings, stay
+/// where they are. A side is only filtered before the join when the join
+/// doesn't pad it with nulls: the left side of inner and left joins, and the
+/// right side of inner and right joins.
+pub(in crate::sql) fn push_down_predicates(
+    query: rq::RelationalQuery,
+) -> Result<rq::RelationalQuery> {
+    let (cid, tid, query) = IdGenerator::load(query);
+    let mut pushdown = PredicatePushdown {
+        cid,
+        tid,
+        tables: Vec::new(),
+    };
+    let mut query = pushdown.fold_query(query)?;
+    query.tables.extend(pushdown.tables);
+    Ok(query)
+}
+
+struct PredicatePushdown {
+    cid: IdGenerator<CId>,
+    tid: IdGenerator<TId>,
+
+    /// Tables that filter the right sides of joins.
+    tables: Vec<rq::TableDecl>,
+}
+
+impl RqFold for PredicatePushdown {
+    fn fold_transforms(&mut self, transforms: Vec<Transform>) -> Result<Vec<Transform>> {
+        let mut pipeline = rq::fold_transforms(self, transforms)?;
+
+        // joins are visited from the last one, so that conditions moved before
+        // a join can be pushed again, into the joins that precede it
+        for j in (0..pipeline.len()).rev() {
+            let Transform::Join { side, with, .. } = &pipeline[j] else {
+                continue;
+            };
+            let to_left = matches!(side, JoinSide::Inner | JoinSide::Left);
+            let to_right = matches!(side, JoinSide::Inner | JoinSide::Right);
+
+            let left: HashSet<CId> = pipeline[..j].iter().flat_map(declared_cids).collect();
+            let right: HashSet<CId> = with.columns.iter().map(|(_, cid)| *cid).collect();
+
+            let mut left_conditions = Vec::new();
+            let mut right_conditions = Vec::new();
+            let mut k = j + 1;
+            while let Some(Transform::Filter(filter)) = pipeline.get(k) {
+                let mut staying = Vec::new();
+                for condition in split_conjunction(filter.clone()) {
+                    let cids = CidCollector::collect(condition.clone());
+                    if cids.is_empty() || contains_sstring(&condition) {
+                        staying.push(condition);
+                    } else if to_left && cids.iter().all(|c| left.contains(c)) {
+                        left_conditions.push(condition);
+                    } else if to_right && cids.iter().all(|c| right.contains(c)) {
+                        right_conditions.push(condition);
+                    } else {
+                        staying.push(condition);
+                    }
+                }
+
+                if let Some(filter) = join_conjunction(staying) {
+                    pipeline[k] = Transform::Filter(filter);
+                    k += 1;
+                } else {
+                    pipeline.remove(k);
+                }
+            }
+
+            if let Some(filter) = join_conjunction(right_conditions) {
+                let Transform::Join {
+                    side,
+                    with,
+                    filter: join_filter,
+                } = pipeline.remove(j)
+                else {
+                    unreachable!()
+                };
+                let with = self.filter_table_ref(with, filter);
+                let join = Transform::Join {
+                    side,
+                    with,
+                    filter: join_filter,
+                };
+                pipeline.insert(j, join);
+            }
+            if let Some(filter) = join_conjunction(left_conditions) {
+                pipeline.insert(j, Transform::Filter(filter));
+            }
+        }
+        Ok(pipeline)
+    }
+}
+
+impl PredicatePushdown {
+    /// Declares a table that applies the filter to the referenced table, and
+    /// returns a reference to it, with the same columns.
+    fn filter_table_ref(&mut self, table_ref: TableRef, filter: Expr) -> TableRef {
+        let redirects: HashMap<CId, CId> = (table_ref.columns.iter())
+            .map(|(_, cid)| (*cid, self.cid.gen()))
+            .collect();
+        let columns = (table_ref.columns.iter())
+            .map(|(col, cid)| (col.clone(), redirects[cid]))
+            .collect_vec();
+        let filter = CidRenamer { redirects }.fold_expr(filter).unwrap();
+
+        let from = TableRef {
+            source: table_ref.source,
+            columns: columns.clone(),
+            name: table_ref.name.clone(),
+        };
+        let relation = rq::Relation {
+            kind: rq::RelationKind::Pipeline(vec![
+                Transform::From(from),
+                Transform::Filter(filter),
+                Transform::Select(columns.into_iter().map(|(_, cid)| cid).collect()),
+            ]),
+            columns: table_ref
+                .columns
+                .iter()
+                .map(|(col, _)| col.clone())
+                .collect(),
+        };
+        let id = self.tid.gen();
+        self.tables.push(rq::TableDecl {
+            id,
+            name: None,
+            relation,
+        });
+
+        TableRef {
+            source: id,
+            ..table_ref
+        }
+    }
+}
+
+/// Emulates full joins, for databases that don't support FULL JOIN. The rows
+/// of a left join are combined with the rows of the joined relation that have
+/// no match:
+///
+/// ```sql
+/// SELECT ... FROM a LEFT JOIN b ON a.id = b.id
+/// UNION ALL
+/// SELECT ... FROM b LEFT JOIN a ON a.id = b.id WHERE a.id IS NULL
+/// ```
+///
+/// The pipeline before the join is moved into a new table, so it can be
+/// joined from both sides. Rows without a match are found by a column of the
+/// left side that the join condition compares for equality, which is never
+/// null in rows with a match.
+pub(in crate::sql) fn emulate_full_joins(
+    query: rq::RelationalQuery,
+) -> Result<rq::RelationalQuery> {
+    let (cid, tid, query) = IdGenerator::load(query);
+    let mut emulator = FullJoinEmulator {
+        cid,
+        tid,
+        tables: Vec::new(),
+    };
+    let mut query = emulator.fold_query(query)?;
+    query.tables.extend(emulator.tables);
+    Ok(query)
+}
+
+struct FullJoinEmulator {
+    cid: IdGenerator<CId>,
+    tid: IdGenerator<TId>,
+
+    /// Tables of the left sides of joins, and of the rows without a match.
+    tables: Vec<rq::TableDecl>,
+}
+
+impl RqFold for FullJoinEmulator {
+    fn fold_relation(&mut self, relation: rq::Relation) -> Result<rq::Relation> {
+        let relation = rq::fold_relation(self, relation)?;
+        let rq::RelationKind::Pipeline(mut pipeline) = relation.kind else {
+            return Ok(relation);
+        };
+
+        // names of the columns in the output of the relation
+        let mut output_names = HashMap::new();
+        if let Some(Transform::Select(cids)) = pipeline.last() {
+            for (cid, col) in zip(cids, &relation.columns) {
+                if let RelationColumn::Single(Some(name)) = col {
+                    output_names.insert(*cid, name.clone());
+                }
+            }
+        }
+
+        let is_full_join = |t: &Transform| {
+            matches!(
+                t,
+                Transform::Join {
+                    side: JoinSide::Full,
+                    ..
+                }
+            )
+        };
+        while let Some(j) = pipeline.iter().position(is_full_join) {
+            pipeline = self.emulate_full_join(pipeline, j, &output_names)?;
+        }
+
+        Ok(rq::Relation {
+            kind: rq::RelationKind::Pipeline(pipeline),
+            ..relation
+        })
+    }
+}
+
+impl FullJoinEmulator {
+    fn emulate_full_join(
+        &mut self,
+        mut prefix: Vec<Transform>,
+        j: usize,
+        output_names: &HashMap<CId, String>,
+    ) -> Result<Vec<Transform>> {
+        let suffix = prefix.split_off(j + 1);
+        let Some(Transform::Join {
+            with: right,
+            filter,
+            ..
+        }) = prefix.pop()
+        else {
+            unreachable!()
+        };
+
+        // columns of both sides that are used after the join
+        let left_decls: HashMap<CId, Option<RelationColumn>> = (prefix.iter())
+            .flat_map(|t| match t {
+                Transform::From(table_ref)
+                | Transform::Join {
+                    with: table_ref, ..
+                } => (table_ref.columns.iter())
+                    .map(|(col, cid)| (*cid, Some(col.clone())))
+                    .collect_vec(),
+                t => declared_cids(t)
+                    .into_iter()
+                    .map(|cid| (cid, None))
+                    .collect_vec(),
+            })
+            .collect();
+        let referenced = (suffix.iter().cloned())
+            .flat_map(|t| CidCollector::collect_t(t).1)
+            .chain(CidCollector::collect(filter.clone()))
+            .unique()
+            .collect_vec();
+        let left_cids = (referenced.iter())
+            .filter(|cid| left_decls.contains_key(cid))
+            .copied()
+            .collect_vec();
+        let right_columns = (right.columns.iter())
+            .filter(|(_, cid)| referenced.contains(cid))
+            .cloned()
+            .collect_vec();
+        let right_cids = right_columns.iter().map(|(_, cid)| *cid).collect_vec();
+
+        let has_wildcard = (left_cids.iter())
+            .filter_map(|cid| left_decls[cid].as_ref())
+            .chain(right_columns.iter().map(|(col, _)| col))
+            .any(|col| matches!(col, RelationColumn::Wildcard));
+        if has_wildcard {
+            return Err(Error::new_simple(
+                "full joins are emulated for this database, which requires the columns of both sides to be known",
+            )
+            .push_hint("select the columns of both relations before the join")
+            .with_span(filter.span));
+        }
+
+        let Some(key) = equality_key(&filter, &left_decls, &right_cids) else {
+            return Err(Error::new_simple(
+                "full joins are emulated for this database, which requires the join condition to compare a column of each side for equality",
+            )
+            .push_hint("join on columns, such as `(==id)`")
+            .with_span(filter.span));
+        };
+
+        // names of the columns of both sides, which are the columns of the
+        // tables that this declares
+        let left_names = (left_cids.iter()).map(|cid| {
+            output_names
+                .get(cid)
+                .cloned()
+                .or_else(|| match &left_decls[cid] {
+                    Some(RelationColumn::Single(name)) => name.clone(),
+                    _ => None,
+                })
+        });
+        let right_names = (right_columns.iter()).map(|(col, _)| match col {
+            RelationColumn::Single(name) => name.clone(),
+            RelationColumn::Wildcard => None,
+        });
+        let names = unique_names(left_names.chain(right_names));
+        let (left_names, _) = names.split_at(left_cids.len());
+
+        // the left side
+        if let Some(Transform::Select(_)) = prefix.last() {
+            prefix.pop();
+        }
+        prefix.push(Transform::Select(left_cids.clone()));
+        let left_tid = self.declare_table(prefix, left_names);
+
+        // the rows of the left join
+        let (left_ref, redirects) = self.table_ref(left_tid, left_names, &left_cids);
+        let left_output = left_ref.columns.iter().map(|(_, cid)| *cid).collect_vec();
+        let mut union = vec![
+            Transform::From(left_ref),
+            Transform::Join {
+                side: JoinSide::Left,
+                with: right.clone(),
+                filter: CidRenamer { redirects }.fold_expr(filter.clone())?,
+            },
+            Transform::Select([left_output, right_cids.clone()].concat()),
+        ];
+
+        // the rows of the right side without a match
+        let unmatched = {
+            let right_ref = TableRef {
+                columns: (right_columns.into_iter())
+                    .map(|(col, _)| (col, self.cid.gen()))
+                    .collect(),
+                ..right
+            };
+            let (left_ref, mut redirects) = self.table_ref(left_tid, left_names, &left_cids);
+            redirects.extend(zip(
+                right_cids.iter().copied(),
+                right_ref.columns.iter().map(|(_, cid)| *cid),
+            ));
+
+            let output = (left_ref.columns.iter())
+                .chain(&right_ref.columns)
+                .map(|(_, cid)| *cid)
+                .collect();
+            let key = Expr {
+                kind: ExprKind::ColumnRef(redirects[&key]),
+                span: None,
+            };
+            let null = Expr {
+                kind: ExprKind::Literal(Literal::Null),
+                span: None,
+            };
+            let filter = CidRenamer { redirects }.fold_expr(filter)?;
+            let pipeline = vec![
+                Transform::From(right_ref),
+                Transform::Join {
+                    side: JoinSide::Left,
+                    with: left_ref,
+                    filter,
+                },
+                Transform::Filter(new_binop(key, "std.eq", null)),
+                Transform::Select(output),
+            ];
+            self.declare_table(pipeline, &names)
+        };
+        let (unmatched_ref, _) = self.table_ref(unmatched, &names, &[]);
+        union.push(Transform::Append(unmatched_ref));
+
+        // the rest of the pipeline continues from the union
+        let union = self.declare_table(union, &names);
+        let (union_ref, redirects) =
+            self.table_ref(union, &names, &[left_cids, right_cids].concat());
+        let mut pipeline = vec![Transform::From(union_ref)];
+        pipeline.extend(CidRenamer { redirects }.fold_transforms(suffix)?);
+        Ok(pipeline)
+    }
+
+    fn declare_table(&mut self, pipeline: Vec<Transform>, names: &[String]) -> TId {
+        let id = self.tid.gen();
+        let columns = (names.iter())
+            .map(|name| RelationColumn::Single(Some(name.clone())))
+            .collect();
+        self.tables.push(rq::TableDecl {
+            id,
+            name: None,
+            relation: rq::Relation {
+                kind: rq::RelationKind::Pipeline(pipeline),
+                columns,
+            },
+        });
+        id
+    }
+
+    /// A reference to a declared table, with new ids for its columns, and
+    /// the redirects of the given columns to them.
+    fn table_ref(
+        &mut self,
+        source: TId,
+        names: &[String],
+        cids: &[CId],
+    ) -> (TableRef, HashMap<CId, CId>) {
+        let columns = (names.iter())
+            .map(|name| (RelationColumn::Single(Some(name.clone())), self.cid.gen()))
+            .collect_vec();
+        let redirects = zip(cids.iter().copied(), columns.iter().map(|(_, cid)| *cid)).collect();
+        let table_ref = TableRef {
+            source,
+            columns,
+            name: None,
+        };
+        (table_ref, redirects)
+    }
+}
+
+/// A column of the left side of a join, which its condition compares for
+/// equality with a column of the right side.
+fn equality_key(
+    filter: &Expr,
+    left: &HashMap<CId, Option<RelationColumn>>,
+    right: &[CId],
+) -> Option<CId> {
+    split_conjunction(filter.clone())
+        .into_iter()
+        .find_map(|condition| {
+            let ExprKind::Operator { name, args } = condition.kind else {
+                return None;
+            };
+            let [ExprKind::ColumnRef(a), ExprKind::ColumnRef(b)] =
+                [&args.first()?.kind, &args.get(1)?.kind]
+            else {
+                return None;
+            };
+            if name != "std.eq" {
+                return None;
+            }
+            if left.contains_key(a) && right.contains(b) {
+                Some(*a)
+            } else if left.contains_key(b) && right.contains(a) {
+                Some(*b)
+            } else {
+                None
+            }
+        })
+}
+
+/// Makes names unique, by appending a number to those already used. Columns
+/// without a name are named `_expr`, with a number.
+fn unique_names(names: impl Iterator<Item = Option<String>>) -> Vec<String> {
+    let mut used = HashSet::new();
+    names
+        .map(|name| {
+            let mut unique = name.clone().filter(|n| !used.contains(n));
+            let base = name.unwrap_or_else(|| "_expr".to_string());
+            let mut i = 0;
+            while unique.is_none() {
+                let candidate = format!("{base}_{i}");
+                if !used.contains(&candidate) {
+                    unique = Some(candidate);
+                }
+                i += 1;
+            }
+            let unique = unique.unwrap();
+            used.insert(unique.clone());
+            unique
+        })
+        .collect()
+}
+
+/// Merges filters that directly follow one another into a single filter of
+/// the conjunction of their conditions. Filters separated by any other
+/// transform stay apart, so a filter after an aggregation (HAVING) is never
+/// merged with one before it (WHERE). Comments between filters are kept
+/// before the merged filter.
+pub(in crate::sql) fn merge_filters(query: rq::RelationalQuery) -> Result<rq::RelationalQuery> {
+    FilterMerger {}.fold_query(query)
+}
+
+struct FilterMerger {}
+
+impl RqFold for FilterMerger {
+    fn fold_transforms(&mut self, transforms: Vec<Transform>) -> Result<Vec<Transform>> {
+        let transforms = rq::fold_transforms(self, transforms)?;
+
+        let mut res: Vec<Transform> = Vec::with_capacity(transforms.len());
+        for transform in transforms {
+            let comments = res.iter().rev().take_while(|t| t.is_comment()).count();
+            let prev = res.len().checked_sub(comments + 1);
+
+            let transform = match (prev, transform) {
+                (Some(prev), Transform::Filter(filter)) if res[prev].is_filter() => {
+                    let prev = res.remove(prev).into_filter().unwrap();
+                    Transform::Filter(new_binop(prev, "std.and", filter))
+                }
+                (_, transform) => transform,
+            };
+            res.push(transform);
+        }
+        Ok(res)
+    }
+}
+
+/// Removes filters whose condition is constant:
+/// - `filter true` keeps all rows, so it is removed,
+/// - `filter false` keeps no rows, so the filters, sorts and takes before it
+///   in the same pipeline are removed, and its condition is written as
+///   `1 = 0`, which all databases accept.
+///
+/// To find more of these, `and` and `or` with a boolean literal are
+/// simplified first, which holds in SQL's three-valued logic too. None of
+/// the removed transforms declare columns, so the columns of the pipeline do
+/// not change. A `filter false` stays in place, so an aggregation after it
+/// still produces its row of a count of zero.
+pub(in crate::sql) fn eliminate_constant_filters(
+    query: rq::RelationalQuery,
+) -> Result<rq::RelationalQuery> {
+    ConstantFilterEliminator {}.fold_query(query)
+}
+
+struct ConstantFilterEliminator {}
+
+impl RqFold for ConstantFilterEliminator {
+    fn fold_transforms(&mut self, transforms: Vec<Transform>) -> Result<Vec<Transform>> {
+        let transforms = rq::fold_transforms(self, transforms)?;
+
+        let mut res: Vec<Transform> = Vec::with_capacity(transforms.len());
+        for transform in transforms {
+            let transform = match transform {
+                Transform::Filter(filter) => match as_bool_literal(&filter) {
+                    Some(true) => continue,
+                    Some(false) => {
+                        res.retain(|t| {
+                            !matches!(
+                                t,
+                                Transform::Filter(_) | Transform::Sort(_) | Transform::Take(_)
+                            )
+                        });
+                        Transform::Filter(new_binop(int_expr(1), "std.eq", int_expr(0)))
+                    }
+                    None => Transform::Filter(filter),
+                },
+                transform => transform,
+            };
+            res.push(transform);
+        }
+        Ok(res)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Result<Expr> {
+        let expr = Expr {
+            kind: rq::fold_expr_kind(self, expr.kind)?,
+            ..expr
+        };
+
+        let ExprKind::Operator { name, mut args } = expr.kind else {
+            return Ok(expr);
+        };
+        if let ("std.and" | "std.or", 2) = (name.as_str(), args.len()) {
+            // the value that decides the result on its own
+            let dominant = name == "std.or";
+            let literals = (as_bool_literal(&args[0]), as_bool_literal(&args[1]));
+            match literals {
+                (Some(l), _) if l == dominant => return Ok(args.remove(0)),
+                (_, Some(r)) if r == dominant => return Ok(args.remove(1)),
+                (Some(_), _) => return Ok(args.remove(1)),
+                (_, Some(_)) => return Ok(args.remove(0)),
+                _ => {}
+            }
+        }
+        Ok(Expr {
+            kind: ExprKind::Operator { name, args },
+            ..expr
+        })
+    }
+}
+
+/// Orders the columns that `group` emits when its pipeline also lists some of
+/// the columns that it groups by, which would otherwise be emitted twice.
+///
+/// By default, the grouped columns come first, in the order of `by`, followed
+/// by the results of the aggregation, in source order. When `interleave` is
+/// set, the grouped columns that the aggregation lists are emitted where they
+/// are written instead.
+pub(in crate::sql) fn order_group_columns(
+    query: rq::RelationalQuery,
+    interleave: bool,
+) -> Result<rq::RelationalQuery> {
+    GroupColumnOrderer { interleave }.fold_query(query)
+}
+
+struct GroupColumnOrderer {
+    interleave: bool,
+}
+
+impl RqFold for GroupColumnOrderer {
+    fn fold_relation(&mut self, relation: rq::Relation) -> Result<rq::Relation> {
+        let relation = rq::fold_relation(self, relation)?;
+        let rq::RelationKind::Pipeline(mut pipeline) = relation.kind else {
+            return Ok(relation);
+        };
+        let mut columns = relation.columns;
+
+        if let Some(repeated) = self.repeated_group_columns(&pipeline) {
+            let Some(Transform::Select(cids)) = pipeline.pop() else {
+                unreachable!()
+            };
+            pipeline.push(Transform::Select(without_positions(cids, &repeated)));
+            columns = without_positions(columns, &repeated);
+        }
+
+        Ok(rq::Relation {
+            kind: rq::RelationKind::Pipeline(pipeline),
+            columns,
+        })
+    }
+}
+
+impl GroupColumnOrderer {
+    /// Positions in the final select of the grouped columns that are emitted
+    /// twice, and should be emitted only at the other position.
+    fn repeated_group_columns(&self, pipeline: &[Transform]) -> Option<HashSet<usize>> {
+        // only the select that the group itself implies, which is the last
+        // transform and is not preceded by a select of the user
+        let aggregate = pipeline.iter().rposition(|t| t.as_aggregate().is_some())?;
+        let selects = (pipeline[aggregate..].iter())
+            .positions(|t| t.as_select().is_some())
+            .collect_vec();
+        if selects != [pipeline.len() - 1 - aggregate] {
+            return None;
+        }
+        let (partition, compute) = pipeline[aggregate].as_aggregate().unwrap();
+        let cids = pipeline.last().unwrap().as_select().unwrap();
+
+        let keys = partition.len();
+        if cids.get(..keys) != Some(partition)
+            || cids.get(keys..keys + compute.len()) != Some(compute)
+        {
+            return None;
+        }
+
+        let repeated: HashSet<_> = (compute.iter().enumerate())
+            .filter_map(|(index, cid)| {
+                let first = partition.iter().position(|p| p == cid)?;
+                Some(if self.interleave { first } else { keys + index })
+            })
+            .collect();
+        (!repeated.is_empty()).then_some(repeated)
+    }
+}
+
+fn without_positions<T>(items: Vec<T>, positions: &HashSet<usize>) -> Vec<T> {
+    (items.into_iter().enumerate())
+        .filter(|(index, _)| !positions.contains(index))
+        .map(|(_, item)| item)
+        .collect()
+}
+
+fn as_bool_literal(expr: &Expr) -> Option<bool> {
+    match &expr.kind {
+        ExprKind::Literal(Literal::Boolean(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Columns that a transform makes available to the transforms after it.
+fn declared_cids(transform: &Transform) -> Vec<CId> {
+    match transform {
+        Transform::From(table_ref)
+        | Transform::Join {
+            with: table_ref, ..
+        } => table_ref.columns.iter().map(|(_, cid)| *cid).collect(),
+        Transform::Compute(compute) => vec![compute.id],
+        _ => Vec::new(),
+    }
+}
+
+fn split_conjunction(expr: Expr) -> Vec<Expr> {
+    match expr.kind {
+        ExprKind::Operator { name, mut args } if name == "std.and" && args.len() == 2 => {
+            let right = args.pop().unwrap();
+            let left = args.pop().unwrap();
+            [split_conjunction(left), split_conjunction(right)].concat()
+        }
+        kind => vec![Expr { kind, ..expr }],
+    }
+}
+
+fn join_conjunction(conditions: Vec<Expr>) -> Option<Expr> {
+    (conditions.into_iter()).reduce(|left, right| new_binop(left, "std.and", right))
+}
+
+fn contains_sstring(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::SString(_) => true,
+        ExprKind::Operator { args, .. } | ExprKind::Array(args) => {
+            args.iter().any(contains_sstring)
+        }
+        ExprKind::Case(cases) => (cases.iter())
+            .any(|case| contains_sstring(&case.condition) || contains_sstring(&case.value)),
+        _ => false,
+    }
+}
+
+struct CidRenamer {
+    redirects: HashMap<CId, CId>,
+}
+
+impl RqFold for CidRenamer {
+    fn fold_cid(&mut self, cid: CId) -> Result<CId> {
+        Ok(self.redirects.get(&cid).cloned().unwrap_or(cid))
+    }
+}
+
+/// Simplifies boolean predicates:
+/// - pushes `not` into `and` & `or`, following de Morgan's laws,
+/// - removes double negations,
+/// - converts `not (a == b)` into `a != b` and vice versa.
+///
+/// All of these hold in SQL's three-valued logic too: when `a` or `b` is null,
+/// both sides evaluate to null. A negated comparison with a null literal is
+/// still compiled to `IS NULL` / `IS NOT NULL`.
+pub(in crate::sql) fn simplify_predicates(
+    query: rq::RelationalQuery,
+) -> Result<rq::RelationalQuery> {
+    PredicateSimplifier {}.fold_query(query)
+}
+
+struct PredicateSimplifier {}
+
+impl RqFold for PredicateSimplifier {
+    fn fold_expr(&mut self, expr: Expr) -> Result<Expr> {
+        let expr = Expr {
+            kind: rq::fold_expr_kind(self, expr.kind)?,
+            ..expr
+        };
+
+        Ok(match expr.kind {
+            ExprKind::Operator { name, mut args } if name == "std.not" && args.len() == 1 => {
+                negate(args.remove(0), expr.span)
+            }
+            kind => Expr { kind, ..expr },
+        })
+    }
+}
+
+/// Returns the negation of an already simplified expression, pushing the
+/// negation as far inwards as possible.
+fn negate(expr: Expr, span: Option<Span>) -> Expr {
+    let ExprKind::Operator { name, mut args } = expr.kind else {
+        return new_not(expr, span);
+    };
+
+    let negated = match (name.as_str(), args.len()) {
+        ("std.not", 1) => return args.remove(0),
+        ("std.eq", 2) => "std.ne",
+        ("std.ne", 2) => "std.eq",
+        ("std.and", 2) | ("std.or", 2) => {
+            let right = negate(args.pop().unwrap(), None);
+            let left = negate(args.pop().unwrap(), None);
+            args = vec![left, right];
+
+            if name == "std.and" {
+                "std.or"
+            } else {
+                "std.and"
+            }
+        }
+        _ => {
+            let expr = Expr {
+                kind: ExprKind::Operator { name, args },
+                ..expr
+            };
+            return new_not(expr, span);
+        }
+    };
+
+    Expr {
+        kind: ExprKind::Operator {
+            name: negated.to_string(),
+            args,
+        },
+        span: span.or(expr.span),
+    }
+}
+
+fn new_not(expr: Expr, span: Option<Span>) -> Expr {
+    Expr {
+        kind: ExprKind::Operator {
+            name: "std.not".to_string(),
+            args: vec![expr],
+        },
+        span,
+    }
+}
+
+/// Adjusts booleans for dialects that do not have a boolean type, where they
+/// are represented by 1 and 0. Conditions that are not comparisons (such as a
+/// bare boolean column) compare with 1, and comparisons that are used as
+/// values are converted to 1 or 0 with a CASE.
+pub(in crate::sql) fn emulate_booleans(query: rq::RelationalQuery) -> Result<rq::RelationalQuery> {
+    BooleanEmulator {}.fold_query(query)
+}
+
+struct BooleanEmulator {}
+
+impl RqFold for BooleanEmulator {
+    fn fold_transform(&mut self, transform: Transform) -> Result<Transform> {
+        Ok(match transform {
+            Transform::Filter(condition) => Transform::Filter(self.fold_condition(condition)?),
+            Transform::Join { side, with, filter } => Transform::Join {
+                side,
+                with: self.fold_table_ref(with)?,
+                filter: self.fold_condition(filter)?,
+            },
+            transform => rq::fold_transform(self, transform)?,
+        })
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Result<Expr> {
+        if is_predicate(&expr) {
+            // CASE WHEN {expr} THEN 1 ELSE 0 END
+            let span = expr.span;
+            let cases = vec![
+                SwitchCase {
+                    condition: self.fold_condition(expr)?,
+                    value: bool_expr(true),
+                },
+                SwitchCase {
+                    condition: bool_expr(true),
+                    value: bool_expr(false),
+                },
+            ];
+            return Ok(Expr {
+                kind: ExprKind::Case(cases),
+                span,
+            });
+        }
+
+        let kind = match expr.kind {
+            ExprKind::Case(cases) => ExprKind::Case(
+                (cases.into_iter())
+                    .map(|case| -> Result<_> {
+                        // the default is a case with condition `true`
+                        let is_default =
+                            case.condition.kind == ExprKind::Literal(Literal::Boolean(true));
+                        Ok(SwitchCase {
+                            condition: if is_default {
+                                case.condition
+                            } else {
+                                self.fold_condition(case.condition)?
+                            },
+                            value: self.fold_expr(case.value)?,
+                        })
+                    })
+                    .try_collect()?,
+            ),
+            ExprKind::Operator { name, args } if name == "std.filtered" => {
+                let [condition, aggregation]: [Expr; 2] = args.try_into().unwrap();
+                ExprKind::Operator {
+                    name,
+                    args: vec![
+                        self.fold_condition(condition)?,
+                        self.fold_expr(aggregation)?,
+                    ],
+                }
+            }
+            kind => fold_expr_kind(self, kind)?,
+        };
+        Ok(Expr { kind, ..expr })
+    }
+}
+
+impl BooleanEmulator {
+    fn fold_condition(&mut self, expr: Expr) -> Result<Expr> {
+        if !is_predicate(&expr) {
+            // {expr} = 1
+            let span = expr.span;
+            return Ok(Expr {
+                span,
+                ..new_binop(self.fold_expr(expr)?, "std.eq", bool_expr(true))
+            });
+        }
+
+        let ExprKind::Operator { name, args } = expr.kind else {
+            unreachable!()
+        };
+        let args = if matches!(name.as_str(), "std.and" | "std.or" | "std.not") {
+            args.into_iter()
+                .map(|a| self.fold_condition(a))
+                .try_collect()?
+        } else {
+            args.into_iter().map(|a| self.fold_expr(a)).try_collect()?
+        };
+        Ok(Expr {
+            kind: ExprKind::Operator { name, args },
+            ..expr
+        })
+    }
+}
+
+/// Whether an expression is a comparison or a logical operation, which SQL
+/// can use as a condition, but not always as a value.
+fn is_predicate(expr: &Expr) -> bool {
+    let ExprKind::Operator { name, .. } = &expr.kind else {
+        return false;
+    };
+    matches!(
+        name.as_str(),
+        "std.eq"
+            | "std.ne"
+            | "std.gt"
+            | "std.gte"
+            | "std.lt"
+            | "std.lte"
+            | "std.and"
+            | "std.or"
+            | "std.not"
+            | "std.regex_search"
+            | "std.text.starts_with"
+            | "std.text.contains"
+            | "std.text.ends_with"
+            | "std.array_in"
+            | "std.relation_in"
+    )
+}
+
+fn bool_expr(value: bool) -> Expr {
+    Expr {
+        kind: ExprKind::Literal(Literal::Boolean(value)),
+        span: None,
+    }
+}
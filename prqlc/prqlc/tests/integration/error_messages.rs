@@ -275,6 +275,26 @@ fn test_ambiguous_inference() {
     ");
 }
 
+#[test]
+fn test_ambiguous_self_join() {
+    assert_snapshot!(compile(r#"
+    from e = employees
+    join m = employees (e.manager_id == m.id)
+    select {name}
+    "#)
+    .unwrap_err(), @r"
+    Error:
+       ╭─[:4:13]
+       │
+     4 │     select {name}
+       │             ──┬─
+       │               ╰─── Ambiguous name
+       │
+       │ Help: could be any of: e.name, m.name
+    ───╯
+    ");
+}
+
 #[test]
 fn date_to_text_generic() {
     assert_snapshot!(compile(r#"
@@ -292,6 +312,49 @@ fn date_to_text_generic() {
     "#);
 }
 
+#[test]
+fn test_relation_literal_columns() {
+    assert_snapshot!(compile(r#"
+    from [{id = 1, name = "a"}, {id = 2}]
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:2:33]
+       │
+     2 │     from [{id = 1, name = "a"}, {id = 2}]
+       │                                 ────┬───
+       │                                     ╰───── relation literal row is missing column `name`
+    ───╯
+    "#);
+
+    assert_snapshot!(compile(r#"
+    from [{id = 1, name = "a"}, {id = 2, nme = "b"}]
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:2:48]
+       │
+     2 │     from [{id = 1, name = "a"}, {id = 2, nme = "b"}]
+       │                                                ─┬─
+       │                                                 ╰─── relation literal row contains an unexpected column `nme`
+       │
+       │ Help: expected columns id, name
+    ───╯
+    "#);
+
+    assert_snapshot!(compile(r#"
+    from []
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:2:10]
+       │
+     2 │     from []
+       │          ─┬
+       │           ╰── relation literal must contain at least one row
+       │
+       │ Help: columns of a relation literal are inferred from its rows
+    ───╯
+    ");
+}
+
 #[test]
 fn date_to_text_not_supported_dialect() {
     assert_snapshot!(compile(r#"
@@ -378,3 +441,624 @@ fn empty_interpolations() {
     ───╯
     "#);
 }
+
+#[test]
+fn test_multiple_errors() {
+    // errors of independent statements are all reported
+    assert_snapshot!(compile(r#"
+    let addadd = a b -> a + b
+
+    let x = (from t | derive {y = addadd 1 2 3})
+
+    from t2
+    derive {z = addadd 4 5 6}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:4:35]
+       │
+     4 │     let x = (from t | derive {y = addadd 1 2 3})
+       │                                   ──────┬─────
+       │                                         ╰─────── Too many arguments to function `addadd`
+    ───╯
+    Error:
+       ╭─[:7:17]
+       │
+     7 │     derive {z = addadd 4 5 6}
+       │                 ──────┬─────
+       │                       ╰─────── Too many arguments to function `addadd`
+    ───╯
+    ");
+
+    // main references `x`, so its error is omitted
+    assert_snapshot!(compile(r#"
+    let addadd = a b -> a + b
+
+    let x = (from t | derive {y = addadd 1 2 3})
+
+    from x
+    derive {z = addadd 4 5 6}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:4:35]
+       │
+     4 │     let x = (from t | derive {y = addadd 1 2 3})
+       │                                   ──────┬─────
+       │                                         ╰─────── Too many arguments to function `addadd`
+    ───╯
+    ");
+}
+
+#[test]
+fn test_error_spans() {
+    let source = r#"
+    let addadd = a b -> a + b
+
+    let x = (from t | select {a} | select {b})
+
+    from t2
+    derive {z = addadd 4 5 6}
+    "#;
+    let errors = prqlc::compile(source, &prqlc::Options::default()).unwrap_err();
+
+    let spans = (errors.inner.iter())
+        .map(|e| e.span.unwrap())
+        .map(|span| &source[span.start..span.end])
+        .collect::<Vec<_>>();
+    assert_eq!(spans, vec!["b", "addadd 4 5 6"]);
+}
+
+#[test]
+fn test_unsorted_take() {
+    use prqlc::{LintLevel, Options};
+
+    let deny = Options::default()
+        .no_signature()
+        .with_unsorted_take(LintLevel::Deny)
+        .with_display(prqlc::DisplayOptions::Plain);
+
+    assert_snapshot!(prqlc::compile(r#"
+    from employees
+    select {id, name}
+    take 10
+    "#, &deny).unwrap_err(), @r"
+    Error:
+       ╭─[:4:5]
+       │
+     4 │     take 10
+       │     ───┬───
+       │        ╰───── `take` without a preceding `sort` returns arbitrary rows
+       │
+       │ Help: add a `sort` before `take` to choose which rows are returned
+    ───╯
+    ");
+
+    // within relation variables and joined relations
+    let source = r#"
+    let top = (from salaries | take 5)
+
+    from e = employees
+    join d = (from departments | take 1) (e.dept_id == d.id)
+    join t = top (e.id == t.employee_id)
+    "#;
+    let errors = prqlc::compile(source, &deny).unwrap_err();
+    let spans = (errors.inner.iter())
+        .map(|e| e.span.unwrap())
+        .map(|span| &source[span.start..span.end])
+        .collect::<Vec<_>>();
+    assert_eq!(spans, vec!["take 5", "take 1"]);
+
+    // a preceding sort, a take within a group, or the default of allowing
+    // unsorted takes compile
+    for source in [
+        "from employees | sort id | select {id, name} | take 10",
+        "from employees | sort {-salary} | take 1..10 | take 3",
+        "from employees | group {dept_id} (take 1)",
+        "from employees | group {dept_id} (sort id | take 2)",
+    ] {
+        prqlc::compile(source, &deny).unwrap();
+    }
+    prqlc::compile("from employees | take 10", &Options::default()).unwrap();
+
+    // warnings don't fail the compilation
+    let warn = Options::default().with_unsorted_take(LintLevel::Warn);
+    prqlc::compile("from employees | take 10", &warn).unwrap();
+}
+
+#[test]
+fn test_take_last() {
+    // the last rows depend on the order of the rows
+    assert_snapshot!(compile(r#"
+    from employees
+    take (-5)..
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:5]
+       │
+     3 │     take (-5)..
+       │     ─────┬─────
+       │          ╰─────── `take` of the last rows requires the rows to be sorted
+       │
+       │ Help: add a `sort` before the `take`, as in `sort id | take (-5)..`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    from employees
+    sort id
+    take (-5..10)
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:4:5]
+       │
+     4 │     take (-5..10)
+       │     ──────┬──────
+       │           ╰──────── `take` cannot count one bound of a range from the first row, and the other from the last
+    ───╯
+    ");
+}
+
+#[test]
+fn test_cartesian_join() {
+    use prqlc::{LintLevel, MessageKind, Options};
+
+    let warn = Options::default()
+        .no_signature()
+        .with_cartesian_join(LintLevel::Warn)
+        .with_display(prqlc::DisplayOptions::Plain);
+
+    // warnings are returned along with the SQL
+    let result = prqlc::compile_with_warnings(
+        r#"
+    from shirts
+    join hats true
+    join socks (shirts.color == socks.color)
+    "#,
+        &warn,
+    )
+    .unwrap();
+    assert_eq!(result.warnings.len(), 1);
+    assert_eq!(result.warnings[0].kind, MessageKind::Warning);
+    assert_snapshot!(result.warnings[0], @r"
+    Warning:
+       ╭─[:3:5]
+       │
+     3 │     join hats true
+       │     ───────┬──────
+       │            ╰──────── `join` on `true` returns every combination of rows
+       │
+       │ Help: join on a condition that relates the rows of both relations
+    ───╯
+    ");
+
+    // joins on a condition, and the default of allowing cartesian joins, don't
+    // warn
+    let result = prqlc::compile_with_warnings("from shirts | join hats (==color)", &warn).unwrap();
+    assert!(result.warnings.is_empty());
+    let result =
+        prqlc::compile_with_warnings("from shirts | join hats true", &Options::default()).unwrap();
+    assert!(result.warnings.is_empty());
+
+    // denied, they fail the compilation
+    let deny = warn.with_cartesian_join(LintLevel::Deny);
+    prqlc::compile("from shirts | join hats true", &deny).unwrap_err();
+}
+
+#[test]
+fn test_recursive_functions() {
+    assert_snapshot!(compile(r#"
+    let countdown = x -> case [x > 0 => countdown (x - 1), true => 0]
+
+    from t
+    derive {y = countdown a}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:2:41]
+       │
+     2 │     let countdown = x -> case [x > 0 => countdown (x - 1), true => 0]
+       │                                         ────────┬────────
+       │                                                 ╰────────── Recursive functions are not supported: `countdown` -> `countdown`
+       │
+       │ Help: functions are inlined into the query, so recursion over rows has to be written with `loop`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    let is_even = x -> case [x == 0 => true, true => is_odd (x - 1)]
+    let is_odd = x -> case [x == 0 => false, true => is_even (x - 1)]
+
+    from t
+    derive {y = is_even a}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:54]
+       │
+     3 │     let is_odd = x -> case [x == 0 => false, true => is_even (x - 1)]
+       │                                                      ───────┬───────
+       │                                                             ╰───────── Recursive functions are not supported: `is_even` -> `is_odd` -> `is_even`
+       │
+       │ Help: functions are inlined into the query, so recursion over rows has to be written with `loop`
+    ───╯
+    ");
+
+    // declaring mutually recursive functions is fine, as long as they are not
+    // called
+    compile(
+        r#"
+    let f = x -> (g x)
+    let g = x -> (f x)
+
+    from t
+    select {a}
+    "#,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_invalid_date_literal() {
+    assert_snapshot!(compile(r#"
+    from employees
+    filter hired_at > @2024-13-01
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:23]
+       │
+     3 │     filter hired_at > @2024-13-01
+       │                       ─────┬─────
+       │                            ╰─────── invalid date `@2024-13-01`: month must be between 1 and 12
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    from employees
+    filter hired_at > @2024-01-15T25:00
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:23]
+       │
+     3 │     filter hired_at > @2024-01-15T25:00
+       │                       ────────┬────────
+       │                               ╰────────── invalid timestamp `@2024-01-15T25:00`: hour must be between 0 and 23
+    ───╯
+    ");
+}
+
+#[test]
+fn test_unsafe_interpolations() {
+    use prqlc::{LintLevel, Options};
+
+    let deny = Options::default()
+        .no_signature()
+        .with_unsafe_interpolations(LintLevel::Deny)
+        .with_display(prqlc::DisplayOptions::Plain);
+
+    // interpolations are idents, but they may resolve to any expression
+    assert_snapshot!(prqlc::compile(r#"
+    let total = x -> s"SUM({x})"
+
+    from employees
+    aggregate {t = total (salary + bonus)}
+    "#, &deny).unwrap_err(), @r"
+    Error:
+       ╭─[:5:27]
+       │
+     5 │     aggregate {t = total (salary + bonus)}
+       │                           ───────┬──────
+       │                                  ╰──────── s-string interpolates an expression other than a column
+       │
+       │ Help: interpolate only columns and numbers, and compute the expression with PRQL
+    ───╯
+    ");
+
+    assert_snapshot!(prqlc::compile(r#"
+    let name = "admin"
+
+    from employees
+    filter s"name = {name}"
+    "#, &deny).unwrap_err(), @r#"
+    Error:
+       ╭─[:2:16]
+       │
+     2 │     let name = "admin"
+       │                ───┬───
+       │                   ╰───── s-string interpolates an expression other than a column
+       │
+       │ Help: interpolate only columns and numbers, and compute the expression with PRQL
+    ───╯
+    "#);
+
+    // columns, relations and numbers are fine
+    prqlc::compile(
+        r#"
+    let exponent = 2
+    let total = x -> s"SUM({x})"
+
+    from employees
+    derive {p = s"POWER({salary}, {exponent})"}
+    join d = (s"SELECT * FROM {departments}") (==dept_id)
+    aggregate {t = total p}
+    "#,
+        &deny,
+    )
+    .unwrap();
+
+    // the check is off by default
+    let query = r#"
+    let name = "admin"
+    from employees | filter s"name = {name}"
+    "#;
+    prqlc::compile(query, &Options::default()).unwrap();
+}
+
+#[test]
+fn test_target_version() {
+    use prqlc::Options;
+
+    let options = Options::default()
+        .with_display(prqlc::DisplayOptions::Plain)
+        .with_target_version(Some("3.24".parse().unwrap()));
+
+    assert_snapshot!(prqlc::compile(r#"
+    prql target:sql.sqlite
+
+    from employees
+    derive {r = rank salary}
+    "#, &options).unwrap_err(), @r"
+    Error:
+       ╭─[:5:17]
+       │
+     5 │     derive {r = rank salary}
+       │                 ─────┬─────
+       │                      ╰─────── sqlite 3.24 does not support window functions
+       │
+       │ Help: window functions were added in 3.25
+    ───╯
+    ");
+
+    let options = options.with_target_version(Some("5.7".parse().unwrap()));
+    assert_snapshot!(prqlc::compile(r#"
+    prql target:sql.mysql
+
+    from [{n = 1}]
+    loop (filter n < 3 | select {n = n + 1})
+    "#, &options).unwrap_err(), @r"
+    Error: mysql 5.7 does not support CTEs, which are required by `loop`
+    ↳ Hint: CTEs were added in 8.0
+    ");
+}
+
+#[test]
+fn test_emulated_full_join() {
+    assert_snapshot!(compile(r#"
+    prql target:sql.mysql
+
+    from employees
+    join side:full departments (employees.dept_id >= departments.min_id)
+    select {employees.name, departments.title}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:5:33]
+       │
+     5 │     join side:full departments (employees.dept_id >= departments.min_id)
+       │                                 ───────────────────┬───────────────────
+       │                                                    ╰───────────────────── full joins are emulated for this database, which requires the join condition to compare a column of each side for equality
+       │
+       │ Help: join on columns, such as `(==id)`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.mysql
+
+    from employees
+    join side:full departments (==dept_id)
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:5:33]
+       │
+     5 │     join side:full departments (==dept_id)
+       │                                 ────┬────
+       │                                     ╰────── full joins are emulated for this database, which requires the columns of both sides to be known
+       │
+       │ Help: select the columns of both relations before the join
+    ───╯
+    ");
+}
+
+#[test]
+fn test_column_patterns() {
+    // columns of the table are not known
+    assert_snapshot!(compile(r#"
+    from payments
+    select {id, column_names.starts_with "amt_"}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:17]
+       │
+     3 │     select {id, column_names.starts_with "amt_"}
+       │                 ───────────────┬───────────────
+       │                                ╰───────────────── cannot match column names, because the columns of `payments` are unknown
+       │
+       │ Help: declare the columns of the table with `Options::table_columns`
+    ───╯
+    "#);
+
+    assert_snapshot!(compile(r#"
+    from payments
+    select {id, amt_net, amt_tax}
+    select {column_names.starts_with "fee_"}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:4:13]
+       │
+     4 │     select {column_names.starts_with "fee_"}
+       │             ───────────────┬───────────────
+       │                            ╰───────────────── no columns match prefix `fee_`
+    ───╯
+    "#);
+}
+
+#[test]
+fn test_aggregate_filter() {
+    assert_snapshot!(compile(r#"
+    from orders
+    aggregate {paid = (count this | filtered "paid")}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:46]
+       │
+     3 │     aggregate {paid = (count this | filtered "paid")}
+       │                                              ───┬──
+       │                                                 ╰──── function std.filtered, param `condition` expected type `bool`, but found type `text`
+    ───╯
+    "#);
+
+    assert_snapshot!(compile(r#"
+    from orders
+    derive {paid = (amount | filtered (status == "paid"))}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:21]
+       │
+     3 │     derive {paid = (amount | filtered (status == "paid"))}
+       │                     ───┬──
+       │                        ╰──── `filtered` applies only to aggregate functions, such as `sum` or `count`
+    ───╯
+    "#);
+}
+
+#[test]
+fn test_aggregate_bare_column() {
+    assert_snapshot!(compile(r#"
+    from orders
+    group {customer_id} (
+      aggregate {customer_id, total = sum amount, status}
+    )
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:4:51]
+       │
+     4 │       aggregate {customer_id, total = sum amount, status}
+       │                                                   ───┬──
+       │                                                      ╰──── column `status` is used in `aggregate` without an aggregate function
+       │
+       │ Help: wrap it in an aggregate function, such as `sum status`, or group by it
+    ───╯
+    ");
+}
+
+#[test]
+fn test_cast_to_non_scalar() {
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {name = first_name as {first = text}}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:34]
+       │
+     3 │     derive {name = first_name as {first = text}}
+       │                                  ───────┬──────
+       │                                         ╰──────── cannot cast to `{first = text}`, which is not a scalar type
+       │
+       │ Help: cast to one of int, float, bool, text, date, time or timestamp
+    ───╯
+    ");
+
+    // the result of a cast has the type it is cast to
+    assert_snapshot!(compile(r#"
+    from employees
+    filter (is_active as int) && is_remote
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:13]
+       │
+     3 │     filter (is_active as int) && is_remote
+       │             ────────┬───────
+       │                     ╰───────── function std.and, param `left` expected type `bool`, but found type `int`
+    ───╯
+    ");
+}
+
+#[test]
+fn test_pipeline_let_recursive() {
+    assert_snapshot!(compile(r#"
+    from products
+    let price = price * 0.9
+    select {id, price}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:5]
+       │
+     3 │     let price = price * 0.9
+       │     ───────────┬───────────
+       │                ╰───────────── `let price` references itself
+       │
+       │ Help: use another name for the binding, or `derive` a column instead
+    ───╯
+    ");
+}
+
+#[test]
+fn test_max_subquery_depth() {
+    use prqlc::Options;
+
+    let query = r#"
+    from employees
+    group department (take 1)
+    sort salary
+    derive {r = rank salary}
+    filter r < 3
+    group title (aggregate {n = count this})
+    "#;
+
+    // the row numbers of `take`, the rank and the aggregation are each
+    // computed by a separate query
+    let options = Options::default().with_display(prqlc::DisplayOptions::Plain);
+    prqlc::compile(query, &options.clone().with_max_subquery_depth(Some(3))).unwrap();
+
+    assert_snapshot!(prqlc::compile(query, &options.with_max_subquery_depth(Some(2))).unwrap_err(), @r"
+    Error:
+       ╭─[:7:33]
+       │
+     7 │     group title (aggregate {n = count this})
+       │                                 ─────┬────
+       │                                      ╰────── query is nested 3 levels deep, which exceeds `max_subquery_depth` of 2
+       │
+       │ Help: each transform that cannot be applied within the preceding SELECT starts a new level
+    ───╯
+    ");
+}
+
+#[test]
+fn test_json_get_invalid_path() {
+    assert_snapshot!(compile(r#"
+    from customers
+    select {city = json.get "$.address..city" profile}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:20]
+       │
+     3 │     select {city = json.get "$.address..city" profile}
+       │                    ─────────────────┬────────────────
+       │                                     ╰────────────────── invalid JSON path `$.address..city`
+       │
+       │ Help: a path is a sequence of keys and indexes, as in `$.address.city` or `$.tags[0]`
+    ───╯
+    "#);
+}
+
+#[test]
+fn test_comparison_chain_sstring() {
+    assert_snapshot!(compile(r#"
+    from events
+    filter (0 < s"RANDOM()" < 0.5)
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:17]
+       │
+     3 │     filter (0 < s"RANDOM()" < 0.5)
+       │                 ─────┬─────
+       │                      ╰─────── an s-string can't be in the middle of a chain of comparisons, since it would be evaluated twice; `derive` it as a column first
+    ───╯
+    "#);
+}
@@ -0,0 +1,182 @@
+//! Tests of translating SQL into PRQL, which compile the PRQL back into SQL
+//! to check that the query keeps its shape.
+use insta::assert_snapshot;
+use prqlc::sql::Dialect;
+use prqlc::{Options, Target};
+
+#[track_caller]
+fn round_trip(sql: &str, dialect: Dialect) -> String {
+    let prql = prqlc::sql_to_prql(sql, dialect).unwrap();
+    let options = Options::default()
+        .no_signature()
+        .with_target(Target::Sql(Some(dialect)));
+    let sql = prqlc::compile(&prql, &options).unwrap();
+    format!("{prql}\n{sql}")
+}
+
+#[test]
+fn test_select_filter_sort_take() {
+    assert_snapshot!(round_trip(
+        "SELECT name, age FROM employees WHERE age > 30 AND NOT retired ORDER BY age DESC, name LIMIT 10",
+        Dialect::Generic,
+    ), @r"
+    from employees
+    filter age > 30 && !retired
+    select {name, age}
+    sort {-age, name}
+    take 10
+
+    SELECT
+      name,
+      age
+    FROM
+      employees
+    WHERE
+      age > 30
+      AND NOT retired
+    ORDER BY
+      age DESC,
+      name
+    LIMIT
+      10
+    ");
+}
+
+#[test]
+fn test_join() {
+    assert_snapshot!(round_trip(
+        r#"
+        SELECT e.name, d.title AS department, e.salary * 1.1 AS raised
+        FROM employees AS e
+        LEFT JOIN departments d ON e.dept_id = d.id
+        WHERE e.age BETWEEN 20 AND 30 AND d.title IS NOT NULL
+        ORDER BY raised DESC
+        LIMIT 5 OFFSET 10
+        "#,
+        Dialect::Postgres,
+    ), @r"
+    from e = employees
+    join side:left d = departments e.dept_id == d.id
+    filter (e.age | in 20..30) && d.title != null
+    select {
+      e.name,
+      department = d.title,
+      raised = e.salary * 1.1,
+    }
+    sort {-raised}
+    take 11..15
+
+    SELECT
+      e.name,
+      d.title AS department,
+      e.salary * 1.1 AS raised
+    FROM
+      employees AS e
+      LEFT JOIN departments AS d ON e.dept_id = d.id
+    WHERE
+      e.age BETWEEN 20 AND 30
+      AND d.title IS NOT NULL
+    ORDER BY
+      raised DESC
+    LIMIT
+      5 OFFSET 10
+    ");
+
+    assert_snapshot!(round_trip(
+        r#"
+        SELECT *, CASE WHEN o.total > 100 THEN 'big' ELSE 'small' END AS size
+        FROM orders o
+        JOIN customers c USING (customer_id)
+        "#,
+        Dialect::Generic,
+    ), @r#"
+    from o = orders
+    join c = customers (==customer_id)
+    derive {
+      size = case [o.total > 100 => "big", true => "small"],
+    }
+
+    SELECT
+      o.*,
+      c.*,
+      CASE
+        WHEN o.total > 100 THEN 'big'
+        ELSE 'small'
+      END AS size
+    FROM
+      orders AS o
+      JOIN customers AS c ON o.customer_id = c.customer_id
+    "#);
+}
+
+#[test]
+fn test_group_aggregate() {
+    assert_snapshot!(round_trip(
+        r#"
+        SELECT country, city, COUNT(*) AS n, AVG(salary), COUNT(DISTINCT title) AS titles
+        FROM employees
+        WHERE department IN ('sales', 'marketing')
+        GROUP BY country, city
+        ORDER BY n DESC
+        "#,
+        Dialect::Generic,
+    ), @r#"
+    from employees
+    filter (department | in ["sales", "marketing"])
+    group {country, city} (aggregate {
+      n = count this,
+      average salary,
+      titles = count_distinct title,
+    })
+    sort {-n}
+
+    SELECT
+      country,
+      city,
+      COUNT(*) AS n,
+      AVG(salary),
+      COUNT(DISTINCT title) AS titles
+    FROM
+      employees
+    WHERE
+      department IN ('sales', 'marketing')
+    GROUP BY
+      country,
+      city
+    ORDER BY
+      n DESC
+    "#);
+
+    assert_snapshot!(round_trip(
+        "SELECT MIN(age), MAX(age) FROM employees",
+        Dialect::Generic,
+    ), @r"
+    from employees
+    aggregate {min age, max age}
+
+    SELECT
+      MIN(age),
+      MAX(age)
+    FROM
+      employees
+    ");
+}
+
+#[test]
+fn test_unsupported() {
+    let sql = r#"
+    WITH t AS (SELECT * FROM a)
+    SELECT DISTINCT LOWER(name), x FROM t CROSS JOIN b GROUP BY x HAVING COUNT(*) > 1
+    "#;
+    assert_snapshot!(prqlc::sql_to_prql(sql, Dialect::Generic).unwrap_err(), @r"
+    Error: cannot translate `WITH` to PRQL
+    Error: cannot translate `DISTINCT` to PRQL
+    Error: cannot translate `HAVING` to PRQL
+    Error: cannot translate `CROSS JOIN b` to PRQL
+    Error: cannot translate `LOWER(name)` to PRQL
+    ↳ Hint: the projection of an aggregation must start with the `GROUP BY` keys
+    ");
+
+    assert_snapshot!(prqlc::sql_to_prql("DELETE FROM a", Dialect::Generic).unwrap_err(), @"Error: expected a single SELECT query");
+    assert_snapshot!(prqlc::sql_to_prql("SELECT FROM", Dialect::Generic).unwrap_err(), @"Error: sql parser error: Expected an expression, found: FROM");
+}
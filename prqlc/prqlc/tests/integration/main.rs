@@ -1,6 +1,8 @@
 mod bad_error_messages;
 mod dbs;
 mod error_messages;
+mod from_sql;
+mod polars;
 mod queries;
 mod resolving;
 mod sql;
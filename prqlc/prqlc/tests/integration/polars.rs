@@ -0,0 +1,68 @@
+//! Tests of compiling PRQL into Python code of a Polars lazy frame.
+use insta::assert_snapshot;
+
+#[track_caller]
+fn compile(prql: &str) -> String {
+    prqlc::compile_to_polars(prql).unwrap()
+}
+
+#[test]
+fn test_group_aggregate() {
+    assert_snapshot!(compile(r#"
+    from employees
+    filter country == "USA"
+    group {title, country} (
+      aggregate {average_salary = average salary, ct = count this}
+    )
+    sort {-average_salary}
+    "#), @r#"
+    (
+      employees
+      .filter((pl.col("country") == pl.lit("USA")))
+      .group_by([pl.col("title"), pl.col("country")], maintain_order=True)
+      .agg([pl.col("salary").mean().alias("average_salary"), pl.len().alias("ct")])
+      .sort([pl.col("average_salary")], descending=[True])
+      .select([pl.col("title"), pl.col("country"), pl.col("average_salary"), pl.col("ct")])
+    )
+    "#);
+}
+
+#[test]
+fn test_derive_join_take() {
+    assert_snapshot!(compile(r#"
+    from e = employees
+    join side:left d = departments (==dept_id)
+    derive {gross = e.salary + e.benefits}
+    filter gross > 100 && d.name != null
+    select {e.name, department = d.name, gross}
+    take 11..20
+    "#), @r#"
+    (
+      employees
+      .join(departments, left_on=["dept_id"], right_on=["dept_id"], how="left", coalesce=False)
+      .with_columns([(pl.col("salary") + pl.col("benefits")).alias("gross")])
+      .filter(((pl.col("gross") > pl.lit(100)) & pl.col("name_right").is_not_null()))
+      .with_columns([pl.col("name_right").alias("department")])
+      .select([pl.col("name"), pl.col("department"), pl.col("gross")])
+      .slice(10, 10)
+      .select([pl.col("name"), pl.col("department"), pl.col("gross")])
+    )
+    "#);
+}
+
+#[test]
+fn test_unsupported() {
+    let errors = prqlc::compile_to_polars(
+        r#"
+    from employees
+    append managers
+    loop (filter id > 0)
+    "#,
+    )
+    .unwrap_err();
+    let reasons: Vec<_> = errors.inner.iter().map(|e| e.reason.as_str()).collect();
+    assert_snapshot!(reasons.join("\n"), @r"
+    cannot compile `append` to Polars
+    cannot compile `loop` to Polars
+    ");
+}
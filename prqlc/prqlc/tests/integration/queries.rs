@@ -59,15 +59,16 @@ mod fmt {
         let test_name = prql_path.file_stem().unwrap().to_str().unwrap();
         let prql = fs::read_to_string(prql_path).unwrap();
 
-        let pl = prqlc::prql_to_pl(&prql).unwrap();
-        let formatted = prqlc::pl_to_prql(&pl).unwrap();
+        let formatted = prqlc::format(&prql).unwrap();
 
         with_settings!({ input_file => prql_path }, {
             assert_snapshot!(test_name, &formatted, &prql)
         });
 
-        // Check the formatted queries can still compile
+        // Check the formatted queries can still compile, and are left unchanged
+        // by formatting them again
         prqlc::prql_to_pl(&formatted).unwrap();
+        similar_asserts::assert_eq!(prqlc::format(&formatted).unwrap(), formatted);
     }
 }
 
@@ -1,4 +1,5 @@
 use insta::assert_snapshot;
+use itertools::Itertools;
 use prqlc::ErrorMessages;
 use prqlc_parser::parser::pr;
 
@@ -112,8 +113,127 @@ fn resolve_generics_01() {
       std.add a 1
     )
 
-    let my_float <float> = `(std.add ...)`
+    let my_float <float> = 2
 
-    let my_int <int> = `(std.add ...)`
+    let my_int <int> = 2
     ");
 }
+
+#[test]
+fn resolve_json_round_trip() {
+    let pl = prqlc::prql_to_pl(
+        r#"
+    from employees
+    derive {gross = salary + bonus}
+    group {department} (aggregate {total = sum gross})
+    sort {-total}
+    "#,
+    )
+    .unwrap();
+    let resolved = prqlc::internal::pl_to_resolved(pl).unwrap();
+
+    let json = prqlc::internal::json::from_resolved(&resolved).unwrap();
+    assert!(json.contains("TransformCall"));
+
+    // lineage of previous frames is not serialized, since it is only used
+    // during resolution
+    let parsed = prqlc::internal::json::to_resolved(&json).unwrap();
+    assert_eq!(prqlc::internal::json::from_resolved(&parsed).unwrap(), json);
+}
+
+#[test]
+fn resolve_incremental() {
+    let versions = [
+        // the first update resolves the std module too
+        (
+            "let rate = 0.1\nlet bonus = s -> s * rate\nfrom employees | derive {b = bonus salary}",
+            Some(4),
+        ),
+        // changes to the main pipeline resolve only the main pipeline
+        (
+            "let rate = 0.1\nlet bonus = s -> s * rate\nfrom employees | derive {b = bonus salary} | take 5",
+            Some(1),
+        ),
+        // changes to a declaration resolve the statements that follow it
+        (
+            "let rate = 0.1\nlet bonus = s -> s * rate * 2\nfrom employees | derive {b = bonus salary} | take 5",
+            Some(2),
+        ),
+        (
+            "let rate = 0.1\nlet bonus = s -> s * rate * 2\nfrom employees | derive {b = bonus wage} | take 5",
+            Some(1),
+        ),
+        // the same query again
+        (
+            "let rate = 0.1\nlet bonus = s -> s * rate * 2\nfrom employees | derive {b = bonus wage} | take 5",
+            Some(0),
+        ),
+        // errors, which are then fixed
+        (
+            "let rate = 0.1\nlet bonus = s -> s * rate * 2\nfrom employees | derive {b = bonus wage} | take",
+            None,
+        ),
+        (
+            "let rate = 0.1\nlet bonus = s -> s * rate * 2\nfrom employees | derive {b = bonus wage 3} | take 5",
+            None,
+        ),
+        (
+            "let rate = 0.2\nlet bonus = s -> s * rate * 2\nfrom employees | derive {b = bonus wage} | take 5",
+            Some(3),
+        ),
+    ];
+
+    let mut compiler = prqlc::Compiler::new();
+    let options = prqlc::Options::default()
+        .with_target(prqlc::Target::Sql(Some(prqlc::sql::Dialect::Postgres)));
+    for (source, resolved) in versions {
+        assert_eq!(compiler.update(source).ok(), resolved, "{source}");
+
+        let sql = compiler.current_sql(prqlc::sql::Dialect::Postgres);
+        let expected = prqlc::compile(source, &options);
+        match expected {
+            Ok(expected) => assert_eq!(sql.unwrap(), expected),
+            Err(expected) => assert_eq!(sql.unwrap_err().to_string(), expected.to_string()),
+        }
+    }
+}
+
+#[test]
+fn resolve_to_rq() {
+    use prqlc::ir::rq::{RelationKind, Transform};
+
+    let rq = prqlc::resolve(
+        r#"
+    let managers = (
+      from employees
+      filter is_manager
+      select {id, dept_id}
+    )
+    from managers
+    join departments (==dept_id)
+    group {departments.title} (aggregate {n = count this})
+    sort {-n}
+    "#,
+    )
+    .unwrap();
+
+    let kinds = |kind: &RelationKind| match kind {
+        RelationKind::Pipeline(transforms) => transforms.iter().map(Transform::as_ref).join(", "),
+        kind => format!("{kind:?}"),
+    };
+    let tables = (rq.tables.iter())
+        .map(|t| {
+            format!(
+                "{}: {}",
+                t.name.as_deref().unwrap_or("?"),
+                kinds(&t.relation.kind)
+            )
+        })
+        .join("\n");
+    assert_snapshot!(format!("{tables}\nmain: {}", kinds(&rq.relation.kind)), @r#"
+    ?: ExternRef(LocalTable(["departments"]))
+    ?: ExternRef(LocalTable(["employees"]))
+    managers: From, Filter, Select, Select
+    main: From, Join, Compute, Aggregate, Sort, Select
+    "#);
+}
@@ -1,5 +1,5 @@
 //! Simple tests for "this PRQL creates this SQL" go here.
-use insta::assert_snapshot;
+use insta::{assert_debug_snapshot, assert_snapshot};
 use prqlc::{sql, ErrorMessages, Options, SourceTree, Target};
 use rstest::rstest;
 
@@ -41,12 +41,12 @@ fn test_stdlib() {
     assert_snapshot!(compile(r###"
     from employees
     aggregate (
-        {salary_usd = (math.round 2 salary)}
+        {salary_usd = (math.round 2 (max salary))}
     )
     "###).unwrap(),
         @r"
     SELECT
-      ROUND(salary, 2) AS salary_usd
+      ROUND(MAX(salary), 2) AS salary_usd
     FROM
       employees
     "
@@ -140,7 +140,7 @@ fn test_stdlib_math_module_mssql() {
       EXP(salary) AS salary_exp,
       LOG(salary) AS salary_ln,
       LOG10(salary) AS salary_log10,
-      LOG10(salary) / LOG10(2) AS salary_log,
+      LOG(salary, 2) AS salary_log,
       SQRT(salary) AS salary_sqrt,
       DEGREES(salary) AS salary_degrees,
       RADIANS(salary) AS salary_radians,
@@ -157,6 +157,51 @@ fn test_stdlib_math_module_mssql() {
     );
 }
 
+#[rstest]
+#[case::generic(sql::Dialect::Generic, "LOG10(x) / LOG10(10)", "ROUND(price, 2)")]
+#[case::postgres(
+    sql::Dialect::Postgres,
+    "LOG10(x) / LOG10(10)",
+    "ROUND((price)::numeric, 2)"
+)]
+#[case::mysql(sql::Dialect::MySql, "LOG(10, x)", "ROUND(price, 2)")]
+#[case::sqlite(sql::Dialect::SQLite, "LN(x) / LN(10)", "ROUND(price, 2)")]
+#[case::mssql(sql::Dialect::MsSql, "LOG(x, 10)", "ROUND(price, 2)")]
+fn math_log_and_round(
+    #[case] dialect: sql::Dialect,
+    #[case] expected_log: &'static str,
+    #[case] expected_round: &'static str,
+) {
+    let query = r#"
+    from t
+    select {
+      log = math.log 10 x,
+      round = math.round 2 price,
+    }"#;
+    let opts = Options::default()
+        .no_signature()
+        .no_format()
+        .with_target(Target::Sql(Some(dialect)));
+    assert_eq!(
+        prqlc::compile(query, &opts).unwrap(),
+        format!("SELECT {expected_log} AS log, {expected_round} AS round FROM t")
+    )
+}
+
+#[test]
+fn test_math_log_precedence() {
+    // `log` compiles to a division, which is parenthesized within another one
+    assert_snapshot!(compile(r#"
+    from t
+    select {inverse = 1 / (math.log 10 x)}
+    "#).unwrap(), @r"
+    SELECT
+      1 / (LOG10(x) / LOG10(10)) AS inverse
+    FROM
+      t
+    ");
+}
+
 #[test]
 fn test_stdlib_text_module() {
     assert_snapshot!(compile(r#"
@@ -193,6 +238,41 @@ fn test_stdlib_text_module() {
     );
 }
 
+#[rstest]
+#[case::generic(sql::Dialect::Generic, "CHAR_LENGTH(name)", "SUBSTRING(name, 2, 5)")]
+#[case::duckdb(sql::Dialect::DuckDb, "LENGTH(name)", "SUBSTRING(name, 2, 5)")]
+#[case::mssql(sql::Dialect::MsSql, "LEN(name)", "SUBSTRING(name, 2, 5)")]
+#[case::mysql(sql::Dialect::MySql, "CHAR_LENGTH(name)", "SUBSTRING(name, 2, 5)")]
+#[case::postgres(sql::Dialect::Postgres, "CHAR_LENGTH(name)", "SUBSTR(name, 2, 5)")]
+#[case::sqlite(sql::Dialect::SQLite, "LENGTH(name)", "SUBSTRING(name, 2, 5)")]
+fn text_substring(
+    #[case] dialect: sql::Dialect,
+    #[case] expected_length: &'static str,
+    #[case] expected_substring: &'static str,
+) {
+    // positions start at 1, as in SQL
+    let query = r#"
+  from employees
+  select {
+    name_length = (name | text.length),
+    name_substring = (name | text.substring 2 5),
+  }
+  "#;
+    let expected = format!(
+        r#"
+SELECT
+  {expected_length} AS name_length,
+  {expected_substring} AS name_substring
+FROM
+  employees
+"#
+    );
+    assert_eq!(
+        compile_with_sql_dialect(query, dialect).unwrap(),
+        expected.trim_start()
+    )
+}
+
 #[rstest]
 #[case::generic(sql::Dialect::Generic, "LIKE CONCAT('%', 'pika', '%')")]
 #[case::sqlite(sql::Dialect::SQLite, "LIKE '%' || 'pika' || '%'")] // `CONCAT` is not supported in SQLite
@@ -217,6 +297,173 @@ FROM
     )
 }
 
+#[rstest]
+#[case::generic(sql::Dialect::Generic, "INTEGER", "DOUBLE", "BOOLEAN", "TIMESTAMP")]
+#[case::postgres(
+    sql::Dialect::Postgres,
+    "INTEGER",
+    "DOUBLE PRECISION",
+    "BOOLEAN",
+    "TIMESTAMP"
+)]
+#[case::sqlite(sql::Dialect::SQLite, "INTEGER", "REAL", "INTEGER", "TEXT")]
+#[case::mssql(sql::Dialect::MsSql, "INTEGER", "FLOAT", "BIT", "DATETIME2")]
+#[case::mysql(sql::Dialect::MySql, "SIGNED", "DOUBLE", "SIGNED", "DATETIME")]
+#[case::bigquery(sql::Dialect::BigQuery, "INT64", "FLOAT64", "BOOL", "TIMESTAMP")]
+#[case::clickhouse(sql::Dialect::ClickHouse, "Int64", "Float64", "Bool", "DateTime")]
+fn cast_types(
+    #[case] dialect: sql::Dialect,
+    #[case] int: &'static str,
+    #[case] float: &'static str,
+    #[case] bool: &'static str,
+    #[case] timestamp: &'static str,
+) {
+    let query = r#"
+  from employees
+  select {
+    age = (age as int),
+    salary = salary as float * 1.1,
+    active = (is_active as bool),
+    hired = (hired_at as timestamp),
+  }
+  "#;
+    let expected = format!(
+        r#"
+SELECT
+  CAST(age AS {int}) AS age,
+  CAST(salary AS {float}) * 1.1 AS salary,
+  CAST(is_active AS {bool}) AS active,
+  CAST(hired_at AS {timestamp}) AS hired
+FROM
+  employees
+"#
+    );
+    assert_eq!(
+        compile_with_sql_dialect(query, dialect).unwrap(),
+        expected.trim_start()
+    )
+}
+
+#[rstest]
+#[case::postgres(
+    sql::Dialect::Postgres,
+    "CONCAT(city, ', ', country)",
+    "CONCAT_WS(', ', city, country)"
+)]
+#[case::mysql(
+    sql::Dialect::MySql,
+    "CONCAT(COALESCE(city, ''), ', ', COALESCE(country, ''))",
+    "CONCAT_WS(', ', city, country)"
+)]
+#[case::sqlite(
+    sql::Dialect::SQLite,
+    "COALESCE(city, '') || ', ' || COALESCE(country, '')",
+    "CONCAT_WS(', ', city, country)"
+)]
+#[case::bigquery(
+    sql::Dialect::BigQuery,
+    "CONCAT(COALESCE(city, ''), ', ', COALESCE(country, ''))",
+    "ARRAY_TO_STRING([city, country], ', ')"
+)]
+#[case::snowflake(
+    sql::Dialect::Snowflake,
+    "CONCAT(COALESCE(city, ''), ', ', COALESCE(country, ''))",
+    "ARRAY_TO_STRING(ARRAY_CONSTRUCT_COMPACT(city, country), ', ')"
+)]
+#[case::clickhouse(
+    sql::Dialect::ClickHouse,
+    "CONCAT(COALESCE(city, ''), ', ', COALESCE(country, ''))",
+    "arrayStringConcat(arrayFilter(x -> isNotNull(x), [city, country]), ', ')"
+)]
+fn text_concat(
+    #[case] dialect: sql::Dialect,
+    #[case] expected_concat: &'static str,
+    #[case] expected_concat_ws: &'static str,
+) {
+    // nulls are empty strings in `concat` and are skipped in `concat_ws`
+    let query = r#"
+    from addresses
+    select {
+      a = (text.concat [city, ", ", country]),
+      b = (text.concat_ws ", " [city, country]),
+    }"#;
+    let options = Options::default()
+        .no_signature()
+        .no_format()
+        .with_target(Target::Sql(Some(dialect)));
+    assert_eq!(
+        prqlc::compile(query, &options).unwrap(),
+        format!("SELECT {expected_concat} AS a, {expected_concat_ws} AS b FROM addresses")
+    )
+}
+
+#[test]
+fn test_spread_args() {
+    assert_snapshot!(compile(r#"
+    let first_non_null = func columns -> (coalesce ...columns)
+
+    from employees
+    derive {
+      contact = first_non_null {email, phone},
+      nickname = coalesce ...{alias, name},
+    }
+    "#).unwrap(), @r"
+    SELECT
+      *,
+      COALESCE(email, phone) AS contact,
+      COALESCE(alias, name) AS nickname
+    FROM
+      employees
+    ");
+
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {contact = coalesce ...email}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:32]
+       │
+     3 │     derive {contact = coalesce ...email}
+       │                                ────┬───
+       │                                    ╰───── cannot spread `this.employees.email` into function arguments
+       │
+       │ Help: only tuples and arrays can be spread
+    ───╯
+    ");
+}
+
+#[test]
+fn test_null_handling_functions() {
+    let query = r#"
+    from invoices
+    derive {
+      average_line = total / (nullif line_count 0),
+      discount = zero_if_null discount,
+      note = blank_if_null note,
+    }
+    "#;
+
+    assert_snapshot!(compile(query).unwrap(), @r"
+    SELECT
+      *,
+      total / NULLIF(line_count, 0) AS average_line,
+      COALESCE(discount, 0) AS discount,
+      COALESCE(note, '') AS note
+    FROM
+      invoices
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::Snowflake).unwrap(), @r"
+    SELECT
+      *,
+      (total / NULLIF(line_count, 0)) AS average_line,
+      ZEROIFNULL(discount) AS discount,
+      COALESCE(note, '') AS note
+    FROM
+      invoices
+    ");
+}
+
 #[rstest]
 #[case::clickhouse(
     sql::Dialect::ClickHouse,
@@ -249,6 +496,127 @@ FROM
     )
 }
 
+#[rstest]
+#[case::postgres(
+    sql::Dialect::Postgres,
+    "DATE_TRUNC('month', created)",
+    "EXTRACT(year FROM created)"
+)]
+#[case::duckdb(
+    sql::Dialect::DuckDb,
+    "DATE_TRUNC('month', created)",
+    "EXTRACT(year FROM created)"
+)]
+#[case::bigquery(
+    sql::Dialect::BigQuery,
+    "DATE_TRUNC(created, month)",
+    "EXTRACT(year FROM created)"
+)]
+#[case::mssql(
+    sql::Dialect::MsSql,
+    "DATETRUNC(month, created)",
+    "DATEPART(year, created)"
+)]
+#[case::mysql(
+    sql::Dialect::MySql,
+    "(MAKEDATE(YEAR(created), 1) + INTERVAL (MONTH(created) - 1) MONTH)",
+    "EXTRACT(year FROM created)"
+)]
+fn date_trunc_and_part_operators(
+    #[case] dialect: sql::Dialect,
+    #[case] expected_trunc: &'static str,
+    #[case] expected_part: &'static str,
+) {
+    let query = r#"
+    from events
+    select {
+      month = (created | date.trunc "month"),
+      year = (created | date.part "year"),
+    }"#;
+    let opts = Options::default()
+        .no_signature()
+        .no_format()
+        .with_target(Target::Sql(Some(dialect)));
+    assert_eq!(
+        prqlc::compile(query, &opts).unwrap(),
+        format!("SELECT {expected_trunc} AS month, {expected_part} AS year FROM events")
+    )
+}
+
+#[test]
+fn test_date_trunc_mysql() {
+    // MySQL has no DATE_TRUNC, so dates are built from their parts
+    assert_snapshot!(compile(r#"
+    prql target:sql.mysql
+
+    from events
+    select {
+      year = date.trunc "year" created,
+      quarter = date.trunc "quarter" created,
+      week = date.trunc "week" created,
+      day = date.trunc "day" created,
+      hour = date.trunc "hour" created,
+    }
+    "#).unwrap(), @r"
+    SELECT
+      MAKEDATE(YEAR(created), 1) AS year,
+      (
+        MAKEDATE(YEAR(created), 1) + INTERVAL (QUARTER(created) - 1) QUARTER
+      ) AS quarter,
+      (DATE(created) - INTERVAL WEEKDAY(created) DAY) AS week,
+      DATE(created) AS day,
+      TIMESTAMP(DATE(created), MAKETIME(HOUR(created), 0, 0)) AS hour
+    FROM
+      events
+    ");
+}
+
+#[test]
+fn test_date_trunc_errors() {
+    assert_snapshot!(compile(r#"
+    from events
+    group {month = date.trunc "fortnight" created} (
+      aggregate {n = count this}
+    )
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:31]
+       │
+     3 │     group {month = date.trunc "fortnight" created} (
+       │                               ─────┬─────
+       │                                    ╰─────── `date.trunc` expected year, quarter, month, week, day or hour, but found `"fortnight"`
+    ───╯
+    "#);
+
+    assert_snapshot!(compile(r#"
+    from events
+    select {year = date.part unit created}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:30]
+       │
+     3 │     select {year = date.part unit created}
+       │                              ──┬─
+       │                                ╰─── `date.part` expected year, quarter, month, week, day or hour, but found `this.events.unit`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.sqlite
+
+    from events
+    select {month = date.trunc "month" created}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:5:21]
+       │
+     5 │     select {month = date.trunc "month" created}
+       │                     ─────────────┬────────────
+       │                                  ╰────────────── operator std.date.trunc is not supported for dialect sqlite
+    ───╯
+    "#);
+}
+
 #[test]
 fn json_of_test() {
     let pl = prqlc::prql_to_pl("from employees | take 10").unwrap();
@@ -590,17 +958,22 @@ fn test_remove_02() {
 
 #[test]
 fn test_remove_03() {
+    // columns of both relations are known, so the dialect falls back to an
+    // anti-join instead of EXCEPT ALL
     assert_snapshot!(compile(r#"
+    prql target:sql.sqlite
+
     from album
     select {artist_id, title}
     remove (
-        from artist | select artist_id
+        from artist | select {artist_id, name}
     )
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-        artist_id
+        artist_id,
+        name
       FROM
         artist
     )
@@ -610,8 +983,10 @@ fn test_remove_03() {
     FROM
       album
       LEFT JOIN table_0 ON album.artist_id = table_0.artist_id
+      AND album.title = table_0.name
     WHERE
       table_0.artist_id IS NULL
+      AND table_0.name IS NULL
     "
     );
 }
@@ -868,6 +1243,36 @@ fn test_intersect_06() {
     );
 }
 
+#[test]
+fn test_set_operations_arity() {
+    assert_snapshot!(compile(r#"
+    from album
+    select artist_id
+    intersect (from artist | select {artist_id, name})
+    "#).unwrap_err(), @r"
+    Error: cannot compare two relations with non-matching number of columns.
+    ↳ Hint: top has 1 columns, but bottom has 2
+    ");
+
+    assert_snapshot!(compile(r#"
+    from album
+    select {artist_id, title}
+    remove (from artist | select artist_id)
+    "#).unwrap_err(), @r"
+    Error: cannot compare two relations with non-matching number of columns.
+    ↳ Hint: top has 2 columns, but bottom has 1
+    ");
+
+    assert_snapshot!(compile(r#"
+    from album
+    select artist_id
+    append (from artist | select {artist_id, name})
+    "#).unwrap_err(), @r"
+    Error: cannot append two relations with non-matching number of columns.
+    ↳ Hint: top has 1 columns, but bottom has 2
+    ");
+}
+
 #[test]
 fn test_intersect_07() {
     assert_snapshot!(compile(r#"
@@ -1009,6 +1414,71 @@ fn test_quoting_05() {
     "#);
 }
 
+#[test]
+fn test_stdlib_aliases() {
+    let canonical = compile(
+        r#"
+    from employees
+    aggregate {avg_salary = average salary}
+    "#,
+    )
+    .unwrap();
+
+    let avg = compile(
+        r#"
+    from employees
+    aggregate {avg_salary = avg salary}
+    "#,
+    )
+    .unwrap();
+    assert_eq!(avg, canonical);
+
+    let mean = compile(
+        r#"
+    from employees
+    group department (
+      derive {ratio = salary / (salary | std.mean)}
+      aggregate {avg_salary = mean salary, avg_ratio = average ratio}
+    )
+    "#,
+    )
+    .unwrap();
+    assert_snapshot!(mean, @r"
+    WITH table_0 AS (
+      SELECT
+        department,
+        salary,
+        AVG(salary) OVER (PARTITION BY department) AS _expr_0
+      FROM
+        employees
+    )
+    SELECT
+      department,
+      AVG(salary) AS avg_salary,
+      AVG(salary / _expr_0) AS avg_ratio
+    FROM
+      table_0
+    GROUP BY
+      department
+    ");
+
+    // an alias declared in the query isn't renamed
+    assert_snapshot!(compile(
+        r#"
+    let avg = column -> s"MEDIAN({column})"
+
+    from employees
+    aggregate {avg_salary = avg salary}
+    "#,
+    )
+    .unwrap(), @r"
+    SELECT
+      MEDIAN(salary) AS avg_salary
+    FROM
+      employees
+    ");
+}
+
 #[test]
 fn test_sorts_01() {
     assert_snapshot!((compile(r###"
@@ -1033,19 +1503,12 @@ fn test_sorts_01() {
     select {renamed = somefield}
     "#
     ).unwrap()), @r"
-    WITH table_0 AS (
-      SELECT
-        'something' AS renamed,
-        'something' AS _expr_0
-      FROM
-        x
-    )
     SELECT
-      renamed
+      'something' AS renamed
     FROM
-      table_0
+      x
     ORDER BY
-      _expr_0
+      'something'
     ");
 }
 
@@ -1089,6 +1552,7 @@ fn test_sorts_02() {
 fn test_sorts_03() {
     // TODO: this is invalid SQL: a._expr_0 does not exist
     assert_snapshot!((compile(r#"
+    prql target:sql.duckdb
     from a
     join b side:left (==col)
     sort a.col
@@ -1098,19 +1562,19 @@ fn test_sorts_03() {
     ).unwrap()), @r"
     WITH table_0 AS (
       SELECT
-        a.*,
+        a.* EXCLUDE (col),
         b.*,
         a.col AS _expr_0
       FROM
         a
         LEFT JOIN b ON a.col = b.col
       ORDER BY
-        a._expr_0
+        _expr_0
       LIMIT
         5
     )
     SELECT
-      *
+      * EXCLUDE (_expr_0)
     FROM
       table_0
     ORDER BY
@@ -1118,6 +1582,227 @@ fn test_sorts_03() {
     ");
 }
 
+#[test]
+fn test_sort_expressions() {
+    // expressions are computed within ORDER BY, and a negated expression is
+    // sorted in descending order, whatever its type
+    assert_snapshot!(compile(r#"
+    from employees
+    sort {-(salary + bonus), (text.length name)}
+    select {name}
+    "#).unwrap(), @r"
+    SELECT
+      name
+    FROM
+      employees
+    ORDER BY
+      salary + bonus DESC,
+      CHAR_LENGTH(name)
+    ");
+
+    // a column derived by the pipeline is sorted by its name, but within an
+    // expression it is inlined, since some databases don't allow names of
+    // columns of the same SELECT in expressions of ORDER BY
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {total = salary + bonus}
+    sort {-total, total % 10}
+    "#).unwrap(), @r"
+    SELECT
+      *,
+      salary + bonus AS total
+    FROM
+      employees
+    ORDER BY
+      total DESC,
+      (salary + bonus) % 10
+    ");
+
+    // expressions of aggregated columns are sorted by in a SELECT of their own
+    assert_snapshot!(compile(r#"
+    from employees
+    group {department} (aggregate {total = sum salary})
+    sort {-(total / 1000)}
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        department,
+        COALESCE(SUM(salary), 0) AS total
+      FROM
+        employees
+      GROUP BY
+        department
+    )
+    SELECT
+      department,
+      total
+    FROM
+      table_0
+    ORDER BY
+      total / 1000 DESC
+    ");
+}
+
+#[test]
+fn test_sort_before_aggregate() {
+    // a sort before an aggregation does not leak its column into the SELECT,
+    // which would be invalid without a GROUP BY
+    assert_snapshot!(compile(r#"
+    from employees
+    sort age
+    filter salary > 0
+    aggregate {max_salary = max salary}
+    "#).unwrap(), @r"
+    SELECT
+      MAX(salary) AS max_salary
+    FROM
+      employees
+    WHERE
+      salary > 0
+    ");
+
+    // the window function is computed in a CTE, which is then aggregated
+    assert_snapshot!(compile(r#"
+    from employees
+    sort age
+    derive {rn = row_number this}
+    aggregate {max_rn = max rn}
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        ROW_NUMBER() OVER (
+          ORDER BY
+            age
+        ) AS _expr_0,
+        age
+      FROM
+        employees
+    )
+    SELECT
+      MAX(_expr_0) AS max_rn
+    FROM
+      table_0
+    ");
+
+    // take still depends on the sort
+    assert_snapshot!(compile(r#"
+    from employees
+    sort age
+    take 10
+    aggregate {max_salary = max salary}
+    "#).unwrap(), @r"
+    WITH table_1 AS (
+      SELECT
+        salary,
+        age
+      FROM
+        employees
+      ORDER BY
+        age
+      LIMIT
+        10
+    ), table_0 AS (
+      SELECT
+        salary,
+        age
+      FROM
+        table_1
+    )
+    SELECT
+      MAX(salary) AS max_salary
+    FROM
+      table_0
+    ");
+}
+
+#[test]
+fn test_sort_nulls() {
+    assert_snapshot!(compile(r#"
+    prql target:sql.postgres
+
+    from employees
+    sort nulls:last {-salary, name}
+    take 10
+    "#).unwrap(), @r"
+    SELECT
+      *
+    FROM
+      employees
+    ORDER BY
+      salary DESC NULLS LAST,
+      name NULLS LAST
+    LIMIT
+      10
+    ");
+
+    // MySQL has no NULLS FIRST or NULLS LAST, so it is emulated, also within
+    // the window of a grouped take
+    assert_snapshot!(compile(r#"
+    prql target:sql.mysql
+
+    from employees
+    group {department} (
+      sort nulls:first {-salary}
+      take 1
+    )
+    sort nulls:last name
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (
+          PARTITION BY department
+          ORDER BY
+            CASE
+              WHEN salary IS NULL THEN 0
+              ELSE 1
+            END,
+            salary DESC
+        ) AS _expr_0
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      _expr_0 <= 1
+    ORDER BY
+      CASE
+        WHEN name IS NULL THEN 1
+        ELSE 0
+      END,
+      name
+    ");
+
+    // the default leaves nulls where the database places them
+    assert_snapshot!(compile(r#"
+    from employees
+    sort nulls:default {salary}
+    "#).unwrap(), @r"
+    SELECT
+      *
+    FROM
+      employees
+    ORDER BY
+      salary
+    ");
+
+    assert_snapshot!(compile(r#"
+    from employees
+    sort nulls:middle {salary}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:16]
+       │
+     3 │     sort nulls:middle {salary}
+       │                ───┬──
+       │                   ╰──── `nulls` expected first, last or default, but found middle
+    ───╯
+    ");
+}
+
 #[test]
 fn test_numbers() {
     let query = r###"
@@ -1237,89 +1922,254 @@ fn test_not_in_values() {
     FROM
       employees
     WHERE
-      NOT title IN ('Sales Manager', 'Sales Support Agent')
+      title NOT IN ('Sales Manager', 'Sales Support Agent')
     ");
 }
 
 #[test]
-fn test_in_no_values() {
+fn test_in_operator() {
     assert_snapshot!((compile(r#"
     from employees
-    filter (title | in [])
+    filter (country in ["US", "CA", "MX"])
+    filter !(title in ["Sales Manager"])
     "#).unwrap()), @r"
     SELECT
       *
     FROM
       employees
     WHERE
-      false
+      country IN ('US', 'CA', 'MX')
+      AND title NOT IN ('Sales Manager')
     ");
-}
 
-#[test]
-fn test_in_values_err_01() {
-    assert_snapshot!((compile(r###"
+    // an empty list matches no rows, so negating it matches all rows
+    assert_snapshot!((compile(r#"
     from employees
-    derive { ng = ([1, 2] | in [3, 4]) }
-    "###).unwrap_err()), @r"
-    Error:
-       ╭─[:3:29]
-       │
-     3 │     derive { ng = ([1, 2] | in [3, 4]) }
-       │                             ────┬────
-       │                                 ╰────── args to `std.array_in` must be an expression and an array
-    ───╯
+    filter (country in [])
+    derive {is_any = !(country in [])}
+    "#).unwrap()), @r"
+    SELECT
+      *,
+      true AS is_any
+    FROM
+      employees
+    WHERE
+      false
     ");
 }
 
 #[test]
-fn test_interval() {
-    let query = r###"
-    from projects
-    derive first_check_in = start + 10days
-    "###;
-
-    assert_snapshot!((compile(query).unwrap()), @r#"
+fn test_in_relation() {
+    assert_snapshot!((compile(r#"
+    from employees
+    filter (country in (from countries | filter is_active | select code))
+    "#).unwrap()), @r"
     SELECT
-      *,
-      "start" + INTERVAL 10 DAY AS first_check_in
+      *
     FROM
-      projects
-    "#);
+      employees
+    WHERE
+      country IN (
+        SELECT
+          code
+        FROM
+          countries
+        WHERE
+          is_active
+      )
+    ");
 
-    let query = r###"
-    prql target:sql.postgres
+    assert_snapshot!((compile(r#"
+    let closed = (from offices | filter is_closed | select {city})
 
-    from projects
-    derive first_check_in = start + 10days
-    "###;
-    assert_snapshot!((compile(query).unwrap()), @r#"
+    from employees
+    filter !(city in closed)
+    "#).unwrap()), @r"
+    WITH closed AS (
+      SELECT
+        city
+      FROM
+        offices
+      WHERE
+        is_closed
+    )
     SELECT
-      *,
-      "start" + INTERVAL '10 DAY' AS first_check_in
+      *
     FROM
-      projects
-    "#);
-
-    let query = r###"
-    prql target:sql.glaredb
+      employees
+    WHERE
+      city NOT IN (
+        SELECT
+          city
+        FROM
+          closed
+      )
+    ");
 
-    from projects
-    derive first_check_in = start + 10days
-    "###;
-    assert_snapshot!((compile(query).unwrap()), @r#"
-    SELECT
-      *,
-      "start" + INTERVAL '10 DAY' AS first_check_in
-    FROM
-      projects
-    "#);
+    assert_snapshot!((compile(r#"
+    from employees
+    filter (country in (from countries))
+    "#).unwrap_err()), @r"
+    Error:
+       ╭─[:3:25]
+       │
+     3 │     filter (country in (from countries))
+       │                         ───────┬──────
+       │                                ╰──────── the relation on the right side of `in` must have exactly one column
+       │
+       │ Help: select the column, as in `(from t | select {id})`
+    ───╯
+    ");
 }
 
 #[test]
-fn test_dates() {
-    assert_snapshot!((compile(r###"
-    from to_do_empty_table
+fn test_derive_destructure() {
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {
+      {min_salary, max_salary} = (from employees | aggregate {min salary, max salary}),
+      bonus = salary * 0.1,
+    }
+    select {name, bonus, min_salary, max_salary}
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        MIN(salary) AS min_salary,
+        MAX(salary) AS max_salary
+      FROM
+        employees
+    )
+    SELECT
+      employees.name,
+      employees.salary * 0.1 AS bonus,
+      table_0.min_salary,
+      table_0.max_salary
+    FROM
+      employees
+      CROSS JOIN table_0
+    ");
+
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {lo, hi} = (from salaries | aggregate {min amount})
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:12]
+       │
+     3 │     derive {lo, hi} = (from salaries | aggregate {min amount})
+       │            ─────────────────────────┬─────────────────────────
+       │                                     ╰─────────────────────────── expected a relation with 2 columns, but found one with 1
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {{lo} = (from salaries)}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:13]
+       │
+     3 │     derive {{lo} = (from salaries)}
+       │             ───────────┬──────────
+       │                        ╰──────────── cannot destructure a relation with unknown columns
+       │
+       │ Help: select the columns, as in `(from t | select {a, b})`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    from employees
+    select {{lo} = (from salaries | select amount)}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:13]
+       │
+     3 │     select {{lo} = (from salaries | select amount)}
+       │             ───────────────────┬──────────────────
+       │                                ╰──────────────────── columns can only be destructured within `derive`
+    ───╯
+    ");
+}
+
+#[test]
+fn test_in_no_values() {
+    assert_snapshot!((compile(r#"
+    from employees
+    filter (title | in [])
+    "#).unwrap()), @r"
+    SELECT
+      *
+    FROM
+      employees
+    WHERE
+      false
+    ");
+}
+
+#[test]
+fn test_in_values_err_01() {
+    assert_snapshot!((compile(r###"
+    from employees
+    derive { ng = ([1, 2] | in [3, 4]) }
+    "###).unwrap_err()), @r"
+    Error:
+       ╭─[:3:29]
+       │
+     3 │     derive { ng = ([1, 2] | in [3, 4]) }
+       │                             ────┬────
+       │                                 ╰────── args to `std.array_in` must be an expression and an array
+    ───╯
+    ");
+}
+
+#[test]
+fn test_interval() {
+    let query = r###"
+    from projects
+    derive first_check_in = start + 10days
+    "###;
+
+    assert_snapshot!((compile(query).unwrap()), @r#"
+    SELECT
+      *,
+      "start" + INTERVAL 10 DAY AS first_check_in
+    FROM
+      projects
+    "#);
+
+    let query = r###"
+    prql target:sql.postgres
+
+    from projects
+    derive first_check_in = start + 10days
+    "###;
+    assert_snapshot!((compile(query).unwrap()), @r#"
+    SELECT
+      *,
+      "start" + INTERVAL '10 DAY' AS first_check_in
+    FROM
+      projects
+    "#);
+
+    let query = r###"
+    prql target:sql.glaredb
+
+    from projects
+    derive first_check_in = start + 10days
+    "###;
+    assert_snapshot!((compile(query).unwrap()), @r#"
+    SELECT
+      *,
+      "start" + INTERVAL '10 DAY' AS first_check_in
+    FROM
+      projects
+    "#);
+}
+
+#[test]
+fn test_dates() {
+    assert_snapshot!((compile(r###"
+    from to_do_empty_table
     derive {
         date = @2011-02-01,
         timestamp = @2011-02-01T10:00,
@@ -1347,7 +2197,7 @@ fn test_window_functions_00() {
     "###).unwrap()), @r"
     SELECT
       *,
-      COUNT(*) OVER (PARTITION BY last_name)
+      COUNT(first_name) OVER (PARTITION BY last_name)
     FROM
       employees
     ");
@@ -1385,7 +2235,7 @@ fn test_window_functions_02() {
         TO_CHAR(co.order_date, '%Y-%m') AS order_month,
         TO_CHAR(co.order_date, '%Y-%m-%d') AS order_day,
         COUNT(DISTINCT co.order_id) AS num_orders,
-        COUNT(*) AS num_books,
+        COUNT(ol.book_id) AS num_books,
         COALESCE(SUM(ol.price), 0) AS total_price
       FROM
         cust_order AS co
@@ -1597,6 +2447,130 @@ fn test_window_functions_11() {
     ");
 }
 
+#[test]
+fn test_window_functions_row_number_bare() {
+    // bare `row_number` takes the ordering of the pipeline
+    assert_snapshot!((compile(r###"
+    from employees
+    group department (
+      sort age
+      derive {num = row_number}
+    )
+    "###).unwrap()), @r"
+    SELECT
+      *,
+      ROW_NUMBER() OVER (
+        PARTITION BY department
+        ORDER BY
+          age
+      ) AS num
+    FROM
+      employees
+    ");
+
+    assert_snapshot!((compile(r###"
+    from employees
+    derive {num = row_number}
+    "###).unwrap_err()), @r"
+    Error:
+       ╭─[:3:19]
+       │
+     3 │     derive {num = row_number}
+       │                   ─────┬────
+       │                        ╰────── `row_number` without an argument requires an ordering
+       │
+       │ Help: add a `sort` before this transform, or use `row_number this` for an arbitrary order
+    ───╯
+    ");
+}
+
+#[test]
+fn test_window_functions_args() {
+    // partition and ordering are taken from the enclosing transforms
+    assert_snapshot!((compile(r###"
+    from orders
+    group customer_id (
+      sort day
+      window rolling:3 (
+        derive {
+          r = rank amount,
+          prev = lag 1 amount,
+          next = lead 1 amount default:0,
+        }
+      )
+    )
+    "###).unwrap()), @r"
+    SELECT
+      *,
+      RANK() OVER window_0 AS r,
+      LAG(amount, 1) OVER window_0 AS prev,
+      LEAD(amount, 1, 0) OVER window_0 AS next
+    FROM
+      orders WINDOW window_0 AS (
+        PARTITION BY customer_id
+        ORDER BY
+          day
+      )
+    ");
+
+    // ... unless they are given with `by` and `sort`
+    assert_snapshot!((compile(r###"
+    from orders
+    derive {
+      r = dense_rank amount sort:{-amount},
+      prev = lag 1 amount by:customer_id sort:day,
+      next = lead 1 amount default:0 sort:day,
+      change = amount - (lag 1 amount sort:day),
+    }
+    "###).unwrap()), @r"
+    SELECT
+      *,
+      DENSE_RANK() OVER (
+        ORDER BY
+          amount DESC
+      ) AS r,
+      LAG(amount, 1) OVER (
+        PARTITION BY customer_id
+        ORDER BY
+          day
+      ) AS prev,
+      LEAD(amount, 1, 0) OVER (
+        ORDER BY
+          day
+      ) AS next,
+      amount - LAG(amount, 1) OVER (
+        ORDER BY
+          day
+      ) AS change
+    FROM
+      orders
+    ");
+
+    // `by` and `sort` take precedence over the enclosing transforms
+    assert_snapshot!((compile(r###"
+    from orders
+    group customer_id (
+      sort day
+      derive {prev = lag 1 amount sort:id, next = lead 1 amount default:amount}
+    )
+    "###).unwrap()), @r"
+    SELECT
+      *,
+      LAG(amount, 1) OVER (
+        PARTITION BY customer_id
+        ORDER BY
+          id
+      ) AS prev,
+      LEAD(amount, 1, amount) OVER (
+        PARTITION BY customer_id
+        ORDER BY
+          day
+      ) AS next
+    FROM
+      orders
+    ");
+}
+
 #[test]
 fn test_window_functions_12() {
     // window params need to be simple expressions
@@ -1680,70 +2654,304 @@ fn test_window_functions_13() {
 }
 
 #[test]
-fn test_window_single_item_range() {
-    assert_snapshot!(compile(r###"
-      from login_event
-      window rows:1..1 (
-        sort time_upload
-        derive {
-            last_user = min user_id
-        }
-      )
-    "###).unwrap(), @r"
+fn test_window_frame_bounds() {
+    // `0` is the current row, a missing end is unbounded
+    assert_snapshot!((compile(r###"
+    from tracks
+    sort milliseconds
+    window rows:-2..0 (derive {a = sum bytes})
+    window rows:.. (derive {b = sum bytes})
+    window rows:1.. (derive {c = sum bytes})
+    window range:..-3 (derive {d = sum bytes})
+    "###).unwrap()), @r"
     SELECT
       *,
-      MIN(user_id) OVER (
+      SUM(bytes) OVER (
         ORDER BY
-          time_upload ROWS BETWEEN 1 FOLLOWING AND 1 FOLLOWING
-      ) AS last_user
+          milliseconds ROWS BETWEEN 2 PRECEDING AND CURRENT ROW
+      ) AS a,
+      SUM(bytes) OVER (
+        ORDER BY
+          milliseconds ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING
+      ) AS b,
+      SUM(bytes) OVER (
+        ORDER BY
+          milliseconds ROWS BETWEEN 1 FOLLOWING AND UNBOUNDED FOLLOWING
+      ) AS c,
+      SUM(bytes) OVER (
+        ORDER BY
+          milliseconds RANGE BETWEEN UNBOUNDED PRECEDING AND 3 PRECEDING
+      ) AS d
     FROM
-      login_event
+      tracks
     ORDER BY
-      time_upload
-    ");
+      milliseconds
+    "
+    );
 }
 
 #[test]
-fn test_name_resolving() {
-    let query = r###"
-    from numbers
-    derive x = 5
-    select {y = 6, z = x + y + a}
-    "###;
-    assert_snapshot!((compile(query).unwrap()), @r"
+fn test_merge_filters() {
+    assert_snapshot!(compile(r#"
+    from employees
+    filter age > 30
+    filter country == "NZ"
+    filter salary < 10000
+    "#).unwrap(), @r"
     SELECT
-      6 AS y,
-      5 + 6 + a AS z
+      *
     FROM
-      numbers
+      employees
+    WHERE
+      age > 30
+      AND country = 'NZ'
+      AND salary < 10000
     ");
-}
 
-#[test]
-fn test_strings() {
-    let query = r#"
-    from empty_table_to_do
-    select {
-        x = "two households'",
-        y = 'two households"',
-        z = f"a {x} b' {y} c",
-        v = f'a {x} b" {y} c',
-    }
-    "#;
-    assert_snapshot!((compile(query).unwrap()), @r#"
+    // filters after an aggregation are not merged with those before it
+    assert_snapshot!(compile(r#"
+    from employees
+    filter age > 30
+    filter country == "NZ"
+    group {department} (
+      aggregate {total = sum salary}
+    )
+    filter total > 100000
+    filter department != "sales"
+    "#).unwrap(), @r"
     SELECT
-      'two households''' AS x,
-      'two households"' AS y,
-      CONCAT(
-        'a ',
-        'two households''',
-        ' b'' ',
-        'two households"',
-        ' c'
-      ) AS z,
-      CONCAT(
-        'a ',
-        'two households''',
+      department,
+      COALESCE(SUM(salary), 0) AS total
+    FROM
+      employees
+    WHERE
+      age > 30
+      AND country = 'NZ'
+    GROUP BY
+      department
+    HAVING
+      COALESCE(SUM(salary), 0) > 100000
+      AND department <> 'sales'
+    ");
+
+    // neither are filters on either side of a take
+    assert_snapshot!(compile(r#"
+    from employees
+    sort age
+    filter age > 30
+    take 10
+    filter country == "NZ"
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        employees
+      WHERE
+        age > 30
+      ORDER BY
+        age
+      LIMIT
+        10
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      country = 'NZ'
+    ORDER BY
+      age
+    ");
+}
+
+#[test]
+fn test_constant_filters() {
+    // a filter that is always true is removed
+    assert_snapshot!(compile(r#"
+    from employees
+    filter (1 == 1)
+    filter country == "NZ" || true
+    select {name}
+    "#).unwrap(), @r"
+    SELECT
+      name
+    FROM
+      employees
+    ");
+
+    // one that is never true removes the filters, sorts and takes before it
+    assert_snapshot!(compile(r#"
+    from employees
+    filter age > 30
+    sort age
+    take 10
+    filter (country == "NZ" && false)
+    select {name}
+    "#).unwrap(), @r"
+    SELECT
+      name
+    FROM
+      employees
+    WHERE
+      1 = 0
+    ");
+
+    // an aggregation of no rows still has a row
+    assert_snapshot!(compile(r#"
+    from employees
+    filter false
+    aggregate {n = count this}
+    "#).unwrap(), @r"
+    SELECT
+      COUNT(*) AS n
+    FROM
+      employees
+    WHERE
+      1 = 0
+    ");
+}
+
+#[test]
+fn test_qualify() {
+    // a filter on a window function compiles to QUALIFY on Snowflake
+    assert_snapshot!(compile(r#"
+    prql target:sql.snowflake
+
+    from employees
+    derive {rn = row_number this}
+    filter rn == 1
+    "#).unwrap(), @r"
+    SELECT
+      *,
+      ROW_NUMBER() OVER () AS rn
+    FROM
+      employees QUALIFY ROW_NUMBER() OVER () = 1
+    ");
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.snowflake
+
+    from employees
+    group {department} (
+      sort {-salary}
+      take 3
+    )
+    "#).unwrap(), @r"
+    SELECT
+      *
+    FROM
+      employees QUALIFY ROW_NUMBER() OVER (
+        PARTITION BY department
+        ORDER BY
+          salary DESC
+      ) <= 3
+    ");
+
+    // a filter directly before the filter on the window function is merged into it
+    assert_snapshot!(compile(r#"
+    prql target:sql.snowflake
+
+    from employees
+    derive {rn = row_number this}
+    filter salary > 1000
+    filter rn == 1
+    "#).unwrap(), @r"
+    SELECT
+      *,
+      ROW_NUMBER() OVER () AS rn
+    FROM
+      employees QUALIFY salary > 1000
+      AND ROW_NUMBER() OVER () = 1
+    ");
+
+    // other dialects use a subquery
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {rn = row_number this}
+    filter rn == 1
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER () AS rn
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      rn = 1
+    ");
+}
+
+#[test]
+fn test_window_single_item_range() {
+    assert_snapshot!(compile(r###"
+      from login_event
+      window rows:1..1 (
+        sort time_upload
+        derive {
+            last_user = min user_id
+        }
+      )
+    "###).unwrap(), @r"
+    SELECT
+      *,
+      MIN(user_id) OVER (
+        ORDER BY
+          time_upload ROWS BETWEEN 1 FOLLOWING AND 1 FOLLOWING
+      ) AS last_user
+    FROM
+      login_event
+    ORDER BY
+      time_upload
+    ");
+}
+
+#[test]
+fn test_name_resolving() {
+    let query = r###"
+    from numbers
+    derive x = 5
+    select {y = 6, z = x + y + a}
+    "###;
+    assert_snapshot!((compile(query).unwrap()), @r"
+    SELECT
+      6 AS y,
+      5 + 6 + a AS z
+    FROM
+      numbers
+    ");
+}
+
+#[test]
+fn test_strings() {
+    let query = r#"
+    from empty_table_to_do
+    select {
+        x = "two households'",
+        y = 'two households"',
+        z = f"a {x} b' {y} c",
+        v = f'a {x} b" {y} c',
+    }
+    "#;
+    assert_snapshot!((compile(query).unwrap()), @r#"
+    SELECT
+      'two households''' AS x,
+      'two households"' AS y,
+      CONCAT(
+        'a ',
+        'two households''',
+        ' b'' ',
+        'two households"',
+        ' c'
+      ) AS z,
+      CONCAT(
+        'a ',
+        'two households''',
         ' b" ',
         'two households"',
         ' c'
@@ -1813,7 +3021,7 @@ fn test_nulls_02() {
     "###).unwrap()), @r"
     SELECT
       *,
-      COALESCE(amount + 2, 3 * 5) AS amount
+      COALESCE(amount + 2, 15) AS amount
     FROM
       employees
     ");
@@ -1956,6 +3164,65 @@ fn test_take_06() {
     ");
 }
 
+#[test]
+fn test_take_sqlite() {
+    assert_snapshot!((compile(r###"
+    prql target:sql.sqlite
+
+    from employees
+    take 5..
+    "###).unwrap()), @r"
+    SELECT
+      *
+    FROM
+      employees
+    LIMIT
+      -1 OFFSET 4
+    ");
+
+    assert_snapshot!((compile(r###"
+    prql target:sql.sqlite
+
+    from employees
+    take 11..20
+    "###).unwrap()), @r"
+    SELECT
+      *
+    FROM
+      employees
+    LIMIT
+      10 OFFSET 10
+    ");
+
+    assert_snapshot!((compile(r###"
+    prql target:sql.sqlite
+
+    from employees
+    take 1..1
+    "###).unwrap()), @r"
+    SELECT
+      *
+    FROM
+      employees
+    LIMIT
+      1
+    ");
+
+    assert_snapshot!((compile(r###"
+    prql target:sql.sqlite
+
+    from employees
+    take ..10
+    "###).unwrap()), @r"
+    SELECT
+      *
+    FROM
+      employees
+    LIMIT
+      10
+    ");
+}
+
 #[test]
 fn test_take_07() {
     assert_snapshot!((compile(r###"
@@ -1983,7 +3250,9 @@ fn test_take_08() {
        │
      3 │     take (-1..)
        │     ─────┬─────
-       │          ╰─────── take expected a positive int range, but found -1..
+       │          ╰─────── `take` of the last rows requires the rows to be sorted
+       │
+       │ Help: add a `sort` before the `take`, as in `sort id | take (-5)..`
     ───╯
     ");
 }
@@ -2048,6 +3317,18 @@ fn test_take_mssql() {
     from tracks
     take ..5
     "#).unwrap()), @r"
+    SELECT
+      TOP 5 *
+    FROM
+      tracks
+    ");
+
+    assert_snapshot!((compile(r#"
+    prql target:sql.mssql
+
+    from tracks
+    take 3..
+    "#).unwrap()), @r"
     SELECT
       *
     FROM
@@ -2056,21 +3337,210 @@ fn test_take_mssql() {
       (
         SELECT
           NULL
-      ) OFFSET 0 ROWS
-    FETCH FIRST
-      5 ROWS ONLY
+      ) OFFSET 2 ROWS
     ");
 
+    // the ORDER BY of a sort is used by both TOP and OFFSET
     assert_snapshot!((compile(r#"
     prql target:sql.mssql
 
     from tracks
-    take 3..
+    sort {-plays}
+    take 10
     "#).unwrap()), @r"
     SELECT
-      *
+      TOP 10 *
     FROM
-      tracks OFFSET 2 ROWS
+      tracks
+    ORDER BY
+      plays DESC
+    ");
+
+    assert_snapshot!((compile(r#"
+    prql target:sql.mssql
+
+    from tracks
+    sort {-plays}
+    take 11..20
+    "#).unwrap()), @r"
+    SELECT
+      *
+    FROM
+      tracks
+    ORDER BY
+      plays DESC OFFSET 10 ROWS
+    FETCH FIRST
+      10 ROWS ONLY
+    ");
+}
+
+#[test]
+fn test_take_last() {
+    // the last rows are the first rows of the reversed sort, which is then
+    // sorted back
+    assert_snapshot!((compile(r###"
+    from invoices
+    sort {-total, invoice_date}
+    take (-5)..
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        invoices
+      ORDER BY
+        total,
+        invoice_date DESC
+      LIMIT
+        5
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    ORDER BY
+      total DESC,
+      invoice_date
+    ");
+
+    assert_snapshot!((compile(r###"
+    from invoices
+    sort invoice_date
+    take (-10..-6)
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        invoices
+      ORDER BY
+        invoice_date DESC
+      LIMIT
+        5 OFFSET 5
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    ORDER BY
+      invoice_date
+    ");
+
+    // within a group, it is numbered in the reversed order
+    assert_snapshot!((compile(r###"
+    from invoices
+    group customer_id (
+      sort invoice_date
+      take (-2)..
+    )
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (
+          PARTITION BY customer_id
+          ORDER BY
+            invoice_date DESC
+        ) AS _expr_0
+      FROM
+        invoices
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      _expr_0 <= 2
+    ");
+}
+
+#[test]
+fn test_booleans_mssql() {
+    // conditions compare boolean columns with 1
+    assert_snapshot!((compile(r#"
+    prql target:sql.mssql
+
+    from tracks
+    filter (is_explicit || !is_published)
+    join albums (tracks.album_id == albums.id && albums.is_live)
+    "#).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        tracks
+      WHERE
+        is_explicit = 1
+        OR NOT is_published = 1
+    )
+    SELECT
+      table_0.*,
+      albums.*
+    FROM
+      table_0
+      JOIN albums ON table_0.album_id = albums.id
+      AND albums.is_live = 1
+    ");
+
+    // comparisons that are selected are converted to 1 or 0
+    assert_snapshot!((compile(r#"
+    prql target:sql.mssql
+
+    from tracks
+    derive {
+      is_long = milliseconds > 300000,
+      is_new = true,
+      is_explicit,
+    }
+    filter is_long
+    "#).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        CASE
+          WHEN milliseconds > 300000 THEN 1
+          ELSE 0
+        END AS is_long,
+        1 AS is_new
+      FROM
+        tracks
+    )
+    SELECT
+      *,
+      is_explicit
+    FROM
+      table_0
+    WHERE
+      is_long = 1
+    ");
+
+    assert_snapshot!((compile(r#"
+    prql target:sql.mssql
+
+    from tracks
+    group {album_id} (
+      aggregate {
+        all_published = all is_published,
+        any_long = any (milliseconds > 300000),
+      }
+    )
+    "#).unwrap()), @r"
+    SELECT
+      album_id,
+      COALESCE(MIN(is_published), 1) AS all_published,
+      COALESCE(
+        MAX(
+          CASE
+            WHEN milliseconds > 300000 THEN 1
+            ELSE 0
+          END
+        ),
+        0
+      ) AS any_long
+    FROM
+      tracks
+    GROUP BY
+      album_id
     ");
 }
 
@@ -2098,6 +3568,56 @@ fn test_distinct_01() {
     ");
 }
 
+#[test]
+fn test_filter_window_rank() {
+    // filtering on a window function moves it into a CTE
+    assert_snapshot!((compile(r###"
+    from employees
+    group department (
+      sort {-salary}
+      derive {r = rank salary}
+    )
+    filter r <= 3
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        RANK() OVER (
+          PARTITION BY department
+          ORDER BY
+            salary DESC
+        ) AS r
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      r <= 3
+    ");
+
+    // ... but without an ordering, all rows would rank equally
+    assert_snapshot!((compile(r###"
+    from employees
+    group department (
+      derive {r = rank salary}
+    )
+    filter r <= 3
+    "###).unwrap_err()), @r"
+    Error:
+       ╭─[:6:12]
+       │
+     6 │     filter r <= 3
+       │            ───┬──
+       │               ╰──── filter depends on `rank` without an ordering, so it keeps either all rows or none
+       │
+       │ Help: add a `sort` before the transform that computes `rank`
+    ───╯
+    ");
+}
+
 #[test]
 fn test_distinct_02() {
     // basic distinct
@@ -2263,6 +3783,58 @@ fn test_distinct_09() {
     ");
 }
 
+#[test]
+fn test_distinct_10() {
+    // top rows per group of several columns
+    assert_snapshot!((compile(r###"
+    from employees
+    group {department, title} (sort {-salary} | take 3)
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (
+          PARTITION BY department,
+          title
+          ORDER BY
+            salary DESC
+        ) AS _expr_0
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      _expr_0 <= 3
+    ");
+
+    assert_snapshot!((compile(r###"
+    from employees
+    group {department, title} (sort {-salary} | take 2..4)
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (
+          PARTITION BY department,
+          title
+          ORDER BY
+            salary DESC
+        ) AS _expr_0
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      _expr_0 BETWEEN 2 AND 4
+    ");
+}
+
 #[test]
 fn test_distinct_on_01() {
     assert_snapshot!((compile(r###"
@@ -2351,6 +3923,71 @@ fn test_distinct_on_04() {
     ");
 }
 
+#[test]
+fn test_unique() {
+    assert_snapshot!(compile(r#"
+    from employees
+    select {department, title}
+    unique
+    "#).unwrap(), @r"
+    SELECT
+      DISTINCT department,
+      title
+    FROM
+      employees
+    ");
+
+    assert_snapshot!(compile(r#"
+    from orders
+    unique by:{customer_id} sort:{-ordered_at}
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (
+          PARTITION BY customer_id
+          ORDER BY
+            ordered_at DESC
+        ) AS _expr_0
+      FROM
+        orders
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      _expr_0 <= 1
+    ");
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.postgres
+
+    from orders
+    unique by:{customer_id} sort:{-ordered_at}
+    "#).unwrap(), @r"
+    SELECT
+      DISTINCT ON (customer_id) *
+    FROM
+      orders
+    ORDER BY
+      customer_id,
+      ordered_at DESC
+    ");
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.postgres
+
+    from orders
+    unique by:customer_id
+    "#).unwrap(), @r"
+    SELECT
+      DISTINCT ON (customer_id) *
+    FROM
+      orders
+    ");
+}
+
 #[test]
 fn test_group_take_n_01() {
     assert_snapshot!((compile(r###"
@@ -2414,1292 +4051,4022 @@ fn test_group_take_n_02() {
 }
 
 #[test]
-fn test_join() {
+fn test_group_take_n_clickhouse() {
+    // LIMIT n BY takes the first rows of each group, without a window function
     assert_snapshot!((compile(r###"
-    from x
-    join y (==id)
-    "###).unwrap()), @r"
-    SELECT
-      x.*,
-      y.*
+    prql target:sql.clickhouse
+
+    from employees
+    group department (
+      sort {-salary}
+      take 2..3
+    )
+    select {name, salary}
+    "###).unwrap()), @r"
+    SELECT
+      name,
+      salary
     FROM
-      x
-      JOIN y ON x.id = y.id
+      employees
+    ORDER BY
+      salary DESC
+    LIMIT
+      2 OFFSET 1 BY department
     ");
 
-    compile("from x | join y {==x.id}").unwrap_err();
-}
-
-#[test]
-fn test_join_side_literal() {
+    // a later take needs a LIMIT of its own
     assert_snapshot!((compile(r###"
-    let my_side = "right"
+    prql target:sql.clickhouse
 
-    from x
-    join y (==id) side:my_side
+    from employees
+    group department (
+      sort age
+      take 2
+    )
+    take 10
     "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        employees
+      ORDER BY
+        age
+      LIMIT
+        2 BY department
+    )
     SELECT
-      x.*,
-      y.*
+      *
     FROM
-      x
-      RIGHT JOIN y ON x.id = y.id
+      table_0
+    ORDER BY
+      age
+    LIMIT
+      10
     ");
-}
 
-#[test]
-fn test_join_side_literal_err() {
+    // an open range is still filtered by ROW_NUMBER()
     assert_snapshot!((compile(r###"
-    let my_side = 42
+    prql target:sql.clickhouse
 
-    from x
-    join y (==id) side:my_side
-    "###).unwrap_err()), @r"
-    Error:
-       ╭─[:5:24]
-       │
-     5 │     join y (==id) side:my_side
-       │                        ───┬───
-       │                           ╰───── `side` expected inner, left, right or full, but found 42
-    ───╯
+    from employees
+    group department (
+      sort age
+      take 2..
+    )
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (
+          PARTITION BY department
+          ORDER BY
+            age
+        ) AS _expr_0
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      _expr_0 >= 2
     ");
 }
 
 #[test]
-fn test_join_side_literal_via_func() {
+fn test_group_aggregate_sort_take() {
+    // top groups: the limit applies to the aggregated result
     assert_snapshot!((compile(r###"
-    let my_join = func m <relation> c <scalar> s <text>:"right" tbl <relation> -> (
-        join side:_param.s m (c == that.k) tbl
+    from orders
+    group {category} (
+      aggregate {total = sum amount}
     )
-
-    from x
-    my_join default_db.y this.id s:"left"
+    sort {-total}
+    take 5
     "###).unwrap()), @r"
     SELECT
-      x.*,
-      y.*
+      category,
+      COALESCE(SUM(amount), 0) AS total
     FROM
-      x
-      LEFT JOIN y ON x.id = y.k
+      orders
+    GROUP BY
+      category
+    ORDER BY
+      total DESC
+    LIMIT
+      5
     ");
-}
 
-#[test]
-fn test_join_side_literal_via_func_err() {
+    // a take before the group must still be applied before aggregating
     assert_snapshot!((compile(r###"
-    let my_join = func m <relation> c <scalar> s <text>:"right" tbl <relation> -> (
-        join side:_param.s m (c == that.k) tbl
+    from orders
+    take 100
+    group {category} (
+      aggregate {total = sum amount}
     )
-
-    from x
-    my_join default_db.y this.id s:"four"
-    "###).unwrap_err()), @r#"
-    Error:
-       ╭─[:3:25]
-       │
-     3 │         join side:_param.s m (c == that.k) tbl
-       │                         ─┬
-       │                          ╰── `side` expected inner, left, right or full, but found "four"
-    ───╯
-    "#);
+    sort {-total}
+    take 5
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        category,
+        amount
+      FROM
+        orders
+      LIMIT
+        100
+    )
+    SELECT
+      category,
+      COALESCE(SUM(amount), 0) AS total
+    FROM
+      table_0
+    GROUP BY
+      category
+    ORDER BY
+      total DESC
+    LIMIT
+      5
+    ");
 }
 
 #[test]
-fn test_from_json() {
-    // Test that the SQL generated from the JSON of the PRQL is the same as the raw PRQL
-    let original_prql = r#"
-    from e=employees
-    join salaries (==emp_no)
-    group {e.emp_no, e.gender} (
-      aggregate {
-        emp_salary = average salaries.salary
-      }
-    )
-    join de=dept_emp (==emp_no)
-    join dm=dept_manager (
-      (dm.dept_no == de.dept_no) && s"(de.from_date, de.to_date) OVERLAPS (dm.from_date, dm.to_date)"
+fn test_unnest() {
+    let query = r#"
+    from events
+    select {id, tags}
+    unnest {tag = tags}
+    filter tag != null
+    "#;
+
+    assert_snapshot!(compile(query).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        id,
+        tags,
+        UNNEST(tags) AS tag
+      FROM
+        events
     )
-    group {dm.emp_no, gender} (
-      aggregate {
-        salary_avg = average emp_salary,
-        salary_sd = stddev emp_salary
-      }
+    SELECT
+      id,
+      tags,
+      tag
+    FROM
+      table_0
+    WHERE
+      tag IS NOT NULL
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::ClickHouse).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        id,
+        tags,
+        arrayJoin(tags) AS tag
+      FROM
+        events
     )
-    derive mng_no = emp_no
-    join managers=employees (==emp_no)
-    derive mng_name = s"managers.first_name || ' ' || managers.last_name"
-    select {mng_name, managers.gender, salary_avg, salary_sd}
-    "#;
+    SELECT
+      id,
+      tags,
+      tag
+    FROM
+      table_0
+    WHERE
+      tag IS NOT NULL
+    ");
 
-    let source_tree = SourceTree::from(original_prql);
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::BigQuery).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        events.id,
+        events.tags,
+        table_1 AS tag
+      FROM
+        events,
+        UNNEST(events.tags) AS table_1
+    )
+    SELECT
+      id,
+      tags,
+      tag
+    FROM
+      table_0
+    WHERE
+      tag IS NOT NULL
+    ");
 
-    let sql_from_prql = Ok(prqlc::prql_to_pl_tree(&source_tree).unwrap())
-        .and_then(|ast| prqlc::semantic::resolve_and_lower(ast, &[], None))
-        .and_then(|rq| sql::compile(rq, &Options::default()))
-        .unwrap();
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::Snowflake).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        events.id,
+        events.tags,
+        table_1.value AS tag
+      FROM
+        events,
+        LATERAL FLATTEN(events.tags) AS table_1
+    )
+    SELECT
+      id,
+      tags,
+      tag
+    FROM
+      table_0
+    WHERE
+      tag IS NOT NULL
+    ");
 
-    let sql_from_json = prqlc::prql_to_pl(original_prql)
-        .map(|x| prqlc::json::from_pl(&x).unwrap())
-        .map(|json| prqlc::json::to_pl(&json).unwrap())
-        .and_then(prqlc::pl_to_rq)
-        .and_then(|rq| prqlc::rq_to_sql(rq, &Options::default()))
-        .unwrap();
+    // without a name, the elements are named after the array column
+    assert_snapshot!(compile(r#"
+    from events
+    unnest tags
+    "#).unwrap(), @r"
+    SELECT
+      *,
+      UNNEST(tags) AS tags
+    FROM
+      events
+    ");
 
-    assert_eq!(sql_from_prql, sql_from_json);
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::MySql).unwrap_err(), @r"
+    Error:
+       ╭─[:4:19]
+       │
+     4 │     unnest {tag = tags}
+       │                   ──┬─
+       │                     ╰─── operator std.array.unnest is not supported for dialect mysql
+    ───╯
+    ");
 }
 
 #[test]
-fn test_f_string() {
+fn test_struct_field() {
     let query = r#"
-    from employees
-    derive age = year_born - s'now()'
-    select {
-        f"Hello my name is {prefix}{first_name} {last_name}",
-        f"and I am {age} years old."
-    }
+    from c = customers
+    derive {city = address.city, lat = c.address.geo.lat}
+    filter lat > 0
     "#;
 
-    assert_snapshot!(
-      compile(query).unwrap(),
-        @r"
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::BigQuery).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        address.city AS city,
+        address.geo.lat AS lat
+      FROM
+        customers AS c
+    )
     SELECT
-      CONCAT(
-        'Hello my name is ',
-        prefix,
-        first_name,
-        ' ',
-        last_name
-      ),
-      CONCAT('and I am ', year_born - now(), ' years old.')
+      *
     FROM
-      employees
-    "
-    );
+      table_0
+    WHERE
+      lat > 0
+    ");
 
-    assert_snapshot!(
-        prqlc::compile(
-          query,
-          &Options::default()
-              .no_signature()
-              .with_target(Target::Sql(Some(sql::Dialect::SQLite)))
-
-      ).unwrap(),
-          @r"
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::Postgres).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        (address).city AS city,
+        (address).geo.lat AS lat
+      FROM
+        customers AS c
+    )
     SELECT
-      'Hello my name is ' || prefix || first_name || ' ' || last_name,
-      'and I am ' || year_born - now() || ' years old.'
+      *
     FROM
-      employees
-    "
-    );
-}
-
-#[test]
-fn test_sql_of_ast_1() {
-    let query = r#"
-    from employees
-    filter country == "USA"
-    group {title, country} (
-        aggregate {average salary}
-    )
-    sort title
-    take 20
-    "#;
+      table_0
+    WHERE
+      lat > 0
+    ");
 
-    let sql = compile(query).unwrap();
-    assert_snapshot!(sql,
-        @r"
+    // a column of a relation in scope takes precedence over a field
+    assert_snapshot!(compile(r#"
+    from address
+    join customers (==id)
+    select {address.city}
+    "#).unwrap(), @r"
     SELECT
-      title,
-      country,
-      AVG(salary)
+      address.city
     FROM
-      employees
-    WHERE
-      country = 'USA'
-    GROUP BY
-      title,
-      country
-    ORDER BY
-      title
-    LIMIT
-      20
-    "
-    );
+      address
+      JOIN customers ON address.id = customers.id
+    ");
+
+    assert_snapshot!(compile(r#"
+    from orders
+    join customers (==customer_id)
+    select {address.city}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:4:13]
+       │
+     4 │     select {address.city}
+       │             ──────┬─────
+       │                   ╰─────── Ambiguous name
+       │
+       │ Help: could be any of: customers.address, orders.address
+    ───╯
+    ");
 }
 
 #[test]
-fn test_sql_of_ast_02() {
+fn test_comparison_chain() {
     assert_snapshot!(compile(r#"
-    from employees
-    aggregate sum_salary = s"sum({salary})"
-    filter sum_salary > 100
+    from students
+    filter (0 < score <= 100)
+    filter 1 <= grade + 1 < level < 10
+    derive {is_passing = (score >= 60) == is_enrolled}
     "#).unwrap(), @r"
     SELECT
-      sum(salary) AS sum_salary
+      *,
+      (score >= 60) = is_enrolled AS is_passing
     FROM
-      employees
-    HAVING
-      sum(salary) > 100
+      students
+    WHERE
+      0 < score
+      AND score <= 100
+      AND 1 <= grade + 1
+      AND grade + 1 < level
+      AND level < 10
     ");
 }
 
 #[test]
-fn test_bare_s_string() {
+fn test_json_get() {
     let query = r#"
-    let grouping = s"""
-        SELECT SUM(a)
-        FROM tbl
-        GROUP BY
-          GROUPING SETS
-          ((b, c, d), (d), (b, d))
-      """
-    from grouping
+    from customers
+    select {
+      city = json.get "$.address.city" profile,
+      city_text = json.get_text "$.address.city" profile,
+      first_tag = json.get_text "$.tags[0]" profile,
+    }
     "#;
 
-    let sql = compile(query).unwrap();
-    assert_snapshot!(sql,
-        @r"
-    WITH table_0 AS (
-      SELECT
-        SUM(a)
-      FROM
-        tbl
-      GROUP BY
-        GROUPING SETS ((b, c, d), (d), (b, d))
-    )
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::Postgres).unwrap(), @r"
     SELECT
-      *
+      profile -> 'address' -> 'city' AS city,
+      profile -> 'address' ->> 'city' AS city_text,
+      profile -> 'tags' ->> 0 AS first_tag
     FROM
-      table_0
-    "
-    );
+      customers
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::MySql).unwrap(), @r"
+    SELECT
+      JSON_EXTRACT(profile, '$.address.city') AS city,
+      JSON_UNQUOTE(JSON_EXTRACT(profile, '$.address.city')) AS city_text,
+      JSON_UNQUOTE(JSON_EXTRACT(profile, '$.tags[0]')) AS first_tag
+    FROM
+      customers
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::SQLite).unwrap(), @r"
+    SELECT
+      JSON_EXTRACT(profile, '$.address.city') AS city,
+      JSON_EXTRACT(profile, '$.address.city') AS city_text,
+      JSON_EXTRACT(profile, '$.tags[0]') AS first_tag
+    FROM
+      customers
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::Snowflake).unwrap(), @r"
+    SELECT
+      profile :address.city AS city,
+      profile :address.city :: STRING AS city_text,
+      profile :tags [0] :: STRING AS first_tag
+    FROM
+      customers
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::DuckDb).unwrap(), @r"
+    SELECT
+      json_extract(profile, '$.address.city') AS city,
+      json_extract_string(profile, '$.address.city') AS city_text,
+      json_extract_string(profile, '$.tags[0]') AS first_tag
+    FROM
+      customers
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::BigQuery).unwrap(), @r"
+    SELECT
+      JSON_QUERY(profile, '$.address.city') AS city,
+      JSON_VALUE(profile, '$.address.city') AS city_text,
+      JSON_VALUE(profile, '$.tags[0]') AS first_tag
+    FROM
+      customers
+    ");
 }
 
 #[test]
-fn test_bare_s_string_01() {
-    // Test that case insensitive SELECT is accepted. We allow it as it is valid SQL.
+fn test_pivot() {
     assert_snapshot!(compile(r#"
-    let a = s"select insensitive from rude"
-    from a
-    "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        insensitive
-      from
-        rude
-    )
+    from sales
+    pivot (quarter | in ["Q1", "Q2"]) {sum amount} by:{region}
+    "#).unwrap(), @r#"
     SELECT
-      *
+      region,
+      COALESCE(
+        SUM(
+          CASE
+            WHEN quarter = 'Q1' THEN amount
+          END
+        ),
+        0
+      ) AS "Q1",
+      COALESCE(
+        SUM(
+          CASE
+            WHEN quarter = 'Q2' THEN amount
+          END
+        ),
+        0
+      ) AS "Q2"
+    FROM
+      sales
+    GROUP BY
+      region
+    "#);
+
+    assert_snapshot!(compile(r#"
+    from sales
+    filter year == 2024
+    pivot (quarter | in ["Q1", "Q2"]) {total = sum amount, orders = count this} by:{region}
+    "#).unwrap(), @r#"
+    SELECT
+      region,
+      COALESCE(
+        SUM(
+          CASE
+            WHEN quarter = 'Q1' THEN amount
+          END
+        ),
+        0
+      ) AS "total_Q1",
+      COALESCE(
+        SUM(
+          CASE
+            WHEN quarter = 'Q1' THEN 1
+            ELSE 0
+          END
+        ),
+        0
+      ) AS "orders_Q1",
+      COALESCE(
+        SUM(
+          CASE
+            WHEN quarter = 'Q2' THEN amount
+          END
+        ),
+        0
+      ) AS "total_Q2",
+      COALESCE(
+        SUM(
+          CASE
+            WHEN quarter = 'Q2' THEN 1
+            ELSE 0
+          END
+        ),
+        0
+      ) AS "orders_Q2"
+    FROM
+      sales
+    WHERE
+      year = 2024
+    GROUP BY
+      region
+    "#);
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.bigquery
+
+    from sales
+    filter year == 2024
+    pivot (quarter | in ["Q1", "Q2"]) {total = sum amount, orders = count this} by:{region}
+    "#).unwrap(), @r"
+    SELECT
+      region,
+      COALESCE(`total_Q1`, 0) AS `total_Q1`,
+      `orders_Q1`,
+      COALESCE(`total_Q2`, 0) AS `total_Q2`,
+      `orders_Q2`
     FROM
-      table_0
-    "
-    );
+      (
+        SELECT
+          region,
+          quarter,
+          amount
+        FROM
+          sales
+        WHERE
+          year = 2024
+      ) PIVOT(
+        SUM(amount) AS total,
+        COUNT(*) AS orders FOR quarter IN ('Q1' AS `Q1`, 'Q2' AS `Q2`)
+      )
+    ");
+
+    // Snowflake's PIVOT takes only a single aggregation
+    assert_snapshot!(compile(r#"
+    prql target:sql.snowflake
+
+    from sales
+    pivot (quarter | in ["Q1", "Q2"]) {max amount} by:{region}
+    "#).unwrap(), @r#"
+    SELECT
+      region,
+      "Q1",
+      "Q2"
+    FROM
+      (
+        SELECT
+          region,
+          quarter,
+          amount
+        FROM
+          sales
+      ) PIVOT(
+        MAX(amount) FOR quarter IN ('Q1' AS "Q1", 'Q2' AS "Q2")
+      )
+    "#);
+
+    // the native PIVOT can't be followed by a sort
+    assert_snapshot!(compile(r#"
+    prql target:sql.snowflake
+
+    from sales
+    pivot (quarter | in ["Q1", "Q2"]) {max amount} by:{region}
+    sort region
+    "#).unwrap(), @r#"
+    SELECT
+      region,
+      MAX(
+        CASE
+          WHEN quarter = 'Q1' THEN amount
+        END
+      ) AS "Q1",
+      MAX(
+        CASE
+          WHEN quarter = 'Q2' THEN amount
+        END
+      ) AS "Q2"
+    FROM
+      sales
+    GROUP BY
+      region
+    ORDER BY
+      region
+    "#);
+
+    assert_snapshot!(compile(r#"
+    from sales
+    pivot (quarter == "Q1") {sum amount}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:12]
+       │
+     3 │     pivot (quarter == "Q1") {sum amount}
+       │            ───────┬───────
+       │                   ╰───────── `pivot` requires `on` to test a column against a literal list
+       │
+       │ Help: the values must be known statically, as in `quarter | in ["Q1", "Q2"]`
+    ───╯
+    "#);
+
+    assert_snapshot!(compile(r#"
+    from sales
+    pivot (quarter | in ["Q1", "Q2"]) {sum amount, count this}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:40]
+       │
+     3 │     pivot (quarter | in ["Q1", "Q2"]) {sum amount, count this}
+       │                                        ─────┬────
+       │                                             ╰────── multiple aggregations in `pivot` require names
+       │
+       │ Help: name them, as in `{total = sum amount, n = count this}`
+    ───╯
+    "#);
 }
 
 #[test]
-fn test_bare_s_string_02() {
-    // Check a mixture of cases for good measure.
-    assert_snapshot!(compile(r#"
-    let a = s"sElEcT insensitive from rude"
-    from a
-    "#).unwrap(),
-        @r"
+fn test_fill() {
+    assert_snapshot!(compile_with_sql_dialect(r#"
+    from prices
+    select {ticker, day, price}
+    fill {price} by:{ticker} sort:{day}
+    "#, sql::Dialect::DuckDb).unwrap(), @r"
+    SELECT
+      ticker,
+      day,
+      LAST_VALUE(price IGNORE NULLS) OVER (
+        PARTITION BY ticker
+        ORDER BY
+          day ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+      ) AS price
+    FROM
+      prices
+    ");
+
+    // without IGNORE NULLS, values are spread over a running count
+    assert_snapshot!(compile_with_sql_dialect(r#"
+    from prices
+    select {ticker, day, price, volume}
+    fill {price, volume} by:{ticker} sort:{day}
+    "#, sql::Dialect::Postgres).unwrap(), @r"
     WITH table_0 AS (
       SELECT
-        insensitive
-      from
-        rude
+        ticker,
+        day,
+        volume,
+        COUNT(volume) OVER window_0 AS _expr_0,
+        price,
+        COUNT(price) OVER window_0 AS _expr_1
+      FROM
+        prices WINDOW window_0 AS (
+          PARTITION BY ticker
+          ORDER BY
+            day
+        )
     )
     SELECT
-      *
+      ticker,
+      day,
+      MAX(price) OVER (PARTITION BY ticker, _expr_1) AS price,
+      MAX(volume) OVER (PARTITION BY ticker, _expr_0) AS volume
     FROM
       table_0
-    "
-    );
-}
+    ");
 
-#[test]
-fn test_bare_s_string_03() {
-    // Check SELECT\n.
     assert_snapshot!(compile(r#"
-    let a = s"
+    from prices
+    select {day, price}
+    fill {price} sort:{day} direction:backward
+    "#).unwrap(), @r"
     SELECT
-      foo
+      day,
+      FIRST_VALUE(price) IGNORE NULLS OVER (
+        ORDER BY
+          day ROWS BETWEEN CURRENT ROW
+          AND UNBOUNDED FOLLOWING
+      ) AS price
     FROM
-      bar"
+      prices
+    ");
 
-    from a
-    "#).unwrap(), @r"
+    assert_snapshot!(compile_with_sql_dialect(r#"
+    from prices
+    select {day, price}
+    fill {price} sort:{day} direction:backward
+    "#, sql::Dialect::SQLite).unwrap(), @r"
     WITH table_0 AS (
       SELECT
-        foo
+        day,
+        price,
+        COUNT(price) OVER (
+          ORDER BY
+            day DESC
+        ) AS _expr_0
       FROM
-        bar
+        prices
     )
     SELECT
-      *
+      day,
+      MAX(price) OVER (PARTITION BY _expr_0) AS price
     FROM
       table_0
     ");
-}
 
-#[test]
-fn test_bare_s_string_04() {
     assert_snapshot!(compile(r#"
-    s"SELECTfoo"
+    from prices
+    fill {price} by:{ticker}
     "#).unwrap_err(), @r"
-    Error: s-strings representing a table must start with `SELECT `
-    ↳ Hint: this is a limitation by current compiler implementation
+    Error:
+       ╭─[:3:10]
+       │
+     3 │     fill {price} by:{ticker}
+       │          ───┬───
+       │             ╰───── `fill` requires an ordering, since it depends on the order of rows
+       │
+       │ Help: add a `sort` argument, as in `fill {price} sort:{timestamp}`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    from prices
+    fill {price} sort:{day} direction:up
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:3:39]
+       │
+     3 │     fill {price} sort:{day} direction:up
+       │                                       ─┬
+       │                                        ╰── `direction` expected forward or backward, but found up
+    ───╯
     ");
 }
 
 #[test]
-// Confirm that a regular expr_call in a table definition works as expected.
-fn test_table_definition_with_expr_call() {
-    let query = r###"
-    let e = take 4 (from employees)
-    from e
-    "###;
-
+fn test_named_args_deterministic() {
+    // named args are kept in a map, so compiling the same query must not
+    // depend on the order in which it is iterated
+    let query = r#"
+    from prices
+    join side:left tickers (==ticker)
+    window rows:-2..0 (
+      derive {avg_price = average prices.price}
+    )
+    fill {prices.price} by:{prices.ticker} sort:{prices.day} direction:backward
+    group {prices.ticker} (
+      aggregate {n = count this}
+    )
+    "#;
+
+    let first = compile(query).unwrap();
+    for _ in 0..10 {
+        assert_eq!(compile(query).unwrap(), first);
+    }
+}
+
+#[test]
+fn test_join() {
+    assert_snapshot!((compile(r###"
+    from x
+    join y (==id)
+    "###).unwrap()), @r"
+    SELECT
+      x.*,
+      y.*
+    FROM
+      x
+      JOIN y ON x.id = y.id
+    ");
+
+    compile("from x | join y {==x.id}").unwrap_err();
+}
+
+#[test]
+fn test_join_range() {
+    // conditions other than equality are kept as they are
+    assert_snapshot!((compile(r###"
+    from s = sessions
+    join e = events (s.start <= e.ts && e.ts < s.end)
+    select {s.id, e.ts}
+    "###).unwrap()), @r#"
+    SELECT
+      s.id,
+      e.ts
+    FROM
+      sessions AS s
+      JOIN events AS e ON s."start" <= e.ts
+      AND e.ts < s."end"
+    "#);
+
+    assert_snapshot!((compile(r###"
+    from s = sessions
+    join side:left e = events (s.id == e.session_id && e.ts > s.start || e.pinned)
+    "###).unwrap()), @r#"
+    SELECT
+      s.*,
+      e.*
+    FROM
+      sessions AS s
+      LEFT JOIN events AS e ON s.id = e.session_id
+      AND e.ts > s."start"
+      OR e.pinned
+    "#);
+}
+
+#[test]
+fn test_join_self_equality_err() {
+    assert_snapshot!((compile(r###"
+    from x
+    join y (==(id + 1))
+    "###).unwrap_err()), @r"
+    Error:
+       ╭─[:3:16]
+       │
+     3 │     join y (==(id + 1))
+       │                ───┬──
+       │                   ╰──── you can only use column names with self-equality operator
+       │
+       │ Help: compare the columns explicitly, as in `(this.a + 1 == that.b)`
+    ───╯
+    ");
+
+    assert_snapshot!((compile(r###"
+    from x
+    join y (==x.id)
+    "###).unwrap_err()), @r"
+    Error:
+       ╭─[:3:16]
+       │
+     3 │     join y (==x.id)
+       │                ─┬─
+       │                 ╰─── you cannot use namespace prefix with self-equality operator
+       │
+       │ Help: use `==id`
+    ───╯
+    ");
+}
+
+#[test]
+fn test_join_side_literal() {
+    assert_snapshot!((compile(r###"
+    let my_side = "right"
+
+    from x
+    join y (==id) side:my_side
+    "###).unwrap()), @r"
+    SELECT
+      x.*,
+      y.*
+    FROM
+      x
+      RIGHT JOIN y ON x.id = y.id
+    ");
+}
+
+#[test]
+fn test_join_side_literal_err() {
+    assert_snapshot!((compile(r###"
+    let my_side = 42
+
+    from x
+    join y (==id) side:my_side
+    "###).unwrap_err()), @r"
+    Error:
+       ╭─[:5:24]
+       │
+     5 │     join y (==id) side:my_side
+       │                        ───┬───
+       │                           ╰───── `side` expected inner, left, right or full, but found 42
+    ───╯
+    ");
+}
+
+#[test]
+fn test_join_side_literal_via_func() {
+    assert_snapshot!((compile(r###"
+    let my_join = func m <relation> c <scalar> s <text>:"right" tbl <relation> -> (
+        join side:_param.s m (c == that.k) tbl
+    )
+
+    from x
+    my_join default_db.y this.id s:"left"
+    "###).unwrap()), @r"
+    SELECT
+      x.*,
+      y.*
+    FROM
+      x
+      LEFT JOIN y ON x.id = y.k
+    ");
+}
+
+#[test]
+fn test_join_side_literal_via_func_err() {
+    assert_snapshot!((compile(r###"
+    let my_join = func m <relation> c <scalar> s <text>:"right" tbl <relation> -> (
+        join side:_param.s m (c == that.k) tbl
+    )
+
+    from x
+    my_join default_db.y this.id s:"four"
+    "###).unwrap_err()), @r#"
+    Error:
+       ╭─[:3:25]
+       │
+     3 │         join side:_param.s m (c == that.k) tbl
+       │                         ─┬
+       │                          ╰── `side` expected inner, left, right or full, but found "four"
+    ───╯
+    "#);
+}
+
+#[test]
+fn test_from_json() {
+    // Test that the SQL generated from the JSON of the PRQL is the same as the raw PRQL
+    let original_prql = r#"
+    from e=employees
+    join salaries (==emp_no)
+    group {e.emp_no, e.gender} (
+      aggregate {
+        emp_salary = average salaries.salary
+      }
+    )
+    join de=dept_emp (==emp_no)
+    join dm=dept_manager (
+      (dm.dept_no == de.dept_no) && s"(de.from_date, de.to_date) OVERLAPS (dm.from_date, dm.to_date)"
+    )
+    group {dm.emp_no, gender} (
+      aggregate {
+        salary_avg = average emp_salary,
+        salary_sd = stddev emp_salary
+      }
+    )
+    derive mng_no = emp_no
+    join managers=employees (==emp_no)
+    derive mng_name = s"managers.first_name || ' ' || managers.last_name"
+    select {mng_name, managers.gender, salary_avg, salary_sd}
+    "#;
+
+    let source_tree = SourceTree::from(original_prql);
+
+    let sql_from_prql = Ok(prqlc::prql_to_pl_tree(&source_tree).unwrap())
+        .and_then(|ast| prqlc::semantic::resolve_and_lower(ast, &[], None))
+        .and_then(|rq| Ok(sql::compile(rq, &Options::default())?))
+        .unwrap();
+
+    let sql_from_json = prqlc::prql_to_pl(original_prql)
+        .map(|x| prqlc::json::from_pl(&x).unwrap())
+        .map(|json| prqlc::json::to_pl(&json).unwrap())
+        .and_then(prqlc::pl_to_rq)
+        .and_then(|rq| prqlc::rq_to_sql(rq, &Options::default()))
+        .unwrap();
+
+    assert_eq!(sql_from_prql, sql_from_json);
+}
+
+#[test]
+fn test_f_string() {
+    let query = r#"
+    from employees
+    derive age = year_born - s'now()'
+    select {
+        f"Hello my name is {prefix}{first_name} {last_name}",
+        f"and I am {age} years old."
+    }
+    "#;
+
+    assert_snapshot!(
+      compile(query).unwrap(),
+        @r"
+    SELECT
+      CONCAT(
+        'Hello my name is ',
+        prefix,
+        first_name,
+        ' ',
+        last_name
+      ),
+      CONCAT('and I am ', year_born - now(), ' years old.')
+    FROM
+      employees
+    "
+    );
+
+    assert_snapshot!(
+        prqlc::compile(
+          query,
+          &Options::default()
+              .no_signature()
+              .with_target(Target::Sql(Some(sql::Dialect::SQLite)))
+
+      ).unwrap(),
+          @r"
+    SELECT
+      'Hello my name is ' || COALESCE(prefix, '') || COALESCE(first_name, '') || ' ' || COALESCE(last_name, ''),
+      'and I am ' || COALESCE(year_born - now(), '') || ' years old.'
+    FROM
+      employees
+    "
+    );
+}
+
+#[test]
+fn test_sql_of_ast_1() {
+    let query = r#"
+    from employees
+    filter country == "USA"
+    group {title, country} (
+        aggregate {average salary}
+    )
+    sort title
+    take 20
+    "#;
+
+    let sql = compile(query).unwrap();
+    assert_snapshot!(sql,
+        @r"
+    SELECT
+      title,
+      country,
+      AVG(salary)
+    FROM
+      employees
+    WHERE
+      country = 'USA'
+    GROUP BY
+      title,
+      country
+    ORDER BY
+      title
+    LIMIT
+      20
+    "
+    );
+}
+
+#[test]
+fn test_sql_of_ast_02() {
+    assert_snapshot!(compile(r#"
+    from employees
+    aggregate sum_salary = s"sum({salary})"
+    filter sum_salary > 100
+    "#).unwrap(), @r"
+    SELECT
+      sum(salary) AS sum_salary
+    FROM
+      employees
+    HAVING
+      sum(salary) > 100
+    ");
+}
+
+#[test]
+fn test_bare_s_string() {
+    let query = r#"
+    let grouping = s"""
+        SELECT SUM(a)
+        FROM tbl
+        GROUP BY
+          GROUPING SETS
+          ((b, c, d), (d), (b, d))
+      """
+    from grouping
+    "#;
+
+    let sql = compile(query).unwrap();
+    assert_snapshot!(sql,
+        @r"
+    WITH table_0 AS (
+      SELECT
+        SUM(a)
+      FROM
+        tbl
+      GROUP BY
+        GROUPING SETS ((b, c, d), (d), (b, d))
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    "
+    );
+}
+
+#[test]
+fn test_bare_s_string_01() {
+    // Test that case insensitive SELECT is accepted. We allow it as it is valid SQL.
+    assert_snapshot!(compile(r#"
+    let a = s"select insensitive from rude"
+    from a
+    "#).unwrap(),
+        @r"
+    WITH table_0 AS (
+      SELECT
+        insensitive
+      from
+        rude
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    "
+    );
+}
+
+#[test]
+fn test_bare_s_string_02() {
+    // Check a mixture of cases for good measure.
+    assert_snapshot!(compile(r#"
+    let a = s"sElEcT insensitive from rude"
+    from a
+    "#).unwrap(),
+        @r"
+    WITH table_0 AS (
+      SELECT
+        insensitive
+      from
+        rude
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    "
+    );
+}
+
+#[test]
+fn test_bare_s_string_03() {
+    // Check SELECT\n.
+    assert_snapshot!(compile(r#"
+    let a = s"
+    SELECT
+      foo
+    FROM
+      bar"
+
+    from a
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        foo
+      FROM
+        bar
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    ");
+}
+
+#[test]
+fn test_bare_s_string_04() {
+    assert_snapshot!(compile(r#"
+    s"SELECTfoo"
+    "#).unwrap_err(), @r"
+    Error: s-strings representing a table must start with `SELECT `
+    ↳ Hint: this is a limitation by current compiler implementation
+    ");
+}
+
+#[test]
+fn test_s_string_template() {
+    // a function with an s-string body is a template for its arguments
+    assert_snapshot!(compile(r#"
+    let percentile = p col -> s"PERCENTILE_CONT({p}) WITHIN GROUP (ORDER BY {col})"
+
+    from prices
+    group ticker (
+      aggregate {p95 = percentile 0.95 price}
+    )
+    "#).unwrap(), @r"
+    SELECT
+      ticker,
+      PERCENTILE_CONT(0.95) WITHIN GROUP (
+        ORDER BY
+          price
+      ) AS p95
+    FROM
+      prices
+    GROUP BY
+      ticker
+    ");
+
+    // partial application is fine, as long as all arguments are given in the end
+    assert_snapshot!(compile(r#"
+    let percentile = p col -> s"PERCENTILE_CONT({p}) WITHIN GROUP (ORDER BY {col})"
+    let median = percentile 0.5
+
+    from prices
+    aggregate {m = median price}
+    "#).unwrap(), @r"
+    SELECT
+      PERCENTILE_CONT(0.5) WITHIN GROUP (
+        ORDER BY
+          price
+      ) AS m
+    FROM
+      prices
+    ");
+
+    assert_snapshot!(compile(r#"
+    let percentile = p col -> s"PERCENTILE_CONT({p}) WITHIN GROUP (ORDER BY {col})"
+
+    from prices
+    aggregate {p95 = percentile 0.95}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:5:22]
+       │
+     5 │     aggregate {p95 = percentile 0.95}
+       │                      ───────┬───────
+       │                             ╰───────── s-string template `percentile` expects 2 arguments, but was called with 1
+       │
+       │ Help: its parameters are `p`, `col`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    let percentile = p col -> s"PERCENTILE_CONT({p}) WITHIN GROUP (ORDER BY {col})"
+
+    from prices
+    aggregate {p95 = percentile 0.95 price 3}
+    "#).unwrap_err(), @r"
+    Error:
+       ╭─[:5:22]
+       │
+     5 │     aggregate {p95 = percentile 0.95 price 3}
+       │                      ───────────┬───────────
+       │                                 ╰───────────── Too many arguments to function `percentile`
+    ───╯
+    ");
+}
+
+#[test]
+// Confirm that a regular expr_call in a table definition works as expected.
+fn test_table_definition_with_expr_call() {
+    let query = r###"
+    let e = take 4 (from employees)
+    from e
+    "###;
+
+    let sql = compile(query).unwrap();
+    assert_snapshot!(sql,
+        @r"
+    WITH e AS (
+      SELECT
+        *
+      FROM
+        employees
+      LIMIT
+        4
+    )
+    SELECT
+      *
+    FROM
+      e
+    "
+    );
+}
+
+#[test]
+fn test_prql_to_sql_1() {
+    assert_snapshot!(compile(r#"
+    from employees
+    aggregate {
+        count salary,
+        sum salary,
+    }
+    "#).unwrap(), @r"
+    SELECT
+      COUNT(salary),
+      COALESCE(SUM(salary), 0)
+    FROM
+      employees
+    "
+    );
+    assert_snapshot!(compile(r#"
+    prql target:sql.postgres
+    from developers
+    group team (
+        aggregate {
+            skill_width = count_distinct specialty,
+        }
+    )
+    "#).unwrap(), @r"
+    SELECT
+      team,
+      COUNT(DISTINCT specialty) AS skill_width
+    FROM
+      developers
+    GROUP BY
+      team
+    "
+    )
+}
+
+#[test]
+#[ignore]
+fn test_prql_to_sql_2() {
+    let query = r#"
+from employees
+filter country == "USA"                           # Each line transforms the previous result.
+derive {                                         # This adds columns / variables.
+gross_salary = salary + payroll_tax,
+gross_cost = gross_salary + benefits_cost      # Variables can use other variables.
+}
+filter gross_cost > 0
+group {title, country} (
+aggregate  {                                 # `by` are the columns to group by.
+    average salary,                          # These are aggregation calcs run on each group.
+    sum     salary,
+    average gross_salary,
+    sum     gross_salary,
+    average gross_cost,
+    sum_gross_cost = sum gross_cost,
+    ct = count salary,
+}
+)
+sort sum_gross_cost
+filter ct > 200
+take 20
+"#;
+
+    let sql = compile(query).unwrap();
+    assert_snapshot!(sql, @r###"
+    WITH table_0 AS (
+      SELECT
+        title,
+        country,
+        salary + payroll_tax + benefits_cost AS _expr_0,
+        salary + payroll_tax AS _expr_1,
+        salary
+      FROM
+        employees
+      WHERE
+        country = 'USA'
+    )
+    SELECT
+      title,
+      country,
+      AVG(salary),
+      COALESCE(SUM(salary), 0),
+      AVG(_expr_1),
+      COALESCE(SUM(_expr_1), 0),
+      AVG(_expr_0),
+      COALESCE(SUM(_expr_0), 0) AS sum_gross_cost,
+      COUNT(*) AS ct
+    FROM
+      table_0
+    WHERE
+      _expr_0 > 0
+    GROUP BY
+      title,
+      country
+    HAVING
+      COUNT(*) > 200
+    ORDER BY
+      sum_gross_cost
+    LIMIT
+      20
+    "###);
+}
+
+#[test]
+fn test_prql_to_sql_table() {
+    // table
+    let query = r#"
+    let newest_employees = (
+        from employees
+        sort tenure
+        take 50
+    )
+    let average_salaries = (
+        from salaries
+        group country (
+            aggregate {
+                average_country_salary = average salary
+            }
+        )
+    )
+    from newest_employees
+    join average_salaries (==country)
+    select {name, salary, average_country_salary}
+    "#;
+    let sql = compile(query).unwrap();
+    assert_snapshot!(sql,
+        @r"
+    WITH newest_employees AS (
+      SELECT
+        *
+      FROM
+        employees
+      ORDER BY
+        tenure
+      LIMIT
+        50
+    ), average_salaries AS (
+      SELECT
+        country,
+        AVG(salary) AS average_country_salary
+      FROM
+        salaries
+      GROUP BY
+        country
+    )
+    SELECT
+      newest_employees.name,
+      newest_employees.salary,
+      average_salaries.average_country_salary
+    FROM
+      newest_employees
+      JOIN average_salaries ON newest_employees.country = average_salaries.country
+    ORDER BY
+      employees.tenure
+    "
+    );
+}
+
+#[test]
+fn test_nonatomic() {
+    // A take, then two aggregates
+    let query = r#"
+        from employees
+        take 20
+        filter country == "USA"
+        group {title, country} (
+            aggregate {
+                salary = average salary
+            }
+        )
+        group {title, country} (
+            aggregate {
+                sum_gross_cost = average salary
+            }
+        )
+        sort sum_gross_cost
+    "#;
+
+    assert_snapshot!((compile(query).unwrap()), @r"
+    WITH table_1 AS (
+      SELECT
+        title,
+        country,
+        salary
+      FROM
+        employees
+      LIMIT
+        20
+    ), table_0 AS (
+      SELECT
+        title,
+        country,
+        AVG(salary) AS _expr_0
+      FROM
+        table_1
+      WHERE
+        country = 'USA'
+      GROUP BY
+        title,
+        country
+    )
+    SELECT
+      title,
+      country,
+      AVG(_expr_0) AS sum_gross_cost
+    FROM
+      table_0
+    GROUP BY
+      title,
+      country
+    ORDER BY
+      sum_gross_cost
+    ");
+
+    // A aggregate, then sort and filter
+    let query = r###"
+        from employees
+        group {title, country} (
+            aggregate {
+                sum_gross_cost = average salary
+            }
+        )
+        sort sum_gross_cost
+        filter sum_gross_cost > 0
+    "###;
+
+    assert_snapshot!((compile(query).unwrap()), @r"
+    SELECT
+      title,
+      country,
+      AVG(salary) AS sum_gross_cost
+    FROM
+      employees
+    GROUP BY
+      title,
+      country
+    HAVING
+      AVG(salary) > 0
+    ORDER BY
+      sum_gross_cost
+    ");
+}
+
+#[test]
+/// Confirm a nonatomic table works.
+fn test_nonatomic_table() {
+    // A take, then two aggregates
+    let query = r#"
+    let a = (
+        from employees
+        take 50
+        group country (aggregate {s"count(*)"})
+    )
+    from a
+    join b (==country)
+    select {name, salary, average_country_salary}
+"#;
+
+    assert_snapshot!((compile(query).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        country
+      FROM
+        employees
+      LIMIT
+        50
+    ), a AS (
+      SELECT
+        country,
+        count(*)
+      FROM
+        table_0
+      GROUP BY
+        country
+    )
+    SELECT
+      b.name,
+      b.salary,
+      b.average_country_salary
+    FROM
+      a
+      JOIN b ON a.country = b.country
+    ");
+}
+
+#[test]
+fn test_table_names_between_splits_01() {
+    assert_snapshot!(compile(r###"
+    from employees
+    join d = department (==dept_no)
+    take 10
+    derive emp_no = employees.emp_no
+    join s = salaries (==emp_no)
+    select {employees.emp_no, d.name, s.salary}
+    "###).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        employees.emp_no,
+        d.name
+      FROM
+        employees
+        JOIN department AS d ON employees.dept_no = d.dept_no
+      LIMIT
+        10
+    )
+    SELECT
+      table_0.emp_no,
+      table_0.name,
+      s.salary
+    FROM
+      table_0
+      JOIN salaries AS s ON table_0.emp_no = s.emp_no
+    ");
+}
+
+#[test]
+fn test_table_names_between_splits_02() {
+    assert_snapshot!(compile(r###"
+    from e = employees
+    take 10
+    join salaries (==emp_no)
+    select {e.*, salaries.salary}
+    "###).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        employees AS e
+      LIMIT
+        10
+    )
+    SELECT
+      table_0.*,
+      salaries.salary
+    FROM
+      table_0
+      JOIN salaries ON table_0.emp_no = salaries.emp_no
+    ");
+}
+
+#[test]
+fn test_table_alias_01() {
+    assert_snapshot!((compile(r###"
+    from e = employees
+    join salaries side:left (salaries.emp_no == e.emp_no)
+    group {e.emp_no} (
+        aggregate {
+            emp_salary = average salaries.salary
+        }
+    )
+    select {emp_no, emp_salary}
+    "###).unwrap()), @r"
+    SELECT
+      e.emp_no,
+      AVG(salaries.salary) AS emp_salary
+    FROM
+      employees AS e
+      LEFT JOIN salaries ON salaries.emp_no = e.emp_no
+    GROUP BY
+      e.emp_no
+    ");
+}
+
+#[test]
+fn test_table_alias_02() {
+    assert_snapshot!((compile(r#"
+    from e = employees
+    select e.first_name
+    filter e.first_name == "Fred"
+    "#).unwrap()), @r"
+    SELECT
+      first_name
+    FROM
+      employees AS e
+    WHERE
+      first_name = 'Fred'
+    ");
+}
+
+#[test]
+fn test_targets() {
+    // Generic
+    let query = r###"
+    prql target:sql.generic
+    from Employees
+    select {FirstName, `last name`}
+    take 3
+    "###;
+
+    assert_snapshot!((compile(query).unwrap()), @r#"
+    SELECT
+      "FirstName",
+      "last name"
+    FROM
+      "Employees"
+    LIMIT
+      3
+    "#);
+
+    // SQL server
+    let query = r###"
+    prql target:sql.mssql
+    from Employees
+    select {FirstName, `last name`}
+    take 3
+    "###;
+
+    assert_snapshot!((compile(query).unwrap()), @r"
+    SELECT
+      TOP 3 [FirstName],
+      [last name]
+    FROM
+      [Employees]
+    ");
+
+    // MySQL
+    let query = r###"
+    prql target:sql.mysql
+    from Employees
+    select {FirstName, `last name`}
+    take 3
+    "###;
+
+    assert_snapshot!((compile(query).unwrap()), @r"
+    SELECT
+      `FirstName`,
+      `last name`
+    FROM
+      `Employees`
+    LIMIT
+      3
+    ");
+}
+
+#[rstest]
+#[case::take(
+    "from e | sort a | take 5..10",
+    "SELECT * FROM e ORDER BY a LIMIT 6 OFFSET 4",
+    "SELECT * FROM e ORDER BY a OFFSET 4 ROWS FETCH FIRST 6 ROWS ONLY"
+)]
+#[case::concat(
+    r#"from e | select {n = f"{a}-{b}"}"#,
+    "SELECT CONCAT(a, '-', b) AS n FROM e",
+    "SELECT COALESCE(a, '') || '-' || COALESCE(b, '') AS n FROM e"
+)]
+#[case::modulo(
+    "from e | select {m = x % 3}",
+    "SELECT x % 3 AS m FROM e",
+    "SELECT MOD(x, 3) AS m FROM e"
+)]
+#[case::weeks(
+    "from e | select {d = t + 2weeks}",
+    "SELECT t + INTERVAL '2 WEEK' AS d FROM e",
+    "SELECT t + INTERVAL '14' DAY AS d FROM e"
+)]
+#[case::milliseconds(
+    "from e | select {d = t + 250milliseconds}",
+    "SELECT t + INTERVAL '250 MILLISECOND' AS d FROM e",
+    "SELECT t + INTERVAL '0.250' SECOND AS d FROM e"
+)]
+#[case::starts_with(
+    r#"from e | filter (name | text.starts_with "A")"#,
+    "SELECT * FROM e WHERE name LIKE CONCAT('A', '%')",
+    "SELECT * FROM e WHERE name LIKE ('A' || '%')"
+)]
+#[case::cast(
+    "from e | select {x = (y | as int)}",
+    "SELECT CAST(y AS int) AS x FROM e",
+    "SELECT CAST(y AS int) AS x FROM e"
+)]
+#[case::cast_expr(
+    "from e | select {x = y as float}",
+    "SELECT CAST(y AS DOUBLE PRECISION) AS x FROM e",
+    "SELECT CAST(y AS DOUBLE PRECISION) AS x FROM e"
+)]
+fn test_ansi_against_postgres(#[case] prql: &str, #[case] postgres: &str, #[case] ansi: &str) {
+    let compile_to = |dialect| {
+        let opts = Options::default()
+            .no_signature()
+            .no_format()
+            .with_target(Target::Sql(Some(dialect)));
+        prqlc::compile(prql, &opts).unwrap()
+    };
+
+    assert_eq!(compile_to(sql::Dialect::Postgres), postgres);
+    assert_eq!(compile_to(sql::Dialect::Ansi), ansi);
+}
+
+#[test]
+fn test_ansi_unsupported() {
+    assert_snapshot!(compile(r#"
+    prql target:sql.ansi
+
+    from e
+    filter (name ~= "^a")
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:5:13]
+       │
+     5 │     filter (name ~= "^a")
+       │             ──────┬─────
+       │                   ╰─────── operator std.regex_search is not supported for dialect ansi
+    ───╯
+    "#);
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.ansi
+
+    from e
+    select {d = (created | date.to_text "%Y")}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:5:41]
+       │
+     5 │     select {d = (created | date.to_text "%Y")}
+       │                                         ──┬─
+       │                                           ╰─── Date formatting is not supported by dialect ansi, since there is no standard format
+    ───╯
+    "#);
+}
+
+#[test]
+fn test_quoting_policy() {
+    use prqlc::QuotingPolicy;
+
+    let query = r#"
+    from invoices
+    select {this.`select`, amount, `Total`}
+    "#;
+    let compile_with = |quoting, target| {
+        let opts = Options::default()
+            .no_signature()
+            .no_format()
+            .with_target(Target::Sql(Some(target)))
+            .with_quoting(quoting);
+        prqlc::compile(query, &opts).unwrap()
+    };
+
+    assert_snapshot!(compile_with(QuotingPolicy::WhenNeeded, sql::Dialect::Postgres), @r#"SELECT "select", amount, "Total" FROM invoices"#);
+    assert_snapshot!(compile_with(QuotingPolicy::WhenNeeded, sql::Dialect::MySql), @"SELECT `select`, amount, `Total` FROM invoices");
+    assert_snapshot!(compile_with(QuotingPolicy::WhenNeeded, sql::Dialect::MsSql), @"SELECT [select], amount, [Total] FROM invoices");
+    assert_snapshot!(compile_with(QuotingPolicy::Always, sql::Dialect::Postgres), @r#"SELECT "select", "amount", "Total" FROM "invoices""#);
+    assert_snapshot!(compile_with(QuotingPolicy::Never, sql::Dialect::Postgres), @"SELECT select, amount, Total FROM invoices");
+}
+
+#[test]
+fn test_target_clickhouse() {
+    let query = r###"
+    prql target:sql.clickhouse
+
+    from github_json
+    derive {event_type_dotted = `event.type`}
+    "###;
+
+    assert_snapshot!((compile(query).unwrap()), @r"
+    SELECT
+      *,
+      `event.type` AS event_type_dotted
+    FROM
+      github_json
+    ");
+}
+
+#[test]
+fn test_clickhouse_aggregates() {
+    assert_snapshot!((compile(r###"
+    prql target:sql.clickhouse
+
+    from employees
+    group department (
+      aggregate {
+        best_paid = arg_max salary name,
+        youngest = arg_min age name,
+        any_active = any active,
+        all_active = all active,
+      }
+    )
+    derive ids = [1, 2, 3]
+    "###).unwrap()), @r"
+    WITH table_0 AS (
+      SELECT
+        department,
+        argMax(name, salary) AS best_paid,
+        argMin(name, age) AS youngest,
+        COALESCE(max(active), FALSE) AS any_active,
+        COALESCE(min(active), TRUE) AS all_active
+      FROM
+        employees
+      GROUP BY
+        department
+    )
+    SELECT
+      department,
+      best_paid,
+      youngest,
+      any_active,
+      all_active,
+      [1, 2, 3] AS ids
+    FROM
+      table_0
+    ");
+
+    // other dialects without such a function report so
+    assert_snapshot!((compile(r###"
+    prql target:sql.postgres
+
+    from employees
+    aggregate {best_paid = arg_max salary name}
+    "###).unwrap_err()), @r"
+    Error:
+       ╭─[:5:28]
+       │
+     5 │     aggregate {best_paid = arg_max salary name}
+       │                            ─────────┬─────────
+       │                                     ╰─────────── operator std.arg_max is not supported for dialect postgres
+    ───╯
+    ");
+}
+
+#[test]
+fn test_strict_target() {
+    let query = r#"
+    prql target:sql.mysql
+    from Employees
+    "#;
+
+    // without `strict`, the target from the options takes precedence
+    let options = Options::default()
+        .no_signature()
+        .no_format()
+        .with_target(Target::Sql(Some(sql::Dialect::Postgres)));
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r#"SELECT * FROM "Employees""#);
+
+    assert_snapshot!(prqlc::compile(query, &options.clone().with_strict(true)).unwrap_err(), @r"
+    Error: query declares `target:sql.mysql`, but is being compiled to `sql.postgres`
+    ↳ Hint: remove the `prql target` from the query or compile to the declared target
+    ");
+
+    // a matching or unspecified target is accepted
+    let options = options
+        .with_strict(true)
+        .with_target(Target::Sql(Some(sql::Dialect::MySql)));
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @"SELECT * FROM `Employees`");
+
+    let options = options.with_target(Target::Sql(None));
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @"SELECT * FROM `Employees`");
+}
+
+#[test]
+fn test_indent() {
+    let query = r#"
+    from employees
+    select {name, age}
+    "#;
+
+    let options = Options::default().no_signature().with_indent(4);
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    SELECT
+        name,
+        age
+    FROM
+        employees
+    ");
+}
+
+#[test]
+fn test_simplify_predicates() {
+    let query = r#"
+    from employees
+    filter !(country == "USA" || !(age > 30))
+    filter !(!(is_active))
+    filter !(manager == null && !(title != "CEO"))
+    "#;
+
+    let options = Options::default().no_signature();
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    SELECT
+      *
+    FROM
+      employees
+    WHERE
+      NOT (
+        country = 'USA'
+        OR NOT age > 30
+      )
+      AND NOT NOT is_active
+      AND NOT (
+        manager IS NULL
+        AND NOT title <> 'CEO'
+      )
+    ");
+
+    let options = options.with_simplify_predicates(true);
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    SELECT
+      *
+    FROM
+      employees
+    WHERE
+      country <> 'USA'
+      AND age > 30
+      AND is_active
+      AND (
+        manager IS NOT NULL
+        OR title <> 'CEO'
+      )
+    ");
+}
+
+#[test]
+fn test_push_down_predicates() {
+    let options = Options::default()
+        .no_signature()
+        .with_push_down_predicates(true);
+
+    let query = r#"
+    from a
+    join b (==id)
+    filter a.x > 1 && b.y < 3 && a.z == b.z
+    "#;
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    WITH table_1 AS (
+      SELECT
+        *
+      FROM
+        a
+      WHERE
+        x > 1
+    ),
+    table_0 AS (
+      SELECT
+        *
+      FROM
+        b
+      WHERE
+        y < 3
+    )
+    SELECT
+      table_1.*,
+      b.*
+    FROM
+      table_1
+      JOIN table_0 AS b ON table_1.id = b.id
+    WHERE
+      table_1.z = b.z
+    ");
+
+    // the right side of a left join is padded with nulls, so it can't be
+    // filtered before the join
+    let query = r#"
+    from a
+    join side:left b (==id)
+    filter a.x > 1 && b.y < 3
+    "#;
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        a
+      WHERE
+        x > 1
+    )
+    SELECT
+      table_0.*,
+      b.*
+    FROM
+      table_0
+      LEFT JOIN b ON table_0.id = b.id
+    WHERE
+      b.y < 3
+    ");
+
+    // conditions moved before the last join are pushed into the first one too
+    let query = r#"
+    from a
+    join b (a.id == b.id)
+    join c (a.id == c.id)
+    filter a.x > 1 && b.y < 3 && s"{c.w} = 2"
+    "#;
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    WITH table_1 AS (
+      SELECT
+        *
+      FROM
+        a
+      WHERE
+        x > 1
+    ),
+    table_0 AS (
+      SELECT
+        *
+      FROM
+        b
+      WHERE
+        y < 3
+    )
+    SELECT
+      table_1.*,
+      b.*,
+      c.*
+    FROM
+      table_1
+      JOIN table_0 AS b ON table_1.id = b.id
+      JOIN c ON table_1.id = c.id
+    WHERE
+      c.w = 2
+    ");
+}
+
+#[test]
+fn test_inline_ctes() {
+    let options = Options::default().no_signature().with_inline_ctes(true);
+
+    // `x` is referenced once, so it is inlined, while `y` is joined to itself
+    let query = r#"
+    let x = (from a | filter b > 1)
+    let y = (from c | take 10)
+
+    from x
+    join y1=y (==id)
+    join y2=y (x.id == y2.id)
+    "#;
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    WITH y AS (
+      SELECT
+        *
+      FROM
+        c
+      LIMIT
+        10
+    )
+    SELECT
+      x.*,
+      y1.*,
+      y2.*
+    FROM
+      (
+        SELECT
+          *
+        FROM
+          a
+        WHERE
+          b > 1
+      ) AS x
+      JOIN y AS y1 ON x.id = y1.id
+      JOIN y AS y2 ON x.id = y2.id
+    ");
+
+    // tables created while splitting the pipeline are not inlined
+    let query = r#"
+    from a
+    take 10
+    filter b > 1
+    "#;
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        a
+      LIMIT
+        10
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      b > 1
+    ");
+
+    // nested tables are inlined too
+    let query = r#"
+    let x = (from a | filter b > 1)
+    let y = (from x | aggregate {total = sum b})
+
+    from y
+    select {total}
+    "#;
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    SELECT
+      total
+    FROM
+      (
+        SELECT
+          COALESCE(SUM(b), 0) AS total
+        FROM
+          (
+            SELECT
+              *
+            FROM
+              a
+            WHERE
+              b > 1
+          ) AS x
+      ) AS y
+    ");
+
+    let query = r#"
+    let x = (from a | select {b})
+
+    from c
+    select {b}
+    append x
+    "#;
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    SELECT
+      b
+    FROM
+      c
+    UNION
+    ALL
+    SELECT
+      *
+    FROM
+      (
+        SELECT
+          b
+        FROM
+          a
+      ) AS x
+    ");
+
+    let query = r#"
+    let x = (from a | select {n})
+
+    from x
+    loop (
+        filter n < 4
+        select {n = n + 1}
+    )
+    "#;
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    WITH RECURSIVE table_0 AS (
+      SELECT
+        n
+      FROM
+        (
+          SELECT
+            n
+          FROM
+            a
+        ) AS x
+      UNION
+      ALL
+      SELECT
+        n + 1
+      FROM
+        table_0
+      WHERE
+        n < 4
+    )
+    SELECT
+      n
+    FROM
+      table_0 AS table_1
+    ");
+}
+
+#[test]
+fn test_prefer_subqueries() {
+    // `y` is joined to itself, so its sub-query is repeated
+    let query = r#"
+    let x = (from a | filter b > 1)
+    let y = (from c | take 10)
+
+    from x
+    join y1=y (==id)
+    join y2=y (x.id == y2.id)
+    "#;
+    let options = Options::default().no_signature();
+    assert_snapshot!(prqlc::compile(query, &options.clone().with_prefer_subqueries(true)).unwrap(), @r"
+    SELECT
+      x.*,
+      y1.*,
+      y2.*
+    FROM
+      (
+        SELECT
+          *
+        FROM
+          a
+        WHERE
+          b > 1
+      ) AS x
+      JOIN (
+        SELECT
+          *
+        FROM
+          c
+        LIMIT
+          10
+      ) AS y1 ON x.id = y1.id
+      JOIN (
+        SELECT
+          *
+        FROM
+          c
+        LIMIT
+          10
+      ) AS y2 ON x.id = y2.id
+    ");
+
+    assert!(prqlc::compile(query, &options)
+        .unwrap()
+        .starts_with("WITH x AS"));
+}
+
+#[test]
+fn test_target_version() {
+    let query = r#"
+    prql target:sql.mysql
+
+    let recent = (from orders | filter placed_at > @2024-01-01)
+
+    from recent
+    join customers (==customer_id)
+    "#;
+    let compile = |version: &str| {
+        let options = Options::default()
+            .no_signature()
+            .with_target_version(Some(version.parse().unwrap()));
+        prqlc::compile(query, &options).unwrap()
+    };
+
+    assert_snapshot!(compile("8.0"), @r"
+    WITH recent AS (
+      SELECT
+        *
+      FROM
+        orders
+      WHERE
+        placed_at > DATE '2024-01-01'
+    )
+    SELECT
+      recent.*,
+      customers.*
+    FROM
+      recent
+      JOIN customers ON recent.customer_id = customers.customer_id
+    ");
+
+    // MySQL 5.7 does not support CTEs
+    assert_snapshot!(compile("5.7"), @r"
+    SELECT
+      recent.*,
+      customers.*
+    FROM
+      (
+        SELECT
+          *
+        FROM
+          orders
+        WHERE
+          placed_at > DATE '2024-01-01'
+      ) AS recent
+      JOIN customers ON recent.customer_id = customers.customer_id
+    ");
+}
+
+#[test]
+fn test_full_join() {
+    let query = |target: &str| {
+        format!(
+            r#"
+    prql target:sql.{target}
+
+    from e = employees
+    join side:full d = departments (==dept_id)
+    select {{e.name, d.title}}
+    "#
+        )
+    };
+    let compile = |target: &str, version: Option<&str>| {
+        let options = Options::default()
+            .no_signature()
+            .with_target_version(version.map(|v| v.parse().unwrap()));
+        prqlc::compile(&query(target), &options).unwrap()
+    };
+
+    assert_snapshot!(compile("postgres", None), @r"
+    SELECT
+      e.name,
+      d.title
+    FROM
+      employees AS e
+      FULL JOIN departments AS d ON e.dept_id = d.dept_id
+    ");
+
+    // SQLite supports FULL JOIN since 3.39
+    assert_eq!(compile("sqlite", Some("3.39")), compile("postgres", None));
+    assert_snapshot!(compile("sqlite", Some("3.38")), @r"
+    WITH table_0 AS (
+      SELECT
+        name,
+        dept_id
+      FROM
+        employees AS e
+    ),
+    table_1 AS (
+      SELECT
+        table_0.name,
+        table_0.dept_id,
+        d.dept_id AS dept_id_0,
+        d.title
+      FROM
+        departments AS d
+        LEFT JOIN table_0 ON table_0.dept_id = d.dept_id
+      WHERE
+        table_0.dept_id IS NULL
+    ),
+    table_2 AS (
+      SELECT
+        table_0.name,
+        table_0.dept_id,
+        d.dept_id AS dept_id_0,
+        d.title
+      FROM
+        table_0
+        LEFT JOIN departments AS d ON table_0.dept_id = d.dept_id
+      UNION
+      ALL
+      SELECT
+        *
+      FROM
+        table_1
+    )
+    SELECT
+      name,
+      title
+    FROM
+      table_2
+    ");
+
+    // MySQL does not support FULL JOIN
+    assert_eq!(compile("mysql", None), compile("sqlite", Some("3.38")));
+}
+
+#[test]
+fn test_column_patterns() {
+    use std::collections::HashMap;
+
+    let table_columns = HashMap::from([(
+        "payments".to_string(),
+        ["id", "amt_net", "amt_tax", "paid_at", "refunded_at"]
+            .map(String::from)
+            .to_vec(),
+    )]);
+    let options = Options::default()
+        .no_signature()
+        .with_table_columns(table_columns);
+
+    assert_snapshot!(prqlc::compile(r#"
+    from payments
+    select {id, column_names.starts_with "amt_"}
+    "#, &options).unwrap(), @r"
+    SELECT
+      id,
+      amt_net,
+      amt_tax
+    FROM
+      payments
+    ");
+
+    assert_snapshot!(prqlc::compile(r#"
+    from payments
+    derive {amt_total = amt_net + amt_tax}
+    select {column_names.ends_with "_at", column_names.matches "^amt_(net|total)$"}
+    "#, &options).unwrap(), @r"
+    SELECT
+      paid_at,
+      refunded_at,
+      amt_net,
+      amt_net + amt_tax AS amt_total
+    FROM
+      payments
+    ");
+}
+
+#[test]
+fn test_compile_project() {
+    use std::collections::HashMap;
+
+    let options = Options::default()
+        .no_signature()
+        .with_display(prqlc::DisplayOptions::Plain);
+    let compile = |entry: &str, sources: &[(&str, &str)]| {
+        let sources: HashMap<_, _> = (sources.iter())
+            .map(|(path, source)| (path.to_string(), source.to_string()))
+            .collect();
+        prqlc::compile_project(entry, sources, &options)
+    };
+
+    let lib = "let double = x -> x * 2";
+    let dates = "let days = d -> (d | as int) * 86400";
+    assert_snapshot!(compile(r#"
+    import utils.dates.days
+    from events
+    derive {a = lib.double amount, b = days duration}
+    "#, &[("lib.prql", lib), ("utils/dates.prql", dates)]).unwrap(), @r"
+    SELECT
+      *,
+      amount * 2 AS a,
+      CAST(duration AS int) * 86400 AS b
+    FROM
+      events
+    ");
+
+    // a module declared by a file can be extended within another file
+    assert_snapshot!(compile(r#"
+    module lib {
+      let triple = x -> x * 3
+    }
+    from events
+    derive {a = lib.double amount, b = lib.triple amount}
+    "#, &[("lib.prql", lib)]).unwrap(), @r"
+    SELECT
+      *,
+      amount * 2 AS a,
+      amount * 3 AS b
+    FROM
+      events
+    ");
+
+    // but a name cannot be declared in both
+    assert_snapshot!(compile(r#"
+    module lib {
+      let double = x -> x + x
+    }
+    from events
+    derive {a = lib.double amount}
+    "#, &[("lib.prql", lib)]).unwrap_err(), @r"
+    Error:
+       ╭─[:2:17]
+       │
+     2 │ ╭─▶     module lib {
+     3 │ ├─▶       let double = x -> x + x
+       │ │
+       │ ╰─────────────────────────────────── `double` is declared in more than one source file
+       │
+       │     Help: `double` is also declared in `lib.prql`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    import a.f
+    from events
+    derive {y = f amount}
+    "#, &[("a.prql", "import b.f"), ("b.prql", "import a.f")]).unwrap_err(), @r"
+    Error:
+       ╭─[:4:17]
+       │
+     4 │     derive {y = f amount}
+       │                 ┬
+       │                 ╰── Circular import: `a.f` -> `b.f` -> `a.f`
+    ───╯
+    ");
+
+    assert_snapshot!(compile(r#"
+    from events
+    derive {y = a.f amount}
+    "#, &[("a.prql", "let f = x -> b.g x"), ("b.prql", "let g = x -> a.f x")]).unwrap_err(), @r"
+    Error:
+       ╭─[b.prql:1:14]
+       │
+     1 │ let g = x -> a.f x
+       │              ──┬──
+       │                ╰──── Recursive functions are not supported: `a.f` -> `b.g` -> `a.f`
+       │
+       │ Help: functions are inlined into the query, so recursion over rows has to be written with `loop`
+    ───╯
+    ");
+}
+
+#[test]
+fn test_compile_json() {
+    assert_snapshot!(prqlc::compile_json(
+        "from a | take 10",
+        r#"{"target": "sql.mssql", "signature_comment": false}"#,
+    ), @r#"{"sql":"SELECT\n  TOP 10 *\nFROM\n  a\n"}"#);
+
+    // errors are returned as data, with the byte range of the offending token
+    assert_snapshot!(prqlc::compile_json("from x | select a | select b", "{}"), @r#"{"errors":[{"message":"Unknown name `b`","span":[27,28],"hint":"available columns: x.a"}]}"#);
+
+    assert_snapshot!(prqlc::compile_json(
+        "from employees\nfilter (age | in 1)",
+        "{}",
+    ), @r#"{"errors":[{"message":"std.in expected a pattern, but found 1","span":[32,33],"hint":null}]}"#);
+
+    assert_snapshot!(prqlc::compile_json("from a", r#"{"target": "sql.foo"}"#), @r#"{"errors":[{"message":"target `\"sql.foo\"` not found","span":null,"hint":null}]}"#);
+
+    assert_snapshot!(prqlc::compile_json("from a", r#"{"dialect": "postgres"}"#), @r#"{"errors":[{"message":"invalid options: unknown field `dialect`, expected `target` or `signature_comment` at line 1 column 10","span":null,"hint":null}]}"#);
+}
+
+#[test]
+fn test_compile_parameterized() {
+    let query = r#"
+    from employees
+    derive {bonus = salary * 0.1}
+    filter country == "USA" && age > 30
+    sort name
+    take 11..20
+    "#;
+
+    let options = Options::default()
+        .no_signature()
+        .with_target(Target::Sql(Some(sql::Dialect::Postgres)));
+    let (sql, params) = prqlc::compile_parameterized(query, &options).unwrap();
+    assert_snapshot!(sql, @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        salary * $1 AS bonus
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      country = $2
+      AND age > $3
+    ORDER BY
+      name
+    LIMIT
+      10 OFFSET 10
+    ");
+    assert_debug_snapshot!(params, @r#"
+    [
+        Float(
+            0.1,
+        ),
+        String(
+            "USA",
+        ),
+        Integer(
+            30,
+        ),
+    ]
+    "#);
+
+    let options = options.with_target(Target::Sql(Some(sql::Dialect::MySql)));
+    let (sql, params) = prqlc::compile_parameterized(query, &options).unwrap();
+    assert_snapshot!(sql, @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        salary * ? AS bonus
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      country = ?
+      AND age > ?
+    ORDER BY
+      name
+    LIMIT
+      10 OFFSET 10
+    ");
+    assert_debug_snapshot!(params, @r#"
+    [
+        Float(
+            0.1,
+        ),
+        String(
+            "USA",
+        ),
+        Integer(
+            30,
+        ),
+    ]
+    "#);
+
+    let options = options.with_bind_take(true);
+    let (sql, params) = prqlc::compile_parameterized(query, &options).unwrap();
+    assert_snapshot!(sql, @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        salary * ? AS bonus
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      country = ?
+      AND age > ?
+    ORDER BY
+      name
+    LIMIT
+      ? OFFSET ?
+    ");
+    assert_debug_snapshot!(params, @r#"
+    [
+        Float(
+            0.1,
+        ),
+        String(
+            "USA",
+        ),
+        Integer(
+            30,
+        ),
+        Integer(
+            10,
+        ),
+        Integer(
+            10,
+        ),
+    ]
+    "#);
+}
+
+#[test]
+fn test_ident_escaping() {
+    // Generic
+    let query = r#"
+    from `anim"ls`
+    derive {`čebela` = BeeName, medved = `bear's_name`}
+    "#;
+
+    assert_snapshot!((compile(query).unwrap()), @r#"
+    SELECT
+      *,
+      "BeeName" AS "čebela",
+      "bear's_name" AS medved
+    FROM
+      "anim""ls"
+    "#);
+
+    // MySQL
+    let query = r#"
+    prql target:sql.mysql
+
+    from `anim"ls`
+    derive {`čebela` = BeeName, medved = `bear's_name`}
+    "#;
+
+    assert_snapshot!((compile(query).unwrap()), @r#"
+    SELECT
+      *,
+      `BeeName` AS `čebela`,
+      `bear's_name` AS medved
+    FROM
+      `anim"ls`
+    "#);
+}
+
+#[test]
+fn test_literal() {
+    let query = r###"
+    from employees
+    derive {always_true = true}
+    "###;
+
     let sql = compile(query).unwrap();
     assert_snapshot!(sql,
         @r"
-    WITH e AS (
-      SELECT
-        *
-      FROM
-        employees
-      LIMIT
-        4
-    )
     SELECT
-      *
+      *,
+      true AS always_true
+    FROM
+      employees
+    "
+    );
+}
+
+#[test]
+fn test_same_column_names() {
+    // #820
+    let query = r###"
+let x = (
+from x_table
+select only_in_x = foo
+)
+
+let y = (
+from y_table
+select foo
+)
+
+from x
+join y (foo == only_in_x)
+"###;
+
+    assert_snapshot!(compile(query).unwrap(),
+        @r"
+    WITH x AS (
+      SELECT
+        foo AS only_in_x
+      FROM
+        x_table
+    ),
+    y AS (
+      SELECT
+        foo
+      FROM
+        y_table
+    )
+    SELECT
+      x.only_in_x,
+      y.foo
+    FROM
+      x
+      JOIN y ON y.foo = x.only_in_x
+    "
+    );
+}
+
+#[test]
+fn test_double_aggregate() {
+    // #941
+    compile(
+        r###"
+    from numbers
+    group {type} (
+        aggregate {
+            total_amt = sum amount,
+        }
+        aggregate {
+            max amount
+        }
+    )
+    "###,
+    )
+    .unwrap_err();
+
+    assert_snapshot!(compile(r###"
+    from numbers
+    group {`type`} (
+        aggregate {
+            total_amt = sum amount,
+            max amount
+        }
+    )
+    "###).unwrap(),
+        @r"
+    SELECT
+      type,
+      COALESCE(SUM(amount), 0) AS total_amt,
+      MAX(amount)
+    FROM
+      numbers
+    GROUP BY
+      type
+    "
+    );
+}
+
+#[test]
+fn test_window_function_coalesce() {
+    // #3587
+    assert_snapshot!(compile(r###"
+    from x
+    select {a, b=a}
+    window (
+      select {
+        cumsum_a=(sum a),
+        cumsum_b=(sum b)
+      }
+    )
+    "###).unwrap(),
+        @r"
+    SELECT
+      SUM(a) OVER () AS cumsum_a,
+      SUM(a) OVER () AS cumsum_b
+    FROM
+      x
+    "
+    );
+}
+
+#[test]
+fn test_named_windows() {
+    let query = r###"
+    from orders
+    group {customer_id} (
+      sort {placed_at}
+      window rolling:3 (
+        derive {avg_total = average total, max_total = max total}
+      )
+    )
+    "###;
+
+    assert_snapshot!(compile(query).unwrap(), @r"
+    SELECT
+      *,
+      AVG(total) OVER window_0 AS avg_total,
+      MAX(total) OVER window_0 AS max_total
+    FROM
+      orders WINDOW window_0 AS (
+        PARTITION BY customer_id
+        ORDER BY
+          placed_at ROWS BETWEEN 2 PRECEDING AND CURRENT ROW
+      )
+    ");
+
+    // SQLite falls back to repeating the window of each function
+    assert_snapshot!(compile(&format!("prql target:sql.sqlite\n{query}")).unwrap(), @r"
+    SELECT
+      *,
+      AVG(total) OVER (
+        PARTITION BY customer_id
+        ORDER BY
+          placed_at ROWS BETWEEN 2 PRECEDING AND CURRENT ROW
+      ) AS avg_total,
+      MAX(total) OVER (
+        PARTITION BY customer_id
+        ORDER BY
+          placed_at ROWS BETWEEN 2 PRECEDING AND CURRENT ROW
+      ) AS max_total
+    FROM
+      orders
+    ");
+}
+
+#[test]
+fn test_casting() {
+    assert_snapshot!(compile(r###"
+    from x
+    select {a}
+    derive {
+        b = (a | as int) + 10,
+        c = (a | as int) - 10,
+        d = (a | as float) * 10,
+        e = (a | as float) / 10,
+    }
+    "###).unwrap(),
+        @r"
+    SELECT
+      a,
+      CAST(a AS int) + 10 AS b,
+      CAST(a AS int) - 10 AS c,
+      CAST(a AS float) * 10 AS d,
+      CAST(a AS float) / 10 AS e
+    FROM
+      x
+    "
+    );
+}
+
+#[test]
+fn test_toposort() {
+    // #1183
+
+    assert_snapshot!(compile(r###"
+    let b = (
+        from somesource
+    )
+
+    let a = (
+        from b
+    )
+
+    from b
+    "###).unwrap(),
+        @r"
+    WITH b AS (
+      SELECT
+        *
+      FROM
+        somesource
+    )
+    SELECT
+      *
+    FROM
+      b
+    "
+    );
+}
+
+#[test]
+fn test_inline_tables() {
+    assert_snapshot!(compile(r###"
+    (
+        from employees
+        select {emp_id, name, surname, `type`, amount}
+    )
+    join s = (from salaries | select {emp_id, salary}) (==emp_id)
+    "###).unwrap(),
+        @r"
+    WITH table_0 AS (
+      SELECT
+        emp_id,
+        salary
+      FROM
+        salaries
+    )
+    SELECT
+      employees.emp_id,
+      employees.name,
+      employees.surname,
+      employees.type,
+      employees.amount,
+      table_0.emp_id,
+      table_0.salary
+    FROM
+      employees
+      JOIN table_0 ON employees.emp_id = table_0.emp_id
+    "
+    );
+}
+
+#[test]
+fn test_filter_and_select_unchanged_alias() {
+    // #1185
+
+    assert_snapshot!(compile(r###"
+    from account
+    filter account.name != null
+    select {name = account.name}
+    "###).unwrap(),
+        @r"
+    SELECT
+      name
+    FROM
+      account
+    WHERE
+      name IS NOT NULL
+    "
+    );
+}
+
+#[test]
+fn test_filter_and_select_changed_alias() {
+    // #1185
+    assert_snapshot!(compile(r###"
+    from account
+    filter account.name != null
+    select {renamed_name = account.name}
+    "###).unwrap(),
+        @r"
+    SELECT
+      name AS renamed_name
     FROM
-      e
+      account
+    WHERE
+      name IS NOT NULL
+    "
+    );
+
+    // #1207
+    assert_snapshot!(compile(r#"
+    from x
+    filter name != "Bob"
+    select name = name ?? "Default"
+    "#).unwrap(),
+        @r"
+    SELECT
+      COALESCE(name, 'Default') AS name
+    FROM
+      x
+    WHERE
+      name <> 'Bob'
     "
     );
 }
 
 #[test]
-fn test_prql_to_sql_1() {
+fn test_coalesce_chain() {
     assert_snapshot!(compile(r#"
     from employees
-    aggregate {
-        count salary,
-        sum salary,
+    derive {
+      nickname = alias ?? first_name ?? "anonymous",
+      (manager_id ?? 0) ?? director_id,
+      bonus = null ?? 1,
     }
     "#).unwrap(), @r"
     SELECT
-      COUNT(*),
-      COALESCE(SUM(salary), 0)
+      *,
+      COALESCE(alias, first_name, 'anonymous') AS nickname,
+      COALESCE(manager_id, 0, director_id),
+      1 AS bonus
     FROM
       employees
+    ");
+}
+
+#[test]
+fn test_unused_alias() {
+    // #1308
+    assert_snapshot!(compile(r###"
+    from account
+    select n = {account.name}
+    "###).unwrap_err(), @r"
+    Error:
+       ╭─[:3:16]
+       │
+     3 │     select n = {account.name}
+       │                ───────┬──────
+       │                       ╰──────── unexpected assign to `n`
+       │
+       │ Help: move assign into the tuple: `[n = ...]`
+    ───╯
+    ")
+}
+
+#[test]
+fn test_table_s_string_01() {
+    assert_snapshot!(compile(r#"
+    let main <relation> = s"SELECT DISTINCT ON first_name, age FROM employees ORDER BY age ASC"
+    "#).unwrap(),
+        @r"
+    WITH table_0 AS (
+      SELECT
+        DISTINCT ON first_name,
+        age
+      FROM
+        employees
+      ORDER BY
+        age ASC
+    )
+    SELECT
+      *
+    FROM
+      table_0
     "
     );
+}
+#[test]
+fn test_table_s_string_02() {
     assert_snapshot!(compile(r#"
-    prql target:sql.postgres
-    from developers
-    group team (
-        aggregate {
-            skill_width = count_distinct specialty,
-        }
+    s"""
+        SELECT DISTINCT ON first_name, id, age FROM employees ORDER BY age ASC
+    """
+    join s = s"SELECT * FROM salaries" (==id)
+    "#).unwrap(),
+        @r"
+    WITH table_0 AS (
+      SELECT
+        DISTINCT ON first_name,
+        id,
+        age
+      FROM
+        employees
+      ORDER BY
+        age ASC
+    ),
+    table_1 AS (
+      SELECT
+        *
+      FROM
+        salaries
     )
-    "#).unwrap(), @r"
     SELECT
-      team,
-      COUNT(DISTINCT specialty) AS skill_width
+      table_0.*,
+      table_1.*
     FROM
-      developers
-    GROUP BY
-      team
+      table_0
+      JOIN table_1 ON table_0.id = table_1.id
     "
-    )
+    );
 }
-
 #[test]
-#[ignore]
-fn test_prql_to_sql_2() {
-    let query = r#"
-from employees
-filter country == "USA"                           # Each line transforms the previous result.
-derive {                                         # This adds columns / variables.
-gross_salary = salary + payroll_tax,
-gross_cost = gross_salary + benefits_cost      # Variables can use other variables.
-}
-filter gross_cost > 0
-group {title, country} (
-aggregate  {                                 # `by` are the columns to group by.
-    average salary,                          # These are aggregation calcs run on each group.
-    sum     salary,
-    average gross_salary,
-    sum     gross_salary,
-    average gross_cost,
-    sum_gross_cost = sum gross_cost,
-    ct = count salary,
-}
-)
-sort sum_gross_cost
-filter ct > 200
-take 20
-"#;
-
-    let sql = compile(query).unwrap();
-    assert_snapshot!(sql, @r###"
+fn test_table_s_string_03() {
+    assert_snapshot!(compile(r#"
+    s"""SELECT * FROM employees"""
+    filter country == "USA"
+    "#).unwrap(),
+        @r"
     WITH table_0 AS (
       SELECT
-        title,
-        country,
-        salary + payroll_tax + benefits_cost AS _expr_0,
-        salary + payroll_tax AS _expr_1,
-        salary
+        *
       FROM
         employees
-      WHERE
-        country = 'USA'
     )
     SELECT
-      title,
-      country,
-      AVG(salary),
-      COALESCE(SUM(salary), 0),
-      AVG(_expr_1),
-      COALESCE(SUM(_expr_1), 0),
-      AVG(_expr_0),
-      COALESCE(SUM(_expr_0), 0) AS sum_gross_cost,
-      COUNT(*) AS ct
+      *
     FROM
       table_0
     WHERE
-      _expr_0 > 0
-    GROUP BY
-      title,
-      country
-    HAVING
-      COUNT(*) > 200
-    ORDER BY
-      sum_gross_cost
-    LIMIT
-      20
-    "###);
+      country = 'USA'
+    "
+    );
 }
-
 #[test]
-fn test_prql_to_sql_table() {
-    // table
-    let query = r#"
-    let newest_employees = (
-        from employees
-        sort tenure
-        take 50
+fn test_table_s_string_04() {
+    assert_snapshot!(compile(r#"
+    s"""SELECT * FROM employees"""
+    select {e = this}
+    filter e.country == "USA"
+    "#).unwrap(),
+        @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        employees
     )
-    let average_salaries = (
-        from salaries
-        group country (
-            aggregate {
-                average_country_salary = average salary
-            }
-        )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      country = 'USA'
+    "
+    );
+}
+#[test]
+fn test_table_s_string_05() {
+    assert_snapshot!(compile(r#"
+    let weeks_between = start end -> s"SELECT generate_series({start}, {end}, '1 week') as date"
+    let current_week = -> s"date(date_trunc('week', current_date))"
+
+    weeks_between @2022-06-03 (current_week + 4)
+    "#).unwrap(),
+        @r"
+    WITH table_0 AS (
+      SELECT
+        generate_series(
+          DATE '2022-06-03',
+          date(date_trunc('week', current_date)) + 4,
+          '1 week'
+        ) as date
     )
-    from newest_employees
-    join average_salaries (==country)
-    select {name, salary, average_country_salary}
-    "#;
-    let sql = compile(query).unwrap();
-    assert_snapshot!(sql,
+    SELECT
+      *
+    FROM
+      table_0
+    "
+    );
+}
+#[test]
+fn test_table_s_string_06() {
+    assert_snapshot!(compile(r#"
+    s"SELECT * FROM {default_db.x}"
+    "#).unwrap(),
         @r"
-    WITH newest_employees AS (
+    WITH table_0 AS (
       SELECT
         *
       FROM
-        employees
-      ORDER BY
-        tenure
-      LIMIT
-        50
-    ), average_salaries AS (
-      SELECT
-        country,
-        AVG(salary) AS average_country_salary
-      FROM
-        salaries
-      GROUP BY
-        country
+        x
     )
     SELECT
-      newest_employees.name,
-      newest_employees.salary,
-      average_salaries.average_country_salary
+      *
     FROM
-      newest_employees
-      JOIN average_salaries ON newest_employees.country = average_salaries.country
-    ORDER BY
-      employees.tenure
+      table_0
     "
     );
 }
 
 #[test]
-fn test_nonatomic() {
-    // A take, then two aggregates
-    let query = r#"
-        from employees
-        take 20
-        filter country == "USA"
-        group {title, country} (
-            aggregate {
-                salary = average salary
-            }
-        )
-        group {title, country} (
-            aggregate {
-                sum_gross_cost = average salary
-            }
-        )
-        sort sum_gross_cost
-    "#;
+fn test_direct_table_references() {
+    assert_snapshot!(compile(
+        r#"
+    from x
+    select s"{x}.field"
+    "#,
+    )
+    .unwrap_err(), @r#"
+    Error:
+       ╭─[:3:15]
+       │
+     3 │     select s"{x}.field"
+       │               ┬
+       │               ╰── table instance cannot be referenced directly
+       │
+       │ Help: did you forget to specify the column name?
+    ───╯
+    "#);
 
-    assert_snapshot!((compile(query).unwrap()), @r"
-    WITH table_1 AS (
-      SELECT
-        title,
-        country,
-        salary
-      FROM
-        employees
-      LIMIT
-        20
-    ), table_0 AS (
+    assert_snapshot!(compile(
+        r###"
+    from x
+    select x
+    "###,
+    )
+    .unwrap(), @r"
+    SELECT
+      *
+    FROM
+      x
+    ");
+}
+
+#[test]
+fn test_name_shadowing() {
+    assert_snapshot!(compile(
+        r###"
+    from x
+    select {a, a, a = a + 1}
+    "###).unwrap(),
+        @r"
+    SELECT
+      a AS _expr_0,
+      a + 1 AS a
+    FROM
+      x
+    "
+    );
+
+    assert_snapshot!(compile(
+        r###"
+    from x
+    select a
+    derive a
+    derive a = a + 1
+    derive a = a + 2
+    "###).unwrap(),
+        @r"
+    SELECT
+      a AS _expr_0,
+      a + 1 + 2 AS a
+    FROM
+      x
+    "
+    );
+
+    // only the latest definition of a name is in the output, even when it
+    // references the earlier one
+    assert_snapshot!(compile(
+        r###"
+    from x
+    derive {b = 1, c = 1}
+    derive {b = 2, c = c + 1}
+    group {g} (aggregate {c = sum c})
+    derive {c = c / 2}
+    "###).unwrap(),
+        @r"
+    WITH table_0 AS (
       SELECT
-        title,
-        country,
-        AVG(salary) AS _expr_0
+        g,
+        COALESCE(SUM(1 + 1), 0) AS _expr_0
       FROM
-        table_1
-      WHERE
-        country = 'USA'
+        x
       GROUP BY
-        title,
-        country
+        g
     )
     SELECT
-      title,
-      country,
-      AVG(_expr_0) AS sum_gross_cost
+      g,
+      _expr_0 / 2 AS c
     FROM
       table_0
-    GROUP BY
-      title,
-      country
-    ORDER BY
-      sum_gross_cost
     ");
+}
 
-    // A aggregate, then sort and filter
-    let query = r###"
-        from employees
-        group {title, country} (
-            aggregate {
-                sum_gross_cost = average salary
-            }
-        )
-        sort sum_gross_cost
-        filter sum_gross_cost > 0
-    "###;
+#[test]
+fn test_group_all() {
+    assert_snapshot!(compile(
+        r###"
+    prql target:sql.sqlite
 
-    assert_snapshot!((compile(query).unwrap()), @r"
-    SELECT
-      title,
-      country,
-      AVG(salary) AS sum_gross_cost
-    FROM
-      employees
-    GROUP BY
-      title,
-      country
-    HAVING
-      AVG(salary) > 0
-    ORDER BY
-      sum_gross_cost
+    from a=albums
+    group a.* (aggregate {count this})
+        "###).unwrap_err(), @"Error: Target dialect does not support * in this position.");
+
+    assert_snapshot!(compile(
+        r###"
+    from e=albums
+    group !{genre_id} (aggregate {count this})
+        "###).unwrap_err(), @r"
+    Error: Excluding columns `genre_id` from `albums` is not supported by dialect GenericDialect, because the columns of `albums` are not known
+    ↳ Hint: declare the columns of `albums` with `let albums <[{...}]>` within `module default_db`
+    ↳ Hint: or select the columns explicitly
     ");
 }
 
 #[test]
-/// Confirm a nonatomic table works.
-fn test_nonatomic_table() {
-    // A take, then two aggregates
-    let query = r#"
-    let a = (
-        from employees
-        take 50
-        group country (aggregate {s"count(*)"})
+fn test_output_column_deduplication() {
+    // #1249
+    assert_snapshot!(compile(
+        r#"
+    from report
+    derive r = s"RANK() OVER ()"
+    filter r == 1
+        "#).unwrap(),
+        @r"
+    WITH table_0 AS (
+      SELECT
+        *,
+        RANK() OVER () AS r
+      FROM
+        report
     )
-    from a
-    join b (==country)
-    select {name, salary, average_country_salary}
-"#;
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      r = 1
+    "
+    );
+}
+
+#[test]
+fn test_case_01() {
+    assert_snapshot!(compile(
+        r###"
+    from employees
+    derive display_name = case [
+        nickname != null => nickname,
+        true => f'{first_name} {last_name}'
+    ]
+        "###).unwrap(),
+        @r"
+    SELECT
+      *,
+      CASE
+        WHEN nickname IS NOT NULL THEN nickname
+        ELSE CONCAT(first_name, ' ', last_name)
+      END AS display_name
+    FROM
+      employees
+    "
+    );
+}
 
-    assert_snapshot!((compile(query).unwrap()), @r"
-    WITH table_0 AS (
-      SELECT
-        country
-      FROM
-        employees
-      LIMIT
-        50
-    ), a AS (
-      SELECT
-        country,
-        count(*)
-      FROM
-        table_0
-      GROUP BY
-        country
-    )
+#[test]
+fn test_case_else() {
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {sign = case [salary > 0 => "pos", salary < 0 => "neg"] else "zero"}
+    "#).unwrap(), @r"
     SELECT
-      b.name,
-      b.salary,
-      b.average_country_salary
+      *,
+      CASE
+        WHEN salary > 0 THEN 'pos'
+        WHEN salary < 0 THEN 'neg'
+        ELSE 'zero'
+      END AS sign
     FROM
-      a
-      JOIN b ON a.country = b.country
+      employees
     ");
+
+    assert_snapshot!(compile(r#"
+    from employees
+    derive {sign = case []}
+    "#).unwrap_err(), @r#"
+    Error:
+       ╭─[:3:20]
+       │
+     3 │     derive {sign = case []}
+       │                    ───┬───
+       │                       ╰───── `case` requires at least one branch
+       │
+       │ Help: add a branch, as in `case [x > 0 => "positive"]`
+    ───╯
+    "#);
 }
 
 #[test]
-fn test_table_names_between_splits_01() {
-    assert_snapshot!(compile(r###"
+fn test_case_02() {
+    assert_snapshot!(compile(
+        r###"
     from employees
-    join d = department (==dept_no)
-    take 10
-    derive emp_no = employees.emp_no
-    join s = salaries (==emp_no)
-    select {employees.emp_no, d.name, s.salary}
-    "###).unwrap(), @r"
-    WITH table_0 AS (
-      SELECT
-        employees.emp_no,
-        d.name
-      FROM
-        employees
-        JOIN department AS d ON employees.dept_no = d.dept_no
-      LIMIT
-        10
-    )
+    derive display_name = case [
+        nickname != null => nickname,
+        first_name != null => f'{first_name} {last_name}'
+    ]
+        "###).unwrap(),
+        @r"
     SELECT
-      table_0.emp_no,
-      table_0.name,
-      s.salary
+      *,
+      CASE
+        WHEN nickname IS NOT NULL THEN nickname
+        WHEN first_name IS NOT NULL THEN CONCAT(first_name, ' ', last_name)
+      END AS display_name
     FROM
-      table_0
-      JOIN salaries AS s ON table_0.emp_no = s.emp_no
-    ");
+      employees
+    "
+    );
 }
 
 #[test]
-fn test_table_names_between_splits_02() {
-    assert_snapshot!(compile(r###"
-    from e = employees
-    take 10
-    join salaries (==emp_no)
-    select {e.*, salaries.salary}
-    "###).unwrap(), @r"
+fn test_case_03() {
+    assert_snapshot!(compile(
+        r###"
+    from tracks
+    select category = case [
+        length > avg_length => 'long'
+    ]
+    group category (aggregate {count this})
+        "###).unwrap(),
+        @r"
     WITH table_0 AS (
       SELECT
-        *
+        CASE
+          WHEN length > avg_length THEN 'long'
+        END AS category,
+        length,
+        avg_length
       FROM
-        employees AS e
-      LIMIT
-        10
+        tracks
     )
     SELECT
-      table_0.*,
-      salaries.salary
+      category,
+      COUNT(*)
     FROM
       table_0
-      JOIN salaries ON table_0.emp_no = salaries.emp_no
-    ");
+    GROUP BY
+      category
+    "
+    );
 }
 
 #[test]
-fn test_table_alias_01() {
-    assert_snapshot!((compile(r###"
-    from e = employees
-    join salaries side:left (salaries.emp_no == e.emp_no)
-    group {e.emp_no} (
-        aggregate {
-            emp_salary = average salaries.salary
-        }
-    )
-    select {emp_no, emp_salary}
-    "###).unwrap()), @r"
+fn test_sql_options() {
+    let options = Options::default();
+    let sql = prqlc::compile("from x", &options).unwrap();
+
+    assert!(sql.contains('\n'));
+    assert!(sql.contains("-- Generated by"));
+
+    let options = Options::default().no_signature().no_format();
+    let sql = prqlc::compile("from x", &options).unwrap();
+
+    assert!(!sql.contains('\n'));
+    assert!(!sql.contains("-- Generated by"));
+}
+
+#[test]
+fn test_static_analysis() {
+    assert_snapshot!(compile(
+        r###"
+    from x
+    select {
+        a = (- (-3)),
+        b = !(!(!(!(!(true))))),
+        a3 = null ?? y,
+
+        a3 = case [
+            false == true => 1,
+            7 == 3 => 2,
+            7 == y => 3,
+            7.3 == 7.3 => 4,
+            z => 5,
+            true => 6
+        ],
+    }
+        "###).unwrap(),
+        @r"
     SELECT
-      e.emp_no,
-      AVG(salaries.salary) AS emp_salary
+      3 AS a,
+      false AS b,
+      CASE
+        WHEN 7 = y THEN 3
+        ELSE 4
+      END AS a3
     FROM
-      employees AS e
-      LEFT JOIN salaries ON salaries.emp_no = e.emp_no
-    GROUP BY
-      e.emp_no
-    ");
+      x
+    "
+    );
 }
 
 #[test]
-fn test_table_alias_02() {
-    assert_snapshot!((compile(r#"
-    from e = employees
-    select e.first_name
-    filter e.first_name == "Fred"
-    "#).unwrap()), @r"
+fn test_static_arithmetic() {
+    assert_snapshot!(compile(
+        r###"
+    from products
+    filter price > 1 + 1
+    derive {
+        a = 2 * 3 - 1,
+        b = 7 // 2,
+        c = 1.5 * 3,
+        d = 2.5 >= 1,
+        e = 4 % 3 == 1,
+        # left for the database to evaluate
+        f = 1 // 0,
+        g = 7 / 2,
+        h = price + 1 + 2,
+    }
+        "###).unwrap(),
+        @r"
     SELECT
-      first_name
-    FROM
-      employees AS e
+      *,
+      5 AS a,
+      3 AS b,
+      4.5 AS c,
+      true AS d,
+      true AS e,
+      FLOOR(ABS(1 / 0)) * SIGN(1) * SIGN(0) AS f,
+      7 / 2 AS g,
+      price + 1 + 2 AS h
+    FROM
+      products
     WHERE
-      first_name = 'Fred'
+      price > 2
     ");
 }
 
 #[test]
-fn test_targets() {
-    // Generic
-    let query = r###"
-    prql target:sql.generic
-    from Employees
-    select {FirstName, `last name`}
-    take 3
-    "###;
-
-    assert_snapshot!((compile(query).unwrap()), @r#"
-    SELECT
-      "FirstName",
-      "last name"
-    FROM
-      "Employees"
-    LIMIT
-      3
-    "#);
-
-    // SQL server
-    let query = r###"
-    prql target:sql.mssql
-    from Employees
-    select {FirstName, `last name`}
-    take 3
-    "###;
+fn test_closures_and_pipelines() {
+    assert_snapshot!(compile(
+        r#"
+    let addthree = a b c -> s"{a} || {b} || {c}"
+    let arg = myarg myfunc <func> -> ( myfunc myarg )
 
-    assert_snapshot!((compile(query).unwrap()), @r#"
+    from y
+    select x = (
+        addthree "apples"
+        arg "bananas"
+        arg "citrus"
+    )
+        "#).unwrap(),
+        @r"
     SELECT
-      "FirstName",
-      "last name"
+      'apples' || 'bananas' || 'citrus' AS x
     FROM
-      "Employees"
-    ORDER BY
-      (
-        SELECT
-          NULL
-      ) OFFSET 0 ROWS
-    FETCH FIRST
-      3 ROWS ONLY
-    "#);
-
-    // MySQL
-    let query = r###"
-    prql target:sql.mysql
-    from Employees
-    select {FirstName, `last name`}
-    take 3
-    "###;
+      y
+    "
+    );
+}
 
-    assert_snapshot!((compile(query).unwrap()), @r"
+#[test]
+fn test_basic_agg() {
+    assert_snapshot!(compile(r#"
+    from employees
+    aggregate {
+      count salary,
+      count this,
+    }
+    "#).unwrap(),
+        @r"
     SELECT
-      `FirstName`,
-      `last name`
+      COUNT(salary),
+      COUNT(*)
     FROM
-      `Employees`
-    LIMIT
-      3
-    ");
+      employees
+    "
+    );
 }
 
 #[test]
-fn test_target_clickhouse() {
-    let query = r###"
-    prql target:sql.clickhouse
-
-    from github_json
-    derive {event_type_dotted = `event.type`}
-    "###;
-
-    assert_snapshot!((compile(query).unwrap()), @r"
+fn test_exclude_columns_01() {
+    assert_snapshot!(compile(r#"
+    from tracks
+    select {track_id, title, composer, bytes}
+    select !{title, composer}
+    "#).unwrap(),
+        @r"
     SELECT
-      *,
-      `event.type` AS event_type_dotted
+      track_id,
+      bytes
     FROM
-      github_json
-    ");
+      tracks
+    "
+    );
 }
 
 #[test]
-fn test_ident_escaping() {
-    // Generic
-    let query = r#"
-    from `anim"ls`
-    derive {`čebela` = BeeName, medved = `bear's_name`}
-    "#;
-
-    assert_snapshot!((compile(query).unwrap()), @r#"
+fn test_exclude_columns_02() {
+    assert_snapshot!(compile(r#"
+    from tracks
+    select {track_id, title, composer, bytes}
+    group !{title, composer} (aggregate {count this})
+    "#).unwrap(),
+        @r"
     SELECT
-      *,
-      "BeeName" AS "čebela",
-      "bear's_name" AS medved
+      track_id,
+      bytes,
+      COUNT(*)
     FROM
-      "anim""ls"
-    "#);
-
-    // MySQL
-    let query = r#"
-    prql target:sql.mysql
-
-    from `anim"ls`
-    derive {`čebela` = BeeName, medved = `bear's_name`}
-    "#;
+      tracks
+    GROUP BY
+      track_id,
+      bytes
+    "
+    );
+}
 
-    assert_snapshot!((compile(query).unwrap()), @r#"
+#[test]
+fn test_exclude_columns_03() {
+    assert_snapshot!(compile(r#"
+    from artists
+    derive nick = name
+    select !{artists.*}
+    "#).unwrap(),
+        @r"
     SELECT
-      *,
-      `BeeName` AS `čebela`,
-      `bear's_name` AS medved
+      name AS nick
     FROM
-      `anim"ls`
-    "#);
+      artists
+    "
+    );
 }
 
 #[test]
-fn test_literal() {
-    let query = r###"
-    from employees
-    derive {always_true = true}
-    "###;
-
-    let sql = compile(query).unwrap();
-    assert_snapshot!(sql,
+fn test_exclude_columns_04() {
+    assert_snapshot!(compile(r#"
+    prql target:sql.bigquery
+    from tracks
+    select !{milliseconds,bytes}
+    "#).unwrap(),
         @r"
     SELECT
-      *,
-      true AS always_true
+      * EXCEPT (milliseconds, bytes)
     FROM
-      employees
+      tracks
     "
     );
 }
 
 #[test]
-fn test_same_column_names() {
-    // #820
-    let query = r###"
-let x = (
-from x_table
-select only_in_x = foo
-)
-
-let y = (
-from y_table
-select foo
-)
-
-from x
-join y (foo == only_in_x)
-"###;
-
-    assert_snapshot!(compile(query).unwrap(),
+fn test_exclude_columns_05() {
+    assert_snapshot!(compile(r#"
+    prql target:sql.snowflake
+    from tracks
+    select !{milliseconds,bytes}
+    "#).unwrap(),
         @r"
-    WITH x AS (
-      SELECT
-        foo AS only_in_x
-      FROM
-        x_table
-    ),
-    y AS (
-      SELECT
-        foo
-      FROM
-        y_table
-    )
     SELECT
-      x.only_in_x,
-      y.foo
+      * EXCLUDE (milliseconds, bytes)
     FROM
-      x
-      JOIN y ON y.foo = x.only_in_x
+      tracks
     "
     );
 }
 
 #[test]
-fn test_double_aggregate() {
-    // #941
-    compile(
-        r###"
-    from numbers
-    group {type} (
-        aggregate {
-            total_amt = sum amount,
-        }
-        aggregate {
-            max amount
-        }
-    )
-    "###,
-    )
-    .unwrap_err();
-
-    assert_snapshot!(compile(r###"
-    from numbers
-    group {`type`} (
-        aggregate {
-            total_amt = sum amount,
-            max amount
-        }
-    )
-    "###).unwrap(),
+fn test_exclude_columns_06() {
+    assert_snapshot!(compile(r#"
+    prql target:sql.duckdb
+    from tracks
+    select !{milliseconds,bytes}
+    "#).unwrap(),
         @r"
     SELECT
-      type,
-      COALESCE(SUM(amount), 0) AS total_amt,
-      MAX(amount)
+      * EXCLUDE (milliseconds, bytes)
     FROM
-      numbers
-    GROUP BY
-      type
+      tracks
     "
     );
 }
 
 #[test]
-fn test_window_function_coalesce() {
-    // #3587
-    assert_snapshot!(compile(r###"
-    from x
-    select {a, b=a}
-    window (
-      select {
-        cumsum_a=(sum a),
-        cumsum_b=(sum b)
-      }
-    )
-    "###).unwrap(),
+fn test_exclude_columns_07() {
+    assert_snapshot!(compile(r#"
+    prql target:sql.duckdb
+    from s"SELECT * FROM foo"
+    select !{bar}
+    "#).unwrap(),
         @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        foo
+    )
     SELECT
-      SUM(a) OVER () AS cumsum_a,
-      SUM(a) OVER () AS cumsum_b
+      * EXCLUDE (bar)
     FROM
-      x
+      table_0
     "
     );
 }
 
 #[test]
-fn test_casting() {
-    assert_snapshot!(compile(r###"
-    from x
-    select {a}
-    derive {
-        b = (a | as int) + 10,
-        c = (a | as int) - 10,
-        d = (a | as float) * 10,
-        e = (a | as float) / 10,
+fn test_exclude_columns_08() {
+    // columns of `tracks` are unknown, so they cannot be enumerated
+    assert_snapshot!(compile(r#"
+    prql target:sql.postgres
+    from tracks
+    select !{milliseconds,bytes}
+    "#).unwrap_err(),
+        @r"
+    Error: Excluding columns `milliseconds, bytes` from `tracks` is not supported by dialect PostgresDialect, because the columns of `tracks` are not known
+    ↳ Hint: declare the columns of `tracks` with `let tracks <[{...}]>` within `module default_db`
+    ↳ Hint: or select the columns explicitly
+    "
+    );
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.postgres
+    module default_db {
+        let tracks <[{track_id = int, name = text, milliseconds = int, bytes = int}]>
     }
-    "###).unwrap(),
+    from tracks
+    select !{milliseconds,bytes}
+    "#).unwrap(),
         @r"
     SELECT
-      a,
-      CAST(a AS int) + 10 AS b,
-      CAST(a AS int) - 10 AS c,
-      CAST(a AS float) * 10 AS d,
-      CAST(a AS float) / 10 AS e
+      track_id,
+      name
     FROM
-      x
+      tracks
     "
     );
 }
 
 #[test]
-fn test_toposort() {
-    // #1183
-
-    assert_snapshot!(compile(r###"
-    let b = (
-        from somesource
-    )
-
-    let a = (
-        from b
-    )
-
-    from b
-    "###).unwrap(),
+fn test_array_literal() {
+    assert_snapshot!(compile(r#"
+    prql target:sql.duckdb
+    from tracks
+    derive {sizes = [bytes, milliseconds, 0]}
+    "#).unwrap(),
         @r"
-    WITH b AS (
-      SELECT
-        *
-      FROM
-        somesource
-    )
     SELECT
-      *
+      *,
+      [bytes, milliseconds, 0] AS sizes
     FROM
-      b
+      tracks
+    "
+    );
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.postgres
+    from tracks
+    derive {sizes = [bytes, milliseconds, 0]}
+    "#).unwrap_err(),
+        @r"
+    Error:
+       ╭─[:4:21]
+       │
+     4 │     derive {sizes = [bytes, milliseconds, 0]}
+       │                     ────────────┬───────────
+       │                                 ╰───────────── unexpected array of values (not supported here)
+    ───╯
     "
     );
 }
 
 #[test]
-fn test_inline_tables() {
-    assert_snapshot!(compile(r###"
-    (
-        from employees
-        select {emp_id, name, surname, `type`, amount}
+fn test_custom_transforms() {
+    assert_snapshot!(compile(r#"
+    let my_transform = (
+        derive double = single * 2
+        sort name
     )
-    join s = (from salaries | select {emp_id, salary}) (==emp_id)
-    "###).unwrap(),
+
+    from tab
+    my_transform
+    take 3
+    "#).unwrap(),
         @r"
-    WITH table_0 AS (
-      SELECT
-        emp_id,
-        salary
-      FROM
-        salaries
-    )
     SELECT
-      employees.emp_id,
-      employees.name,
-      employees.surname,
-      employees.type,
-      employees.amount,
-      table_0.emp_id,
-      table_0.salary
+      *,
+      single * 2 AS double
     FROM
-      employees
-      JOIN table_0 ON employees.emp_id = table_0.emp_id
+      tab
+    ORDER BY
+      name
+    LIMIT
+      3
     "
     );
 }
 
 #[test]
-fn test_filter_and_select_unchanged_alias() {
-    // #1185
-
-    assert_snapshot!(compile(r###"
-    from account
-    filter account.name != null
-    select {name = account.name}
-    "###).unwrap(),
+fn test_name_inference() {
+    assert_snapshot!(compile(r#"
+    from albums
+    select {artist_id + album_id}
+    # nothing inferred infer
+    "#).unwrap(),
         @r"
     SELECT
-      name
+      artist_id + album_id
     FROM
-      account
-    WHERE
-      name IS NOT NULL
+      albums
     "
     );
-}
 
-#[test]
-fn test_filter_and_select_changed_alias() {
-    // #1185
-    assert_snapshot!(compile(r###"
-    from account
-    filter account.name != null
-    select {renamed_name = account.name}
-    "###).unwrap(),
+    let sql1 = compile(
+        r#"
+    from albums
+    select {artist_id}
+    select {albums.artist_id}
+    "#,
+    )
+    .unwrap();
+    let sql2 = compile(
+        r#"
+    from albums
+    select {albums.artist_id}
+    select {albums.artist_id}
+    "#,
+    )
+    .unwrap();
+    assert_eq!(sql1, sql2);
+
+    assert_snapshot!(
+        sql1,
         @r"
     SELECT
-      name AS renamed_name
+      artist_id
     FROM
-      account
-    WHERE
-      name IS NOT NULL
+      albums
     "
     );
+}
 
-    // #1207
+#[test]
+fn test_from_text_01() {
     assert_snapshot!(compile(r#"
-    from x
-    filter name != "Bob"
-    select name = name ?? "Default"
+    from_text format:csv """
+a,b,c
+1,2,3
+4,5,6
+    """
+    select {b, c}
     "#).unwrap(),
         @r"
+    WITH table_0 AS (
+      SELECT
+        '1' AS a,
+        '2' AS b,
+        '3' AS c
+      UNION
+      ALL
+      SELECT
+        '4' AS a,
+        '5' AS b,
+        '6' AS c
+    )
     SELECT
-      COALESCE(name, 'Default') AS name
+      b,
+      c
     FROM
-      x
-    WHERE
-      name <> 'Bob'
+      table_0
     "
     );
 }
 
 #[test]
-fn test_unused_alias() {
-    // #1308
-    assert_snapshot!(compile(r###"
-    from account
-    select n = {account.name}
-    "###).unwrap_err(), @r"
-    Error:
-       ╭─[:3:16]
-       │
-     3 │     select n = {account.name}
-       │                ───────┬──────
-       │                       ╰──────── unexpected assign to `n`
-       │
-       │ Help: move assign into the tuple: `[n = ...]`
-    ───╯
-    ")
-}
-
-#[test]
-fn test_table_s_string_01() {
+fn test_from_text_02() {
     assert_snapshot!(compile(r#"
-    let main <relation> = s"SELECT DISTINCT ON first_name, age FROM employees ORDER BY age ASC"
+    from_text format:json '''
+      [{"a": 1, "b": "x", "c": false }, {"a": 4, "b": "y", "c": null }]
+    '''
+    select {b, c}
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-        DISTINCT ON first_name,
-        age
-      FROM
-        employees
-      ORDER BY
-        age ASC
+        1 AS a,
+        'x' AS b,
+        false AS c
+      UNION
+      ALL
+      SELECT
+        4 AS a,
+        'y' AS b,
+        NULL AS c
     )
     SELECT
-      *
+      b,
+      c
     FROM
       table_0
     "
     );
 }
+
 #[test]
-fn test_table_s_string_02() {
+fn test_from_text_03() {
     assert_snapshot!(compile(r#"
-    s"""
-        SELECT DISTINCT ON first_name, id, age FROM employees ORDER BY age ASC
-    """
-    join s = s"SELECT * FROM salaries" (==id)
+    std.from_text format:json '''{
+        "columns": ["a", "b", "c"],
+        "data": [
+            [1, "x", false],
+            [4, "y", null]
+        ]
+    }'''
+    select {b, c}
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-        DISTINCT ON first_name,
-        id,
-        age
-      FROM
-        employees
-      ORDER BY
-        age ASC
-    ),
-    table_1 AS (
-      SELECT
-        *
-      FROM
-        salaries
+        1 AS a,
+        'x' AS b,
+        false AS c
+      UNION
+      ALL
+      SELECT
+        4 AS a,
+        'y' AS b,
+        NULL AS c
     )
     SELECT
-      table_0.*,
-      table_1.*
+      b,
+      c
     FROM
       table_0
-      JOIN table_1 ON table_0.id = table_1.id
     "
     );
 }
+
 #[test]
-fn test_table_s_string_03() {
+fn test_from_text_04() {
     assert_snapshot!(compile(r#"
-    s"""SELECT * FROM employees"""
-    filter country == "USA"
+    std.from_text 'a,b'
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-        *
-      FROM
-        employees
+        NULL AS a,
+        NULL AS b
+      WHERE
+        false
     )
     SELECT
-      *
+      a,
+      b
     FROM
       table_0
-    WHERE
-      country = 'USA'
     "
     );
 }
+
 #[test]
-fn test_table_s_string_04() {
+fn test_from_text_05() {
     assert_snapshot!(compile(r#"
-    s"""SELECT * FROM employees"""
-    select {e = this}
-    filter e.country == "USA"
+    std.from_text format:json '''{"columns": ["a", "b", "c"], "data": []}'''
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-        *
-      FROM
-        employees
+        NULL AS a,
+        NULL AS b,
+        NULL AS c
+      WHERE
+        false
     )
     SELECT
-      *
+      a,
+      b,
+      c
     FROM
       table_0
-    WHERE
-      country = 'USA'
     "
     );
 }
+
 #[test]
-fn test_table_s_string_05() {
+fn test_from_text_06() {
     assert_snapshot!(compile(r#"
-    let weeks_between = start end -> s"SELECT generate_series({start}, {end}, '1 week') as date"
-    let current_week = -> s"date(date_trunc('week', current_date))"
-
-    weeks_between @2022-06-03 (current_week + 4)
+    std.from_text ''
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-        generate_series(
-          DATE '2022-06-03',
-          date(date_trunc('week', current_date)) + 4,
-          '1 week'
-        ) as date
+        NULL
+      WHERE
+        false
     )
     SELECT
-      *
+      NULL
     FROM
       table_0
     "
     );
 }
+
 #[test]
-fn test_table_s_string_06() {
+fn test_from_text_07() {
     assert_snapshot!(compile(r#"
-    s"SELECT * FROM {default_db.x}"
+    std.from_text format:json '''{"columns": [], "data": [[], []]}'''
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-        *
-      FROM
-        x
+      UNION
+      ALL
+      SELECT
     )
     SELECT
-      *
+      NULL
     FROM
       table_0
     "
@@ -3707,1666 +8074,2052 @@ fn test_table_s_string_06() {
 }
 
 #[test]
-fn test_direct_table_references() {
-    assert_snapshot!(compile(
+fn test_header() {
+    // Test both target & version at the same time
+    let header = format!(
         r#"
-    from x
-    select s"{x}.field"
-    "#,
-    )
-    .unwrap_err(), @r#"
-    Error:
-       ╭─[:3:15]
-       │
-     3 │     select s"{x}.field"
-       │               ┬
-       │               ╰── table instance cannot be referenced directly
-       │
-       │ Help: did you forget to specify the column name?
-    ───╯
-    "#);
-
-    assert_snapshot!(compile(
-        r###"
-    from x
-    select x
-    "###,
-    )
-    .unwrap(), @r"
-    SELECT
-      *
-    FROM
-      x
-    ");
-}
-
-#[test]
-fn test_name_shadowing() {
-    assert_snapshot!(compile(
-        r###"
-    from x
-    select {a, a, a = a + 1}
-    "###).unwrap(),
-        @r"
-    SELECT
-      a AS _expr_0,
-      a AS _expr_0,
-      a + 1 AS a
-    FROM
-      x
-    "
+            prql target:sql.mssql version:"{}.{}"
+            "#,
+        env!("CARGO_PKG_VERSION_MAJOR"),
+        env!("CARGO_PKG_VERSION_MINOR")
     );
+    assert_snapshot!(compile(format!(r#"
+    {header}
 
-    assert_snapshot!(compile(
-        r###"
-    from x
-    select a
-    derive a
-    derive a = a + 1
-    derive a = a + 2
-    "###).unwrap(),
-        @r"
+    from a
+    take 5
+    "#).as_str()).unwrap(),@r"
     SELECT
-      a AS _expr_0,
-      a AS _expr_0,
-      a + 1,
-      a + 1 + 2 AS a
+      TOP 5 *
     FROM
-      x
-    "
-    );
+      a
+    ");
 }
-
 #[test]
-fn test_group_all() {
-    assert_snapshot!(compile(
-        r###"
-    prql target:sql.sqlite
+fn test_header_target_error() {
+    assert_snapshot!(compile(r#"
+    prql target:foo
+    from a
+    "#).unwrap_err(),@r#"Error: target `"foo"` not found"#);
 
-    from a=albums
-    group a.* (aggregate {count this})
-        "###).unwrap_err(), @"Error: Target dialect does not support * in this position.");
+    assert_snapshot!(compile(r#"
+    prql target:sql.foo
+    from a
+    "#).unwrap_err(),@r#"Error: target `"sql.foo"` not found"#);
 
-    assert_snapshot!(compile(
-        r###"
-    from e=albums
-    group !{genre_id} (aggregate {count this})
-        "###).unwrap_err(), @"Error: Excluding columns not supported as this position");
-}
+    assert_snapshot!(compile(r#"
+    prql target:foo.bar
+    from a
+    "#).unwrap_err(),@r#"Error: target `"foo.bar"` not found"#);
 
-#[test]
-fn test_output_column_deduplication() {
-    // #1249
-    assert_snapshot!(compile(
-        r#"
-    from report
-    derive r = s"RANK() OVER ()"
-    filter r == 1
-        "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        *,
-        RANK() OVER () AS r
-      FROM
-        report
-    )
-    SELECT
-      *
-    FROM
-      table_0
-    WHERE
-      r = 1
-    "
-    );
+    // TODO: Can we use the span of:
+    // - Ideally just `dialect`?
+    // - At least not the first empty line?
+    assert_snapshot!(compile(r#"
+    prql dialect:foo.bar
+    from a
+    "#).unwrap_err(),@r"
+    Error:
+       ╭─[:1:1]
+       │
+     1 │ ╭─▶
+     2 │ ├─▶     prql dialect:foo.bar
+       │ │
+       │ ╰────────────────────────────── unknown query definition arguments `dialect`
+    ───╯
+    ");
 }
 
 #[test]
-fn test_case_01() {
-    assert_snapshot!(compile(
-        r###"
-    from employees
-    derive display_name = case [
-        nickname != null => nickname,
-        true => f'{first_name} {last_name}'
-    ]
-        "###).unwrap(),
-        @r"
-    SELECT
-      *,
-      CASE
-        WHEN nickname IS NOT NULL THEN nickname
-        ELSE CONCAT(first_name, ' ', last_name)
-      END AS display_name
-    FROM
-      employees
-    "
-    );
-}
+fn shortest_prql_version() {
+    let mut escape_version = insta::Settings::new();
+    escape_version.add_filter(r"'.*'", "[VERSION]");
+    escape_version.bind(|| {
+        assert_snapshot!(compile(r#"[{version = prql.version}]"#).unwrap(),@r"
+        WITH table_0 AS (
+          SELECT
+            [VERSION] AS version
+        )
+        SELECT
+          version
+        FROM
+          table_0
+        ");
 
-#[test]
-fn test_case_02() {
-    assert_snapshot!(compile(
-        r###"
-    from employees
-    derive display_name = case [
-        nickname != null => nickname,
-        first_name != null => f'{first_name} {last_name}'
-    ]
-        "###).unwrap(),
-        @r"
-    SELECT
-      *,
-      CASE
-        WHEN nickname IS NOT NULL THEN nickname
-        WHEN first_name IS NOT NULL THEN CONCAT(first_name, ' ', last_name)
-        ELSE NULL
-      END AS display_name
-    FROM
-      employees
-    "
-    );
+        assert_snapshot!(compile(r#"
+    from x
+    derive y = std.prql.version
+    "#).unwrap(),@r"
+        SELECT
+          *,
+          [VERSION] AS y
+        FROM
+          x
+        ");
+    })
 }
 
 #[test]
-fn test_case_03() {
-    assert_snapshot!(compile(
-        r###"
-    from tracks
-    select category = case [
-        length > avg_length => 'long'
-    ]
-    group category (aggregate {count this})
-        "###).unwrap(),
+fn test_loop() {
+    assert_snapshot!(compile(r#"
+    [{n = 1}]
+    select n = n - 2
+    loop (
+        select n = n+1
+        filter n<5
+    )
+    select n = n * 2
+    take 4
+    "#).unwrap(),
         @r"
-    WITH table_0 AS (
+    WITH RECURSIVE table_0 AS (
       SELECT
-        CASE
-          WHEN length > avg_length THEN 'long'
-          ELSE NULL
-        END AS category,
-        length,
-        avg_length
+        1 AS n
+    ),
+    table_1 AS (
+      SELECT
+        n - 2 AS _expr_0
       FROM
-        tracks
+        table_0
+      UNION
+      ALL
+      SELECT
+        _expr_1
+      FROM
+        (
+          SELECT
+            _expr_0 + 1 AS _expr_1
+          FROM
+            table_1
+        ) AS table_4
+      WHERE
+        _expr_1 < 5
     )
     SELECT
-      category,
-      COUNT(*)
+      _expr_0 * 2 AS n
     FROM
-      table_0
-    GROUP BY
-      category
+      table_1 AS table_3
+    LIMIT
+      4
     "
     );
 }
 
 #[test]
-fn test_sql_options() {
-    let options = Options::default();
-    let sql = prqlc::compile("from x", &options).unwrap();
-
-    assert!(sql.contains('\n'));
-    assert!(sql.contains("-- Generated by"));
-
-    let options = Options::default().no_signature().no_format();
-    let sql = prqlc::compile("from x", &options).unwrap();
-
-    assert!(!sql.contains('\n'));
-    assert!(!sql.contains("-- Generated by"));
-}
-
-#[test]
-fn test_static_analysis() {
-    assert_snapshot!(compile(
-        r###"
-    from x
-    select {
-        a = (- (-3)),
-        b = !(!(!(!(!(true))))),
-        a3 = null ?? y,
-
-        a3 = case [
-            false == true => 1,
-            7 == 3 => 2,
-            7 == y => 3,
-            7.3 == 7.3 => 4,
-            z => 5,
-            true => 6
-        ],
-    }
-        "###).unwrap(),
+fn test_loop_2() {
+    assert_snapshot!(compile(r#"
+    read_csv 'employees.csv'
+    filter last_name=="Mitchell"
+    loop (
+      join manager=employees (manager.employee_id==this.reports_to)
+      select manager.*
+    )
+    "#).unwrap(),
         @r"
+    WITH RECURSIVE table_0 AS (
+      SELECT
+        *
+      FROM
+        read_csv('employees.csv')
+    ),
+    table_1 AS (
+      SELECT
+        *
+      FROM
+        table_0
+      WHERE
+        last_name = 'Mitchell'
+      UNION
+      ALL
+      SELECT
+        manager.*
+      FROM
+        table_1
+        JOIN employees AS manager ON manager.employee_id = table_1.reports_to
+    )
     SELECT
-      3 AS a,
-      false AS b,
-      y AS _expr_0,
-      CASE
-        WHEN 7 = y THEN 3
-        ELSE 4
-      END AS a3
+      *
     FROM
-      x
+      table_1 AS table_2
     "
     );
 }
 
 #[test]
-fn test_closures_and_pipelines() {
-    assert_snapshot!(compile(
-        r#"
-    let addthree = a b c -> s"{a} || {b} || {c}"
-    let arg = myarg myfunc <func> -> ( myfunc myarg )
-
-    from y
-    select x = (
-        addthree "apples"
-        arg "bananas"
-        arg "citrus"
+fn test_loop_factorial() {
+    // a sequence of factorials, where each step computes the next row from
+    // the previous one
+    assert_snapshot!(compile(r#"
+    from [{n = 1, factorial = 1}]
+    loop (
+        filter n < 5
+        select {next_n = n + 1, factorial = factorial * (n + 1)}
+        select {n = next_n, factorial}
+    )
+    sort n
+    "#).unwrap(), @r"
+    WITH RECURSIVE table_0 AS (
+      SELECT
+        1 AS n,
+        1 AS factorial
+    ),
+    table_1 AS (
+      SELECT
+        n,
+        factorial
+      FROM
+        table_0
+      UNION
+      ALL
+      SELECT
+        n + 1,
+        factorial * (n + 1)
+      FROM
+        table_1
+      WHERE
+        n < 5
     )
-        "#).unwrap(),
-        @r"
     SELECT
-      'apples' || 'bananas' || 'citrus' AS x
+      n,
+      factorial
     FROM
-      y
-    "
-    );
+      table_1 AS table_2
+    ORDER BY
+      n
+    ");
+
+    assert_snapshot!(compile(r#"
+    prql target:sql.glaredb
+
+    from [{n = 1}]
+    loop (filter n < 5 | select n = n + 1)
+    "#).unwrap_err(), @"Error: The dialect GlareDbDialect does not support recursive CTEs, which are required by `loop`");
 }
 
 #[test]
-fn test_basic_agg() {
+fn test_params() {
     assert_snapshot!(compile(r#"
-    from employees
-    aggregate {
-      count salary,
-      count this,
+    from invoices
+    select {i = this}
+    filter $1 <= i.date || i.date <= $2
+    select {
+        i.id,
+        i.total,
     }
+    filter i.total > $3
     "#).unwrap(),
         @r"
     SELECT
-      COUNT(*),
-      COUNT(*)
+      id,
+      total
     FROM
-      employees
+      invoices
+    WHERE
+      (
+        $1 <= date
+        OR date <= $2
+      )
+      AND total > $3
     "
-    );
+    )
 }
 
+// for #1969
 #[test]
-fn test_exclude_columns_01() {
-    assert_snapshot!(compile(r#"
-    from tracks
-    select {track_id, title, composer, bytes}
-    select !{title, composer}
-    "#).unwrap(),
-        @r"
+fn test_datetime() {
+    let query = &r#"
+        from test_table
+        select {date = @2022-12-31, time = @08:30, timestamp = @2020-01-01T13:19:55-0800}
+        "#;
+
+    assert_snapshot!(
+                compile(query).unwrap(),
+                @r"
     SELECT
-      track_id,
-      bytes
+      DATE '2022-12-31' AS date,
+      TIME '08:30' AS time,
+      TIMESTAMP '2020-01-01T13:19:55-0800' AS timestamp
     FROM
-      tracks
+      test_table
     "
-    );
+    )
 }
 
 #[test]
-fn test_exclude_columns_02() {
+fn test_datetime_sqlite() {
+    // for #1969
+
     assert_snapshot!(compile(r#"
-    from tracks
-    select {track_id, title, composer, bytes}
-    group !{title, composer} (aggregate {count this})
+    prql target:sql.sqlite
+
+    from x
+    select {
+        date = @2022-12-31,
+        time = @08:30,
+        time_tz = @03:05+08:00,
+        time_tz2 = @03:05+0800,
+        timestamp1 = @2020-01-01T13:19:55-0800,
+        timestamp2 = @2021-03-14T03:05+0800,
+        timestamp3 = @2021-03-14T03:05+08:00,
+    }
     "#).unwrap(),
         @r"
     SELECT
-      track_id,
-      bytes,
-      COUNT(*)
+      DATE('2022-12-31') AS date,
+      TIME('08:30') AS time,
+      TIME('03:05+08:00') AS time_tz,
+      TIME('03:05+08:00') AS time_tz2,
+      DATETIME('2020-01-01T13:19:55-08:00') AS timestamp1,
+      DATETIME('2021-03-14T03:05+08:00') AS timestamp2,
+      DATETIME('2021-03-14T03:05+08:00') AS timestamp3
     FROM
-      tracks
-    GROUP BY
-      track_id,
-      bytes
+      x
     "
     );
 }
 
 #[test]
-fn test_exclude_columns_03() {
+fn test_datetime_parsing() {
     assert_snapshot!(compile(r#"
-    from artists
-    derive nick = name
-    select !{artists.*}
+    from test_tables
+    select {date = @2022-12-31, time = @08:30, timestamp = @2020-01-01T13:19:55-0800}
     "#).unwrap(),
         @r"
     SELECT
-      name AS nick
+      DATE '2022-12-31' AS date,
+      TIME '08:30' AS time,
+      TIMESTAMP '2020-01-01T13:19:55-0800' AS timestamp
     FROM
-      artists
+      test_tables
     "
     );
 }
 
 #[test]
-fn test_exclude_columns_04() {
+fn test_lower() {
     assert_snapshot!(compile(r#"
-    prql target:sql.bigquery
-    from tracks
-    select !{milliseconds,bytes}
+    from test_tables
+    derive {lower_name = (name | text.lower)}
     "#).unwrap(),
         @r"
     SELECT
-      * EXCEPT (milliseconds, bytes)
+      *,
+      LOWER(name) AS lower_name
     FROM
-      tracks
+      test_tables
     "
     );
 }
 
 #[test]
-fn test_exclude_columns_05() {
+fn test_upper() {
     assert_snapshot!(compile(r#"
-    prql target:sql.snowflake
-    from tracks
-    select !{milliseconds,bytes}
+    from test_tables
+    derive {upper_name = text.upper name}
+    select {upper_name}
     "#).unwrap(),
         @r"
     SELECT
-      * EXCLUDE (milliseconds, bytes)
+      UPPER(name) AS upper_name
     FROM
-      tracks
+      test_tables
     "
     );
 }
 
 #[test]
-fn test_exclude_columns_06() {
+fn test_1535() {
     assert_snapshot!(compile(r#"
-    prql target:sql.duckdb
-    from tracks
-    select !{milliseconds,bytes}
+    from x.y.z
     "#).unwrap(),
         @r"
     SELECT
-      * EXCLUDE (milliseconds, bytes)
+      *
     FROM
-      tracks
+      x.y.z
     "
     );
 }
 
 #[test]
-fn test_exclude_columns_07() {
+fn test_read_parquet_duckdb() {
     assert_snapshot!(compile(r#"
-    prql target:sql.duckdb
-    from s"SELECT * FROM foo"
-    select !{bar}
+    std.read_parquet 'x.parquet'
+    join (std.read_parquet "y.parquet") (==foo)
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
         *
       FROM
-        foo
+        read_parquet('x.parquet')
+    ),
+    table_1 AS (
+      SELECT
+        *
+      FROM
+        read_parquet('y.parquet')
     )
     SELECT
-      * EXCLUDE (bar)
+      table_0.*,
+      table_1.*
     FROM
       table_0
+      JOIN table_1 ON table_0.foo = table_1.foo
     "
     );
+
+    // TODO: `from x=(read_parquet 'x.parquet')` currently fails
 }
 
 #[test]
-fn test_custom_transforms() {
+fn test_excess_columns() {
+    // https://github.com/PRQL/prql/issues/2079
     assert_snapshot!(compile(r#"
-    let my_transform = (
-        derive double = single * 2
-        sort name
-    )
-
-    from tab
-    my_transform
-    take 3
+    from tracks
+    derive d = track_id
+    sort d
+    select {title}
     "#).unwrap(),
         @r"
     SELECT
-      *,
-      single * 2 AS double
+      title
     FROM
-      tab
+      tracks
     ORDER BY
-      name
-    LIMIT
-      3
+      track_id
     "
     );
 }
 
 #[test]
-fn test_name_inference() {
+fn test_regex_search() {
     assert_snapshot!(compile(r#"
-    from albums
-    select {artist_id + album_id}
-    # nothing inferred infer
+    from tracks
+    derive is_bob_marley = artist_name ~= "Bob\\sMarley"
     "#).unwrap(),
         @r"
     SELECT
-      artist_id + album_id
+      *,
+      REGEXP(artist_name, 'Bob\sMarley') AS is_bob_marley
     FROM
-      albums
+      tracks
     "
     );
+}
 
-    let sql1 = compile(
-        r#"
-    from albums
-    select {artist_id}
-    select {albums.artist_id}
-    "#,
-    )
-    .unwrap();
-    let sql2 = compile(
+#[rstest]
+#[case::postgres(sql::Dialect::Postgres, "artist_name ~ 'Bob'")]
+#[case::mysql(sql::Dialect::MySql, "REGEXP_LIKE(artist_name, 'Bob', 'c')")]
+#[case::sqlite(sql::Dialect::SQLite, "artist_name REGEXP 'Bob'")] // needs a registered `regexp` function
+#[case::bigquery(sql::Dialect::BigQuery, "REGEXP_CONTAINS(artist_name, 'Bob')")]
+#[case::duckdb(sql::Dialect::DuckDb, "REGEXP_MATCHES(artist_name, 'Bob')")]
+fn regex_not_search(#[case] dialect: sql::Dialect, #[case] expected_search: &'static str) {
+    let query = r#"
+  from tracks
+  filter artist_name !~= "Bob"
+  "#;
+    let expected = format!(
         r#"
-    from albums
-    select {albums.artist_id}
-    select {albums.artist_id}
-    "#,
-    )
-    .unwrap();
-    assert_eq!(sql1, sql2);
-
-    assert_snapshot!(
-        sql1,
-        @r"
-    SELECT
-      artist_id
-    FROM
-      albums
-    "
+SELECT
+  *
+FROM
+  tracks
+WHERE
+  NOT {expected_search}
+"#
     );
-}
-
-#[test]
-fn test_from_text_01() {
-    assert_snapshot!(compile(r#"
-    from_text format:csv """
-a,b,c
-1,2,3
-4,5,6
-    """
-    select {b, c}
-    "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        '1' AS a,
-        '2' AS b,
-        '3' AS c
-      UNION
-      ALL
-      SELECT
-        '4' AS a,
-        '5' AS b,
-        '6' AS c
+    assert_eq!(
+        compile_with_sql_dialect(query, dialect).unwrap(),
+        expected.trim_start()
     )
-    SELECT
-      b,
-      c
-    FROM
-      table_0
-    "
-    );
 }
 
 #[test]
-fn test_from_text_02() {
+fn test_intervals() {
     assert_snapshot!(compile(r#"
-    from_text format:json '''
-      [{"a": 1, "b": "x", "c": false }, {"a": 4, "b": "y", "c": null }]
-    '''
-    select {b, c}
+    from foo
+    select dt = 1years + 1months + 1weeks + 1days + 1hours + 1minutes + 1seconds + 1milliseconds + 1microseconds
     "#).unwrap(),
         @r"
-    WITH table_0 AS (
-      SELECT
-        1 AS a,
-        'x' AS b,
-        false AS c
-      UNION
-      ALL
-      SELECT
-        4 AS a,
-        'y' AS b,
-        NULL AS c
-    )
     SELECT
-      b,
-      c
+      INTERVAL 13 MONTH + INTERVAL 1 WEEK + INTERVAL 1 DAY + INTERVAL 1 HOUR + INTERVAL 1 MINUTE + INTERVAL 1 SECOND + INTERVAL 1 MILLISECOND + INTERVAL 1 MICROSECOND AS dt
     FROM
-      table_0
+      foo
     "
     );
 }
 
 #[test]
-fn test_from_text_03() {
-    assert_snapshot!(compile(r#"
-    std.from_text format:json '''{
-        "columns": ["a", "b", "c"],
-        "data": [
-            [1, "x", false],
-            [4, "y", null]
-        ]
-    }'''
-    select {b, c}
-    "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        1 AS a,
-        'x' AS b,
-        false AS c
-      UNION
-      ALL
-      SELECT
-        4 AS a,
-        'y' AS b,
-        NULL AS c
-    )
+fn test_intervals_combined() {
+    let query = r#"
+    from projects
+    derive {
+      review = start + (1years + 6months),
+      reminder = 2hours - 30minutes,
+      deadline = 1weeks + 2days,
+      renewal = 1months + 30days,
+      overflow = 9223372036854775807years + 1months,
+    }
+    "#;
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::Postgres).unwrap(), @r#"
     SELECT
-      b,
-      c
+      *,
+      "start" + INTERVAL '18 MONTH' AS review,
+      INTERVAL '90 MINUTE' AS reminder,
+      INTERVAL '9 DAY' AS deadline,
+      INTERVAL '1 MONTH' + INTERVAL '30 DAY' AS renewal,
+      INTERVAL '9223372036854775807 YEAR' + INTERVAL '1 MONTH' AS overflow
     FROM
-      table_0
-    "
-    );
+      projects
+    "#);
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::MySql).unwrap(), @r"
+    SELECT
+      *,
+      `start` + INTERVAL 18 MONTH AS review,
+      INTERVAL 90 MINUTE AS reminder,
+      INTERVAL 9 DAY AS deadline,
+      INTERVAL 1 MONTH + INTERVAL 30 DAY AS renewal,
+      INTERVAL 9223372036854775807 YEAR + INTERVAL 1 MONTH AS overflow
+    FROM
+      projects
+    ");
 }
 
 #[test]
-fn test_from_text_04() {
+fn test_into() {
     assert_snapshot!(compile(r#"
-    std.from_text 'a,b'
+    from data
+    into table_a
+
+    from table_a
+    select {x, y}
     "#).unwrap(),
         @r"
-    WITH table_0 AS (
+    WITH table_a AS (
       SELECT
-        NULL AS a,
-        NULL AS b
-      WHERE
-        false
+        *
+      FROM
+        data
     )
     SELECT
-      a,
-      b
+      x,
+      y
     FROM
-      table_0
+      table_a
     "
     );
 }
 
 #[test]
-fn test_from_text_05() {
+fn test_array_01() {
+    compile(
+        r#"
+    let a = [1, 2, false]
+
+    from x
+    "#,
+    )
+    .unwrap();
+
     assert_snapshot!(compile(r#"
-    std.from_text format:json '''{"columns": ["a", "b", "c"], "data": []}'''
+    let my_relation = [
+        {a = 3, b = false},
+        {a = 4, b = true},
+    ]
+
+    let main = (my_relation | filter b)
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-        NULL AS a,
-        NULL AS b,
-        NULL AS c
-      WHERE
-        false
+        3 AS a,
+        false AS b
+      UNION
+      ALL
+      SELECT
+        4 AS a,
+        true AS b
+    ),
+    my_relation AS (
+      SELECT
+        a,
+        b
+      FROM
+        table_0
     )
     SELECT
       a,
-      b,
-      c
+      b
     FROM
-      table_0
+      my_relation
+    WHERE
+      b
     "
     );
 }
 
 #[test]
-fn test_from_text_06() {
+fn test_array_02() {
     assert_snapshot!(compile(r#"
-    std.from_text ''
-    "#).unwrap(),
-        @r"
+    from [
+      {x = null},
+      {x = '1'},
+    ]
+    "#)
+    .unwrap(), @r"
     WITH table_0 AS (
       SELECT
-        NULL
-      WHERE
-        false
+        NULL AS x
+      UNION
+      ALL
+      SELECT
+        '1' AS x
     )
     SELECT
-      NULL
+      x
     FROM
       table_0
-    "
-    );
+    ");
 }
 
 #[test]
-fn test_from_text_07() {
+fn test_double_stars() {
     assert_snapshot!(compile(r#"
-    std.from_text format:json '''{"columns": [], "data": [[], []]}'''
+    from tb1
+    join tb2 (==c2)
+    take 5
+    filter (tb2.c3 < 100)
     "#).unwrap(),
         @r"
     WITH table_0 AS (
       SELECT
-      UNION
-      ALL
-      SELECT
+        tb1.*,
+        tb2.c2 AS _expr_0,
+        tb2.*
+      FROM
+        tb1
+        JOIN tb2 ON tb1.c2 = tb2.c2
+      LIMIT
+        5
     )
     SELECT
-      NULL
+      *
     FROM
       table_0
+    WHERE
+      c3 < 100
     "
     );
-}
-
-#[test]
-fn test_header() {
-    // Test both target & version at the same time
-    let header = format!(
-        r#"
-            prql target:sql.mssql version:"{}.{}"
-            "#,
-        env!("CARGO_PKG_VERSION_MAJOR"),
-        env!("CARGO_PKG_VERSION_MINOR")
-    );
-    assert_snapshot!(compile(format!(r#"
-    {header}
-
-    from a
-    take 5
-    "#).as_str()).unwrap(),@r"
-    SELECT
-      *
-    FROM
-      a
-    ORDER BY
-      (
-        SELECT
-          NULL
-      ) OFFSET 0 ROWS
-    FETCH FIRST
-      5 ROWS ONLY
-    ");
-}
-#[test]
-fn test_header_target_error() {
-    assert_snapshot!(compile(r#"
-    prql target:foo
-    from a
-    "#).unwrap_err(),@r#"Error: target `"foo"` not found"#);
-
-    assert_snapshot!(compile(r#"
-    prql target:sql.foo
-    from a
-    "#).unwrap_err(),@r#"Error: target `"sql.foo"` not found"#);
-
-    assert_snapshot!(compile(r#"
-    prql target:foo.bar
-    from a
-    "#).unwrap_err(),@r#"Error: target `"foo.bar"` not found"#);
 
-    // TODO: Can we use the span of:
-    // - Ideally just `dialect`?
-    // - At least not the first empty line?
     assert_snapshot!(compile(r#"
-    prql dialect:foo.bar
-    from a
-    "#).unwrap_err(),@r"
-    Error:
-       ╭─[:1:1]
-       │
-     1 │ ╭─▶
-     2 │ ├─▶     prql dialect:foo.bar
-       │ │
-       │ ╰────────────────────────────── unknown query definition arguments `dialect`
-    ───╯
-    ");
-}
-
-#[test]
-fn shortest_prql_version() {
-    let mut escape_version = insta::Settings::new();
-    escape_version.add_filter(r"'.*'", "[VERSION]");
-    escape_version.bind(|| {
-        assert_snapshot!(compile(r#"[{version = prql.version}]"#).unwrap(),@r"
-        WITH table_0 AS (
-          SELECT
-            [VERSION] AS version
-        )
-        SELECT
-          version
-        FROM
-          table_0
-        ");
-
-        assert_snapshot!(compile(r#"
-    from x
-    derive y = std.prql.version
-    "#).unwrap(),@r"
-        SELECT
-          *,
-          [VERSION] AS y
-        FROM
-          x
-        ");
-    })
-}
+    prql target:sql.duckdb
 
-#[test]
-fn test_loop() {
-    assert_snapshot!(compile(r#"
-    [{n = 1}]
-    select n = n - 2
-    loop (
-        select n = n+1
-        filter n<5
-    )
-    select n = n * 2
-    take 4
+    from tb1
+    join tb2 (==c2)
+    take 5
+    filter (tb2.c3 < 100)
     "#).unwrap(),
         @r"
-    WITH RECURSIVE table_0 AS (
-      SELECT
-        1 AS n
-    ),
-    table_1 AS (
-      SELECT
-        n - 2 AS _expr_0
-      FROM
-        table_0
-      UNION
-      ALL
+    WITH table_0 AS (
       SELECT
-        _expr_1
-      FROM
-        (
-          SELECT
-            _expr_0 + 1 AS _expr_1
-          FROM
-            table_1
-        ) AS table_4
-      WHERE
-        _expr_1 < 5
+        tb1.*,
+        tb2.c2 AS _expr_0,
+        tb2.* EXCLUDE (c2)
+      FROM
+        tb1
+        JOIN tb2 ON tb1.c2 = tb2.c2
+      LIMIT
+        5
     )
     SELECT
-      _expr_0 * 2 AS n
+      *
     FROM
-      table_1 AS table_3
-    LIMIT
-      4
+      table_0
+    WHERE
+      c3 < 100
     "
     );
 }
 
 #[test]
-fn test_loop_2() {
+fn test_lineage() {
+    // #2627
     assert_snapshot!(compile(r#"
-    read_csv 'employees.csv'
-    filter last_name=="Mitchell"
-    loop (
-      join manager=employees (manager.employee_id==this.reports_to)
-      select manager.*
-    )
+    from_text """
+    a
+    1
+    2
+    3
+    """
+    derive a = a
     "#).unwrap(),
         @r"
-    WITH RECURSIVE table_0 AS (
+    WITH table_0 AS (
       SELECT
-        *
-      FROM
-        read_csv('employees.csv')
-    ),
-    table_1 AS (
+        '    1' AS a
+      UNION
+      ALL
       SELECT
-        *
-      FROM
-        table_0
-      WHERE
-        last_name = 'Mitchell'
+        '    2' AS a
       UNION
       ALL
       SELECT
-        manager.*
-      FROM
-        table_1
-        JOIN employees AS manager ON manager.employee_id = table_1.reports_to
+        '    3' AS a
     )
     SELECT
-      *
+      a
     FROM
-      table_1 AS table_2
+      table_0
     "
     );
-}
 
-#[test]
-fn test_params() {
+    // #2392
     assert_snapshot!(compile(r#"
-    from invoices
-    select {i = this}
-    filter $1 <= i.date || i.date <= $2
-    select {
-        i.id,
-        i.total,
-    }
-    filter i.total > $3
+    from_text format:json """{
+        "columns": ["a"],
+        "data": [[1]]
+    }"""
+    derive a = a + 1
     "#).unwrap(),
         @r"
+    WITH table_0 AS (
+      SELECT
+        1 AS a
+    )
     SELECT
-      id,
-      total
+      a + 1 AS a
     FROM
-      invoices
-    WHERE
-      (
-        $1 <= date
-        OR date <= $2
-      )
-      AND total > $3
+      table_0
     "
-    )
+    );
 }
 
-// for #1969
 #[test]
-fn test_datetime() {
-    let query = &r#"
-        from test_table
-        select {date = @2022-12-31, time = @08:30, timestamp = @2020-01-01T13:19:55-0800}
-        "#;
+fn test_type_as_column_name() {
+    // #2503
+    assert_snapshot!(compile(r#"
+    let f = tbl -> (
+      t = tbl
+      select t.date
+    )
 
-    assert_snapshot!(
-                compile(query).unwrap(),
-                @r"
+    from foo
+    f"#)
+    .unwrap(), @r"
     SELECT
-      DATE '2022-12-31' AS date,
-      TIME '08:30' AS time,
-      TIMESTAMP '2020-01-01T13:19:55-0800' AS timestamp
+      date
     FROM
-      test_table
-    "
-    )
+      foo AS t
+    ");
 }
 
 #[test]
-fn test_datetime_sqlite() {
-    // for #1969
+fn test_error_code() {
+    let err = compile(
+        r###"
+    let a = (from x)
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(err.inner[0].code.as_ref().unwrap(), "E0001");
+}
 
-    assert_snapshot!(compile(r#"
-    prql target:sql.sqlite
+#[test]
+fn large_query() {
+    // This was causing a stack overflow on Windows, ref https://github.com/PRQL/prql/issues/2857
+    compile(
+        r###"
+from employees
+filter gross_cost > 0
+group {title} (
+  aggregate {
+    ct = count this,
+  }
+)
+sort ct
+filter ct > 200
+take 20
+sort ct
+filter ct > 200
+take 20
+sort ct
+filter ct > 200
+take 20
+sort ct
+filter ct > 200
+take 20
+    "###,
+    )
+    .unwrap();
+}
 
-    from x
-    select {
-        date = @2022-12-31,
-        time = @08:30,
-        time_tz = @03:05+08:00,
-        time_tz2 = @03:05+0800,
-        timestamp1 = @2020-01-01T13:19:55-0800,
-        timestamp2 = @2021-03-14T03:05+0800,
-        timestamp3 = @2021-03-14T03:05+08:00,
-    }
-    "#).unwrap(),
-        @r"
+#[test]
+fn test_returning_constants_only() {
+    assert_snapshot!(compile(
+        r###"
+    from tb1
+    sort {a}
+    select {c = b}
+    select {d = 10}
+    "###,
+    )
+    .unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        10 AS d,
+        a
+      FROM
+        tb1
+    )
     SELECT
-      DATE('2022-12-31') AS date,
-      TIME('08:30') AS time,
-      TIME('03:05+08:00') AS time_tz,
-      TIME('03:05+08:00') AS time_tz2,
-      DATETIME('2020-01-01T13:19:55-08:00') AS timestamp1,
-      DATETIME('2021-03-14T03:05+08:00') AS timestamp2,
-      DATETIME('2021-03-14T03:05+08:00') AS timestamp3
+      d
     FROM
-      x
-    "
-    );
-}
+      table_0
+    ORDER BY
+      a
+    ");
 
-#[test]
-fn test_datetime_parsing() {
-    assert_snapshot!(compile(r#"
-    from test_tables
-    select {date = @2022-12-31, time = @08:30, timestamp = @2020-01-01T13:19:55-0800}
-    "#).unwrap(),
-        @r"
+    assert_snapshot!(compile(
+        r###"
+    from tb1
+    take 10
+    filter true
+    take 20
+    filter true
+    select d = 10
+    "###,
+    )
+    .unwrap(), @r"
     SELECT
-      DATE '2022-12-31' AS date,
-      TIME '08:30' AS time,
-      TIMESTAMP '2020-01-01T13:19:55-0800' AS timestamp
+      10 AS d
     FROM
-      test_tables
-    "
-    );
+      tb1
+    LIMIT
+      10
+    ");
 }
 
 #[test]
-fn test_lower() {
-    assert_snapshot!(compile(r#"
-    from test_tables
-    derive {lower_name = (name | text.lower)}
-    "#).unwrap(),
-        @r"
+fn test_conflicting_names_at_split() {
+    // issue #2697
+    assert_snapshot!(compile(
+        r#"
+    from s = workflow_steps
+    join wp=workflow_phases (s.phase_id == wp.id)
+    filter wp.name == "CREATE_OUTLET"
+    join w=workflow (wp.workflow_id == w.id)
+    select {
+        step_id = s.id,
+        phase_id = wp.id,
+    }
+    "#,
+    )
+    .unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        wp.id,
+        s.id AS _expr_0,
+        wp.workflow_id
+      FROM
+        workflow_steps AS s
+        JOIN workflow_phases AS wp ON s.phase_id = wp.id
+      WHERE
+        wp.name = 'CREATE_OUTLET'
+    )
     SELECT
-      *,
-      LOWER(name) AS lower_name
+      table_0._expr_0 AS step_id,
+      table_0.id AS phase_id
     FROM
-      test_tables
-    "
-    );
+      table_0
+      JOIN workflow AS w ON table_0.workflow_id = w.id
+    ");
 }
 
 #[test]
-fn test_upper() {
-    assert_snapshot!(compile(r#"
-    from test_tables
-    derive {upper_name = text.upper name}
-    select {upper_name}
-    "#).unwrap(),
-        @r"
+fn test_relation_literal_quoting() {
+    // issue #3484
+    assert_snapshot!(compile(
+        r###"
+    from [
+        {`small number`=1e-10, `large number`=1e10},
+    ]
+    select {`small number`, `large number`}
+    "###,
+    )
+    .unwrap(), @r#"
+    WITH table_0 AS (
+      SELECT
+        1e-10 AS "small number",
+        10000000000.0 AS "large number"
+    )
     SELECT
-      UPPER(name) AS upper_name
+      "small number",
+      "large number"
     FROM
-      test_tables
-    "
-    );
+      table_0
+    "#);
 }
 
 #[test]
-fn test_1535() {
-    assert_snapshot!(compile(r#"
-    from x.y.z
-    "#).unwrap(),
-        @r"
-    SELECT
-      *
-    FROM
-      x.y.z
-    "
-    );
-}
+fn test_relation_literal_values() {
+    // rows can list their columns in any order
+    assert_snapshot!(compile(
+        r###"
+    prql target:sql.postgres
 
-#[test]
-fn test_read_parquet_duckdb() {
-    assert_snapshot!(compile(r#"
-    std.read_parquet 'x.parquet'
-    join (std.read_parquet "y.parquet") (==foo)
-    "#).unwrap(),
-        @r"
+    from [
+        {id = 1, name = "a", active = true},
+        {name = "b", active = false, id = 2},
+    ]
+    filter active
+    "###,
+    )
+    .unwrap(), @r"
     WITH table_0 AS (
       SELECT
         *
       FROM
-        read_parquet('x.parquet')
-    ),
-    table_1 AS (
-      SELECT
-        *
-      FROM
-        read_parquet('y.parquet')
+        (
+          VALUES
+            (1, 'a', true),
+            (2, 'b', false)
+        ) AS table_1 (id, name, active)
     )
     SELECT
-      table_0.*,
-      table_1.*
+      id,
+      name,
+      active
     FROM
       table_0
-      JOIN table_1 ON table_0.foo = table_1.foo
-    "
-    );
+    WHERE
+      active
+    ");
 
-    // TODO: `from x=(read_parquet 'x.parquet')` currently fails
-}
+    // dialects without VALUES in FROM get a SELECT for each row
+    assert_snapshot!(compile(
+        r###"
+    prql target:sql.sqlite
 
-#[test]
-fn test_excess_columns() {
-    // https://github.com/PRQL/prql/issues/2079
-    assert_snapshot!(compile(r#"
-    from tracks
-    derive d = track_id
-    sort d
-    select {title}
-    "#).unwrap(),
-        @r"
+    from [
+        {id = 1, name = "a", active = true},
+        {name = "b", active = false, id = 2},
+    ]
+    filter active
+    "###,
+    )
+    .unwrap(), @r"
     WITH table_0 AS (
       SELECT
-        title,
-        track_id AS _expr_0
-      FROM
-        tracks
+        1 AS id,
+        'a' AS name,
+        true AS active
+      UNION
+      ALL
+      SELECT
+        2 AS id,
+        'b' AS name,
+        false AS active
     )
     SELECT
-      title
+      id,
+      name,
+      active
     FROM
       table_0
-    ORDER BY
-      _expr_0
-    "
-    );
-}
+    WHERE
+      active
+    ");
 
-#[test]
-fn test_regex_search() {
-    assert_snapshot!(compile(r#"
-    from tracks
-    derive is_bob_marley = artist_name ~= "Bob\\sMarley"
-    "#).unwrap(),
-        @r"
-    SELECT
-      *,
-      REGEXP(artist_name, 'Bob\sMarley') AS is_bob_marley
-    FROM
-      tracks
-    "
-    );
-}
+    assert_snapshot!(compile(
+        r###"
+    prql target:sql.mssql
 
-#[test]
-fn test_intervals() {
-    assert_snapshot!(compile(r#"
-    from foo
-    select dt = 1years + 1months + 1weeks + 1days + 1hours + 1minutes + 1seconds + 1milliseconds + 1microseconds
-    "#).unwrap(),
-        @r"
+    from e = employees
+    join s = [{code = "A", scale = 1.5}, {code = "B", scale = 1.0}] (e.grade == s.code)
+    select {e.id, salary = e.salary * s.scale}
+    "###,
+    )
+    .unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        *
+      FROM
+        (
+          VALUES
+            ('A', 1.5),
+            ('B', 1.0)
+        ) AS table_1 (code, scale)
+    )
     SELECT
-      INTERVAL 1 YEAR + INTERVAL 1 MONTH + INTERVAL 1 WEEK + INTERVAL 1 DAY + INTERVAL 1 HOUR + INTERVAL 1 MINUTE + INTERVAL 1 SECOND + INTERVAL 1 MILLISECOND + INTERVAL 1 MICROSECOND AS dt
+      e.id,
+      e.salary * table_0.scale AS salary
     FROM
-      foo
-    "
-    );
+      employees AS e
+      JOIN table_0 ON e.grade = table_0.code
+    ");
 }
 
 #[test]
-fn test_into() {
-    assert_snapshot!(compile(r#"
-    from data
-    into table_a
+fn test_relation_var_name_clashes_01() {
+    assert_snapshot!(compile(
+        r###"
+    let table_0 = (from a)
 
-    from table_a
-    select {x, y}
-    "#).unwrap(),
-        @r"
-    WITH table_a AS (
+    from table_0
+    take 10
+    filter x > 0
+        "###,
+    )
+    .unwrap(), @r"
+    WITH table_0 AS (
       SELECT
         *
       FROM
-        data
+        a
+    ),
+    table_1 AS (
+      SELECT
+        *
+      FROM
+        table_0
+      LIMIT
+        10
     )
     SELECT
-      x,
-      y
+      *
     FROM
-      table_a
-    "
-    );
+      table_1
+    WHERE
+      x > 0
+    ");
 }
 
 #[test]
-fn test_array_01() {
-    compile(
-        r#"
-    let a = [1, 2, false]
-
-    from x
-    "#,
+fn test_relation_var_name_clashes_02() {
+    // issue #3713
+    assert_snapshot!(compile(
+        r###"
+    from t
+    join t (==x)
+        "###,
     )
-    .unwrap();
+    .unwrap(), @r"
+    SELECT
+      t.*,
+      table_0.*
+    FROM
+      t
+      JOIN t AS table_0 ON t.x = table_0.x
+    ");
+}
 
-    assert_snapshot!(compile(r#"
-    let my_relation = [
-        {a = 3, b = false},
-        {a = 4, b = true},
-    ]
+#[test]
+fn test_relation_var_name_clashes_03() {
+    // a relation named after the table it reads from is renamed with a suffix
+    assert_snapshot!(compile(
+        r###"
+    let employees = (from employees | filter active)
 
-    let main = (my_relation | filter b)
-    "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        3 AS a,
-        false AS b
-      UNION
-      ALL
-      SELECT
-        4 AS a,
-        true AS b
-    ),
-    my_relation AS (
+    from e = employees
+    join m = employees (e.manager_id == m.id)
+        "###,
+    )
+    .unwrap(), @r"
+    WITH employees_1 AS (
       SELECT
-        a,
-        b
+        *
       FROM
-        table_0
+        employees
+      WHERE
+        active
     )
     SELECT
-      a,
-      b
+      e.*,
+      m.*
     FROM
-      my_relation
-    WHERE
-      b
-    "
-    );
-}
+      employees_1 AS e
+      JOIN employees_1 AS m ON e.manager_id = m.id
+    ");
 
-#[test]
-fn test_array_02() {
-    assert_snapshot!(compile(r#"
-    from [
-      {x = null},
-      {x = '1'},
-    ]
-    "#)
+    // generated names skip names from the query, even of later relations
+    assert_snapshot!(compile(
+        r###"
+    let a = (from x | join side:left (from z | take 1) (this.id == that.z_id) | select {x.id})
+    let table_0 = (from a | take 2)
+
+    from table_0
+        "###,
+    )
     .unwrap(), @r"
-    WITH table_0 AS (
+    WITH table_1 AS (
       SELECT
-        NULL AS x
-      UNION
-      ALL
+        *
+      FROM
+        z
+      LIMIT
+        1
+    ), a AS (
       SELECT
-        '1' AS x
+        x.id
+      FROM
+        x
+        LEFT JOIN table_1 ON x.id = table_1.z_id
+    ),
+    table_0 AS (
+      SELECT
+        id
+      FROM
+        a
+      LIMIT
+        2
     )
     SELECT
-      x
+      id
     FROM
       table_0
     ");
 }
 
 #[test]
-fn test_double_stars() {
-    assert_snapshot!(compile(r#"
-    from tb1
-    join tb2 (==c2)
-    take 5
-    filter (tb2.c3 < 100)
-    "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        tb1.*,
-        tb2.*
-      FROM
-        tb1
-        JOIN tb2 ON tb1.c2 = tb2.c2
-      LIMIT
-        5
+#[ignore]
+fn test_select_this() {
+    // Currently broken for a few reasons:
+    // - type of `this` is not resolved as tuple, but an union?
+    // - lineage is not computed correctly
+    assert_snapshot!(compile(
+        r###"
+    from x
+    select {a, b}
+    select this
+        "###,
     )
+    .unwrap(), @r###"
     SELECT
-      *
+      a,
+      b
     FROM
-      table_0
-    WHERE
-      c3 < 100
-    "
-    );
+      x
+    "###);
+}
 
-    assert_snapshot!(compile(r#"
-    prql target:sql.duckdb
+#[test]
+fn test_group_exclude() {
+    assert_snapshot!(compile(
+        r###"
+    from x
+    select {a, b}
+    group {a} (derive c = a + 1)
+        "###,
+    )
+    .unwrap_err(), @r"
+    Error:
+       ╭─[:4:27]
+       │
+     4 │     group {a} (derive c = a + 1)
+       │                           ┬
+       │                           ╰── Unknown name `a`
+       │
+       │ Help: available columns: x.b
+    ───╯
+    ");
 
-    from tb1
-    join tb2 (==c2)
-    take 5
-    filter (tb2.c3 < 100)
-    "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        tb1.*,
-        tb2.*
-      FROM
-        tb1
-        JOIN tb2 ON tb1.c2 = tb2.c2
-      LIMIT
-        5
+    // assert_snapshot!(compile(
+    //     r###"
+    // from x
+    // select {a, b}
+    // group {a + 1} (aggregate {sum b})
+    //     "###,
+    // )
+    // .unwrap_err(), @r###"
+    // SELECT
+    //   a,
+    //   b
+    // FROM
+    //   x
+    // "###);
+}
+
+#[test]
+fn test_table_declarations() {
+    assert_snapshot!(compile(
+        r###"
+    module default_db {
+      module my_schema {
+        let my_table <[{ id = int, a = text }]>
+      }
+
+      let another_table <[{ id = int, b = text }]>
+    }
+
+    from my_schema.my_table | join another_table (==id) | take 10
+        "###,
     )
+    .unwrap(), @r"
     SELECT
-      *
+      my_table.id,
+      my_table.a,
+      another_table.id,
+      another_table.b
     FROM
-      table_0
-    WHERE
-      c3 < 100
-    "
-    );
+      my_schema.my_table
+      JOIN another_table ON my_table.id = another_table.id
+    LIMIT
+      10
+    ");
 }
 
 #[test]
-fn test_lineage() {
-    // #2627
-    assert_snapshot!(compile(r#"
-    from_text """
-    a
-    1
-    2
-    3
-    """
-    derive a = a
-    "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        '    1' AS a
-      UNION
-      ALL
-      SELECT
-        '    2' AS a
-      UNION
-      ALL
-      SELECT
-        '    3' AS a
+fn test_param_declarations() {
+    assert_snapshot!(compile(
+        r###"
+    let a <int>
+
+    from x | filter b == a
+        "###,
     )
+    .unwrap(), @r"
     SELECT
-      a,
-      a
+      *
     FROM
-      table_0
-    "
-    );
+      x
+    WHERE
+      b = $a
+    ");
+}
 
-    // #2392
-    assert_snapshot!(compile(r#"
-    from_text format:json """{
-        "columns": ["a"],
-        "data": [[1]]
-    }"""
-    derive a = a + 1
-    "#).unwrap(),
-        @r"
-    WITH table_0 AS (
-      SELECT
-        1 AS a
+#[test]
+fn test_relation_aliasing() {
+    assert_snapshot!(compile(
+        r###"
+    from x | select {y = this} | select {y.hello}
+        "###,
     )
+    .unwrap(), @r"
     SELECT
-      a AS _expr_0,
-      a + 1 AS a
+      hello
     FROM
-      table_0
-    "
-    );
+      x
+    ");
 }
 
 #[test]
-fn test_type_as_column_name() {
-    // #2503
-    assert_snapshot!(compile(r#"
-    let f = tbl -> (
-      t = tbl
-      select t.date
+fn test_import() {
+    assert_snapshot!(compile(
+        r###"
+    module hello {
+        let world = 1
+    }
+
+    import a = hello.world
+
+    from x | select a
+        "###,
     )
-
-    from foo
-    f"#)
     .unwrap(), @r"
     SELECT
-      date
+      1
     FROM
-      foo AS t
+      x
     ");
 }
 
 #[test]
-fn test_error_code() {
-    let err = compile(
-        r###"
-    let a = (from x)
-    "###,
+fn test_transform_comments() {
+    let query = r#"
+    # the employees
+    from e = employees
+    # only active
+    filter active
+    # recent
+    # hires
+    filter hired > @2020-01-01
+    derive {x = 1}  # trailing comments are not emitted
+    # join departments
+    join side:left d = departments (==dept_id)
+    # by department
+    group {d.title} (
+      aggregate {n = count e.id}
+    )
+    # big ones
+    filter n > 10
+    # largest first
+    sort {-n}
+    # top 5
+    take 5
+    "#;
+    let opts = Options::default()
+        .no_signature()
+        .with_transform_comments(true);
+    assert_snapshot!(prqlc::compile(query, &opts).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        id,
+        dept_id
+      -- the employees
+      FROM
+        employees AS e
+      -- only active
+      -- recent
+      -- hires
+      WHERE
+        active
+        AND hired > DATE '2020-01-01'
     )
-    .unwrap_err();
-    assert_eq!(err.inner[0].code.as_ref().unwrap(), "E0001");
+    SELECT
+      d.title,
+      COUNT(table_0.id) AS n
+    FROM
+      table_0
+      -- join departments
+      LEFT JOIN departments AS d ON table_0.dept_id = d.dept_id
+    -- by department
+    GROUP BY
+      d.title
+    -- big ones
+    HAVING
+      COUNT(table_0.id) > 10
+    -- largest first
+    ORDER BY
+      n DESC
+    -- top 5
+    LIMIT
+      5
+    ");
+
+    assert_snapshot!(prqlc::compile(query, &opts.clone().no_format()).unwrap(), @r"
+    WITH table_0 AS (SELECT id, dept_id
+    -- the employees
+    FROM employees AS e
+    -- only active
+    -- recent
+    -- hires
+    WHERE active AND hired > DATE '2020-01-01') SELECT d.title, COUNT(table_0.id) AS n FROM table_0
+    -- join departments
+    LEFT JOIN departments AS d ON table_0.dept_id = d.dept_id
+    -- by department
+    GROUP BY d.title
+    -- big ones
+    HAVING COUNT(table_0.id) > 10
+    -- largest first
+    ORDER BY n DESC
+    -- top 5
+    LIMIT 5
+    ");
+
+    // comments are not emitted by default
+    assert!(!compile(query).unwrap().contains("--"));
 }
 
 #[test]
-fn large_query() {
-    // This was causing a stack overflow on Windows, ref https://github.com/PRQL/prql/issues/2857
-    compile(
-        r###"
-from employees
-filter gross_cost > 0
-group {title} (
-  aggregate {
-    ct = count this,
-  }
-)
-sort ct
-filter ct > 200
-take 20
-sort ct
-filter ct > 200
-take 20
-sort ct
-filter ct > 200
-take 20
-sort ct
-filter ct > 200
-take 20
-    "###,
-    )
-    .unwrap();
+fn test_transform_comments_without_clause() {
+    // derive has no clause of its own, so its comment is placed above SELECT
+    let query = r#"
+    from employees
+    # gross pay
+    derive {gross = salary + bonus}
+    # only ids
+    select {id, gross}
+    "#;
+    let opts = Options::default()
+        .no_signature()
+        .with_transform_comments(true);
+    assert_snapshot!(prqlc::compile(query, &opts).unwrap(), @r"
+    -- gross pay
+    -- only ids
+    SELECT
+      id,
+      salary + bonus AS gross
+    FROM
+      employees
+    ");
 }
 
 #[test]
-fn test_returning_constants_only() {
-    assert_snapshot!(compile(
-        r###"
-    from tb1
-    sort {a}
-    select {c = b}
-    select {d = 10}
-    "###,
+fn test_alias_output_columns() {
+    let query = r#"
+    from employees
+    derive {gross = salary + bonus}
+    filter (gross * 2) > 100
+    group {department} (
+      aggregate {average gross, count this, top = max gross}
     )
-    .unwrap(), @r"
+    "#;
+    let opts = Options::default().no_signature();
+    assert_snapshot!(prqlc::compile(query, &opts).unwrap(), @r#"
     WITH table_0 AS (
       SELECT
-        10 AS d,
-        a
+        department,
+        salary + bonus AS _expr_0
       FROM
-        tb1
+        employees
     )
     SELECT
-      d
+      department,
+      AVG(_expr_0),
+      COUNT(*),
+      MAX(_expr_0) AS "top"
     FROM
       table_0
-    ORDER BY
-      a
-    ");
+    WHERE
+      _expr_0 * 2 > 100
+    GROUP BY
+      department
+    "#);
 
-    assert_snapshot!(compile(
-        r###"
-    from tb1
-    take 10
-    filter true
-    take 20
-    filter true
-    select d = 10
-    "###,
-    )
-    .unwrap(), @r"
-    WITH table_1 AS (
-      SELECT
-        NULL
-      FROM
-        tb1
-      LIMIT
-        10
-    ), table_0 AS (
+    // computed columns without a name are given one, which does not clash
+    // with the names of the query
+    let opts = opts.with_alias_output_columns(true);
+    assert_snapshot!(prqlc::compile(query, &opts).unwrap(), @r#"
+    WITH table_0 AS (
       SELECT
-        NULL
+        department,
+        salary + bonus AS _expr_0
       FROM
-        table_1
-      WHERE
-        true
-      LIMIT
-        20
+        employees
     )
     SELECT
-      10 AS d
+      department,
+      AVG(_expr_0) AS _expr_1,
+      COUNT(*) AS _expr_2,
+      MAX(_expr_0) AS "top"
     FROM
       table_0
     WHERE
-      true
+      _expr_0 * 2 > 100
+    GROUP BY
+      department
+    "#);
+
+    assert_snapshot!(prqlc::compile(r#"
+    from employees
+    select {id, salary * 2, bonus = salary / 10}
+    "#, &opts).unwrap(), @r"
+    SELECT
+      id,
+      salary * 2 AS _expr_0,
+      salary / 10 AS bonus
+    FROM
+      employees
     ");
 }
 
 #[test]
-fn test_conflicting_names_at_split() {
-    // issue #2697
-    assert_snapshot!(compile(
+fn test_compile_all() {
+    let sqls = prqlc::compile_all(
         r#"
-    from s = workflow_steps
-    join wp=workflow_phases (s.phase_id == wp.id)
-    filter wp.name == "CREATE_OUTLET"
-    join w=workflow (wp.workflow_id == w.id)
-    select {
-        step_id = s.id,
-        phase_id = wp.id,
-    }
+    let recent = (from orders | filter year >= 2020)
+
+    from recent
+    aggregate {total = sum amount}
+    into totals
+
+    from recent
+    sort {-amount}
+    take 3
     "#,
+        &Options::default().no_signature(),
     )
-    .unwrap(), @r"
-    WITH table_0 AS (
+    .unwrap();
+
+    assert_snapshot!(sqls.join("\n"), @r"
+    WITH recent AS (
       SELECT
-        wp.id,
-        s.id AS _expr_0,
-        wp.workflow_id
+        *
       FROM
-        workflow_steps AS s
-        JOIN workflow_phases AS wp ON s.phase_id = wp.id
+        orders
       WHERE
-        wp.name = 'CREATE_OUTLET'
+        year >= 2020
     )
     SELECT
-      table_0._expr_0 AS step_id,
-      table_0.id AS phase_id
+      COALESCE(SUM(amount), 0) AS total
     FROM
-      table_0
-      JOIN workflow AS w ON table_0.workflow_id = w.id
+      recent
+
+    WITH recent AS (
+      SELECT
+        *
+      FROM
+        orders
+      WHERE
+        year >= 2020
+    )
+    SELECT
+      *
+    FROM
+      recent
+    ORDER BY
+      amount DESC
+    LIMIT
+      3
+    ");
+
+    // functions are shared too, and a relation referenced by another one is
+    // not a query of its own
+    let sqls = prqlc::compile_all(
+        r#"
+    let big = a -> a > 100
+    let large = (from orders | filter (big amount))
+    let largest = (from large | sort {-amount} | take 1)
+    from customers | filter (big balance)
+    "#,
+        &Options::default().no_signature().no_format(),
+    )
+    .unwrap();
+
+    assert_snapshot!(sqls.join("\n"), @r"
+    WITH large AS (SELECT * FROM orders WHERE amount > 100) SELECT * FROM large ORDER BY amount DESC LIMIT 1
+    SELECT * FROM customers WHERE balance > 100
     ");
 }
 
 #[test]
-fn test_relation_literal_quoting() {
-    // issue #3484
-    assert_snapshot!(compile(
-        r###"
-    from [
-        {`small number`=1e-10, `large number`=1e10},
-    ]
-    select {`small number`, `large number`}
-    "###,
-    )
-    .unwrap(), @r#"
+fn test_merge_duplicate_tables() {
+    // the same filtered pipeline, joined twice, is computed once
+    assert_snapshot!(compile(r#"
+    from e = employees
+    join m = (from employees | filter active | select {id, name}) (this.manager_id == that.id)
+    join r = (from employees | filter active | select {id, name}) (this.reviewer_id == that.id)
+    select {e.name, manager = m.name, reviewer = r.name}
+    "#).unwrap(), @r"
     WITH table_0 AS (
       SELECT
-        1e-10 AS "small number",
-        10000000000.0 AS "large number"
+        id,
+        name
+      FROM
+        employees
+      WHERE
+        active
     )
     SELECT
-      "small number",
-      "large number"
+      e.name,
+      table_0.name AS manager,
+      table_1.name AS reviewer
     FROM
-      table_0
-    "#);
-}
-
-#[test]
-fn test_relation_var_name_clashes_01() {
-    assert_snapshot!(compile(
-        r###"
-    let table_0 = (from a)
+      employees AS e
+      JOIN table_0 ON e.manager_id = table_0.id
+      JOIN table_0 AS table_1 ON e.reviewer_id = table_1.id
+    ");
 
-    from table_0
-    take 10
-    filter x > 0
-        "###,
-    )
-    .unwrap(), @r"
+    // tables that only pick columns are not merged
+    assert_snapshot!(compile(r#"
+    from a = employees
+    join b = (from employees | select {id}) (a.manager_id == b.id)
+    join c = (from employees | select {id}) (a.reviewer_id == c.id)
+    "#).unwrap(), @r"
     WITH table_0 AS (
       SELECT
-        *
+        id
       FROM
-        a
+        employees
     ),
     table_1 AS (
       SELECT
-        *
+        id
       FROM
-        table_0
-      LIMIT
-        10
+        employees
+    )
+    SELECT
+      a.*,
+      table_0.id,
+      table_1.id
+    FROM
+      employees AS a
+      JOIN table_0 ON a.manager_id = table_0.id
+      JOIN table_1 ON a.reviewer_id = table_1.id
+    ");
+}
+
+#[test]
+fn test_referenced_tables() {
+    let tables = |prql: &str| {
+        (prqlc::referenced_tables(prql).unwrap().iter())
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    // a relation variable is not a table, but the tables it reads are
+    assert_snapshot!(tables(r#"
+    let recent = (from orders | filter year >= 2020)
+    from c = customers
+    join r = recent (c.id == r.customer_id)
+    join o = orders (c.last_order_id == o.id)
+    "#), @"customers, orders");
+
+    assert_snapshot!(tables(r#"
+    from e = sales.employees
+    join s = schema.`salaries.2024` (==id)
+    "#), @"sales.employees, schema.salaries.2024");
+
+    // a table that is declared, but not read, is not referenced
+    assert_snapshot!(tables(r#"
+    let unused = (from invoices)
+    from tracks
+    "#), @"tracks");
+}
+
+#[test]
+fn test_count() {
+    let query = r#"
+    from orders
+    group {customer_id} (
+      aggregate {
+        n_orders = count this,
+        n_paid = count paid_at,
+        n_products = count_distinct product_id,
+        n_product_stores = count_distinct [product_id, store_id],
+      }
     )
+    "#;
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::Postgres).unwrap(), @r"
+    SELECT
+      customer_id,
+      COUNT(*) AS n_orders,
+      COUNT(paid_at) AS n_paid,
+      COUNT(DISTINCT product_id) AS n_products,
+      COUNT(DISTINCT product_id || '|' || store_id) AS n_product_stores
+    FROM
+      orders
+    GROUP BY
+      customer_id
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::SQLite).unwrap(), @r"
+    SELECT
+      customer_id,
+      COUNT(*) AS n_orders,
+      COUNT(paid_at) AS n_paid,
+      COUNT(DISTINCT product_id) AS n_products,
+      COUNT(DISTINCT product_id || '|' || store_id) AS n_product_stores
+    FROM
+      orders
+    GROUP BY
+      customer_id
+    ");
+
+    assert_snapshot!(compile_with_sql_dialect(query, sql::Dialect::MySql).unwrap(), @r"
     SELECT
-      *
+      customer_id,
+      COUNT(*) AS n_orders,
+      COUNT(paid_at) AS n_paid,
+      COUNT(DISTINCT product_id) AS n_products,
+      COUNT(DISTINCT product_id, store_id) AS n_product_stores
     FROM
-      table_1
-    WHERE
-      x > 0
+      orders
+    GROUP BY
+      customer_id
     ");
 }
 
 #[test]
-fn test_relation_var_name_clashes_02() {
-    // issue #3713
-    assert_snapshot!(compile(
-        r###"
-    from t
-    join t (==x)
-        "###,
+fn test_aggregate_filter() {
+    let query = r#"
+    from orders
+    group {customer_id} (
+      aggregate {
+        paid = (count this | filtered (status == "paid")),
+        revenue = (sum amount | filtered (status == "paid")),
+      }
     )
-    .unwrap(), @r"
+    "#;
+
+    // dialects that support FILTER attach the condition to the aggregate
+    assert_snapshot!(compile(&format!("prql target:sql.postgres\n{query}")).unwrap(), @r"
+    SELECT
+      customer_id,
+      COUNT(*) FILTER (
+        WHERE
+          status = 'paid'
+      ) AS paid,
+      COALESCE(
+        SUM(amount) FILTER (
+          WHERE
+            status = 'paid'
+        ),
+        0
+      ) AS revenue
+    FROM
+      orders
+    GROUP BY
+      customer_id
+    ");
+    assert_snapshot!(compile(&format!("prql target:sql.sqlite\n{query}")).unwrap(), @r"
+    SELECT
+      customer_id,
+      COUNT(*) FILTER (
+        WHERE
+          status = 'paid'
+      ) AS paid,
+      COALESCE(
+        SUM(amount) FILTER (
+          WHERE
+            status = 'paid'
+        ),
+        0
+      ) AS revenue
+    FROM
+      orders
+    GROUP BY
+      customer_id
+    ");
+
+    // others emulate it by aggregating only the values for which it holds
+    assert_snapshot!(compile(&format!("prql target:sql.mysql\n{query}")).unwrap(), @r"
     SELECT
-      t.*,
-      table_0.*
+      customer_id,
+      COUNT(
+        CASE
+          WHEN status = 'paid' THEN 1
+        END
+      ) AS paid,
+      COALESCE(
+        SUM(
+          CASE
+            WHEN status = 'paid' THEN amount
+          END
+        ),
+        0
+      ) AS revenue
+    FROM
+      orders
+    GROUP BY
+      customer_id
+    ");
+
+    // ... which also applies to aggregates that are not a single function call
+    assert_snapshot!(compile(r#"
+    prql target:sql.sqlite
+
+    from orders
+    aggregate {all_shipped = (all shipped | filtered (status == "paid"))}
+    "#).unwrap(), @r"
+    SELECT
+      COALESCE(
+        MIN(
+          CASE
+            WHEN status = 'paid' THEN shipped
+          END
+        ) > 0,
+        TRUE
+      ) AS all_shipped
     FROM
-      t
-      JOIN t AS table_0 ON t.x = table_0.x
+      orders
+    ");
+
+    // within a window, FILTER comes before OVER
+    assert_snapshot!(compile(r#"
+    prql target:sql.postgres
+
+    from orders
+    window rows:-2..0 (derive {recent = (sum amount | filtered (amount > 0))})
+    "#).unwrap(), @r"
+    SELECT
+      *,
+      SUM(amount) FILTER (
+        WHERE
+          amount > 0
+      ) OVER (ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) AS recent
+    FROM
+      orders
     ");
 }
 
 #[test]
-#[ignore]
-fn test_select_this() {
-    // Currently broken for a few reasons:
-    // - type of `this` is not resolved as tuple, but an union?
-    // - lineage is not computed correctly
-    assert_snapshot!(compile(
-        r###"
-    from x
-    select {a, b}
-    select this
-        "###,
+fn test_aggregate_grouped_columns() {
+    // columns of the `group` can be used without an aggregate function
+    assert_snapshot!(compile(r#"
+    from orders
+    group {customer_id} (
+      aggregate {customer = customer_id, total = sum amount, last_status = max status}
     )
-    .unwrap(), @r###"
+    "#).unwrap(), @r"
     SELECT
-      a,
-      b
+      customer_id,
+      customer_id AS customer,
+      COALESCE(SUM(amount), 0) AS total,
+      MAX(status) AS last_status
     FROM
-      x
-    "###);
+      orders
+    GROUP BY
+      customer_id
+    ");
 }
 
 #[test]
-fn test_group_exclude() {
-    assert_snapshot!(compile(
-        r###"
-    from x
-    select {a, b}
-    group {a} (derive c = a + 1)
-        "###,
-    )
-    .unwrap_err(), @r"
-    Error:
-       ╭─[:4:27]
-       │
-     4 │     group {a} (derive c = a + 1)
-       │                           ┬
-       │                           ╰── Unknown name `a`
-       │
-       │ Help: available columns: x.b
-    ───╯
+fn test_pipeline_let() {
+    // the expression is inlined wherever the name is referenced, and it is a
+    // column only where it is selected
+    assert_snapshot!(compile(r#"
+    from products
+    let discounted = price * 0.9
+    filter discounted > 10
+    filter discounted < 100
+    select {id, discounted}
+    "#).unwrap(), @r"
+    SELECT
+      id,
+      price * 0.9 AS discounted
+    FROM
+      products
+    WHERE
+      price * 0.9 > 10
+      AND price * 0.9 < 100
     ");
 
-    // assert_snapshot!(compile(
-    //     r###"
-    // from x
-    // select {a, b}
-    // group {a + 1} (aggregate {sum b})
-    //     "###,
-    // )
-    // .unwrap_err(), @r###"
-    // SELECT
-    //   a,
-    //   b
-    // FROM
-    //   x
-    // "###);
+    // a binding can reference the ones before it
+    assert_snapshot!(compile(r#"
+    from products
+    let discounted = price * 0.9
+    let taxed = discounted * 1.2
+    derive {total = taxed * quantity}
+    "#).unwrap(), @r"
+    SELECT
+      *,
+      price * 0.9 * 1.2 * quantity AS total
+    FROM
+      products
+    ");
 }
 
 #[test]
-fn test_table_declarations() {
-    assert_snapshot!(compile(
-        r###"
-    module default_db {
-      module my_schema {
-        let my_table <[{ id = int, a = text }]>
-      }
-
-      let another_table <[{ id = int, b = text }]>
-    }
-
-    from my_schema.my_table | join another_table (==id) | take 10
-        "###,
+fn test_group_column_order() {
+    // grouped columns come first, in the order of `by`, and then the
+    // aggregates, also when the aggregate lists some of the grouped columns
+    let query = r#"
+    from sales
+    group {region, year, product} (
+      aggregate {total = sum amount, year, orders = count this, region}
     )
-    .unwrap(), @r"
+    "#;
+    let options = Options::default().no_signature();
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
     SELECT
-      my_table.id,
-      my_table.a,
-      another_table.id,
-      another_table.b
+      region,
+      year,
+      product,
+      COALESCE(SUM(amount), 0) AS total,
+      COUNT(*) AS orders
     FROM
-      my_schema.my_table
-      JOIN another_table ON my_table.id = another_table.id
-    LIMIT
-      10
+      sales
+    GROUP BY
+      region,
+      year,
+      product
+    ");
+
+    // ... or where they are listed
+    let options = options.with_interleave_group_columns(true);
+    assert_snapshot!(prqlc::compile(query, &options).unwrap(), @r"
+    SELECT
+      product,
+      COALESCE(SUM(amount), 0) AS total,
+      year,
+      COUNT(*) AS orders,
+      region
+    FROM
+      sales
+    GROUP BY
+      region,
+      year,
+      product
     ");
 }
 
 #[test]
-fn test_param_declarations() {
-    assert_snapshot!(compile(
-        r###"
-    let a <int>
-
-    from x | filter b == a
-        "###,
+fn test_group_filter_around_aggregate() {
+    // a filter before the aggregate filters rows, and one after it filters groups
+    assert_snapshot!(compile(r#"
+    from sales
+    group {category} (
+      filter amount > 0
+      aggregate {total = sum amount}
+      filter total > 100
     )
-    .unwrap(), @r"
+    "#).unwrap(), @r"
     SELECT
-      *
+      category,
+      COALESCE(SUM(amount), 0) AS total
     FROM
-      x
+      sales
     WHERE
-      b = $a
+      amount > 0
+    GROUP BY
+      category
+    HAVING
+      COALESCE(SUM(amount), 0) > 100
     ");
-}
 
-#[test]
-fn test_relation_aliasing() {
-    assert_snapshot!(compile(
-        r###"
-    from x | select {y = this} | select {y.hello}
-        "###,
+    // sorting and taking after the aggregate happens after filtering groups
+    assert_snapshot!(compile(r#"
+    from sales
+    group {category} (
+      aggregate {total = sum amount}
+      filter total > 100
+      sort total
+      take 1
+    )
+    "#).unwrap(), @r"
+    WITH table_1 AS (
+      SELECT
+        category,
+        COALESCE(SUM(amount), 0) AS total
+      FROM
+        sales
+      GROUP BY
+        category
+    ),
+    table_0 AS (
+      SELECT
+        category,
+        total,
+        ROW_NUMBER() OVER (
+          PARTITION BY category
+          ORDER BY
+            total
+        ) AS _expr_0
+      FROM
+        table_1
+      WHERE
+        total > 100
     )
-    .unwrap(), @r"
     SELECT
-      hello
+      category,
+      total
     FROM
-      x
+      table_0
+    WHERE
+      _expr_0 <= 1
     ");
 }
 
 #[test]
-fn test_import() {
-    assert_snapshot!(compile(
-        r###"
-    module hello {
-        let world = 1
-    }
+fn test_join_same_column_names() {
+    use std::collections::HashMap;
 
-    import a = hello.world
+    let table_columns = HashMap::from([
+        (
+            "orders".to_string(),
+            ["id", "customer_id", "total"].map(String::from).to_vec(),
+        ),
+        (
+            "customers".to_string(),
+            ["id", "name"].map(String::from).to_vec(),
+        ),
+        (
+            "employees".to_string(),
+            ["id", "name", "manager_id"].map(String::from).to_vec(),
+        ),
+    ]);
+    let options = Options::default()
+        .no_signature()
+        .with_table_columns(table_columns);
+
+    // a name of only one of the relations is qualified with it
+    assert_snapshot!(prqlc::compile(r#"
+    from o = orders
+    join c = customers (o.customer_id == c.id)
+    select {name, total}
+    "#, &options).unwrap(), @r"
+    SELECT
+      c.name,
+      o.total
+    FROM
+      orders AS o
+      JOIN customers AS c ON o.customer_id = c.id
+    ");
 
-    from x | select a
-        "###,
+    // in a self-join, columns of the two sides that are projected together are
+    // given distinct names, and referred to by them
+    assert_snapshot!(prqlc::compile(r#"
+    from e = employees
+    join m = employees (e.manager_id == m.id)
+    sort m.name
+    take 10
+    select {e.name}
+    "#, &options).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        e.name,
+        m.name AS _expr_0
+      FROM
+        employees AS e
+        JOIN employees AS m ON e.manager_id = m.id
+      ORDER BY
+        _expr_0
+      LIMIT
+        10
     )
-    .unwrap(), @r"
     SELECT
-      1
+      name
     FROM
-      x
+      table_0
+    ORDER BY
+      _expr_0
+    ");
+
+    assert_snapshot!(prqlc::compile(r#"
+    from e = employees
+    join m = employees (e.manager_id == m.id)
+    take 10
+    filter m.name != null
+    "#, &options).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        e.id,
+        e.name,
+        e.manager_id,
+        m.id AS _expr_0,
+        m.name AS _expr_1,
+        m.manager_id AS _expr_2
+      FROM
+        employees AS e
+        JOIN employees AS m ON e.manager_id = m.id
+      LIMIT
+        10
+    )
+    SELECT
+      id,
+      name,
+      manager_id,
+      _expr_0,
+      _expr_1,
+      _expr_2
+    FROM
+      table_0
+    WHERE
+      _expr_1 IS NOT NULL
+    ");
+
+    // ... including columns that are otherwise included by a star
+    assert_snapshot!(compile(r#"
+    from a
+    join b (a.x == b.x)
+    filter a.y > 1
+    take 3
+    filter b.y > 2
+    "#).unwrap(), @r"
+    WITH table_0 AS (
+      SELECT
+        a.*,
+        b.x AS _expr_0,
+        b.y AS _expr_1,
+        b.*
+      FROM
+        a
+        JOIN b ON a.x = b.x
+      WHERE
+        a.y > 1
+      LIMIT
+        3
+    )
+    SELECT
+      *
+    FROM
+      table_0
+    WHERE
+      _expr_1 > 2
     ");
 }